@@ -1,18 +1,64 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
-use crate::models::{AuthType, ServerConfig, SessionConfig, SessionWindow};
-use crate::config::{ConfigManager, get_db_path, get_session_dir, SessionManager};
-use crate::utils::{SshClient, import_ssh_config, connect_via_system_ssh, connect_via_system_ssh_with_command, ssh_command_connect, russh_connect};
+use crate::models::{enable_audit_mode, set_ssh_verbosity, Algorithms, AntiIdle, AuthType, CommandPolicy, ConnectionType, DialogStep, ForwardAgent, HostKeyPolicy, JobHostStatus, LoginBanner, PromptPatterns, Schedule, ServerConfig, SessionConfig, SessionWindow};
+use crate::config::{ConfigManager, CredentialBackend, HistoryEntry, HistoryStore, ImportFailure, JobStore, ScheduleStore, get_cert_dir, get_db_path, get_key_dir, get_motd_dir, get_run_dir, get_session_dir, get_ssh_config_dir, SessionManager, Settings, TrafficStore, Workspace, WORKSPACE_FILE};
+use crate::utils::{SshClient, import_ssh_config, connect_via_system_ssh, connect_via_system_ssh_with_command, ssh_command_connect, russh_connect, RsyncOptions};
 use crate::utils::rclone::RcloneConfig;
 use uuid::Uuid;
-use std::io::{self, Write, stdout};
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-use crate::utils::server_info::display_server_info;
+use std::io::{self, IsTerminal, Write, stdout};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use crate::utils::server_info::{display_agent_forwarding, display_server_info, display_server_qr, ssh_command_string};
+use crate::utils::picker;
+use crate::utils::plugin;
+use crate::utils::clipboard;
+use crate::utils::scrollback;
+use crate::utils::triggers;
+use crate::utils::macros;
+use crate::utils::hooks;
+use crate::utils::retention;
+use crate::utils::completions::{self, Candidates, Shell};
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::ssh_config::sanitize_host_alias;
+use crate::utils::telnet;
+use crate::utils::watch::{watch_download, WatchDownloadOptions};
+use crate::utils::cp::Endpoint;
+use crate::utils::fleet;
+use crate::utils::multi_upload;
+use crate::utils::fleet_diff::{self, Reference};
+use crate::utils::template_push;
+use crate::utils::notification;
+use crate::utils::address_fallback;
+use crate::utils::network_location;
+use crate::utils::session_registry::{list_active_sessions, SessionRegistry};
+use crate::utils::audit;
+use crate::utils::siem;
+use crate::utils::credential_vault;
+use crate::utils::traffic;
+use crate::utils::key_permissions;
+use crate::utils::sudo::Sudo;
+use crate::utils::{tunnel, jupyter};
+use crate::utils::secure_temp;
+use crate::utils::desktop::{self, DesktopProtocol};
+use crate::utils::git_remote::{self, GitRemote};
+use crate::utils::passwd;
+use crate::utils::remote_user::{self, Distro};
+use crate::utils::bootstrap::{self, Playbook};
+use crate::utils::sshd_audit::{self, SshdConfig};
+use crate::utils::ca;
+use crate::utils::share;
+use crate::utils::recording::{self, format_offset, Cast};
+use crate::utils::session_escape::{Forward, ForwardKind};
+use crate::utils::badge::Badge;
+use crate::utils::idle_lock::{self, IdleLockConfig};
+use crate::utils::connection_pool::{control_path, SftpPool};
+use crate::utils::ssh_command_builder::SshCommand;
+use crate::utils::deploy::{deploy, DeployMethod, DeployOptions, DEFAULT_EXCLUDES};
+use crate::utils::ssh_uri::{format_ssh_uri, is_ssh_uri, parse_uri_list, server_from_uri};
+use crate::utils::secret::{find_provider, is_secret_reference, provider_names};
 use shell_escape;
 use std::process::Command;
-use std::process::Stdio;
 
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
@@ -30,6 +76,14 @@ use ratatui::{
 #[command(version = "0.1.0")]
 #[command(about = "SSH连接管理工具", long_about = None)]
 pub struct Cli {
+    /// 审计模式：禁止密码认证、强制严格校验主机密钥和 FIPS 算法，远程执行的命令写入审计日志
+    #[arg(long, global = true)]
+    audit: bool,
+
+    /// 输出格式：json / table / plain，供脚本读取（list、info、session-list、exec 支持）
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -41,29 +95,36 @@ pub enum ConnectionMode {
     Exec,
     Debug,
     Russh,
+    Telnet,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
 pub enum TransferMode {
     Scp,
     Sftp,
+    Rsync,
     Auto,
 }
 
+// 命令行参数只在启动时解析一次，变体大小差异无关紧要
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     Add {
         #[arg(short = 'n', long)]
         name: String,
         
+        /// 主机地址；串口类型时为设备路径（如 /dev/ttyUSB0）
         #[arg(short = 'H', long)]
         host: String,
         
-        #[arg(short = 'P', long, default_value = "22")]
-        port: u16,
+        /// 端口；IPMI 类型未指定时使用 623
+        #[arg(short = 'P', long)]
+        port: Option<u16>,
         
+        /// 用户名；串口类型不需要
         #[arg(short, long)]
-        username: String,
+        username: Option<String>,
         
         #[arg(short = 't', long = "auth-type", default_value = "password")]
         auth_type: String,
@@ -79,22 +140,344 @@ enum Commands {
         
         #[arg(short, long)]
         description: Option<String>,
+
+        /// 密钥提供者 (db/env/cmd/pass/bw/op/keychain/vault)，设置后密码字段作为该提供者的引用
+        #[arg(long = "secret-provider")]
+        secret_provider: Option<String>,
+
+        /// 连接类型: ssh / serial (串口控制台) / ipmi (IPMI/iDRAC SOL) / telnet
+        #[arg(long = "type", default_value = "ssh")]
+        connection_type: String,
+
+        /// 串口波特率 (默认 115200)
+        #[arg(long)]
+        baud: Option<u32>,
+
+        /// 启用 SSH 压缩，适合慢速链路
+        #[arg(long)]
+        compress: bool,
+
+        /// 加密算法偏好（OpenSSH 写法，如 `chacha20-poly1305@openssh.com` 或 `+aes128-cbc`）
+        #[arg(long)]
+        ciphers: Option<String>,
+
+        /// 密钥交换算法偏好，如 `+diffie-hellman-group1-sha1`
+        #[arg(long)]
+        kex: Option<String>,
+
+        /// MAC 算法偏好
+        #[arg(long)]
+        macs: Option<String>,
+
+        /// 主机密钥 / 公钥签名算法偏好（默认 `+ssh-rsa`）
+        #[arg(long = "host-key-algorithms")]
+        host_key_algorithms: Option<String>,
+
+        /// 主机密钥校验策略，未指定时使用 config.toml 中的全局设置
+        #[arg(long = "host-key-policy", value_enum)]
+        host_key_policy: Option<HostKeyPolicy>,
+
+        /// 远程登录横幅 / MOTD 的处理方式
+        #[arg(long = "login-banner", value_enum, default_value = "show")]
+        login_banner: LoginBanner,
+
+        /// 连接前在本地显示的提示，例如 "生产环境，变更需要工单"
+        #[arg(long)]
+        note: Option<String>,
+
+        /// SSH agent 转发：no / yes / confirm（每次连接前确认）
+        #[arg(long = "forward-agent", value_enum, default_value = "no")]
+        forward_agent: ForwardAgent,
+
+        /// 备用地址（内网 IP、VPN 域名等），可重复；连接时依次尝试，并记住连上的地址
+        #[arg(long = "alt-host", value_name = "HOST")]
+        alt_hosts: Vec<String>,
+
+        /// 在内网（办公网 / VPN）时使用的地址，是否在内网由 config.toml `[networks]` 的检测命令判断
+        #[arg(long = "internal-host", value_name = "HOST")]
+        internal_host: Option<String>,
+
+        /// 内网地址对应的网络位置名称，config.toml 只定义了一个网络时可省略
+        #[arg(long)]
+        network: Option<String>,
+
+        /// 跳板机 `[user@]host[:port]`；配置了内网地址时只在不在内网时使用
+        #[arg(long = "jump", value_name = "JUMP")]
+        jump_host: Option<String>,
+
+        /// 经另一台已保存的服务器连接（名称或 ID），该服务器可以再经其它服务器；
+        /// 系统 ssh 模式使用 `-J`，内置库模式逐跳登录，各跳都使用自己保存的认证信息
+        #[arg(long = "proxy-jump", value_name = "SERVER", conflicts_with = "jump_host")]
+        proxy_jump: Option<String>,
+
+        /// 只允许 `rssh exec` 执行匹配该正则（匹配整条命令）的命令，可重复
+        #[arg(long = "allow-command", value_name = "REGEX")]
+        allow_commands: Vec<String>,
+
+        /// 禁止 `rssh exec` 执行匹配该正则（匹配整条命令）的命令，可重复
+        #[arg(long = "deny-command", value_name = "REGEX")]
+        deny_commands: Vec<String>,
+
+        /// expect 自动填密码时匹配的密码提示（Tcl 正则，不区分大小写），默认匹配常见语言的 password:
+        #[arg(long = "password-prompt", value_name = "REGEX")]
+        password_prompt: Option<String>,
+
+        /// 发送密码后等待的就绪提示（Tcl 正则），默认 `Opt>`
+        #[arg(long = "ready-prompt", value_name = "REGEX")]
+        ready_prompt: Option<String>,
+
+        /// 登录后的对话 `<正则> => <发送内容>`，可重复，按顺序执行（内置库连接），
+        /// 例如 `--dialog '> => enable' --dialog 'Password: => env:ENABLE_PASS'`
+        #[arg(long = "dialog", value_name = "EXPECT => SEND")]
+        login_dialog: Vec<String>,
+
+        /// 额外尝试的私钥，可重复；密钥认证时在 --auth-data 的密钥之后尝试，agent 认证时在 agent 之后尝试
+        #[arg(long = "identity-file", value_name = "KEY")]
+        identity_files: Vec<String>,
+
+        /// 只使用配置的私钥（IdentitiesOnly=yes），agent 中密钥较多时避免超出服务器的认证次数上限
+        #[arg(long = "identities-only")]
+        identities_only: bool,
+
+        /// 防空闲 `<秒>[:keepalive|resize|nul]`：会话空闲该秒数后发送 keepalive（默认）、重发窗口大小或 NUL 字节，
+        /// 用于设置了 ServerAliveInterval 仍会断开空闲连接的防火墙
+        #[arg(long = "anti-idle", value_name = "SECS[:METHOD]", value_parser = AntiIdle::parse)]
+        anti_idle: Option<AntiIdle>,
+
+        /// 私钥或 .ssh 目录权限过宽时直接修复，不再询问
+        #[arg(long = "fix-perms")]
+        fix_perms: bool,
     },
     
     List {
         #[arg(short, long)]
         group: Option<String>,
+
+        /// 不进入交互界面，直接打印服务器表格；标准输出不是终端时（管道、脚本）自动如此，每行一台
+        #[arg(long)]
+        no_tui: bool,
     },
 
     Connect {
-        server: String,
+        /// 服务器 ID、名称或别名；`-` 表示上一次连接的服务器（同 `rssh last`）。
+        /// 省略时使用项目配置 .rssh.toml 中的服务器
+        server: Option<String>,
 
         /// 在 wezterm 终端下，使用不保活的 SSH: 域而非默认的 SSHMUX: 多路复用域
         /// （当远端未安装 wezterm、无法跑 mux server 时使用）
         #[arg(long = "no-mux")]
         no_mux: bool,
+
+        /// 连接分组内的所有服务器（需配合 --each-window）
+        #[arg(short, long, conflicts_with = "server")]
+        group: Option<String>,
+
+        /// 为分组内每台服务器各开一个窗口/标签（kitty/wezterm/tmux）
+        #[arg(long = "each-window", requires = "group")]
+        each_window: bool,
+
+        /// 在当前终端的新窗口/标签中打开连接，不占用当前 shell
+        #[arg(long = "new-window", conflicts_with = "group")]
+        new_window: bool,
+
+        /// 连接方式，默认按服务器的连接类型选择（SSH 服务器使用系统 ssh）
+        #[arg(short, long, value_enum)]
+        mode: Option<ConnectionMode>,
+
+        /// 本次连接启用 SSH 压缩（覆盖服务器配置）
+        #[arg(short = 'C', long)]
+        compress: bool,
+
+        /// 通过 rzsz 代理连接，支持在会话中用 rz/sz 传输文件（需要本地安装 lrzsz）
+        #[arg(long, conflicts_with_all = ["mode", "new_window"])]
+        rzsz: bool,
+
+        /// 只读会话：只显示远程输出，键盘输入不发送到服务器（行首 ~. 断开），使用内置库连接
+        #[arg(long, conflicts_with_all = ["rzsz", "new_window", "group"])]
+        read_only: bool,
+
+        /// 在服务器上执行的命令，按 --mode 选择的方式执行后退出，例如 `-- uptime`；
+        /// 配合 --read-only 时在只读会话中执行，例如 `-- tail -f /var/log/app.log`
+        #[arg(last = true, conflicts_with_all = ["rzsz", "new_window", "group"])]
+        command: Vec<String>,
+
+        /// 输出 SSH 调试信息，可重复（-vvv）：系统 ssh 对应 -v，内置库和 russh 输出连接过程
+        #[arg(short = 'v', long = "ssh-verbose", action = clap::ArgAction::Count)]
+        ssh_verbose: u8,
+
+        /// 私钥或 .ssh 目录权限过宽时直接修复，不再询问
+        #[arg(long = "fix-perms")]
+        fix_perms: bool,
+
+        /// 本地端口转发 `[绑定地址:]端口:主机:主机端口`，可重复，例如 `-L 8080:localhost:80`；
+        /// 所有连接方式都支持，本地端口被占用时改用下一个可用端口
+        #[arg(short = 'L', long = "local-forward", value_name = "SPEC", conflicts_with_all = ["group", "new_window", "rzsz"])]
+        local_forward: Vec<String>,
+
+        /// 本次经指定的已保存服务器连接（代替服务器配置的跳板机）
+        #[arg(long, value_name = "SERVER", conflicts_with_all = ["group", "new_window"])]
+        via: Option<String>,
+    },
+
+    /// 按上一次的连接方式和选项重新连接最近连接过的服务器
+    Last,
+
+    /// 使用内置 SSH 库打开交互式会话，支持 ~. 断开、~C 添加端口转发等转义命令
+    Shell {
+        server: String,
+
+        /// 本次连接启用 SSH 压缩（覆盖服务器配置）
+        #[arg(short = 'C', long)]
+        compress: bool,
+
+        /// 把会话录制为 asciicast 文件，会话中行首输入 ~M 添加标记；只写文件名时保存到数据目录的 recordings/ 中
+        #[arg(long)]
+        record: Option<PathBuf>,
+
+        /// 输出 SSH 调试信息，可重复（-vvv）：输出内置库的连接过程
+        #[arg(short = 'v', long = "ssh-verbose", action = clap::ArgAction::Count)]
+        ssh_verbose: u8,
+    },
+
+    /// 回放 `rssh shell --record` 录制的会话
+    Replay {
+        /// 录制文件，当前目录中不存在时在 recordings/ 中查找
+        file: PathBuf,
+
+        /// 只列出录制中的标记
+        #[arg(long)]
+        markers: bool,
+
+        /// 从指定标记（序号或内容）开始播放，之前的内容快进显示
+        #[arg(long)]
+        from: Option<String>,
+
+        /// 播放倍速
+        #[arg(long, default_value = "1.0")]
+        speed: f64,
+
+        /// 两次输出之间最长停顿的秒数
+        #[arg(long, default_value = "2.0")]
+        max_idle: f64,
+    },
+
+    /// 只建立端口转发、不打开 shell，Ctrl+C 结束
+    Tunnel {
+        server: String,
+
+        /// 本地转发 [绑定地址:]端口:主机:主机端口，可重复
+        #[arg(short = 'L', long = "local")]
+        local: Vec<String>,
+
+        /// 远程转发 [绑定地址:]端口:主机:主机端口，可重复
+        #[arg(short = 'R', long = "remote")]
+        remote: Vec<String>,
+
+        /// 本地端口被占用时自动改用下一个可用端口
+        #[arg(long)]
+        auto_port: bool,
+
+        /// 第一个本地转发是 HTTP 服务，确认可用后用浏览器打开 http://localhost:<端口>
+        #[arg(long)]
+        open: bool,
+
+        /// 结束或失败时发送通知: desktop、slack:<URL>、webhook:<URL> 或 config.toml [notify] 中的名称，可重复
+        #[arg(long, value_name = "TARGET")]
+        notify: Vec<String>,
+    },
+
+    /// 启动或连接远程 Jupyter notebook，建立端口转发并在浏览器中打开
+    Jupyter {
+        server: String,
+
+        /// 远程 notebook 端口
+        #[arg(long, default_value_t = 8888)]
+        remote_port: u16,
+
+        /// 本地端口，默认与远程端口相同，被占用时自动改用下一个
+        #[arg(long)]
+        local_port: Option<u16>,
+
+        /// 需要启动时使用 JupyterLab 而不是 notebook
+        #[arg(long)]
+        lab: bool,
+
+        /// 远程 jupyter 命令，不在 PATH 中时指定完整路径（如 conda 环境中的 jupyter）
+        #[arg(long, default_value = "jupyter")]
+        jupyter: String,
+    },
+
+    /// 经隧道连接远程 VNC 桌面，并启动本地查看器
+    Vnc {
+        server: String,
+
+        /// 远程 VNC 端口（显示号 :1 对应 5901）
+        #[arg(long, default_value_t = 5900)]
+        remote: u16,
+
+        /// 从服务器访问的桌面主机，默认为服务器本身
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// 本地端口，默认与远程端口相同，被占用时自动改用下一个
+        #[arg(long)]
+        local_port: Option<u16>,
+
+        /// 查看器命令模板，覆盖 config.toml 中的 vnc_viewer
+        #[arg(long)]
+        viewer: Option<String>,
+    },
+
+    /// 经隧道连接远程 RDP 桌面（可经跳板机访问内网 Windows），并启动本地查看器
+    Rdp {
+        server: String,
+
+        /// 远程 RDP 端口
+        #[arg(long, default_value_t = 3389)]
+        remote: u16,
+
+        /// 从服务器访问的桌面主机，默认为服务器本身
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// 本地端口，默认与远程端口相同，被占用时自动改用下一个
+        #[arg(long)]
+        local_port: Option<u16>,
+
+        /// 查看器命令模板，覆盖 config.toml 中的 rdp_viewer
+        #[arg(long)]
+        viewer: Option<String>,
+    },
+
+    /// git 远程助手，由 git 通过 git-remote-rssh（指向 rssh 的符号链接）调用，
+    /// 让 `git clone rssh://服务器/路径` 使用保存的服务器配置
+    #[command(name = "git-remote")]
+    GitRemote {
+        /// git 中的远程名称
+        remote: String,
+
+        /// 仓库地址 rssh://服务器/路径
+        url: String,
+    },
+
+    /// 经服务器转发到目标主机（与 ssh -W 相同），用作 scp/rsync/git 等工具的 ProxyCommand；
+    /// 不指定目标时打印可直接使用的 ProxyCommand 配置
+    #[command(name = "proxycommand")]
+    ProxyCommand {
+        /// 用作跳板的服务器
+        server: String,
+
+        /// 目标主机（ssh 的 %h）
+        host: Option<String>,
+
+        /// 目标端口（ssh 的 %p）
+        #[arg(default_value_t = 22)]
+        port: u16,
     },
 
+    /// 列出正在运行的内置库 / russh 会话和隧道，以及它们的端口转发
+    Ps,
+
     Remove {
         server: String,
     },
@@ -102,19 +485,69 @@ enum Commands {
     Edit {
         server: String,
     },
+
+    /// 管理服务器别名，别名可以代替名称用于 connect / upload / exec 等: `rssh alias add db1 prod-database-01`
+    Alias {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+
+    /// 管理快捷编号，设置后 `rssh 3` 直接连接: `rssh slot set 3 web-01`
+    Slot {
+        #[command(subcommand)]
+        action: SlotAction,
+    },
     
     Upload {
+        /// 目标服务器；指定 --group 时省略，位置参数依次为本地文件和远程路径
         #[arg(index = 1)]
         server: String,
         
-        #[arg(index = 2)]
-        local_path: PathBuf,
+        #[arg(index = 2, required_unless_present = "group")]
+        local_path: Option<PathBuf>,
         
         #[arg(index = 3)]
         remote_path: Option<String>,
+
+        /// 并发上传到分组内的所有服务器（使用内置 SFTP，显示每台主机的进度）
+        #[arg(short, long, conflicts_with = "mode")]
+        group: Option<String>,
+
+        /// 分组上传时最多同时连接的主机数
+        #[arg(short = 'j', long, default_value = "10", requires = "group")]
+        parallel: usize,
+
+        /// 跳过分组上传前的目标确认
+        #[arg(short, long, requires = "group")]
+        yes: bool,
         
+        /// 传输方式，auto 按本地终端和远程已安装的工具（探测结果缓存 7 天）在 kitty、rsync、scp 中选择
         #[arg(short, long, value_enum, default_value = "auto")]
         mode: TransferMode,
+
+        /// 本次连接启用 SSH 压缩（覆盖服务器配置）
+        #[arg(short = 'C', long)]
+        compress: bool,
+
+        /// 显示 auto 模式的探测结果和选择原因
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// 结束或失败时发送通知: desktop、slack:<URL>、webhook:<URL> 或 config.toml [notify] 中的名称，可重复
+        #[arg(long, value_name = "TARGET")]
+        notify: Vec<String>,
+
+        /// 输出 SSH 调试信息，可重复：scp/rsync 使用的 ssh 对应 -v，内置 SFTP 输出连接过程
+        #[arg(long = "ssh-verbose", action = clap::ArgAction::Count)]
+        ssh_verbose: u8,
+
+        /// rsync 模式：删除目标中源没有的文件，同步目录时使用（分组上传使用 SFTP，不支持）
+        #[arg(long, conflicts_with = "group")]
+        delete: bool,
+
+        /// rsync 模式的排除规则，可重复指定（如 --exclude node_modules --exclude '*.log'）
+        #[arg(long, value_name = "PATTERN", conflicts_with = "group")]
+        exclude: Vec<String>,
     },
     
     Download {
@@ -127,13 +560,174 @@ enum Commands {
         #[arg(index = 3)]
         local_path: Option<PathBuf>,
         
+        /// 传输方式，auto 按本地终端和远程已安装的工具（探测结果缓存 7 天）在 kitty、rsync、scp 中选择
         #[arg(short, long, value_enum, default_value = "auto")]
         mode: TransferMode,
+
+        /// 本次连接启用 SSH 压缩（覆盖服务器配置）
+        #[arg(short = 'C', long)]
+        compress: bool,
+
+        /// 显示 auto 模式的探测结果和选择原因
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// 结束或失败时发送通知: desktop、slack:<URL>、webhook:<URL> 或 config.toml [notify] 中的名称，可重复
+        #[arg(long, value_name = "TARGET")]
+        notify: Vec<String>,
+
+        /// 输出 SSH 调试信息，可重复：scp/rsync 使用的 ssh 对应 -v，内置 SFTP 输出连接过程
+        #[arg(long = "ssh-verbose", action = clap::ArgAction::Count)]
+        ssh_verbose: u8,
+
+        /// rsync 模式：删除目标中源没有的文件，同步目录时使用
+        #[arg(long)]
+        delete: bool,
+
+        /// rsync 模式的排除规则，可重复指定（如 --exclude node_modules --exclude '*.log'）
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
     },
     
+    /// 监视远程目录，自动下载新出现的文件
+    #[command(name = "watch-download")]
+    WatchDownload {
+        server: String,
+
+        /// 要监视的远程目录
+        remote_dir: String,
+
+        /// 本地保存目录
+        #[arg(long, default_value = ".")]
+        to: PathBuf,
+
+        /// 轮询间隔（秒）
+        #[arg(long, default_value = "5")]
+        interval: u64,
+
+        /// 使用远程 inotifywait 实时监听（需要远程安装 inotify-tools）
+        #[arg(long)]
+        inotify: bool,
+
+        /// 启动时也下载目录中已有的文件
+        #[arg(long)]
+        existing: bool,
+    },
+
+    /// 在服务器或整个分组上执行命令: `rssh exec web1 -- uptime`、`rssh exec -g prod -- uptime`
+    #[command(alias = "run")]
+    Exec {
+        #[arg(required_unless_present = "group")]
+        server: Option<String>,
+
+        /// 在分组内的所有服务器上执行
+        #[arg(short, long, conflicts_with = "server")]
+        group: Option<String>,
+
+        /// 分组执行时最多同时连接的主机数
+        #[arg(short = 'j', long, default_value = "10")]
+        parallel: usize,
+
+        /// 本次连接启用 SSH 压缩（覆盖服务器配置）
+        #[arg(short = 'C', long)]
+        compress: bool,
+
+        /// 跳过执行前的目标确认（用于脚本）；金丝雀全部成功时自动继续
+        #[arg(short, long)]
+        yes: bool,
+
+        /// 先在 N 台主机上执行，确认结果后再继续
+        #[arg(long, default_value = "0")]
+        canary: usize,
+
+        /// 分批执行，每批的主机数（0 表示不分批）
+        #[arg(long = "batch-size", default_value = "0")]
+        batch_size: usize,
+
+        /// 每批之间等待的秒数
+        #[arg(long = "batch-delay", default_value = "0")]
+        batch_delay: u64,
+
+        /// 执行完成后按输出分组显示，突出与多数主机不同的离群主机
+        #[arg(long)]
+        collate: bool,
+
+        /// 结束或失败时发送通知: desktop、slack:<URL>、webhook:<URL> 或 config.toml [notify] 中的名称，可重复
+        #[arg(long, value_name = "TARGET")]
+        notify: Vec<String>,
+
+        /// 越过服务器的命令规则执行，每台被越过的服务器都会写入审计日志
+        #[arg(long = "override-policy")]
+        override_policy: bool,
+
+        /// 通过 `sudo -S` 以 root 身份执行：使用服务器保存的密码（没有时执行前输入一次），经标准输入发送，并从输出中去掉
+        #[arg(long)]
+        sudo: bool,
+
+        /// 要执行的命令（写在 -- 之后）
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// 统一的复制命令：`rssh cp [-r] 源... 目标`，路径写作本地路径或 `服务器:路径`
+    Cp {
+        /// 源路径（可多个）和最后的目标路径
+        #[arg(required = true, num_args = 2..)]
+        paths: Vec<String>,
+
+        /// 递归复制目录
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// 结束或失败时发送通知: desktop、slack:<URL>、webhook:<URL> 或 config.toml [notify] 中的名称，可重复
+        #[arg(long, value_name = "TARGET")]
+        notify: Vec<String>,
+
+        /// 输出 SSH 调试信息，可重复（-vvv）：输出内置 SFTP 的连接过程
+        #[arg(short = 'v', long = "ssh-verbose", action = clap::ArgAction::Count)]
+        ssh_verbose: u8,
+    },
+
+    /// 将本地目录同步到远程目录，--watch 时保存即同步
+    Deploy {
+        /// 目标服务器；省略本地和远程目录时为 .rssh.toml 中的部署目标名称
+        server: Option<String>,
+
+        /// 本地目录
+        #[arg(requires = "remote_dir")]
+        local_dir: Option<PathBuf>,
+
+        /// 远程目录
+        remote_dir: Option<String>,
+
+        /// 监视本地目录，文件变化后自动同步
+        #[arg(short, long)]
+        watch: bool,
+
+        /// 排除规则，可重复指定（如 --exclude node_modules --exclude '*.map'）
+        #[arg(short, long)]
+        exclude: Vec<String>,
+
+        /// 最后一次变化后等待多少毫秒再同步
+        #[arg(long, default_value = "300")]
+        debounce: u64,
+
+        /// 同步方式
+        #[arg(long, value_enum, default_value = "auto")]
+        method: DeployMethod,
+
+        /// 删除远程目录中本地没有的文件（SFTP 方式只删除监视期间本地删除的文件和目录）
+        #[arg(long)]
+        delete: bool,
+    },
+
     Import {
         #[arg(short, long)]
         config: Option<PathBuf>,
+
+        /// 从 ssh:// URI 列表文件导入（每行一个 URI，可在其后跟服务器名称）
+        #[arg(long = "uri-list", conflicts_with = "config")]
+        uri_list: Option<PathBuf>,
         
         #[arg(short, long)]
         group: Option<String>,
@@ -145,6 +739,10 @@ enum Commands {
     Export {
         #[arg(index = 1)]
         path: PathBuf,
+
+        /// 导出为 ssh:// URI 列表文件（每行一个），而不是配置目录
+        #[arg(long)]
+        uris: bool,
     },
 
     #[command(name = "export-ssh-config")]
@@ -158,93 +756,540 @@ enum Commands {
         path: PathBuf,
     },
 
-    Info {
+    /// 修改服务器上登录用户的密码，成功后更新保存的密码并记录修改时间
+    Passwd {
         server: String,
     },
 
-    Copy {
-        #[arg(short, long)]
-        from: String,
-        
-        #[arg(short, long)]
-        from_path: String,
-        
-        #[arg(short, long)]
-        to: String,
-        
-        #[arg(short, long)]
-        to_path: String,
+    /// 在服务器上创建 / 删除用户
+    User {
+        #[command(subcommand)]
+        action: UserAction,
     },
 
-    #[command(name = "session-create")]
-    SessionCreate {
-        #[arg(short = 'n', long)]
-        name: String,
-        
-        #[arg(short, long)]
-        description: Option<String>,
-        
-        #[arg(short, long)]
-        config: Option<PathBuf>,
-    },
-    
-    #[command(name = "session-list")]
-    SessionList,
-    
-    #[command(name = "session-edit")]
-    SessionEdit {
-        #[arg(index = 1)]
+    /// 把正在运行的 rssh tmux 会话共享给本机的其他用户，默认只读（需要 tmux 3.3+）
+    Share {
+        /// 会话名称/ID，或 tmux 会话名（rssh_xxx）
         session: String,
+
+        /// 共享给该用户
+        #[arg(short, long)]
+        user: String,
+
+        /// 允许对方输入（默认只能观看）
+        #[arg(short, long, conflicts_with = "stop")]
+        write: bool,
+
+        /// 取消对该用户的共享并断开其连接
+        #[arg(long)]
+        stop: bool,
     },
-    
-    #[command(name = "session-remove")]
-    SessionRemove {
-        #[arg(index = 1)]
-        session: String,
+
+    /// 团队 CA：签发 SSH 用户证书、查看连接时自动签发的证书
+    Ca {
+        #[command(subcommand)]
+        action: CaAction,
     },
-    
-    #[command(name = "session-start")]
-    SessionStart {
-        #[arg(index = 1)]
-        session: String,
 
-        #[arg(long)]
-        tmux: bool,
+    /// 按服务器统计内置库 / russh 连接的收发流量（系统 ssh 模式不统计）
+    Stats {
+        /// 只显示该服务器
+        server: Option<String>,
 
+        /// 统计区间，例如 24h、7d、2w 或 2024-05-01（默认全部）
         #[arg(long)]
-        kitty: bool,
+        since: Option<String>,
+    },
 
-        #[arg(long)]
-        wezterm: bool,
+    /// 查看或修改全局配置（config.toml）
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
     },
-}
 
-fn run_list_tui<B: Backend>(
-    terminal: &mut Terminal<B>,
-    servers: Vec<ServerConfig>,
-    group_filter: Option<String>,
-) -> Result<Option<ServerConfig>> {
-    let mut table_state = TableState::default();
-    let mut search = String::new();
+    /// 凭据库：用主密码加密数据库中保存的密码，连接时自动解密
+    Vault {
+        #[command(subcommand)]
+        action: VaultAction,
+    },
 
-    loop {
-        let filtered: Vec<&ServerConfig> = if search.is_empty() {
-            servers.iter().collect()
-        } else {
-            let needle = search.to_lowercase();
-            servers
-                .iter()
-                .filter(|s| {
-                    s.name.to_lowercase().contains(&needle)
-                        || s.host.to_lowercase().contains(&needle)
-                        || s.username.to_lowercase().contains(&needle)
-                        || s.group
-                            .as_deref()
-                            .map(|g| g.to_lowercase().contains(&needle))
-                            .unwrap_or(false)
-                })
-                .collect()
-        };
+    /// 输入主密码解锁凭据库，有效期内执行的命令不再询问主密码（同 rssh vault unlock）
+    Login {
+        /// 有效期，例如 30m、8h、1d，不带单位时为分钟
+        #[arg(short, long, default_value = "15m", value_parser = credential_vault::parse_duration)]
+        duration: std::time::Duration,
+    },
+
+    /// 立即锁定凭据库，删除缓存的密钥（同 rssh vault lock）
+    Logout,
+
+    /// 按 TOML 清单初始化新服务器：安装软件包、上传文件、安装公钥、加固 sshd 等，每一步都可重复执行
+    Bootstrap {
+        server: String,
+
+        /// 步骤清单文件
+        #[arg(long)]
+        playbook: PathBuf,
+    },
+
+    /// 检查服务器的 sshd 配置并按风险打分: `rssh audit-sshd web1`、`rssh audit-sshd -g prod`
+    #[command(name = "audit-sshd")]
+    AuditSshd {
+        #[arg(required_unless_present = "group")]
+        server: Option<String>,
+
+        /// 检查分组内的所有服务器
+        #[arg(short, long, conflicts_with = "server")]
+        group: Option<String>,
+
+        /// 最多同时连接的主机数
+        #[arg(short = 'j', long, default_value = "10")]
+        parallel: usize,
+    },
+
+    /// 比较分组内各主机上的同一个文件，找出配置漂移: `rssh diff -g lb /etc/haproxy/haproxy.cfg`
+    Diff {
+        /// 远程文件路径
+        path: String,
+
+        /// 要比较的分组
+        #[arg(short, long)]
+        group: String,
+
+        /// 与本地文件比较，默认以分组内第一台读取成功的主机为基准
+        #[arg(long)]
+        against: Option<PathBuf>,
+
+        /// 最多同时连接的主机数
+        #[arg(short = 'j', long, default_value = "10")]
+        parallel: usize,
+    },
+
+    /// 按主机渲染模板并推送: `rssh push-template nginx.conf.tmpl -g web --dest /etc/nginx/nginx.conf`
+    #[command(name = "push-template")]
+    PushTemplate {
+        /// 本地模板文件（minijinja 语法），可用 server、index、hosts、vars 变量
+        template: PathBuf,
+
+        /// 推送到分组内的所有服务器
+        #[arg(short, long, required_unless_present = "server")]
+        group: Option<String>,
+
+        /// 推送到单台服务器
+        #[arg(short, long, conflicts_with = "group")]
+        server: Option<String>,
+
+        /// 远程目标路径
+        #[arg(long)]
+        dest: String,
+
+        /// 模板变量，可重复: `--var workers=4`
+        #[arg(long = "var", value_name = "KEY=VALUE", value_parser = template_push::parse_var)]
+        vars: Vec<(String, String)>,
+
+        /// 写入后执行的命令，只在文件有变化的主机上执行: `--reload "nginx -t && systemctl reload nginx"`
+        #[arg(long)]
+        reload: Option<String>,
+
+        /// 通过 sudo -n 读写目标文件并执行重载命令
+        #[arg(long)]
+        sudo: bool,
+
+        /// 只显示 diff，不写入
+        #[arg(long)]
+        dry_run: bool,
+
+        /// 跳过确认
+        #[arg(short, long)]
+        yes: bool,
+
+        /// 最多同时连接的主机数
+        #[arg(short = 'j', long, default_value = "10")]
+        parallel: usize,
+    },
+
+    /// 显示服务器信息；不指定服务器时列出开启了 SSH agent 转发的服务器
+    Info {
+        server: Option<String>,
+
+        /// 以二维码形式显示 ssh:// URI，方便用手机 SSH 客户端扫码连接
+        #[arg(long)]
+        qr: bool,
+    },
+
+    Copy {
+        #[arg(short, long)]
+        from: String,
+        
+        #[arg(long)]
+        from_path: String,
+        
+        #[arg(short, long)]
+        to: String,
+        
+        #[arg(long)]
+        to_path: String,
+    },
+
+    /// 选择服务器并连接，适合绑定到 tmux popup / fzf 快捷键
+    Pick {
+        /// 初始搜索词；与某个服务器名称完全一致时直接选中
+        query: Option<String>,
+
+        #[arg(short, long)]
+        group: Option<String>,
+
+        /// 只输出服务器列表（每行一个，Tab 分隔，第一列为名称）
+        #[arg(long)]
+        list: bool,
+
+        /// 打印选中服务器的 ssh 命令而不是直接连接
+        #[arg(long = "print-command")]
+        print_command: bool,
+
+        /// 在新窗口中连接选中的服务器
+        #[arg(long = "new-window")]
+        new_window: bool,
+    },
+
+    /// 查看批量执行任务的结果，只重试失败的主机
+    Job {
+        #[command(subcommand)]
+        action: JobAction,
+    },
+
+    /// 按 cron 表达式定时在分组上执行命令: `rssh schedule add "0 3 * * *" -g backup run-backup.sh`
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+
+    /// 新用户引导：导入 ~/.ssh/config、选择默认设置、测试连接并安装 shell 补全
+    Quickstart,
+
+    /// 输出 shell 补全脚本: `rssh completions zsh > ~/.zfunc/_rssh`，加 `--install` 直接安装
+    Completions {
+        /// bash / zsh / fish，省略时根据 $SHELL 判断
+        shell: Option<Shell>,
+        /// 安装到该 shell 默认加载补全的位置
+        #[arg(long)]
+        install: bool,
+    },
+
+    /// 按 [retention] 设置清理过期的会话录制、调试日志和批量任务结果
+    Clean {
+        /// 只列出会清理的内容，不删除
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 安装与其它工具的集成
+    Integrate {
+        #[command(subcommand)]
+        target: IntegrateTarget,
+    },
+
+    /// 管理插件：PATH 中的 `rssh-<名称>` 可执行文件可以作为 `rssh <名称>` 运行
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
+    },
+
+    #[command(name = "session-create")]
+    SessionCreate {
+        #[arg(short = 'n', long)]
+        name: String,
+        
+        #[arg(short, long)]
+        description: Option<String>,
+        
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+    
+    #[command(name = "session-list")]
+    SessionList,
+    
+    #[command(name = "session-edit")]
+    SessionEdit {
+        #[arg(index = 1)]
+        session: String,
+    },
+    
+    #[command(name = "session-remove")]
+    SessionRemove {
+        #[arg(index = 1)]
+        session: String,
+    },
+    
+    #[command(name = "session-start")]
+    SessionStart {
+        /// 会话 ID 或名称，省略时使用 .rssh.toml 中的 session
+        #[arg(index = 1)]
+        session: Option<String>,
+
+        #[arg(long)]
+        tmux: bool,
+
+        #[arg(long)]
+        kitty: bool,
+
+        #[arg(long)]
+        wezterm: bool,
+    },
+
+    /// 其它子命令交给 PATH 中的插件 `rssh-<名称>` 执行
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+#[derive(Subcommand)]
+enum JobAction {
+    /// 列出最近的任务
+    List {
+        /// 最多显示的任务数
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// 查看任务中每台主机的输出和退出码
+    Show {
+        /// 任务 ID（可只输入前几位），`last` 表示最近一次任务
+        id: String,
+
+        /// 只显示失败和未执行的主机
+        #[arg(long)]
+        failed: bool,
+    },
+
+    /// 在任务中失败或未执行（任务被中断）的主机上重新执行
+    #[command(name = "rerun-failed")]
+    RerunFailed {
+        /// 任务 ID（可只输入前几位），`last` 表示最近一次任务
+        id: String,
+
+        /// 最大并发数
+        #[arg(short = 'j', long, default_value = "10")]
+        parallel: usize,
+
+        /// 跳过执行前确认
+        #[arg(short = 'y', long)]
+        yes: bool,
+
+        /// 执行完成后按输出分组显示
+        #[arg(long)]
+        collate: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AliasAction {
+    /// 为服务器添加别名
+    Add {
+        /// 别名
+        alias: String,
+
+        /// 服务器名称或 ID
+        server: String,
+    },
+
+    /// 删除别名
+    #[command(alias = "rm")]
+    Remove {
+        alias: String,
+    },
+
+    /// 列出所有别名
+    List,
+}
+
+#[derive(Subcommand)]
+enum SlotAction {
+    /// 把快捷编号分配给服务器，已被占用时从原服务器上移走
+    Set {
+        /// 快捷编号（1-99）
+        #[arg(value_parser = clap::value_parser!(u32).range(1..=99))]
+        slot: u32,
+
+        /// 服务器名称、ID 或别名
+        server: String,
+    },
+
+    /// 清除快捷编号
+    #[command(alias = "rm")]
+    Clear {
+        slot: u32,
+    },
+
+    /// 列出所有快捷编号
+    List,
+}
+
+#[derive(Subcommand)]
+enum ScheduleAction {
+    /// 添加定时任务，需要运行 `rssh schedule daemon` 才会执行
+    Add {
+        /// 五段 cron 表达式（分 时 日 月 周），按本地时间计算
+        cron: String,
+
+        /// 目标分组
+        #[arg(short, long)]
+        group: String,
+
+        /// 最多同时连接的主机数
+        #[arg(short = 'j', long, default_value = "10")]
+        parallel: usize,
+
+        /// 要执行的命令
+        #[arg(required = true, num_args = 1..)]
+        command: Vec<String>,
+    },
+
+    /// 列出定时任务及下一次执行时间
+    List,
+
+    /// 删除定时任务
+    #[command(alias = "rm")]
+    Remove {
+        /// 定时任务 ID（可只输入前几位）
+        id: String,
+    },
+
+    /// 在前台运行调度器，到时间后在分组上执行命令并记录到任务历史（rssh job list）
+    Daemon,
+}
+
+#[derive(Subcommand)]
+enum CaAction {
+    /// 用 config.toml 中配置的 CA 为公钥签发证书，证书保存为 `<公钥名>-cert.pub`
+    SignKey {
+        /// 要签名的公钥文件，例如 ~/.ssh/id_ed25519.pub
+        public_key: PathBuf,
+
+        /// 证书允许登录的用户名，逗号分隔
+        #[arg(short = 'n', long, value_delimiter = ',', required = true)]
+        principals: Vec<String>,
+
+        /// 有效期，例如 30m、8h、1d（默认使用 [ca] 的 validity）
+        #[arg(short = 'V', long)]
+        validity: Option<String>,
+
+        /// 证书标识，会出现在服务器的认证日志中（默认 `<本机用户>@rssh`）
+        #[arg(short = 'I', long)]
+        identity: Option<String>,
+    },
+
+    /// 显示 CA 配置和各服务器当前证书的有效期
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// 修改一项配置，例如 `rssh config set credential-backend keychain`
+    Set {
+        key: String,
+        value: String,
+    },
+
+    /// 显示一项配置的值
+    Get {
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum VaultAction {
+    /// 设置主密码并加密所有已保存的明文密码，之后添加的密码也会加密保存
+    Init,
+
+    /// 输入主密码解锁，期间连接不再询问主密码
+    Unlock {
+        /// 解锁时长（分钟）
+        #[arg(short, long, default_value_t = 15)]
+        minutes: u64,
+    },
+
+    /// 立即锁定，删除缓存的密钥
+    Lock,
+
+    /// 显示凭据库是否启用、是否已解锁以及加密的密码数量
+    Status,
+}
+
+#[derive(Subcommand)]
+enum UserAction {
+    /// 创建用户并安装公钥，用户已存在时只追加公钥
+    Add {
+        server: String,
+        username: String,
+
+        /// 公钥文件路径或公钥内容，可重复
+        #[arg(long)]
+        key: Vec<String>,
+
+        /// 加入管理员组（Debian 系为 sudo，其他为 wheel）
+        #[arg(long)]
+        sudo: bool,
+    },
+
+    /// 删除用户及其主目录
+    Remove {
+        server: String,
+        username: String,
+
+        /// 保留主目录
+        #[arg(long)]
+        keep_home: bool,
+
+        /// 不询问确认
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginAction {
+    /// 列出 PATH 中找到的插件
+    List,
+
+    /// 以 JSON 输出服务器清单（不含凭据），供插件读取
+    Inventory {
+        /// 只输出指定分组
+        #[arg(short, long)]
+        group: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IntegrateTarget {
+    /// 在 ~/.tmux.conf 中安装 popup 快捷键 (prefix + key 打开 rssh pick)
+    Tmux {
+        #[arg(long, default_value = "S")]
+        key: String,
+    },
+}
+
+fn run_list_tui<B: Backend>(
+    terminal: &mut Terminal<B>,
+    servers: Vec<ServerConfig>,
+    group_filter: Option<String>,
+) -> Result<Option<ServerConfig>> {
+    let mut table_state = TableState::default();
+    let mut search = String::new();
+    // 按 `/` 进入搜索模式，输入的字符进入搜索框；否则 j/k 等按键用于导航
+    let mut searching = false;
+    let mut filtered: Vec<&ServerConfig> = Vec::new();
+    let mut filtered_for: Option<String> = None;
+    let mut last_key = std::time::Instant::now();
+    let debounce = hooks::fuzzy_score_runs_command();
+
+    loop {
+        // 打分命令每次都要启动进程，只在搜索内容变化、且输入停顿 200ms 后重新过滤
+        let settled = !debounce || filtered_for.is_none() || last_key.elapsed() >= std::time::Duration::from_millis(200);
+        if filtered_for.as_deref() != Some(search.as_str()) && settled {
+            filtered = picker::fuzzy_filter(&servers, &search);
+            filtered_for = Some(search.clone());
+        }
 
         if filtered.is_empty() {
             table_state.select(None);
@@ -258,14 +1303,16 @@ fn run_list_tui<B: Backend>(
             }
         }
 
-        terminal.draw(|f| ui(f, &filtered, group_filter.as_deref(), &search, &mut table_state))?;
+        terminal.draw(|f| ui(f, &filtered, group_filter.as_deref(), &search, searching, &mut table_state))?;
 
         if event::poll(std::time::Duration::from_millis(50))? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
+                    last_key = std::time::Instant::now();
+                    let down = matches!(key.code, KeyCode::Down) || (!searching && key.code == KeyCode::Char('j'));
+                    let up = matches!(key.code, KeyCode::Up) || (!searching && key.code == KeyCode::Char('k'));
                     match key.code {
-                        KeyCode::Esc => return Ok(None),
-                        KeyCode::Down => {
+                        _ if down => {
                             if !filtered.is_empty() {
                                 let i = match table_state.selected() {
                                     Some(i) if i >= filtered.len() - 1 => 0,
@@ -275,7 +1322,7 @@ fn run_list_tui<B: Backend>(
                                 table_state.select(Some(i));
                             }
                         }
-                        KeyCode::Up => {
+                        _ if up => {
                             if !filtered.is_empty() {
                                 let i = match table_state.selected() {
                                     Some(0) | None => filtered.len() - 1,
@@ -284,6 +1331,26 @@ fn run_list_tui<B: Backend>(
                                 table_state.select(Some(i));
                             }
                         }
+                        KeyCode::Esc if searching => {
+                            searching = false;
+                            search.clear();
+                            table_state.select(Some(0));
+                        }
+                        KeyCode::Enter if searching => searching = false,
+                        KeyCode::Backspace if searching => {
+                            search.pop();
+                        }
+                        KeyCode::Char(c) if searching => {
+                            search.push(c);
+                            table_state.select(Some(0));
+                        }
+                        KeyCode::Char('/') => searching = true,
+                        // 有过滤条件时 Esc 先清除过滤，再按一次退出
+                        KeyCode::Esc if !search.is_empty() => {
+                            search.clear();
+                            table_state.select(Some(0));
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
                         KeyCode::Enter => {
                             if let Some(i) = table_state.selected() {
                                 if let Some(s) = filtered.get(i) {
@@ -291,12 +1358,6 @@ fn run_list_tui<B: Backend>(
                                 }
                             }
                         }
-                        KeyCode::Backspace => {
-                            search.pop();
-                        }
-                        KeyCode::Char(c) => {
-                            search.push(c);
-                        }
                         _ => {}
                     }
                 }
@@ -310,6 +1371,7 @@ fn ui(
     servers: &[&ServerConfig],
     group_filter: Option<&str>,
     search: &str,
+    searching: bool,
     state: &mut TableState,
 ) {
     let main_layout = Layout::default()
@@ -332,14 +1394,16 @@ fn ui(
         .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT);
     f.render_widget(title, main_layout[0]);
 
-    let search_display = if search.is_empty() {
-        "(输入字符开始过滤)".to_string()
+    let search_display = if searching {
+        format!("/{}_", search)
+    } else if search.is_empty() {
+        "(按 / 开始模糊搜索: 名称、主机、分组、描述)".to_string()
     } else {
-        format!("{}_", search)
+        format!("/{}", search)
     };
     let search_box = Paragraph::new(Text::styled(
         search_display,
-        Style::default().fg(if search.is_empty() {
+        Style::default().fg(if !searching && search.is_empty() {
             Color::DarkGray
         } else {
             Color::Yellow
@@ -374,10 +1438,18 @@ fn ui(
             } else {
                 &server.id
             };
-            let auth_str = match &server.auth_type {
-                AuthType::Password(_) => "密码",
-                AuthType::Key(_) => "密钥",
-                AuthType::Agent => "代理",
+            let auth_str = match (&server.connection_type, &server.auth_type) {
+                (ConnectionType::Serial { .. }, _) => "串口",
+                (ConnectionType::Ipmi, _) => "IPMI",
+                (ConnectionType::Telnet, _) => "Telnet",
+                (_, AuthType::Password(_)) => "密码",
+                (_, AuthType::Key(_)) => "密钥",
+                (_, AuthType::Agent) => "代理",
+            };
+            // 串口没有端口，显示波特率
+            let port_str = match &server.connection_type {
+                ConnectionType::Serial { baud } => baud.to_string(),
+                _ => server.port.to_string(),
             };
             let group_str = server.group.as_deref().unwrap_or("--");
 
@@ -385,9 +1457,10 @@ fn ui(
                 Cell::from(short_id).style(Style::default().fg(Color::Yellow)),
                 Cell::from(server.name.clone()).style(Style::default().fg(Color::Green)),
                 Cell::from(server.host.clone()),
-                Cell::from(server.port.to_string()).style(Style::default().fg(Color::Cyan)),
+                Cell::from(port_str).style(Style::default().fg(Color::Cyan)),
                 Cell::from(server.username.clone()),
                 Cell::from(auth_str).style(match &server.auth_type {
+                     _ if server.connection_type != ConnectionType::Ssh => Style::default().fg(Color::Red),
                      AuthType::Password(_) => Style::default().fg(Color::Yellow),
                      AuthType::Key(_) => Style::default().fg(Color::Blue),
                      AuthType::Agent => Style::default().fg(Color::Cyan),
@@ -417,21 +1490,176 @@ fn ui(
     }
 
     let footer_text = Text::styled(
-        "输入: 过滤 | Backspace: 删字符 | ↑/↓: 选择 | Enter: 连接 | Esc: 退出",
+        if searching {
+            "输入: 过滤 | Backspace: 删字符 | ↑/↓: 选择 | Enter: 确定 | Esc: 取消搜索"
+        } else {
+            "/: 搜索 | j/k ↑/↓: 选择 | Enter: 连接 | Esc: 清除过滤/退出 | q: 退出"
+        },
         Style::default().fg(Color::DarkGray),
     );
     let footer = Paragraph::new(footer_text).alignment(Alignment::Center);
     f.render_widget(footer, main_layout[3]);
 }
 
+/// 每行输出一个服务器、分组或会话名称，供补全脚本读取
+fn print_candidates(kind: Candidates) -> Result<()> {
+    let mut names: Vec<String> = match kind {
+        Candidates::Servers => ConfigManager::new(get_db_path()?)?
+            .list_servers()?
+            .into_iter()
+            .flat_map(|server| std::iter::once(server.name).chain(server.aliases))
+            .collect(),
+        Candidates::Groups => ConfigManager::new(get_db_path()?)?
+            .list_servers()?
+            .into_iter()
+            .filter_map(|server| server.group)
+            .collect(),
+        Candidates::Sessions => SessionManager::new(get_session_dir()?)?
+            .list_sessions()?
+            .into_iter()
+            .map(|session| session.name)
+            .collect(),
+    };
+    names.sort();
+    names.dedup();
+    output::print(&names.iter().map(|name| format!("{}\n", name)).collect::<String>())
+}
+
 pub fn run() -> Result<()> {
-    let cli = Cli::parse();
-    let config_manager = ConfigManager::new(get_db_path()?)?;
-    
-    match cli.command {
-        Commands::Add { name, host, port, username, auth_type, auth_data, password, group, description } => {
-            let auth = match auth_type.as_str() {
-                "password" => {
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    // 先于其他会打开数据库的操作（快捷编号、补全候选），否则会在新位置建出空数据库；
+    // 补全脚本调用时不输出提示，避免混进命令行
+    let completing = args.get(1).is_some_and(|arg| arg == "__complete");
+    for moved in crate::config::migrate_legacy_layout()? {
+        if !completing {
+            eprintln!("{} 已移动到 {}", "✓".bright_green(), moved.display());
+        }
+    }
+    // 以 git-remote-rssh 的名字运行时，git 传入的参数是 <远程名称> <地址>
+    let program = args.first().map(PathBuf::from);
+    if program.as_deref().and_then(|p| p.file_stem()).is_some_and(|name| name == "git-remote-rssh") {
+        args.insert(1, "git-remote".into());
+    }
+    // `rssh 3 [选项]` 连接快捷编号为 3 的服务器，等同于 `rssh connect <服务器> [选项]`
+    if let Some(slot) = args.get(1).and_then(|arg| arg.to_str()).and_then(|arg| arg.parse::<u32>().ok()) {
+        let server = ConfigManager::new(get_db_path()?)?
+            .find_by_slot(slot)?
+            .ok_or_else(|| anyhow::anyhow!("编号 {} 没有分配给任何服务器，使用 rssh slot set {} <服务器> 分配", slot, slot))?;
+        args.splice(1..2, ["connect".into(), server.id.into()]);
+    }
+    // 补全脚本调用的 `rssh __complete <类型>`，不作为子命令出现在帮助和补全中
+    if completing {
+        let kind = args
+            .get(2)
+            .and_then(|arg| arg.to_str())
+            .and_then(|arg| Candidates::from_str(arg, true).ok())
+            .ok_or_else(|| anyhow::anyhow!("用法: rssh __complete servers|groups|sessions"))?;
+        return print_candidates(kind);
+    }
+    let cli = Cli::parse_from(args);
+    crate::utils::ssh::set_debug_log_path(crate::config::get_debug_log_path()?);
+    if let Some(format) = cli.output {
+        output::configure(format);
+    }
+    let config_manager = ConfigManager::new(get_db_path()?)?;
+    let settings = Settings::load()?;
+    if let Some(policy) = settings.host_key_policy {
+        HostKeyPolicy::set_default(policy);
+    }
+    if cli.audit || settings.audit || std::env::var_os("RSSH_AUDIT").is_some() {
+        enable_audit_mode();
+        // 新窗口、会话中启动的 rssh 子进程也继承审计模式
+        std::env::set_var("RSSH_AUDIT", "1");
+    }
+    if let Some(groups) = settings.agent_forwarding_groups.clone() {
+        ForwardAgent::restrict_to_groups(groups);
+    }
+    address_fallback::configure(|id, host| ConfigManager::new(get_db_path()?)?.set_preferred_host(id, host));
+    ServerConfig::set_server_lookup(|name| find_server(&ConfigManager::new(get_db_path()?)?, name));
+    if let Some(timeout) = settings.connect_timeout.filter(|&secs| secs > 0) {
+        address_fallback::set_connect_timeout(std::time::Duration::from_secs(timeout));
+    }
+    traffic::configure(|id, name, received, sent| TrafficStore::new(get_db_path()?)?.record(id, name, received, sent));
+    network_location::configure(settings.networks.clone());
+    hooks::configure(settings.hooks.clone());
+    clipboard::configure(settings.clipboard.clone());
+    scrollback::configure(settings.scrollback_kb.unwrap_or(scrollback::DEFAULT_KB));
+    triggers::configure(settings.triggers.clone(), notify_trigger);
+    macros::configure(settings.macros.clone());
+    if !matches!(cli.command, Commands::Clean { .. }) {
+        retention::auto_clean(&settings.retention);
+    }
+    if settings.theme.as_deref() == Some("mono") {
+        colored::control::set_override(false);
+    }
+    if let Some(ca_settings) = settings.ca.clone() {
+        ca::configure(ca_settings, get_cert_dir()?);
+    }
+    if let Some(siem_settings) = settings.siem.clone() {
+        siem::configure(siem_settings);
+    }
+    CredentialBackend::set_default(settings.credential_backend.unwrap_or_default());
+    if let Some(meta) = config_manager.vault_meta()? {
+        let key_file = settings.vault_key_file.as_deref().map(|path| PathBuf::from(crate::utils::ssh_config::expand_tilde(path)));
+        credential_vault::configure(meta, key_file);
+    }
+    if let Some(timeout) = settings.idle_timeout.filter(|&secs| secs > 0) {
+        idle_lock::configure(IdleLockConfig {
+            timeout: std::time::Duration::from_secs(timeout),
+            password: settings.idle_lock_password.clone(),
+        });
+    }
+    crate::utils::motd::set_capture_dir(get_motd_dir()?);
+    
+    // `rssh last` 等同于 `rssh connect -`
+    let command = match cli.command {
+        Commands::Last => Commands::Connect {
+            server: Some("-".into()),
+            no_mux: false,
+            group: None,
+            each_window: false,
+            new_window: false,
+            mode: None,
+            compress: false,
+            rzsz: false,
+            read_only: false,
+            command: Vec::new(),
+            ssh_verbose: 0,
+            fix_perms: false,
+            local_forward: Vec::new(),
+            via: None,
+        },
+        command => command,
+    };
+
+    match command {
+        Commands::Add { name, host, port, username, auth_type, auth_data, password, group, description, secret_provider, connection_type, baud, compress, ciphers, kex, macs, host_key_algorithms, host_key_policy, login_banner, note, forward_agent, alt_hosts, internal_host, network, jump_host, proxy_jump, allow_commands, deny_commands, password_prompt, ready_prompt, login_dialog, identity_files, identities_only, anti_idle, fix_perms } => {
+            let connection_type = ConnectionType::parse(&connection_type, baud)?;
+
+            if let Some(provider) = &secret_provider {
+                if find_provider(provider).is_none() {
+                    return Err(anyhow::anyhow!("未知的密钥提供者: {} (可选: {})", provider, provider_names()));
+                }
+            }
+
+            let (username, port) = match connection_type {
+                // 串口不需要用户名和端口
+                ConnectionType::Serial { .. } => (username.unwrap_or_default(), 0),
+                ConnectionType::Ipmi => (
+                    username.ok_or_else(|| anyhow::anyhow!("IPMI 需要指定 BMC 用户名 (-u)"))?,
+                    port.unwrap_or(623),
+                ),
+                // telnet 的登录在会话中交互完成
+                ConnectionType::Telnet => (username.unwrap_or_default(), port.unwrap_or(telnet::DEFAULT_PORT)),
+                ConnectionType::Ssh => (
+                    username.ok_or_else(|| anyhow::anyhow!("请指定用户名 (-u)"))?,
+                    port.unwrap_or(22),
+                ),
+            };
+
+            let auth = match auth_type.as_str() {
+                _ if matches!(connection_type, ConnectionType::Serial { .. } | ConnectionType::Telnet) => AuthType::Agent,
+                "password" => {
                     let pwd = auth_data.ok_or_else(|| anyhow::anyhow!("使用密码认证时必须提供密码"))?;
                     AuthType::Password(pwd)
                 },
@@ -442,8 +1670,16 @@ pub fn run() -> Result<()> {
                 "agent" => AuthType::Agent,
                 _ => return Err(anyhow::anyhow!("未知的认证类型: {}", auth_type)),
             };
-            
-            let server = ServerConfig::new(
+
+            let uses_reference = match &auth {
+                AuthType::Password(value) | AuthType::Key(value) => is_secret_reference(value),
+                AuthType::Agent => false,
+            } || password.as_deref().map(is_secret_reference).unwrap_or(false);
+            if uses_reference || secret_provider.is_some() {
+                println!("检测到密钥引用，将在连接时解析");
+            }
+
+            let mut server = ServerConfig::new(
                 Uuid::new_v4().to_string(),
                 name,
                 host,
@@ -454,12 +1690,39 @@ pub fn run() -> Result<()> {
                 description,
                 password,
             );
+            server.secret_provider = secret_provider;
+            server.connection_type = connection_type;
+            server.compression = compress;
+            server.algorithms = Algorithms { ciphers, kex, macs, host_key: host_key_algorithms };
+            server.host_key_policy = host_key_policy;
+            server.login_banner = login_banner;
+            server.note = note;
+            server.forward_agent = forward_agent;
+            server.alt_hosts = alt_hosts;
+            server.internal_host = internal_host;
+            server.network = network;
+            server.jump_host = jump_host;
+            if let Some(proxy_jump) = proxy_jump {
+                server.proxy_jump = Some(find_server(&config_manager, &proxy_jump)?.name);
+            }
+            server.command_policy = CommandPolicy { allow: allow_commands, deny: deny_commands };
+            server.command_policy.validate()?;
+            server.prompts = PromptPatterns { password: password_prompt, ready: ready_prompt };
+            server.login_dialog = login_dialog.iter().map(|spec| DialogStep::parse(spec)).collect::<Result<_>>()?;
+            server.identity_files = identity_files;
+            server.identities_only = identities_only;
+            server.anti_idle = anti_idle;
+            for key_path in server.identities() {
+                if !is_secret_reference(key_path) {
+                    key_permissions::ensure(key_path, fix_perms)?;
+                }
+            }
             
             config_manager.add_server(server)?;
             println!("服务器添加成功");
         },
         
-        Commands::List { group } => {
+        Commands::List { group, no_tui } => {
             let mut servers = config_manager.list_servers()?;
             
             servers.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
@@ -471,34 +1734,327 @@ pub fn run() -> Result<()> {
                 servers
             };
 
-            enable_raw_mode()?;
-            let mut stdout = stdout();
-            execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-            let backend = CrosstermBackend::new(stdout);
-            let mut terminal = Terminal::new(backend)?;
-
-            let selected_server_option = run_list_tui(&mut terminal, filtered_servers, group)?;
+            // 未指定 --output 时：--no-tui 打印表格，输出到管道时每行一台，便于 grep / cut 处理
+            let format = output::format().or(if !io::stdout().is_terminal() {
+                Some(OutputFormat::Plain)
+            } else if no_tui {
+                Some(OutputFormat::Table)
+            } else {
+                None
+            });
+            match format {
+                Some(OutputFormat::Json) => {
+                    return output::print_json(&filtered_servers.iter().map(plugin::server_json).collect::<Vec<_>>());
+                }
+                Some(OutputFormat::Table) => {
+                    return output::print(&output::server_table(&filtered_servers.iter().collect::<Vec<_>>()));
+                }
+                Some(OutputFormat::Plain) => {
+                    let lines: String = filtered_servers.iter().map(|s| format!("{}\n", picker::server_line(s))).collect();
+                    return output::print(&lines);
+                }
+                None => {}
+            }
 
-            disable_raw_mode()?;
-            execute!(
-                terminal.backend_mut(),
-                LeaveAlternateScreen,
-                DisableMouseCapture
-            )?;
-            terminal.show_cursor()?;
+            let selected_server_option = select_server_tui(filtered_servers, group)?;
 
             if let Some(server_to_connect) = selected_server_option {
                 println!("准备连接到选中的服务器: {}", server_to_connect.name.clone().green());
-                connect_via_system_ssh(&server_to_connect, false, true, true)?;
+                let mut server_config = server_to_connect.with_resolved_secrets()?;
+                audit::record(&server_config, "connect", None)?;
+                hooks::on_connect(&server_config)?;
+                show_connect_banner(&server_config, &settings);
+                authorize_agent_forwarding(&mut server_config)?;
+                connect_server(&server_config, false)?;
             } else {
                 println!("已退出列表视图。");
             }
         },
 
-        Commands::Connect { server, no_mux } => {
-            let server_config = find_server(&config_manager, &server)?;
+        Commands::Connect { server, no_mux, group, each_window, new_window, mode, compress, rzsz, read_only, command, ssh_verbose, fix_perms, local_forward, via } => {
+            enable_ssh_verbose(ssh_verbose);
+            if let Some(group) = group {
+                if !each_window {
+                    return Err(anyhow::anyhow!("连接整个分组需要指定 --each-window"));
+                }
+                let session_config = group_session(&config_manager, &group)?;
+                println!("将为分组 {} 的 {} 台服务器各打开一个窗口",
+                    group.bright_green(), session_config.windows.len());
+                launch_session(&config_manager, &session_config, SessionBackend::Detect)?;
+                return Ok(());
+            }
+
+            let mut server = match server {
+                Some(server) => server,
+                None => workspace_server(&config_manager)?,
+            };
+            let (mut mode, mut no_mux, mut compress, mut rzsz, mut read_only) = (mode, no_mux, compress, rzsz, read_only);
+            if server == "-" {
+                let last = HistoryStore::new(get_db_path()?)?
+                    .last()?
+                    .ok_or_else(|| anyhow::anyhow!("还没有连接记录"))?;
+                server = last.server_id;
+                // 命令行上显式指定的选项优先
+                if mode.is_none() && !rzsz {
+                    mode = last.mode.as_deref().and_then(|m| ConnectionMode::from_str(m, true).ok());
+                    rzsz = last.rzsz;
+                }
+                no_mux |= last.no_mux;
+                compress |= last.compress;
+                read_only |= last.read_only;
+            }
+
+            if new_window {
+                let target = find_server(&config_manager, &server)?;
+                connect_in_new_window(&target, no_mux)?;
+                return Ok(());
+            }
+
+            let mut target = find_server(&config_manager, &server)?;
+            if let Some(via) = via {
+                target.proxy_jump = Some(find_server(&config_manager, &via)?.name);
+                target.jump_host = None;
+            }
+            let mut server_config = target.with_resolved_secrets()?;
+            server_config.compression |= compress;
+            if let AuthType::Key(key_path) = &server_config.auth_type {
+                key_permissions::ensure(key_path, fix_perms)?;
+            }
+            if !local_forward.is_empty() {
+                if server_config.connection_type != ConnectionType::Ssh || mode == Some(ConnectionMode::Telnet) {
+                    return Err(anyhow::anyhow!("只有 SSH 连接支持端口转发"));
+                }
+                let mut forwards = local_forward
+                    .iter()
+                    .map(|spec| Forward::parse(ForwardKind::Local, spec))
+                    .collect::<Result<Vec<_>>>()?;
+                tunnel::check_local_ports(&mut forwards, true, &list_active_sessions(&get_run_dir()?)?)?;
+                server_config.local_forwards = forwards;
+            }
+            audit::record(&server_config, "connect", None)?;
+            hooks::on_connect(&server_config)?;
+            println!("准备连接到服务器: {}", server_config.name.clone().green());
+            show_connect_banner(&server_config, &settings);
+            authorize_agent_forwarding(&mut server_config)?;
+            if server_config.connection_type == ConnectionType::Ssh {
+                // 系统 ssh、russh 自己建立连接，先选出能连上的地址
+                server_config = address_fallback::with_reachable_host(&server_config)?;
+            }
+            if mode.is_none() && !rzsz && server_config.connection_type == ConnectionType::Ssh {
+                mode = default_connect_mode(&settings)?;
+            }
+            if command.is_empty() {
+                HistoryStore::new(get_db_path()?)?.record(&HistoryEntry {
+                    server_id: server_config.id.clone(),
+                    mode: mode.and_then(|m| m.to_possible_value()).map(|v| v.get_name().to_string()),
+                    no_mux,
+                    compress,
+                    rzsz,
+                    read_only,
+                    connected_at: chrono::Utc::now().timestamp(),
+                })?;
+            }
+            if read_only {
+                if mode.is_some_and(|mode| !matches!(mode, ConnectionMode::Library | ConnectionMode::Debug)) {
+                    println!("{} 只读会话需要在本地过滤键盘输入，改用内置库连接", "⚠".bright_yellow());
+                }
+                let mut client = library_client(&server_config)?.read_only();
+                if !command.is_empty() {
+                    client = client.shell_command(command.join(" "));
+                }
+                client.start_shell(register_session(&server_config, "library"))?;
+                return Ok(());
+            }
+            if !command.is_empty() {
+                let code = run_with_mode(&server_config, mode, command.join(" "))?;
+                if code != 0 {
+                    std::process::exit(code);
+                }
+                return Ok(());
+            }
+            match mode {
+                Some(mode) => connect_with_mode(&server_config, mode, no_mux)?,
+                None if rzsz => {
+                    connect_via_system_ssh(&server_config, true, true, !no_mux)?;
+                }
+                None => {
+                    connect_server(&server_config, no_mux)?;
+                }
+            }
+            // 系统 ssh 经 expect 连接时 rssh 进程已被替换，不会执行到这里
+            siem::emit(&server_config, "disconnect", None);
+        },
+
+        Commands::Last => unreachable!("rssh last 已转换为 rssh connect -"),
+
+        Commands::Shell { server, compress, record, ssh_verbose } => {
+            enable_ssh_verbose(ssh_verbose);
+            let mut server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+            server_config.compression |= compress;
+            audit::record(&server_config, "connect", None)?;
+            hooks::on_connect(&server_config)?;
             println!("准备连接到服务器: {}", server_config.name.clone().green());
-            connect_via_system_ssh(&server_config, false, true, !no_mux)?;
+            show_connect_banner(&server_config, &settings);
+            authorize_agent_forwarding(&mut server_config)?;
+            match record.map(recording_path).transpose()? {
+                Some(path) => {
+                    println!("会话录制到 {}，行首输入 ~M 添加标记", path.display());
+                    library_client(&server_config)?
+                        .record_to(path.clone(), &server_config.name)
+                        .start_shell(register_session(&server_config, "library"))?;
+                    println!("回放: rssh replay {}", path.display());
+                }
+                None => connect_with_mode(&server_config, ConnectionMode::Library, false)?,
+            }
+        },
+
+        Commands::Replay { file, markers, from, speed, max_idle } => {
+            let file = if file.exists() { file } else { recording_path(file)? };
+            let cast = Cast::load(&file)?;
+            if markers {
+                let list = cast.markers();
+                if list.is_empty() {
+                    println!("录制中没有标记");
+                }
+                for (number, (_, marker)) in list.iter().enumerate() {
+                    println!("{:>3}  {}  {}", number + 1, format_offset(marker.time).bright_cyan(), marker.data);
+                }
+                return Ok(());
+            }
+            if speed <= 0.0 {
+                return Err(anyhow::anyhow!("--speed 必须大于 0"));
+            }
+            let start = match from {
+                Some(query) => cast.find_marker(&query)?,
+                None => 0,
+            };
+            let header = &cast.header;
+            println!("回放 {}（录制时终端大小 {}x{}）", header.title.as_deref().unwrap_or("会话"), header.width, header.height);
+            recording::replay(&cast, start, speed, max_idle.max(0.0))?;
+            println!("\r\n回放结束");
+        },
+
+        Commands::Pick { query, group, list, print_command, new_window } => {
+            let servers: Vec<ServerConfig> = config_manager
+                .list_servers()?
+                .into_iter()
+                .filter(|s| group.is_none() || s.group == group)
+                .collect();
+
+            if list {
+                for server in &servers {
+                    println!("{}", picker::server_line(server));
+                }
+                return Ok(());
+            }
+
+            let exact = query
+                .as_deref()
+                .and_then(|q| servers.iter().find(|s| s.name == q || s.id == q))
+                .cloned();
+
+            let selected = match exact {
+                Some(s) => Some(s),
+                None if picker::fzf_available() => {
+                    picker::pick_with_fzf(&servers, query.as_deref())?.cloned()
+                }
+                None => select_server_tui(servers, group)?,
+            };
+
+            let Some(selected) = selected else {
+                return Ok(());
+            };
+
+            if print_command {
+                println!("{}", ssh_command_string(&selected));
+            } else if new_window {
+                connect_in_new_window(&selected, false)?;
+            } else {
+                let mut server_config = selected.with_resolved_secrets()?;
+                audit::record(&server_config, "connect", None)?;
+                hooks::on_connect(&server_config)?;
+                show_connect_banner(&server_config, &settings);
+                authorize_agent_forwarding(&mut server_config)?;
+                connect_server(&server_config, false)?;
+            }
+        },
+
+        Commands::Plugin { action } => match action {
+            PluginAction::List => {
+                let plugins = plugin::discover();
+                if plugins.is_empty() {
+                    println!("PATH 中没有找到插件（可执行文件名需以 {} 开头）", plugin::PREFIX);
+                }
+                for (name, path) in plugins {
+                    println!("{:<20} {}", name.bright_green(), path.display());
+                }
+            },
+            PluginAction::Inventory { group } => {
+                let servers: Vec<ServerConfig> = config_manager
+                    .list_servers()?
+                    .into_iter()
+                    .filter(|s| group.is_none() || s.group == group)
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&plugin::inventory(&servers))?);
+            },
+        },
+
+        Commands::External(args) => {
+            let name = args[0].to_string_lossy().into_owned();
+            let path = plugin::find(&name).ok_or_else(|| {
+                anyhow::anyhow!("未知命令: {}（PATH 中也没有插件 {}{}，运行 rssh --help 查看可用命令）", name, plugin::PREFIX, name)
+            })?;
+            let code = plugin::run(&path, &args[1..], &get_db_path()?)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        },
+
+        Commands::Quickstart => run_quickstart(&config_manager)?,
+
+        Commands::Completions { shell, install } => {
+            let shell = shell
+                .or_else(Shell::detect)
+                .ok_or_else(|| anyhow::anyhow!("无法根据 $SHELL 判断当前的 shell，请指定 bash / zsh / fish"))?;
+            if install {
+                let path = completions::install(&Cli::command(), shell)?;
+                println!("{} 已安装 {} 补全: {}", "✓".bright_green(), shell, path.display());
+            } else {
+                output::print(&completions::generate(&Cli::command(), shell))?;
+            }
+        },
+
+        Commands::Clean { dry_run } => {
+            let report = retention::clean(&settings.retention, dry_run)?;
+            let verb = if dry_run { "将删除" } else { "已删除" };
+            for path in &report.recordings {
+                println!("{} {}", verb, path.display());
+            }
+            println!("会话录制: {} {} 个文件 ({:.1} MB)", verb, report.recordings.len(), report.recording_bytes as f64 / 1048576.0);
+            if report.debug_log_bytes > 0 {
+                let verb = if dry_run { "将轮转" } else { "已轮转" };
+                println!("调试日志: {} ({:.1} MB)", verb, report.debug_log_bytes as f64 / 1048576.0);
+            }
+            println!("批量任务: {} {} 个", verb, report.jobs);
+        },
+
+        Commands::Integrate { target } => match target {
+            IntegrateTarget::Tmux { key } => {
+                let rssh_path = std::env::current_exe()
+                    .with_context(|| "无法获取当前rssh可执行文件路径")?;
+                let conf_path = picker::install_tmux_binding(&rssh_path, &key)?;
+                println!("已在 {} 中安装快捷键: prefix + {}", conf_path.display(), key.bright_green());
+
+                if std::env::var("TMUX").is_ok() {
+                    let _ = Command::new("tmux")
+                        .arg("source-file")
+                        .arg(&conf_path)
+                        .status();
+                    println!("已重新加载 tmux 配置");
+                } else {
+                    println!("请在 tmux 中执行 `tmux source-file {}` 使其生效", conf_path.display());
+                }
+            }
         },
 
         Commands::Remove { server } => {
@@ -533,17 +2089,84 @@ pub fn run() -> Result<()> {
             }
         },
         
-        Commands::Edit { server } => {
-            let server_config = config_manager.get_server(&server)?;
-            
-            let server_config = if server_config.is_none() {
+        Commands::Alias { action } => match action {
+            AliasAction::Add { alias, server } => {
+                if alias.is_empty() || alias.contains([',', ' ']) || is_ssh_uri(&alias) {
+                    return Err(anyhow::anyhow!("别名不能为空，也不能包含逗号、空格或写成 ssh:// URI: {}", alias));
+                }
+                if let Some(existing) = config_manager.find_server(&alias)? {
+                    return Err(anyhow::anyhow!("{} 已经是服务器 {} 的名称、ID 或别名", alias, existing.name));
+                }
+                let mut server_config = config_manager
+                    .find_server(&server)?
+                    .ok_or_else(|| anyhow::anyhow!("找不到指定的服务器: {}", server))?;
+                server_config.aliases.push(alias.clone());
+                config_manager.update_server(server_config.clone())?;
+                println!("已添加别名 {} -> {}", alias.bright_green(), server_config.name);
+            },
+            AliasAction::Remove { alias } => {
+                let mut server_config = config_manager
+                    .list_servers()?
+                    .into_iter()
+                    .find(|s| s.aliases.contains(&alias))
+                    .ok_or_else(|| anyhow::anyhow!("没有这个别名: {}", alias))?;
+                server_config.aliases.retain(|a| *a != alias);
+                config_manager.update_server(server_config.clone())?;
+                println!("已删除 {} 的别名 {}", server_config.name, alias);
+            },
+            AliasAction::List => {
                 let servers = config_manager.list_servers()?;
-                servers.into_iter().find(|s| s.name == server)
-            } else {
-                server_config
-            };
-            
-            let mut server_config = match server_config {
+                let aliases: Vec<(&String, &String)> =
+                    servers.iter().flat_map(|s| s.aliases.iter().map(move |alias| (alias, &s.name))).collect();
+                if aliases.is_empty() {
+                    println!("还没有别名，使用 {} 添加", "rssh alias add <别名> <服务器>".bright_blue());
+                }
+                let width = aliases.iter().map(|(alias, _)| alias.chars().count()).max().unwrap_or(0);
+                for (alias, name) in aliases {
+                    println!("{:<width$}  -> {}", alias.bright_green(), name, width = width);
+                }
+            },
+        },
+
+        Commands::Slot { action } => match action {
+            SlotAction::Set { slot, server } => {
+                let mut server_config = config_manager
+                    .find_server(&server)?
+                    .ok_or_else(|| anyhow::anyhow!("找不到指定的服务器: {}", server))?;
+                if let Some(mut previous) = config_manager.find_by_slot(slot)?.filter(|s| s.id != server_config.id) {
+                    previous.slot = None;
+                    config_manager.update_server(previous.clone())?;
+                    println!("{} 编号 {} 原先分配给 {}，已移走", "⚠".bright_yellow(), slot, previous.name);
+                }
+                server_config.slot = Some(slot);
+                config_manager.update_server(server_config.clone())?;
+                println!("已将编号 {} 分配给 {}，使用 {} 连接", slot.to_string().bright_green(), server_config.name,
+                    format!("rssh {}", slot).bright_blue());
+            },
+            SlotAction::Clear { slot } => {
+                let mut server_config = config_manager
+                    .find_by_slot(slot)?
+                    .ok_or_else(|| anyhow::anyhow!("编号 {} 没有分配给任何服务器", slot))?;
+                server_config.slot = None;
+                config_manager.update_server(server_config.clone())?;
+                println!("已清除 {} 的编号 {}", server_config.name, slot);
+            },
+            SlotAction::List => {
+                let mut servers: Vec<ServerConfig> =
+                    config_manager.list_servers()?.into_iter().filter(|s| s.slot.is_some()).collect();
+                if servers.is_empty() {
+                    println!("还没有快捷编号，使用 {} 分配", "rssh slot set <编号> <服务器>".bright_blue());
+                }
+                servers.sort_by_key(|s| s.slot);
+                for server in servers {
+                    println!("{:>2}  {} ({}@{})", server.slot.unwrap_or_default().to_string().bright_green(),
+                        server.name, server.username, server.host);
+                }
+            },
+        },
+
+        Commands::Edit { server } => {
+            let mut server_config = match config_manager.find_server(&server)? {
                 Some(s) => s,
                 None => return Err(anyhow::anyhow!("找不到指定的服务器: {}", server)),
             };
@@ -660,141 +2283,753 @@ pub fn run() -> Result<()> {
             } else {
                 server_config.description = Some(input.trim().to_string());
             }
-            
-            if config_manager.update_server(server_config)? {
-                println!("服务器更新成功");
-            } else {
-                println!("服务器更新失败");
+
+            let compression = if server_config.compression { "y" } else { "n" };
+            print!("启用压缩 [{}] (y/n): ", compression.bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            match input.trim().to_lowercase().as_str() {
+                "" => {},
+                "y" | "yes" => server_config.compression = true,
+                "n" | "no" => server_config.compression = false,
+                _ => println!("输入无效，保持不变"),
             }
-        },
-        
-        Commands::Upload { server, local_path, remote_path, mode } => {
-            let server_config = config_manager.get_server(&server)?;
-            
-            let server_config = if server_config.is_none() {
-                let servers = config_manager.list_servers()?;
-                servers.into_iter().find(|s| s.name == server)
-            } else {
-                server_config
-            };
-            
-            let server_config = match server_config {
-                Some(s) => s,
-                None => return Err(anyhow::anyhow!("找不到指定的服务器: {}", server)),
-            };
-            
-            println!("准备上传文件到 {}@{}:{}...", 
-                server_config.username.bright_yellow(), 
-                server_config.host.bright_green(), 
-                server_config.port.to_string().bright_blue()
-            );
-            
-            match mode {
-                TransferMode::Scp => {
-                    crate::utils::upload_file(&server_config, &local_path, remote_path)?;
-                },
-                TransferMode::Sftp => {
-                    crate::utils::upload_file_sftp(&server_config, &local_path, remote_path)?;
+
+            let policy = server_config.host_key_policy.map(|p| p.name()).unwrap_or("全局设置");
+            print!("主机密钥策略 [{}] (strict/accept-new/off/default): ", policy.bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            match input.trim() {
+                "" => {},
+                "default" | "none" | "无" => server_config.host_key_policy = None,
+                value => match HostKeyPolicy::parse(value) {
+                    Ok(policy) => server_config.host_key_policy = Some(policy),
+                    Err(e) => println!("{}，保持不变", e),
                 },
-                TransferMode::Auto => {
-                    crate::utils::upload_file_auto(&server_config, &local_path, remote_path)?;
+            }
+
+            print!("登录横幅 [{}] (show/quiet/capture): ", server_config.login_banner.name().bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if !input.trim().is_empty() {
+                match LoginBanner::parse(input.trim()) {
+                    Ok(banner) => server_config.login_banner = banner,
+                    Err(e) => println!("{}，保持不变", e),
                 }
             }
-        },
-        
-        Commands::Download { server, remote_path, local_path, mode } => {
-            let server_config = config_manager.get_server(&server)?;
-            
-            let server_config = if server_config.is_none() {
-                let servers = config_manager.list_servers()?;
-                servers.into_iter().find(|s| s.name == server)
+
+            let alt_hosts = if server_config.alt_hosts.is_empty() { "无".to_string() } else { server_config.alt_hosts.join(",") };
+            print!("备用地址 [{}] (逗号分隔): ", alt_hosts.bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().is_empty() {
+            } else if input.trim() == "无" || input.trim() == "none" {
+                server_config.alt_hosts.clear();
             } else {
-                server_config
-            };
-            
-            let server_config = match server_config {
-                Some(s) => s,
-                None => return Err(anyhow::anyhow!("找不到指定的服务器: {}", server)),
-            };
-            
-            println!("准备从 {}@{}:{} 下载文件...", 
-                server_config.username.bright_yellow(), 
-                server_config.host.bright_green(), 
-                server_config.port.to_string().bright_blue()
-            );
-            
-            match mode {
-                TransferMode::Scp => {
-                    crate::utils::download_file(&server_config, &remote_path, local_path)?;
-                },
-                TransferMode::Sftp => {
-                    crate::utils::download_file_sftp(&server_config, &remote_path, local_path)?;
-                },
-                TransferMode::Auto => {
-                    crate::utils::download_file_auto(&server_config, &remote_path, local_path)?;
-                }
+                server_config.alt_hosts = input.trim().split(',').map(|h| h.trim().to_string()).filter(|h| !h.is_empty()).collect();
             }
-        },
-        
-        Commands::Import { config, group, skip_existing } => {
-            let config_path = match config {
-                Some(path) => path,
-                None => {
-                    let mut home = dirs::home_dir()
-                        .ok_or_else(|| anyhow::anyhow!("无法确定用户主目录"))?;
-                    home.push(".ssh");
-                    home.push("config");
-                    home
+
+            for (label, field) in [
+                ("内网地址", &mut server_config.internal_host),
+                ("网络位置", &mut server_config.network),
+                ("跳板机", &mut server_config.jump_host),
+                ("跳板服务器", &mut server_config.proxy_jump),
+            ] {
+                print!("{} [{}]: ", label, field.as_deref().unwrap_or("无").bright_green());
+                io::stdout().flush()?;
+                input.clear();
+                io::stdin().read_line(&mut input)?;
+                if input.trim().is_empty() {
+                } else if input.trim() == "无" || input.trim() == "none" {
+                    *field = None;
+                } else {
+                    *field = Some(input.trim().to_string());
                 }
-            };
-            
-            if !config_path.exists() {
-                return Err(anyhow::anyhow!("找不到 SSH 配置文件: {}", config_path.display()));
             }
-            
-            println!("从 {} 导入服务器配置...", config_path.display());
-            
-            let mut configs = import_ssh_config(&config_path)?;
-            
-            if let Some(ref g) = group {
-                for config in &mut configs {
-                    config.group = Some(g.clone());
+
+            let note = server_config.note.clone().unwrap_or_else(|| "无".to_string());
+            print!("连接提示 [{}]: ", note.bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().is_empty() {
+            } else if input.trim() == "无" || input.trim() == "none" {
+                server_config.note = None;
+            } else {
+                server_config.note = Some(input.trim().to_string());
+            }
+
+            print!("SSH agent 转发 [{}] (no/yes/confirm): ", server_config.forward_agent.name().bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if !input.trim().is_empty() {
+                match ForwardAgent::parse(input.trim()) {
+                    Ok(forward) => server_config.forward_agent = forward,
+                    Err(e) => println!("{}，保持不变", e),
                 }
             }
-            
-            let existing_servers = if skip_existing {
-                config_manager.list_servers()?
-            } else {
-                Vec::new()
-            };
-            
-            let mut imported = 0;
-            let mut skipped = 0;
-            
-            for server_config in configs {
-                if skip_existing && existing_servers.iter().any(|s| 
-                    s.name == server_config.name || 
-                    (s.host == server_config.host && 
-                     s.port == server_config.port && 
-                     s.username == server_config.username)) {
-                    skipped += 1;
-                    continue;
+
+            print!("修改算法偏好 (加密/密钥交换/MAC/主机密钥)？[y/N] ");
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().eq_ignore_ascii_case("y") {
+                println!("使用 OpenSSH 写法（+ 追加、- 移除、^ 置前），输入 none 恢复默认");
+                let algorithms = &mut server_config.algorithms;
+                for (label, value) in [
+                    ("加密算法", &mut algorithms.ciphers),
+                    ("密钥交换算法", &mut algorithms.kex),
+                    ("MAC 算法", &mut algorithms.macs),
+                    ("主机密钥算法", &mut algorithms.host_key),
+                ] {
+                    print!("{} [{}]: ", label, value.as_deref().unwrap_or("默认").bright_green());
+                    io::stdout().flush()?;
+                    input.clear();
+                    io::stdin().read_line(&mut input)?;
+                    match input.trim() {
+                        "" => {},
+                        "none" | "无" => *value = None,
+                        spec => *value = Some(spec.to_string()),
+                    }
                 }
-                
-                config_manager.add_server(server_config)?;
-                imported += 1;
             }
-            
-            println!("导入完成! 已导入 {} 个服务器, 跳过 {} 个已存在的服务器。", 
-                imported.to_string().bright_green(), 
-                skipped.to_string().bright_yellow()
-            );
-        },
-        
-        Commands::Export { path } => {
-            config_manager.export_config(&path)?;
-            println!("配置已导出到: {}", path.display());
-        },
+
+            let provider = server_config.secret_provider.as_deref().unwrap_or("无");
+            print!("密钥提供者 [{}] ({}): ", provider.bright_green(), provider_names());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().is_empty() {
+            } else if input.trim() == "无" || input.trim() == "none" {
+                server_config.secret_provider = None;
+            } else if find_provider(input.trim()).is_some() {
+                server_config.secret_provider = Some(input.trim().to_string());
+            } else {
+                println!("未知密钥提供者，保持不变");
+            }
+
+            print!(
+                "修改命令规则 (允许 {} 条 / 禁止 {} 条)？[y/N] ",
+                server_config.command_policy.allow.len(),
+                server_config.command_policy.deny.len()
+            );
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().eq_ignore_ascii_case("y") {
+                println!("每行一个正则表达式（匹配整条命令），空行结束；不输入任何规则表示不限制");
+                let mut policy = CommandPolicy::default();
+                for (label, patterns) in [("允许执行的命令", &mut policy.allow), ("禁止执行的命令", &mut policy.deny)] {
+                    println!("{}:", label);
+                    loop {
+                        input.clear();
+                        io::stdin().read_line(&mut input)?;
+                        if input.trim().is_empty() {
+                            break;
+                        }
+                        patterns.push(input.trim().to_string());
+                    }
+                }
+                match policy.validate() {
+                    Ok(()) => server_config.command_policy = policy,
+                    Err(e) => println!("{}，保持不变", e),
+                }
+            }
+
+            // expect 自动填密码时匹配的提示，输入 none 恢复默认
+            let prompts = &mut server_config.prompts;
+            for (label, value) in [("密码提示", &mut prompts.password), ("就绪提示", &mut prompts.ready)] {
+                print!("{} [{}]: ", label, value.as_deref().unwrap_or("默认").bright_green());
+                io::stdout().flush()?;
+                input.clear();
+                io::stdin().read_line(&mut input)?;
+                match input.trim() {
+                    "" => {},
+                    "none" | "无" => *value = None,
+                    pattern => *value = Some(pattern.to_string()),
+                }
+            }
+
+            let files = if server_config.identity_files.is_empty() {
+                "无".to_string()
+            } else {
+                server_config.identity_files.join(", ")
+            };
+            print!("额外私钥（逗号分隔） [{}]: ", files.bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            match input.trim() {
+                "" => {},
+                "none" | "无" => server_config.identity_files.clear(),
+                files => server_config.identity_files = files.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect(),
+            }
+
+            let identities_only = if server_config.identities_only { "y" } else { "n" };
+            print!("只使用配置的私钥 IdentitiesOnly [{}] (y/n): ", identities_only.bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            match input.trim().to_lowercase().as_str() {
+                "" => {},
+                "y" | "yes" => server_config.identities_only = true,
+                "n" | "no" => server_config.identities_only = false,
+                _ => println!("输入无效，保持不变"),
+            }
+
+            let anti_idle = server_config.anti_idle.map_or("无".to_string(), |anti_idle| anti_idle.to_string());
+            print!("防空闲 [{}] (<秒>[:keepalive|resize|nul]，none 关闭): ", anti_idle.bright_green());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().is_empty() {
+            } else if input.trim() == "无" || input.trim() == "none" {
+                server_config.anti_idle = None;
+            } else {
+                match AntiIdle::parse(input.trim()) {
+                    Ok(anti_idle) => server_config.anti_idle = Some(anti_idle),
+                    Err(e) => println!("{}，保持不变", e),
+                }
+            }
+
+            print!("修改登录对话 ({} 步)？[y/N] ", server_config.login_dialog.len());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().eq_ignore_ascii_case("y") {
+                for step in &server_config.login_dialog {
+                    println!("  {}", step);
+                }
+                println!("每行一步 `<正则> => <发送内容>`，空行结束；不输入任何一步表示清空");
+                let mut steps = Vec::new();
+                loop {
+                    input.clear();
+                    io::stdin().read_line(&mut input)?;
+                    if input.trim().is_empty() {
+                        break;
+                    }
+                    match DialogStep::parse(input.trim()) {
+                        Ok(step) => steps.push(step),
+                        Err(e) => println!("{}，忽略这一行", e),
+                    }
+                }
+                server_config.login_dialog = steps;
+            }
+            
+            if config_manager.update_server(server_config)? {
+                println!("服务器更新成功");
+            } else {
+                println!("服务器更新失败");
+            }
+        },
+        
+        Commands::Upload { server, local_path, remote_path, group, parallel, yes, mode, compress, verbose, notify, ssh_verbose, delete, exclude } => {
+            enable_ssh_verbose(ssh_verbose);
+            let notify = notify_targets(&notify, &settings)?;
+            let rsync_options = rsync_options(mode, delete, exclude)?;
+            if let Some(group) = group {
+                if remote_path.is_some() {
+                    return Err(anyhow::anyhow!("分组上传的参数为: rssh upload --group <分组> <本地文件> [远程路径]"));
+                }
+                // 省略了服务器，位置参数整体前移
+                let file = PathBuf::from(&server);
+                let remote_path = local_path.map(|path| path.display().to_string()).unwrap_or_else(|| "./".to_string());
+
+                let servers: Vec<ServerConfig> = config_manager
+                    .list_servers()?
+                    .into_iter()
+                    .filter(|s| s.group.as_deref() == Some(group.as_str()))
+                    .map(|s| {
+                        let mut s = s.with_resolved_secrets()?;
+                        s.compression |= compress;
+                        Ok(s)
+                    })
+                    .collect::<Result<_>>()?;
+                if servers.is_empty() {
+                    return Err(anyhow::anyhow!("分组 {} 中没有服务器", group));
+                }
+
+                let action = format!("上传 {} 到 {}", file.display(), remote_path);
+                let settings = Settings::load()?;
+                if !fleet::confirm_targets(&servers, Some(&group), &action, settings.confirm_threshold(), yes)? {
+                    println!("已取消");
+                    return Ok(());
+                }
+                for server in &servers {
+                    audit::record(server, "upload", Some(&file.display().to_string()))?;
+                }
+                let ok = notify_when_done(&notify, format!("upload -g {} {}", group, file.display()), || {
+                    let uploads = multi_upload::upload_to_hosts(&servers, &file, &remote_path, parallel)?;
+                    multi_upload::print_summary(&uploads);
+                    Ok(uploads.iter().all(|upload| upload.success()))
+                })?;
+                if !ok {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            let local_path = local_path.ok_or_else(|| anyhow::anyhow!("请指定本地文件"))?;
+            let mut server_config = match config_manager.find_server(&server)? {
+                Some(s) => s.with_resolved_secrets()?,
+                None => return Err(anyhow::anyhow!("找不到指定的服务器: {}", server)),
+            };
+            server_config.compression |= compress;
+            audit::record(&server_config, "upload", Some(&local_path.display().to_string()))?;
+            
+            println!("准备上传文件到 {}@{}:{}...", 
+                server_config.username.bright_yellow(), 
+                server_config.host.bright_green(), 
+                server_config.port.to_string().bright_blue()
+            );
+            
+            notify_when_done(&notify, format!("upload {} {}", server_config.name, local_path.display()), || {
+                match mode {
+                    TransferMode::Scp => {
+                        crate::utils::upload_file(&server_config, &local_path, remote_path)?;
+                    },
+                    TransferMode::Sftp => {
+                        crate::utils::upload_file_sftp(&server_config, &local_path, remote_path)?;
+                    },
+                    TransferMode::Rsync => {
+                        crate::utils::upload_file_rsync(&server_config, &local_path, remote_path, &rsync_options)?;
+                    },
+                    TransferMode::Auto => {
+                        crate::utils::upload_file_auto(&server_config, &local_path, remote_path, verbose)?;
+                    }
+                }
+                Ok(true)
+            })?;
+        },
+        
+        Commands::Download { server, remote_path, local_path, mode, compress, verbose, notify, ssh_verbose, delete, exclude } => {
+            enable_ssh_verbose(ssh_verbose);
+            let notify = notify_targets(&notify, &settings)?;
+            let rsync_options = rsync_options(mode, delete, exclude)?;
+            let mut server_config = match config_manager.find_server(&server)? {
+                Some(s) => s.with_resolved_secrets()?,
+                None => return Err(anyhow::anyhow!("找不到指定的服务器: {}", server)),
+            };
+            server_config.compression |= compress;
+            audit::record(&server_config, "download", Some(&remote_path))?;
+            
+            println!("准备从 {}@{}:{} 下载文件...", 
+                server_config.username.bright_yellow(), 
+                server_config.host.bright_green(), 
+                server_config.port.to_string().bright_blue()
+            );
+            
+            notify_when_done(&notify, format!("download {}:{}", server_config.name, remote_path), || {
+                match mode {
+                    TransferMode::Scp => {
+                        crate::utils::download_file(&server_config, &remote_path, local_path)?;
+                    },
+                    TransferMode::Sftp => {
+                        crate::utils::download_file_sftp(&server_config, &remote_path, local_path)?;
+                    },
+                    TransferMode::Rsync => {
+                        crate::utils::download_file_rsync(&server_config, &remote_path, local_path, &rsync_options)?;
+                    },
+                    TransferMode::Auto => {
+                        crate::utils::download_file_auto(&server_config, &remote_path, local_path, verbose)?;
+                    }
+                }
+                Ok(true)
+            })?;
+        },
+        
+        Commands::WatchDownload { server, remote_dir, to, interval, inotify, existing } => {
+            let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+            let options = WatchDownloadOptions {
+                interval: std::time::Duration::from_secs(interval.max(1)),
+                inotify,
+                existing,
+            };
+            watch_download(&server_config, &remote_dir, &to, &options)?;
+        },
+
+        Commands::Exec { server, group, parallel, compress, yes, canary, batch_size, batch_delay, collate, command, notify, override_policy, sudo } => {
+            let command = command.join(" ");
+            let notify = notify_targets(&notify, &settings)?;
+
+            let Some(group) = group else {
+                // 单台服务器：使用系统 ssh 并分配终端，便于 sudo 等交互
+                let server = server.ok_or_else(|| anyhow::anyhow!("请指定服务器或分组"))?;
+                let mut server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+                server_config.compression |= compress;
+                enforce_command_policy(std::slice::from_ref(&server_config), &command, override_policy)?;
+                if sudo {
+                    // sudo 的密码经标准输入发送，使用内置库执行，不分配终端
+                    let sudo = Sudo::prepare(std::slice::from_ref(&server_config))?;
+                    audit::record(&server_config, "exec", Some(&format!("sudo {}", command)))?;
+                    let mut code = 0;
+                    notify_when_done(&notify, format!("exec --sudo {}: {}", server_config.name, command), || {
+                        let result = fleet::run_on_host_as(&server_config, &command, Some(&sudo));
+                        if output::is_json() {
+                            output::print_json(&result.to_json())?;
+                            code = result.exit_code.unwrap_or(255);
+                            return Ok(code == 0);
+                        }
+                        if let Some(error) = &result.error {
+                            return Err(anyhow::anyhow!("{}", error));
+                        }
+                        print!("{}", result.stdout);
+                        eprint!("{}", result.stderr);
+                        code = result.exit_code.unwrap_or(1);
+                        Ok(code == 0)
+                    })?;
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                    return Ok(());
+                }
+                audit::record(&server_config, "exec", Some(&command))?;
+                if output::is_json() {
+                    // 需要捕获输出，改用内置库执行，不分配终端
+                    let result = fleet::run_on_host_as(&server_config, &command, None);
+                    output::print_json(&result.to_json())?;
+                    let code = result.exit_code.unwrap_or(255);
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                    return Ok(());
+                }
+                let mut code = 0;
+                notify_when_done(&notify, format!("exec {}: {}", server_config.name, command), || {
+                    code = connect_via_system_ssh_with_command(&server_config, Some(command.clone()), false, false, false)?;
+                    Ok(code == 0)
+                })?;
+                if code != 0 {
+                    std::process::exit(code);
+                }
+                return Ok(());
+            };
+
+            let servers: Vec<ServerConfig> = config_manager
+                .list_servers()?
+                .into_iter()
+                .filter(|s| s.group.as_deref() == Some(group.as_str()))
+                .map(|mut s| {
+                    s.compression |= compress;
+                    s
+                })
+                .collect();
+            if servers.is_empty() {
+                return Err(anyhow::anyhow!("分组 {} 中没有服务器", group));
+            }
+
+            let settings = Settings::load()?;
+            if !fleet::confirm_targets(&servers, Some(&group), &command, settings.confirm_threshold(), yes)? {
+                println!("已取消");
+                return Ok(());
+            }
+
+            let rollout = fleet::Rollout {
+                canary,
+                batch_size,
+                batch_delay: std::time::Duration::from_secs(batch_delay),
+            };
+            let job = FleetJob { kind: "exec", command: &command, target: Some(&group), parent_id: None, parallel, collate, override_policy, sudo };
+            let ok = notify_when_done(&notify, format!("exec -g {}: {}", group, command), || {
+                run_fleet_job(&servers, &job, &rollout, yes)
+            })?;
+            if !ok {
+                std::process::exit(1);
+            }
+        },
+
+        Commands::Schedule { action } => {
+            let store = ScheduleStore::new(get_db_path()?)?;
+            match action {
+                ScheduleAction::Add { cron, group, parallel, command } => {
+                    Schedule::parse_cron(&cron)?;
+                    let command = command.join(" ");
+                    let servers = config_manager.list_servers()?;
+                    if !servers.iter().any(|s| s.group.as_deref() == Some(group.as_str())) {
+                        println!("{} 分组 {} 中目前没有服务器", "⚠".bright_yellow(), group);
+                    }
+                    let schedule = store.add(&cron, &group, &command, parallel)?;
+                    println!("已添加定时任务 {}", schedule.id.bright_cyan());
+                    println!("下一次执行: {}", schedule.next_run_after(chrono::Local::now())?.format("%Y-%m-%d %H:%M"));
+                    println!("需要运行 {} 才会按时执行", "rssh schedule daemon".bright_blue());
+                },
+
+                ScheduleAction::List => {
+                    let schedules = store.list()?;
+                    if schedules.is_empty() {
+                        println!("没有定时任务");
+                        return Ok(());
+                    }
+
+                    println!("{:<10} {:<16} {:<12} {:<18} {:<22} 命令", "ID", "cron", "分组", "下一次执行", "上次执行");
+                    let now = chrono::Local::now();
+                    for schedule in schedules {
+                        let next = schedule
+                            .next_run_after(now)
+                            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or_else(|_| "-".to_string());
+                        let last = match (schedule.last_run_at, schedule.last_ok) {
+                            (None, _) => "-".normal(),
+                            (Some(at), None) => format!("{} 执行中", format_timestamp(at)).bright_yellow(),
+                            (Some(at), Some(true)) => format!("{} ✓", format_timestamp(at)).bright_green(),
+                            (Some(at), Some(false)) => format!("{} ✗", format_timestamp(at)).bright_red(),
+                        };
+                        println!(
+                            "{:<10} {:<16} {:<12} {:<18} {:<22} {}",
+                            schedule.id.bright_cyan(),
+                            schedule.cron,
+                            schedule.group,
+                            next,
+                            last,
+                            schedule.command
+                        );
+                    }
+                },
+
+                ScheduleAction::Remove { id } => match store.remove(&id)? {
+                    Some(schedule) => println!("已删除定时任务 {}: {}", schedule.id, schedule.command),
+                    None => return Err(anyhow::anyhow!("找不到定时任务: {}", id)),
+                },
+
+                ScheduleAction::Daemon => run_scheduler(&config_manager)?,
+            }
+        },
+
+        Commands::Job { action } => {
+            let store = JobStore::new(get_db_path()?)?;
+            match action {
+                JobAction::List { limit } => {
+                    let jobs = store.list_jobs(limit)?;
+                    if jobs.is_empty() {
+                        println!("没有任务记录");
+                        return Ok(());
+                    }
+
+                    println!("{:<10} {:<20} {:<12} {:<16} 命令", "ID", "开始时间", "分组", "结果");
+                    for job in jobs {
+                        let mut counts = format!("{}✓ {}✗", job.succeeded, job.failed);
+                        if job.pending() > 0 {
+                            counts.push_str(&format!(" {}…", job.pending()));
+                        }
+                        let counts = if job.failed > 0 || job.pending() > 0 { counts.bright_red() } else { counts.bright_green() };
+                        println!(
+                            "{:<10} {:<20} {:<12} {:<16} {}",
+                            job.id.bright_cyan(),
+                            format_timestamp(job.started_at),
+                            job.target.as_deref().unwrap_or("-"),
+                            counts,
+                            job.command
+                        );
+                    }
+                },
+
+                JobAction::Show { id, failed } => {
+                    let job = store.find_job(&id)?.ok_or_else(|| anyhow::anyhow!("找不到任务: {}", id))?;
+
+                    println!("任务: {}", job.id.bright_cyan());
+                    println!("命令: {}", job.command);
+                    if let Some(target) = &job.target {
+                        println!("分组: {}", target);
+                    }
+                    if let Some(parent) = &job.parent_id {
+                        println!("重试自: {}", parent);
+                    }
+                    println!("开始: {}", format_timestamp(job.started_at));
+                    match job.finished_at {
+                        Some(finished) => println!("结束: {}", format_timestamp(finished)),
+                        None => println!("结束: {}", "未完成（已中断）".bright_yellow()),
+                    }
+                    println!(
+                        "结果: {} 成功, {} 失败, {} 未执行",
+                        job.succeeded.to_string().bright_green(),
+                        job.failed.to_string().bright_red(),
+                        job.pending()
+                    );
+
+                    for record in store.job_hosts(&job.id)? {
+                        if failed && record.status == JobHostStatus::Success {
+                            continue;
+                        }
+                        println!();
+                        if record.status == JobHostStatus::Pending {
+                            println!("[{}] 未执行", record.server_name.bright_yellow());
+                            continue;
+                        }
+                        println!("{} {} ({:.1}s)", "==>".bright_blue(), record.server_name, record.duration_ms as f64 / 1000.0);
+                        fleet::print_host_result(&fleet::HostResult::from(&record));
+                    }
+                },
+
+                JobAction::RerunFailed { id, parallel, yes, collate } => {
+                    let job = store.find_job(&id)?.ok_or_else(|| anyhow::anyhow!("找不到任务: {}", id))?;
+
+                    let mut servers = Vec::new();
+                    for record in store.job_hosts(&job.id)? {
+                        if record.status == JobHostStatus::Success {
+                            continue;
+                        }
+                        match config_manager.get_server(&record.server_id)? {
+                            Some(server) => servers.push(server),
+                            None => println!("{} 服务器 {} 已被删除，跳过", "⚠".bright_yellow(), record.server_name),
+                        }
+                    }
+                    if servers.is_empty() {
+                        println!("任务 {} 没有需要重试的主机", job.id);
+                        return Ok(());
+                    }
+
+                    let settings = Settings::load()?;
+                    if !fleet::confirm_targets(&servers, job.target.as_deref(), &job.command, settings.confirm_threshold(), yes)? {
+                        println!("已取消");
+                        return Ok(());
+                    }
+
+                    let fleet_job = FleetJob {
+                        kind: "exec",
+                        command: &job.command,
+                        target: job.target.as_deref(),
+                        parent_id: Some(&job.id),
+                        parallel,
+                        collate,
+                        override_policy: false,
+                        sudo: false,
+                    };
+                    if !run_fleet_job(&servers, &fleet_job, &fleet::Rollout::default(), yes)? {
+                        std::process::exit(1);
+                    }
+                },
+            }
+        },
+
+        Commands::Cp { paths, recursive, notify, ssh_verbose } => {
+            enable_ssh_verbose(ssh_verbose);
+            let notify = notify_targets(&notify, &settings)?;
+            let mut endpoints = Vec::new();
+            for spec in &paths {
+                let endpoint = match Endpoint::parse(spec, |name| find_server(&config_manager, name).ok())? {
+                    Endpoint::Remote { server, path } => Endpoint::Remote {
+                        server: Box::new(server.with_resolved_secrets()?),
+                        path,
+                    },
+                    local => local,
+                };
+                endpoints.push(endpoint);
+            }
+
+            let dest = endpoints.pop().ok_or_else(|| anyhow::anyhow!("请指定目标路径"))?;
+            for endpoint in &endpoints {
+                if let Endpoint::Remote { server, path } = endpoint {
+                    audit::record(server, "download", Some(&path.display().to_string()))?;
+                }
+            }
+            if let Endpoint::Remote { server, path } = &dest {
+                audit::record(server, "upload", Some(&path.display().to_string()))?;
+            }
+            notify_when_done(&notify, format!("cp {}", paths.join(" ")), || {
+                crate::utils::cp::copy(&endpoints, &dest, recursive)?;
+                Ok(true)
+            })?;
+        },
+
+        Commands::Deploy { server, local_dir, remote_dir, watch, exclude, debounce, method, delete } => {
+            let mut excludes: Vec<String> = DEFAULT_EXCLUDES.iter().map(|p| p.to_string()).collect();
+            let (server, local_dir, remote_dir) = match (server, local_dir, remote_dir) {
+                (Some(server), Some(local_dir), Some(remote_dir)) => (server, local_dir, remote_dir),
+                (target, _, _) => {
+                    let workspace = Workspace::current()?
+                        .ok_or_else(|| anyhow::anyhow!("请指定服务器、本地目录和远程目录，或在项目的 {} 中配置部署目标", WORKSPACE_FILE))?;
+                    let (name, target) = workspace.deploy_target(target.as_deref())?;
+                    println!("使用 {} 中的部署目标 {}", WORKSPACE_FILE, name.bright_green());
+                    excludes.extend(target.exclude.iter().cloned());
+                    (target.server.clone(), target.local_dir.clone(), target.remote_dir.clone())
+                }
+            };
+            let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+            excludes.extend(exclude);
+
+            let options = DeployOptions {
+                method,
+                watch,
+                debounce: std::time::Duration::from_millis(debounce),
+                excludes,
+                delete,
+            };
+            println!("部署 {} -> {}:{}", local_dir.display(), server_config.name.bright_green(), remote_dir);
+            deploy(&server_config, &local_dir, &remote_dir, &options)?;
+        },
+
+        Commands::Import { config, uri_list, group, skip_existing } => {
+            let mut configs = match uri_list {
+                Some(path) => {
+                    let content = std::fs::read_to_string(&path)
+                        .with_context(|| format!("无法读取 URI 列表: {}", path.display()))?;
+                    println!("从 {} 导入服务器配置...", path.display());
+
+                    let mut configs = Vec::new();
+                    for (uri, name) in parse_uri_list(&content)? {
+                        let mut server = server_from_uri(&uri, name.as_deref())?;
+                        server.id = Uuid::new_v4().to_string();
+                        configs.push(server);
+                    }
+                    configs
+                }
+                None => {
+                    let config_path = match config {
+                        Some(path) => path,
+                        None => {
+                            let mut home = dirs::home_dir()
+                                .ok_or_else(|| anyhow::anyhow!("无法确定用户主目录"))?;
+                            home.push(".ssh");
+                            home.push("config");
+                            home
+                        }
+                    };
+
+                    if !config_path.exists() {
+                        return Err(anyhow::anyhow!("找不到 SSH 配置文件: {}", config_path.display()));
+                    }
+
+                    println!("从 {} 导入服务器配置...", config_path.display());
+                    import_ssh_config(&config_path)?
+                }
+            };
+            
+            if let Some(ref g) = group {
+                for config in &mut configs {
+                    config.group = Some(g.clone());
+                }
+            }
+            
+            let (imported, skipped) = import_servers(&config_manager, configs, skip_existing)?;
+            
+            println!("导入完成! 已导入 {} 个服务器, 跳过 {} 个已存在的服务器。", 
+                imported.to_string().bright_green(), 
+                skipped.to_string().bright_yellow()
+            );
+        },
+        
+        Commands::Export { path, uris } => {
+            if uris {
+                let content: String = config_manager
+                    .list_servers()?
+                    .iter()
+                    .map(|s| format!("{} {}\n", format_ssh_uri(s), s.name))
+                    .collect();
+                std::fs::write(&path, content)
+                    .with_context(|| format!("无法写入 URI 列表: {}", path.display()))?;
+                println!("ssh:// URI 列表已导出到: {}", path.display());
+            } else {
+                config_manager.export_config(&path)?;
+                let session_manager = SessionManager::new(get_session_dir()?)?;
+                let sessions = session_manager
+                    .export_sessions(&SessionManager::new(path.join("sessions"))?, &config_manager.list_servers()?)?;
+                println!("配置已导出到: {}（包含 {} 个会话）", path.display(), sessions);
+            }
+        },
 
         Commands::ExportSshConfig { path } => {
             config_manager.export_ssh_config(&path)?;
@@ -806,27 +3041,484 @@ pub fn run() -> Result<()> {
             );
         },
 
-        Commands::ImportConfig { path } => {
-            config_manager.import_config(&path)?;
-            println!("配置已从 {} 导入", path.display());
+        Commands::ImportConfig { path } => {
+            report_import_failures(&config_manager.import_config(&path, &get_key_dir()?)?)?;
+            println!("配置已从 {} 导入", path.display());
+
+            let sessions_dir = path.join("sessions");
+            if sessions_dir.is_dir() {
+                let session_manager = SessionManager::new(get_session_dir()?)?;
+                let (count, missing) =
+                    session_manager.import_sessions(&SessionManager::new(sessions_dir)?, &config_manager.list_servers()?)?;
+                println!("已导入 {} 个会话", count);
+                if !missing.is_empty() {
+                    println!("{} 会话中引用的以下服务器不存在: {}", "⚠".bright_yellow(), missing.join(", "));
+                }
+            }
+        },
+
+        Commands::Passwd { server } => {
+            let mut stored = find_server(&config_manager, &server)?;
+            let resolved = stored.with_resolved_secrets()?;
+            let current = match (&resolved.auth_type, &resolved.password) {
+                (AuthType::Password(password), _) | (_, Some(password)) => password.clone(),
+                _ => return Err(anyhow::anyhow!(
+                    "{} 没有保存密码，只能修改使用密码认证或设置了备用密码的服务器", resolved.name
+                )),
+            };
+
+            print!("新密码: ");
+            io::stdout().flush()?;
+            let new = rpassword::read_password()?;
+            print!("再次输入新密码: ");
+            io::stdout().flush()?;
+            if rpassword::read_password()? != new {
+                return Err(anyhow::anyhow!("两次输入的密码不一致"));
+            }
+            if new.is_empty() || new == current {
+                return Err(anyhow::anyhow!("新密码不能为空或与当前密码相同"));
+            }
+
+            audit::record(&resolved, "passwd", None)?;
+            println!("正在修改 {}@{} 的密码...", resolved.username, resolved.host);
+            passwd::change_password(&SshClient::connect(&resolved)?, &current, &new)?;
+
+            // 保存的是密钥引用（env:/pass: 等）时不能直接覆盖，需要到对应的密钥管理器中更新
+            let saved = match &mut stored.auth_type {
+                AuthType::Password(password) => password,
+                _ => stored.password.get_or_insert_with(String::new),
+            };
+            if stored.secret_provider.as_deref().is_some_and(|provider| provider != "db")
+                || (*saved != current && !credential_vault::is_encrypted(saved))
+            {
+                println!(
+                    "{} 密码来自密钥引用 {}，请在对应的密钥管理器中更新为新密码",
+                    "⚠".bright_yellow(), saved
+                );
+            } else {
+                *saved = new;
+            }
+            stored.password_changed_at = Some(chrono::Local::now().timestamp());
+            if config_manager.update_server(stored)? {
+                println!("{} 密码已修改并保存", "✓".bright_green());
+            } else {
+                println!("{} 远程密码已修改，但该服务器不在数据库中，未保存", "⚠".bright_yellow());
+            }
+        },
+
+        Commands::Share { session, user, write, stop } => {
+            share::check_tmux()?;
+            let session_manager = SessionManager::new(get_session_dir()?)?;
+            let session_config = if session_manager.session_exists(&session) {
+                Some(session_manager.load_session(&session)?)
+            } else {
+                session_manager.find_session_by_name(&session)?
+            };
+            let tmux_session = session_config.as_ref().map(share::tmux_session_name).unwrap_or(session);
+            if share::socket_path(&tmux_session).is_err() {
+                let running = share::list_rssh_sessions();
+                return Err(anyhow::anyhow!(
+                    "没有正在运行的 tmux 会话 {}（先用 rssh session-start --tmux 启动）{}",
+                    tmux_session,
+                    if running.is_empty() { String::new() } else { format!("\n正在运行: {}", running.join(", ")) }
+                ));
+            }
+
+            if stop {
+                share::revoke(&tmux_session, &user)?;
+                println!("{} 已取消 {} 对 {} 的共享", "✓".bright_green(), user, tmux_session);
+                return Ok(());
+            }
+
+            let attach = share::grant(&tmux_session, &user, write)?;
+            let access = if write { "可写".bright_red() } else { "只读".bright_green() };
+            println!("{} 已将 {} 共享给 {}（{}）", "✓".bright_green(), tmux_session.bright_green(), user, access);
+            println!("请 {} 在本机执行:\n  {}", user, attach.bright_white());
+            if write {
+                println!("{} 对方的输入会直接发送到服务器", "注意:".bright_yellow());
+            }
+            println!("结束共享: rssh share {} --user {} --stop", tmux_session, user);
+        },
+
+        Commands::Stats { server, since } => {
+            let server_id = server.map(|server| find_server(&config_manager, &server).map(|s| s.id)).transpose()?;
+            let since = since.map(|since| crate::config::traffic_store::parse_since(&since, chrono::Local::now())).transpose()?;
+            let totals = TrafficStore::new(get_db_path()?)?.totals(server_id.as_deref(), since)?;
+            if totals.is_empty() {
+                println!("没有流量记录（只统计 library / russh 模式的连接）");
+                return Ok(());
+            }
+
+            println!("{:<24} {:>6} {:>12} {:>12} {:>12}  最近使用", "服务器", "连接", "接收", "发送", "合计");
+            for total in &totals {
+                let last_used = chrono::DateTime::from_timestamp(total.last_used, 0)
+                    .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                println!(
+                    "{:<24} {:>6} {:>12} {:>12} {:>12}  {}",
+                    total.server_name,
+                    total.connections,
+                    multi_upload::format_size(total.bytes_in),
+                    multi_upload::format_size(total.bytes_out),
+                    multi_upload::format_size(total.bytes_in + total.bytes_out).bright_yellow(),
+                    last_used,
+                );
+            }
+            if totals.len() > 1 {
+                let (received, sent) = totals.iter().fold((0, 0), |(r, s), t| (r + t.bytes_in, s + t.bytes_out));
+                println!("{:<24} {:>6} {:>12} {:>12} {:>12}", "总计", "", multi_upload::format_size(received), multi_upload::format_size(sent), multi_upload::format_size(received + sent));
+            }
+        },
+
+        Commands::Config { action } => match action {
+            ConfigAction::Set { key, value } => {
+                let updated = Settings::set(&key, &value)?;
+                println!("{} {} = {}", "✓".bright_green(), key, value);
+
+                // 切换密码保存位置后立即迁移已保存的密码，迁移中断时重新执行即可继续
+                if key.replace('-', "_") == "credential_backend" {
+                    let backend = updated.credential_backend.unwrap_or_default();
+                    CredentialBackend::set_default(backend);
+                    let servers = config_manager.list_servers()?;
+                    for server in &servers {
+                        config_manager.update_server(server.clone())
+                            .with_context(|| format!("迁移 {} 的密码失败", server.name))?;
+                    }
+                    let target = match backend {
+                        CredentialBackend::Keychain => "系统钥匙串",
+                        CredentialBackend::Db => "数据库",
+                    };
+                    println!("已把 {} 台服务器的密码迁移到{}", servers.len(), target);
+                }
+            },
+            ConfigAction::Get { key } => match settings.get(&key)? {
+                Some(value) => println!("{}", value),
+                None => println!("{} 未设置", key),
+            },
+        },
+
+        Commands::Vault { action } => match action {
+            VaultAction::Init => {
+                if credential_vault::is_enabled() {
+                    return Err(anyhow::anyhow!("凭据库已经初始化"));
+                }
+                print!("设置主密码: ");
+                io::stdout().flush()?;
+                let master = rpassword::read_password()?;
+                print!("再次输入主密码: ");
+                io::stdout().flush()?;
+                if rpassword::read_password()? != master {
+                    return Err(anyhow::anyhow!("两次输入的主密码不一致"));
+                }
+                if master.is_empty() {
+                    return Err(anyhow::anyhow!("主密码不能为空"));
+                }
+
+                let meta = credential_vault::create(&master, credential_vault::DEFAULT_ITERATIONS)?;
+                credential_vault::configure(meta.clone(), None);
+                credential_vault::unlock(&master, std::time::Duration::from_secs(15 * 60))?;
+
+                // 重新保存即加密，密钥引用和其它提供者的引用保持不变
+                let count = config_manager.init_vault(&meta)?;
+                println!("{} 凭据库已初始化，{} 台服务器的密码已加密", "✓".bright_green(), count);
+                println!("主密码无法找回，请妥善保管；已解锁 15 分钟，rssh vault lock 立即锁定");
+            },
+            VaultAction::Unlock { minutes } => unlock_vault(std::time::Duration::from_secs(minutes * 60))?,
+            VaultAction::Lock => lock_vault()?,
+            VaultAction::Status => {
+                if !credential_vault::is_enabled() {
+                    println!("凭据库未启用，密码以明文保存在数据库中（rssh vault init 启用）");
+                    return Ok(());
+                }
+                match credential_vault::unlocked_until() {
+                    Some(until) => println!("状态: {}，到 {}", "已解锁".bright_yellow(), until.format("%H:%M:%S")),
+                    None => println!("状态: {}", "已锁定".bright_green()),
+                }
+                let servers = config_manager.list_servers()?;
+                let (encrypted, plain): (Vec<_>, Vec<_>) = servers
+                    .iter()
+                    .flat_map(|s| {
+                        let password = match &s.auth_type {
+                            AuthType::Password(password) => Some(password),
+                            _ => None,
+                        };
+                        password.into_iter().chain(s.password.as_ref())
+                    })
+                    .filter(|value| credential_vault::is_encrypted(value) || !is_secret_reference(value))
+                    .partition(|value| credential_vault::is_encrypted(value));
+                println!("加密的密码: {}", encrypted.len());
+                if !plain.is_empty() {
+                    println!("{} 仍有 {} 个明文密码（配置了其它密钥提供者的服务器不加密）", "⚠".bright_yellow(), plain.len());
+                }
+            },
+        },
+
+        Commands::Login { duration } => unlock_vault(duration)?,
+
+        Commands::Logout => lock_vault()?,
+
+        Commands::Ca { action } => {
+            let ca_settings = ca::settings()
+                .ok_or_else(|| anyhow::anyhow!("未配置团队 CA，请在 config.toml 中添加 [ca] 并设置 key"))?;
+            match action {
+                CaAction::SignKey { public_key, principals, validity, identity } => {
+                    let public_key = PathBuf::from(crate::utils::ssh_config::expand_tilde(&public_key.to_string_lossy()));
+                    if !public_key.is_file() {
+                        return Err(anyhow::anyhow!("公钥文件不存在: {}", public_key.display()));
+                    }
+                    let identity = identity.unwrap_or_else(|| {
+                        format!("{}@rssh", std::env::var("USER").unwrap_or_else(|_| "rssh".into()))
+                    });
+                    let validity = validity.unwrap_or_else(|| ca_settings.validity.clone());
+                    let cert = ca::sign_key(ca_settings, &public_key, &identity, &principals, &validity)?;
+                    println!("{} 已签发证书: {}", "✓".bright_green(), cert.display());
+                    println!("principals: {}，有效期: {}", principals.join(","), validity);
+                },
+                CaAction::Status => {
+                    println!("CA: {}", ca_settings.key.bright_green());
+                    println!("有效期: {}，principals: {}", ca_settings.validity,
+                        ca_settings.principals.as_ref().map(|p| p.join(",")).unwrap_or_else(|| "服务器登录用户".into()));
+                    if let Some(groups) = &ca_settings.groups {
+                        println!("分组: {}", groups.join(", "));
+                    }
+
+                    let cert_dir = get_cert_dir()?;
+                    let now = chrono::Local::now().timestamp();
+                    let mut found = false;
+                    for server in config_manager.list_servers()? {
+                        let cert = cert_dir.join(format!("{}-cert.pub", server.id));
+                        if !cert.exists() {
+                            continue;
+                        }
+                        if !found {
+                            println!("\n{:<20} 到期时间", "服务器");
+                            found = true;
+                        }
+                        let state = match ca::certificate_expiry(&cert) {
+                            Ok(None) => "永不过期".bright_yellow().to_string(),
+                            Ok(Some(expiry)) if expiry <= now => format!("{}（已过期，下次连接时续签）", format_timestamp(expiry)).dimmed().to_string(),
+                            Ok(Some(expiry)) => format!("{}（剩余 {} 分钟）", format_timestamp(expiry), (expiry - now) / 60).bright_green().to_string(),
+                            Err(e) => format!("无法读取: {}", e).bright_red().to_string(),
+                        };
+                        println!("{:<20} {}", server.name, state);
+                    }
+                    if !found {
+                        println!("\n还没有签发过证书，连接服务器时会自动签发");
+                    }
+                },
+            }
+        },
+
+        Commands::User { action } => match action {
+            UserAction::Add { server, username, key, sudo } => {
+                remote_user::validate_username(&username)?;
+                let keys = key.iter().map(|k| remote_user::load_public_key(k)).collect::<Result<Vec<_>>>()?;
+                let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+                let client = SshClient::connect(&server_config)?;
+                let distro = remote_user::detect_distro(&client)?;
+                let script = remote_user::add_user_script(distro, &username, &keys, sudo);
+                audit::record(&server_config, "user-add", Some(&username))?;
+                print!("{}", remote_user::run_as_root(&client, &server_config, &script)?);
+                println!(
+                    "{} 已在 {} 上创建用户 {}，安装了 {} 个公钥",
+                    "✓".bright_green(), server_config.name, username.bright_green(), keys.len()
+                );
+                if distro == Distro::Other {
+                    println!("{} 未能识别发行版，使用了通用命令，请确认结果", "⚠".bright_yellow());
+                }
+            }
+            UserAction::Remove { server, username, keep_home, yes } => {
+                remote_user::validate_username(&username)?;
+                let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+                if username == server_config.username || username == "root" {
+                    return Err(anyhow::anyhow!("不能删除 {}（当前登录用户或 root）", username));
+                }
+                if !yes {
+                    print!(
+                        "确定要删除 {} 上的用户 {}{} 吗? [y/N] ",
+                        server_config.name, username.on_bright_yellow(),
+                        if keep_home { "" } else { " 及其主目录" }
+                    );
+                    io::stdout().flush()?;
+                    let mut confirm = String::new();
+                    io::stdin().read_line(&mut confirm)?;
+                    if !confirm.trim().eq_ignore_ascii_case("y") {
+                        println!("已取消");
+                        return Ok(());
+                    }
+                }
+                let client = SshClient::connect(&server_config)?;
+                let distro = remote_user::detect_distro(&client)?;
+                audit::record(&server_config, "user-remove", Some(&username))?;
+                print!("{}", remote_user::run_as_root(&client, &server_config, &remote_user::remove_user_script(distro, &username, keep_home))?);
+                println!("{} 已删除 {} 上的用户 {}", "✓".bright_green(), server_config.name, username);
+            }
+        },
+
+        Commands::Bootstrap { server, playbook } => {
+            let steps = Playbook::load(&playbook)?;
+            let base_dir = playbook.parent().unwrap_or(Path::new("."));
+            let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+            audit::record(&server_config, "bootstrap", Some(&playbook.display().to_string()))?;
+            println!("按 {} 初始化 {}", playbook.display(), server_config.name.bright_green());
+            bootstrap::run_playbook(&server_config, &steps, base_dir)?;
         },
 
-        Commands::Info { server } => {
-            let server_config = config_manager.get_server(&server)?;
-            
-            let server_config = if server_config.is_none() {
-                let servers = config_manager.list_servers()?;
-                servers.into_iter().find(|s| s.name == server)
-            } else {
-                server_config
+        Commands::AuditSshd { server, group, parallel } => {
+            let servers: Vec<ServerConfig> = match (server, group) {
+                (_, Some(group)) => {
+                    let servers: Vec<ServerConfig> = config_manager
+                        .list_servers()?
+                        .into_iter()
+                        .filter(|s| s.group.as_deref() == Some(group.as_str()))
+                        .collect();
+                    if servers.is_empty() {
+                        return Err(anyhow::anyhow!("分组 {} 中没有服务器", group));
+                    }
+                    servers
+                }
+                (Some(server), None) => vec![find_server(&config_manager, &server)?],
+                (None, None) => return Err(anyhow::anyhow!("请指定服务器或分组")),
             };
-            
-            let server_config = match server_config {
-                Some(s) => s,
-                None => return Err(anyhow::anyhow!("找不到指定的服务器: {}", server)),
+
+            let results = fleet::run_on_hosts(&servers, &sshd_audit::fetch_command(), parallel, None, |_| {});
+            let mut failed = false;
+            // 按服务器列表的顺序输出，而不是完成顺序
+            for server in &servers {
+                let Some(result) = results.iter().find(|r| r.server_id == server.id) else { continue };
+                if !result.success() || result.stdout.trim().is_empty() {
+                    failed = true;
+                    let reason = result.error.clone().unwrap_or_else(|| result.stderr.trim().to_string());
+                    println!("{} {} 无法读取 sshd 配置: {}\n", Colorize::bold(server.name.as_str()), "✗".bright_red(), reason);
+                    continue;
+                }
+                sshd_audit::print_report(&server.name, &SshdConfig::parse(&result.stdout));
+                println!();
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        },
+
+        Commands::Diff { path, group, against, parallel } => {
+            let servers: Vec<ServerConfig> = config_manager
+                .list_servers()?
+                .into_iter()
+                .filter(|s| s.group.as_deref() == Some(group.as_str()))
+                .collect();
+            if servers.is_empty() {
+                return Err(anyhow::anyhow!("分组 {} 中没有服务器", group));
+            }
+
+            let results = fleet::run_on_hosts(&servers, &fleet_diff::fetch_command(&path), parallel, None, fleet::print_progress);
+            let reference = match against {
+                Some(local) => Reference {
+                    label: local.display().to_string(),
+                    content: std::fs::read_to_string(&local)
+                        .with_context(|| format!("无法读取本地文件: {}", local.display()))?,
+                    host: None,
+                },
+                None => {
+                    // 按服务器列表的顺序取第一台读取成功的主机，而不是最先完成的
+                    let first = servers
+                        .iter()
+                        .find_map(|server| results.iter().find(|r| r.server_id == server.id && r.success()))
+                        .ok_or_else(|| anyhow::anyhow!("所有主机都无法读取 {}", path))?;
+                    Reference {
+                        label: format!("{}:{}", first.server_name, path),
+                        content: first.stdout.clone(),
+                        host: Some(first.server_name.clone()),
+                    }
+                }
             };
-            
+            println!();
+            if fleet_diff::print_report(&reference, &results, &path) {
+                std::process::exit(1);
+            }
+        },
+
+        Commands::PushTemplate { template, group, server, dest, vars, reload, sudo, dry_run, yes, parallel } => {
+            let servers: Vec<ServerConfig> = match (&group, &server) {
+                (Some(group), _) => config_manager
+                    .list_servers()?
+                    .into_iter()
+                    .filter(|s| s.group.as_deref() == Some(group.as_str()))
+                    .collect(),
+                (None, Some(server)) => vec![find_server(&config_manager, server)?],
+                (None, None) => unreachable!(),
+            };
+            if servers.is_empty() {
+                return Err(anyhow::anyhow!("分组 {} 中没有服务器", group.unwrap_or_default()));
+            }
+            let source = std::fs::read_to_string(&template)
+                .with_context(|| format!("无法读取模板: {}", template.display()))?;
+            let vars = vars.into_iter().collect();
+
+            let plans = template_push::plan(&servers, &source, &dest, &vars, sudo, parallel)?;
+            template_push::print_preview(&plans, &dest);
+            let failed = plans.iter().filter(|p| matches!(p.current, template_push::Current::Failed(_))).count();
+            let changed: Vec<&template_push::HostPlan> = plans.iter().filter(|p| p.changed()).collect();
+            println!();
+            if changed.is_empty() {
+                if failed == 0 {
+                    println!("所有主机已是最新");
+                }
+            } else if dry_run {
+                println!("{} 台主机需要更新（--dry-run，未写入）", changed.len());
+            } else {
+                let targets: Vec<ServerConfig> = changed.iter().map(|p| p.server.clone()).collect();
+                let action = match &reload {
+                    Some(reload) => format!("写入 {} 并执行 {}", dest, reload),
+                    None => format!("写入 {}", dest),
+                };
+                if !fleet::confirm_targets(&targets, group.as_deref(), &action, Settings::load()?.confirm_threshold(), yes)? {
+                    println!("已取消");
+                    return Ok(());
+                }
+                let mut errors = 0;
+                for plan in changed {
+                    match template_push::apply(plan, &dest, sudo, reload.as_deref()) {
+                        Ok(()) => println!("{} {}", "✓".bright_green(), plan.server.name),
+                        Err(e) => {
+                            errors += 1;
+                            println!("{} {}: {:#}", "✗".bright_red(), plan.server.name, e);
+                        }
+                    }
+                }
+                if errors > 0 {
+                    std::process::exit(1);
+                }
+            }
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        },
+
+        Commands::Info { server, qr } => {
+            let Some(server) = server else {
+                let servers = config_manager.list_servers()?;
+                if output::is_json() {
+                    let forwarding: Vec<serde_json::Value> = servers
+                        .iter()
+                        .filter(|s| s.forward_agent != ForwardAgent::No)
+                        .map(server_info_json)
+                        .collect();
+                    return output::print_json(&forwarding);
+                }
+                display_agent_forwarding(&servers);
+                return Ok(());
+            };
+            let server_config = find_server(&config_manager, &server)?;
+            if output::is_json() {
+                return output::print_json(&server_info_json(&server_config));
+            }
+
             display_server_info(&server_config)?;
+
+            if qr {
+                println!();
+                display_server_qr(&server_config)?;
+            }
         },
 
         Commands::Copy { from, from_path, to, to_path } => {
@@ -842,6 +3534,7 @@ pub fn run() -> Result<()> {
                     .find(|s| s.name.to_lowercase() == from.to_lowercase())
                     .ok_or_else(|| anyhow::anyhow!("源服务器 '{}' 不存在，请使用 'rssh list' 查看可用服务器", from))?
             };
+            let from_server = from_server.with_resolved_secrets()?;
             println!("找到源服务器: {} ({})", from_server.name, from_server.host);
             
             println!("查找目标服务器: {}", to);
@@ -853,6 +3546,7 @@ pub fn run() -> Result<()> {
                     .find(|s| s.name.to_lowercase() == to.to_lowercase())
                     .ok_or_else(|| anyhow::anyhow!("目标服务器 '{}' 不存在，请使用 'rssh list' 查看可用服务器", to))?
             };
+            let to_server = to_server.with_resolved_secrets()?;
             println!("找到目标服务器: {} ({})", to_server.name, to_server.host);
             
             println!("检查 rclone 是否已安装...");
@@ -940,109 +3634,960 @@ pub fn run() -> Result<()> {
                 session_manager.create_session(name, description, Vec::new(), None)?;
                 println!("已创建空会话配置，请使用 'rssh session-edit' 编辑它");
             }
-        },
-        
-        Commands::SessionList => {
-            let session_manager = SessionManager::new(get_session_dir()?)?;
-            let sessions = session_manager.list_sessions()?;
+        },
+        
+        Commands::Tunnel { server, local, remote, auto_port, open, notify } => {
+            let notify = notify_targets(&notify, &settings)?;
+            let mut forwards = Vec::new();
+            for spec in &local {
+                forwards.push(Forward::parse(ForwardKind::Local, spec)?);
+            }
+            for spec in &remote {
+                forwards.push(Forward::parse(ForwardKind::Remote, spec)?);
+            }
+            if forwards.is_empty() {
+                return Err(anyhow::anyhow!("请用 -L 或 -R 指定至少一个端口转发"));
+            }
+
+            let run_dir = get_run_dir()?;
+            tunnel::check_local_ports(&mut forwards, auto_port, &list_active_sessions(&run_dir)?)?;
+
+            let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+            audit::record(&server_config, "tunnel", None)?;
+            show_connect_banner(&server_config, &settings);
+            let client = library_client(&server_config)?;
+            notify_when_done(&notify, format!("tunnel {}", server_config.name), || {
+                client.run_forwards(forwards, register_session(&server_config, "tunnel"), |forwards| {
+                    if !open {
+                        return;
+                    }
+                    match forwards.iter().find(|f| f.kind == ForwardKind::Local) {
+                        Some(forward) => tunnel::open_when_ready(forward, tunnel::local_url(forward)),
+                        None => eprintln!("{} --open 需要至少一个 -L 本地转发", "⚠".bright_yellow()),
+                    }
+                })?;
+                Ok(true)
+            })?;
+        },
+
+        Commands::Jupyter { server, remote_port, local_port, lab, jupyter } => {
+            let spec = format!("{}:127.0.0.1:{}", local_port.unwrap_or(remote_port), remote_port);
+            let mut forwards = vec![Forward::parse(ForwardKind::Local, &spec)?];
+            tunnel::check_local_ports(&mut forwards, true, &list_active_sessions(&get_run_dir()?)?)?;
+
+            let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+            audit::record(&server_config, "jupyter", None)?;
+            show_connect_banner(&server_config, &settings);
+            let client = library_client(&server_config)?;
+            let notebook = jupyter::ensure_notebook(&client, &jupyter, lab, remote_port)?;
+            client.run_forwards(forwards, register_session(&server_config, "jupyter"), |forwards| {
+                let url = notebook.local_url(&forwards[0]);
+                println!("Notebook 地址: {}", url.bright_blue());
+                println!("{}", format!("退出后 notebook 仍在服务器上运行，可用 {} server stop {} 停止", jupyter, remote_port).dimmed());
+                tunnel::open_when_ready(&forwards[0], url);
+            })?;
+        },
+
+        Commands::Vnc { server, remote, host, local_port, viewer } => {
+            let viewer = viewer.or_else(|| settings.vnc_viewer.clone());
+            desktop_tunnel(&config_manager, &settings, DesktopProtocol::Vnc, &server, &host, remote, local_port, viewer)?;
+        },
+
+        Commands::Rdp { server, remote, host, local_port, viewer } => {
+            let viewer = viewer.or_else(|| settings.rdp_viewer.clone());
+            desktop_tunnel(&config_manager, &settings, DesktopProtocol::Rdp, &server, &host, remote, local_port, viewer)?;
+        },
+
+        Commands::GitRemote { remote: _, url } => {
+            let remote = GitRemote::parse(&url)?;
+            let code = git_remote::run_helper(&remote, |server| {
+                let server_config = find_server(&config_manager, server)?.with_resolved_secrets()?;
+                audit::record(&server_config, "git", Some(&remote.path))?;
+                SshClient::connect(&server_config)
+            })?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        },
+
+        Commands::ProxyCommand { server, host, port } => {
+            let Some(host) = host else {
+                let exe = std::env::current_exe().with_context(|| "无法确定 rssh 的路径")?;
+                println!(
+                    "ProxyCommand {} proxycommand {} %h %p",
+                    shell_escape::escape(exe.to_string_lossy()),
+                    shell_escape::escape(server.as_str().into())
+                );
+                return Ok(());
+            };
+
+            // 标准输出是转发的数据，这里不能打印任何提示
+            let server_config = find_server(&config_manager, &server)?.with_resolved_secrets()?;
+            audit::record(&server_config, "proxy", Some(&format!("{}:{}", host, port)))?;
+            SshClient::connect(&server_config)?.pipe_direct(&host, port)?;
+        },
+
+        Commands::Ps => {
+            let sessions = list_active_sessions(&get_run_dir()?)?;
+            if sessions.is_empty() {
+                println!("没有正在运行的会话");
+                return Ok(());
+            }
+
+            for session in &sessions {
+                println!(
+                    "{:>7}  {}  {}  {}",
+                    session.pid.to_string().bright_blue(),
+                    session.server.bright_green(),
+                    session.mode,
+                    format_timestamp(session.started_at).dimmed()
+                );
+                for forward in &session.forwards {
+                    println!("         {}", forward);
+                }
+            }
+        },
+
+        Commands::SessionList => {
+            let session_manager = SessionManager::new(get_session_dir()?)?;
+            let sessions = session_manager.list_sessions()?;
+
+            match output::format() {
+                Some(OutputFormat::Json) => return output::print_json(&sessions),
+                Some(OutputFormat::Plain) => {
+                    let lines: String = sessions.iter().map(|s| format!("{}\t{}\t{}\n", s.id, s.name, s.windows.len())).collect();
+                    return output::print(&lines);
+                }
+                _ => {}
+            }
+
+            if sessions.is_empty() {
+                println!("没有找到会话配置");
+                return Ok(());
+            }
+
+            println!("共找到 {} 个会话配置\n", sessions.len().to_string().bright_green().bold());
+            for s in &sessions {
+                let short_id = s.id.split('-').next().unwrap_or(&s.id);
+                let desc = s.description.as_deref().unwrap_or("");
+                println!(
+                    "  {}  {}  ({} 窗口)  {}",
+                    short_id.bright_blue(),
+                    s.name.bright_green(),
+                    s.windows.len(),
+                    desc.dimmed()
+                );
+            }
+
+            println!("\n提示: 使用 {} 启动会话", "rssh session-start <ID或名称>".bright_yellow());
+        },
+        
+        Commands::SessionEdit { session } => {
+            let session_manager = SessionManager::new(get_session_dir()?)?;
+            
+            let session_id = if session_manager.session_exists(&session) {
+                session.clone()
+            } else {
+                match session_manager.find_session_by_name(&session)? {
+                    Some(s) => s.id,
+                    None => return Err(anyhow::anyhow!("未找到会话: {}", session)),
+                }
+            };
+            
+            let session_path = session_manager.get_session_path(&session_id);
+            
+            let status = std::process::Command::new(settings.editor())
+                .arg(&session_path)
+                .status()
+                .context("无法启动编辑器")?;
+            
+            if !status.success() {
+                return Err(anyhow::anyhow!("编辑器返回非零状态码: {}", status));
+            }
+            
+            println!("会话配置已更新");
+        },
+        
+        Commands::SessionRemove { session } => {
+            let session_manager = SessionManager::new(get_session_dir()?)?;
+            
+            let session_id = if session_manager.session_exists(&session) {
+                session.clone()
+            } else {
+                match session_manager.find_session_by_name(&session)? {
+                    Some(s) => s.id,
+                    None => return Err(anyhow::anyhow!("未找到会话: {}", session)),
+                }
+            };
+            
+            session_manager.remove_session(&session_id)?;
+            println!("会话已删除");
+        },
+        
+        Commands::SessionStart { session, tmux, kitty, wezterm } => {
+            let session_manager = SessionManager::new(get_session_dir()?)?;
+            let session = match session {
+                Some(session) => session,
+                None => Workspace::current()?
+                    .and_then(|workspace| workspace.session)
+                    .ok_or_else(|| anyhow::anyhow!("请指定要启动的会话，或在项目的 {} 中设置 session", WORKSPACE_FILE))?,
+            };
+            
+            let session_config = if session_manager.session_exists(&session) {
+                session_manager.load_session(&session)?
+            } else {
+                match session_manager.find_session_by_name(&session)? {
+                    Some(s) => s,
+                    None => return Err(anyhow::anyhow!("未找到会话: {}", session)),
+                }
+            };
+            
+            if session_config.windows.is_empty() {
+                return Err(anyhow::anyhow!("会话 '{}' 没有配置窗口", session_config.name));
+            }
+            
+            let backend = if kitty {
+                SessionBackend::Kitty
+            } else if wezterm {
+                SessionBackend::Wezterm
+            } else if tmux {
+                SessionBackend::Tmux
+            } else {
+                SessionBackend::Detect
+            };
+            launch_session(&config_manager, &session_config, backend)?;
+        },
+    }
+    
+    Ok(())
+}
+
+/// 进入全屏 TUI 列表让用户选择服务器，退出时恢复终端
+fn select_server_tui(servers: Vec<ServerConfig>, group: Option<String>) -> Result<Option<ServerConfig>> {
+    enable_raw_mode()?;
+    let mut stdout = stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let selected = run_list_tui(&mut terminal, servers, group);
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    selected
+}
+
+/// `rssh connect` 未指定服务器时使用项目配置中的服务器：只有一台时直接连接，多台时在列表中选择
+fn workspace_server(config_manager: &ConfigManager) -> Result<String> {
+    let workspace = Workspace::current()?
+        .filter(|workspace| !workspace.servers.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("请指定要连接的服务器，或在项目的 {} 中配置 servers", WORKSPACE_FILE))?;
+    let servers = workspace
+        .servers
+        .iter()
+        .map(|server| find_server(config_manager, server))
+        .collect::<Result<Vec<_>>>()?;
+    let server = match <[ServerConfig; 1]>::try_from(servers) {
+        Ok([server]) => server,
+        Err(servers) => select_server_tui(servers, None)?.ok_or_else(|| anyhow::anyhow!("未选择服务器"))?,
+    };
+    println!("使用 {} 中的服务器 {}", WORKSPACE_FILE, server.name.bright_green());
+    Ok(server.id)
+}
+
+/// 按服务器的连接类型进入交互式会话：SSH 走系统 ssh，其它类型走对应的控制台。
+/// 配置了登录对话时改用内置库连接
+fn connect_server(server: &ServerConfig, no_mux: bool) -> Result<i32> {
+    match server.connection_type {
+        ConnectionType::Ssh if !server.login_dialog.is_empty() => {
+            library_client(server)?.start_shell(register_session(server, "library"))
+        }
+        ConnectionType::Ssh => connect_via_system_ssh(server, false, true, !no_mux),
+        _ => crate::utils::console::connect_console(server),
+    }
+}
+
+/// 用指定的连接方式在服务器上执行一条命令，返回命令的退出码；未指定时 SSH 服务器使用系统 ssh
+fn run_with_mode(server: &ServerConfig, mode: Option<ConnectionMode>, command: String) -> Result<i32> {
+    if server.connection_type != ConnectionType::Ssh {
+        return Err(anyhow::anyhow!("只有 SSH 服务器支持直接执行命令"));
+    }
+    match mode.unwrap_or(ConnectionMode::System) {
+        ConnectionMode::System | ConnectionMode::Exec => {
+            connect_via_system_ssh_with_command(server, Some(command), false, false, false)
+        }
+        ConnectionMode::Library => {
+            library_client(server)?.shell_command(command).start_shell(register_session(server, "library"))
+        }
+        ConnectionMode::Debug => {
+            println!("调试日志写入 {}", crate::config::get_debug_log_path()?.display());
+            println!("调试日志写入 {}", crate::config::get_debug_log_path()?.display());
+            library_client(server)?.shell_command(command).start_shell(register_session(server, "library"))
+        }
+        ConnectionMode::Russh => russh_connect(server, register_session(server, "russh"), Some(&command)),
+        ConnectionMode::Telnet => Err(anyhow::anyhow!("telnet 连接方式不支持直接执行命令")),
+    }
+}
+
+/// 使用命令行指定的连接方式连接
+fn connect_with_mode(server: &ServerConfig, mode: ConnectionMode, no_mux: bool) -> Result<()> {
+    if server.forward_agent != ForwardAgent::No
+        && matches!(mode, ConnectionMode::Library | ConnectionMode::Debug | ConnectionMode::Russh)
+    {
+        println!("{} 内置库 / russh 模式不支持 SSH agent 转发，需要时请使用系统 ssh 模式", "⚠".bright_yellow());
+    }
+    if !server.login_dialog.is_empty() && !matches!(mode, ConnectionMode::Library | ConnectionMode::Debug) {
+        println!("{} 登录对话只在内置库模式下执行，需要时请使用 --mode library", "⚠".bright_yellow());
+    }
+    match mode {
+        ConnectionMode::System => {
+            connect_via_system_ssh(server, false, true, !no_mux)?;
+        }
+        ConnectionMode::Library => {
+            library_client(server)?.start_shell(register_session(server, "library"))?;
+        }
+        ConnectionMode::Debug => {
+            println!("调试日志写入 {}", crate::config::get_debug_log_path()?.display());
+            println!("调试日志写入 {}", crate::config::get_debug_log_path()?.display());
+            library_client(server)?.start_shell(register_session(server, "library"))?;
+        }
+        ConnectionMode::Exec => ssh_command_connect(server, true)?,
+        ConnectionMode::Russh => {
+            russh_connect(server, register_session(server, "russh"), None)?;
+        }
+        ConnectionMode::Telnet => {
+            // 对 SSH 服务器强制使用 telnet 时，端口改用 telnet 默认端口
+            let port = if server.connection_type == ConnectionType::Telnet {
+                server.port
+            } else {
+                telnet::DEFAULT_PORT
+            };
+            telnet::connect_telnet(&server.host, port)?;
+        }
+    }
+    Ok(())
+}
+
+/// 询问主密码并解锁凭据库 `duration`（`rssh login` / `rssh vault unlock`）
+fn unlock_vault(duration: std::time::Duration) -> Result<()> {
+    if !credential_vault::is_enabled() {
+        return Err(anyhow::anyhow!("凭据库尚未初始化，请先执行 rssh vault init"));
+    }
+    print!("主密码: ");
+    io::stdout().flush()?;
+    let master = rpassword::read_password()?;
+    credential_vault::unlock(&master, duration)?;
+    let until = chrono::Local::now() + chrono::Duration::seconds(duration.as_secs() as i64);
+    println!("{} 已解锁，到 {} 前不再询问主密码（rssh logout 立即锁定）", "✓".bright_green(), until.format("%m-%d %H:%M"));
+    Ok(())
+}
+
+/// `rssh logout` / `rssh vault lock`
+fn lock_vault() -> Result<()> {
+    if credential_vault::lock()? {
+        println!("{} 已锁定", "✓".bright_green());
+    } else {
+        println!("凭据库未解锁");
+    }
+    Ok(())
+}
+
+/// `--ssh-verbose`：系统 ssh/scp/sftp 提高 LogLevel，内置库输出连接过程，russh 输出日志
+fn enable_ssh_verbose(level: u8) {
+    if level == 0 {
+        return;
+    }
+    set_ssh_verbosity(level);
+    let _ = env_logger::Builder::new()
+        .filter_module("russh", russh_log_filter(level))
+        .filter_module("russh_keys", russh_log_filter(level))
+        .try_init();
+}
+
+/// russh 的日志级别：`-v` 为 Info，`-vv` 为 Debug，更多为 Trace
+fn russh_log_filter(level: u8) -> log::LevelFilter {
+    match level {
+        0 => log::LevelFilter::Off,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    }
+}
+
+/// 内置库模式的连接，登录横幅为 capture 时保存到 `~/.config/rssh/motd/<服务器名>.txt`
+fn library_client(server: &ServerConfig) -> Result<SshClient> {
+    let capture = get_motd_dir()?.join(format!("{}.txt", sanitize_host_alias(&server.name)));
+    Ok(SshClient::connect(server)?.capture_banner_to(capture))
+}
+
+/// 建立到远程桌面的转发，转发就绪后启动本地查看器，Ctrl+C 结束
+#[allow(clippy::too_many_arguments)]
+fn desktop_tunnel(
+    config_manager: &ConfigManager,
+    settings: &Settings,
+    protocol: DesktopProtocol,
+    server: &str,
+    host: &str,
+    remote: u16,
+    local_port: Option<u16>,
+    viewer: Option<String>,
+) -> Result<()> {
+    let spec = format!("{}:{}:{}", local_port.unwrap_or(remote), host, remote);
+    let mut forwards = vec![Forward::parse(ForwardKind::Local, &spec)?];
+    tunnel::check_local_ports(&mut forwards, true, &list_active_sessions(&get_run_dir()?)?)?;
+
+    let server_config = find_server(config_manager, server)?.with_resolved_secrets()?;
+    let mode = protocol.name().to_lowercase();
+    audit::record(&server_config, &mode, None)?;
+    show_connect_banner(&server_config, settings);
+    let template = viewer.unwrap_or_else(|| protocol.default_viewer().to_string());
+    library_client(&server_config)?.run_forwards(forwards, register_session(&server_config, &mode), |forwards| {
+        if let Err(e) = desktop::launch_viewer(protocol, &template, &forwards[0]) {
+            eprintln!("{} {}，请手动连接 {}", "⚠".bright_yellow(), e, forwards[0].listen_address());
+        }
+    })
+}
+
+/// 连接前显示服务器所在环境的标识和连接提示
+fn show_connect_banner(server: &ServerConfig, settings: &Settings) {
+    if let Some(badge) = Badge::for_server(server, &settings.badges) {
+        println!("{}", badge.render(server));
+    }
+    if let Some(note) = &server.note {
+        println!("{} {}", "⚠".bright_yellow(), note.bright_yellow().bold());
+    }
+}
+
+/// 连接前检查 agent 转发：服务器不在 `agent_forwarding_groups` 允许的分组中时不转发，
+/// 设置为 confirm 时询问本次是否转发
+fn authorize_agent_forwarding(server: &mut ServerConfig) -> Result<()> {
+    if server.forward_agent == ForwardAgent::No {
+        return Ok(());
+    }
+    if !server.agent_forwarding_allowed() {
+        println!(
+            "{} {} 不在 agent_forwarding_groups 允许的分组中，本次不转发 SSH agent",
+            "⚠".bright_yellow(), server.name
+        );
+        server.forward_agent = ForwardAgent::No;
+        return Ok(());
+    }
+    if server.forward_agent == ForwardAgent::Confirm {
+        print!("允许 {} 使用本机的 SSH agent 吗？服务器上的进程将可以用你的密钥登录其他主机 [y/N] ", server.name.bright_yellow());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        server.forward_agent = if input.trim().eq_ignore_ascii_case("y") {
+            ForwardAgent::Yes
+        } else {
+            ForwardAgent::No
+        };
+    }
+    Ok(())
+}
+
+/// 登记正在运行的会话，供 `rssh ps` 查看；登记失败不影响连接
+fn register_session(server: &ServerConfig, mode: &str) -> Option<SessionRegistry> {
+    match get_run_dir().and_then(|dir| SessionRegistry::register(&dir, server, mode)) {
+        Ok(registry) => Some(registry),
+        Err(e) => {
+            eprintln!("{} 无法登记会话: {:#}", "⚠".bright_yellow(), e);
+            None
+        }
+    }
+}
+
+/// 在新的终端窗口/标签中执行 `rssh connect <id>`，密钥引用在子进程中解析
+fn connect_in_new_window(server: &ServerConfig, no_mux: bool) -> Result<()> {
+    let current_rssh_path = std::env::current_exe()
+        .with_context(|| "无法获取当前rssh可执行文件路径")?;
+
+    let mut argv = vec![
+        current_rssh_path.to_string_lossy().to_string(),
+        "connect".to_string(),
+        server.id.clone(),
+    ];
+    if no_mux {
+        argv.push("--no-mux".to_string());
+    }
+
+    let settings = Settings::load()?;
+    crate::utils::terminal::spawn_in_new_window(
+        &argv,
+        settings.terminal.as_deref(),
+        settings.terminal_command.as_deref(),
+    )?;
+    println!("已在新窗口中连接到服务器: {}", server.name.clone().green());
+
+    Ok(())
+}
+
+/// 解析 `--notify` 的目标。在开始操作前解析，配置写错时不用等到几个小时的传输结束才发现
+fn notify_targets(specs: &[String], settings: &Settings) -> Result<Vec<notification::Target>> {
+    specs.iter().map(|spec| notification::Target::parse(spec, &settings.notify)).collect()
+}
+
+/// `upload` / `download` 的 `--delete`、`--exclude` 只在 rsync 模式下有意义，其它模式下报错而不是忽略
+fn rsync_options(mode: TransferMode, delete: bool, excludes: Vec<String>) -> Result<RsyncOptions> {
+    if (delete || !excludes.is_empty()) && mode != TransferMode::Rsync {
+        return Err(anyhow::anyhow!("--delete 和 --exclude 只用于 rsync 模式，请加 --mode rsync"));
+    }
+    Ok(RsyncOptions { delete, excludes })
+}
+
+/// 输出触发器的通知在后台发送，不阻塞会话
+fn notify_trigger(target: &str, title: &str, body: &str) -> Result<()> {
+    let target = notification::Target::parse(target, &Settings::load()?.notify)?;
+    let (title, body) = (title.to_string(), body.to_string());
+    std::thread::spawn(move || notification::send_message(&[target], &title, &body));
+    Ok(())
+}
+
+/// 执行长时间操作，结束后向 `targets` 发送通知。`f` 返回 Ok(false) 表示部分失败（例如分组中有主机失败）
+fn notify_when_done(targets: &[notification::Target], action: String, f: impl FnOnce() -> Result<bool>) -> Result<bool> {
+    if targets.is_empty() {
+        return f();
+    }
+    let start = std::time::Instant::now();
+    let result = f();
+    let (success, detail) = match &result {
+        Ok(ok) => (*ok, None),
+        Err(e) => (false, Some(format!("{:#}", e))),
+    };
+    notification::send(targets, &notification::Event { action, detail, success, duration: start.elapsed() });
+    result
+}
+
+/// 导入进度，只在终端中显示，例如 `导入中 [##########----------]  50% 250/500`
+fn print_import_progress(done: usize, total: usize) {
+    const WIDTH: usize = 20;
+    if !io::stderr().is_terminal() {
+        return;
+    }
+    let filled = done * WIDTH / total.max(1);
+    eprint!(
+        "\r导入中 [{}{}] {:>3}% {}/{}",
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        done * 100 / total.max(1),
+        done,
+        total
+    );
+    if done == total {
+        eprintln!();
+    }
+}
+
+/// 只写了文件名的录制文件放在数据目录的 recordings/ 中，带路径时原样使用
+fn recording_path(path: PathBuf) -> Result<PathBuf> {
+    if path.components().count() > 1 || path.is_absolute() {
+        return Ok(path);
+    }
+    let dir = crate::config::get_recording_dir()?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("无法创建目录: {}", dir.display()))?;
+    Ok(dir.join(path))
+}
+
+/// `info --output json` 中服务器的表示，在插件清单的字段之外加上认证方式等信息（不含凭据）
+fn server_info_json(server: &ServerConfig) -> serde_json::Value {
+    let mut value = plugin::server_json(server);
+    value["auth"] = match server.auth_type {
+        AuthType::Password(_) => "password",
+        AuthType::Key(_) => "key",
+        AuthType::Agent => "agent",
+    }
+    .into();
+    value["forward_agent"] = serde_json::to_value(server.forward_agent).unwrap_or_default();
+    value["note"] = server.note.clone().into();
+    value["slot"] = server.slot.into();
+    value["ssh_command"] = ssh_command_string(server).into();
+    value
+}
+
+/// config.toml 中 `connect_mode` 指定的默认连接方式
+fn default_connect_mode(settings: &Settings) -> Result<Option<ConnectionMode>> {
+    settings
+        .connect_mode
+        .as_deref()
+        .map(|mode| {
+            ConnectionMode::from_str(mode, true)
+                .map_err(|_| anyhow::anyhow!("config.toml 中的 connect_mode 无效: {}（可选: system、library、russh）", mode))
+        })
+        .transpose()
+}
+
+/// 读取一行输入，直接回车时返回默认值
+fn prompt_with_default(question: &str, default: &str) -> Result<String> {
+    print!("{} [{}]: ", question, default.bright_green());
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+    Ok(if input.is_empty() { default.to_string() } else { input.to_string() })
+}
+
+/// 询问是/否，直接回车时返回默认值
+fn prompt_yes_no(question: &str, default: bool) -> Result<bool> {
+    let answer = prompt_with_default(question, if default { "Y/n" } else { "y/N" })?;
+    Ok(match answer.to_lowercase().as_str() {
+        "y" | "yes" | "是" => true,
+        "n" | "no" | "否" => false,
+        _ => default,
+    })
+}
+
+/// 在给定选项中选择一项，输入无效时重新询问
+fn prompt_choice(question: &str, choices: &[&str], default: &str) -> Result<String> {
+    loop {
+        let answer = prompt_with_default(&format!("{} ({})", question, choices.join("/")), default)?;
+        if choices.contains(&answer.as_str()) {
+            return Ok(answer);
+        }
+        println!("{} 请输入 {} 之一", "⚠".bright_yellow(), choices.join("、"));
+    }
+}
+
+/// `rssh quickstart`：一步步完成新用户的初始配置
+fn run_quickstart(config_manager: &ConfigManager) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!("quickstart 需要在终端中交互运行"));
+    }
+    println!("{}", "欢迎使用 rssh！接下来几步完成初始配置，直接回车使用方括号中的默认值。".bright_cyan());
+
+    println!("\n{} 配置目录", "[1/5]".bright_cyan());
+    println!("  配置文件: {}", crate::config::settings::get_settings_path()?.display());
+    println!("  服务器数据库: {}", get_db_path()?.display());
+
+    println!("\n{} 导入 ~/.ssh/config", "[2/5]".bright_cyan());
+    let ssh_config = dirs::home_dir().map(|home| home.join(".ssh").join("config"));
+    match ssh_config.filter(|path| path.exists()) {
+        Some(path) => {
+            let configs = import_ssh_config(&path)?;
+            if configs.is_empty() {
+                println!("  {} 中没有可导入的主机", path.display());
+            } else if prompt_yes_no(&format!("  导入其中的 {} 台主机?", configs.len()), true)? {
+                let (imported, skipped) = import_servers(config_manager, configs, true)?;
+                println!("  已导入 {} 台，跳过 {} 台已存在的服务器", imported.to_string().bright_green(), skipped);
+            }
+        }
+        None => println!("  没有找到 ~/.ssh/config，之后可以用 {} 添加服务器", "rssh add".bright_yellow()),
+    }
 
-            if sessions.is_empty() {
-                println!("没有找到会话配置");
-                return Ok(());
+    println!("\n{} 默认设置", "[3/5]".bright_cyan());
+    let settings = Settings::load()?;
+    let mode = prompt_choice("  默认连接方式", &["system", "library", "russh"], settings.connect_mode.as_deref().unwrap_or("system"))?;
+    let editor = prompt_with_default("  编辑器", &settings.editor())?;
+    let theme = prompt_choice("  配色", &["default", "mono"], settings.theme.as_deref().unwrap_or("default"))?;
+    Settings::set("connect_mode", &mode)?;
+    Settings::set("editor", &editor)?;
+    let settings = Settings::set("theme", &theme)?;
+    colored::control::set_override(theme != "mono" && io::stdout().is_terminal());
+    println!("  已保存到 {}", crate::config::settings::get_settings_path()?.display());
+
+    println!("\n{} 测试连接", "[4/5]".bright_cyan());
+    let servers = config_manager.list_servers()?;
+    match servers.iter().find(|s| s.connection_type == ConnectionType::Ssh) {
+        None => println!("  还没有 SSH 服务器，跳过（之后可以用 {} 添加）", "rssh add".bright_yellow()),
+        Some(first) => {
+            let name = prompt_with_default("  测试连接哪台服务器（输入 - 跳过）", &first.name)?;
+            if name != "-" {
+                let server = find_server(config_manager, &name)?.with_resolved_secrets()?;
+                let server = address_fallback::with_reachable_host(&server)?;
+                match run_with_mode(&server, default_connect_mode(&settings)?, "true".to_string()) {
+                    Ok(0) => println!("  {} 成功连接到 {}", "✓".bright_green(), server.name),
+                    Ok(code) => println!("  {} 连接 {} 失败（退出码 {}），可以用 {} 查看详细日志", "✗".bright_red(), server.name, code, "rssh connect <服务器> -v".bright_yellow()),
+                    Err(e) => println!("  {} 连接 {} 失败: {}", "✗".bright_red(), server.name, e),
+                }
             }
+        }
+    }
 
-            println!("共找到 {} 个会话配置\n", sessions.len().to_string().bright_green().bold());
-            for s in &sessions {
-                let short_id = s.id.split('-').next().unwrap_or(&s.id);
-                let desc = s.description.as_deref().unwrap_or("");
-                println!(
-                    "  {}  {}  ({} 窗口)  {}",
-                    short_id.bright_blue(),
-                    s.name.bright_green(),
-                    s.windows.len(),
-                    desc.dimmed()
-                );
+    println!("\n{} shell 补全", "[5/5]".bright_cyan());
+    match Shell::detect() {
+        Some(shell) => {
+            if prompt_yes_no(&format!("  为 {} 安装 rssh 命令补全?", shell), true)? {
+                let path = completions::install(&Cli::command(), shell)?;
+                println!("  已写入 {}，重新打开终端后生效", path.display());
             }
+        }
+        None => println!("  暂不支持当前 shell 的补全（支持 bash、zsh、fish）"),
+    }
 
-            println!("\n提示: 使用 {} 启动会话", "rssh session-start <ID或名称>".bright_yellow());
-        },
-        
-        Commands::SessionEdit { session } => {
-            let session_manager = SessionManager::new(get_session_dir()?)?;
-            
-            let session_id = if session_manager.session_exists(&session) {
-                session.clone()
-            } else {
-                match session_manager.find_session_by_name(&session)? {
-                    Some(s) => s.id,
-                    None => return Err(anyhow::anyhow!("未找到会话: {}", session)),
-                }
-            };
-            
-            let session_path = session_manager.get_session_path(&session_id);
-            
-            let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
-            let status = std::process::Command::new(editor)
-                .arg(&session_path)
-                .status()
-                .context("无法启动编辑器")?;
-            
-            if !status.success() {
-                return Err(anyhow::anyhow!("编辑器返回非零状态码: {}", status));
+    println!("\n{} 用 {} 浏览服务器，{} 连接。", "完成！".bright_green(), "rssh list".bright_yellow(), "rssh connect <名称>".bright_yellow());
+    Ok(())
+}
+
+/// 保存导入的服务器，`skip_existing` 时跳过名称或地址已存在的服务器，返回 (导入数, 跳过数)
+fn import_servers(config_manager: &ConfigManager, mut configs: Vec<ServerConfig>, skip_existing: bool) -> Result<(usize, usize)> {
+    let existing_servers = if skip_existing {
+        config_manager.list_servers()?
+    } else {
+        Vec::new()
+    };
+
+    let total = configs.len();
+    configs.retain(|server_config| {
+        !(skip_existing && existing_servers.iter().any(|s|
+            s.name == server_config.name ||
+            (s.host == server_config.host &&
+             s.port == server_config.port &&
+             s.username == server_config.username)))
+    });
+    let configs = hooks::on_import(configs)?;
+    let imported = configs.len();
+
+    let failures = config_manager.add_servers(&configs, print_import_progress)?;
+    report_import_failures(&failures)?;
+    Ok((imported, total - imported))
+}
+
+/// 导入有记录失败时逐条列出原因；此时事务已回滚，没有导入任何服务器
+fn report_import_failures(failures: &[ImportFailure]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    eprintln!("{} 以下 {} 条记录导入失败:", "✗".bright_red(), failures.len());
+    for failure in failures {
+        eprintln!("  {}: {}", failure.name.bright_yellow(), failure.error);
+    }
+    Err(anyhow::anyhow!("导入已回滚，没有导入任何服务器"))
+}
+
+/// 一次批量执行的参数
+struct FleetJob<'a> {
+    /// 任务类型：手动执行为 exec，定时任务为 schedule
+    kind: &'a str,
+    command: &'a str,
+    target: Option<&'a str>,
+    /// 重试时指向原任务
+    parent_id: Option<&'a str>,
+    parallel: usize,
+    collate: bool,
+    /// 越过服务器的命令规则（`--override-policy`）
+    override_policy: bool,
+    /// 通过 `sudo -S` 以 root 身份执行（`--sudo`）
+    sudo: bool,
+}
+
+/// 按服务器的命令规则检查要执行的命令；`override_policy` 时放行，但每台被越过的服务器都写入审计日志
+fn enforce_command_policy(servers: &[ServerConfig], command: &str, override_policy: bool) -> Result<()> {
+    let mut violations = Vec::new();
+    for server in servers {
+        if let Some(reason) = server.command_policy.violation(command)? {
+            violations.push((server, reason));
+        }
+    }
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    if !override_policy {
+        let details: Vec<String> = violations.iter().map(|(server, reason)| format!("  {}: {}", server.name, reason)).collect();
+        return Err(anyhow::anyhow!(
+            "命令不符合服务器的命令规则，已拒绝执行:\n{}\n确需执行时加 --override-policy（会写入审计日志）",
+            details.join("\n")
+        ));
+    }
+    for (server, reason) in &violations {
+        eprintln!("{} {}: {}，按 --override-policy 执行并写入审计日志", "⚠".bright_yellow(), server.name, reason);
+        audit::record_always(server, "exec-override", Some(command))?;
+    }
+    Ok(())
+}
+
+/// 在多台主机上执行命令并把每台主机的结果写入任务记录，全部成功时返回 true
+fn run_fleet_job(servers: &[ServerConfig], job: &FleetJob, rollout: &fleet::Rollout, assume_yes: bool) -> Result<bool> {
+    let servers = servers
+        .iter()
+        .map(|s| s.with_resolved_secrets())
+        .collect::<Result<Vec<_>>>()?;
+    enforce_command_policy(&servers, job.command, job.override_policy)?;
+    let sudo = if job.sudo { Some(Sudo::prepare(&servers)?) } else { None };
+    let audited_command = if job.sudo { format!("sudo {}", job.command) } else { job.command.to_string() };
+    for server in &servers {
+        audit::record(server, "exec", Some(&audited_command))?;
+    }
+
+    let store = JobStore::new(get_db_path()?)?;
+    let hosts: Vec<(String, String)> = servers.iter().map(|s| (s.id.clone(), s.name.clone())).collect();
+    let job_id = store.create_job(job.kind, job.command, job.target, job.parent_id, &hosts)?;
+
+    // 每台主机完成后立即落盘，任务中途被中断也能重试剩下的主机
+    let json = output::is_json();
+    let results = fleet::run_rollout(&servers, job.command, job.parallel, rollout, assume_yes, sudo.as_ref(), |result| {
+        if json {
+            // 结果在全部完成后一次性输出
+        } else if job.collate {
+            fleet::print_progress(result);
+        } else {
+            fleet::print_host_result(result);
+        }
+        if let Err(e) = store.record_result(&job_id, &result.into()) {
+            eprintln!("{} 无法保存 {} 的结果: {:#}", "⚠".bright_yellow(), result.server_name, e);
+        }
+    })?;
+    store.finish_job(&job_id)?;
+
+    // 金丝雀失败后中止时，剩余主机保持未执行状态
+    let ok = results.len() == servers.len() && results.iter().all(fleet::HostResult::success);
+    if json {
+        output::print_json(&serde_json::json!({
+            "job_id": job_id,
+            "ok": ok,
+            "results": results.iter().map(fleet::HostResult::to_json).collect::<Vec<_>>(),
+        }))?;
+        return Ok(ok);
+    }
+
+    if job.collate {
+        fleet::print_collated(&results);
+    }
+    fleet::print_summary(&results);
+
+    if ok {
+        println!("任务 ID: {}", job_id.bright_cyan());
+    } else {
+        println!("任务 ID: {}，可用 rssh job rerun-failed {} 重试失败的主机", job_id.bright_cyan(), job_id);
+    }
+    Ok(ok)
+}
+
+/// `rssh schedule daemon`：每分钟检查一次到期的定时任务，每个任务在单独的线程中执行，
+/// 上一次还没执行完时跳过本次。同一时间只允许一个调度器运行
+fn run_scheduler(config_manager: &ConfigManager) -> Result<()> {
+    let lock_path = crate::config::get_state_dir()?.join("scheduler.lock");
+    let lock = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("无法创建锁文件: {}", lock_path.display()))?;
+    // 锁随文件描述符在进程退出时释放
+    if unsafe { libc::flock(std::os::unix::io::AsRawFd::as_raw_fd(&lock), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(anyhow::anyhow!("已有调度器在运行（{}）", lock_path.display()));
+    }
+
+    let db_path = get_db_path()?;
+    let running = std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashSet::new()));
+    println!("[{}] 调度器已启动，按 Ctrl+C 停止", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    loop {
+        let store = ScheduleStore::new(db_path.clone())?;
+        let now = chrono::Local::now();
+        for schedule in store.list()? {
+            if running.lock().unwrap().contains(&schedule.id) {
+                continue;
             }
-            
-            println!("会话配置已更新");
-        },
-        
-        Commands::SessionRemove { session } => {
-            let session_manager = SessionManager::new(get_session_dir()?)?;
-            
-            let session_id = if session_manager.session_exists(&session) {
-                session.clone()
-            } else {
-                match session_manager.find_session_by_name(&session)? {
-                    Some(s) => s.id,
-                    None => return Err(anyhow::anyhow!("未找到会话: {}", session)),
-                }
-            };
-            
-            session_manager.remove_session(&session_id)?;
-            println!("会话已删除");
-        },
-        
-        Commands::SessionStart { session, tmux, kitty, wezterm } => {
-            let session_manager = SessionManager::new(get_session_dir()?)?;
-            
-            let session_config = if session_manager.session_exists(&session) {
-                session_manager.load_session(&session)?
-            } else {
-                match session_manager.find_session_by_name(&session)? {
-                    Some(s) => s,
-                    None => return Err(anyhow::anyhow!("未找到会话: {}", session)),
+            match schedule.is_due(now) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    eprintln!("{} 定时任务 {}: {:#}", "⚠".bright_yellow(), schedule.id, e);
+                    continue;
                 }
-            };
-            
-            if session_config.windows.is_empty() {
-                return Err(anyhow::anyhow!("会话 '{}' 没有配置窗口", session_config.name));
             }
-            
-            if kitty {
-                start_session_with_kitty(&config_manager, &session_config)?;
-            } else if wezterm {
-                start_session_with_wezterm(&config_manager, &session_config)?;
-            } else if tmux {
-                start_session_with_tmux(&config_manager, &session_config)?;
-            } else if crate::utils::terminal::is_kitty() {
-                start_session_with_kitty(&config_manager, &session_config)?;
+
+            store.mark_started(&schedule.id, now.timestamp())?;
+            let servers: Vec<ServerConfig> = config_manager
+                .list_servers()?
+                .into_iter()
+                .filter(|s| s.group.as_deref() == Some(schedule.group.as_str()))
+                .collect();
+            println!(
+                "[{}] 执行定时任务 {}（{} 台主机）: {}",
+                now.format("%Y-%m-%d %H:%M:%S"),
+                schedule.id.bright_cyan(),
+                servers.len(),
+                schedule.command
+            );
+            if servers.is_empty() {
+                eprintln!("{} 分组 {} 中没有服务器", "⚠".bright_yellow(), schedule.group);
+                store.mark_finished(&schedule.id, false)?;
+                continue;
+            }
+
+            running.lock().unwrap().insert(schedule.id.clone());
+            let running = running.clone();
+            let db_path = db_path.clone();
+            std::thread::spawn(move || {
+                let job = FleetJob {
+                    kind: "schedule",
+                    command: &schedule.command,
+                    target: Some(&schedule.group),
+                    parent_id: None,
+                    parallel: schedule.parallel,
+                    collate: false,
+                    override_policy: false,
+                    sudo: false,
+                };
+                let ok = run_fleet_job(&servers, &job, &fleet::Rollout::default(), true).unwrap_or_else(|e| {
+                    eprintln!("{} 定时任务 {} 执行失败: {:#}", "✗".bright_red(), schedule.id, e);
+                    false
+                });
+                if let Err(e) = ScheduleStore::new(db_path).and_then(|store| store.mark_finished(&schedule.id, ok)) {
+                    eprintln!("{} 无法保存定时任务 {} 的结果: {:#}", "⚠".bright_yellow(), schedule.id, e);
+                }
+                running.lock().unwrap().remove(&schedule.id);
+            });
+        }
+
+        // 睡到下一分钟的开始
+        let seconds = 60 - chrono::Timelike::second(&chrono::Local::now()) as u64;
+        std::thread::sleep(std::time::Duration::from_secs(seconds));
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+fn find_server(config_manager: &ConfigManager, server_id_or_name: &str) -> Result<ServerConfig> {
+    // ssh://user@host:port 直接连接，不需要事先添加到数据库
+    if is_ssh_uri(server_id_or_name) {
+        return server_from_uri(server_id_or_name, None);
+    }
+
+    let server_config = config_manager.find_server(server_id_or_name)?;
+    
+    server_config.ok_or_else(|| anyhow::anyhow!("未找到服务器: {}", server_id_or_name))
+}
+
+/// 会话启动时使用的多窗口后端
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum SessionBackend {
+    Kitty,
+    Wezterm,
+    Tmux,
+    /// 根据当前终端自动选择
+    Detect,
+}
+
+/// 按指定后端启动会话；自动检测失败时按顺序逐个连接
+fn launch_session(config_manager: &ConfigManager, session_config: &SessionConfig, backend: SessionBackend) -> Result<()> {
+    match backend {
+        SessionBackend::Kitty => start_session_with_kitty(config_manager, session_config),
+        SessionBackend::Wezterm => start_session_with_wezterm(config_manager, session_config),
+        SessionBackend::Tmux => start_session_with_tmux(config_manager, session_config),
+        SessionBackend::Detect => {
+            if crate::utils::terminal::is_kitty() {
+                start_session_with_kitty(config_manager, session_config)
             } else if crate::utils::terminal::is_wezterm() {
-                start_session_with_wezterm(&config_manager, &session_config)?;
+                start_session_with_wezterm(config_manager, session_config)
             } else if std::env::var("TMUX").is_ok() {
-                start_session_with_tmux(&config_manager, &session_config)?;
+                start_session_with_tmux(config_manager, session_config)
             } else {
                 println!("警告: 未检测到支持多窗口的环境，将按顺序连接");
                 
                 for window in &session_config.windows {
-                    let server_config = find_server(&config_manager, &window.server)?;
+                    let server_config = find_server(config_manager, &window.server)?.with_resolved_secrets()?;
+                    audit::record(&server_config, "session", window.command.as_deref())?;
                     
                     println!("连接到 {}", server_config.name.bright_green());
                     
@@ -1058,24 +4603,42 @@ pub fn run() -> Result<()> {
                         }
                     }
                 }
+                Ok(())
             }
-        },
+        }
     }
-    
-    Ok(())
 }
 
-fn find_server(config_manager: &ConfigManager, server_id_or_name: &str) -> Result<ServerConfig> {
-    let server_config = config_manager.get_server(server_id_or_name)?;
-    
-    let server_config = if server_config.is_none() {
-        let servers = config_manager.list_servers()?;
-        servers.into_iter().find(|s| s.name == server_id_or_name)
-    } else {
-        server_config
-    };
-    
-    server_config.ok_or_else(|| anyhow::anyhow!("未找到服务器: {}", server_id_or_name))
+/// 为分组内的服务器临时生成一个会话配置（每台服务器一个窗口），不写入会话目录
+fn group_session(config_manager: &ConfigManager, group: &str) -> Result<SessionConfig> {
+    let servers: Vec<ServerConfig> = config_manager
+        .list_servers()?
+        .into_iter()
+        .filter(|s| s.group.as_deref() == Some(group))
+        .collect();
+
+    if servers.is_empty() {
+        return Err(anyhow::anyhow!("分组 '{}' 中没有服务器", group));
+    }
+
+    let windows = servers
+        .iter()
+        .map(|s| SessionWindow {
+            title: Some(s.name.clone()),
+            server: s.id.clone(),
+            command: None,
+            position: None,
+            size: None,
+        })
+        .collect();
+
+    Ok(SessionConfig::new(
+        Uuid::new_v4().to_string(),
+        format!("group-{}", group),
+        None,
+        windows,
+        None,
+    ))
 }
 
 fn start_session_with_kitty(config_manager: &ConfigManager, session: &SessionConfig) -> Result<()> {
@@ -1085,8 +4648,8 @@ fn start_session_with_kitty(config_manager: &ConfigManager, session: &SessionCon
     
     println!("使用kitty启动会话: {}", session.name.bright_green());
     
-    let mut tmp_session_file = std::env::temp_dir();
-    tmp_session_file.push(format!("rssh_kitty_session_{}.conf", session.id));
+    // kitty 在后台启动后才读取会话文件，放在 rssh 退出后仍保留的用户临时目录中
+    let tmp_session_file = secure_temp::write_persistent(&format!("kitty_session_{}.conf", session.id), "", 0o600)?;
     let mut session_conf_writer = std::io::BufWriter::new(std::fs::File::create(&tmp_session_file)?);
     
     writeln!(session_conf_writer, "# RSSH会话配置: {}", session.name)?;
@@ -1094,82 +4657,45 @@ fn start_session_with_kitty(config_manager: &ConfigManager, session: &SessionCon
     writeln!(session_conf_writer, "layout splits")?;
     writeln!(session_conf_writer)?;
 
-    let current_rssh_path = std::env::current_exe()
-        .with_context(|| "无法获取当前rssh可执行文件路径")?;
+    let mut sftp_pool = SftpPool::default();
+    let control_path = control_path()?;
 
     for (i, window) in session.windows.iter().enumerate() {
-        let server_config = find_server(config_manager, &window.server)?;
+        let server_config = find_server(config_manager, &window.server)?.with_resolved_secrets()?;
+        audit::record(&server_config, "session", window.command.as_deref())?;
         let title = window.title.as_deref().unwrap_or(&window.server);
         let window_var = format!("window={}", i);
 
-        let mut base_ssh_args = format!("{}@{} -p {}", 
-            server_config.username, server_config.host, server_config.port);
-        if let Some(key_path) = server_config.auth_type.get_key_path() {
-            let expanded_key_path = crate::utils::ssh_config::expand_tilde(key_path);
-            base_ssh_args.push_str(&format!(" -i \"{}\"", expanded_key_path)); 
-        }
+        let ssh_command = SshCommand::new(&server_config).control_master(&control_path).with_config_file(&get_ssh_config_dir()?)?.arg("-t");
 
-        let final_ssh_payload = if let Some(cmd) = &window.command {
+        let ssh_command = if let Some(cmd) = &window.command {
             println!("  处理窗口 '{}': 找到命令, 准备上传脚本...", title);
             let unique_id = format!("{}_{}", session.id.split('-').next().unwrap_or("session"), i);
-            let local_script_path = std::env::temp_dir().join(format!("rssh_local_init_{}.sh", unique_id));
             let remote_script_path = format!("/tmp/rssh_remote_init_{}.sh", unique_id);
-
             let script_content = format!("#!/bin/sh\nset -e\nexport TERM=xterm-kitty\n{}\n", cmd);
-            std::fs::write(&local_script_path, &script_content)
-                 .with_context(|| format!("创建本地初始化脚本失败: {}", local_script_path.display()))?;
-            println!("    本地脚本: {}", local_script_path.display());
 
+            // 同一台服务器的多个窗口复用同一个 SFTP 连接，不再为每个窗口启动一次 rssh upload
             println!("    尝试上传到: {}@{}...", server_config.username, remote_script_path);
-            let mut upload_command = Command::new(&current_rssh_path);
-            upload_command
-                .arg("upload")
-                .arg(&window.server)
-                .arg(&local_script_path)
-                .arg(&remote_script_path);
-            
-            let upload_result = upload_command
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output();
-
-            let _ = std::fs::remove_file(&local_script_path);
-            println!("    本地临时脚本已删除: {}", local_script_path.display());
-
-            match upload_result {
-                Ok(upload_output) => {
-                    if upload_output.status.success() {
-                        println!("    上传成功 (退出码 0).");
-                        let remote_script_escaped = shell_escape::escape(remote_script_path.into());
-                        format!(
-                            "'while [ ! -f {} ]; do sleep 0.1; done; chmod +x {} && {} && rm {} ; exec $SHELL'",
-                            remote_script_escaped,
-                            remote_script_escaped,
-                            remote_script_escaped,
-                            remote_script_escaped
-                        )
-                    } else {
-                        eprintln!("    [Error] 上传失败 (退出码: {:?}). 将只启动交互式 shell.", upload_output.status.code());
-                        if !upload_output.stdout.is_empty() {
-                            eprintln!("      Upload stdout: {}", String::from_utf8_lossy(&upload_output.stdout));
-                        }
-                        if !upload_output.stderr.is_empty() {
-                            eprintln!("      Upload stderr: {}", String::from_utf8_lossy(&upload_output.stderr));
-                        }
-                        "''".to_string()
-                    }
+            match sftp_pool.write_file(&server_config, Path::new(&remote_script_path), script_content.as_bytes(), 0o700) {
+                Ok(()) => {
+                    println!("    上传成功.");
+                    let remote_script_escaped = shell_escape::escape(remote_script_path.into());
+                    ssh_command.command(format!(
+                        "while [ ! -f {0} ]; do sleep 0.1; done; chmod +x {0} && {0} && rm {0} ; exec $SHELL",
+                        remote_script_escaped
+                    ))
                 },
                 Err(e) => {
-                     eprintln!("    [Error] 执行 'rssh upload' 命令本身失败: {}. 将只启动交互式 shell.", e);
-                     "''".to_string()
+                    eprintln!("    [Error] 上传失败: {:#}. 将只启动交互式 shell.", e);
+                    ssh_command
                 }
             }
         } else {
              println!("  处理窗口 '{}': 无初始命令，直接启动交互式 shell.", title);
-            "''".to_string()
+            ssh_command
         };
 
-        let final_ssh_cmd = format!("ssh -t {} {}", base_ssh_args, final_ssh_payload);
+        let final_ssh_cmd = ssh_command.to_string();
         println!("    最终 SSH 命令: {}", final_ssh_cmd);
 
         if i == 0 {
@@ -1195,8 +4721,7 @@ fn start_session_with_kitty(config_manager: &ConfigManager, session: &SessionCon
     drop(session_conf_writer);
     println!("临时会话配置文件已生成: {}", tmp_session_file.display());
 
-    let mut launch_script_path = std::env::temp_dir();
-    launch_script_path.push(format!("rssh_kitty_launch_{}.sh", session.id));
+    let launch_script_path = secure_temp::write(&format!("kitty_launch_{}.sh", session.id), "", 0o700)?;
     let mut script = std::fs::File::create(&launch_script_path)?;
 
     writeln!(script, "#!/bin/sh")?;
@@ -1208,9 +4733,6 @@ fn start_session_with_kitty(config_manager: &ConfigManager, session: &SessionCon
     
     script.flush()?;
     drop(script);
-    let mut perms = std::fs::metadata(&launch_script_path)?.permissions();
-    perms.set_mode(0o755);
-    std::fs::set_permissions(&launch_script_path, perms)?;
     println!("临时启动脚本已生成: {}", launch_script_path.display());
 
     println!("执行启动脚本以打开 Kitty 窗口...");
@@ -1242,7 +4764,7 @@ fn start_session_with_tmux(config_manager: &ConfigManager, session: &SessionConf
     
     println!("使用tmux启动会话: {}", session.name.bright_green());
     
-    let tmux_session_name = format!("rssh_{}", session.id.split('-').next().unwrap_or("session"));
+    let tmux_session_name = share::tmux_session_name(session);
     
     let create_status = std::process::Command::new("tmux")
         .args(["new-session", "-d", "-s", &tmux_session_name])
@@ -1253,21 +4775,17 @@ fn start_session_with_tmux(config_manager: &ConfigManager, session: &SessionConf
         return Err(anyhow::anyhow!("无法创建tmux会话"));
     }
     
+    let control_path = control_path()?;
+
     for (i, window) in session.windows.iter().enumerate() {
-        let server_config = find_server(config_manager, &window.server)?;
-        
-        let mut ssh_cmd = format!("ssh {}@{} -p {}", 
-            server_config.username, 
-            server_config.host, 
-            server_config.port);
-        
-        if let Some(key_path) = server_config.auth_type.get_key_path() {
-            ssh_cmd.push_str(&format!(" -i {}", key_path));
-        }
+        let server_config = find_server(config_manager, &window.server)?.with_resolved_secrets()?;
+        audit::record(&server_config, "session", window.command.as_deref())?;
         
+        let mut ssh_command = SshCommand::new(&server_config).control_master(&control_path).with_config_file(&get_ssh_config_dir()?)?;
         if let Some(cmd) = &window.command {
-            ssh_cmd.push_str(&format!(" '{}'", cmd.replace("'", "'\''")));
+            ssh_command = ssh_command.command(cmd.clone());
         }
+        let ssh_cmd = ssh_command.to_string();
         
         let title = window.title.as_deref().unwrap_or(&window.server);
         
@@ -1306,25 +4824,17 @@ fn start_session_with_wezterm(config_manager: &ConfigManager, session: &SessionC
     println!("使用 wezterm 启动会话: {}", session.name.bright_green());
 
     let mut prev_pane_id: Option<String> = None;
+    let control_path = control_path()?;
 
     for (i, window) in session.windows.iter().enumerate() {
-        let server_config = find_server(config_manager, &window.server)?;
+        let server_config = find_server(config_manager, &window.server)?.with_resolved_secrets()?;
+        audit::record(&server_config, "session", window.command.as_deref())?;
         let title = window.title.as_deref().unwrap_or(&window.server);
 
-        let mut ssh_cmd = format!(
-            "ssh {}@{} -p {}",
-            server_config.username, server_config.host, server_config.port
-        );
-        if let Some(key_path) = server_config.auth_type.get_key_path() {
-            let expanded = crate::utils::ssh_config::expand_tilde(key_path);
-            ssh_cmd.push_str(&format!(" -i {}", shell_escape::escape(expanded.into())));
-        }
-
-        let final_cmd = if let Some(cmd) = &window.command {
-            let escaped = cmd.replace('\'', "'\\''");
-            format!("{} -t '{}; exec $SHELL'", ssh_cmd, escaped)
-        } else {
-            ssh_cmd
+        let ssh_command = SshCommand::new(&server_config).control_master(&control_path).with_config_file(&get_ssh_config_dir()?)?;
+        let final_cmd = match &window.command {
+            Some(cmd) => ssh_command.arg("-t").command(format!("{}; exec $SHELL", cmd)).to_string(),
+            None => ssh_command.to_string(),
         };
 
         let bash_wrapper = format!("{}; exec $SHELL", final_cmd);
@@ -1372,3 +4882,61 @@ fn start_session_with_wezterm(config_manager: &ConfigManager, session: &SessionC
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_one_off_commands_by_connection_mode() {
+        let cli = Cli::try_parse_from(["rssh", "connect", "web", "--mode", "russh", "--", "df", "-h"]).unwrap();
+        let Commands::Connect { mode, command, read_only, .. } = cli.command else {
+            panic!("应解析为 connect");
+        };
+        assert_eq!((mode, command, read_only), (Some(ConnectionMode::Russh), vec!["df".to_string(), "-h".to_string()], false));
+        assert!(Cli::try_parse_from(["rssh", "connect", "web", "--rzsz", "--", "uptime"]).is_err());
+
+        // 不支持执行命令的连接方式在连接之前报错
+        let mut server =
+            ServerConfig::new("1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, None, None, None);
+        assert!(run_with_mode(&server, Some(ConnectionMode::Telnet), "uptime".into()).is_err());
+        server.connection_type = ConnectionType::Telnet;
+        assert!(run_with_mode(&server, None, "uptime".into()).is_err());
+    }
+
+    #[test]
+    fn parses_repeated_local_forwards_for_connect() {
+        let cli = Cli::try_parse_from(["rssh", "connect", "web", "-L", "8080:localhost:80", "--local-forward", "0.0.0.0:5432:db.internal:5432"])
+            .unwrap();
+        let Commands::Connect { local_forward, .. } = cli.command else {
+            panic!("应解析为 connect");
+        };
+        let forwards: Vec<Forward> = local_forward.iter().map(|spec| Forward::parse(ForwardKind::Local, spec).unwrap()).collect();
+        assert_eq!(
+            forwards.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["-L 127.0.0.1:8080 -> localhost:80", "-L 0.0.0.0:5432 -> db.internal:5432"]
+        );
+        assert!(Cli::try_parse_from(["rssh", "connect", "web", "--group", "prod", "-L", "8080:localhost:80"]).is_err());
+    }
+
+    #[test]
+    fn parses_login_duration() {
+        let duration = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Commands::Login { duration } => duration,
+            _ => panic!("应解析为 login"),
+        };
+        assert_eq!(duration(&["rssh", "login"]), std::time::Duration::from_secs(15 * 60));
+        assert_eq!(duration(&["rssh", "login", "--duration", "8h"]), std::time::Duration::from_secs(8 * 3600));
+        assert!(Cli::try_parse_from(["rssh", "login", "-d", "soon"]).is_err());
+        assert!(matches!(Cli::try_parse_from(["rssh", "logout"]).unwrap().command, Commands::Logout));
+    }
+
+    #[test]
+    fn maps_ssh_verbosity_to_russh_log_level() {
+        let levels: Vec<_> = (0..=4).map(russh_log_filter).collect();
+        assert_eq!(
+            levels,
+            [log::LevelFilter::Off, log::LevelFilter::Info, log::LevelFilter::Debug, log::LevelFilter::Trace, log::LevelFilter::Trace]
+        );
+    }
+}
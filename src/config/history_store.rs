@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::PathBuf;
+
+/// 最多保留的连接记录条数
+const MAX_ENTRIES: i64 = 200;
+
+/// 一次交互式连接的记录，`rssh last` / `rssh connect -` 按同样的方式重新连接
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub server_id: String,
+    /// `--mode` 的取值，未指定时为 None
+    pub mode: Option<String>,
+    pub no_mux: bool,
+    pub compress: bool,
+    pub rzsz: bool,
+    pub read_only: bool,
+    pub connected_at: i64,
+}
+
+/// 连接历史，与服务器配置共用同一个数据库文件
+pub struct HistoryStore {
+    conn: Connection,
+}
+
+impl HistoryStore {
+    pub fn new(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("无法打开数据库 {}", db_path.display()))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS connection_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                server_id TEXT NOT NULL,
+                mode TEXT,
+                no_mux INTEGER NOT NULL DEFAULT 0,
+                compress INTEGER NOT NULL DEFAULT 0,
+                rzsz INTEGER NOT NULL DEFAULT 0,
+                read_only INTEGER NOT NULL DEFAULT 0,
+                connected_at INTEGER NOT NULL
+            );",
+        )?;
+
+        Ok(HistoryStore { conn })
+    }
+
+    /// 记录一次连接，只保留最近的 `MAX_ENTRIES` 条
+    pub fn record(&self, entry: &HistoryEntry) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO connection_history (server_id, mode, no_mux, compress, rzsz, read_only, connected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                entry.server_id,
+                entry.mode,
+                entry.no_mux,
+                entry.compress,
+                entry.rzsz,
+                entry.read_only,
+                entry.connected_at
+            ],
+        )?;
+        self.conn.execute(
+            "DELETE FROM connection_history WHERE id <= (SELECT MAX(id) FROM connection_history) - ?1",
+            params![MAX_ENTRIES],
+        )?;
+        Ok(())
+    }
+
+    /// 最近一次连接
+    pub fn last(&self) -> Result<Option<HistoryEntry>> {
+        let entry = self
+            .conn
+            .query_row(
+                "SELECT server_id, mode, no_mux, compress, rzsz, read_only, connected_at
+                 FROM connection_history ORDER BY id DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok(HistoryEntry {
+                        server_id: row.get(0)?,
+                        mode: row.get(1)?,
+                        no_mux: row.get(2)?,
+                        compress: row.get(3)?,
+                        rzsz: row.get(4)?,
+                        read_only: row.get(5)?,
+                        connected_at: row.get(6)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_most_recent_entry_and_trims_old_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().join("test.db")).unwrap();
+        assert_eq!(store.last().unwrap(), None);
+
+        let entry = |server_id: &str, connected_at| HistoryEntry {
+            server_id: server_id.into(),
+            mode: None,
+            no_mux: false,
+            compress: false,
+            rzsz: false,
+            read_only: false,
+            connected_at,
+        };
+        for i in 0..MAX_ENTRIES + 5 {
+            store.record(&entry("web", i)).unwrap();
+        }
+        let db = HistoryEntry { mode: Some("russh".into()), compress: true, ..entry("db", 1000) };
+        store.record(&db).unwrap();
+
+        assert_eq!(store.last().unwrap(), Some(db));
+        let count: i64 = store
+            .conn
+            .query_row("SELECT COUNT(*) FROM connection_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::path::PathBuf;
+
+use crate::models::{Job, JobHostRecord, JobHostStatus};
+
+/// 批量执行任务的结果存储，与服务器配置共用同一个数据库文件
+pub struct JobStore {
+    conn: Connection,
+}
+
+/// jobs 表的查询列（含按状态统计的主机数），`row_to_job` 依赖这里的顺序
+const JOB_COLUMNS: &str = "j.id, j.kind, j.command, j.target, j.parent_id, j.started_at, j.finished_at, \
+     (SELECT COUNT(*) FROM job_hosts h WHERE h.job_id = j.id), \
+     (SELECT COUNT(*) FROM job_hosts h WHERE h.job_id = j.id AND h.status = 'success'), \
+     (SELECT COUNT(*) FROM job_hosts h WHERE h.job_id = j.id AND h.status = 'failed')";
+
+impl JobStore {
+    pub fn new(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("无法打开数据库 {}", db_path.display()))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                command TEXT NOT NULL,
+                target TEXT,
+                parent_id TEXT,
+                started_at INTEGER NOT NULL,
+                finished_at INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS job_hosts (
+                job_id TEXT NOT NULL,
+                server_id TEXT NOT NULL,
+                server_name TEXT NOT NULL,
+                status TEXT NOT NULL,
+                stdout TEXT NOT NULL DEFAULT '',
+                stderr TEXT NOT NULL DEFAULT '',
+                exit_code INTEGER,
+                error TEXT,
+                duration_ms INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (job_id, server_id)
+            );",
+        )?;
+
+        Ok(JobStore { conn })
+    }
+
+    /// 创建任务，所有目标主机先记为未执行；这样任务中途被中断时也能知道哪些主机还没跑
+    pub fn create_job(
+        &self,
+        kind: &str,
+        command: &str,
+        target: Option<&str>,
+        parent_id: Option<&str>,
+        hosts: &[(String, String)],
+    ) -> Result<String> {
+        let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+
+        self.conn.execute(
+            "INSERT INTO jobs (id, kind, command, target, parent_id, started_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, kind, command, target, parent_id, chrono::Utc::now().timestamp()],
+        )?;
+        for (server_id, server_name) in hosts {
+            self.conn.execute(
+                "INSERT INTO job_hosts (job_id, server_id, server_name, status) VALUES (?1, ?2, ?3, ?4)",
+                params![id, server_id, server_name, JobHostStatus::Pending.as_str()],
+            )?;
+        }
+
+        Ok(id)
+    }
+
+    /// 记录单台主机的结果
+    pub fn record_result(&self, job_id: &str, record: &JobHostRecord) -> Result<()> {
+        self.conn.execute(
+            "UPDATE job_hosts SET status = ?3, stdout = ?4, stderr = ?5, exit_code = ?6, error = ?7, duration_ms = ?8
+             WHERE job_id = ?1 AND server_id = ?2",
+            params![
+                job_id,
+                record.server_id,
+                record.status.as_str(),
+                record.stdout,
+                record.stderr,
+                record.exit_code,
+                record.error,
+                record.duration_ms as i64,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn finish_job(&self, job_id: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE jobs SET finished_at = ?2 WHERE id = ?1",
+            params![job_id, chrono::Utc::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// 最近的任务，新的在前
+    pub fn list_jobs(&self, limit: usize) -> Result<Vec<Job>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM jobs j ORDER BY j.started_at DESC, j.rowid DESC LIMIT ?1",
+            JOB_COLUMNS
+        ))?;
+        let jobs = stmt
+            .query_map(params![limit as i64], Self::row_to_job)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(jobs)
+    }
+
+    /// 按 ID 或 ID 前缀查找任务；`last` 表示最近一次任务
+    pub fn find_job(&self, id: &str) -> Result<Option<Job>> {
+        if id == "last" {
+            return Ok(self.list_jobs(1)?.into_iter().next());
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM jobs j WHERE j.id LIKE ?1 || '%' LIMIT 2",
+            JOB_COLUMNS
+        ))?;
+        let mut jobs = stmt
+            .query_map(params![id], Self::row_to_job)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if jobs.len() > 1 {
+            return Err(anyhow::anyhow!("任务 ID 前缀 {} 不唯一，请输入更长的 ID", id));
+        }
+        Ok(jobs.pop())
+    }
+
+    /// 任务中各主机的结果，按主机名排序
+    pub fn job_hosts(&self, job_id: &str) -> Result<Vec<JobHostRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT server_id, server_name, status, stdout, stderr, exit_code, error, duration_ms
+             FROM job_hosts WHERE job_id = ?1 ORDER BY server_name",
+        )?;
+        let records = stmt
+            .query_map(params![job_id], |row| {
+                let status: String = row.get(2)?;
+                let duration_ms: i64 = row.get(7)?;
+                Ok(JobHostRecord {
+                    server_id: row.get(0)?,
+                    server_name: row.get(1)?,
+                    status: JobHostStatus::parse(&status),
+                    stdout: row.get(3)?,
+                    stderr: row.get(4)?,
+                    exit_code: row.get(5)?,
+                    error: row.get(6)?,
+                    duration_ms: duration_ms.max(0) as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    /// 删除在 `before`（Unix 时间戳）之前开始且已结束的任务及其主机结果，返回删除的任务数。
+    /// `dry_run` 时只统计不删除
+    pub fn prune_before(&self, before: i64, dry_run: bool) -> Result<usize> {
+        const CONDITION: &str = "started_at < ?1 AND finished_at IS NOT NULL";
+        if dry_run {
+            let count: i64 = self.conn.query_row(&format!("SELECT COUNT(*) FROM jobs WHERE {}", CONDITION), params![before], |row| row.get(0))?;
+            return Ok(count as usize);
+        }
+        self.conn.execute(
+            &format!("DELETE FROM job_hosts WHERE job_id IN (SELECT id FROM jobs WHERE {})", CONDITION),
+            params![before],
+        )?;
+        let removed = self.conn.execute(&format!("DELETE FROM jobs WHERE {}", CONDITION), params![before])?;
+        Ok(removed)
+    }
+
+    fn row_to_job(row: &rusqlite::Row) -> rusqlite::Result<Job> {
+        let total: i64 = row.get(7)?;
+        let succeeded: i64 = row.get(8)?;
+        let failed: i64 = row.get(9)?;
+        Ok(Job {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            command: row.get(2)?,
+            target: row.get(3)?,
+            parent_id: row.get(4)?,
+            started_at: row.get(5)?,
+            finished_at: row.get(6)?,
+            total: total as usize,
+            succeeded: succeeded as usize,
+            failed: failed as usize,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_pending_and_failed_hosts() {
+        let base = std::env::temp_dir().join(format!("rssh-test-jobs-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        let store = JobStore::new(base.join("test.db")).unwrap();
+
+        let hosts: Vec<(String, String)> = ["web1", "web2", "web3"]
+            .iter()
+            .map(|n| (n.to_string(), n.to_string()))
+            .collect();
+        let id = store.create_job("exec", "uptime", Some("web"), None, &hosts).unwrap();
+
+        let record = |name: &str, status, code| JobHostRecord {
+            server_id: name.into(),
+            server_name: name.into(),
+            status,
+            stdout: "ok\n".into(),
+            stderr: String::new(),
+            exit_code: Some(code),
+            error: None,
+            duration_ms: 12,
+        };
+        store.record_result(&id, &record("web1", JobHostStatus::Success, 0)).unwrap();
+        store.record_result(&id, &record("web2", JobHostStatus::Failed, 2)).unwrap();
+
+        // web3 未执行（例如任务被 Ctrl+C 中断）
+        let job = store.find_job(&id[..4]).unwrap().unwrap();
+        assert_eq!((job.total, job.succeeded, job.failed, job.pending()), (3, 1, 1, 1));
+        assert!(job.finished_at.is_none());
+
+        let hosts = store.job_hosts(&id).unwrap();
+        assert_eq!(hosts[1].exit_code, Some(2));
+        assert_eq!(hosts[2].status, JobHostStatus::Pending);
+
+        store.finish_job(&id).unwrap();
+        assert!(store.find_job("last").unwrap().unwrap().finished_at.is_some());
+
+        let now = chrono::Utc::now().timestamp();
+        let running = store.create_job("exec", "sleep 1", None, None, &[]).unwrap();
+        assert_eq!(store.prune_before(now - 60, false).unwrap(), 0);
+        assert_eq!(store.prune_before(now + 60, true).unwrap(), 1);
+        assert_eq!(store.prune_before(now + 60, false).unwrap(), 1);
+        assert!(store.find_job(&id).unwrap().is_none());
+        assert!(store.job_hosts(&id).unwrap().is_empty());
+        // 未结束的任务不删除
+        assert!(store.find_job(&running).unwrap().is_some());
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+}
@@ -1,27 +1,100 @@
 use anyhow::{Context, Result};
-use rusqlite::{Connection, params};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use rusqlite::{Connection, TransactionBehavior, params};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::fs;
 
-use crate::models::{AuthType, ServerConfig};
+use crate::models::{Algorithms, AntiIdle, AuthType, CommandPolicy, ConnectionType, DialogStep, ForwardAgent, HostKeyPolicy, LoginBanner, PromptPatterns, ServerConfig};
+use crate::utils::credential_vault::{self, VaultMeta};
+use crate::utils::secret::{is_secret_reference, resolve_secret};
 use crate::utils::ssh_config::{expand_tilde, sanitize_host_alias};
 
 pub struct ConfigManager {
     conn: Arc<Mutex<Connection>>,
 }
 
+/// 密码的保存位置（`config.toml` 的 `credential_backend`，`rssh config set credential-backend keychain`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialBackend {
+    /// 保存在 servers.db 中，启用凭据库时加密
+    #[default]
+    Db,
+    /// 保存到系统钥匙串（macOS 钥匙串 / libsecret / Windows 凭据管理器），数据库中只保存
+    /// `keychain:<账户>` 引用，连接时由 `keychain` 提供者取回
+    Keychain,
+}
+
+static CREDENTIAL_BACKEND: RwLock<CredentialBackend> = RwLock::new(CredentialBackend::Db);
+
+impl CredentialBackend {
+    /// 设置密码的保存位置，启动时调用；`rssh config set credential-backend` 切换后再次调用以迁移已有密码
+    pub fn set_default(backend: Self) {
+        *CREDENTIAL_BACKEND.write().unwrap() = backend;
+    }
+
+    pub fn global_default() -> Self {
+        *CREDENTIAL_BACKEND.read().unwrap()
+    }
+}
+
+/// 钥匙串中的服务名，与 `keychain:` 引用省略服务时的默认值一致。
+/// 账户为服务器 ID，备用密码为 `<ID>-password`
+const KEYCHAIN_SERVICE: &str = "rssh";
+
+/// servers 表的查询列，`row_to_server` 依赖这里的顺序
+const SERVER_COLUMNS: &str = "id, name, host, port, username, auth_type, auth_data, password, group_name, description, \
+     secret_provider, connection_type, connection_data, compression, \
+     ciphers, kex_algorithms, macs, host_key_algorithms, host_key_policy, login_banner, note, forward_agent, password_changed_at, \
+     alt_hosts, preferred_host, internal_host, network, jump_host, allowed_commands, denied_commands, aliases, slot, password_prompt, ready_prompt, login_dialog, \
+     identity_files, identities_only, proxy_jump, proxy_command, extra_options, anti_idle";
+
+/// 在已有数据库上追加的列（列名, 类型），打开数据库时按需补齐
+const MIGRATION_COLUMNS: &[(&str, &str)] = &[
+    ("password", "TEXT"),
+    ("secret_provider", "TEXT"),
+    ("connection_type", "TEXT"),
+    ("connection_data", "TEXT"),
+    ("compression", "INTEGER"),
+    ("ciphers", "TEXT"),
+    ("kex_algorithms", "TEXT"),
+    ("macs", "TEXT"),
+    ("host_key_algorithms", "TEXT"),
+    ("host_key_policy", "TEXT"),
+    ("login_banner", "TEXT"),
+    ("note", "TEXT"),
+    ("forward_agent", "TEXT"),
+    ("password_changed_at", "INTEGER"),
+    ("alt_hosts", "TEXT"),
+    ("preferred_host", "TEXT"),
+    ("internal_host", "TEXT"),
+    ("network", "TEXT"),
+    ("jump_host", "TEXT"),
+    ("allowed_commands", "TEXT"),
+    ("denied_commands", "TEXT"),
+    ("aliases", "TEXT"),
+    ("slot", "INTEGER"),
+    ("password_prompt", "TEXT"),
+    ("ready_prompt", "TEXT"),
+    ("login_dialog", "TEXT"),
+    ("identity_files", "TEXT"),
+    ("identities_only", "INTEGER"),
+    ("proxy_jump", "TEXT"),
+    ("proxy_command", "TEXT"),
+    ("extra_options", "TEXT"),
+    ("anti_idle", "TEXT"),
+];
+
 impl ConfigManager {
     pub fn new(db_path: PathBuf) -> Result<Self> {
-        let create_db = !db_path.exists();
-        
         let conn = Connection::open(&db_path)
             .with_context(|| format!("无法打开数据库 {}", db_path.display()))?;
         
-        if create_db {
-            Self::init_database(&conn)?;
-        }
+        // 新库建表，旧库补齐缺失的列
+        Self::init_database(&conn)?;
         
         Ok(ConfigManager {
             conn: Arc::new(Mutex::new(conn)),
@@ -50,31 +123,263 @@ impl ConfigManager {
                 )",
                 [],
             )?;
-        } else {
-            // 如果表存在，检查是否需要添加 password 列
-            let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('servers') WHERE name = 'password'")?;
-            let has_password = stmt.exists([])?;
-            
-            if !has_password {
-                conn.execute("ALTER TABLE servers ADD COLUMN password TEXT", [])?;
+        }
+
+        // 检查是否需要添加新列
+        for (column, column_type) in MIGRATION_COLUMNS {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('servers') WHERE name = ?1")?;
+            if !stmt.exists(params![column])? {
+                conn.execute(&format!("ALTER TABLE servers ADD COLUMN {} {}", column, column_type), [])?;
+                if *column == "secret_provider" {
+                    Self::keep_literal_passwords(conn)?;
+                }
             }
         }
+
+        // 凭据库参数，最多一行
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS vault (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                salt TEXT NOT NULL,
+                iterations INTEGER NOT NULL,
+                check_value TEXT NOT NULL
+            )",
+            [],
+        )?;
         
         Ok(())
     }
-    
-    pub fn add_server(&self, server: ServerConfig) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+
+    /// 加入密钥提供者之前只有 `env:`/`cmd:` 是引用，那时保存的 `pass:`、`op:` 等开头的密码是明文。
+    /// 补上 `secret_provider` 列时把这些服务器的提供者设为 `db`，连接时仍按明文使用
+    fn keep_literal_passwords(conn: &Connection) -> Result<()> {
+        let was_literal = |value: &Option<String>| {
+            value.as_deref().is_some_and(|v| is_secret_reference(v) && !v.starts_with("env:") && !v.starts_with("cmd:"))
+        };
+        let mut stmt = conn.prepare("SELECT id, auth_type, auth_data, password FROM servers")?;
+        let literal: Vec<String> = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?))
+            })?
+            .filter_map(|row| row.ok())
+            .filter(|(_, auth_type, auth_data, password)| (auth_type == "password" && was_literal(auth_data)) || was_literal(password))
+            .map(|(id, ..)| id)
+            .collect();
+        for id in literal {
+            conn.execute("UPDATE servers SET secret_provider = 'db' WHERE id = ?1", params![id])?;
+        }
+        Ok(())
+    }
+
+    /// 将查询行（按 `SERVER_COLUMNS` 顺序）转换为服务器配置
+    fn row_to_server(row: &rusqlite::Row) -> rusqlite::Result<ServerConfig> {
+        let auth_type: String = row.get(5)?;
+        let auth_data: Option<String> = row.get(6)?;
+        let password: Option<String> = row.get(7)?;
         
-        let (auth_type, auth_data) = match &server.auth_type {
-            AuthType::Password(pwd) => ("password", Some(pwd.clone())),
+        let auth = match (auth_type.as_str(), auth_data) {
+            ("password", Some(pwd)) => AuthType::Password(pwd),
+            ("key", Some(key_path)) => AuthType::Key(key_path),
+            ("agent", _) => AuthType::Agent,
+            _ => return Err(rusqlite::Error::InvalidColumnName("未知的认证类型".into())),
+        };
+
+        // 旧数据没有这两列，视为 SSH
+        let connection_type: Option<String> = row.get(11)?;
+        let connection_data: Option<String> = row.get(12)?;
+        let connection_type = match connection_type.as_deref() {
+            None | Some("ssh") => ConnectionType::Ssh,
+            Some("serial") => ConnectionType::Serial {
+                baud: connection_data
+                    .and_then(|b| b.parse().ok())
+                    .unwrap_or(ConnectionType::DEFAULT_BAUD),
+            },
+            Some("ipmi") => ConnectionType::Ipmi,
+            Some("telnet") => ConnectionType::Telnet,
+            Some(_) => return Err(rusqlite::Error::InvalidColumnName("未知的连接类型".into())),
+        };
+        
+        Ok(ServerConfig {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            host: row.get(2)?,
+            port: row.get(3)?,
+            username: row.get(4)?,
+            auth_type: auth,
+            password,
+            group: row.get(8)?,
+            description: row.get(9)?,
+            secret_provider: row.get(10)?,
+            connection_type,
+            compression: row.get::<_, Option<bool>>(13)?.unwrap_or(false),
+            algorithms: Algorithms {
+                ciphers: row.get(14)?,
+                kex: row.get(15)?,
+                macs: row.get(16)?,
+                host_key: row.get(17)?,
+            },
+            host_key_policy: row
+                .get::<_, Option<String>>(18)?
+                .and_then(|policy| HostKeyPolicy::parse(&policy).ok()),
+            login_banner: row
+                .get::<_, Option<String>>(19)?
+                .and_then(|banner| LoginBanner::parse(&banner).ok())
+                .unwrap_or_default(),
+            note: row.get(20)?,
+            forward_agent: row
+                .get::<_, Option<String>>(21)?
+                .and_then(|forward| ForwardAgent::parse(&forward).ok())
+                .unwrap_or_default(),
+            password_changed_at: row.get(22)?,
+            alt_hosts: row
+                .get::<_, Option<String>>(23)?
+                .map(|hosts| hosts.split(',').filter(|h| !h.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            preferred_host: row.get(24)?,
+            internal_host: row.get(25)?,
+            network: row.get(26)?,
+            jump_host: row.get(27)?,
+            command_policy: CommandPolicy {
+                allow: Self::split_lines(row.get(28)?),
+                deny: Self::split_lines(row.get(29)?),
+            },
+            aliases: row
+                .get::<_, Option<String>>(30)?
+                .map(|aliases| aliases.split(',').filter(|a| !a.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            slot: row.get(31)?,
+            prompts: PromptPatterns {
+                password: row.get(32)?,
+                ready: row.get(33)?,
+            },
+            login_dialog: row
+                .get::<_, Option<String>>(34)?
+                .and_then(|dialog| serde_json::from_str(&dialog).ok())
+                .unwrap_or_default(),
+            identity_files: Self::split_lines(row.get(35)?),
+            identities_only: row.get::<_, Option<bool>>(36)?.unwrap_or(false),
+            proxy_jump: row.get(37)?,
+            proxy_command: row.get(38)?,
+            extra_options: Self::split_lines(row.get(39)?),
+            anti_idle: row.get::<_, Option<String>>(40)?.and_then(|spec| AntiIdle::parse(&spec).ok()),
+            certificate: None,
+            local_forwards: Vec::new(),
+            jump_servers: Vec::new(),
+        })
+    }
+
+    /// 命令规则是正则表达式、私钥路径可能包含逗号，按行保存
+    fn split_lines(value: Option<String>) -> Vec<String> {
+        value
+            .map(|lines| lines.lines().filter(|l| !l.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    /// 认证方式列，密码按 `credential_backend` 保存到钥匙串或加密保存
+    fn auth_columns(server: &ServerConfig) -> Result<(&'static str, Option<String>)> {
+        Ok(match &server.auth_type {
+            AuthType::Password(pwd) => ("password", Some(Self::store_secret(server, &server.id, pwd)?)),
             AuthType::Key(key_path) => ("key", Some(key_path.clone())),
             AuthType::Agent => ("agent", None),
-        };
+        })
+    }
+
+    /// 配置了其它密钥提供者时保存的是引用，原样保存；提供者为 `db` 时一定是明文
+    fn store_secret(server: &ServerConfig, account: &str, value: &str) -> Result<String> {
+        match server.secret_provider.as_deref() {
+            Some("db") => Self::store_password(account, value, true),
+            Some(_) => Ok(value.to_string()),
+            None => Self::store_password(account, value, false),
+        }
+    }
+
+    /// 按 `credential_backend` 保存密码，返回写入数据库的值。
+    ///
+    /// 钥匙串模式下把明文（或凭据库加密的密码）写入钥匙串，返回引用；数据库模式下，
+    /// 之前保存到钥匙串中的密码取回后移出钥匙串，明文按凭据库的设置加密。其它密钥引用原样返回，
+    /// `literal` 为真（服务器的 `secret_provider` 为 `db`）时以引用前缀开头的值也按明文保存
+    fn store_password(account: &str, value: &str, literal: bool) -> Result<String> {
+        if value.is_empty() {
+            return Ok(value.to_string());
+        }
+        let own_reference = keychain_reference(account);
+        let encrypted = credential_vault::is_encrypted(value);
+        match CredentialBackend::global_default() {
+            CredentialBackend::Keychain => {
+                if value == own_reference || (!literal && is_secret_reference(value) && !encrypted) {
+                    return Ok(value.to_string());
+                }
+                let plain = if literal && !encrypted { value.to_string() } else { resolve_secret(value)? };
+                keychain_store(KEYCHAIN_SERVICE, account, &plain)?;
+                Ok(own_reference)
+            },
+            CredentialBackend::Db => {
+                if value != own_reference {
+                    return credential_vault::seal(value, literal);
+                }
+                let sealed = credential_vault::seal(&resolve_secret(value)?, true)?;
+                keychain_delete(KEYCHAIN_SERVICE, account)?;
+                Ok(sealed)
+            },
+        }
+    }
+
+    fn password_column(server: &ServerConfig) -> Result<Option<String>> {
+        let account = password_account(server);
+        server.password.as_deref().map(|password| Self::store_secret(server, &account, password)).transpose()
+    }
+
+    fn connection_columns(server: &ServerConfig) -> (&'static str, Option<String>) {
+        match &server.connection_type {
+            ConnectionType::Serial { baud } => ("serial", Some(baud.to_string())),
+            other => (other.name(), None),
+        }
+    }
+    
+    pub fn add_server(&self, server: ServerConfig) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::insert_server(&conn, &server)
+    }
+
+    /// 在一个事务中批量添加服务器（导入时使用）。
+    ///
+    /// 每条记录失败时不中断，收集到返回值中；只要有失败就整体回滚，数据库保持导入前的状态。
+    /// `on_progress(已处理, 总数)` 每处理一条调用一次。
+    pub fn add_servers(
+        &self,
+        servers: &[ServerConfig],
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> Result<Vec<ImportFailure>> {
+        let mut conn = self.conn.lock().unwrap();
+        // IMMEDIATE 事务在开始时就拿到写锁，避免与同时运行的其它 rssh 交错写入
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("无法开始导入事务，数据库可能正被其它 rssh 进程占用")?;
+
+        let mut failures = Vec::new();
+        for (index, server) in servers.iter().enumerate() {
+            if let Err(e) = Self::insert_server(&tx, server) {
+                failures.push(ImportFailure { name: server.name.clone(), error: format!("{:#}", e) });
+            }
+            on_progress(index + 1, servers.len());
+        }
+
+        if failures.is_empty() {
+            tx.commit().context("无法提交导入事务")?;
+        } else {
+            tx.rollback().context("无法回滚导入事务")?;
+        }
+        Ok(failures)
+    }
+
+    fn insert_server(conn: &Connection, server: &ServerConfig) -> Result<()> {
+        let (auth_type, auth_data) = Self::auth_columns(server)?;
+        let password = Self::password_column(server)?;
+        let (connection_type, connection_data) = Self::connection_columns(server);
         
         conn.execute(
-            "INSERT INTO servers (id, name, host, port, username, auth_type, auth_data, password, group_name, description)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            &format!("INSERT INTO servers ({})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38, ?39, ?40, ?41)", SERVER_COLUMNS),
             params![
                 server.id,
                 server.name,
@@ -83,9 +388,40 @@ impl ConfigManager {
                 server.username,
                 auth_type,
                 auth_data,
-                server.password,
+                password,
                 server.group,
                 server.description,
+                server.secret_provider,
+                connection_type,
+                connection_data,
+                server.compression,
+                server.algorithms.ciphers,
+                server.algorithms.kex,
+                server.algorithms.macs,
+                server.algorithms.host_key,
+                server.host_key_policy.map(|policy| policy.name()),
+                server.login_banner.name(),
+                server.note,
+                server.forward_agent.name(),
+                server.password_changed_at,
+                server.alt_hosts.join(","),
+                server.preferred_host,
+                server.internal_host,
+                server.network,
+                server.jump_host,
+                server.command_policy.allow.join("\n"),
+                server.command_policy.deny.join("\n"),
+                server.aliases.join(","),
+                server.slot,
+                server.prompts.password,
+                server.prompts.ready,
+                login_dialog_json(&server.login_dialog)?,
+                server.identity_files.join("\n"),
+                server.identities_only,
+                server.proxy_jump,
+                server.proxy_command,
+                server.extra_options.join("\n"),
+                server.anti_idle.map(|anti_idle| anti_idle.to_string()),
             ],
         )?;
         
@@ -95,35 +431,11 @@ impl ConfigManager {
     pub fn get_server(&self, id: &str) -> Result<Option<ServerConfig>> {
         let conn = self.conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, name, host, port, username, auth_type, auth_data, password, group_name, description
-             FROM servers WHERE id = ?1"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM servers WHERE id = ?1", SERVER_COLUMNS
+        ))?;
         
-        let server = stmt.query_row(params![id], |row| {
-            let auth_type: String = row.get(5)?;
-            let auth_data: Option<String> = row.get(6)?;
-            let password: Option<String> = row.get(7)?;
-            
-            let auth = match (auth_type.as_str(), auth_data) {
-                ("password", Some(pwd)) => AuthType::Password(pwd),
-                ("key", Some(key_path)) => AuthType::Key(key_path),
-                ("agent", _) => AuthType::Agent,
-                _ => return Err(rusqlite::Error::InvalidColumnName("未知的认证类型".into())),
-            };
-            
-            Ok(ServerConfig {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                host: row.get(2)?,
-                port: row.get(3)?,
-                username: row.get(4)?,
-                auth_type: auth,
-                password,
-                group: row.get(8)?,
-                description: row.get(9)?,
-            })
-        });
+        let server = stmt.query_row(params![id], Self::row_to_server);
         
         match server {
             Ok(s) => Ok(Some(s)),
@@ -132,38 +444,31 @@ impl ConfigManager {
         }
     }
     
+    /// 按 ID、名称、别名的顺序查找服务器
+    pub fn find_server(&self, id_name_or_alias: &str) -> Result<Option<ServerConfig>> {
+        if let Some(server) = self.get_server(id_name_or_alias)? {
+            return Ok(Some(server));
+        }
+        let servers = self.list_servers()?;
+        if let Some(index) = servers.iter().position(|s| s.name == id_name_or_alias) {
+            return Ok(servers.into_iter().nth(index));
+        }
+        Ok(servers.into_iter().find(|s| s.aliases.iter().any(|alias| alias == id_name_or_alias)))
+    }
+    
+    /// 快捷编号对应的服务器（`rssh 3`）
+    pub fn find_by_slot(&self, slot: u32) -> Result<Option<ServerConfig>> {
+        Ok(self.list_servers()?.into_iter().find(|s| s.slot == Some(slot)))
+    }
+    
     pub fn list_servers(&self) -> Result<Vec<ServerConfig>> {
         let conn = self.conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, name, host, port, username, auth_type, auth_data, password, group_name, description
-             FROM servers ORDER BY name"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM servers ORDER BY name", SERVER_COLUMNS
+        ))?;
         
-        let servers_iter = stmt.query_map([], |row| {
-            let auth_type: String = row.get(5)?;
-            let auth_data: Option<String> = row.get(6)?;
-            let password: Option<String> = row.get(7)?;
-            
-            let auth = match (auth_type.as_str(), auth_data) {
-                ("password", Some(pwd)) => AuthType::Password(pwd),
-                ("key", Some(key_path)) => AuthType::Key(key_path),
-                ("agent", _) => AuthType::Agent,
-                _ => return Err(rusqlite::Error::InvalidColumnName("未知的认证类型".into())),
-            };
-            
-            Ok(ServerConfig {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                host: row.get(2)?,
-                port: row.get(3)?,
-                username: row.get(4)?,
-                auth_type: auth,
-                password,
-                group: row.get(8)?,
-                description: row.get(9)?,
-            })
-        })?;
+        let servers_iter = stmt.query_map([], Self::row_to_server)?;
         
         let mut servers = Vec::new();
         for server in servers_iter {
@@ -174,9 +479,22 @@ impl ConfigManager {
     }
     
     pub fn remove_server(&self, id: &str) -> Result<bool> {
+        let removed = self.get_server(id)?;
         let conn = self.conn.lock().unwrap();
         
         let count = conn.execute("DELETE FROM servers WHERE id = ?1", params![id])?;
+        // 一并删除保存在钥匙串中的密码
+        if let Some(server) = removed {
+            let stored = [
+                (server.id.clone(), server.auth_type.password()),
+                (password_account(&server), server.password.as_deref()),
+            ];
+            for (account, value) in stored {
+                if value == Some(keychain_reference(&account).as_str()) {
+                    keychain_delete(KEYCHAIN_SERVICE, &account)?;
+                }
+            }
+        }
         
         Ok(count > 0)
     }
@@ -184,16 +502,22 @@ impl ConfigManager {
     pub fn update_server(&self, server: ServerConfig) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
         
-        let (auth_type, auth_data) = match &server.auth_type {
-            AuthType::Password(pwd) => ("password", Some(pwd.clone())),
-            AuthType::Key(key_path) => ("key", Some(key_path.clone())),
-            AuthType::Agent => ("agent", None),
-        };
+        let (auth_type, auth_data) = Self::auth_columns(&server)?;
+        let password = Self::password_column(&server)?;
+        let (connection_type, connection_data) = Self::connection_columns(&server);
         
         let count = conn.execute(
             "UPDATE servers 
              SET name = ?2, host = ?3, port = ?4, username = ?5, 
-                 auth_type = ?6, auth_data = ?7, password = ?8, group_name = ?9, description = ?10
+                 auth_type = ?6, auth_data = ?7, password = ?8, group_name = ?9, description = ?10,
+                 secret_provider = ?11, connection_type = ?12, connection_data = ?13, compression = ?14,
+                 ciphers = ?15, kex_algorithms = ?16, macs = ?17, host_key_algorithms = ?18,
+                 host_key_policy = ?19, login_banner = ?20, note = ?21, forward_agent = ?22, password_changed_at = ?23,
+                 alt_hosts = ?24, preferred_host = ?25, internal_host = ?26, network = ?27, jump_host = ?28,
+                 allowed_commands = ?29, denied_commands = ?30, aliases = ?31, slot = ?32,
+                 password_prompt = ?33, ready_prompt = ?34, login_dialog = ?35,
+                 identity_files = ?36, identities_only = ?37, proxy_jump = ?38,
+                 proxy_command = ?39, extra_options = ?40, anti_idle = ?41
              WHERE id = ?1",
             params![
                 server.id,
@@ -203,15 +527,104 @@ impl ConfigManager {
                 server.username,
                 auth_type,
                 auth_data,
-                server.password,
+                password,
                 server.group,
                 server.description,
+                server.secret_provider,
+                connection_type,
+                connection_data,
+                server.compression,
+                server.algorithms.ciphers,
+                server.algorithms.kex,
+                server.algorithms.macs,
+                server.algorithms.host_key,
+                server.host_key_policy.map(|policy| policy.name()),
+                server.login_banner.name(),
+                server.note,
+                server.forward_agent.name(),
+                server.password_changed_at,
+                server.alt_hosts.join(","),
+                server.preferred_host,
+                server.internal_host,
+                server.network,
+                server.jump_host,
+                server.command_policy.allow.join("\n"),
+                server.command_policy.deny.join("\n"),
+                server.aliases.join(","),
+                server.slot,
+                server.prompts.password,
+                server.prompts.ready,
+                login_dialog_json(&server.login_dialog)?,
+                server.identity_files.join("\n"),
+                server.identities_only,
+                server.proxy_jump,
+                server.proxy_command,
+                server.extra_options.join("\n"),
+                server.anti_idle.map(|anti_idle| anti_idle.to_string()),
             ],
         )?;
         
         Ok(count > 0)
     }
 
+    /// 凭据库参数，未执行 `rssh vault init` 时为 None
+    pub fn vault_meta(&self) -> Result<Option<VaultMeta>> {
+        let conn = self.conn.lock().unwrap();
+        let meta = conn.query_row("SELECT salt, iterations, check_value FROM vault WHERE id = 1", [], |row| {
+            Ok(VaultMeta { salt: row.get(0)?, iterations: row.get(1)?, check: row.get(2)? })
+        });
+        match meta {
+            Ok(meta) => Ok(Some(meta)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_vault_meta(&self, meta: &VaultMeta) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO vault (id, salt, iterations, check_value) VALUES (1, ?1, ?2, ?3)",
+            params![meta.salt, meta.iterations, meta.check],
+        )?;
+        Ok(())
+    }
+
+    /// 启用凭据库：在一个事务中保存凭据库参数并重新保存所有服务器的密码（即加密），
+    /// 中途失败时整体回滚，不会留下部分加密的数据库。返回服务器数量
+    pub fn init_vault(&self, meta: &VaultMeta) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("无法开始事务，数据库可能正被其它 rssh 进程占用")?;
+
+        tx.execute(
+            "INSERT INTO vault (id, salt, iterations, check_value) VALUES (1, ?1, ?2, ?3)",
+            params![meta.salt, meta.iterations, meta.check],
+        )?;
+        let servers = tx
+            .prepare(&format!("SELECT {} FROM servers", SERVER_COLUMNS))?
+            .query_map([], Self::row_to_server)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for server in &servers {
+            let (_, auth_data) = Self::auth_columns(server)?;
+            let password = Self::password_column(server)?;
+            tx.execute(
+                "UPDATE servers SET auth_data = ?2, password = ?3 WHERE id = ?1",
+                params![server.id, auth_data, password],
+            )?;
+        }
+
+        tx.commit().context("无法提交凭据库事务")?;
+        Ok(servers.len())
+    }
+
+    /// 记录上次连接成功的地址
+    pub fn set_preferred_host(&self, id: &str, host: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE servers SET preferred_host = ?2 WHERE id = ?1", params![id, host])?;
+        Ok(())
+    }
+
     pub fn export_config(&self, export_path: &PathBuf) -> Result<()> {
         // 创建导出目录
         fs::create_dir_all(export_path)
@@ -254,10 +667,11 @@ impl ConfigManager {
             }
         }
 
-        // 创建配置文件
+        // 创建配置文件；加密的密码原样导出，凭据库参数随之导出，导入后用同一个主密码解密
         let config = json!({
             "version": "1.0",
             "servers": servers,
+            "vault": self.vault_meta()?,
         });
         
         let json_string = serde_json::to_string_pretty(&config)?;
@@ -272,10 +686,12 @@ impl ConfigManager {
              导出时间: {}\n\n\
              目录结构:\n\
              - config.json: 服务器配置文件\n\
-             - keys/: 私钥文件目录\n\n\
+             - keys/: 私钥文件目录\n\
+             - sessions/: 会话配置（窗口按服务器名称引用）\n\n\
              导入说明:\n\
-             1. 确保所有私钥文件已正确放置在 ~/.ssh/ 目录下\n\
-             2. 使用命令 'rssh import-config <导出目录>' 导入配置\n",
+             使用命令 'rssh import-config <导出目录>' 导入配置。keys/ 中的私钥会安装到\n\
+             rssh 配置目录下的 keys/（权限 0600），服务器的密钥路径随之改写；\n\
+             本机原路径上已经是同一个密钥时保持原路径不变\n",
             chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
         );
         
@@ -315,8 +731,11 @@ impl ConfigManager {
         // 别名去重，避免重复 Host 块
         let mut used_aliases: std::collections::HashSet<String> = std::collections::HashSet::new();
         let mut password_count = 0;
+        let server_names: std::collections::HashSet<String> =
+            servers.iter().map(|s| sanitize_host_alias(&s.name)).collect();
 
-        for server in &servers {
+        // 串口、IPMI 等非 SSH 服务器无法写成 Host 块
+        for server in servers.iter().filter(|s| s.connection_type == ConnectionType::Ssh) {
             let alias = unique_host_alias(&sanitize_host_alias(&server.name), &mut used_aliases);
 
             // 描述 / 分组写成注释
@@ -335,27 +754,72 @@ impl ConfigManager {
                 content.push_str(&format!("# {}\n", comment_parts.join(" | ")));
             }
 
-            content.push_str(&format!("Host {}\n", alias));
+            // rssh 中的别名写在同一 Host 行上，与服务器名称冲突的略过
+            let mut patterns = vec![alias];
+            for extra in server.aliases.iter().map(|a| sanitize_host_alias(a)) {
+                if !server_names.contains(&extra) && used_aliases.insert(extra.clone()) {
+                    patterns.push(extra);
+                }
+            }
+            content.push_str(&format!("Host {}\n", patterns.join(" ")));
             content.push_str(&format!("    HostName {}\n", server.host));
             content.push_str(&format!("    Port {}\n", server.port));
             content.push_str(&format!("    User {}\n", server.username));
-
-            match &server.auth_type {
-                AuthType::Key(key_path) => {
-                    // ssh 自身支持 ~，保留原始路径即可
-                    content.push_str(&format!("    IdentityFile {}\n", key_path));
-                    content.push_str("    IdentitiesOnly yes\n");
-                }
-                AuthType::Agent => {
-                    // 走 ssh-agent，无需额外指令
+            if server.compression {
+                content.push_str("    Compression yes\n");
+            }
+            let algorithms = &server.algorithms;
+            for (key, value) in [
+                ("Ciphers", &algorithms.ciphers),
+                ("KexAlgorithms", &algorithms.kex),
+                ("MACs", &algorithms.macs),
+                ("HostKeyAlgorithms", &algorithms.host_key),
+            ] {
+                if let Some(value) = value {
+                    content.push_str(&format!("    {} {}\n", key, value));
                 }
-                AuthType::Password(_) => {
-                    // ssh config 无法保存明文密码，连接时交互式输入
-                    content.push_str("    # 密码认证: ssh config 无法保存密码，连接时需手动输入\n");
-                    password_count += 1;
+            }
+            if let Some(policy) = server.host_key_policy {
+                content.push_str(&format!("    StrictHostKeyChecking {}\n", policy.ssh_value()));
+            }
+            if server.login_banner != LoginBanner::Show {
+                content.push_str("    LogLevel ERROR\n");
+            }
+            match server.forward_agent {
+                ForwardAgent::Yes => content.push_str("    ForwardAgent yes\n"),
+                // ssh config 无法在每次连接前确认，导出时不开启
+                ForwardAgent::Confirm => content.push_str("    # agent 转发: rssh 中为每次确认，ssh config 中未开启\n"),
+                ForwardAgent::No => {}
+            }
+            // 跳板服务器也在这份文件中，直接引用它的 Host 别名
+            match (&server.proxy_jump, &server.jump_host, &server.proxy_command) {
+                (Some(proxy_jump), _, _) => content.push_str(&format!("    ProxyJump {}\n", sanitize_host_alias(proxy_jump))),
+                (None, Some(jump), _) => content.push_str(&format!("    ProxyJump {}\n", jump)),
+                (None, None, Some(command)) => content.push_str(&format!("    ProxyCommand {}\n", command)),
+                (None, None, None) => {}
+            }
+            if let Some(anti_idle) = server.anti_idle {
+                content.push_str(&format!("    ServerAliveInterval {}\n", anti_idle.interval));
+            }
+            for option in &server.extra_options {
+                if let Some((key, value)) = option.split_once('=') {
+                    content.push_str(&format!("    {} {}\n", key, value));
                 }
             }
 
+            // ssh 自身支持 ~，保留原始路径即可；agent 认证时只有额外的私钥
+            for key_path in server.identities() {
+                content.push_str(&format!("    IdentityFile {}\n", key_path));
+            }
+            if server.identities_only || matches!(server.auth_type, AuthType::Key(_)) {
+                content.push_str("    IdentitiesOnly yes\n");
+            }
+            if let AuthType::Password(_) = &server.auth_type {
+                // ssh config 无法保存明文密码，连接时交互式输入
+                content.push_str("    # 密码认证: ssh config 无法保存密码，连接时需手动输入\n");
+                password_count += 1;
+            }
+
             content.push('\n');
         }
 
@@ -372,7 +836,9 @@ impl ConfigManager {
         Ok(())
     }
 
-    pub fn import_config(&self, import_path: &PathBuf) -> Result<()> {
+    /// 从 `export` 导出的目录导入；有记录失败时不导入任何服务器，返回失败的记录
+    /// 备份中的 `keys/` 会安装到 `key_dir`，并改写对应服务器的密钥路径
+    pub fn import_config(&self, import_path: &PathBuf, key_dir: &Path) -> Result<Vec<ImportFailure>> {
         // 检查是否是目录
         if !import_path.is_dir() {
             return Err(anyhow::anyhow!("导入路径必须是目录: {}", import_path.display()));
@@ -385,15 +851,126 @@ impl ConfigManager {
         
         let config: Value = serde_json::from_str(&json_string)?;
         
-        if let Some(servers) = config.get("servers").and_then(|s| s.as_array()) {
-            for server_json in servers {
-                let server: ServerConfig = serde_json::from_value(server_json.clone())?;
-                self.add_server(server)?;
+        let mut servers = Vec::new();
+        let mut failures = Vec::new();
+        for (index, server_json) in config.get("servers").and_then(|s| s.as_array()).into_iter().flatten().enumerate() {
+            match serde_json::from_value::<ServerConfig>(server_json.clone()) {
+                Ok(server) => servers.push(server),
+                Err(e) => failures.push(ImportFailure {
+                    name: server_json.get("name").and_then(Value::as_str).map(str::to_string).unwrap_or_else(|| format!("第 {} 条", index + 1)),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        if failures.is_empty() {
+            self.import_vault_meta(&config)?;
+            let keys_dir = import_path.join("keys");
+            let installed = if keys_dir.is_dir() { install_bundled_keys(&keys_dir, key_dir, &mut servers)? } else { Vec::new() };
+            // 服务器没有导入时删除刚安装的私钥，不留下没有服务器使用的文件
+            let result = self.add_servers(&servers, |_, _| {});
+            if !matches!(&result, Ok(failures) if failures.is_empty()) {
+                remove_keys(&installed);
+            } else if !installed.is_empty() {
+                println!("已安装 {} 个私钥到 {}", installed.len(), key_dir.display());
             }
+            failures = result?;
         }
 
-        Ok(())
+        Ok(failures)
+    }
+
+    /// 备份来自启用了凭据库的机器时，本机没有凭据库则沿用备份中的参数，已有不同的凭据库时拒绝导入
+    fn import_vault_meta(&self, config: &Value) -> Result<()> {
+        let Some(imported) = config.get("vault").filter(|v| !v.is_null()) else { return Ok(()) };
+        let imported: VaultMeta = serde_json::from_value(imported.clone()).context("备份中的凭据库参数无效")?;
+        match self.vault_meta()? {
+            None => {
+                self.set_vault_meta(&imported)?;
+                credential_vault::configure(imported, None);
+                println!("已导入凭据库，连接时使用导出机器上的主密码解密");
+                Ok(())
+            },
+            Some(local) if local == imported => Ok(()),
+            Some(_) => Err(anyhow::anyhow!("备份使用了另一个凭据库加密密码，无法导入到本机的凭据库")),
+        }
+    }
+}
+
+/// 把导出时打包的私钥安装到 `key_dir`（权限 0600），并把服务器的密钥路径改为安装后的路径。
+/// 本机原路径上已经是同一个密钥时保持原路径；`key_dir` 中的同名文件内容不同时改名安装。
+/// 返回新安装的私钥，中途失败时删除已经安装的私钥
+fn install_bundled_keys(keys_dir: &Path, key_dir: &Path, servers: &mut [ServerConfig]) -> Result<Vec<PathBuf>> {
+    let mut installed = Vec::new();
+    let result = install_keys_into(keys_dir, key_dir, servers, &mut installed);
+    if result.is_err() {
+        remove_keys(&installed);
     }
+    result.map(|_| installed)
+}
+
+fn install_keys_into(keys_dir: &Path, key_dir: &Path, servers: &mut [ServerConfig], installed: &mut Vec<PathBuf>) -> Result<()> {
+    for server in servers.iter_mut() {
+        let AuthType::Key(key_path) = &server.auth_type else { continue };
+        if is_secret_reference(key_path) {
+            continue;
+        }
+        let local_path = PathBuf::from(expand_tilde(key_path));
+        let Some(file_name) = local_path.file_name() else { continue };
+        let Ok(bundled) = fs::read(keys_dir.join(file_name)) else { continue };
+        if fs::read(&local_path).is_ok_and(|local| local == bundled) {
+            continue;
+        }
+
+        let mut target = key_dir.join(file_name);
+        let mut suffix = 2;
+        while let Ok(existing) = fs::read(&target) {
+            if existing == bundled {
+                break;
+            }
+            target = key_dir.join(format!("{}-{}", file_name.to_string_lossy(), suffix));
+            suffix += 1;
+        }
+        if !target.exists() {
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(key_dir)
+                .with_context(|| format!("无法创建私钥目录: {}", key_dir.display()))?;
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&target)
+                .and_then(|mut file| std::io::Write::write_all(&mut file, &bundled))
+                .with_context(|| format!("无法安装私钥: {}", target.display()))?;
+            installed.push(target.clone());
+        }
+        server.auth_type = AuthType::Key(target.display().to_string());
+    }
+    Ok(())
+}
+
+fn remove_keys(keys: &[PathBuf]) {
+    for key in keys {
+        if let Err(e) = fs::remove_file(key) {
+            eprintln!("警告: 无法删除 {}: {}", key.display(), e);
+        }
+    }
+}
+
+/// 登录对话以 JSON 保存，没有对话时为 NULL
+fn login_dialog_json(steps: &[DialogStep]) -> Result<Option<String>> {
+    if steps.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(steps)?))
+}
+
+/// 导入时写入失败的一条记录
+#[derive(Debug)]
+pub struct ImportFailure {
+    pub name: String,
+    pub error: String,
 }
 
 /// 保证别名唯一，冲突时追加 `-2`、`-3` 等后缀。
@@ -415,10 +992,103 @@ fn unique_host_alias(
     }
 }
 
+/// 服务器备用密码在钥匙串中的账户
+fn password_account(server: &ServerConfig) -> String {
+    format!("{}-password", server.id)
+}
+
+fn keychain_reference(account: &str) -> String {
+    format!("keychain:{}", account)
+}
+
+/// 把密码写入 Windows 凭据管理器，已存在时覆盖
+#[cfg(windows)]
+fn keychain_store(service: &str, account: &str, secret: &str) -> Result<()> {
+    keyring::Entry::new(service, account)
+        .and_then(|entry| entry.set_password(secret))
+        .with_context(|| format!("无法写入 Windows 凭据管理器 {}/{}", service, account))
+}
+
+/// 把密码写入系统钥匙串，已存在时覆盖。密码经标准输入传给 `security` / `secret-tool`，不出现在命令行参数中
+#[cfg(not(windows))]
+fn keychain_store(service: &str, account: &str, secret: &str) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    let (mut cmd, input) = if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("security");
+        cmd.arg("-i");
+        let line = format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            security_quote(service), security_quote(account), security_quote(secret)
+        );
+        (cmd, line)
+    } else {
+        crate::utils::secret::ensure_installed("secret-tool", "请先安装 libsecret-tools（secret-tool）")?;
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["store", &format!("--label=rssh {}", account), "service", service, "account", account]);
+        (cmd, secret.to_string())
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("无法写入钥匙串 {}/{}", service, account))?;
+    std::io::Write::write_all(&mut child.stdin.take().expect("stdin 已设置为 piped"), input.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("写入钥匙串 {}/{} 失败 (退出码: {:?})", service, account, status.code()));
+    }
+    Ok(())
+}
+
+/// 从 Windows 凭据管理器删除密码，条目不存在时不报错
+#[cfg(windows)]
+fn keychain_delete(service: &str, account: &str) -> Result<()> {
+    match keyring::Entry::new(service, account).and_then(|entry| entry.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(anyhow::anyhow!("无法删除 Windows 凭据管理器 {}/{}: {}", service, account, e)),
+    }
+}
+
+/// 从系统钥匙串删除密码，条目不存在时不报错
+#[cfg(not(windows))]
+fn keychain_delete(service: &str, account: &str) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("security");
+        cmd.args(["delete-generic-password", "-s", service, "-a", account]);
+        cmd
+    } else {
+        crate::utils::secret::ensure_installed("secret-tool", "请先安装 libsecret-tools（secret-tool）")?;
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["clear", "service", service, "account", account]);
+        cmd
+    };
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("无法删除钥匙串 {}/{}", service, account))?;
+    Ok(())
+}
+
+/// `security -i` 按 shell 的规则拆分参数，用双引号包裹并转义
+#[cfg(not(windows))]
+fn security_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    #[cfg(not(windows))]
+    fn quotes_values_for_security_interactive_mode() {
+        assert_eq!(security_quote("a b"), "\"a b\"");
+        assert_eq!(security_quote(r#"p"w\d"#), r#""p\"w\\d""#);
+    }
+
     #[test]
     fn dedupes_colliding_aliases() {
         let mut used = std::collections::HashSet::new();
@@ -427,6 +1097,156 @@ mod tests {
         assert_eq!(unique_host_alias("web", &mut used), "web-3");
     }
 
+    #[test]
+    fn keeps_prefixed_plaintext_passwords_from_older_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("old.db");
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute(
+            "CREATE TABLE servers (id TEXT PRIMARY KEY, name TEXT NOT NULL, host TEXT NOT NULL, port INTEGER NOT NULL, \
+             username TEXT NOT NULL, auth_type TEXT NOT NULL, auth_data TEXT, password TEXT, group_name TEXT, description TEXT)",
+            [],
+        )
+        .unwrap();
+        for (id, password) in [("1", "cmd:echo x"), ("2", "pass:word"), ("3", "hunter2")] {
+            let auth_type = if id == "1" { "agent" } else { "password" };
+            let (auth_data, backup) = if id == "1" { (None, Some(password)) } else { (Some(password), None) };
+            conn.execute(
+                "INSERT INTO servers (id, name, host, port, username, auth_type, auth_data, password) VALUES (?1, ?1, 'h', 22, 'u', ?2, ?3, ?4)",
+                params![id, auth_type, auth_data, backup],
+            )
+            .unwrap();
+        }
+        drop(conn);
+
+        let mgr = ConfigManager::new(db_path).unwrap();
+        let provider = |id: &str| mgr.get_server(id).unwrap().unwrap().secret_provider;
+        // 旧版本中 cmd: 已经是引用，pass: 开头的是明文
+        assert_eq!(provider("1"), None);
+        assert_eq!(provider("2").as_deref(), Some("db"));
+        assert_eq!(provider("3"), None);
+        let resolved = mgr.get_server("2").unwrap().unwrap().with_resolved_secrets().unwrap();
+        assert!(matches!(resolved.auth_type, AuthType::Password(password) if password == "pass:word"));
+    }
+
+    #[test]
+    fn saves_and_exports_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = ConfigManager::new(dir.path().join("test.db")).unwrap();
+        let mut slow = ServerConfig::new("1".into(), "slow".into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, None, None, None);
+        slow.compression = true;
+        mgr.add_server(slow).unwrap();
+        mgr.add_server(ServerConfig::new("2".into(), "fast".into(), "10.0.0.2".into(), 22, "root".into(), AuthType::Agent, None, None, None)).unwrap();
+        assert!(mgr.get_server("1").unwrap().unwrap().compression);
+        assert!(!mgr.get_server("2").unwrap().unwrap().compression);
+
+        let export = dir.path().join("ssh_config");
+        mgr.export_ssh_config(&export).unwrap();
+        let content = fs::read_to_string(&export).unwrap();
+        let slow_block = content.split("Host ").find(|block| block.starts_with("slow")).unwrap();
+        assert!(slow_block.contains("Compression yes"));
+        assert_eq!(content.matches("Compression yes").count(), 1);
+    }
+
+    #[test]
+    fn finds_server_by_slot() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = ConfigManager::new(dir.path().join("test.db")).unwrap();
+        let mut web = ServerConfig::new("1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, None, None, None);
+        web.slot = Some(3);
+        mgr.add_server(web).unwrap();
+        mgr.add_server(ServerConfig::new("2".into(), "db".into(), "10.0.0.2".into(), 22, "root".into(), AuthType::Agent, None, None, None)).unwrap();
+
+        assert_eq!(mgr.find_by_slot(3).unwrap().unwrap().name, "web");
+        assert!(mgr.find_by_slot(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn failed_import_rolls_back_every_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = ConfigManager::new(dir.path().join("test.db")).unwrap();
+        let server = |id: &str, name: &str| {
+            ServerConfig::new(id.into(), name.into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, None, None, None)
+        };
+
+        // 第三条与第一条 id 冲突
+        let mut progress = Vec::new();
+        let failures = mgr
+            .add_servers(&[server("1", "a"), server("2", "b"), server("1", "c")], |done, total| progress.push((done, total)))
+            .unwrap();
+        assert_eq!(progress, vec![(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "c");
+        assert!(mgr.list_servers().unwrap().is_empty());
+
+        assert!(mgr.add_servers(&[server("1", "a"), server("2", "b")], |_, _| {}).unwrap().is_empty());
+        assert_eq!(mgr.list_servers().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn installs_bundled_keys_and_rewrites_paths() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let keys_dir = dir.path().join("bundle/keys");
+        let key_dir = dir.path().join("installed");
+        fs::create_dir_all(&keys_dir).unwrap();
+        fs::write(keys_dir.join("id_deploy"), "bundled").unwrap();
+        // 本机已有同名但内容不同的密钥
+        fs::create_dir_all(&key_dir).unwrap();
+        fs::write(key_dir.join("id_deploy"), "other").unwrap();
+        let same = dir.path().join("id_same");
+        fs::write(&same, "same").unwrap();
+        fs::write(keys_dir.join("id_same"), "same").unwrap();
+
+        let server = |name: &str, key: &str| {
+            ServerConfig::new(name.into(), name.into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Key(key.into()), None, None, None)
+        };
+        let mut servers = vec![
+            server("a", "/home/old/.ssh/id_deploy"),
+            server("b", "/elsewhere/id_deploy"),
+            server("c", &same.display().to_string()),
+            server("d", "/home/old/.ssh/id_missing"),
+        ];
+        let installed = key_dir.join("id_deploy-2");
+        assert_eq!(install_bundled_keys(&keys_dir, &key_dir, &mut servers).unwrap(), std::slice::from_ref(&installed));
+
+        assert_eq!(fs::read_to_string(&installed).unwrap(), "bundled");
+        assert_eq!(fs::metadata(&installed).unwrap().permissions().mode() & 0o777, 0o600);
+        let paths: Vec<_> = servers.iter().filter_map(|s| s.auth_type.get_key_path()).collect();
+        assert_eq!(paths[0], installed.display().to_string());
+        assert_eq!(paths[1], installed.display().to_string());
+        assert_eq!(paths[2], same.display().to_string());
+        assert_eq!(paths[3], "/home/old/.ssh/id_missing");
+    }
+
+    #[test]
+    fn failed_import_removes_installed_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("bundle");
+        let key_dir = dir.path().join("installed");
+        fs::create_dir_all(bundle.join("keys")).unwrap();
+        fs::write(bundle.join("keys/id_deploy"), "bundled").unwrap();
+        let server = ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+            AuthType::Key("/home/old/.ssh/id_deploy".into()), None, None, None,
+        );
+        let mgr = ConfigManager::new(dir.path().join("test.db")).unwrap();
+
+        // 重复的 ID 导致整个导入回滚
+        fs::write(bundle.join("config.json"), json!({ "servers": [&server, &server] }).to_string()).unwrap();
+        let failures = mgr.import_config(&bundle, &key_dir).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(mgr.list_servers().unwrap().is_empty());
+        assert!(!key_dir.join("id_deploy").exists());
+
+        fs::write(bundle.join("config.json"), json!({ "servers": [&server] }).to_string()).unwrap();
+        assert!(mgr.import_config(&bundle, &key_dir).unwrap().is_empty());
+        let installed = key_dir.join("id_deploy");
+        assert_eq!(fs::read_to_string(&installed).unwrap(), "bundled");
+        assert_eq!(mgr.get_server("1").unwrap().unwrap().auth_type.get_key_path(), Some(installed.display().to_string().as_str()));
+    }
+
     #[test]
     fn export_ssh_config_emits_valid_blocks() {
         let base = std::env::temp_dir().join(format!("rssh-test-{}", std::process::id()));
@@ -436,16 +1256,22 @@ mod tests {
 
         let mgr = ConfigManager::new(db_path).unwrap();
 
-        // key 认证，名称含空格 -> 别名应被清洗
-        mgr.add_server(ServerConfig::new(
+        // key 认证，名称含空格 -> 别名应被清洗；经跳板服务器 -> 引用它的 Host 别名
+        let mut web = ServerConfig::new(
             "1".into(), "prod web".into(), "10.0.0.1".into(), 2222, "deploy".into(),
             AuthType::Key("~/.ssh/id_ed25519".into()), None, Some("生产机".into()), None,
-        )).unwrap();
-        // agent 认证
-        mgr.add_server(ServerConfig::new(
+        );
+        web.proxy_jump = Some("bastion".into());
+        mgr.add_server(web).unwrap();
+        assert_eq!(mgr.get_server("1").unwrap().unwrap().proxy_jump.as_deref(), Some("bastion"));
+        // agent 认证，别名写在同一 Host 行，与其它服务器名称冲突的略过
+        let mut bastion = ServerConfig::new(
             "2".into(), "bastion".into(), "10.0.0.2".into(), 22, "root".into(),
             AuthType::Agent, Some("infra".into()), None, None,
-        )).unwrap();
+        );
+        bastion.aliases = vec!["jump".into(), "db".into()];
+        mgr.add_server(bastion).unwrap();
+        assert_eq!(mgr.find_server("jump").unwrap().unwrap().id, "2");
         // 密码认证 -> 不应出现 IdentityFile，应出现密码注释
         mgr.add_server(ServerConfig::new(
             "3".into(), "db".into(), "10.0.0.3".into(), 22, "admin".into(),
@@ -465,7 +1291,8 @@ mod tests {
         assert!(content.contains("    Port 2222"));
         assert!(content.contains("    IdentityFile ~/.ssh/id_ed25519"));
         assert!(content.contains("    IdentitiesOnly yes"));
-        assert!(content.contains("Host bastion\n"));
+        assert!(content.contains("    ProxyJump bastion\n"));
+        assert!(content.contains("Host bastion jump\n"));
         assert!(content.contains("Host bastion-2\n"));
         // 密码认证不写明文密码
         assert!(!content.contains("secret"));
@@ -475,4 +1302,27 @@ mod tests {
 
         fs::remove_dir_all(&base).ok();
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn persists_connection_type() {
+        let base = std::env::temp_dir().join(format!("rssh-test-conn-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let mgr = ConfigManager::new(base.join("test.db")).unwrap();
+
+        let mut console = ServerConfig::new(
+            "1".into(), "switch".into(), "/dev/ttyUSB0".into(), 0, String::new(),
+            AuthType::Agent, None, None, None,
+        );
+        console.connection_type = ConnectionType::Serial { baud: 9600 };
+        mgr.add_server(console).unwrap();
+        mgr.add_server(ServerConfig::new(
+            "2".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+            AuthType::Agent, None, None, None,
+        )).unwrap();
+
+        assert_eq!(mgr.get_server("1").unwrap().unwrap().connection_type, ConnectionType::Serial { baud: 9600 });
+        assert_eq!(mgr.get_server("2").unwrap().unwrap().connection_type, ConnectionType::Ssh);
+
+        fs::remove_dir_all(&base).ok();
+    }
+}
@@ -1,13 +1,33 @@
 pub mod manager;
+pub mod history_store;
+pub mod job_store;
+pub mod probe_store;
+pub mod schedule_store;
 pub mod session_manager;
+pub mod settings;
+pub mod traffic_store;
+pub mod workspace;
 
-pub use manager::ConfigManager;
+pub use manager::{ConfigManager, CredentialBackend, ImportFailure};
+pub use history_store::{HistoryEntry, HistoryStore};
+pub use job_store::JobStore;
+pub use probe_store::ProbeStore;
+pub use schedule_store::ScheduleStore;
 pub use session_manager::SessionManager;
+pub use settings::Settings;
+pub use traffic_store::TrafficStore;
+pub use workspace::{Workspace, WORKSPACE_FILE};
 
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use dirs;
 
+/// 旧版本放在配置目录中、现在移到数据目录的文件
+const LEGACY_DATA_ENTRIES: &[&str] = &["servers.db", "servers.db-journal", "servers.db-wal", "servers.db-shm", "recordings"];
+
+/// 旧版本放在配置目录中、现在移到状态目录的文件。`run`、`cm` 等运行时文件不迁移，旧的会话结束后自然失效
+const LEGACY_STATE_ENTRIES: &[&str] = &["audit.log", "motd", "certs", "ssh_config"];
+
 pub fn get_config_dir() -> Result<PathBuf> {
     let mut config_dir = dirs::config_dir()
         .with_context(|| "无法确定配置目录")?;
@@ -22,8 +42,124 @@ pub fn get_config_dir() -> Result<PathBuf> {
     Ok(config_dir)
 }
 
+/// 数据目录（`$XDG_DATA_HOME/rssh`，默认 `~/.local/share/rssh`）：服务器数据库（含连接历史、任务结果、流量统计）和会话录制
+pub fn get_data_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir()
+        .with_context(|| "无法确定数据目录")?
+        .join("rssh");
+    ensure_dir(&data_dir, "数据目录")?;
+
+    Ok(data_dir)
+}
+
+/// 状态目录（`$XDG_STATE_HOME/rssh`，默认 `~/.local/state/rssh`）：审计日志、调试日志、生成的 ssh_config、证书、
+/// 运行中的会话等，删除后可以重建。macOS、Windows 没有对应的约定，与数据目录相同
+pub fn get_state_dir() -> Result<PathBuf> {
+    let state_dir = match dirs::state_dir() {
+        Some(dir) => dir.join("rssh"),
+        None => return get_data_dir(),
+    };
+    ensure_dir(&state_dir, "状态目录")?;
+
+    Ok(state_dir)
+}
+
+fn ensure_dir(dir: &Path, desc: &str) -> Result<()> {
+    if !dir.exists() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("无法创建{}: {}", desc, dir.display()))?;
+    }
+    Ok(())
+}
+
+/// 把旧版本放在配置目录中的数据库、日志等移到数据目录和状态目录，返回移动后的路径。
+///
+/// 每次启动时调用，目标已存在的文件不覆盖（提示后保留原处），没有需要迁移的文件时什么都不做
+pub fn migrate_legacy_layout() -> Result<Vec<PathBuf>> {
+    migrate_entries(&get_config_dir()?, &get_data_dir()?, &get_state_dir()?)
+}
+
+fn migrate_entries(config_dir: &Path, data_dir: &Path, state_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut moved = Vec::new();
+    let entries = LEGACY_DATA_ENTRIES
+        .iter()
+        .map(|name| (name, data_dir))
+        .chain(LEGACY_STATE_ENTRIES.iter().map(|name| (name, state_dir)));
+    for (name, target_dir) in entries {
+        let source = config_dir.join(name);
+        let target = target_dir.join(name);
+        if source == target || std::fs::symlink_metadata(&source).is_err() {
+            continue;
+        }
+        if target.exists() && !is_empty_database(&target) {
+            eprintln!("⚠ {} 和 {} 都存在，保留旧文件，请确认后手动删除其中一个", source.display(), target.display());
+            continue;
+        }
+        if target.exists() {
+            remove_database(&target)
+                .with_context(|| format!("无法删除空数据库 {}", target.display()))?;
+        }
+        move_path(&source, &target)
+            .with_context(|| format!("无法把 {} 移动到 {}", source.display(), target.display()))?;
+        moved.push(target);
+    }
+    Ok(moved)
+}
+
+/// 只有表结构、没有任何数据的数据库：迁移前打开过数据库时在新位置建出的，当作不存在
+fn is_empty_database(path: &Path) -> bool {
+    if path.extension() != Some(std::ffi::OsStr::new("db")) {
+        return false;
+    }
+    let Ok(conn) = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return false;
+    };
+    let tables: Vec<String> = match conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+    {
+        Ok(tables) => tables,
+        Err(_) => return false,
+    };
+    tables.iter().all(|table| {
+        conn.query_row(&format!("SELECT NOT EXISTS (SELECT 1 FROM \"{}\")", table), [], |row| row.get(0))
+            .unwrap_or(false)
+    })
+}
+
+/// 删除数据库和它的日志文件
+fn remove_database(path: &Path) -> std::io::Result<()> {
+    for suffix in ["-journal", "-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        match std::fs::remove_file(&sidecar) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    std::fs::remove_file(path)
+}
+
+/// 移动文件或目录；不在同一个文件系统时复制后删除原文件
+fn move_path(source: &Path, target: &Path) -> std::io::Result<()> {
+    if std::fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    if source.is_dir() {
+        std::fs::create_dir_all(target)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            move_path(&entry.path(), &target.join(entry.file_name()))?;
+        }
+        std::fs::remove_dir(source)
+    } else {
+        std::fs::copy(source, target)?;
+        std::fs::remove_file(source)
+    }
+}
+
 pub fn get_db_path() -> Result<PathBuf> {
-    let mut db_path = get_config_dir()?;
+    let mut db_path = get_data_dir()?;
     db_path.push("servers.db");
     
     Ok(db_path)
@@ -39,4 +175,110 @@ pub fn get_session_dir() -> Result<PathBuf> {
     }
     
     Ok(session_dir)
-} 
\ No newline at end of file
+} 
+/// 正在运行的交互式会话的状态目录（`rssh ps`）
+pub fn get_run_dir() -> Result<PathBuf> {
+    let mut run_dir = get_state_dir()?;
+    run_dir.push("run");
+
+    Ok(run_dir)
+}
+
+/// 保存登录信息（MOTD）的目录，登录横幅为 capture 时使用
+pub fn get_motd_dir() -> Result<PathBuf> {
+    let mut motd_dir = get_state_dir()?;
+    motd_dir.push("motd");
+
+    Ok(motd_dir)
+}
+
+/// 每次连接生成的 ssh_config（`ssh -F`）所在目录
+pub fn get_ssh_config_dir() -> Result<PathBuf> {
+    let mut ssh_config_dir = get_state_dir()?;
+    ssh_config_dir.push("ssh_config");
+
+    Ok(ssh_config_dir)
+}
+
+/// `rssh import-config` 安装备份中私钥的目录
+pub fn get_key_dir() -> Result<PathBuf> {
+    let mut key_dir = get_config_dir()?;
+    key_dir.push("keys");
+
+    Ok(key_dir)
+}
+
+/// CA 模式下连接前自动签发的证书目录
+pub fn get_cert_dir() -> Result<PathBuf> {
+    let mut cert_dir = get_state_dir()?;
+    cert_dir.push("certs");
+
+    Ok(cert_dir)
+}
+
+/// `rssh shell --record <文件名>` 只写文件名时保存会话录制的目录
+pub fn get_recording_dir() -> Result<PathBuf> {
+    let mut recording_dir = get_data_dir()?;
+    recording_dir.push("recordings");
+
+    Ok(recording_dir)
+}
+
+/// 内置库调试模式（`--mode debug`）的日志
+pub fn get_debug_log_path() -> Result<PathBuf> {
+    Ok(get_state_dir()?.join("debug.log"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_legacy_files_out_of_config_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let (config, data, state) = (root.path().join("config"), root.path().join("data"), root.path().join("state"));
+        for dir in [&config, &data, &state] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(config.join("servers.db"), "db").unwrap();
+        std::fs::write(config.join("config.toml"), "").unwrap();
+        std::fs::create_dir_all(config.join("certs")).unwrap();
+        std::fs::write(config.join("certs/web1-cert.pub"), "cert").unwrap();
+        std::fs::write(config.join("audit.log"), "old").unwrap();
+        std::fs::write(state.join("audit.log"), "new").unwrap();
+
+        let moved = migrate_entries(&config, &data, &state).unwrap();
+        assert_eq!(moved, [data.join("servers.db"), state.join("certs")]);
+        assert_eq!(std::fs::read_to_string(data.join("servers.db")).unwrap(), "db");
+        assert!(state.join("certs/web1-cert.pub").exists());
+        assert!(config.join("config.toml").exists());
+        // 目标已存在时不覆盖
+        assert_eq!(std::fs::read_to_string(state.join("audit.log")).unwrap(), "new");
+        assert!(config.join("audit.log").exists());
+
+        assert!(migrate_entries(&config, &data, &state).unwrap().is_empty());
+    }
+
+    #[test]
+    fn replaces_empty_database_opened_before_migration() {
+        let root = tempfile::tempdir().unwrap();
+        let (config, data, state) = (root.path().join("config"), root.path().join("data"), root.path().join("state"));
+        for dir in [&config, &data, &state] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let server = crate::models::ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "deploy".into(),
+            crate::models::AuthType::Agent, None, None, None,
+        );
+        ConfigManager::new(config.join("servers.db")).unwrap().add_server(server).unwrap();
+        // 迁移前先打开了新位置的数据库（如 `rssh <编号>`），只建出了表结构
+        drop(ConfigManager::new(data.join("servers.db")).unwrap());
+
+        let moved = migrate_entries(&config, &data, &state).unwrap();
+        assert_eq!(moved, [data.join("servers.db")]);
+        assert!(!config.join("servers.db").exists());
+        let servers = ConfigManager::new(data.join("servers.db")).unwrap().list_servers().unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].name, "web");
+    }
+}
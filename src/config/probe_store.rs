@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::PathBuf;
+
+use crate::utils::remote_probe::RemoteTools;
+
+/// 远程环境探测结果的缓存，与服务器配置共用同一个数据库文件
+pub struct ProbeStore {
+    conn: Connection,
+}
+
+impl ProbeStore {
+    pub fn new(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("无法打开数据库 {}", db_path.display()))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS remote_probes (
+                server_id TEXT PRIMARY KEY,
+                tools TEXT NOT NULL,
+                probed_at INTEGER NOT NULL
+            );",
+        )?;
+
+        Ok(ProbeStore { conn })
+    }
+
+    pub fn get(&self, server_id: &str) -> Result<Option<RemoteTools>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT tools, probed_at FROM remote_probes WHERE server_id = ?1",
+                params![server_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .optional()?;
+        Ok(row.map(|(tools, probed_at)| RemoteTools::parse(&tools.replace(',', "\n"), probed_at)))
+    }
+
+    pub fn save(&self, server_id: &str, tools: &RemoteTools) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO remote_probes (server_id, tools, probed_at) VALUES (?1, ?2, ?3)",
+            params![server_id, tools.names().join(","), tools.probed_at],
+        )?;
+        Ok(())
+    }
+
+    /// 删除缓存，下次传输时重新探测
+    pub fn forget(&self, server_id: &str) -> Result<()> {
+        self.conn.execute("DELETE FROM remote_probes WHERE server_id = ?1", params![server_id])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::path::PathBuf;
+
+use crate::models::Schedule;
+
+/// 定时任务存储，与服务器配置共用同一个数据库文件
+pub struct ScheduleStore {
+    conn: Connection,
+}
+
+impl ScheduleStore {
+    pub fn new(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("无法打开数据库 {}", db_path.display()))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schedules (
+                id TEXT PRIMARY KEY,
+                cron TEXT NOT NULL,
+                target TEXT NOT NULL,
+                command TEXT NOT NULL,
+                parallel INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_run_at INTEGER,
+                last_ok INTEGER
+            );",
+        )?;
+
+        Ok(ScheduleStore { conn })
+    }
+
+    pub fn add(&self, cron: &str, group: &str, command: &str, parallel: usize) -> Result<Schedule> {
+        let schedule = Schedule {
+            id: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+            cron: cron.to_string(),
+            group: group.to_string(),
+            command: command.to_string(),
+            parallel,
+            created_at: chrono::Utc::now().timestamp(),
+            last_run_at: None,
+            last_ok: None,
+        };
+        self.conn.execute(
+            "INSERT INTO schedules (id, cron, target, command, parallel, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![schedule.id, schedule.cron, schedule.group, schedule.command, parallel as i64, schedule.created_at],
+        )?;
+        Ok(schedule)
+    }
+
+    /// 所有定时任务，按创建时间排序
+    pub fn list(&self) -> Result<Vec<Schedule>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, cron, target, command, parallel, created_at, last_run_at, last_ok
+             FROM schedules ORDER BY created_at, rowid",
+        )?;
+        let schedules = stmt
+            .query_map([], |row| {
+                let parallel: i64 = row.get(4)?;
+                Ok(Schedule {
+                    id: row.get(0)?,
+                    cron: row.get(1)?,
+                    group: row.get(2)?,
+                    command: row.get(3)?,
+                    parallel: parallel as usize,
+                    created_at: row.get(5)?,
+                    last_run_at: row.get(6)?,
+                    last_ok: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(schedules)
+    }
+
+    /// 按 ID 或 ID 前缀删除，返回被删除的定时任务
+    pub fn remove(&self, id: &str) -> Result<Option<Schedule>> {
+        let mut matched: Vec<Schedule> = self.list()?.into_iter().filter(|s| s.id.starts_with(id)).collect();
+        if matched.len() > 1 {
+            return Err(anyhow::anyhow!("定时任务 ID 前缀 {} 不唯一，请输入更长的 ID", id));
+        }
+        let Some(schedule) = matched.pop() else { return Ok(None) };
+        self.conn.execute("DELETE FROM schedules WHERE id = ?1", params![schedule.id])?;
+        Ok(Some(schedule))
+    }
+
+    /// 开始执行时记录时间，结果清空直到执行结束
+    pub fn mark_started(&self, id: &str, at: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE schedules SET last_run_at = ?2, last_ok = NULL WHERE id = ?1",
+            params![id, at],
+        )?;
+        Ok(())
+    }
+
+    pub fn mark_finished(&self, id: &str, ok: bool) -> Result<()> {
+        self.conn.execute("UPDATE schedules SET last_ok = ?2 WHERE id = ?1", params![id, ok])?;
+        Ok(())
+    }
+}
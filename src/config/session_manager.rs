@@ -5,7 +5,7 @@ use std::io::Write;
 use anyhow::{Result, Context};
 use uuid::Uuid;
 use toml;
-use crate::models::{SessionConfig, SessionWindow};
+use crate::models::{ServerConfig, SessionConfig, SessionWindow};
 
 /// Session配置管理器
 pub struct SessionManager {
@@ -149,4 +149,73 @@ impl SessionManager {
     pub fn get_session_path(&self, id: &str) -> PathBuf {
         self.config_dir.join(format!("{}.toml", id))
     }
+
+    /// 把所有session导出到 `target`（导出目录中的 sessions/），窗口引用的服务器ID换成名称，
+    /// 以便在另一台机器上按名称找到服务器
+    pub fn export_sessions(&self, target: &SessionManager, servers: &[ServerConfig]) -> Result<usize> {
+        let sessions = self.list_sessions()?;
+        for mut session in sessions.iter().cloned() {
+            remap_servers_by_name(&mut session, servers);
+            target.save_session(&session)?;
+        }
+        Ok(sessions.len())
+    }
+
+    /// 从 `source` 导入session，同ID的session会被覆盖。
+    /// 服务器引用按名称匹配（按ID引用的也换成名称），返回导入数量和找不到的服务器
+    pub fn import_sessions(&self, source: &SessionManager, servers: &[ServerConfig]) -> Result<(usize, Vec<String>)> {
+        let sessions = source.list_sessions()?;
+        let mut missing = Vec::new();
+        for mut session in sessions.iter().cloned() {
+            for server in remap_servers_by_name(&mut session, servers) {
+                if !missing.contains(&server) {
+                    missing.push(server);
+                }
+            }
+            self.save_session(&session)?;
+        }
+        Ok((sessions.len(), missing))
+    }
+}
+
+/// 把窗口中按ID引用的服务器换成名称，返回找不到的服务器引用
+fn remap_servers_by_name(session: &mut SessionConfig, servers: &[ServerConfig]) -> Vec<String> {
+    let mut missing = Vec::new();
+    for window in &mut session.windows {
+        if servers.iter().any(|s| s.name == window.server) {
+            continue;
+        }
+        match servers.iter().find(|s| s.id == window.server) {
+            Some(server) => window.server = server.name.clone(),
+            None => missing.push(window.server.clone()),
+        }
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    #[test]
+    fn exported_sessions_reference_servers_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = SessionManager::new(dir.path().join("local")).unwrap();
+        let bundle = SessionManager::new(dir.path().join("bundle")).unwrap();
+        let window = |server: &str| SessionWindow { title: None, server: server.into(), command: None, position: None, size: None };
+        local.create_session("dev".into(), None, vec![window("id-web"), window("db"), window("gone")], None).unwrap();
+
+        let server = |id: &str, name: &str| {
+            ServerConfig::new(id.into(), name.into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, None, None, None)
+        };
+        assert_eq!(local.export_sessions(&bundle, &[server("id-web", "web"), server("id-db", "db")]).unwrap(), 1);
+
+        // 新机器上服务器的ID不同，按名称引用仍然有效
+        let target = SessionManager::new(dir.path().join("target")).unwrap();
+        let (count, missing) = target.import_sessions(&bundle, &[server("new-web", "web"), server("new-db", "db")]).unwrap();
+        assert_eq!((count, missing), (1, vec!["gone".to_string()]));
+        let servers: Vec<String> = target.list_sessions().unwrap()[0].windows.iter().map(|w| w.server.clone()).collect();
+        assert_eq!(servers, ["web", "db", "gone"]);
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::get_config_dir;
+use super::manager::CredentialBackend;
+use crate::models::HostKeyPolicy;
+use crate::utils::badge::Badge;
+use crate::utils::ca::CaSettings;
+use crate::utils::clipboard::ClipboardSettings;
+use crate::utils::hooks::HookSettings;
+use crate::utils::macros::MacroSettings;
+use crate::utils::network_location::NetworkProfile;
+use crate::utils::retention::RetentionSettings;
+use crate::utils::siem::SiemSettings;
+use crate::utils::triggers::TriggerSettings;
+
+/// 全局配置（`~/.config/rssh/config.toml`），文件不存在时全部取默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// `connect --new-window` 使用的终端：kitty/wezterm/iterm2/gnome-terminal/tmux，
+    /// 未设置时根据当前终端自动检测
+    pub terminal: Option<String>,
+    /// 自定义新窗口命令模板，`{cmd}` 会被替换为转义后的 rssh 命令，
+    /// 例如 `alacritty -e sh -c {cmd}`；设置后优先于 `terminal`
+    pub terminal_command: Option<String>,
+    /// 批量执行时目标主机超过该数量，需要输入分组名确认（默认 5）
+    pub confirm_threshold: Option<usize>,
+    /// 默认主机密钥策略：strict / accept-new / off（默认 accept-new），可被服务器配置覆盖
+    pub host_key_policy: Option<HostKeyPolicy>,
+    /// 始终以审计模式运行，等同于每次都加 `--audit`
+    pub audit: bool,
+    /// 交互式会话空闲多少秒后锁定，未设置时不锁定
+    pub idle_timeout: Option<u64>,
+    /// 解锁密码，支持 `env:` / `pass:` 等密钥引用；未设置时回车即可解锁
+    pub idle_lock_password: Option<String>,
+    /// 按分组配置的环境标识（`[badges.<分组名>]`），未配置的分组按名称识别 prod/staging/dev
+    pub badges: HashMap<String, Badge>,
+    /// `rssh vnc` 使用的查看器命令模板，`{host}` / `{port}` 替换为本地转发地址，
+    /// 例如 `vncviewer {host}::{port}`；未设置时按平台选择
+    pub vnc_viewer: Option<String>,
+    /// `rssh rdp` 使用的查看器命令模板，例如 `xfreerdp /v:{host}:{port} /dynamic-resolution`
+    pub rdp_viewer: Option<String>,
+    /// 只允许这些分组的服务器转发 SSH agent，未设置时不限制
+    pub agent_forwarding_groups: Option<Vec<String>>,
+    /// 团队 CA（`[ca]`）：连接前用 CA 为密钥签发短期证书，快过期时自动续签
+    pub ca: Option<CaSettings>,
+    /// 命名的通知目标（`[notify]`），`--notify <名称>` 时使用，例如 `team = "slack:pass:rssh/slack-webhook"`
+    pub notify: HashMap<String, String>,
+    /// 网络位置（`[networks.<名称>]`），在该网络中时服务器改用内网地址、不经跳板机
+    pub networks: HashMap<String, NetworkProfile>,
+    /// 事件上报（`[siem]`）：连接、执行、传输等事件以签名的 JSON POST 到安全团队的日志平台
+    pub siem: Option<SiemSettings>,
+    /// 保存凭据库主密码的文件（权限应为 0600），设置后连接时不再询问主密码，适合无人值守的环境
+    pub vault_key_file: Option<String>,
+    /// 密码保存位置：db（默认）/ keychain（系统钥匙串）
+    pub credential_backend: Option<CredentialBackend>,
+    /// 内置库 / russh 模式连接单个地址的超时秒数（默认 5）
+    pub connect_timeout: Option<u64>,
+    /// `connect` 未指定 `--mode` 时 SSH 服务器使用的连接方式：system（默认）/ library / russh
+    pub connect_mode: Option<String>,
+    /// `session-edit` 等使用的编辑器，未设置时使用 `$EDITOR`，再退回 vim
+    pub editor: Option<String>,
+    /// 配色：default / mono（命令行输出不使用颜色）
+    pub theme: Option<String>,
+    /// 脚本钩子（`[hooks]`）：连接、导入、解析密钥和列表搜索时调用的脚本
+    pub hooks: HookSettings,
+    /// 数据保留（`[retention]`）：会话录制、调试日志和批量任务结果的保留时间与大小上限
+    pub retention: RetentionSettings,
+    /// 远程剪贴板（`[clipboard]`）：内置库模式下把远程的 OSC 52 复制写入本地剪贴板
+    pub clipboard: ClipboardSettings,
+    /// 内置库模式会话保留的输出大小（KB，默认 1024，0 为关闭），行首输入 `~/` 在本地分页器中查看
+    pub scrollback_kb: Option<usize>,
+    /// 输出触发器（`[triggers.<名称>]`）：内置库模式下远程输出匹配正则时响铃、发送通知或自动回答
+    pub triggers: HashMap<String, TriggerSettings>,
+    /// 输入宏（`[macros.<名称>]`）：内置库模式下功能键发送保存的命令、缩写展开为完整文本
+    pub macros: HashMap<String, MacroSettings>,
+}
+
+impl Settings {
+    /// 编辑文件使用的命令
+    pub fn editor(&self) -> String {
+        self.editor
+            .clone()
+            .or_else(|| std::env::var("EDITOR").ok())
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vim".to_string())
+    }
+
+    /// 批量执行需要输入分组名确认的主机数阈值
+    pub fn confirm_threshold(&self) -> usize {
+        self.confirm_threshold.unwrap_or(5)
+    }
+
+    /// 加载全局配置
+    pub fn load() -> Result<Self> {
+        let path = get_settings_path()?;
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("无法读取配置文件: {}", path.display()))?;
+        let settings: Settings = toml::from_str(&content)
+            .with_context(|| format!("无法解析配置文件: {}", path.display()))?;
+
+        Ok(settings)
+    }
+
+    /// 修改 `config.toml` 中的一项（`rssh config set`），保留文件中的注释和其它内容。
+    /// 键名中的 `-` 等同于 `_`；值为 true/false 或整数时按对应类型写入，其它按字符串写入
+    pub fn set(key: &str, value: &str) -> Result<Self> {
+        let key = key.replace('-', "_");
+        if !Self::keys().contains(&key) {
+            return Err(anyhow::anyhow!("未知的配置项: {}（可选: {}）", key, Self::keys().join(", ")));
+        }
+
+        let path = get_settings_path()?;
+        let content = if path.exists() {
+            std::fs::read_to_string(&path).with_context(|| format!("无法读取配置文件: {}", path.display()))?
+        } else {
+            String::new()
+        };
+        let mut document: toml_edit::DocumentMut = content
+            .parse()
+            .with_context(|| format!("无法解析配置文件: {}", path.display()))?;
+        document[key.as_str()] = match value {
+            "true" | "false" => toml_edit::value(value == "true"),
+            _ => match value.parse::<i64>() {
+                Ok(number) => toml_edit::value(number),
+                Err(_) => toml_edit::value(value),
+            },
+        };
+
+        let updated = document.to_string();
+        let settings: Settings = toml::from_str(&updated).with_context(|| format!("{} 的值无效: {}", key, value))?;
+        std::fs::write(&path, updated).with_context(|| format!("无法写入配置文件: {}", path.display()))?;
+        Ok(settings)
+    }
+
+    /// 读取一项配置，未设置时为 None
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let key = key.replace('-', "_");
+        let values = serde_json::to_value(self)?;
+        match values.get(&key) {
+            None => Err(anyhow::anyhow!("未知的配置项: {}（可选: {}）", key, Self::keys().join(", "))),
+            Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(value)) => Ok(Some(value.clone())),
+            Some(value) => Ok(Some(value.to_string())),
+        }
+    }
+
+    /// 所有顶层配置项的名称
+    fn keys() -> Vec<String> {
+        match serde_json::to_value(Settings::default()) {
+            Ok(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub fn get_settings_path() -> Result<PathBuf> {
+    let mut path = get_config_dir()?;
+    path.push("config.toml");
+
+    Ok(path)
+}
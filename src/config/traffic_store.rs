@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::path::PathBuf;
+
+/// 一台服务器在统计区间内的流量合计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficTotal {
+    pub server_id: String,
+    /// 最近一次记录时的服务器名称，服务器删除后仍可显示
+    pub server_name: String,
+    pub connections: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub last_used: i64,
+}
+
+/// 内置库 / russh 连接的流量记录（`rssh stats`），与服务器配置共用同一个数据库文件
+pub struct TrafficStore {
+    conn: Connection,
+}
+
+impl TrafficStore {
+    pub fn new(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("无法打开数据库 {}", db_path.display()))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS traffic (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                server_id TEXT NOT NULL,
+                server_name TEXT NOT NULL,
+                bytes_in INTEGER NOT NULL,
+                bytes_out INTEGER NOT NULL,
+                recorded_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS traffic_recorded_at ON traffic (recorded_at);",
+        )?;
+
+        Ok(TrafficStore { conn })
+    }
+
+    /// 记录一次连接的流量
+    pub fn record(&self, server_id: &str, server_name: &str, bytes_in: u64, bytes_out: u64) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO traffic (server_id, server_name, bytes_in, bytes_out, recorded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![server_id, server_name, bytes_in as i64, bytes_out as i64, chrono::Local::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// 按服务器汇总 `since`（Unix 时间戳）之后的流量，总量大的在前；`server_id` 为 None 时汇总所有服务器
+    pub fn totals(&self, server_id: Option<&str>, since: Option<i64>) -> Result<Vec<TrafficTotal>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT server_id,
+                    (SELECT server_name FROM traffic t2 WHERE t2.server_id = t.server_id ORDER BY id DESC LIMIT 1),
+                    COUNT(*), SUM(bytes_in), SUM(bytes_out), MAX(recorded_at)
+             FROM traffic t
+             WHERE (?1 IS NULL OR server_id = ?1) AND recorded_at >= ?2
+             GROUP BY server_id
+             ORDER BY SUM(bytes_in) + SUM(bytes_out) DESC",
+        )?;
+        let totals = stmt.query_map(params![server_id, since.unwrap_or(0)], |row| {
+            Ok(TrafficTotal {
+                server_id: row.get(0)?,
+                server_name: row.get(1)?,
+                connections: row.get::<_, i64>(2)? as u64,
+                bytes_in: row.get::<_, i64>(3)? as u64,
+                bytes_out: row.get::<_, i64>(4)? as u64,
+                last_used: row.get(5)?,
+            })
+        })?;
+        Ok(totals.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+/// `--since` 的取值：`30m`、`24h`、`7d`、`2w` 表示距 `now` 多久以前，或者 `YYYY-MM-DD` 表示当天 0 点
+pub fn parse_since(spec: &str, now: chrono::DateTime<chrono::Local>) -> Result<i64> {
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(spec, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("0 点总是有效的时间");
+        return midnight
+            .and_local_timezone(chrono::Local)
+            .earliest()
+            .map(|time| time.timestamp())
+            .ok_or_else(|| anyhow::anyhow!("无效的日期: {}", spec));
+    }
+
+    let invalid = || anyhow::anyhow!("无法识别的时间: {}，示例: 30m、24h、7d、2w、2024-05-01", spec);
+    let (amount, unit) = spec.split_at(spec.len().saturating_sub(1));
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return Err(invalid()),
+    };
+    Ok(now.timestamp() - amount * seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_per_server_and_filters_by_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TrafficStore::new(dir.path().join("test.db")).unwrap();
+        store.record("a", "web", 100, 10).unwrap();
+        store.record("a", "web-renamed", 200, 20).unwrap();
+        store.record("b", "db", 5000, 1).unwrap();
+
+        let totals = store.totals(None, None).unwrap();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].server_name, "db");
+        assert_eq!((totals[1].server_name.as_str(), totals[1].connections, totals[1].bytes_in, totals[1].bytes_out), ("web-renamed", 2, 300, 30));
+        assert_eq!(store.totals(Some("a"), None).unwrap().len(), 1);
+        assert!(store.totals(None, Some(chrono::Local::now().timestamp() + 60)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_relative_and_absolute_since() {
+        let now = chrono::Local::now();
+        assert_eq!(parse_since("2h", now).unwrap(), now.timestamp() - 7200);
+        assert_eq!(parse_since("1w", now).unwrap(), now.timestamp() - 7 * 86400);
+        assert!(parse_since("2024-05-01", now).is_ok());
+        assert!(parse_since("soon", now).is_err());
+        assert!(parse_since("", now).is_err());
+    }
+}
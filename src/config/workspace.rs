@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 项目配置文件名，放在项目根目录
+pub const WORKSPACE_FILE: &str = ".rssh.toml";
+
+/// 项目级配置（`.rssh.toml`），在项目目录及其子目录中运行时作为 connect / deploy / session-start 的默认值
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Workspace {
+    /// `.rssh.toml` 所在目录，部署目标中的相对路径相对于该目录
+    #[serde(skip)]
+    pub root: PathBuf,
+    /// 项目用到的服务器（名称、ID 或别名），`rssh connect` 未指定服务器时使用
+    pub servers: Vec<String>,
+    /// `rssh session-start` 未指定会话时启动的会话
+    pub session: Option<String>,
+    /// 部署目标（`[deploy.<名称>]`），`rssh deploy [名称]` 未指定目录时使用
+    pub deploy: BTreeMap<String, DeployTarget>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DeployTarget {
+    pub server: String,
+    pub local_dir: PathBuf,
+    pub remote_dir: String,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Workspace {
+    /// 从 `dir` 开始向上查找 `.rssh.toml`，找不到时返回 None
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        let Some(root) = dir.ancestors().find(|d| d.join(WORKSPACE_FILE).is_file()) else {
+            return Ok(None);
+        };
+        let path = root.join(WORKSPACE_FILE);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("无法读取项目配置: {}", path.display()))?;
+        let mut workspace: Workspace = toml::from_str(&content)
+            .with_context(|| format!("无法解析项目配置: {}", path.display()))?;
+        workspace.root = root.to_path_buf();
+        for target in workspace.deploy.values_mut() {
+            target.local_dir = workspace.root.join(&target.local_dir);
+        }
+        Ok(Some(workspace))
+    }
+
+    /// 在当前目录下查找项目配置
+    pub fn current() -> Result<Option<Self>> {
+        match std::env::current_dir() {
+            Ok(dir) => Self::discover(&dir),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// 按名称选择部署目标；未指定名称时只有一个目标则使用它
+    pub fn deploy_target(&self, name: Option<&str>) -> Result<(&str, &DeployTarget)> {
+        let names = || self.deploy.keys().cloned().collect::<Vec<_>>().join(", ");
+        match name {
+            Some(name) => self
+                .deploy
+                .get_key_value(name)
+                .map(|(name, target)| (name.as_str(), target))
+                .ok_or_else(|| anyhow::anyhow!("{} 中没有部署目标 {}（可选: {}）", WORKSPACE_FILE, name, names())),
+            None if self.deploy.len() == 1 => {
+                let (name, target) = self.deploy.iter().next().unwrap();
+                Ok((name.as_str(), target))
+            }
+            None if self.deploy.is_empty() => Err(anyhow::anyhow!("{} 中没有配置部署目标", WORKSPACE_FILE)),
+            None => Err(anyhow::anyhow!("{} 中有多个部署目标，请指定其中之一: {}", WORKSPACE_FILE, names())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovers_workspace_from_subdirectory() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(
+            project.path().join(WORKSPACE_FILE),
+            r#"
+servers = ["web-01", "web-02"]
+session = "shop"
+
+[deploy.staging]
+server = "web-01"
+local_dir = "dist"
+remote_dir = "/var/www/shop"
+exclude = ["*.map"]
+"#,
+        )
+        .unwrap();
+        let nested = project.path().join("src/components");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let workspace = Workspace::discover(&nested).unwrap().unwrap();
+        assert_eq!(workspace.root, project.path());
+        assert_eq!(workspace.servers, ["web-01", "web-02"]);
+        assert_eq!(workspace.session.as_deref(), Some("shop"));
+
+        let (name, target) = workspace.deploy_target(None).unwrap();
+        assert_eq!(name, "staging");
+        assert_eq!(target.local_dir, project.path().join("dist"));
+        assert!(workspace.deploy_target(Some("prod")).is_err());
+
+        let elsewhere = tempfile::tempdir().unwrap();
+        assert!(Workspace::discover(elsewhere.path()).unwrap().is_none());
+    }
+}
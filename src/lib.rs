@@ -1,6 +1,10 @@
 pub mod models;
 pub mod utils {
     pub mod ssh;
+    pub mod address_fallback;
+    pub mod dns_cache;
+    pub mod network_location;
+    pub mod secure_temp;
     pub mod ssh_config;
     pub mod russh_client;
     pub mod terminal_style;
@@ -8,4 +12,22 @@ pub mod utils {
     pub mod kitty_transfer;
     pub mod rzsz;
     pub mod terminal;
+    pub mod secret;
+    pub mod ca;
+    pub mod recording;
+    pub mod ssh_command_builder;
+    pub mod session_escape;
+    pub mod session_registry;
+    pub mod idle_lock;
+    pub mod motd;
+    pub mod zmodem;
+    pub mod login_dialog;
+    pub mod credential_vault;
+    pub mod traffic;
+    pub mod plugin;
+    pub mod hooks;
+    pub mod clipboard;
+    pub mod scrollback;
+    pub mod triggers;
+    pub mod macros;
 } 
\ No newline at end of file
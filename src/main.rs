@@ -5,5 +5,7 @@ mod commands;
 mod rclone;
 
 fn main() -> anyhow::Result<()> {
-    commands::run()
+    let result = commands::run();
+    utils::secure_temp::cleanup();
+    result
 }
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+/// 主机在任务中的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobHostStatus {
+    /// 尚未执行（任务被中断时保留此状态）
+    Pending,
+    Success,
+    Failed,
+}
+
+impl JobHostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobHostStatus::Pending => "pending",
+            JobHostStatus::Success => "success",
+            JobHostStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "success" => JobHostStatus::Success,
+            "failed" => JobHostStatus::Failed,
+            _ => JobHostStatus::Pending,
+        }
+    }
+}
+
+/// 批量执行任务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    /// 任务 ID（8 位十六进制）
+    pub id: String,
+    /// 任务类型，例如 exec
+    pub kind: String,
+    pub command: String,
+    /// 目标分组
+    pub target: Option<String>,
+    /// 由哪个任务重试而来
+    pub parent_id: Option<String>,
+    /// 开始时间（Unix 秒）
+    pub started_at: i64,
+    /// 结束时间，任务被中断时为 None
+    pub finished_at: Option<i64>,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl Job {
+    /// 未执行的主机数量
+    pub fn pending(&self) -> usize {
+        self.total - self.succeeded - self.failed
+    }
+}
+
+/// 任务中单台主机的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobHostRecord {
+    pub server_id: String,
+    pub server_name: String,
+    pub status: JobHostStatus,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
@@ -1,5 +1,9 @@
 mod server;
 mod session;
+mod job;
+mod schedule;
 
 pub use server::*;
-pub use session::*; 
\ No newline at end of file
+pub use session::*;
+pub use job::*;
+pub use schedule::*;
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeZone};
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+
+/// 定时任务：按 cron 表达式在分组上执行命令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    /// 定时任务 ID（8 位十六进制）
+    pub id: String,
+    /// 五段 cron 表达式（分 时 日 月 周），按本地时间计算
+    pub cron: String,
+    pub group: String,
+    pub command: String,
+    /// 最多同时连接的主机数
+    pub parallel: usize,
+    /// 创建时间（Unix 秒）
+    pub created_at: i64,
+    /// 最近一次开始执行的时间
+    pub last_run_at: Option<i64>,
+    /// 最近一次是否全部成功，执行中或从未执行时为 None
+    pub last_ok: Option<bool>,
+}
+
+impl Schedule {
+    /// 解析 cron 表达式，同时用于添加时校验
+    pub fn parse_cron(expr: &str) -> Result<Cron> {
+        Cron::new(expr).parse().with_context(|| format!("无效的 cron 表达式: {}", expr))
+    }
+
+    /// `after` 之后的下一次执行时间
+    pub fn next_run_after(&self, after: DateTime<Local>) -> Result<DateTime<Local>> {
+        Self::parse_cron(&self.cron)?
+            .find_next_occurrence(&after, false)
+            .with_context(|| format!("无法计算 {} 的下一次执行时间", self.cron))
+    }
+
+    /// 从上次执行（从未执行时为创建时间）之后是否已经到了执行时间。
+    /// 调度器停止期间错过的多次执行只补一次
+    pub fn is_due(&self, now: DateTime<Local>) -> Result<bool> {
+        let since = self.last_run_at.unwrap_or(self.created_at);
+        let since = Local.timestamp_opt(since, 0).single().unwrap_or(now);
+        Ok(self.next_run_after(since)? <= now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_becomes_due_after_next_occurrence() {
+        let created = Local.with_ymd_and_hms(2024, 5, 1, 2, 59, 30).unwrap();
+        let mut schedule = Schedule {
+            id: "1a2b3c4d".into(),
+            cron: "0 3 * * *".into(),
+            group: "backup".into(),
+            command: "run-backup.sh".into(),
+            parallel: 10,
+            created_at: created.timestamp(),
+            last_run_at: None,
+            last_ok: None,
+        };
+
+        let three = Local.with_ymd_and_hms(2024, 5, 1, 3, 0, 0).unwrap();
+        assert_eq!(schedule.next_run_after(created).unwrap(), three);
+        assert!(!schedule.is_due(created + chrono::Duration::seconds(29)).unwrap());
+        assert!(schedule.is_due(three).unwrap());
+
+        // 执行过一次后要等到第二天
+        schedule.last_run_at = Some(three.timestamp());
+        assert!(!schedule.is_due(three + chrono::Duration::hours(12)).unwrap());
+        assert!(Schedule::parse_cron("61 * * * *").is_err());
+    }
+}
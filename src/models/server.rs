@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use crate::utils::secret::{resolve_secret, resolve_secret_with};
+use crate::utils::session_escape::Forward;
 use crate::utils::terminal_style::{Style, Styled, StyledText};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +16,530 @@ pub struct ServerConfig {
     pub password: Option<String>,
     pub group: Option<String>,
     pub description: Option<String>,
+    /// 密钥提供者（db/env/cmd/pass/bw/op/keychain/vault），设置后密码字段按该提供者的引用解析
+    #[serde(default)]
+    pub secret_provider: Option<String>,
+    /// 连接类型，默认为 SSH
+    #[serde(default)]
+    pub connection_type: ConnectionType,
+    /// 启用 SSH 压缩（zlib），慢速链路上传输文本较多时有明显效果
+    #[serde(default)]
+    pub compression: bool,
+    /// 加密 / 密钥交换 / MAC / 主机密钥算法偏好
+    #[serde(default)]
+    pub algorithms: Algorithms,
+    /// 主机密钥校验策略，未设置时使用全局配置
+    #[serde(default)]
+    pub host_key_policy: Option<HostKeyPolicy>,
+    /// 远程登录横幅 / MOTD 的处理方式
+    #[serde(default)]
+    pub login_banner: LoginBanner,
+    /// 连接前在本地显示的提示，例如 "生产环境，变更需要工单"
+    #[serde(default)]
+    pub note: Option<String>,
+    /// SSH agent 转发，默认关闭
+    #[serde(default)]
+    pub forward_agent: ForwardAgent,
+    /// 最近一次通过 `rssh passwd` 修改密码的时间（Unix 时间戳）
+    #[serde(default)]
+    pub password_changed_at: Option<i64>,
+    /// 备用地址（内网 IP、VPN 域名等），连接时与主机地址一起按顺序尝试
+    #[serde(default)]
+    pub alt_hosts: Vec<String>,
+    /// 上次连接成功的地址，下次优先尝试
+    #[serde(default)]
+    pub preferred_host: Option<String>,
+    /// 在内网时使用的地址，是否在内网由 `network` 对应的检测命令判断
+    #[serde(default)]
+    pub internal_host: Option<String>,
+    /// 网络位置名称，对应 config.toml 中的 `[networks.<名称>]`；只定义了一个网络时可省略
+    #[serde(default)]
+    pub network: Option<String>,
+    /// 跳板机（`[user@]host[:port]`），不在内网时经跳板机连接
+    #[serde(default)]
+    pub jump_host: Option<String>,
+    /// 经另一台已保存的服务器（名称或 ID）连接，该服务器也可以再经其它服务器，形成多跳；优先于 `jump_host`
+    #[serde(default)]
+    pub proxy_jump: Option<String>,
+    /// 代理命令（OpenSSH `ProxyCommand`，支持 `%h` `%p` `%r`），没有跳板机时用它连接服务器
+    #[serde(default)]
+    pub proxy_command: Option<String>,
+    /// 传给系统 ssh 的其它选项（`Key=Value`），例如从 ~/.ssh/config 导入的 `ServerAliveInterval=30`
+    #[serde(default)]
+    pub extra_options: Vec<String>,
+    /// 别名，connect / upload / exec 等命令中可以代替名称使用，例如旧 ssh_config 里的短名
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// 快捷编号，`rssh 3` 直接连接编号为 3 的服务器
+    #[serde(default)]
+    pub slot: Option<u32>,
+    /// `rssh exec` 在该服务器上允许 / 禁止执行的命令
+    #[serde(default)]
+    pub command_policy: CommandPolicy,
+    /// 系统 ssh 经 expect 自动填密码时匹配的提示
+    #[serde(default)]
+    pub prompts: PromptPatterns,
+    /// 登录后依次执行的对话（例如网络设备的 enable 密码、菜单选择），内置库连接时执行
+    #[serde(default)]
+    pub login_dialog: Vec<DialogStep>,
+    /// 密钥认证的密钥之外再尝试的私钥，按顺序尝试
+    #[serde(default)]
+    pub identity_files: Vec<String>,
+    /// 只使用配置的私钥（IdentitiesOnly），agent 中的其它密钥不再逐个尝试，避免超出服务器的认证次数上限
+    #[serde(default)]
+    pub identities_only: bool,
+    /// 防空闲：会话空闲时定期发送 keepalive 等，应对设置了 ServerAliveInterval 仍会断开空闲连接的防火墙
+    #[serde(default)]
+    pub anti_idle: Option<AntiIdle>,
+    /// 本次连接使用的 SSH 证书，由团队 CA 在连接前签发，不保存到数据库
+    #[serde(skip)]
+    pub certificate: Option<String>,
+    /// 本次连接的本地端口转发（`rssh connect -L`），不保存到数据库
+    #[serde(skip)]
+    pub local_forwards: Vec<Forward>,
+    /// `proxy_jump` 解析出的各跳服务器，第一个直接连接，由 `with_resolved_secrets` 填入，不保存到数据库
+    #[serde(skip)]
+    pub jump_servers: Vec<ServerConfig>,
+}
+
+/// SSH agent 转发：开启后服务器上的进程可以使用本机 agent 中的密钥，服务器被入侵时有风险
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardAgent {
+    /// 不转发
+    #[default]
+    No,
+    /// 总是转发
+    Yes,
+    /// 每次连接前确认
+    Confirm,
+}
+
+static AGENT_FORWARDING_GROUPS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// 按名称或 ID 查找已保存的服务器，由 rssh 启动时设置；未设置时无法使用 `proxy_jump`
+static SERVER_LOOKUP: OnceLock<fn(&str) -> anyhow::Result<ServerConfig>> = OnceLock::new();
+
+/// `proxy_jump` 最多经过的服务器数，超过时视为循环引用
+const MAX_JUMPS: usize = 8;
+
+impl ForwardAgent {
+    /// 只允许这些分组的服务器转发 agent（来自 config.toml），只在启动时调用一次
+    pub fn restrict_to_groups(groups: Vec<String>) {
+        let _ = AGENT_FORWARDING_GROUPS.set(groups);
+    }
+
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "no" => Ok(ForwardAgent::No),
+            "yes" => Ok(ForwardAgent::Yes),
+            "confirm" => Ok(ForwardAgent::Confirm),
+            _ => Err(anyhow::anyhow!("未知的 agent 转发设置: {} (可选: no/yes/confirm)", value)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForwardAgent::No => "no",
+            ForwardAgent::Yes => "yes",
+            ForwardAgent::Confirm => "confirm",
+        }
+    }
+}
+
+/// 远程登录横幅 / MOTD 的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoginBanner {
+    /// 原样显示
+    #[default]
+    Show,
+    /// 不显示
+    Quiet,
+    /// 不显示，保存到 `~/.config/rssh/motd/<服务器名>.txt`
+    Capture,
+}
+
+impl LoginBanner {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "show" => Ok(LoginBanner::Show),
+            "quiet" => Ok(LoginBanner::Quiet),
+            "capture" => Ok(LoginBanner::Capture),
+            _ => Err(anyhow::anyhow!("未知的登录横幅处理方式: {} (可选: show/quiet/capture)", value)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoginBanner::Show => "show",
+            LoginBanner::Quiet => "quiet",
+            LoginBanner::Capture => "capture",
+        }
+    }
+}
+
+/// 主机密钥校验策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostKeyPolicy {
+    /// 只接受 known_hosts 中已有的主机密钥
+    Strict,
+    /// 首次连接时记录主机密钥，之后密钥变化则拒绝连接
+    #[default]
+    AcceptNew,
+    /// 不校验主机密钥
+    Off,
+}
+
+static DEFAULT_HOST_KEY_POLICY: OnceLock<HostKeyPolicy> = OnceLock::new();
+
+impl HostKeyPolicy {
+    /// 设置进程内的全局默认策略（来自 config.toml），只在启动时调用一次
+    pub fn set_default(policy: HostKeyPolicy) {
+        let _ = DEFAULT_HOST_KEY_POLICY.set(policy);
+    }
+
+    /// 全局默认策略，未配置时为 accept-new
+    pub fn global_default() -> HostKeyPolicy {
+        DEFAULT_HOST_KEY_POLICY.get().copied().unwrap_or_default()
+    }
+
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "strict" | "yes" => Ok(HostKeyPolicy::Strict),
+            "accept-new" => Ok(HostKeyPolicy::AcceptNew),
+            "off" | "no" => Ok(HostKeyPolicy::Off),
+            _ => Err(anyhow::anyhow!("未知的主机密钥策略: {} (可选: strict/accept-new/off)", value)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HostKeyPolicy::Strict => "strict",
+            HostKeyPolicy::AcceptNew => "accept-new",
+            HostKeyPolicy::Off => "off",
+        }
+    }
+
+    /// 对应 OpenSSH 的 StrictHostKeyChecking 取值
+    pub fn ssh_value(&self) -> &'static str {
+        match self {
+            HostKeyPolicy::Strict => "yes",
+            HostKeyPolicy::AcceptNew => "accept-new",
+            HostKeyPolicy::Off => "no",
+        }
+    }
+}
+
+static AUDIT_MODE: AtomicBool = AtomicBool::new(false);
+
+/// 开启审计模式：禁止密码认证、强制严格校验主机密钥、只使用 FIPS 认可的算法，
+/// 并把远程执行的命令写入审计日志。只在启动时调用
+pub fn enable_audit_mode() {
+    AUDIT_MODE.store(true, Ordering::Relaxed);
+}
+
+pub fn audit_mode() -> bool {
+    AUDIT_MODE.load(Ordering::Relaxed)
+}
+
+static SSH_VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// 设置 SSH 调试输出级别（`--ssh-verbose` 的次数，最多 3），只在启动时调用
+pub fn set_ssh_verbosity(level: u8) {
+    SSH_VERBOSITY.store(level.min(3), Ordering::Relaxed);
+}
+
+pub fn ssh_verbosity() -> u8 {
+    SSH_VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// 系统 ssh/scp/sftp 的 LogLevel，`verbosity` 为 `--ssh-verbose` 的次数
+fn log_level(verbosity: u8, login_banner: LoginBanner) -> Option<&'static str> {
+    match verbosity {
+        // 系统 ssh 只能隐藏认证前的横幅（Banner），登录后 shell 输出的 MOTD 需要内置库模式处理
+        0 if login_banner != LoginBanner::Show => Some("ERROR"),
+        0 => None,
+        // 与 -v/-vv/-vvv 相同，scp、sftp 和 rsync 使用的 ssh 也会输出调试信息
+        1 => Some("DEBUG1"),
+        2 => Some("DEBUG2"),
+        _ => Some("DEBUG3"),
+    }
+}
+
+/// 算法偏好，值使用 OpenSSH 的写法：`a,b` 替换默认列表，`+a` 追加到默认列表末尾，
+/// `-a` 从默认列表中移除，`^a` 放到默认列表最前面
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Algorithms {
+    pub ciphers: Option<String>,
+    pub kex: Option<String>,
+    pub macs: Option<String>,
+    /// 同时用于 HostKeyAlgorithms 和 PubkeyAcceptedAlgorithms
+    pub host_key: Option<String>,
+}
+
+impl Algorithms {
+    /// 未配置主机密钥算法时的默认值，兼容只支持 ssh-rsa 的老设备
+    pub const LEGACY_HOST_KEY: &'static str = "+ssh-rsa";
+
+    pub fn host_key(&self) -> &str {
+        self.host_key.as_deref().unwrap_or(Self::LEGACY_HOST_KEY)
+    }
+
+    /// 审计模式下使用的算法，全部为 FIPS 140 认可的算法，不允许服务器配置放宽
+    pub fn fips() -> Self {
+        Algorithms {
+            ciphers: Some("aes256-gcm@openssh.com,aes128-gcm@openssh.com,aes256-ctr,aes192-ctr,aes128-ctr".into()),
+            kex: Some(
+                "ecdh-sha2-nistp384,ecdh-sha2-nistp256,ecdh-sha2-nistp521,\
+                 diffie-hellman-group16-sha512,diffie-hellman-group14-sha256"
+                    .into(),
+            ),
+            macs: Some(
+                "hmac-sha2-256-etm@openssh.com,hmac-sha2-512-etm@openssh.com,hmac-sha2-256,hmac-sha2-512".into(),
+            ),
+            host_key: Some(
+                "ecdsa-sha2-nistp256,ecdsa-sha2-nistp384,ecdsa-sha2-nistp521,rsa-sha2-512,rsa-sha2-256".into(),
+            ),
+        }
+    }
+
+    /// 按 OpenSSH 的规则把偏好展开为完整的算法列表
+    pub fn resolve(spec: &str, defaults: &[&str]) -> Vec<String> {
+        let list = |s: &str| -> Vec<String> {
+            s.split(',').map(str::trim).filter(|a| !a.is_empty()).map(String::from).collect()
+        };
+        let defaults: Vec<String> = defaults.iter().map(|a| a.to_string()).collect();
+
+        if let Some(extra) = spec.strip_prefix('+') {
+            let mut result = defaults;
+            for name in list(extra) {
+                if !result.contains(&name) {
+                    result.push(name);
+                }
+            }
+            result
+        } else if let Some(removed) = spec.strip_prefix('-') {
+            let removed = list(removed);
+            defaults.into_iter().filter(|a| !removed.contains(a)).collect()
+        } else if let Some(first) = spec.strip_prefix('^') {
+            let first = list(first);
+            let rest: Vec<String> = defaults.into_iter().filter(|a| !first.contains(a)).collect();
+            [first, rest].concat()
+        } else {
+            list(spec)
+        }
+    }
+}
+
+/// 敏感主机上限制 `rssh exec` 可以执行的命令。每条规则是一个正则表达式，需要匹配整条命令；
+/// 命中禁止规则，或者设置了允许规则却一条都不匹配时拒绝执行
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandPolicy {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl CommandPolicy {
+    /// 检查所有规则都是合法的正则表达式
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for pattern in self.allow.iter().chain(&self.deny) {
+            Self::compile(pattern)?;
+        }
+        Ok(())
+    }
+
+    /// 命令不允许执行时返回原因
+    pub fn violation(&self, command: &str) -> anyhow::Result<Option<String>> {
+        let command = command.trim();
+        for pattern in &self.deny {
+            if Self::compile(pattern)?.is_match(command) {
+                return Ok(Some(format!("命中禁止规则 {}", pattern)));
+            }
+        }
+        if self.allow.is_empty() {
+            return Ok(None);
+        }
+        for pattern in &self.allow {
+            if Self::compile(pattern)?.is_match(command) {
+                return Ok(None);
+            }
+        }
+        Ok(Some("不在允许执行的命令列表中".to_string()))
+    }
+
+    fn compile(pattern: &str) -> anyhow::Result<regex::Regex> {
+        regex::Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|e| anyhow::anyhow!("无效的命令规则 {}: {}", pattern, e))
+    }
+}
+
+/// expect 自动填密码时匹配的提示（Tcl 正则，不区分大小写），未设置时使用默认值。
+/// 服务器的密码提示不是英文或格式特殊时按服务器单独设置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptPatterns {
+    /// 密码提示
+    pub password: Option<String>,
+    /// 密钥认证加备用密码时，发送密码后等待的就绪提示（例如堡垒机菜单的 `Opt>`）
+    pub ready: Option<String>,
+}
+
+impl PromptPatterns {
+    /// 默认的密码提示：常见语言的"密码"后跟冒号
+    pub const DEFAULT_PASSWORD: &'static str =
+        r"(password|passwort|mot de passe|contraseña|senha|parola|密码|口令|パスワード|암호)[^\n]*[:：] *$";
+    pub const DEFAULT_READY: &'static str = "Opt>";
+
+    pub fn password(&self) -> &str {
+        self.password.as_deref().unwrap_or(Self::DEFAULT_PASSWORD)
+    }
+
+    pub fn ready(&self) -> &str {
+        self.ready.as_deref().unwrap_or(Self::DEFAULT_READY)
+    }
+}
+
+/// 登录对话中的一步：等到远程输出匹配 `expect`（正则）后发送 `send` 并回车。
+/// `send` 可以是密钥引用（`env:`、`pass:` 等），连接前解析
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DialogStep {
+    pub expect: String,
+    pub send: String,
+}
+
+impl DialogStep {
+    /// 解析 `<正则> => <发送内容>`，例如 `Password: => env:ENABLE_PASS`
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (expect, send) = spec
+            .split_once("=>")
+            .ok_or_else(|| anyhow::anyhow!("登录对话的格式为 <正则> => <发送内容>: {}", spec))?;
+        let step = DialogStep { expect: expect.trim().to_string(), send: send.trim().to_string() };
+        step.pattern()?;
+        Ok(step)
+    }
+
+    pub fn pattern(&self) -> anyhow::Result<regex::Regex> {
+        regex::Regex::new(&self.expect).map_err(|e| anyhow::anyhow!("无效的登录对话正则 {}: {}", self.expect, e))
+    }
+}
+
+impl std::fmt::Display for DialogStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} => {}", self.expect, self.send)
+    }
+}
+
+/// 防空闲设置：交互式会话没有输入输出超过 `interval` 秒时发送一次 `method`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AntiIdle {
+    pub interval: u32,
+    pub method: AntiIdleMethod,
+}
+
+/// 防空闲发送的内容。默认只发送协议层的 keepalive，不模拟按键
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AntiIdleMethod {
+    /// SSH keepalive 请求（与 ServerAliveInterval 相同），远程 shell 看不到
+    #[default]
+    Keepalive,
+    /// 重新发送当前的窗口大小，产生会话通道上的流量，全屏程序可能重绘一次
+    Resize,
+    /// 向远程发送一个 NUL 字节（Ctrl+@），相当于一次按键，大多数 shell 会忽略
+    Nul,
+}
+
+impl AntiIdle {
+    /// 解析 `<秒>[:keepalive|resize|nul]`，例如 `60`、`300:resize`
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (interval, method) = spec.split_once(':').unwrap_or((spec, "keepalive"));
+        let interval: u32 = interval
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&secs| secs > 0)
+            .ok_or_else(|| anyhow::anyhow!("防空闲的格式为 <秒>[:keepalive|resize|nul]，秒数需大于 0: {}", spec))?;
+        let method = match method.trim() {
+            "keepalive" => AntiIdleMethod::Keepalive,
+            "resize" => AntiIdleMethod::Resize,
+            "nul" => AntiIdleMethod::Nul,
+            other => return Err(anyhow::anyhow!("未知的防空闲方式: {} (可选: keepalive/resize/nul)", other)),
+        };
+        Ok(AntiIdle { interval, method })
+    }
+}
+
+impl AntiIdleMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AntiIdleMethod::Keepalive => "keepalive",
+            AntiIdleMethod::Resize => "resize",
+            AntiIdleMethod::Nul => "nul",
+        }
+    }
+}
+
+impl std::fmt::Display for AntiIdle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.method {
+            AntiIdleMethod::Keepalive => write!(f, "{}", self.interval),
+            method => write!(f, "{}:{}", self.interval, method.name()),
+        }
+    }
+}
+
+/// 服务器的连接方式。除 SSH 外，串口控制台和 IPMI SOL 也可以放进同一份清单
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionType {
+    #[default]
+    Ssh,
+    /// 串口控制台，`host` 为设备路径（如 /dev/ttyUSB0）
+    Serial { baud: u32 },
+    /// IPMI/iDRAC Serial-over-LAN，`host` 为 BMC 地址，用户名/密码为 BMC 凭据
+    Ipmi,
+    /// 只支持 telnet 的老旧网络设备
+    Telnet,
+}
+
+impl ConnectionType {
+    /// 串口默认波特率
+    pub const DEFAULT_BAUD: u32 = 115200;
+
+    /// 根据命令行参数构造连接类型
+    pub fn parse(kind: &str, baud: Option<u32>) -> anyhow::Result<Self> {
+        match kind {
+            "ssh" => Ok(ConnectionType::Ssh),
+            "serial" => Ok(ConnectionType::Serial { baud: baud.unwrap_or(Self::DEFAULT_BAUD) }),
+            "ipmi" => Ok(ConnectionType::Ipmi),
+            "telnet" => Ok(ConnectionType::Telnet),
+            _ => Err(anyhow::anyhow!("未知的连接类型: {} (可选: ssh/serial/ipmi/telnet)", kind)),
+        }
+    }
+
+    /// 类型名称，与命令行参数、数据库中保存的值一致
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConnectionType::Ssh => "ssh",
+            ConnectionType::Serial { .. } => "serial",
+            ConnectionType::Ipmi => "ipmi",
+            ConnectionType::Telnet => "telnet",
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionType::Ssh => write!(f, "SSH"),
+            ConnectionType::Serial { baud } => write!(f, "串口 ({} baud)", baud),
+            ConnectionType::Ipmi => write!(f, "IPMI SOL"),
+            ConnectionType::Telnet => write!(f, "Telnet"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,11 +558,11 @@ impl AuthType {
         }
     }
 
-    /// 获取 SSH 命令参数
-    pub fn get_ssh_args(&self) -> String {
+    /// 获取保存的密码（如果是密码认证）
+    pub fn password(&self) -> Option<&str> {
         match self {
-            AuthType::Key(path) => format!("-i {}", path),
-            _ => String::new(),
+            AuthType::Password(password) => Some(password),
+            _ => None,
         }
     }
 }
@@ -72,7 +600,268 @@ impl ServerConfig {
             password,
             group,
             description,
+            secret_provider: None,
+            connection_type: ConnectionType::Ssh,
+            compression: false,
+            algorithms: Algorithms::default(),
+            host_key_policy: None,
+            login_banner: LoginBanner::Show,
+            note: None,
+            forward_agent: ForwardAgent::No,
+            password_changed_at: None,
+            alt_hosts: Vec::new(),
+            preferred_host: None,
+            internal_host: None,
+            network: None,
+            jump_host: None,
+            proxy_jump: None,
+            proxy_command: None,
+            extra_options: Vec::new(),
+            aliases: Vec::new(),
+            slot: None,
+            command_policy: CommandPolicy::default(),
+            prompts: PromptPatterns::default(),
+            login_dialog: Vec::new(),
+            identity_files: Vec::new(),
+            identities_only: false,
+            anti_idle: None,
+            certificate: None,
+            local_forwards: Vec::new(),
+            jump_servers: Vec::new(),
+        }
+    }
+
+    /// 连接时依次尝试的地址：上次成功的地址、主机地址、备用地址，去掉重复
+    pub fn addresses(&self) -> Vec<&str> {
+        let mut addresses: Vec<&str> = Vec::new();
+        let candidates = self.preferred_host.iter().chain(std::iter::once(&self.host)).chain(&self.alt_hosts);
+        for address in candidates {
+            // 备用地址被删掉后，上次成功的地址不再使用
+            let known = *address == self.host || self.alt_hosts.contains(address);
+            if known && !addresses.contains(&address.as_str()) {
+                addresses.push(address);
+            }
+        }
+        addresses
+    }
+
+    /// 实际生效的主机密钥策略：服务器配置优先，其次是全局配置；审计模式下总是 strict
+    pub fn host_key_policy(&self) -> HostKeyPolicy {
+        if audit_mode() {
+            return HostKeyPolicy::Strict;
+        }
+        self.host_key_policy.unwrap_or_else(HostKeyPolicy::global_default)
+    }
+
+    /// 服务器所在分组是否允许转发 agent，未限制分组时总是允许
+    pub fn agent_forwarding_allowed(&self) -> bool {
+        match AGENT_FORWARDING_GROUPS.get() {
+            Some(groups) => self.group.as_ref().is_some_and(|group| groups.contains(group)),
+            None => true,
+        }
+    }
+
+    /// 是否在 `servers`（名称或别名）/ `groups` 限定的范围内，两者都为空时表示所有服务器
+    pub fn in_scope(&self, servers: &[String], groups: &[String]) -> bool {
+        if servers.is_empty() && groups.is_empty() {
+            return true;
+        }
+        servers.iter().any(|name| *name == self.name || self.aliases.contains(name))
+            || self.group.as_ref().is_some_and(|group| groups.contains(group))
+    }
+
+    /// 依次尝试的私钥：密钥认证的密钥在前，其后是额外配置的私钥
+    pub fn identities(&self) -> Vec<&str> {
+        self.auth_type.get_key_path().into_iter()
+            .chain(self.identity_files.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// 实际生效的算法偏好，审计模式下忽略服务器配置
+    pub fn algorithms(&self) -> Algorithms {
+        if audit_mode() {
+            Algorithms::fips()
+        } else {
+            self.algorithms.clone()
+        }
+    }
+
+    /// 按服务器配置生成的系统 ssh/scp/sftp 附加选项（`-o Key=Value` 形式）
+    pub fn ssh_options(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        let mut push = |key: &str, value: &str| {
+            options.push("-o".to_string());
+            options.push(format!("{}={}", key, value));
+        };
+
+        push("StrictHostKeyChecking", self.host_key_policy().ssh_value());
+        if let Some(level) = log_level(ssh_verbosity(), self.login_banner) {
+            push("LogLevel", level);
+        }
+        if self.compression {
+            push("Compression", "yes");
+        }
+        // confirm 需要在连接前确认，确认后改为 yes，因此这里只处理 yes
+        if self.forward_agent == ForwardAgent::Yes && self.agent_forwarding_allowed() {
+            push("ForwardAgent", "yes");
+        }
+        if let Some(cert) = &self.certificate {
+            // 路径含空格时（如 macOS 的 Application Support）需要加引号
+            if cert.contains(char::is_whitespace) {
+                push("CertificateFile", &format!("\"{}\"", cert));
+            } else {
+                push("CertificateFile", cert);
+            }
+        }
+        if let Some(jump) = self.proxy_jump_spec().or_else(|| self.jump_host.clone()) {
+            push("ProxyJump", &jump);
+        } else if let Some(command) = &self.proxy_command {
+            push("ProxyCommand", command);
+        }
+        // 密钥认证的密钥由调用方以 -i 传入，这里只加额外的私钥
+        for file in &self.identity_files {
+            let file = crate::utils::ssh_config::expand_tilde(file);
+            if file.contains(char::is_whitespace) {
+                push("IdentityFile", &format!("\"{}\"", file));
+            } else {
+                push("IdentityFile", &file);
+            }
+        }
+        if self.identities_only {
+            push("IdentitiesOnly", "yes");
+        }
+        // 系统 ssh 只能发送 keepalive
+        if let Some(anti_idle) = self.anti_idle {
+            push("ServerAliveInterval", &anti_idle.interval.to_string());
+        }
+
+        let algorithms = self.algorithms();
+        push("HostKeyAlgorithms", algorithms.host_key());
+        push("PubkeyAcceptedAlgorithms", algorithms.host_key());
+        if let Some(ciphers) = &algorithms.ciphers {
+            push("Ciphers", ciphers);
+        }
+        if let Some(kex) = &algorithms.kex {
+            push("KexAlgorithms", kex);
+        }
+        if let Some(macs) = &algorithms.macs {
+            push("MACs", macs);
+        }
+        // 放在最后：同一选项出现多次时 ssh 使用第一个值，rssh 自己的设置优先
+        for option in &self.extra_options {
+            if let Some((key, value)) = option.split_once('=') {
+                push(key, value);
+            }
         }
+        options
+    }
+
+    /// 返回一份解析了密钥引用（`env:`/`cmd:`/`pass:` 等）的副本，在真正连接前调用。
+    ///
+    /// 数据库中保存的仍是引用本身，解析结果只存在于本次进程的内存里。
+    /// 配置了 `secret_provider` 时，密码字段整体作为该提供者的引用。
+    /// 同时按当前网络位置选择内网地址或跳板机。
+    pub fn with_resolved_secrets(&self) -> anyhow::Result<ServerConfig> {
+        if audit_mode() && (matches!(self.auth_type, AuthType::Password(_)) || self.password.is_some()) {
+            return Err(anyhow::anyhow!(
+                "审计模式下禁止密码认证: {}，请改用密钥或 SSH Agent",
+                self.name
+            ));
+        }
+        if audit_mode() && self.connection_type == ConnectionType::Telnet {
+            return Err(anyhow::anyhow!("审计模式下禁止明文 telnet 连接: {}", self.name));
+        }
+
+        let mut resolved = self.clone();
+
+        let resolve_password = |value: &str| match &self.secret_provider {
+            Some(provider) => resolve_secret_with(provider, value),
+            None => resolve_secret(value),
+        };
+
+        resolved.auth_type = match &self.auth_type {
+            AuthType::Password(pwd) => AuthType::Password(resolve_password(pwd)?),
+            AuthType::Key(key_path) => AuthType::Key(resolve_secret(key_path)?),
+            AuthType::Agent => AuthType::Agent,
+        };
+
+        if let Some(password) = &self.password {
+            resolved.password = Some(resolve_password(password)?);
+        }
+        for step in &mut resolved.login_dialog {
+            step.send = resolve_secret(&step.send)?;
+        }
+        resolved.certificate = crate::utils::ca::certificate_for(&resolved)?;
+        crate::utils::network_location::apply(&mut resolved)?;
+        resolved.jump_servers = resolved.resolve_proxy_jump()?;
+
+        Ok(resolved)
+    }
+
+    /// 设置按名称或 ID 查找已保存服务器的方式（用于 `proxy_jump`），只在启动时调用一次
+    pub fn set_server_lookup(lookup: fn(&str) -> anyhow::Result<ServerConfig>) {
+        let _ = SERVER_LOOKUP.set(lookup);
+    }
+
+    /// 查找 `proxy_jump` 指向的服务器链并解析各跳的密码，第一个为直接连接的服务器
+    fn resolve_proxy_jump(&self) -> anyhow::Result<Vec<ServerConfig>> {
+        let mut chain: Vec<ServerConfig> = Vec::new();
+        let mut next = self.proxy_jump.clone();
+        while let Some(name) = next {
+            let lookup = SERVER_LOOKUP
+                .get()
+                .ok_or_else(|| anyhow::anyhow!("无法查找跳板服务器 {}", name))?;
+            let mut jump = lookup(&name).map_err(|e| anyhow::anyhow!("找不到跳板服务器 {}: {:#}", name, e))?;
+            if jump.id == self.id || chain.iter().any(|hop| hop.id == jump.id) || chain.len() >= MAX_JUMPS {
+                return Err(anyhow::anyhow!("{} 的跳板服务器形成了循环: {}", self.name, name));
+            }
+            next = jump.proxy_jump.take();
+            chain.push(jump);
+        }
+        chain.reverse();
+        chain.iter().map(ServerConfig::with_resolved_secrets).collect()
+    }
+
+    /// 是否经跳板机、跳板服务器或代理命令连接，此时目标地址由跳板解析和连接
+    pub fn uses_jump(&self) -> bool {
+        self.jump_host.is_some() || self.proxy_jump.is_some() || self.proxy_command.is_some()
+    }
+
+    /// 展开代理命令中的 `%h`（主机）、`%p`（端口）、`%r`（用户名）和 `%%`
+    pub fn expanded_proxy_command(&self) -> Option<String> {
+        let command = self.proxy_command.as_ref()?;
+        let mut expanded = String::with_capacity(command.len());
+        let mut chars = command.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('h') => expanded.push_str(&self.host),
+                Some('p') => expanded.push_str(&self.port.to_string()),
+                Some('r') => expanded.push_str(&self.username),
+                Some('%') => expanded.push('%'),
+                Some(other) => {
+                    expanded.push('%');
+                    expanded.push(other);
+                },
+                None => expanded.push('%'),
+            }
+        }
+        Some(expanded)
+    }
+
+    /// 系统 ssh `-J` 的参数：依次经过的 `user@host:port`；第一跳自己配置了 `jump_host` 时排在最前面
+    pub fn proxy_jump_spec(&self) -> Option<String> {
+        let first = self.jump_servers.first()?;
+        let hops: Vec<String> = first
+            .jump_host
+            .iter()
+            .cloned()
+            .chain(self.jump_servers.iter().map(|hop| format!("{}@{}:{}", hop.username, hop.host, hop.port)))
+            .collect();
+        Some(hops.join(","))
     }
 }
 
@@ -85,4 +874,188 @@ pub struct Server {
     pub auth_type: AuthType,
     pub auth_data: Option<String>,
     pub group: Option<String>,
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_ssh_verbosity_to_log_level() {
+        assert_eq!(log_level(0, LoginBanner::Show), None);
+        assert_eq!(log_level(0, LoginBanner::Quiet), Some("ERROR"));
+        // 调试输出优先于隐藏横幅
+        assert_eq!(log_level(1, LoginBanner::Quiet), Some("DEBUG1"));
+        assert_eq!(log_level(2, LoginBanner::Show), Some("DEBUG2"));
+        assert_eq!(log_level(3, LoginBanner::Show), Some("DEBUG3"));
+        assert_eq!(log_level(7, LoginBanner::Capture), Some("DEBUG3"));
+    }
+
+    #[test]
+    fn maps_host_key_policy_to_strict_host_key_checking() {
+        for (name, policy, ssh_value) in [
+            ("strict", HostKeyPolicy::Strict, "yes"),
+            ("accept-new", HostKeyPolicy::AcceptNew, "accept-new"),
+            ("off", HostKeyPolicy::Off, "no"),
+        ] {
+            assert_eq!(HostKeyPolicy::parse(name).unwrap(), policy);
+            assert_eq!(policy.name(), name);
+            assert_eq!(policy.ssh_value(), ssh_value);
+        }
+        // 也接受 OpenSSH 的写法
+        assert_eq!(HostKeyPolicy::parse("yes").unwrap(), HostKeyPolicy::Strict);
+        assert_eq!(HostKeyPolicy::parse("no").unwrap(), HostKeyPolicy::Off);
+        assert!(HostKeyPolicy::parse("ask").is_err());
+
+        let mut server = ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+            AuthType::Agent, None, None, None,
+        );
+        // 未配置时使用全局默认 accept-new，服务器配置优先
+        assert_eq!(server.host_key_policy(), HostKeyPolicy::AcceptNew);
+        assert!(server.ssh_options().contains(&"StrictHostKeyChecking=accept-new".to_string()));
+        server.host_key_policy = Some(HostKeyPolicy::Off);
+        assert!(server.ssh_options().contains(&"StrictHostKeyChecking=no".to_string()));
+        assert!(!server.ssh_options().contains(&"StrictHostKeyChecking=accept-new".to_string()));
+    }
+
+    #[test]
+    fn resolves_openssh_algorithm_syntax() {
+        let defaults = ["curve25519-sha256", "diffie-hellman-group14-sha256"];
+        assert_eq!(
+            Algorithms::resolve("+diffie-hellman-group1-sha1", &defaults),
+            vec!["curve25519-sha256", "diffie-hellman-group14-sha256", "diffie-hellman-group1-sha1"]
+        );
+        assert_eq!(Algorithms::resolve("-curve25519-sha256", &defaults), vec!["diffie-hellman-group14-sha256"]);
+        assert_eq!(
+            Algorithms::resolve("^diffie-hellman-group14-sha256", &defaults),
+            vec!["diffie-hellman-group14-sha256", "curve25519-sha256"]
+        );
+        assert_eq!(Algorithms::resolve("a, b", &defaults), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn forwards_agent_only_when_confirmed() {
+        let mut server = ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+            AuthType::Agent, None, None, None,
+        );
+        let forwards = |server: &ServerConfig| server.ssh_options().contains(&"ForwardAgent=yes".to_string());
+        assert!(!forwards(&server));
+        // confirm 未经确认时不转发
+        server.forward_agent = ForwardAgent::Confirm;
+        assert!(!forwards(&server));
+        server.forward_agent = ForwardAgent::Yes;
+        assert!(forwards(&server));
+    }
+
+    #[test]
+    fn command_policy_matches_whole_command() {
+        let policy = CommandPolicy {
+            allow: vec![r"systemctl status \S+".into(), "uptime".into()],
+            deny: vec![r".*\brm\b.*".into()],
+        };
+        assert_eq!(policy.violation("uptime").unwrap(), None);
+        assert_eq!(policy.violation(" systemctl status nginx ").unwrap(), None);
+        // 允许规则需要匹配整条命令，不能在后面追加其它命令
+        assert!(policy.violation("uptime; reboot").unwrap().is_some());
+        assert!(policy.violation("systemctl status nginx && rm -rf /tmp/x").unwrap().unwrap().contains("禁止"));
+        assert_eq!(CommandPolicy::default().violation("reboot").unwrap(), None);
+        assert!(CommandPolicy { allow: vec!["(".into()], deny: vec![] }.validate().is_err());
+    }
+
+    #[test]
+    fn default_password_prompt_matches_common_languages() {
+        let prompt = regex::Regex::new(&format!("(?i){}", PromptPatterns::DEFAULT_PASSWORD)).unwrap();
+        for text in ["root@10.0.0.1's password: ", "Password:", "Passwort für root: ", "密码：", "[sudo] 密码："] {
+            assert!(prompt.is_match(text), "{}", text);
+        }
+        assert!(!prompt.is_match("Last login: Mon"));
+        assert!(!prompt.is_match("password expires in 3 days\nOpt>"));
+        assert_eq!(PromptPatterns::default().ready(), "Opt>");
+    }
+
+    #[test]
+    fn parses_dialog_step() {
+        let step = DialogStep::parse(r"Password: => env:ENABLE_PASS").unwrap();
+        assert_eq!(step, DialogStep { expect: "Password:".into(), send: "env:ENABLE_PASS".into() });
+        // 发送内容中的 => 保留
+        assert_eq!(DialogStep::parse("Opt> => a=>b").unwrap().send, "a=>b");
+        assert!(DialogStep::parse("Password:").is_err());
+        assert!(DialogStep::parse("( => x").is_err());
+    }
+
+    #[test]
+    fn parses_anti_idle_and_maps_to_server_alive_interval() {
+        let anti_idle = AntiIdle::parse("300:resize").unwrap();
+        assert_eq!(anti_idle, AntiIdle { interval: 300, method: AntiIdleMethod::Resize });
+        assert_eq!(anti_idle.to_string(), "300:resize");
+        assert_eq!(AntiIdle::parse("60").unwrap().to_string(), "60");
+        assert!(AntiIdle::parse("0").is_err());
+        assert!(AntiIdle::parse("60:space").is_err());
+
+        let mut server = ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+            AuthType::Agent, None, None, None,
+        );
+        server.anti_idle = Some(anti_idle);
+        assert!(server.ssh_options().contains(&"ServerAliveInterval=300".to_string()));
+    }
+
+    #[test]
+    fn extra_identities_follow_the_auth_key() {
+        let mut server = ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+            AuthType::Key("/keys/main".into()), None, None, None,
+        );
+        server.identity_files = vec!["/keys/backup".into(), "/keys/old key".into()];
+        assert_eq!(server.identities(), ["/keys/main", "/keys/backup", "/keys/old key"]);
+
+        let options = server.ssh_options();
+        assert!(options.contains(&"IdentityFile=/keys/backup".to_string()));
+        assert!(options.contains(&"IdentityFile=\"/keys/old key\"".to_string()));
+        // -i 由调用方传入，不重复
+        assert!(!options.contains(&"IdentityFile=/keys/main".to_string()));
+        assert!(!options.contains(&"IdentitiesOnly=yes".to_string()));
+        server.identities_only = true;
+        assert!(server.ssh_options().contains(&"IdentitiesOnly=yes".to_string()));
+
+        server.auth_type = AuthType::Agent;
+        assert_eq!(server.identities(), ["/keys/backup", "/keys/old key"]);
+    }
+
+    #[test]
+    fn resolves_proxy_jump_chain_outermost_first() {
+        fn saved(name: &str) -> anyhow::Result<ServerConfig> {
+            let via = match name {
+                "bastion" => Some("edge"),
+                "loop-a" => Some("loop-b"),
+                "loop-b" => Some("loop-a"),
+                "edge" => None,
+                _ => return Err(anyhow::anyhow!("未找到服务器: {}", name)),
+            };
+            let mut server = ServerConfig::new(
+                name.into(), name.into(), format!("{}.example.com", name), 22, "ops".into(),
+                AuthType::Agent, None, None, None,
+            );
+            server.proxy_jump = via.map(str::to_string);
+            Ok(server)
+        }
+        ServerConfig::set_server_lookup(saved);
+
+        let mut server = ServerConfig::new(
+            "1".into(), "db".into(), "10.0.0.5".into(), 22, "root".into(),
+            AuthType::Agent, None, None, None,
+        );
+        server.proxy_jump = Some("bastion".into());
+        let resolved = server.with_resolved_secrets().unwrap();
+        let hops: Vec<&str> = resolved.jump_servers.iter().map(|hop| hop.name.as_str()).collect();
+        assert_eq!(hops, ["edge", "bastion"]);
+        assert_eq!(resolved.proxy_jump_spec().as_deref(), Some("ops@edge.example.com:22,ops@bastion.example.com:22"));
+        assert!(server.proxy_jump_spec().is_none());
+
+        server.proxy_jump = Some("loop-a".into());
+        assert!(server.with_resolved_secrets().unwrap_err().to_string().contains("循环"));
+        server.proxy_jump = Some("missing".into());
+        assert!(server.with_resolved_secrets().is_err());
+    }
+}
@@ -1,105 +1,200 @@
 use std::io::{self, Read, Write};
-use std::process::{Command, Stdio};
+use std::process::{Child, ChildStdin, Command, Stdio};
 use std::path::{Path, PathBuf};
 use std::env;
-use std::fs::File;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-/// rzsz命令的特征码
-const RZ_COMMAND: &[u8] = b"rz\r";
-const SZ_COMMAND_PREFIX: &[u8] = b"sz ";
-const ZMODEM_START: &[u8] = b"**\x18B00000000000000\r\n";
+use rssh::utils::zmodem::{self, ZmodemMonitor};
+
+/// ZMODEM 十六进制头部的前缀（ZPAD ZPAD ZDLE 'B' 以及帧类型高位）
+const ZMODEM_HEADER_PREFIX: &[u8] = b"**\x18B0";
+/// 取消 ZMODEM 会话：连续的 CAN 后跟退格
+const ZMODEM_CANCEL: &[u8] = b"\x18\x18\x18\x18\x18\x18\x18\x18\x08\x08\x08\x08\x08\x08\x08\x08";
+
+/// 远程发起的 ZMODEM 会话
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+    /// 远程执行了 sz（ZRQINIT），本地用 rz 接收
+    Download,
+    /// 远程执行了 rz（ZRINIT），本地用 sz 发送
+    Upload,
+}
+
+/// 在 ssh 输出中识别 ZMODEM 会话的开始。
+///
+/// 头部可能被拆在两次读取之间，末尾不完整的头部会先留着，等下一次读取拼上后再判断，
+/// 避免一半头部写到终端、一半交给 lrzsz。
+#[derive(Default)]
+struct ZmodemDetector {
+    held: Vec<u8>,
+}
+
+impl ZmodemDetector {
+    /// 返回应写到终端的数据；识别到会话开始时，同时返回会话类型和从头部开始的全部数据
+    fn feed(&mut self, chunk: &[u8]) -> (Vec<u8>, Option<(Transfer, Vec<u8>)>) {
+        let mut data = std::mem::take(&mut self.held);
+        data.extend_from_slice(chunk);
+
+        let header_len = ZMODEM_HEADER_PREFIX.len() + 1;
+        let mut search_from = 0;
+        while let Some(offset) = find(&data[search_from..], ZMODEM_HEADER_PREFIX) {
+            let pos = search_from + offset;
+            let Some(&frame_type) = data.get(pos + header_len - 1) else {
+                self.held = data.split_off(pos);
+                return (data, None);
+            };
+            let transfer = match frame_type {
+                b'0' => Some(Transfer::Download),
+                b'1' => Some(Transfer::Upload),
+                _ => None,
+            };
+            if let Some(transfer) = transfer {
+                let rest = data.split_off(pos);
+                return (data, Some((transfer, rest)));
+            }
+            search_from = pos + 1;
+        }
+
+        // 末尾是不完整的头部（至少已包含 ZDLE，普通文本不会出现）时留到下一次
+        for len in (3..ZMODEM_HEADER_PREFIX.len()).rev() {
+            if data.ends_with(&ZMODEM_HEADER_PREFIX[..len]) {
+                self.held = data.split_off(data.len() - len);
+                break;
+            }
+        }
+        (data, None)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// ssh 输出的去向：终端，或正在进行的 ZMODEM 传输
+enum Route {
+    Terminal,
+    /// lrzsz 尚未启动（例如正在选择文件）时，先把数据缓存在 pending 中；
+    /// 下载时 progress 旁路解析远程 sz 发来的数据，显示文件名和进度
+    Transfer { sink: Option<ChildStdin>, pending: Vec<u8>, progress: Option<Progress> },
+}
+
+/// 代理自身的提示一律写到 stderr，stdout 只承载远程数据，保证传输不被打断
+macro_rules! status {
+    ($($arg:tt)*) => {
+        eprint!("\r\n[rzsz] {}\r\n", format!($($arg)*))
+    };
+}
+
+/// 下载进度，写到 stderr
+struct Progress {
+    monitor: ZmodemMonitor,
+    /// 当前文件已显示过文件名和大小
+    announced: Option<String>,
+    last_draw: Instant,
+}
+
+impl Progress {
+    fn new() -> Self {
+        Progress { monitor: ZmodemMonitor::default(), announced: None, last_draw: Instant::now() }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        self.monitor.feed(data);
+        let Some(offer) = self.monitor.offer() else {
+            return;
+        };
+
+        if self.announced.as_deref() != Some(offer.name.as_str()) {
+            let size = offer.size.map(zmodem::format_size).unwrap_or_else(|| "大小未知".to_string());
+            status!("接收 {} ({})", offer.name, size);
+            self.announced = Some(offer.name.clone());
+        }
+
+        // 限制刷新频率，文件结束时画最后一次
+        let finished = self.monitor.finished();
+        if finished || self.last_draw.elapsed() >= Duration::from_millis(200) {
+            eprint!("\r[rzsz] {}\x1b[K", zmodem::progress_line(offer, self.monitor.position()));
+            if finished {
+                eprint!("\r\n");
+            }
+            self.last_draw = Instant::now();
+        }
+    }
+}
 
 fn main() -> io::Result<()> {
-    // 创建一个共享的原子布尔值，用于控制程序退出
-    let running = Arc::new(AtomicBool::new(true));
-    
-    // 克隆一份用于信号处理
-    let r = running.clone();
-    
-    // 设置Ctrl+C信号处理
-    ctrlc::set_handler(move || {
-        r.store(false, Ordering::SeqCst);
-    }).expect("无法设置Ctrl+C处理程序");
-    
-    println!("RSSH-RZSZ代理启动...");
-    
     // 检查是否从环境变量提供SSH连接信息
     let host = env::var("RSSH_HOST").ok();
     let port = env::var("RSSH_PORT").ok();
     let user = env::var("RSSH_USER").ok();
     let key = env::var("RSSH_KEY").ok();
-    
+
     let args: Vec<String>;
-    
+
     if host.is_some() && user.is_some() {
-        // 从环境变量构建SSH命令
-        println!("从环境变量获取SSH连接信息");
-        println!("主机: {} 用户: {} 端口: {}", 
-            host.as_ref().unwrap(), 
-            user.as_ref().unwrap(), 
-            port.as_ref().unwrap_or(&"22".to_string()));
-        
         let mut ssh_args = Vec::new();
-        
+
         // 获取ssh命令的完整路径
         let ssh_cmd = "ssh"; // 使用系统默认的SSH
-        
+
         ssh_args.push(ssh_cmd.to_string());
-        
+
+        // 标准输入是管道，需要强制分配远程终端
+        ssh_args.push("-tt".to_string());
+
         // 添加端口
         if let Some(port_str) = port {
             ssh_args.push("-p".to_string());
             ssh_args.push(port_str);
         }
-        
+
         // 添加密钥
         if let Some(key_path) = key {
             ssh_args.push("-i".to_string());
             ssh_args.push(key_path);
         }
-        
-        // 添加常用SSH选项
-        ssh_args.push("-o".to_string());
-        ssh_args.push("StrictHostKeyChecking=no".to_string());
-        
+
+        // 服务器级别的选项（主机密钥策略、压缩、算法偏好等）由 rssh 传入
+        match env::var("RSSH_SSH_OPTIONS") {
+            Ok(options) => ssh_args.extend(options.split_whitespace().map(String::from)),
+            Err(_) => {
+                ssh_args.push("-o".to_string());
+                ssh_args.push("StrictHostKeyChecking=accept-new".to_string());
+            }
+        }
+
         ssh_args.push("-o".to_string());
         ssh_args.push("HashKnownHosts=no".to_string());
-        
+
         ssh_args.push("-o".to_string());
         ssh_args.push("ServerAliveInterval=60".to_string());
-        
+
         // 添加用户和主机
         ssh_args.push(format!("{}@{}", user.unwrap(), host.unwrap()));
-        
+
         args = ssh_args;
     } else {
         // 使用命令行参数
         args = env::args().skip(1).collect();
-        
+
         if args.is_empty() {
             eprintln!("错误: 没有提供SSH命令，也没有设置环境变量");
             return Ok(());
         }
     }
-    
-    println!("启动SSH命令: {}", args.join(" "));
-    
-    // 创建子进程
-    println!("准备启动SSH命令：{}", &args[0]);
-    println!("参数: {}", args[1..].join(" "));
-    
+
     let cmd_result = Command::new(&args[0])
         .args(&args[1..])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .spawn();
-    
-    let mut cmd = match cmd_result {
+
+    let mut ssh = match cmd_result {
         Ok(cmd) => cmd,
         Err(e) => {
             eprintln!("启动SSH命令失败：{}", e);
@@ -113,162 +208,255 @@ fn main() -> io::Result<()> {
             return Err(e);
         }
     };
-    
+
     // 获取子进程的标准输入和输出
-    let mut child_stdin = cmd.stdin.take().expect("无法获取子进程stdin");
-    let mut child_stdout = cmd.stdout.take().expect("无法获取子进程stdout");
-    
-    // 创建缓冲区
-    let mut stdin_buf = [0u8; 1024];
-    let mut stdout_buf = [0u8; 4096];
-    
-    // 将标准输入设置为非阻塞模式 (仅Linux/MacOS)
+    let ssh_stdin = Arc::new(Mutex::new(ssh.stdin.take().expect("无法获取子进程stdin")));
+    let mut ssh_stdout = ssh.stdout.take().expect("无法获取子进程stdout");
+
+    // ssh 的标准输入不是终端，不会自己切换原始模式，由代理负责
     #[cfg(unix)]
-    set_stdin_nonblocking();
-    
-    // 创建输出线程
-    let r_clone = running.clone();
-    let stdout_thread = thread::spawn(move || {
-        let mut zmodem_mode = false;
-        
-        while r_clone.load(Ordering::SeqCst) {
-            // 从SSH读取数据
-            match child_stdout.read(&mut stdout_buf) {
-                Ok(0) => break, // EOF
-                Ok(n) => {
-                    // 检查是否是ZMODEM数据开始
-                    if !zmodem_mode && contains_zmodem_data(&stdout_buf[0..n]) {
-                        println!("检测到ZMODEM数据传输开始");
-                        zmodem_mode = true;
-                        
-                        // 启动rz下载助手 (在单独的线程中)
-                        thread::spawn(|| {
-                            if let Err(e) = start_zmodem_download() {
-                                eprintln!("启动下载助手失败: {}", e);
-                            }
-                        });
-                        
-                        // 等待rz程序启动
-                        thread::sleep(Duration::from_millis(500));
+    let terminal = RawTerminal::enter();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let route = Arc::new(Mutex::new(Route::Terminal));
+    let (start_tx, start_rx) = mpsc::channel::<Transfer>();
+
+    // 输出线程：ssh 输出写到终端，ZMODEM 会话期间交给本地 lrzsz
+    let output_thread = {
+        let running = running.clone();
+        let route = route.clone();
+        thread::spawn(move || {
+            let mut detector = ZmodemDetector::default();
+            let mut buf = [0u8; 8192];
+            let mut stdout = io::stdout();
+
+            while running.load(Ordering::SeqCst) {
+                let n = match ssh_stdout.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+
+                let mut route = route.lock().unwrap();
+                match &mut *route {
+                    Route::Terminal => {
+                        let (to_terminal, started) = detector.feed(&buf[..n]);
+                        let _ = stdout.write_all(&to_terminal);
+                        let _ = stdout.flush();
+                        if let Some((transfer, rest)) = started {
+                            let progress = (transfer == Transfer::Download).then(Progress::new);
+                            *route = Route::Transfer { sink: None, pending: rest, progress };
+                            let _ = start_tx.send(transfer);
+                        }
                     }
-                    
-                    // 将数据写入标准输出
-                    io::stdout().write_all(&stdout_buf[0..n]).ok();
-                    io::stdout().flush().ok();
-                },
-                Err(e) => {
-                    // 只报告非阻塞错误
-                    if e.kind() != io::ErrorKind::WouldBlock {
-                        eprintln!("从SSH读取错误: {}", e);
-                        break;
+                    Route::Transfer { sink: Some(sink), progress, .. } => {
+                        let _ = sink.write_all(&buf[..n]);
+                        let _ = sink.flush();
+                        if let Some(progress) = progress {
+                            progress.feed(&buf[..n]);
+                        }
                     }
+                    Route::Transfer { sink: None, pending, .. } => pending.extend_from_slice(&buf[..n]),
                 }
             }
-            
-            // 短暂休眠
-            thread::sleep(Duration::from_millis(5));
-        }
-    });
-    
-    // 主线程处理标准输入
+            running.store(false, Ordering::SeqCst);
+        })
+    };
+
+    // 主线程：终端输入发送到 ssh，并管理本地 lrzsz 进程
+    #[cfg(unix)]
+    set_stdin_nonblocking();
+    let mut stdin_buf = [0u8; 1024];
+    let mut transfer: Option<(Child, thread::JoinHandle<()>)> = None;
+
     while running.load(Ordering::SeqCst) {
-        // 从标准输入读取
-        let read_result = io::stdin().read(&mut stdin_buf);
-        
-        match read_result {
-            Ok(0) => break, // EOF
-            Ok(n) => {
-                // 检测rz/sz命令
-                if is_rz_command(&stdin_buf[0..n]) {
-                    println!("检测到rz命令");
-                    
-                    if rssh::utils::terminal::is_kitty() {
-                        println!("\n提示: 您正在使用Kitty终端，建议使用Kitty的文件传输协议代替rz/sz。");
-                        println!("命令示例:");
-                        println!("  上传: kitty +kitten transfer 本地文件路径");
-                        println!("  下载: kitty +kitten transfer --direction=receive 远程文件路径");
-                        println!("或者直接使用rssh命令:");
-                        println!("  上传: rssh upload 服务器名 本地文件路径 [远程路径] --mode kitty");
-                        println!("  下载: rssh download 服务器名 远程文件路径 [本地路径] --mode kitty\n");
-                    }
+        if let Ok(Some(_)) = ssh.try_wait() {
+            break;
+        }
 
-                    // 处理rz命令 (上传文件到远程)
-                    if let Ok(file_path) = select_file_dialog() {
-                        if !file_path.is_empty() {
-                            println!("上传文件: {}", file_path);
-                            
-                            // 将rz命令发送给SSH
-                            child_stdin.write_all(&stdin_buf[0..n]).ok();
-                            child_stdin.flush().ok();
-                            
-                            // 启动sz上传助手 (在单独的线程中)
-                            thread::spawn(move || {
-                                thread::sleep(Duration::from_millis(300));
-                                if let Err(e) = start_zmodem_upload(&file_path) {
-                                    eprintln!("启动上传助手失败: {}", e);
-                                }
-                            });
-                            
-                            continue;
-                        }
-                    }
+        if let Ok(kind) = start_rx.try_recv() {
+            match start_transfer(kind, &route, &ssh_stdin) {
+                Ok(started) => transfer = Some(started),
+                Err(e) => {
+                    status!("{}", e);
+                    cancel_remote(&ssh_stdin);
+                    *route.lock().unwrap() = Route::Terminal;
                 }
-                else if is_sz_command(&stdin_buf[0..n]) {
-                    println!("检测到sz命令");
-                    
-                    if rssh::utils::terminal::is_kitty() {
-                        println!("\n提示: 您正在使用Kitty终端，建议使用Kitty的文件传输协议代替rz/sz。");
-                        println!("命令示例:");
-                        println!("  上传: kitty +kitten transfer 本地文件路径");
-                        println!("  下载: kitty +kitten transfer --direction=receive 远程文件路径");
-                        println!("或者直接使用rssh命令:");
-                        println!("  上传: rssh upload 服务器名 本地文件路径 [远程路径] --mode kitty");
-                        println!("  下载: rssh download 服务器名 远程文件路径 [本地路径] --mode kitty\n");
-                    }
+            }
+        }
 
-                    // 将sz命令发送给SSH (下载文件会在stdout线程处理)
+        if let Some((child, _)) = transfer.as_mut() {
+            if let Ok(Some(exit)) = child.try_wait() {
+                *route.lock().unwrap() = Route::Terminal;
+                if let Some((_, pump)) = transfer.take() {
+                    let _ = pump.join();
                 }
-                
-                // 将数据发送给SSH
-                if let Err(e) = child_stdin.write_all(&stdin_buf[0..n]) {
-                    eprintln!("发送数据到SSH失败: {}", e);
-                    break;
+                if exit.success() {
+                    status!("传输完成");
+                } else {
+                    status!("传输失败 ({})", exit);
                 }
-                
-                if let Err(e) = child_stdin.flush() {
-                    eprintln!("刷新SSH输入失败: {}", e);
-                    break;
+            }
+        }
+
+        match io::stdin().read(&mut stdin_buf) {
+            Ok(0) => break, // EOF
+            Ok(n) => {
+                let input = &stdin_buf[..n];
+                if let Some((child, _)) = transfer.as_mut() {
+                    // 传输期间终端输入不发给远程；Ctrl+C / Ctrl+X 取消传输
+                    if input.contains(&0x03) || input.contains(&0x18) {
+                        let _ = child.kill();
+                        cancel_remote(&ssh_stdin);
+                        status!("已取消传输");
+                    }
+                } else {
+                    let mut stdin = ssh_stdin.lock().unwrap();
+                    if stdin.write_all(input).and_then(|_| stdin.flush()).is_err() {
+                        break;
+                    }
                 }
-            },
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(5)),
             Err(e) => {
-                // 只报告非阻塞错误
-                if e.kind() != io::ErrorKind::WouldBlock {
-                    eprintln!("读取标准输入错误: {}", e);
-                    break;
-                }
+                status!("读取标准输入错误: {}", e);
+                break;
             }
         }
-        
-        // 短暂休眠
-        thread::sleep(Duration::from_millis(5));
     }
-    
-    // 等待子进程结束
-    let _ = cmd.wait();
-    
-    // 等待输出线程结束
-    let _ = stdout_thread.join();
-    
-    println!("代理程序退出");
+
+    running.store(false, Ordering::SeqCst);
+    if let Some((mut child, _)) = transfer {
+        let _ = child.kill();
+    }
+    let _ = ssh.kill();
+    let _ = ssh.wait();
+    let _ = output_thread.join();
+
+    #[cfg(unix)]
+    drop(terminal);
     Ok(())
 }
 
+/// 启动本地 lrzsz，并把 ZMODEM 数据在它和 ssh 之间转发
+fn start_transfer(
+    kind: Transfer,
+    route: &Arc<Mutex<Route>>,
+    ssh_stdin: &Arc<Mutex<ChildStdin>>,
+) -> io::Result<(Child, thread::JoinHandle<()>)> {
+    // -e 转义所有控制字符：远程或本地运行在 tmux/screen 中时，部分控制字符会被吞掉
+    let mut command = match kind {
+        Transfer::Download => {
+            let dir = select_download_dir()?;
+            status!("保存到 {}", dir.display());
+            let mut command = Command::new("rz");
+            // -E 已存在同名文件时自动改名，不覆盖
+            command.args(["-e", "-E", "-b"]).current_dir(dir);
+            command
+        }
+        Transfer::Upload => {
+            if rssh::utils::terminal::is_kitty() {
+                status!("提示: Kitty 终端下可以用 rssh upload <服务器> <文件> --mode kitty 代替 rz");
+            }
+            let file = select_file()?;
+            status!("上传 {}", file);
+            let mut command = Command::new("sz");
+            command.args(["-e", "-b"]).arg(file);
+            command
+        }
+    };
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut child_stdin = child.stdin.take().expect("无法获取lrzsz stdin");
+    let mut child_stdout = child.stdout.take().expect("无法获取lrzsz stdout");
+
+    {
+        let mut route = route.lock().unwrap();
+        let mut progress = None;
+        if let Route::Transfer { pending, progress: pending_progress, .. } = &mut *route {
+            child_stdin.write_all(pending)?;
+            child_stdin.flush()?;
+            progress = pending_progress.take();
+            if let Some(progress) = progress.as_mut() {
+                progress.feed(pending);
+            }
+            pending.clear();
+        }
+        *route = Route::Transfer { sink: Some(child_stdin), pending: Vec::new(), progress };
+    }
+
+    let ssh_stdin = ssh_stdin.clone();
+    let pump = thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        while let Ok(n) = child_stdout.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let mut stdin = ssh_stdin.lock().unwrap();
+            if stdin.write_all(&buf[..n]).and_then(|_| stdin.flush()).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok((child, pump))
+}
+
+/// 让远程的 rz/sz 退出
+fn cancel_remote(ssh_stdin: &Arc<Mutex<ChildStdin>>) {
+    let mut stdin = ssh_stdin.lock().unwrap();
+    let _ = stdin.write_all(ZMODEM_CANCEL);
+    let _ = stdin.flush();
+}
+
+/// 终端原始模式，离开作用域时恢复
+#[cfg(unix)]
+struct RawTerminal {
+    original: Option<termios::Termios>,
+}
+
+#[cfg(unix)]
+impl RawTerminal {
+    fn enter() -> Self {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = io::stdin().as_raw_fd();
+        let original = termios::Termios::from_fd(fd).ok();
+        if let Some(original) = &original {
+            let mut raw = *original;
+            termios::cfmakeraw(&mut raw);
+            let _ = termios::tcsetattr(fd, termios::TCSANOW, &raw);
+        }
+        RawTerminal { original }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        use std::os::unix::io::AsRawFd;
+        use libc::{fcntl, F_GETFL, F_SETFL, O_NONBLOCK};
+
+        let fd = io::stdin().as_raw_fd();
+        if let Some(original) = &self.original {
+            let _ = termios::tcsetattr(fd, termios::TCSANOW, original);
+        }
+        unsafe {
+            let flags = fcntl(fd, F_GETFL, 0);
+            fcntl(fd, F_SETFL, flags & !O_NONBLOCK);
+        }
+    }
+}
+
 /// 设置标准输入为非阻塞模式 (仅Unix系统)
 #[cfg(unix)]
 fn set_stdin_nonblocking() {
     use std::os::unix::io::AsRawFd;
     use libc::{fcntl, F_GETFL, F_SETFL, O_NONBLOCK};
-    
+
     let stdin_fd = io::stdin().as_raw_fd();
     unsafe {
         let flags = fcntl(stdin_fd, F_GETFL, 0);
@@ -276,181 +464,117 @@ fn set_stdin_nonblocking() {
     }
 }
 
-/// 检测输入是否是rz命令
-fn is_rz_command(data: &[u8]) -> bool {
-    data.len() >= 3 && &data[0..3] == RZ_COMMAND
-}
+/// 选择要上传的文件：优先使用图形界面，否则在终端中输入路径
+fn select_file() -> io::Result<String> {
+    if let Ok(path) = try_gui_file_selection() {
+        if !path.is_empty() {
+            return Ok(path);
+        }
+    }
 
-/// 检测输入是否是sz命令
-fn is_sz_command(data: &[u8]) -> bool {
-    data.len() >= 3 && &data[0..3] == SZ_COMMAND_PREFIX
-}
+    // 临时恢复普通终端模式读取一行
+    #[cfg(unix)]
+    let path = {
+        let _cooked = CookedTerminal::enter();
+        eprint!("\r\n请输入要上传的文件完整路径: ");
+        let mut path = String::new();
+        io::stdin().read_line(&mut path)?;
+        path
+    };
+    #[cfg(not(unix))]
+    let path = {
+        let mut path = String::new();
+        io::stdin().read_line(&mut path)?;
+        path
+    };
 
-/// 检测输出是否包含ZMODEM数据
-fn contains_zmodem_data(data: &[u8]) -> bool {
-    // 更精确地检测ZMODEM的启动序列
-    if data.len() < 4 {
-        return false;
+    let path = path.trim().to_string();
+    if path.is_empty() || !Path::new(&path).is_file() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "文件不存在或路径为空，已取消上传"));
     }
-    
-    // 查找ZMODEM头部标识: "**\x18B"
-    for i in 0..data.len()-4 {
-        if data[i] == b'*' && data[i+1] == b'*' && data[i+2] == 0x18 && data[i+3] == b'B' {
-            return true;
+    Ok(path)
+}
+
+/// 下载的默认目录：RSSH_DOWNLOAD_DIR，其次 ~/Downloads，都没有时为当前目录
+fn default_download_dir() -> io::Result<PathBuf> {
+    if let Ok(dir) = env::var("RSSH_DOWNLOAD_DIR") {
+        if !dir.is_empty() {
+            return Ok(PathBuf::from(dir));
         }
     }
-    
-    false
+    match dirs::download_dir().or_else(|| dirs::home_dir().map(|home| home.join("Downloads"))) {
+        Some(dir) if dir.is_dir() => Ok(dir),
+        _ => env::current_dir(),
+    }
 }
 
-/// 启动文件上传助手 (使用sz命令)
-fn start_zmodem_upload(file_path: &str) -> io::Result<()> {
-    println!("准备上传文件: {}", file_path);
-    
-    // 创建一个临时脚本来执行sz命令
-    let script = format!(r#"
-        #!/bin/bash
-        sleep 0.5
-        echo "开始上传文件: {}"
-        # 使用更多选项提高兼容性
-        # -e: 转义控制字符
-        # -y: 覆盖已存在的文件
-        # -v: 详细模式
-        # -b: 二进制模式
-        # -O: 使用16比特CRC
-        # 增加超时时间到30秒
-        sz -veybO --timeout=30 "{}" > /tmp/sz_debug.log 2>&1
-        exit_code=$?
-        if [ $exit_code -eq 0 ]; then
-            echo "文件上传成功！"
-        else
-            echo "文件上传失败，错误码: $exit_code (详情请查看 /tmp/sz_debug.log)"
-            tail -n 20 /tmp/sz_debug.log
-            
-            echo ""
-            echo "可能的问题:"
-            echo "1. 终端不支持完整的ZMODEM协议"
-            echo "2. 传输超时 - 文件太大或网络延迟高"
-            echo "3. 本地lrzsz版本与远程不兼容"
-            echo ""
-            echo "尝试使用直接的命令进行文件传输:"
-            echo "rssh upload remote_host {} /path/to/remote/dir/"
-        fi
-    "#, file_path, file_path, file_path);
-    
-    let temp_script = create_temp_script("rssh_rzsz_upload.sh", &script)?;
-    
-    // 启动脚本
-    let mut cmd = Command::new(&temp_script)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()?;
-    
-    // 等待完成
-    let _ = cmd.wait();
-    
-    // 删除临时脚本
-    let _ = std::fs::remove_file(temp_script);
-    
-    Ok(())
+/// 询问下载保存的目录，直接回车使用默认目录；目录不存在时创建
+fn select_download_dir() -> io::Result<PathBuf> {
+    let default = default_download_dir()?;
+
+    #[cfg(unix)]
+    let input = {
+        let _cooked = CookedTerminal::enter();
+        eprint!("\r\n[rzsz] 远程发起下载，保存到目录 [{}]: ", default.display());
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        input
+    };
+    #[cfg(not(unix))]
+    let input = String::new();
+
+    let input = input.trim();
+    let dir = if input.is_empty() {
+        default
+    } else if let Some(rest) = input.strip_prefix("~/") {
+        dirs::home_dir().map(|home| home.join(rest)).unwrap_or_else(|| PathBuf::from(input))
+    } else {
+        PathBuf::from(input)
+    };
+
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
 }
 
-/// 启动文件下载助手 (使用rz命令)
-fn start_zmodem_download() -> io::Result<()> {
-    println!("检测到ZMODEM下载请求，准备接收文件");
-    
-    // 创建一个临时脚本来执行rz命令，使用更多选项以提高兼容性
-    let script = r#"
-        #!/bin/bash
-        sleep 0.5
-        echo "开始下载文件..."
-        cd "$HOME"  # 切换到用户主目录
-        
-        # 使用更多的rz选项来提高兼容性
-        # -e: 转义控制字符
-        # -y: 覆盖已存在的文件
-        # -v: 详细模式
-        # -b: 二进制模式
-        # -O: 使用16比特CRC
-        # 增加超时时间到30秒
-        rz -veyb -O --timeout=30 > /tmp/rz_debug.log 2>&1
-        exit_code=$?
-        if [ $exit_code -eq 0 ]; then
-            echo "文件下载成功！保存在 $(pwd)"
-            ls -l | grep -E "^-" | tail -n 1  # 显示最新下载的文件
-        else
-            echo "文件下载失败，错误码: $exit_code (详情请查看 /tmp/rz_debug.log)"
-            tail -n 20 /tmp/rz_debug.log
-            
-            echo ""
-            echo "可能的问题:"
-            echo "1. 终端不支持完整的ZMODEM协议"
-            echo "2. 传输超时 - 文件太大或网络延迟高"
-            echo "3. 本地lrzsz版本与远程不兼容"
-            echo ""
-            echo "尝试使用直接的命令进行文件传输:"
-            echo "rssh download remote_host /path/to/remote/file local_file"
-        fi
-    "#;
-    
-    let temp_script = create_temp_script("rssh_rzsz_download.sh", script)?;
-    
-    // 启动脚本
-    let mut cmd = Command::new(&temp_script)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()?;
-    
-    // 等待完成
-    let _ = cmd.wait();
-    
-    // 删除临时脚本
-    let _ = std::fs::remove_file(temp_script);
-    
-    Ok(())
+/// 临时切回普通（阻塞、回显）终端模式，离开作用域时回到原始模式
+#[cfg(unix)]
+struct CookedTerminal {
+    raw: Option<termios::Termios>,
 }
 
-/// 创建临时脚本文件
-fn create_temp_script(name: &str, content: &str) -> io::Result<PathBuf> {
-    let mut temp_path = std::env::temp_dir();
-    temp_path.push(name);
-    
-    let mut file = File::create(&temp_path)?;
-    file.write_all(content.as_bytes())?;
-    
-    // 在Unix系统上设置执行权限
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(&temp_path)?.permissions();
-        perms.set_mode(0o755);
-        std::fs::set_permissions(&temp_path, perms)?;
+#[cfg(unix)]
+impl CookedTerminal {
+    fn enter() -> Self {
+        use std::os::unix::io::AsRawFd;
+        use libc::{fcntl, F_GETFL, F_SETFL, O_NONBLOCK};
+
+        let fd = io::stdin().as_raw_fd();
+        let raw = termios::Termios::from_fd(fd).ok();
+        if let Some(raw) = &raw {
+            let mut cooked = *raw;
+            cooked.c_lflag |= termios::ICANON | termios::ECHO;
+            cooked.c_iflag |= termios::ICRNL;
+            let _ = termios::tcsetattr(fd, termios::TCSANOW, &cooked);
+        }
+        unsafe {
+            let flags = fcntl(fd, F_GETFL, 0);
+            fcntl(fd, F_SETFL, flags & !O_NONBLOCK);
+        }
+        CookedTerminal { raw }
     }
-    
-    Ok(temp_path)
 }
 
-/// 弹出文件选择对话框
-fn select_file_dialog() -> io::Result<String> {
-    // 首先尝试使用GUI选择器
-    if let Ok(path) = try_gui_file_selection() {
-        return Ok(path);
-    }
-    
-    // 回退到命令行
-    println!("请输入要上传的文件完整路径:");
-    let mut path = String::new();
-    io::stdin().read_line(&mut path)?;
-    
-    let path = path.trim().to_string();
-    if path.is_empty() || !Path::new(&path).exists() {
-        println!("文件不存在或路径为空");
-        return Ok(String::new());
+#[cfg(unix)]
+impl Drop for CookedTerminal {
+    fn drop(&mut self) {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = io::stdin().as_raw_fd();
+        if let Some(raw) = &self.raw {
+            let _ = termios::tcsetattr(fd, termios::TCSANOW, raw);
+        }
+        set_stdin_nonblocking();
     }
-    
-    Ok(path)
 }
 
 /// 尝试使用GUI文件选择器
@@ -460,20 +584,21 @@ fn try_gui_file_selection() -> io::Result<String> {
         let output = Command::new("osascript")
             .args(["-e", "POSIX path of (choose file)"])
             .output()?;
-        
+
         if output.status.success() {
             let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
             return Ok(path);
         }
     }
-    
+
     #[cfg(target_os = "linux")]
     {
         // 尝试使用 zenity
         let output = Command::new("zenity")
             .args(["--file-selection"])
+            .stderr(Stdio::null())
             .output();
-        
+
         if let Ok(output) = output {
             if output.status.success() {
                 let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -481,7 +606,37 @@ fn try_gui_file_selection() -> io::Result<String> {
             }
         }
     }
-    
+
     // 如果无法使用GUI，返回错误
     Err(io::Error::new(io::ErrorKind::NotFound, "无法使用GUI文件选择器"))
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_zmodem_header_across_reads() {
+        let mut detector = ZmodemDetector::default();
+
+        // 普通输出原样写到终端，包括单独的 *
+        let (out, started) = detector.feed(b"ls **/*.rs\r\n");
+        assert_eq!(out, b"ls **/*.rs\r\n");
+        assert!(started.is_none());
+
+        // 头部被拆成两段：前半段先不输出
+        let (out, started) = detector.feed(b"rz\r**\x18B");
+        assert_eq!(out, b"rz\r");
+        assert!(started.is_none());
+
+        let (out, started) = detector.feed(b"0100000023be50\r\n");
+        assert!(out.is_empty());
+        let (transfer, rest) = started.unwrap();
+        assert_eq!(transfer, Transfer::Upload);
+        assert!(rest.starts_with(b"**\x18B01"));
+
+        let mut detector = ZmodemDetector::default();
+        let (_, started) = detector.feed(b"**\x18B00000000000000\r\n");
+        assert_eq!(started.unwrap().0, Transfer::Download);
+    }
+}
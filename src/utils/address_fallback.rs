@@ -0,0 +1,214 @@
+//! 按 happy eyeballs 的方式建立 TCP 连接：按顺序发起尝试，前一个尝试 250ms 内没有结果就同时开始下一个，
+//! 最先连上的胜出。
+//!
+//! - 一个主机名解析出多个地址（A / AAAA 记录）时，IPv6 与 IPv4 地址交替尝试
+//! - 服务器有多个地址（公网 IP、内网 IP、VPN 域名）时，各地址之间同样错开尝试，连上的地址记录下来供下次优先尝试
+//!
+//! 每个地址的连接超时默认 5 秒，可在 `config.toml` 中用 `connect_timeout` 修改。
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+
+use crate::models::ServerConfig;
+use crate::utils::dns_cache;
+
+/// 前一个尝试没有结果时，等待多久开始下一个
+const STAGGER: Duration = Duration::from_millis(250);
+
+/// 单个地址默认的连接超时
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 保存连上的地址（服务器 ID, 地址），由 rssh 启动时设置；未设置时不记录
+static RECORDER: OnceLock<fn(&str, &str) -> Result<()>> = OnceLock::new();
+
+static CONNECT_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// 设置保存连上地址的方式，只在启动时调用一次
+pub fn configure(recorder: fn(&str, &str) -> Result<()>) {
+    let _ = RECORDER.set(recorder);
+}
+
+/// 设置单个地址的连接超时，只在启动时调用一次
+pub fn set_connect_timeout(timeout: Duration) {
+    let _ = CONNECT_TIMEOUT.set(timeout);
+}
+
+fn connect_timeout() -> Duration {
+    CONNECT_TIMEOUT.get().copied().unwrap_or(DEFAULT_CONNECT_TIMEOUT)
+}
+
+/// 按顺序错开发起尝试，返回最先成功的候选项和连接；全部失败时返回每个候选项的错误
+fn stagger<T, F>(candidates: Vec<T>, attempt: F) -> Result<(T, TcpStream), Vec<(T, anyhow::Error)>>
+where
+    T: Clone + Send + 'static,
+    F: Fn(&T) -> Result<TcpStream> + Clone + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let total = candidates.len();
+    let mut pending = candidates.into_iter();
+    let mut started = 0;
+    let mut errors = Vec::new();
+
+    while errors.len() < total {
+        if let Some(candidate) = pending.next() {
+            let tx = tx.clone();
+            let attempt = attempt.clone();
+            // 落败的连接在发送失败时随线程一起丢弃
+            std::thread::spawn(move || {
+                let result = attempt(&candidate);
+                let _ = tx.send((candidate, result));
+            });
+            started += 1;
+        }
+
+        let received = if started < total {
+            rx.recv_timeout(STAGGER).ok()
+        } else {
+            rx.recv().ok()
+        };
+        match received {
+            Some((candidate, Ok(stream))) => return Ok((candidate, stream)),
+            Some((candidate, Err(e))) => errors.push((candidate, e)),
+            None => {},
+        }
+    }
+    Err(errors)
+}
+
+/// 把解析出的地址排成 IPv6、IPv4 交替的顺序（RFC 8305），从第一个地址的协议族开始
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_v6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|addr| addr.is_ipv6() == first_is_v6);
+    preferred.reverse();
+    other.reverse();
+
+    let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+    while let Some(addr) = preferred.pop() {
+        ordered.push(addr);
+        ordered.extend(other.pop());
+    }
+    ordered.extend(other.into_iter().rev());
+    ordered
+}
+
+/// 连接一个主机名：解析出的各地址错开尝试，最先连上的胜出
+pub fn connect_host(host: &str, port: u16) -> Result<TcpStream> {
+    let addrs = interleave(dns_cache::lookup(host, port)?);
+    if addrs.is_empty() {
+        return Err(anyhow::anyhow!("{} 没有可用的地址", host));
+    }
+    let timeout = connect_timeout();
+    match stagger(addrs, move |addr| {
+        TcpStream::connect_timeout(addr, timeout).with_context(|| format!("连接 {} 失败", addr))
+    }) {
+        Ok((_, stream)) => Ok(stream),
+        Err(mut errors) if errors.len() == 1 => Err(errors.remove(0).1),
+        Err(errors) => {
+            let details: Vec<String> = errors.iter().map(|(_, e)| format!("{:#}", e)).collect();
+            Err(anyhow::anyhow!("{} 的所有地址都无法连接: {}", host, details.join("; ")))
+        },
+    }
+}
+
+/// 按顺序错开连接各个地址，返回最先连上的地址和连接；全部失败时列出每个地址的错误
+pub fn race(addresses: &[&str], port: u16) -> Result<(String, TcpStream)> {
+    let hosts: Vec<String> = addresses.iter().map(|host| host.to_string()).collect();
+    stagger(hosts, move |host| connect_host(host, port)).map_err(|errors| {
+        let errors: Vec<String> = errors.iter().map(|(host, e)| format!("{}: {:#}", host, e)).collect();
+        anyhow::anyhow!("所有地址都无法连接 (端口 {}):\n  {}", port, errors.join("\n  "))
+    })
+}
+
+/// 连接服务器的 SSH 端口，返回连接和实际使用的地址。没有备用地址时直接连接主机地址，
+/// 否则依次尝试所有地址，并记住连上的地址
+pub fn connect(server: &ServerConfig) -> Result<(TcpStream, String)> {
+    if server.alt_hosts.is_empty() {
+        let stream = connect_host(&server.host, server.port)
+            .with_context(|| format!("无法连接到服务器 {}:{}", server.host, server.port))?;
+        return Ok((stream, server.host.clone()));
+    }
+
+    let (host, stream) = race(&server.addresses(), server.port)
+        .with_context(|| format!("无法连接到服务器 {}", server.name))?;
+    remember(server, &host);
+    Ok((stream, host))
+}
+
+/// 系统 ssh、russh 等自己建立连接的方式：先找到可以连上的地址，返回使用该地址、不再带备用地址的配置。
+/// 经跳板机连接时本地无法直接探测，保持不变
+pub fn with_reachable_host(server: &ServerConfig) -> Result<ServerConfig> {
+    let mut server = server.clone();
+    if !server.alt_hosts.is_empty() && !server.uses_jump() {
+        let (_, host) = connect(&server)?;
+        server.host = host;
+        server.alt_hosts.clear();
+    }
+    Ok(server)
+}
+
+/// 记录连上的地址；地址与上次不同且不是主机地址时提示
+fn remember(server: &ServerConfig, host: &str) {
+    if server.preferred_host.as_deref() == Some(host) {
+        return;
+    }
+    if host != server.host {
+        println!("{} 通过备用地址 {} 连接", "→".bright_blue(), host.bright_green());
+    }
+    if let Some(Err(e)) = RECORDER.get().map(|record| record(&server.id, host)) {
+        eprintln!("{} 无法记录可用地址: {:#}", "⚠".bright_yellow(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn falls_back_to_reachable_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // 第一个地址无法解析，第二个地址可以连上
+        let (host, _stream) = race(&["unreachable.invalid", "127.0.0.1"], port).unwrap();
+        assert_eq!(host, "127.0.0.1");
+
+        let error = race(&["unreachable.invalid"], port).unwrap_err().to_string();
+        assert!(error.contains("unreachable.invalid"));
+
+        let mut server = ServerConfig::new(
+            "1".into(), "web".into(), "203.0.113.5".into(), port, "deploy".into(),
+            crate::models::AuthType::Agent, None, None, None,
+        );
+        server.alt_hosts = vec!["10.0.0.5".into(), "web.vpn".into()];
+        server.preferred_host = Some("web.vpn".into());
+        assert_eq!(server.addresses(), ["web.vpn", "203.0.113.5", "10.0.0.5"]);
+        server.alt_hosts.pop();
+        assert_eq!(server.addresses(), ["203.0.113.5", "10.0.0.5"]);
+    }
+
+    #[test]
+    fn alternates_address_families() {
+        let addrs: Vec<SocketAddr> = ["[2001:db8::1]:22", "[2001:db8::2]:22", "[2001:db8::3]:22", "192.0.2.1:22"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let ordered: Vec<String> = interleave(addrs).iter().map(ToString::to_string).collect();
+        assert_eq!(ordered, ["[2001:db8::1]:22", "192.0.2.1:22", "[2001:db8::2]:22", "[2001:db8::3]:22"]);
+
+        let v4_first: Vec<SocketAddr> = vec!["192.0.2.1:22".parse().unwrap(), "192.0.2.2:22".parse().unwrap(), "[2001:db8::1]:22".parse().unwrap()];
+        let ordered: Vec<String> = interleave(v4_first).iter().map(ToString::to_string).collect();
+        assert_eq!(ordered, ["192.0.2.1:22", "[2001:db8::1]:22", "192.0.2.2:22"]);
+    }
+
+    #[test]
+    fn connects_to_first_reachable_resolved_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = connect_host("127.0.0.1", port).unwrap();
+        assert_eq!(stream.peer_addr().unwrap().port(), port);
+    }
+}
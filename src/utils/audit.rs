@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::models::{audit_mode, ServerConfig};
+use crate::utils::siem;
+
+/// 审计日志的一条记录，以 JSON Lines 追加写入 `audit.log`
+#[derive(Debug, serde::Serialize)]
+struct AuditEntry<'a> {
+    time: String,
+    local_user: String,
+    server: &'a str,
+    target: String,
+    /// connect / exec / exec-override / session / upload / download
+    kind: &'a str,
+    /// 远程执行的命令，交互式会话为 None
+    command: Option<&'a str>,
+}
+
+pub fn get_audit_log_path() -> Result<PathBuf> {
+    Ok(crate::config::get_state_dir()?.join("audit.log"))
+}
+
+/// 审计模式下记录一次远程执行；写入失败时返回错误，调用方应中止执行。
+/// 配置了 `[siem]` 时不论是否处于审计模式都上报
+pub fn record(server: &ServerConfig, kind: &str, command: Option<&str>) -> Result<()> {
+    siem::emit(server, kind, command);
+    if !audit_mode() {
+        return Ok(());
+    }
+    write_entry(server, kind, command)
+}
+
+/// 不论是否处于审计模式都记录，用于越过命令规则等需要留痕的操作
+pub fn record_always(server: &ServerConfig, kind: &str, command: Option<&str>) -> Result<()> {
+    siem::emit(server, kind, command);
+    write_entry(server, kind, command)
+}
+
+fn write_entry(server: &ServerConfig, kind: &str, command: Option<&str>) -> Result<()> {
+    let entry = AuditEntry {
+        time: chrono::Local::now().to_rfc3339(),
+        local_user: std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default(),
+        server: &server.name,
+        target: format!("{}@{}:{}", server.username, server.host, server.port),
+        kind,
+        command,
+    };
+
+    let path = get_audit_log_path()?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("无法打开审计日志: {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)
+        .with_context(|| format!("无法写入审计日志: {}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use colored::{Color, Colorize};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::models::ServerConfig;
+use crate::utils::ssh::terminal_size;
+
+/// 分组的环境标识，连接前以整行色块显示，提醒当前连接的是哪个环境
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Badge {
+    /// 显示的文字，例如 PROD
+    pub label: String,
+    /// 背景色：red / green / yellow / blue / magenta / cyan 等
+    pub color: String,
+}
+
+impl Badge {
+    fn new(label: &str, color: &str) -> Self {
+        Badge { label: label.to_string(), color: color.to_string() }
+    }
+
+    /// 服务器的环境标识：`config.toml` 中为其分组配置的优先，
+    /// 否则按分组名识别常见环境（prod / staging / dev / test）
+    pub fn for_server(server: &ServerConfig, configured: &HashMap<String, Badge>) -> Option<Badge> {
+        let group = server.group.as_deref()?;
+        if let Some(badge) = configured.get(group) {
+            return (!badge.label.is_empty()).then(|| badge.clone());
+        }
+
+        let group = group.to_lowercase();
+        let words: Vec<&str> = group.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        let has = |names: &[&str]| words.iter().any(|word| names.contains(word));
+        if has(&["prod", "production", "prd", "live"]) {
+            Some(Badge::new("PROD", "red"))
+        } else if has(&["staging", "stage", "stg", "pre", "uat"]) {
+            Some(Badge::new("STAGING", "yellow"))
+        } else if has(&["dev", "develop", "development", "test", "testing", "qa"]) {
+            Some(Badge::new("DEV", "green"))
+        } else {
+            None
+        }
+    }
+
+    /// 占满终端宽度的一行：` PROD  web-1 (root@10.0.0.1) `
+    pub fn render(&self, server: &ServerConfig) -> String {
+        let text = format!(
+            " {}  {} ({}@{}) ",
+            self.label, server.name, server.username, server.host
+        );
+        let width = terminal_size().0.max(display_width(&text));
+        let line = format!("{}{}", text, " ".repeat(width - display_width(&text)));
+        let color: Color = self.color.parse().unwrap_or(Color::Red);
+        let fg = match color {
+            Color::Yellow | Color::BrightYellow | Color::White | Color::BrightWhite | Color::Cyan => Color::Black,
+            _ => Color::White,
+        };
+        line.color(fg).on_color(color).bold().to_string()
+    }
+}
+
+/// 终端中的显示宽度，中日韩字符占两列
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c as u32 >= 0x1100 { 2 } else { 1 }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    fn server(group: &str) -> ServerConfig {
+        ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+            AuthType::Agent, Some(group.into()), None, None,
+        )
+    }
+
+    #[test]
+    fn resolves_badge_by_group() {
+        let mut configured = HashMap::new();
+        configured.insert("payments".to_string(), Badge::new("PCI", "magenta"));
+        configured.insert("prod-sandbox".to_string(), Badge::new("", "red"));
+
+        assert_eq!(Badge::for_server(&server("payments"), &configured), Some(Badge::new("PCI", "magenta")));
+        assert_eq!(Badge::for_server(&server("eu-prod"), &configured), Some(Badge::new("PROD", "red")));
+        assert_eq!(Badge::for_server(&server("dev"), &configured), Some(Badge::new("DEV", "green")));
+        // 配置为空标签表示不显示；不含环境关键词的分组没有标识
+        assert_eq!(Badge::for_server(&server("prod-sandbox"), &configured), None);
+        assert_eq!(Badge::for_server(&server("product"), &configured), None);
+    }
+}
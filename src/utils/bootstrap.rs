@@ -0,0 +1,291 @@
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Deserialize;
+use shell_escape::escape;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::models::ServerConfig;
+use crate::utils::remote_user::{self, Distro};
+use crate::utils::ssh::SshClient;
+
+/// 初始化新服务器的步骤清单（TOML），按顺序执行，每一步都可以重复执行
+#[derive(Debug, Deserialize)]
+pub struct Playbook {
+    #[serde(default, rename = "step")]
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Step {
+    /// 显示的名称，未设置时按操作生成
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// 安装软件包，已安装的跳过
+    Packages(Vec<String>),
+    /// 上传文件，内容相同时跳过
+    Copy(CopyFile),
+    /// 为用户安装公钥，用户不存在时创建
+    AuthorizedKey(AuthorizedKey),
+    /// 设置 sshd_config 中的选项，校验通过后重新加载 sshd
+    Sshd(BTreeMap<String, String>),
+    /// 执行命令；`unless` 命令成功时跳过
+    Run(RunCommand),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CopyFile {
+    /// 本地路径，相对路径以清单文件所在目录为准
+    pub src: PathBuf,
+    pub dest: String,
+    #[serde(default = "default_mode")]
+    pub mode: String,
+    /// 文件所有者，例如 `root:root`
+    pub owner: Option<String>,
+}
+
+fn default_mode() -> String {
+    "644".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorizedKey {
+    pub user: String,
+    /// 公钥文件路径或公钥内容
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunCommand {
+    pub command: String,
+    pub unless: Option<String>,
+}
+
+/// 每一步的脚本最后输出 `changed` 或 `ok`
+const CHANGED: &str = "changed";
+
+impl Playbook {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取清单: {}", path.display()))?;
+        let playbook: Playbook = toml::from_str(&content)
+            .with_context(|| format!("无法解析清单: {}", path.display()))?;
+        if playbook.steps.is_empty() {
+            return Err(anyhow::anyhow!("清单中没有步骤（[[step]]）: {}", path.display()));
+        }
+        Ok(playbook)
+    }
+}
+
+impl Step {
+    pub fn title(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        match &self.action {
+            Action::Packages(packages) => format!("安装 {}", packages.join(" ")),
+            Action::Copy(copy) => format!("上传 {}", copy.dest),
+            Action::AuthorizedKey(key) => format!("为 {} 安装公钥", key.user),
+            Action::Sshd(options) => format!("配置 sshd: {}", options.keys().cloned().collect::<Vec<_>>().join(", ")),
+            Action::Run(run) => format!("执行 {}", run.command),
+        }
+    }
+}
+
+/// 安装缺少的软件包，自动选择 apt-get / dnf / yum / apk / zypper
+pub fn packages_script(packages: &[String]) -> String {
+    let list = packages.iter().map(|p| escape(p.as_str().into()).into_owned()).collect::<Vec<_>>().join(" ");
+    format!(
+        r#"set -e
+installed() {{
+  if command -v dpkg-query >/dev/null; then dpkg-query -W -f='${{Status}}' "$1" 2>/dev/null | grep -q 'ok installed'
+  elif command -v rpm >/dev/null; then rpm -q "$1" >/dev/null 2>&1
+  elif command -v apk >/dev/null; then apk info -e "$1" >/dev/null 2>&1
+  else return 1; fi
+}}
+missing=""
+for p in {list}; do installed "$p" || missing="$missing $p"; done
+[ -z "$missing" ] && {{ echo ok; exit 0; }}
+if command -v apt-get >/dev/null; then DEBIAN_FRONTEND=noninteractive apt-get update -q >&2 && DEBIAN_FRONTEND=noninteractive apt-get install -y -q $missing >&2
+elif command -v dnf >/dev/null; then dnf install -y -q $missing >&2
+elif command -v yum >/dev/null; then yum install -y -q $missing >&2
+elif command -v apk >/dev/null; then apk add -q $missing >&2
+elif command -v zypper >/dev/null; then zypper -n -q install $missing >&2
+else echo "未找到支持的包管理器" >&2; exit 1; fi
+echo {CHANGED}"#
+    )
+}
+
+/// 把已上传到 `tmp` 的文件安装到目标位置，内容和权限相同时跳过
+pub fn install_file_script(tmp: &str, copy: &CopyFile) -> String {
+    let tmp = escape(tmp.into());
+    let dest = escape(copy.dest.as_str().into());
+    let mode = escape(copy.mode.as_str().into());
+    let chown = match &copy.owner {
+        Some(owner) => format!("chown {} {dest}", escape(owner.as_str().into())),
+        None => "true".to_string(),
+    };
+    format!(
+        r#"set -e
+if cmp -s {tmp} {dest} && [ "$(stat -c %a {dest})" = {mode} ]; then rm -f {tmp}; echo ok; exit 0; fi
+mkdir -p "$(dirname {dest})"
+cp {tmp} {dest} && rm -f {tmp}
+chmod {mode} {dest}
+{chown}
+echo {CHANGED}"#
+    )
+}
+
+/// 修改 sshd_config：已生效的选项跳过；否则把新值插入到文件开头（sshd 以第一次出现的值为准，
+/// 也不会落入 Match 块），`sshd -t` 校验失败时恢复原文件
+pub fn sshd_script(options: &BTreeMap<String, String>) -> String {
+    let mut script = String::from(
+        "set -e\nconf=/etc/ssh/sshd_config\nsshd=$(command -v sshd || echo /usr/sbin/sshd)\nchanged=\ncp \"$conf\" \"$conf.rssh-bak\"\n",
+    );
+    for (key, value) in options {
+        let mut wanted = vec![format!("{} {}", key.to_lowercase(), value.to_lowercase())];
+        // 旧版 sshd -T 把 prohibit-password 显示为 without-password
+        if value.eq_ignore_ascii_case("prohibit-password") {
+            wanted.push(format!("{} without-password", key.to_lowercase()));
+        }
+        let patterns = wanted.iter().map(|w| format!("-e {}", escape(w.as_str().into()))).collect::<Vec<_>>().join(" ");
+        let line = escape(format!("{} {}", key, value).into());
+        script.push_str(&format!(
+            "if ! \"$sshd\" -T 2>/dev/null | grep -qixF {patterns}; then\n  sed -i \"1i $(printf %s {line})\" \"$conf\"\n  changed=1\nfi\n"
+        ));
+    }
+    script.push_str(&format!(
+        r#"[ -z "$changed" ] && {{ rm -f "$conf.rssh-bak"; echo ok; exit 0; }}
+if ! "$sshd" -t; then mv "$conf.rssh-bak" "$conf"; echo "sshd 配置校验失败，已恢复" >&2; exit 1; fi
+rm -f "$conf.rssh-bak"
+systemctl reload sshd 2>/dev/null || systemctl reload ssh 2>/dev/null || service ssh reload 2>/dev/null || rc-service sshd reload
+echo {CHANGED}"#
+    ));
+    script
+}
+
+/// 执行命令，`unless` 成功时跳过
+pub fn run_script(run: &RunCommand) -> String {
+    let command = format!("set -e\n{}\necho {}", run.command, CHANGED);
+    match &run.unless {
+        Some(unless) => format!(
+            "if ( {} ) >/dev/null 2>&1; then echo ok; exit 0; fi\n{}",
+            unless, command
+        ),
+        None => command,
+    }
+}
+
+/// 按顺序执行清单，遇到失败的步骤停止。`base_dir` 用于解析 copy 的相对路径
+pub fn run_playbook(server: &ServerConfig, playbook: &Playbook, base_dir: &Path) -> Result<()> {
+    let client = SshClient::connect(server)?;
+    let distro = remote_user::detect_distro(&client)?;
+    let total = playbook.steps.len();
+    let mut changed = 0;
+
+    for (index, step) in playbook.steps.iter().enumerate() {
+        print!("[{}/{}] {} ... ", index + 1, total, step.title());
+        std::io::stdout().flush()?;
+
+        let output = run_step(&client, server, distro, step, base_dir)
+            .with_context(|| format!("第 {} 步失败: {}", index + 1, step.title()));
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                println!("{}", "失败".bright_red());
+                return Err(e);
+            }
+        };
+        if output.lines().last().map(str::trim) == Some(CHANGED) {
+            changed += 1;
+            println!("{}", "已更改".bright_yellow());
+        } else {
+            println!("{}", "无需更改".bright_green());
+        }
+    }
+
+    println!("{} 共 {} 步，{} 步有更改", "✓".bright_green(), total, changed);
+    Ok(())
+}
+
+fn run_step(client: &SshClient, server: &ServerConfig, distro: Distro, step: &Step, base_dir: &Path) -> Result<String> {
+    let script = match &step.action {
+        Action::Packages(packages) => packages_script(packages),
+        Action::Copy(copy) => {
+            let src = base_dir.join(&copy.src);
+            let content = std::fs::read(&src)
+                .with_context(|| format!("无法读取本地文件: {}", src.display()))?;
+            // 先以登录用户上传到临时文件，再以 root 身份安装到目标位置
+            let tmp = format!("/tmp/rssh-bootstrap-{}", uuid::Uuid::new_v4());
+            let mut file = client.sftp()?.create(Path::new(&tmp))
+                .with_context(|| format!("无法创建远程临时文件: {}", tmp))?;
+            file.write_all(&content)?;
+            drop(file);
+            install_file_script(&tmp, copy)
+        }
+        Action::AuthorizedKey(key) => {
+            remote_user::validate_username(&key.user)?;
+            let public_key = remote_user::load_public_key(&key.key)?;
+            let before = format!("cat ~{}/.ssh/authorized_keys 2>/dev/null | grep -qxF {}", key.user, escape(public_key.as_str().into()));
+            let install = remote_user::add_user_script(distro, &key.user, &[public_key], false);
+            format!("if ( {} ); then echo ok; exit 0; fi\n{}\necho {}", before, install, CHANGED)
+        }
+        Action::Sshd(options) => {
+            if let Some(key) = options.keys().find(|key| !key.chars().all(|c| c.is_ascii_alphanumeric())) {
+                return Err(anyhow::anyhow!("无效的 sshd 选项名: {}", key));
+            }
+            sshd_script(options)
+        }
+        Action::Run(run) => run_script(run),
+    };
+    remote_user::run_as_root(client, server, &script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_playbook_steps() {
+        let playbook: Playbook = toml::from_str(
+            r#"
+            [[step]]
+            packages = ["curl", "htop"]
+
+            [[step]]
+            name = "禁用密码登录"
+            sshd = { PasswordAuthentication = "no", PermitRootLogin = "prohibit-password" }
+
+            [[step]]
+            copy = { src = "files/motd", dest = "/etc/motd" }
+
+            [[step]]
+            run = { command = "timedatectl set-timezone Asia/Shanghai", unless = "timedatectl | grep -q Asia/Shanghai" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(playbook.steps.len(), 4);
+        assert_eq!(playbook.steps[0].title(), "安装 curl htop");
+        assert_eq!(playbook.steps[1].title(), "禁用密码登录");
+        let Action::Copy(copy) = &playbook.steps[2].action else { panic!("应为 copy") };
+        assert_eq!(copy.mode, "644");
+        assert!(run_script(match &playbook.steps[3].action {
+            Action::Run(run) => run,
+            _ => panic!("应为 run"),
+        })
+        .starts_with("if ( timedatectl | grep -q Asia/Shanghai )"));
+        assert!(sshd_script(match &playbook.steps[1].action {
+            Action::Sshd(options) => options,
+            _ => panic!("应为 sshd"),
+        })
+        .contains("grep -qixF -e 'passwordauthentication no'"));
+    }
+}
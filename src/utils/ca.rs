@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::models::{AuthType, ConnectionType, ServerConfig};
+use crate::utils::ssh_config::expand_tilde;
+
+/// 证书剩余有效期少于这么多秒时重新签发
+const RENEW_BEFORE: i64 = 5 * 60;
+
+/// 团队 CA 配置，对应 `config.toml` 中的 `[ca]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaSettings {
+    /// CA 私钥路径；以 `.pub` 结尾时表示 CA 私钥在 ssh-agent 中（`ssh-keygen -U`）
+    pub key: String,
+    /// 证书有效期，ssh-keygen 的时间格式，例如 `30m`、`8h`、`1d`（默认 1h）
+    #[serde(default = "default_validity")]
+    pub validity: String,
+    /// 证书中的 principals，未设置时使用服务器的登录用户
+    pub principals: Option<Vec<String>>,
+    /// 只为这些分组的服务器签发证书，未设置时为所有使用密钥认证的服务器签发
+    pub groups: Option<Vec<String>>,
+}
+
+fn default_validity() -> String {
+    "1h".to_string()
+}
+
+struct CaState {
+    settings: CaSettings,
+    cert_dir: PathBuf,
+}
+
+static CA: OnceLock<CaState> = OnceLock::new();
+
+/// 启用 CA 模式，连接前自动签发的证书保存在 `cert_dir`，只在启动时调用一次
+pub fn configure(settings: CaSettings, cert_dir: PathBuf) {
+    let _ = CA.set(CaState { settings, cert_dir });
+}
+
+/// 已配置的 CA
+pub fn settings() -> Option<&'static CaSettings> {
+    CA.get().map(|state| &state.settings)
+}
+
+/// ssh-keygen 的 `-V` 参数：从 5 分钟前开始生效，避免服务器时钟偏慢时证书尚未生效
+pub fn validity_interval(validity: &str) -> Result<String> {
+    let validity = validity.trim_start_matches('+');
+    let valid = validity.starts_with(|c: char| c.is_ascii_digit())
+        && validity.chars().all(|c| c.is_ascii_digit() || "smhdwSMHDW".contains(c));
+    if !valid {
+        return Err(anyhow::anyhow!("无效的证书有效期: {}（例如 30m、8h、1d）", validity));
+    }
+    Ok(format!("-5m:+{}", validity))
+}
+
+/// 用 CA 为公钥签发用户证书，证书写在公钥旁边（`xxx.pub` → `xxx-cert.pub`），返回证书路径
+pub fn sign_key(ca: &CaSettings, public_key: &Path, identity: &str, principals: &[String], validity: &str) -> Result<PathBuf> {
+    which::which("ssh-keygen").map_err(|_| anyhow::anyhow!("未找到 ssh-keygen，请先安装 OpenSSH 客户端"))?;
+    if principals.is_empty() {
+        return Err(anyhow::anyhow!("证书至少需要一个 principal"));
+    }
+
+    let ca_key = expand_tilde(&ca.key);
+    let mut command = Command::new("ssh-keygen");
+    command.arg("-s").arg(&ca_key);
+    if ca_key.ends_with(".pub") {
+        command.arg("-U");
+    }
+    let status = command
+        .args(["-q", "-I", identity, "-n", &principals.join(","), "-V", &validity_interval(validity)?])
+        .arg(public_key)
+        .status()
+        .with_context(|| "无法执行 ssh-keygen")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("ssh-keygen 签发证书失败（CA: {}）", ca_key));
+    }
+
+    let public_key = public_key.to_string_lossy();
+    let stem = public_key.strip_suffix(".pub").unwrap_or(&public_key);
+    Ok(PathBuf::from(format!("{}-cert.pub", stem)))
+}
+
+/// 解析 `ssh-keygen -L` 的 `Valid:` 行，返回过期时间（Unix 时间戳），永不过期时为 None
+pub fn parse_valid_line(line: &str) -> Result<Option<i64>> {
+    let value = line.trim().trim_start_matches("Valid:").trim();
+    let until = if let Some(range) = value.strip_prefix("from ") {
+        range.split_once(" to ").map(|(_, until)| until)
+    } else {
+        value.strip_prefix("before ")
+    };
+    let Some(until) = until else {
+        // forever 或 after <时间>
+        return Ok(None);
+    };
+    let time = NaiveDateTime::parse_from_str(until.trim(), "%Y-%m-%dT%H:%M:%S")
+        .with_context(|| format!("无法解析证书有效期: {}", value))?;
+    let time = Local
+        .from_local_datetime(&time)
+        .earliest()
+        .ok_or_else(|| anyhow::anyhow!("无效的本地时间: {}", until))?;
+    Ok(Some(time.timestamp()))
+}
+
+/// 读取证书的过期时间，永不过期时为 None
+pub fn certificate_expiry(cert: &Path) -> Result<Option<i64>> {
+    let output = Command::new("ssh-keygen")
+        .arg("-L")
+        .arg("-f")
+        .arg(cert)
+        .output()
+        .with_context(|| "无法执行 ssh-keygen")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("无法读取证书: {}", cert.display()));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let line = text
+        .lines()
+        .find(|line| line.trim_start().starts_with("Valid:"))
+        .ok_or_else(|| anyhow::anyhow!("证书中没有有效期: {}", cert.display()))?;
+    parse_valid_line(line)
+}
+
+/// 证书在 `now` 时是否还能继续使用，快过期时视为需要续签
+pub fn needs_renewal(expiry: Option<i64>, now: i64) -> bool {
+    expiry.is_some_and(|expiry| expiry - now < RENEW_BEFORE)
+}
+
+/// 连接前调用：服务器在 CA 范围内时返回可用的证书路径，没有证书或快过期时自动签发
+pub fn certificate_for(server: &ServerConfig) -> Result<Option<String>> {
+    let Some(state) = CA.get() else {
+        return Ok(None);
+    };
+    let AuthType::Key(key_path) = &server.auth_type else {
+        return Ok(None);
+    };
+    if server.connection_type != ConnectionType::Ssh {
+        return Ok(None);
+    }
+    if let Some(groups) = &state.settings.groups {
+        if !server.group.as_ref().is_some_and(|group| groups.contains(group)) {
+            return Ok(None);
+        }
+    }
+
+    let cert = state.cert_dir.join(format!("{}-cert.pub", server.id));
+    let now = Local::now().timestamp();
+    if cert.exists() {
+        // 证书损坏或无法读取时重新签发
+        if let Ok(expiry) = certificate_expiry(&cert) {
+            if !needs_renewal(expiry, now) {
+                return Ok(Some(cert.display().to_string()));
+            }
+        }
+    }
+
+    let public_key = PathBuf::from(format!("{}.pub", expand_tilde(key_path)));
+    if !public_key.is_file() {
+        return Err(anyhow::anyhow!(
+            "CA 模式需要公钥文件 {} 来签发证书，可用 ssh-keygen -y -f {} > {} 生成",
+            public_key.display(), key_path, public_key.display()
+        ));
+    }
+    std::fs::create_dir_all(&state.cert_dir)
+        .with_context(|| format!("无法创建证书目录: {}", state.cert_dir.display()))?;
+    let staged = state.cert_dir.join(format!("{}.pub", server.id));
+    std::fs::copy(&public_key, &staged)
+        .with_context(|| format!("无法复制公钥: {}", public_key.display()))?;
+
+    let principals = state.settings.principals.clone().unwrap_or_else(|| vec![server.username.clone()]);
+    let local_user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "rssh".into());
+    let identity = format!("{}@rssh:{}", local_user, server.name);
+    let issued = sign_key(&state.settings, &staged, &identity, &principals, &state.settings.validity)?;
+    println!("已为 {} 签发 SSH 证书（principals: {}，有效期 {}）", server.name, principals.join(","), state.settings.validity);
+    Ok(Some(issued.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_certificate_validity() {
+        assert_eq!(validity_interval("8h").unwrap(), "-5m:+8h");
+        assert_eq!(validity_interval("+1d").unwrap(), "-5m:+1d");
+        assert!(validity_interval("1h; rm").is_err());
+
+        assert_eq!(parse_valid_line("        Valid: forever").unwrap(), None);
+        assert_eq!(parse_valid_line("Valid: after 2026-10-17T10:00:00").unwrap(), None);
+        let expiry = parse_valid_line("        Valid: from 2026-10-17T10:00:00 to 2026-10-17T11:00:00")
+            .unwrap()
+            .unwrap();
+        let start = parse_valid_line("Valid: before 2026-10-17T10:00:00").unwrap().unwrap();
+        assert_eq!(expiry - start, 3600);
+
+        assert!(needs_renewal(Some(expiry), expiry - 60));
+        assert!(!needs_renewal(Some(expiry), expiry - 3000));
+        assert!(!needs_renewal(None, expiry));
+    }
+}
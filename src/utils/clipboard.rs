@@ -0,0 +1,227 @@
+//! 远程剪贴板（OSC 52）：内置库模式的交互式会话中，把远程程序（vim、tmux 等）发出的
+//! `ESC ] 52 ; <选区> ; <base64> BEL` 转为写入本地剪贴板，终端本身不支持或不转发 OSC 52 时复制也能生效。
+//!
+//! 需要在 `config.toml` 的 `[clipboard]` 中开启。超过大小上限的内容忽略；
+//! 远程读取剪贴板的请求（内容为 `?`）不会得到应答，避免本地剪贴板内容泄露给服务器。
+//! 本地没有可用的剪贴板命令时，序列原样交给本地终端处理。
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// `config.toml` 中的 `[clipboard]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardSettings {
+    /// 把远程的 OSC 52 复制转为写入本地剪贴板（默认关闭）
+    pub osc52: bool,
+    /// 单次复制的内容上限（字节，默认 1 MB），超过时忽略
+    pub max_bytes: usize,
+}
+
+impl Default for ClipboardSettings {
+    fn default() -> Self {
+        Self { osc52: false, max_bytes: 1024 * 1024 }
+    }
+}
+
+static SETTINGS: OnceLock<ClipboardSettings> = OnceLock::new();
+
+/// 设置本进程的剪贴板桥接，只在启动时调用一次
+pub fn configure(settings: ClipboardSettings) {
+    let _ = SETTINGS.set(settings);
+}
+
+const OSC52: &[u8] = b"\x1b]52;";
+
+/// 从远程输出中识别出的剪贴板操作
+#[derive(Debug, PartialEq, Eq)]
+enum Event {
+    /// 写入剪贴板的内容，以及原始的转义序列（本地无法写入时交给终端）
+    Copy { data: Vec<u8>, sequence: Vec<u8> },
+    /// 内容超过上限，已忽略
+    TooLarge,
+}
+
+/// 从远程输出中取出 OSC 52 序列，跨多次读取的序列会先缓存
+#[derive(Debug)]
+pub struct Osc52Bridge {
+    max_bytes: usize,
+    /// 尚未结束的序列（或可能是序列开头的几个字节）
+    pending: Vec<u8>,
+    /// 正在跳过超过上限的序列，直到结束符
+    skipping: bool,
+}
+
+impl Osc52Bridge {
+    /// 按全局配置创建，未开启时返回 None
+    pub fn from_config() -> Option<Self> {
+        SETTINGS.get().filter(|settings| settings.osc52).map(|settings| Self::new(settings.max_bytes))
+    }
+
+    pub fn new(max_bytes: usize) -> Self {
+        Osc52Bridge { max_bytes, pending: Vec::new(), skipping: false }
+    }
+
+    /// 处理一段远程输出，写入本地剪贴板，返回应显示的部分
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        let (mut output, events) = self.filter(data);
+        for event in events {
+            match event {
+                Event::Copy { data, sequence } => {
+                    if !copy_to_local(data) {
+                        output.extend_from_slice(&sequence);
+                    }
+                },
+                Event::TooLarge => {
+                    output.extend_from_slice(format!("\r\nrssh: 远程复制的内容超过 {} 字节，已忽略\r\n", self.max_bytes).as_bytes());
+                },
+            }
+        }
+        output
+    }
+
+    fn filter(&mut self, data: &[u8]) -> (Vec<u8>, Vec<Event>) {
+        let mut input = std::mem::take(&mut self.pending);
+        input.extend_from_slice(data);
+        let mut output = Vec::with_capacity(input.len());
+        let mut events = Vec::new();
+        let mut rest = &input[..];
+
+        while !rest.is_empty() {
+            if self.skipping {
+                match find_terminator(rest) {
+                    Some((end, len)) => {
+                        self.skipping = false;
+                        rest = &rest[end + len..];
+                    },
+                    None => rest = &[],
+                }
+                continue;
+            }
+            let Some(start) = find(rest, OSC52) else {
+                // 结尾可能是被截断的序列开头，留到下次
+                let keep = (1..OSC52.len()).rev().find(|&len| rest.ends_with(&OSC52[..len])).unwrap_or(0);
+                output.extend_from_slice(&rest[..rest.len() - keep]);
+                self.pending.extend_from_slice(&rest[rest.len() - keep..]);
+                break;
+            };
+            output.extend_from_slice(&rest[..start]);
+            let sequence = &rest[start..];
+            let Some((end, len)) = find_terminator(sequence) else {
+                // base64 编码后约为原来的 4/3
+                if sequence.len() > self.encoded_limit() {
+                    self.skipping = true;
+                    events.push(Event::TooLarge);
+                } else {
+                    self.pending.extend_from_slice(sequence);
+                }
+                break;
+            };
+            let body = &sequence[OSC52.len()..end];
+            let payload = body.iter().position(|&b| b == b';').map_or(&body[..0], |i| &body[i + 1..]);
+            if payload.len() > self.encoded_limit() {
+                events.push(Event::TooLarge);
+            } else if payload != b"?" {
+                // 读取剪贴板的请求不应答，无效的 base64 忽略
+                if let Ok(data) = STANDARD.decode(payload) {
+                    if data.len() > self.max_bytes {
+                        events.push(Event::TooLarge);
+                    } else {
+                        events.push(Event::Copy { data, sequence: sequence[..end + len].to_vec() });
+                    }
+                }
+            }
+            rest = &sequence[end + len..];
+        }
+        (output, events)
+    }
+
+    fn encoded_limit(&self) -> usize {
+        self.max_bytes / 3 * 4 + 64
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// 序列的结束符位置和长度：BEL 或 ST（ESC \）
+fn find_terminator(data: &[u8]) -> Option<(usize, usize)> {
+    data.iter().enumerate().find_map(|(i, &b)| match b {
+        0x07 => Some((i, 1)),
+        0x1b if data.get(i + 1) == Some(&b'\\') => Some((i, 2)),
+        _ => None,
+    })
+}
+
+/// 本地的剪贴板命令，按平台和显示服务器选择第一个可用的
+fn clipboard_command() -> Option<Command> {
+    let candidates: &[&[&str]] = if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        &[&["wl-copy"], &["xclip", "-selection", "clipboard"], &["xsel", "--clipboard", "--input"]]
+    } else {
+        &[&["xclip", "-selection", "clipboard"], &["xsel", "--clipboard", "--input"]]
+    };
+    let args = candidates.iter().find(|args| which::which(args[0]).is_ok())?;
+    let mut command = Command::new(args[0]);
+    command.args(&args[1..]);
+    Some(command)
+}
+
+/// 在后台写入本地剪贴板，不阻塞会话；没有可用的剪贴板命令时返回 false
+fn copy_to_local(data: Vec<u8>) -> bool {
+    let Some(mut command) = clipboard_command() else {
+        return false;
+    };
+    std::thread::spawn(move || {
+        let child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+        if let Ok(mut child) = child {
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(&data);
+            }
+            let _ = child.wait();
+        }
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copy(data: &str, sequence: &[u8]) -> Event {
+        Event::Copy { data: data.as_bytes().to_vec(), sequence: sequence.to_vec() }
+    }
+
+    #[test]
+    fn extracts_sequences_split_across_reads() {
+        let mut bridge = Osc52Bridge::new(1024);
+        let (output, events) = bridge.filter(b"before\x1b]5");
+        assert_eq!((output.as_slice(), events.len()), (&b"before"[..], 0));
+        let (output, events) = bridge.filter(b"2;c;aGVs");
+        assert_eq!((output.as_slice(), events.len()), (&b""[..], 0));
+        let (output, events) = bridge.filter(b"bG8=\x07after\x1b]52;;d29ybGQ=\x1b\\!");
+        assert_eq!(output, b"after!");
+        assert_eq!(events, [copy("hello", b"\x1b]52;c;aGVsbG8=\x07"), copy("world", b"\x1b]52;;d29ybGQ=\x1b\\")]);
+    }
+
+    #[test]
+    fn ignores_queries_and_oversized_content() {
+        let mut bridge = Osc52Bridge::new(4);
+        let (output, events) = bridge.filter(b"\x1b]52;c;?\x07\x1b]52;c;aGVsbG8=\x07ok\x1b]0;title\x07");
+        assert_eq!(output, b"ok\x1b]0;title\x07");
+        assert_eq!(events, [Event::TooLarge]);
+
+        let (output, events) = bridge.filter(&[b"\x1b]52;c;".as_slice(), &[b'A'; 200]].concat());
+        assert_eq!((output.len(), events), (0, vec![Event::TooLarge]));
+        let (output, events) = bridge.filter(b"AAAA\x07visible");
+        assert_eq!((output.as_slice(), events.len()), (&b"visible"[..], 0));
+    }
+}
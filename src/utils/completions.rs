@@ -0,0 +1,234 @@
+//! Shell 补全脚本：用 clap_complete 根据命令行定义生成 bash / zsh / fish 的补全，并安装到各 shell 默认加载的位置。
+//!
+//! 子命令和选项的补全是静态的；服务器、分组和会话名称在补全时调用隐藏的 `rssh __complete <类型>` 取得，
+//! 新添加的服务器不需要重新生成补全脚本。
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+/// 支持生成补全的 shell
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// 根据 `$SHELL` 判断当前使用的 shell
+    pub fn detect() -> Option<Self> {
+        let shell = PathBuf::from(std::env::var_os("SHELL")?);
+        match shell.file_name()?.to_str()? {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Shell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish"),
+        }
+    }
+}
+
+/// `rssh __complete <类型>` 可以列出的名称
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Candidates {
+    /// 服务器名称和别名
+    Servers,
+    /// 分组名称
+    Groups,
+    /// 会话名称
+    Sessions,
+}
+
+/// 参数需要动态补全的子命令：第一个位置参数是服务器或会话的子命令
+struct Dynamic {
+    servers: Vec<String>,
+    sessions: Vec<String>,
+}
+
+impl Dynamic {
+    /// bash / zsh 中 `case` 的分支，按子命令名称决定补全的类型
+    fn case_branches(&self, indent: &str) -> String {
+        [("servers", &self.servers), ("sessions", &self.sessions)]
+            .into_iter()
+            .filter(|(_, commands)| !commands.is_empty())
+            .map(|(kind, commands)| format!("{}{}) kind={} ;;\n", indent, commands.join("|"), kind))
+            .collect()
+    }
+
+    fn new(cmd: &clap::Command) -> Self {
+        let mut dynamic = Dynamic { servers: Vec::new(), sessions: Vec::new() };
+        for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+            match sub.get_positionals().next().map(|arg| arg.get_id().as_str()) {
+                Some("server") => dynamic.servers.push(sub.get_name().to_string()),
+                // share 的参数也可以是运行中的 tmux 会话，不只是保存的会话
+                Some("session") if sub.get_name() != "share" => dynamic.sessions.push(sub.get_name().to_string()),
+                _ => {},
+            }
+        }
+        dynamic
+    }
+}
+
+/// 生成补全脚本
+pub fn generate(cmd: &clap::Command, shell: Shell) -> String {
+    let bin = cmd.get_name().to_string();
+    let mut script = Vec::new();
+    let generator = match shell {
+        Shell::Bash => clap_complete::Shell::Bash,
+        Shell::Zsh => clap_complete::Shell::Zsh,
+        Shell::Fish => clap_complete::Shell::Fish,
+    };
+    clap_complete::generate(generator, &mut cmd.clone(), &bin, &mut script);
+    let script = String::from_utf8_lossy(&script).into_owned();
+
+    let dynamic = Dynamic::new(cmd);
+    let extra = match shell {
+        Shell::Bash => bash(&bin, &dynamic),
+        Shell::Zsh => zsh(&bin, &dynamic),
+        Shell::Fish => fish(&bin, &dynamic),
+    };
+    format!("{}\n# 服务器、分组和会话名称由 {} __complete 动态提供\n{}", script, bin, extra)
+}
+
+/// 安装补全脚本，返回写入的文件路径
+pub fn install(cmd: &clap::Command, shell: Shell) -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("无法确定用户主目录"))?;
+    let xdg = |var: &str, default: &str| std::env::var_os(var).map(PathBuf::from).unwrap_or_else(|| home.join(default));
+    let path = match shell {
+        // bash-completion 按需加载该目录下与命令同名的文件
+        Shell::Bash => xdg("XDG_DATA_HOME", ".local/share").join("bash-completion/completions").join(cmd.get_name()),
+        Shell::Zsh => crate::config::get_config_dir()?.join("completions").join(format!("{}.zsh", cmd.get_name())),
+        Shell::Fish => xdg("XDG_CONFIG_HOME", ".config").join("fish/completions").join(format!("{}.fish", cmd.get_name())),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("无法创建目录: {}", dir.display()))?;
+    }
+    std::fs::write(&path, generate(cmd, shell)).with_context(|| format!("无法写入 {}", path.display()))?;
+
+    // zsh 没有按命令名自动加载补全文件的目录，在 ~/.zshrc 中 source
+    if shell == Shell::Zsh {
+        let zshrc = home.join(".zshrc");
+        let line = format!("[ -f {0} ] && source {0}", path.display());
+        let existing = std::fs::read_to_string(&zshrc).unwrap_or_default();
+        if !existing.contains(&line) {
+            let separator = if existing.is_empty() || existing.ends_with('\n') { "" } else { "\n" };
+            std::fs::write(&zshrc, format!("{}{}# rssh 补全\n{}\n", existing, separator, line))
+                .with_context(|| format!("无法写入 {}", zshrc.display()))?;
+        }
+    }
+    Ok(path)
+}
+
+/// 包装 clap_complete 生成的 `_rssh`：补全服务器、会话和 `-g/--group` 的值时改为调用 `rssh __complete`
+fn bash(bin: &str, dynamic: &Dynamic) -> String {
+    let function = format!("_{}", bin.replace('-', "_"));
+    format!(
+        "{function}_dynamic() {{\n    \
+             local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\" kind=\"\"\n    \
+             case \"$prev\" in\n        \
+                 -g|--group) kind=groups ;;\n    \
+             esac\n    \
+             if [ -z \"$kind\" ] && [ \"$COMP_CWORD\" -eq 2 ]; then\n        \
+                 case \"${{COMP_WORDS[1]}}\" in\n\
+         {branches}        \
+                 esac\n    \
+             fi\n    \
+             if [ -n \"$kind\" ] && [[ \"$cur\" != -* ]]; then\n        \
+                 local IFS=$'\\n'\n        \
+                 COMPREPLY=($(compgen -W \"$({bin} __complete \"$kind\" 2>/dev/null)\" -- \"$cur\"))\n        \
+                 return 0\n    \
+             fi\n    \
+             {function} \"$@\"\n\
+         }}\n\
+         complete -F {function}_dynamic -o bashdefault -o default {bin}\n",
+        branches = dynamic.case_branches("            "),
+    )
+}
+
+fn zsh(bin: &str, dynamic: &Dynamic) -> String {
+    let function = format!("_{}", bin.replace('-', "_"));
+    format!(
+        "{function}_dynamic() {{\n    \
+             local kind=\"\"\n    \
+             case \"${{words[CURRENT-1]}}\" in\n        \
+                 -g|--group) kind=groups ;;\n    \
+             esac\n    \
+             if [[ -z \"$kind\" && $CURRENT -eq 3 ]]; then\n        \
+                 case \"${{words[2]}}\" in\n\
+         {branches}        \
+                 esac\n    \
+             fi\n    \
+             if [[ -n \"$kind\" && \"$PREFIX\" != -* ]]; then\n        \
+                 local -a names\n        \
+                 names=(${{(f)\"$({bin} __complete $kind 2>/dev/null)\"}})\n        \
+                 compadd -a names\n        \
+                 return\n    \
+             fi\n    \
+             {function} \"$@\"\n\
+         }}\n\
+         compdef {function}_dynamic {bin}\n",
+        branches = dynamic.case_branches("            "),
+    )
+}
+
+fn fish(bin: &str, dynamic: &Dynamic) -> String {
+    let mut script = String::new();
+    for (kind, commands) in [("servers", &dynamic.servers), ("sessions", &dynamic.sessions)] {
+        if !commands.is_empty() {
+            script.push_str(&format!(
+                "complete -c {bin} -n '__fish_seen_subcommand_from {}' -f -a '({bin} __complete {kind} 2>/dev/null)'\n",
+                commands.join(" "),
+            ));
+        }
+    }
+    script.push_str(&format!("complete -c {bin} -s g -l group -x -a '({bin} __complete groups 2>/dev/null)'\n"));
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> clap::Command {
+        clap::Command::new("rssh")
+            .arg(clap::Arg::new("audit").long("audit").action(clap::ArgAction::SetTrue))
+            .subcommand(
+                clap::Command::new("connect")
+                    .about("连接到服务器")
+                    .arg(clap::Arg::new("server"))
+                    .arg(clap::Arg::new("group").long("group").short('g')),
+            )
+            .subcommand(clap::Command::new("session-start").arg(clap::Arg::new("session")))
+            .subcommand(clap::Command::new("job").subcommand(clap::Command::new("list")))
+            .subcommand(clap::Command::new("share").arg(clap::Arg::new("session")))
+    }
+
+    #[test]
+    fn bash_completes_names_dynamically() {
+        let script = generate(&command(), Shell::Bash);
+        assert!(script.contains("_rssh() {"));
+        assert!(script.contains("            connect) kind=servers ;;\n"));
+        assert!(script.contains("            session-start) kind=sessions ;;\n"));
+        assert!(script.contains("rssh __complete \"$kind\""));
+        assert!(script.ends_with("complete -F _rssh_dynamic -o bashdefault -o default rssh\n"));
+        assert!(generate(&command(), Shell::Zsh).ends_with("compdef _rssh_dynamic rssh\n"));
+    }
+
+    #[test]
+    fn fish_completes_names_dynamically() {
+        let script = generate(&command(), Shell::Fish);
+        assert!(script.contains("complete -c rssh -n '__fish_seen_subcommand_from connect' -f -a '(rssh __complete servers 2>/dev/null)'\n"));
+        assert!(script.contains("-l group -x -a '(rssh __complete groups 2>/dev/null)'\n"));
+        assert!(script.contains("__fish_seen_subcommand_from session-start' -f -a '(rssh __complete sessions"));
+        assert!(!script.contains("__fish_seen_subcommand_from share'"));
+    }
+}
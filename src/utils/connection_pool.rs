@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::models::ServerConfig;
+use crate::utils::ssh::SshClient;
+
+/// 一次命令内按服务器 ID 复用的 SFTP 连接，同一台服务器只认证一次
+#[derive(Default)]
+pub struct SftpPool {
+    clients: HashMap<String, (SshClient, ssh2::Sftp)>,
+}
+
+impl SftpPool {
+    /// 确保已连接到服务器
+    pub fn connect(&mut self, server: &ServerConfig) -> Result<()> {
+        if !self.clients.contains_key(&server.id) {
+            let client = SshClient::connect(server)
+                .with_context(|| format!("无法连接到服务器: {}", server.name))?;
+            let sftp = client.sftp()?;
+            self.clients.insert(server.id.clone(), (client, sftp));
+        }
+        Ok(())
+    }
+
+    /// 已建立的 SFTP 连接，调用前需先 `connect`
+    pub fn get(&self, server: &ServerConfig) -> &ssh2::Sftp {
+        &self.clients[&server.id].1
+    }
+
+    /// 将内容写入远程文件
+    pub fn write_file(&mut self, server: &ServerConfig, remote_path: &Path, content: &[u8], mode: i32) -> Result<()> {
+        self.connect(server)?;
+        let mut file = self
+            .get(server)
+            .open_mode(
+                remote_path,
+                ssh2::OpenFlags::WRITE | ssh2::OpenFlags::CREATE | ssh2::OpenFlags::TRUNCATE,
+                mode,
+                ssh2::OpenType::File,
+            )
+            .with_context(|| format!("无法创建远程文件: {}", remote_path.display()))?;
+        file.write_all(content)
+            .with_context(|| format!("写入远程文件失败: {}", remote_path.display()))?;
+        Ok(())
+    }
+}
+
+/// 系统 ssh 复用连接（ControlMaster）的套接字路径，会话中多个窗口连同一台服务器时只认证一次
+pub fn control_path() -> Result<PathBuf> {
+    let socket_dir = crate::config::get_state_dir()?.join("cm");
+    std::fs::create_dir_all(&socket_dir)
+        .with_context(|| format!("无法创建目录: {}", socket_dir.display()))?;
+
+    // %C 是连接参数的哈希，长度固定，避免套接字路径超过系统上限
+    Ok(socket_dir.join("%C"))
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::process::Command;
+
+use crate::models::{AuthType, ConnectionType, ServerConfig};
+use crate::utils::raw_terminal::run_raw_session;
+use crate::utils::telnet::connect_telnet;
+
+/// 连接非 SSH 类型的服务器（串口控制台、IPMI SOL、telnet），返回退出码
+pub fn connect_console(server: &ServerConfig) -> Result<i32> {
+    match &server.connection_type {
+        ConnectionType::Serial { baud } => connect_serial(&server.host, *baud),
+        ConnectionType::Ipmi => connect_ipmi_sol(server),
+        ConnectionType::Telnet => connect_telnet(&server.host, server.port),
+        ConnectionType::Ssh => Err(anyhow::anyhow!("{} 是 SSH 服务器，请使用 SSH 连接", server.name)),
+    }
+}
+
+/// 打开串口设备。内置实现支持常见波特率，其它波特率交给 screen 处理
+fn connect_serial(device: &str, baud: u32) -> Result<i32> {
+    let speed = match baud_constant(baud) {
+        Some(speed) => speed,
+        None => {
+            let screen = which::which("screen")
+                .map_err(|_| anyhow::anyhow!("内置串口支持不支持波特率 {}，且未找到 screen 命令", baud))?;
+            let status = Command::new(screen)
+                .arg(device)
+                .arg(baud.to_string())
+                .status()
+                .with_context(|| "无法启动 screen")?;
+            return Ok(status.code().unwrap_or(1));
+        }
+    };
+
+    let mut port = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(device)
+        .with_context(|| format!("无法打开串口设备: {} (是否有权限？可能需要加入 dialout 组)", device))?;
+
+    let fd = port.as_raw_fd();
+    let mut tio = termios::Termios::from_fd(fd)
+        .with_context(|| format!("{} 不是串口设备", device))?;
+    termios::cfmakeraw(&mut tio);
+    termios::cfsetspeed(&mut tio, speed)?;
+    tio.c_cflag |= termios::CLOCAL | termios::CREAD;
+    termios::tcsetattr(fd, termios::TCSANOW, &tio)
+        .with_context(|| format!("无法设置串口参数: {}", device))?;
+
+    println!("已连接串口 {} ({} baud)，按 Ctrl+] 退出", device, baud);
+    run_raw_session(&mut port)?;
+    println!("\r\n串口会话已结束");
+
+    Ok(0)
+}
+
+fn baud_constant(baud: u32) -> Option<termios::speed_t> {
+    let speed = match baud {
+        1200 => termios::B1200,
+        2400 => termios::B2400,
+        4800 => termios::B4800,
+        9600 => termios::B9600,
+        19200 => termios::B19200,
+        38400 => termios::B38400,
+        57600 => termios::os::target::B57600,
+        115200 => termios::os::target::B115200,
+        230400 => termios::os::target::B230400,
+        _ => return None,
+    };
+    Some(speed)
+}
+
+/// 通过 ipmitool 打开 Serial-over-LAN 控制台（iDRAC/iLO/通用 BMC）。
+///
+/// 密码通过 IPMI_PASSWORD 环境变量传给 ipmitool，不会出现在进程列表中。
+fn connect_ipmi_sol(server: &ServerConfig) -> Result<i32> {
+    let ipmitool = which::which("ipmitool")
+        .map_err(|_| anyhow::anyhow!("未找到 ipmitool 命令，请先安装 ipmitool"))?;
+
+    let password = match &server.auth_type {
+        AuthType::Password(pwd) => Some(pwd.clone()),
+        _ => server.password.clone(),
+    };
+
+    let mut cmd = Command::new(ipmitool);
+    cmd.args(["-I", "lanplus", "-H", &server.host, "-p", &server.port.to_string(), "-U", &server.username]);
+    if let Some(password) = password {
+        cmd.arg("-E").env("IPMI_PASSWORD", password);
+    }
+    cmd.args(["sol", "activate"]);
+
+    println!("正在打开 {} 的 SOL 控制台，输入 ~. 退出", server.host);
+    let status = cmd.status().with_context(|| "无法启动 ipmitool")?;
+
+    Ok(status.code().unwrap_or(1))
+}
@@ -0,0 +1,283 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::models::ServerConfig;
+use crate::utils::connection_pool::SftpPool;
+use crate::utils::deploy::sftp_mkdir_p;
+
+/// cp 的一端：本地路径或 `服务器:路径`
+#[derive(Debug, Clone)]
+pub enum Endpoint {
+    Local(PathBuf),
+    Remote { server: Box<ServerConfig>, path: PathBuf },
+}
+
+impl Endpoint {
+    /// 解析 `name:/path` 形式的参数。冒号前的部分能在数据库中找到对应服务器时才视为远程，
+    /// 否则按本地路径处理（兼容文件名中含冒号的情况）
+    pub fn parse<F>(spec: &str, lookup: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<ServerConfig>,
+    {
+        if let Some((name, path)) = spec.split_once(':') {
+            if !name.is_empty() && !name.contains('/') {
+                if let Some(server) = lookup(name) {
+                    // `name:` 表示远程用户主目录
+                    let path = if path.is_empty() { "." } else { path };
+                    return Ok(Endpoint::Remote { server: Box::new(server), path: PathBuf::from(path) });
+                }
+            }
+        }
+        Ok(Endpoint::Local(PathBuf::from(spec)))
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Endpoint::Local(path) => path,
+            Endpoint::Remote { path, .. } => path,
+        }
+    }
+
+    fn with_path(&self, path: PathBuf) -> Endpoint {
+        match self {
+            Endpoint::Local(_) => Endpoint::Local(path),
+            Endpoint::Remote { server, .. } => Endpoint::Remote { server: server.clone(), path },
+        }
+    }
+
+    fn join(&self, rel: &Path) -> Endpoint {
+        self.with_path(self.path().join(rel))
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Endpoint::Local(path) => path.display().to_string(),
+            Endpoint::Remote { server, path } => format!("{}:{}", server.name, path.display()),
+        }
+    }
+}
+
+/// 多个服务器的 SFTP 连接，按服务器 ID 复用
+#[derive(Default)]
+struct Connections {
+    pool: SftpPool,
+}
+
+impl Connections {
+    fn ensure(&mut self, endpoint: &Endpoint) -> Result<()> {
+        if let Endpoint::Remote { server, .. } = endpoint {
+            self.pool.connect(server)?;
+        }
+        Ok(())
+    }
+
+    fn sftp(&self, server: &ServerConfig) -> &ssh2::Sftp {
+        self.pool.get(server)
+    }
+
+    fn is_dir(&self, endpoint: &Endpoint) -> bool {
+        match endpoint {
+            Endpoint::Local(path) => path.is_dir(),
+            Endpoint::Remote { server, path } => self
+                .sftp(server)
+                .stat(path)
+                .map(|stat| stat.is_dir())
+                .unwrap_or(false),
+        }
+    }
+
+    fn mkdir_p(&self, endpoint: &Endpoint) -> Result<()> {
+        match endpoint {
+            Endpoint::Local(path) => std::fs::create_dir_all(path)
+                .with_context(|| format!("无法创建目录: {}", path.display())),
+            Endpoint::Remote { server, path } => sftp_mkdir_p(self.sftp(server), path),
+        }
+    }
+
+    /// 递归列出目录下的所有条目（相对路径, 是否目录），目录排在其内容之前
+    fn walk(&self, endpoint: &Endpoint) -> Result<Vec<(PathBuf, bool)>> {
+        let mut entries = Vec::new();
+        let mut stack = vec![PathBuf::new()];
+
+        while let Some(rel_dir) = stack.pop() {
+            let dir = endpoint.path().join(&rel_dir);
+            let children: Vec<(String, bool)> = match endpoint {
+                Endpoint::Local(_) => std::fs::read_dir(&dir)
+                    .with_context(|| format!("无法读取目录: {}", dir.display()))?
+                    .filter_map(|e| e.ok())
+                    .map(|e| {
+                        let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                        (e.file_name().to_string_lossy().to_string(), is_dir)
+                    })
+                    .collect(),
+                Endpoint::Remote { server, .. } => self
+                    .sftp(server)
+                    .readdir(&dir)
+                    .with_context(|| format!("无法读取远程目录: {}", dir.display()))?
+                    .into_iter()
+                    .filter_map(|(p, stat)| Some((p.file_name()?.to_string_lossy().to_string(), stat.is_dir())))
+                    .filter(|(name, _)| name != "." && name != "..")
+                    .collect(),
+            };
+
+            for (name, is_dir) in children {
+                let rel = rel_dir.join(name);
+                if is_dir {
+                    stack.push(rel.clone());
+                }
+                entries.push((rel, is_dir));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// 源和目标是否为同一个文件：本地比较规范化后的路径，同一服务器上比较 realpath
+    fn same_file(&self, src: &Endpoint, dst: &Endpoint) -> bool {
+        match (src, dst) {
+            (Endpoint::Local(a), Endpoint::Local(b)) => match (a.canonicalize(), b.canonicalize()) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            },
+            (Endpoint::Remote { server: sa, path: a }, Endpoint::Remote { server: sb, path: b }) if sa.id == sb.id => {
+                let sftp = self.sftp(sa);
+                match (sftp.realpath(a), sftp.realpath(b)) {
+                    (Ok(a), Ok(b)) => a == b,
+                    _ => false,
+                }
+            },
+            _ => false,
+        }
+    }
+
+    fn copy_file(&self, src: &Endpoint, dst: &Endpoint) -> Result<u64> {
+        // 先打开目标会把源文件截断
+        if self.same_file(src, dst) {
+            return Err(anyhow::anyhow!("{} 和 {} 是同一个文件", src.label(), dst.label()));
+        }
+        let mut reader: Box<dyn Read> = match src {
+            Endpoint::Local(path) => Box::new(
+                std::fs::File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?,
+            ),
+            Endpoint::Remote { server, path } => Box::new(
+                self.sftp(server)
+                    .open(path)
+                    .with_context(|| format!("无法打开远程文件: {}", src.label()))?,
+            ),
+        };
+        let mut writer: Box<dyn Write> = match dst {
+            Endpoint::Local(path) => Box::new(
+                std::fs::File::create(path).with_context(|| format!("无法创建文件: {}", path.display()))?,
+            ),
+            Endpoint::Remote { server, path } => Box::new(
+                self.sftp(server)
+                    .create(path)
+                    .with_context(|| format!("无法创建远程文件: {}", dst.label()))?,
+            ),
+        };
+
+        // 远程到远程时数据经由本机中转，两台服务器之间不需要互通
+        let bytes = std::io::copy(&mut reader, &mut writer)
+            .with_context(|| format!("复制失败: {} -> {}", src.label(), dst.label()))?;
+        writer.flush()?;
+        Ok(bytes)
+    }
+}
+
+/// 计算源文件在目标中的位置：目标是目录（或以 / 结尾、或有多个源）时放到目录下
+pub fn resolve_target(src: &Path, dest: &Path, dest_is_dir: bool) -> PathBuf {
+    let dest_is_dir = dest_is_dir || dest.to_string_lossy().ends_with('/');
+    match src.file_name() {
+        Some(name) if dest_is_dir => dest.join(name),
+        _ => dest.to_path_buf(),
+    }
+}
+
+/// 将若干源复制到目标，源和目标都可以是本地路径或任意服务器上的路径
+pub fn copy(sources: &[Endpoint], dest: &Endpoint, recursive: bool) -> Result<()> {
+    let mut conns = Connections::default();
+    for endpoint in sources.iter().chain(std::iter::once(dest)) {
+        conns.ensure(endpoint)?;
+    }
+
+    let dest_is_dir = conns.is_dir(dest);
+    if sources.len() > 1 && !dest_is_dir {
+        conns.mkdir_p(dest)?;
+    }
+    let dest_is_dir = dest_is_dir || sources.len() > 1;
+
+    for src in sources {
+        let target = dest.with_path(resolve_target(src.path(), dest.path(), dest_is_dir));
+
+        if conns.is_dir(src) {
+            if !recursive {
+                return Err(anyhow::anyhow!("{} 是目录，请使用 -r 递归复制", src.label()));
+            }
+            conns.mkdir_p(&target)?;
+            for (rel, is_dir) in conns.walk(src)? {
+                if is_dir {
+                    conns.mkdir_p(&target.join(&rel))?;
+                } else {
+                    conns.copy_file(&src.join(&rel), &target.join(&rel))?;
+                }
+            }
+            println!("{} {} -> {}", "✓".bright_green(), src.label(), target.label());
+        } else {
+            let bytes = conns.copy_file(src, &target)?;
+            println!("{} {} -> {} ({} 字节)", "✓".bright_green(), src.label(), target.label(), bytes);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    fn lookup(name: &str) -> Option<ServerConfig> {
+        (name == "web").then(|| {
+            ServerConfig::new(
+                "1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(),
+                AuthType::Agent, None, None, None,
+            )
+        })
+    }
+
+    #[test]
+    fn parses_endpoints() {
+        match Endpoint::parse("web:/var/log/app.log", lookup).unwrap() {
+            Endpoint::Remote { server, path } => {
+                assert_eq!(server.name, "web");
+                assert_eq!(path, PathBuf::from("/var/log/app.log"));
+            }
+            other => panic!("应解析为远程: {:?}", other),
+        }
+        assert!(matches!(Endpoint::parse("web:", lookup).unwrap(), Endpoint::Remote { .. }));
+        // 未知服务器名、相对路径中的冒号都按本地处理
+        assert!(matches!(Endpoint::parse("db:/tmp", lookup).unwrap(), Endpoint::Local(_)));
+        assert!(matches!(Endpoint::parse("./a:b", lookup).unwrap(), Endpoint::Local(_)));
+    }
+
+    #[test]
+    fn refuses_to_copy_a_file_onto_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "data").unwrap();
+
+        let sources = [Endpoint::Local(file.clone())];
+        assert!(copy(&sources, &Endpoint::Local(dir.path().to_path_buf()), false).is_err());
+        assert!(copy(&sources, &Endpoint::Local(dir.path().join(".").join("a.txt")), false).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "data");
+    }
+
+    #[test]
+    fn resolves_target_path() {
+        assert_eq!(resolve_target(Path::new("/a/x.txt"), Path::new("/b"), true), PathBuf::from("/b/x.txt"));
+        assert_eq!(resolve_target(Path::new("/a/x.txt"), Path::new("/b/"), false), PathBuf::from("/b/x.txt"));
+        assert_eq!(resolve_target(Path::new("/a/x.txt"), Path::new("/b/y.txt"), false), PathBuf::from("/b/y.txt"));
+    }
+}
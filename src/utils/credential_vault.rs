@@ -0,0 +1,308 @@
+//! 凭据库：用主密码加密数据库中的密码（`rssh vault init`）。
+//!
+//! 主密码经 PBKDF2-HMAC-SHA256 派生出 AES-256-GCM 密钥，密码列保存为 `enc:v1:<base64(nonce||密文)>`，
+//! 连接时由 `enc` 提供者透明解密。`rssh vault unlock` 把派生出的密钥缓存到 `$XDG_RUNTIME_DIR/rssh`
+//! （通常为 tmpfs，没有时使用当前用户的临时目录，权限 0600），`rssh vault lock` 或到期后第一次读取时删除；
+//! 未解锁时在终端上询问主密码，
+//! 也可以在 `config.toml` 中用 `vault_key_file` 指定保存主密码的文件。
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::utils::secret::is_secret_reference;
+use crate::utils::secure_temp;
+
+/// 加密值的前缀，同时也是 `enc` 提供者的引用前缀
+pub const PREFIX: &str = "enc:";
+
+/// 新建凭据库时 PBKDF2 的迭代次数
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+
+/// 校验主密码用的明文
+const CHECK_PLAINTEXT: &str = "rssh-vault";
+
+const NONCE_LEN: usize = 12;
+
+type Key = [u8; 32];
+
+/// 凭据库参数，保存在数据库中，不包含任何密钥
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VaultMeta {
+    /// base64 编码的随机盐
+    pub salt: String,
+    pub iterations: u32,
+    /// 用派生密钥加密的固定内容，解锁时据此判断主密码是否正确
+    pub check: String,
+}
+
+struct VaultState {
+    meta: VaultMeta,
+    key_file: Option<PathBuf>,
+    /// 本次运行中已经得到的密钥
+    key: Mutex<Option<Key>>,
+}
+
+static VAULT: OnceLock<VaultState> = OnceLock::new();
+
+/// 启用凭据库，只在启动时（或 `rssh vault init` 创建后）调用一次
+pub fn configure(meta: VaultMeta, key_file: Option<PathBuf>) {
+    let _ = VAULT.set(VaultState { meta, key_file, key: Mutex::new(None) });
+}
+
+/// 是否已经启用凭据库
+pub fn is_enabled() -> bool {
+    VAULT.get().is_some()
+}
+
+/// 用主密码新建凭据库参数
+pub fn create(master: &str, iterations: u32) -> Result<VaultMeta> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let key = derive_key(master, &salt, iterations);
+    Ok(VaultMeta {
+        salt: STANDARD.encode(salt),
+        iterations,
+        check: encrypt_with(&key, CHECK_PLAINTEXT)?,
+    })
+}
+
+/// 校验主密码并把密钥缓存 `ttl`，期间连接不再询问主密码
+pub fn unlock(master: &str, ttl: Duration) -> Result<()> {
+    let state = state()?;
+    let key = verify(&state.meta, master)?;
+    let expires = chrono::Local::now().timestamp() + ttl.as_secs() as i64;
+    let cache = format!("{}\n{}\n{}\n", expires, state.meta.salt, STANDARD.encode(key));
+    secure_temp::write_runtime(CACHE_FILE, cache, 0o600)?;
+    *state.key.lock().unwrap() = Some(key);
+    Ok(())
+}
+
+/// 解锁时长：`30m`、`8h`、`1d`、`90s`，不带单位时为分钟
+pub fn parse_duration(spec: &str) -> Result<Duration> {
+    let invalid = || anyhow::anyhow!("无法识别的时长: {}，示例: 30m、8h、1d", spec);
+    let spec = spec.trim();
+    let (amount, seconds) = match spec.char_indices().last() {
+        Some((at, unit)) if unit.is_ascii_alphabetic() => {
+            let seconds = match unit {
+                's' => 1,
+                'm' => 60,
+                'h' => 3600,
+                'd' => 86400,
+                _ => return Err(invalid()),
+            };
+            (&spec[..at], seconds)
+        },
+        _ => (spec, 60),
+    };
+    match amount.parse::<u64>() {
+        Ok(amount) if amount > 0 => Ok(Duration::from_secs(amount * seconds)),
+        _ => Err(invalid()),
+    }
+}
+
+/// 删除缓存的密钥，返回之前是否处于解锁状态
+pub fn lock() -> Result<bool> {
+    let unlocked = unlocked_until().is_some();
+    // 旧版本把密钥缓存在临时目录中
+    for dir in [secure_temp::runtime_dir()?, secure_temp::user_dir()?] {
+        let path = dir.join(CACHE_FILE);
+        if path.exists() {
+            std::fs::remove_file(&path).with_context(|| format!("无法删除 {}", path.display()))?;
+        }
+    }
+    if let Some(state) = VAULT.get() {
+        *state.key.lock().unwrap() = None;
+    }
+    Ok(unlocked)
+}
+
+/// 缓存的密钥到期时间，未解锁时为 None
+pub fn unlocked_until() -> Option<chrono::DateTime<chrono::Local>> {
+    let state = VAULT.get()?;
+    let (expires, _) = cached_key(&state.meta)?;
+    chrono::DateTime::from_timestamp(expires, 0).map(|time| time.with_timezone(&chrono::Local))
+}
+
+/// 值是否为凭据库加密的内容
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// 凭据库启用时加密明文，密钥引用和已加密的值原样返回；`literal` 为真时以引用前缀开头的值也加密
+pub fn seal(value: &str, literal: bool) -> Result<String> {
+    if !is_enabled() || value.is_empty() || is_encrypted(value) || (!literal && is_secret_reference(value)) {
+        return Ok(value.to_string());
+    }
+    encrypt_with(&key()?, value)
+}
+
+/// 解密 `enc:` 之后的部分
+pub fn decrypt(reference: &str) -> Result<String> {
+    if !is_enabled() {
+        return Err(anyhow::anyhow!("密码已加密，但凭据库没有初始化（数据库可能来自其它机器）"));
+    }
+    decrypt_with(&key()?, reference)
+}
+
+const CACHE_FILE: &str = "vault-key";
+
+fn state() -> Result<&'static VaultState> {
+    VAULT.get().ok_or_else(|| anyhow::anyhow!("凭据库尚未初始化，请先执行 rssh vault init"))
+}
+
+/// 依次使用本次运行的密钥、`rssh vault unlock` 缓存的密钥、`vault_key_file`，最后在终端上询问主密码
+fn key() -> Result<Key> {
+    let state = state()?;
+    let mut current = state.key.lock().unwrap();
+    if let Some(key) = *current {
+        return Ok(key);
+    }
+
+    let key = if let Some((_, key)) = cached_key(&state.meta) {
+        key
+    } else if let Some(path) = &state.key_file {
+        let master = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取 vault_key_file: {}", path.display()))?;
+        verify(&state.meta, master.trim_end_matches(['\n', '\r']))
+            .with_context(|| format!("vault_key_file 中的主密码不正确: {}", path.display()))?
+    } else if std::io::stdin().is_terminal() {
+        let master = rpassword::prompt_password("凭据库主密码: ")?;
+        verify(&state.meta, &master)?
+    } else {
+        return Err(anyhow::anyhow!("凭据库已锁定，请先执行 rssh vault unlock"));
+    };
+    *current = Some(key);
+    Ok(key)
+}
+
+/// 读取未过期且属于当前凭据库的缓存密钥
+fn cached_key(meta: &VaultMeta) -> Option<(i64, Key)> {
+    read_cached_key(&secure_temp::runtime_dir().ok()?.join(CACHE_FILE), meta)
+}
+
+/// 缓存已过期、属于其它凭据库或已损坏时删除文件
+fn read_cached_key(path: &Path, meta: &VaultMeta) -> Option<(i64, Key)> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let parse = || -> Option<(i64, Key)> {
+        let mut lines = content.lines();
+        let expires: i64 = lines.next()?.parse().ok()?;
+        if expires <= chrono::Local::now().timestamp() || lines.next()? != meta.salt {
+            return None;
+        }
+        let key = STANDARD.decode(lines.next()?).ok()?.try_into().ok()?;
+        Some((expires, key))
+    };
+    let cached = parse();
+    if cached.is_none() {
+        let _ = std::fs::remove_file(path);
+    }
+    cached
+}
+
+fn verify(meta: &VaultMeta, master: &str) -> Result<Key> {
+    let salt = STANDARD.decode(&meta.salt).context("凭据库参数已损坏")?;
+    let key = derive_key(master, &salt, meta.iterations);
+    match decrypt_with(&key, meta.check.strip_prefix(PREFIX).unwrap_or(&meta.check)) {
+        Ok(check) if check == CHECK_PLAINTEXT => Ok(key),
+        _ => Err(anyhow::anyhow!("主密码不正确")),
+    }
+}
+
+fn derive_key(master: &str, salt: &[u8], iterations: u32) -> Key {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(master.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+fn encrypt_with(key: &Key, plaintext: &str) -> Result<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| anyhow::anyhow!("加密失败"))?;
+    let mut data = nonce.to_vec();
+    data.extend_from_slice(&ciphertext);
+    Ok(format!("{}v1:{}", PREFIX, STANDARD.encode(data)))
+}
+
+/// `reference` 为 `v1:<base64>`
+fn decrypt_with(key: &Key, reference: &str) -> Result<String> {
+    let encoded = reference
+        .strip_prefix("v1:")
+        .ok_or_else(|| anyhow::anyhow!("不支持的加密格式: {}", reference.split(':').next().unwrap_or_default()))?;
+    let data = STANDARD.decode(encoded.trim()).context("加密内容不是有效的 base64")?;
+    if data.len() <= NONCE_LEN {
+        return Err(anyhow::anyhow!("加密内容已损坏"));
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("无法解密，主密码不正确或内容已损坏"))?;
+    String::from_utf8(plaintext).context("解密结果不是有效的 UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_rejects_wrong_master_password() {
+        let meta = create("correct horse", 1_000).unwrap();
+        let key = verify(&meta, "correct horse").unwrap();
+        assert!(verify(&meta, "wrong").is_err());
+
+        let sealed = encrypt_with(&key, "hunter2").unwrap();
+        assert!(is_encrypted(&sealed) && !sealed.contains("hunter2"));
+        // 每次加密使用新的 nonce
+        assert_ne!(sealed, encrypt_with(&key, "hunter2").unwrap());
+        let reference = sealed.strip_prefix(PREFIX).unwrap();
+        assert_eq!(decrypt_with(&key, reference).unwrap(), "hunter2");
+
+        let other = derive_key("wrong", &STANDARD.decode(&meta.salt).unwrap(), meta.iterations);
+        assert!(decrypt_with(&other, reference).is_err());
+    }
+
+    #[test]
+    fn removes_expired_or_foreign_key_caches() {
+        let meta = create("correct horse", 1_000).unwrap();
+        let key = verify(&meta, "correct horse").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CACHE_FILE);
+        let now = chrono::Local::now().timestamp();
+        let write = |expires: i64, salt: &str| std::fs::write(&path, format!("{}\n{}\n{}\n", expires, salt, STANDARD.encode(key))).unwrap();
+
+        write(now + 60, &meta.salt);
+        assert_eq!(read_cached_key(&path, &meta), Some((now + 60, key)));
+        assert!(path.exists());
+
+        write(now - 1, &meta.salt);
+        assert_eq!(read_cached_key(&path, &meta), None);
+        assert!(!path.exists());
+
+        write(now + 60, "other-vault");
+        assert_eq!(read_cached_key(&path, &meta), None);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn parses_unlock_durations() {
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_duration("8h").unwrap(), Duration::from_secs(8 * 3600));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45 * 60));
+        for invalid in ["", "0m", "h", "2w", "-5m", "1.5h"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
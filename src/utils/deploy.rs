@@ -0,0 +1,300 @@
+use anyhow::{Context, Result};
+use colored::*;
+use notify::{RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::models::{AuthType, ServerConfig};
+use crate::utils::file_transfer::{rsync_command, RsyncOptions};
+use crate::utils::ssh::SshClient;
+
+/// 默认排除的文件：版本库目录和编辑器临时文件（4913 是 vim 保存前创建的探测文件）
+pub const DEFAULT_EXCLUDES: &[&str] = &[".git", "*.swp", "*.swx", "*~", ".DS_Store", "4913"];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum DeployMethod {
+    /// 本地有 rsync 且不是密码认证时使用 rsync，否则使用 SFTP
+    Auto,
+    Rsync,
+    Sftp,
+}
+
+pub struct DeployOptions {
+    pub method: DeployMethod,
+    /// 监视本地目录，变化后自动同步
+    pub watch: bool,
+    /// 最后一次变化后等待多久再同步
+    pub debounce: Duration,
+    /// 排除规则：不含 `/` 时匹配任意一级文件名，含 `/` 时匹配相对路径；支持 `*` 和 `?`
+    pub excludes: Vec<String>,
+    /// 删除远程目录中本地没有的文件：rsync 每次同步都删除，SFTP 删除监视期间本地删除的文件和目录
+    pub delete: bool,
+}
+
+/// 将本地目录同步到远程目录；`watch` 时持续监视并增量同步，直到 Ctrl+C
+pub fn deploy(server: &ServerConfig, local_dir: &Path, remote_dir: &str, options: &DeployOptions) -> Result<()> {
+    if !local_dir.is_dir() {
+        return Err(anyhow::anyhow!("本地目录不存在: {}", local_dir.display()));
+    }
+
+    let use_rsync = match options.method {
+        DeployMethod::Rsync => true,
+        DeployMethod::Sftp => false,
+        DeployMethod::Auto => {
+            which::which("rsync").is_ok() && !matches!(server.auth_type, AuthType::Password(_))
+        }
+    };
+
+    if use_rsync {
+        rsync(server, local_dir, remote_dir, options)?;
+    } else {
+        let client = SshClient::connect(server)?;
+        let sftp = client.sftp()?;
+        let files = collect_files(local_dir, &options.excludes)?;
+        sftp_sync(&sftp, local_dir, remote_dir, &files, options.delete)?;
+    }
+    println!("{} 初次同步完成", "✓".bright_green());
+
+    if !options.watch {
+        return Ok(());
+    }
+
+    // 监视规范化后的路径，事件中的路径才能与之对应
+    let local_root = local_dir.canonicalize().unwrap_or_else(|_| local_dir.to_path_buf());
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            for path in event.paths {
+                let _ = tx.send(path);
+            }
+        }
+    })
+    .with_context(|| "无法创建文件监视器")?;
+    watcher
+        .watch(&local_root, RecursiveMode::Recursive)
+        .with_context(|| format!("无法监视目录: {}", local_dir.display()))?;
+
+    println!("正在监视 {}，按 Ctrl+C 停止", local_dir.display());
+
+    // SFTP 模式复用同一个连接
+    let client = if use_rsync { None } else { Some(SshClient::connect(server)?) };
+    let sftp = match &client {
+        Some(client) => Some(client.sftp()?),
+        None => None,
+    };
+
+    loop {
+        let first = rx.recv().with_context(|| "文件监视器已停止")?;
+        let mut changed = BTreeSet::new();
+        changed.insert(first);
+
+        // 防抖：直到 debounce 时间内没有新的事件
+        while let Ok(path) = rx.recv_timeout(options.debounce) {
+            changed.insert(path);
+        }
+
+        let changed: Vec<PathBuf> = changed
+            .into_iter()
+            .filter_map(|p| p.strip_prefix(&local_root).ok().map(Path::to_path_buf))
+            .filter(|rel| !rel.as_os_str().is_empty() && !is_excluded(rel, &options.excludes))
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        let result = match &sftp {
+            Some(sftp) => sftp_sync(sftp, local_dir, remote_dir, &changed, options.delete),
+            None => rsync(server, local_dir, remote_dir, options),
+        };
+        match result {
+            Ok(()) => println!(
+                "[{}] 已同步 {} 个变更",
+                chrono::Local::now().format("%H:%M:%S"),
+                changed.len().to_string().bright_green()
+            ),
+            // 监视模式下同步失败不退出，等下一次保存重试
+            Err(e) => eprintln!("{} 同步失败: {:#}", "✗".bright_red(), e),
+        }
+    }
+}
+
+/// 判断相对路径是否被排除
+pub fn is_excluded(rel: &Path, excludes: &[String]) -> bool {
+    let rel_str = rel.to_string_lossy();
+    excludes.iter().any(|pattern| {
+        if pattern.contains('/') {
+            wildcard_match(pattern.trim_matches('/'), &rel_str)
+        } else {
+            rel.components()
+                .any(|c| wildcard_match(pattern, &c.as_os_str().to_string_lossy()))
+        }
+    })
+}
+
+/// 简单通配符匹配，`*` 匹配任意字符串，`?` 匹配单个字符
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// 列出本地目录下所有未被排除的文件（相对路径）
+fn collect_files(local_dir: &Path, excludes: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut stack = vec![PathBuf::new()];
+
+    while let Some(rel_dir) = stack.pop() {
+        let dir = local_dir.join(&rel_dir);
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("无法读取目录: {}", dir.display()))? {
+            let entry = entry?;
+            let rel = rel_dir.join(entry.file_name());
+            if is_excluded(&rel, excludes) {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                stack.push(rel);
+            } else {
+                files.push(rel);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// 通过 SFTP 同步指定的相对路径：本地存在则上传，已删除且 `delete` 时删除远程的文件或目录
+fn sftp_sync(sftp: &ssh2::Sftp, local_dir: &Path, remote_dir: &str, files: &[PathBuf], delete: bool) -> Result<()> {
+    let remote_root = Path::new(remote_dir);
+
+    for rel in files {
+        let local_path = local_dir.join(rel);
+        let remote_path = remote_root.join(rel);
+
+        if local_path.is_dir() {
+            sftp_mkdir_p(sftp, &remote_path)?;
+            continue;
+        }
+
+        if !local_path.exists() {
+            // 远程已经不存在（例如所在目录刚被删除）时跳过
+            let Ok(stat) = sftp.lstat(&remote_path) else { continue };
+            if !delete {
+                continue;
+            }
+            if stat.is_dir() {
+                sftp_remove_dir_all(sftp, &remote_path)?;
+            } else {
+                sftp.unlink(&remote_path)
+                    .with_context(|| format!("无法删除远程文件: {}", remote_path.display()))?;
+            }
+            println!("  {} {}", "-".bright_red(), rel.display());
+            continue;
+        }
+
+        if let Some(parent) = remote_path.parent() {
+            sftp_mkdir_p(sftp, parent)?;
+        }
+
+        let mut local = std::fs::File::open(&local_path)
+            .with_context(|| format!("无法打开本地文件: {}", local_path.display()))?;
+        let mut remote = sftp
+            .create(&remote_path)
+            .with_context(|| format!("无法创建远程文件: {}", remote_path.display()))?;
+        std::io::copy(&mut local, &mut remote)
+            .with_context(|| format!("上传失败: {}", rel.display()))?;
+        println!("  {} {}", "+".bright_green(), rel.display());
+    }
+
+    Ok(())
+}
+
+/// 在远程逐级创建目录（相当于 mkdir -p）
+pub fn sftp_mkdir_p(sftp: &ssh2::Sftp, dir: &Path) -> Result<()> {
+    let mut missing = Vec::new();
+    for ancestor in dir.ancestors() {
+        if ancestor.as_os_str().is_empty() || sftp.stat(ancestor).is_ok() {
+            break;
+        }
+        missing.push(ancestor);
+    }
+
+    for path in missing.into_iter().rev() {
+        sftp.mkdir(path, 0o755)
+            .with_context(|| format!("无法创建远程目录: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// 递归删除远程目录（相当于 rm -r），符号链接只删除链接本身
+fn sftp_remove_dir_all(sftp: &ssh2::Sftp, dir: &Path) -> Result<()> {
+    let entries = sftp.readdir(dir).with_context(|| format!("无法读取远程目录: {}", dir.display()))?;
+    for (path, _) in entries {
+        match sftp.lstat(&path) {
+            Ok(stat) if stat.is_dir() => sftp_remove_dir_all(sftp, &path)?,
+            _ => sftp.unlink(&path).with_context(|| format!("无法删除远程文件: {}", path.display()))?,
+        }
+    }
+    sftp.rmdir(dir).with_context(|| format!("无法删除远程目录: {}", dir.display()))
+}
+
+/// 使用 rsync 增量同步整个目录
+fn rsync(server: &ServerConfig, local_dir: &Path, remote_dir: &str, options: &DeployOptions) -> Result<()> {
+    let mut cmd = rsync_command(server, &RsyncOptions { delete: options.delete, excludes: options.excludes.clone() })?;
+    // 结尾的 / 表示同步目录内容而不是目录本身
+    cmd.arg(format!("{}/", local_dir.display()))
+        .arg(format!("{}@{}:{}/", server.username, server.host, remote_dir.trim_end_matches('/')));
+
+    let status = cmd.status().with_context(|| "无法执行rsync命令")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("rsync 执行失败，退出码: {:?}", status.code()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excludes(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("*.swp", ".index.html.swp"));
+        assert!(wildcard_match("a?c*", "abcdef"));
+        assert!(!wildcard_match("*.js", "app.jsx"));
+    }
+
+    #[test]
+    fn excludes_by_component_or_path() {
+        let rules = excludes(&[".git", "*.map", "assets/tmp/*"]);
+        assert!(is_excluded(Path::new(".git/HEAD"), &rules));
+        assert!(is_excluded(Path::new("js/app.js.map"), &rules));
+        assert!(is_excluded(Path::new("assets/tmp/x.png"), &rules));
+        assert!(!is_excluded(Path::new("assets/img/x.png"), &rules));
+        assert!(!is_excluded(Path::new("index.html"), &rules));
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use std::process::{Command, Stdio};
+
+use crate::utils::session_escape::Forward;
+use crate::utils::tunnel::local_host;
+
+/// 经隧道访问的远程桌面协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DesktopProtocol {
+    Vnc,
+    Rdp,
+}
+
+impl DesktopProtocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            DesktopProtocol::Vnc => "VNC",
+            DesktopProtocol::Rdp => "RDP",
+        }
+    }
+
+    /// `config.toml` 中对应的查看器配置项
+    pub fn setting(self) -> &'static str {
+        match self {
+            DesktopProtocol::Vnc => "vnc_viewer",
+            DesktopProtocol::Rdp => "rdp_viewer",
+        }
+    }
+
+    /// 当前平台默认的查看器命令模板
+    pub fn default_viewer(self) -> &'static str {
+        match self {
+            DesktopProtocol::Vnc if cfg!(target_os = "macos") => "open vnc://{host}:{port}",
+            // TigerVNC / TightVNC 用 `主机::端口` 表示端口而不是显示号
+            DesktopProtocol::Vnc => "vncviewer {host}::{port}",
+            DesktopProtocol::Rdp if cfg!(target_os = "macos") => "open 'rdp://full%20address=s:{host}:{port}'",
+            DesktopProtocol::Rdp if cfg!(windows) => "mstsc /v:{host}:{port}",
+            DesktopProtocol::Rdp => "xfreerdp /v:{host}:{port}",
+        }
+    }
+}
+
+/// 把模板中的 `{host}` / `{port}` 替换为本地转发的地址
+pub fn viewer_command(template: &str, host: &str, port: u16) -> String {
+    template.replace("{host}", host).replace("{port}", &port.to_string())
+}
+
+/// 启动本地查看器连接到转发端口，不等待其退出
+pub fn launch_viewer(protocol: DesktopProtocol, template: &str, forward: &Forward) -> Result<()> {
+    let command_line = viewer_command(template, local_host(forward), forward.port);
+    let program = command_line.split_whitespace().next().unwrap_or_default();
+    if which::which(program).is_err() {
+        return Err(anyhow::anyhow!(
+            "未找到 {} 查看器 {}，请安装或在 config.toml 中设置 {}（也可用 --viewer 指定）",
+            protocol.name(), program, protocol.setting()
+        ));
+    }
+
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    command
+        .arg(&command_line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("无法启动查看器 {}: {}", command_line, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_viewer_template() {
+        assert_eq!(
+            viewer_command("vncviewer {host}::{port}", "localhost", 5901),
+            "vncviewer localhost::5901"
+        );
+        assert_eq!(
+            viewer_command("remmina -c rdp://{host}:{port}", "127.0.0.2", 13389),
+            "remmina -c rdp://127.0.0.2:13389"
+        );
+    }
+}
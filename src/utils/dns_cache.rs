@@ -0,0 +1,99 @@
+//! 本次运行内的域名解析缓存。
+//!
+//! 批量操作开始前用 [`resolve_all`] 并发解析所有目标主机，之后建立连接时 [`lookup`] 直接使用缓存的结果，
+//! 无法解析的主机可以在连接任何主机之前统一报告。解析失败的结果同样缓存，本次运行中不再重试。
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Mutex, OnceLock};
+
+/// 同时进行的解析数量
+const PARALLEL: usize = 32;
+
+/// 主机名 → 解析出的地址，失败时为错误信息
+type Cache = Mutex<HashMap<String, Result<Vec<IpAddr>, String>>>;
+
+static CACHE: OnceLock<Cache> = OnceLock::new();
+
+fn cache() -> &'static Cache {
+    CACHE.get_or_init(Default::default)
+}
+
+/// 解析主机名，IP 地址直接返回，其它主机名优先使用缓存
+pub fn lookup(host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+    let ips = match host.parse::<IpAddr>() {
+        Ok(ip) => Ok(vec![ip]),
+        Err(_) => {
+            let cached = cache().lock().unwrap().get(host).cloned();
+            cached.unwrap_or_else(|| resolve(host))
+        },
+    };
+    match ips {
+        Ok(ips) => Ok(ips.into_iter().map(|ip| SocketAddr::new(ip, port)).collect()),
+        Err(e) => Err(anyhow::anyhow!("无法解析 {}: {}", host, e)),
+    }
+}
+
+/// 并发解析所有主机名并缓存结果，返回无法解析的主机名和原因（按输入顺序，去重）
+pub fn resolve_all(hosts: &[&str]) -> Vec<(String, String)> {
+    let mut pending: Vec<&str> = Vec::new();
+    {
+        let cache = cache().lock().unwrap();
+        for host in hosts {
+            if host.parse::<IpAddr>().is_err() && !cache.contains_key(*host) && !pending.contains(host) {
+                pending.push(host);
+            }
+        }
+    }
+
+    for chunk in pending.chunks(PARALLEL) {
+        std::thread::scope(|scope| {
+            for host in chunk {
+                scope.spawn(move || resolve(host));
+            }
+        });
+    }
+
+    let cache = cache().lock().unwrap();
+    let mut failed: Vec<(String, String)> = Vec::new();
+    for host in hosts {
+        if let Some(Err(e)) = cache.get(*host) {
+            if !failed.iter().any(|(name, _)| name == host) {
+                failed.push((host.to_string(), e.clone()));
+            }
+        }
+    }
+    failed
+}
+
+/// 解析主机名并写入缓存
+fn resolve(host: &str) -> Result<Vec<IpAddr>, String> {
+    let result = match (host, 0).to_socket_addrs() {
+        Ok(addrs) => {
+            let ips: Vec<IpAddr> = addrs.map(|addr| addr.ip()).collect();
+            if ips.is_empty() { Err("没有可用的地址".to_string()) } else { Ok(ips) }
+        },
+        Err(e) => Err(e.to_string()),
+    };
+    cache().lock().unwrap().insert(host.to_string(), result.clone());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_unresolvable_hosts_once_and_caches_results() {
+        let failed = resolve_all(&["localhost", "127.0.0.1", "nohost.invalid", "nohost.invalid"]);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "nohost.invalid");
+
+        assert!(cache().lock().unwrap().get("localhost").is_some_and(|result| result.is_ok()));
+        assert!(!cache().lock().unwrap().contains_key("127.0.0.1"));
+        assert_eq!(lookup("127.0.0.1", 22).unwrap(), vec!["127.0.0.1:22".parse().unwrap()]);
+        assert!(lookup("localhost", 22).unwrap().iter().all(|addr| addr.port() == 22));
+        assert!(lookup("nohost.invalid", 22).unwrap_err().to_string().contains("nohost.invalid"));
+    }
+}
@@ -1,9 +1,20 @@
 use anyhow::{Context, Result};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use colored::*;
 
 use crate::models::ServerConfig;
+use crate::utils::cp::resolve_target;
+use crate::utils::deploy::sftp_mkdir_p;
+use crate::utils::interrupt;
+use crate::utils::ssh::SshClient;
+use crate::utils::multi_upload::format_size;
+use crate::utils::progress::TransferProgress;
+use crate::utils::remote_probe::{self, TransferMethod};
+use crate::config::get_ssh_config_dir;
+use crate::utils::ssh_command_builder::SshCommand;
 use crate::utils::ssh_config::expand_tilde;
 
 /// 使用SCP上传文件到远程服务器
@@ -53,8 +64,8 @@ pub fn upload_file<P: AsRef<Path>>(
         }
     }
     
-    // 禁用主机密钥检查
-    cmd.args(["-o", "StrictHostKeyChecking=no"]);
+    // 主机密钥策略、压缩、算法偏好等
+    cmd.args(server.ssh_options());
     
     // 添加本地和远程路径
     cmd.arg(local_path.as_os_str())
@@ -65,8 +76,8 @@ pub fn upload_file<P: AsRef<Path>>(
     println!("执行: {}", cmd_str.bright_blue());
     
     // 执行命令
-    let status = cmd.status()
-        .with_context(|| "无法执行SCP命令")?;
+    let status = interrupt::status(&mut cmd)
+        .inspect_err(|e| report_upload_interrupted(e, local_path, &remote_dest))?;
     
     if status.success() {
         println!("文件上传成功！");
@@ -117,8 +128,8 @@ pub fn download_file(
         }
     }
     
-    // 禁用主机密钥检查
-    cmd.args(["-o", "StrictHostKeyChecking=no"]);
+    // 主机密钥策略、压缩、算法偏好等
+    cmd.args(server.ssh_options());
     
     // 添加远程和本地路径
     cmd.arg(format!("{}@{}:{}", server.username, server.host, remote_path))
@@ -129,8 +140,8 @@ pub fn download_file(
     println!("执行: {}", cmd_str.bright_blue());
     
     // 执行命令
-    let status = cmd.status()
-        .with_context(|| "无法执行SCP命令")?;
+    let status = interrupt::status(&mut cmd)
+        .inspect_err(|e| report_download_interrupted(e, &local_dest))?;
     
     if status.success() {
         println!("文件下载成功！");
@@ -140,172 +151,217 @@ pub fn download_file(
     }
 }
 
-/// 使用SFTP上传文件到远程服务器（作为备选方案）
+/// 使用内置 SFTP 客户端上传文件或目录，不依赖外部 sftp 命令，支持密码、密钥和 SSH 代理认证。
+/// 目录递归上传，文件和目录保留本地的权限与修改时间
 pub fn upload_file_sftp<P: AsRef<Path>>(
     server: &ServerConfig,
     local_path: P,
     remote_path: Option<String>,
 ) -> Result<()> {
     let local_path = local_path.as_ref();
-    
-    // 确保本地文件存在
-    if !local_path.exists() {
-        return Err(anyhow::anyhow!("本地文件不存在: {}", local_path.display()));
+    let metadata = std::fs::metadata(local_path)
+        .with_context(|| format!("本地文件不存在: {}", local_path.display()))?;
+
+    let client = SshClient::connect(server)?;
+    let sftp = client.sftp()?;
+
+    // 未指定远程路径时上传到远程用户主目录
+    let remote_dest = PathBuf::from(remote_path.unwrap_or_else(|| "./".to_string()));
+    let remote_is_dir = sftp.stat(&remote_dest).is_ok_and(|stat| stat.is_dir());
+    if remote_dest.to_string_lossy().ends_with('/') && !remote_is_dir {
+        sftp_mkdir_p(&sftp, &remote_dest)?;
     }
-    
-    // 确定远程路径
-    let remote_dest = match remote_path {
-        Some(path) => path,
-        None => {
-            // 如果没有指定远程路径，使用本地文件名
-            let file_name = local_path.file_name()
-                .ok_or_else(|| anyhow::anyhow!("无法确定文件名"))?
-                .to_string_lossy();
-            format!("./{}", file_name)
-        }
+    let target = resolve_target(local_path, &remote_dest, remote_is_dir);
+    println!("通过内置SFTP上传 {} -> {}@{}:{}", local_path.display(), server.username, server.host, target.display());
+
+    let plan = if metadata.is_dir() {
+        plan_upload_dir(&sftp, local_path, &target)?
+    } else {
+        TransferPlan { files: vec![(local_path.to_path_buf(), target, metadata)], dirs: Vec::new() }
     };
-    
-    // 构建SFTP批处理命令
-    let sftp_command = format!("put {} {}", 
-        local_path.display(), 
-        remote_dest
-    );
-    
-    // 创建临时批处理文件
-    let mut sftp_batch = std::env::temp_dir();
-    sftp_batch.push("rssh_sftp_batch.txt");
-    std::fs::write(&sftp_batch, sftp_command)
-        .with_context(|| "无法创建SFTP批处理文件")?;
-    
-    // 构建SFTP命令
-    let mut cmd = Command::new("sftp");
-    
-    // 设置端口
-    if server.port != 22 {
-        cmd.args(["-P", &server.port.to_string()]);
+    let (total, count) = (plan.total_bytes(), plan.files.len());
+    let mut progress = TransferProgress::new(total, count);
+    for (local, remote, metadata) in &plan.files {
+        progress.start_file(&display_name(local_path, local), metadata.len());
+        sftp_upload_one(&sftp, local, remote, metadata, &mut progress)?;
     }
-    
-    // 添加认证相关参数
-    match &server.auth_type {
-        crate::models::AuthType::Key(key_path) => {
-            let expanded_path = expand_tilde(key_path);
-            cmd.args(["-i", &expanded_path]);
-        },
-        crate::models::AuthType::Agent => {
-            // 使用SSH代理，不需要额外参数
-        },
-        crate::models::AuthType::Password(_) => {
-            return Err(anyhow::anyhow!("SFTP不支持直接传递密码，请使用密钥或代理认证"));
-        }
-    }
-    
-    // 禁用主机密钥检查
-    cmd.args(["-o", "StrictHostKeyChecking=no"]);
-    
-    // 使用批处理文件
-    cmd.args(["-b", sftp_batch.to_str().unwrap()]);
-    
-    // 添加远程主机
-    cmd.arg(format!("{}@{}", server.username, server.host));
-    
-    // 显示命令
-    let cmd_str = format!("{:?}", cmd);
-    println!("执行: {}", cmd_str.bright_blue());
-    
-    // 执行命令
-    let status = cmd.status()
-        .with_context(|| "无法执行SFTP命令")?;
-    
-    // 删除临时批处理文件
-    let _ = std::fs::remove_file(sftp_batch);
-    
-    if status.success() {
-        println!("文件上传成功！");
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!("文件上传失败，SFTP退出代码: {:?}", status.code()))
+    progress.finish();
+    // 目录的属性在其内容上传完后再设置，避免只读目录或修改时间被随后的写入改变
+    for (remote, metadata) in plan.dirs.iter().rev() {
+        sftp.setstat(remote, local_file_stat(metadata))
+            .with_context(|| format!("无法设置远程目录属性: {}", remote.display()))?;
     }
+    println!("文件上传成功！共 {} 个文件，{}", count, format_size(total));
+    Ok(())
 }
 
-/// 从远程服务器使用SFTP下载文件（作为备选方案）
+/// 使用内置 SFTP 客户端下载文件或目录，认证方式和保留的属性与 [`upload_file_sftp`] 相同
 pub fn download_file_sftp(
     server: &ServerConfig,
     remote_path: &str,
     local_path: Option<PathBuf>,
 ) -> Result<()> {
-    // 确定本地路径
-    let local_dest = match local_path {
-        Some(path) => path,
-        None => {
-            // 如果没有指定本地路径，使用远程文件的基本名称
-            let file_name = Path::new(remote_path)
-                .file_name()
-                .unwrap_or_else(|| std::ffi::OsStr::new(remote_path))
-                .to_string_lossy();
-            PathBuf::from(file_name.to_string())
-        }
+    let client = SshClient::connect(server)?;
+    let sftp = client.sftp()?;
+
+    let remote = Path::new(remote_path);
+    let stat = sftp.stat(remote).with_context(|| format!("远程文件不存在: {}", remote_path))?;
+    // 未指定本地路径时下载到当前目录
+    let local_dest = local_path.unwrap_or_else(|| PathBuf::from("./"));
+    let target = resolve_target(remote, &local_dest, local_dest.is_dir());
+    println!("通过内置SFTP下载 {}@{}:{} -> {}", server.username, server.host, remote_path, target.display());
+
+    let plan = if stat.is_dir() {
+        plan_download_dir(&sftp, remote, &target)?
+    } else {
+        TransferPlan { files: vec![(remote.to_path_buf(), target, stat)], dirs: Vec::new() }
     };
-    
-    // 构建SFTP批处理命令
-    let sftp_command = format!("get {} {}", 
-        remote_path, 
-        local_dest.display()
-    );
-    
-    // 创建临时批处理文件
-    let mut sftp_batch = std::env::temp_dir();
-    sftp_batch.push("rssh_sftp_batch.txt");
-    std::fs::write(&sftp_batch, sftp_command)
-        .with_context(|| "无法创建SFTP批处理文件")?;
-    
-    // 构建SFTP命令
-    let mut cmd = Command::new("sftp");
-    
-    // 设置端口
-    if server.port != 22 {
-        cmd.args(["-P", &server.port.to_string()]);
+    let (total, count) = (plan.total_bytes(), plan.files.len());
+    let mut progress = TransferProgress::new(total, count);
+    for (remote_file, local, stat) in &plan.files {
+        progress.start_file(&display_name(remote, remote_file), stat.size.unwrap_or(0));
+        sftp_download_one(&sftp, remote_file, local, stat, &mut progress)?;
     }
-    
-    // 添加认证相关参数
-    match &server.auth_type {
-        crate::models::AuthType::Key(key_path) => {
-            let expanded_path = expand_tilde(key_path);
-            cmd.args(["-i", &expanded_path]);
-        },
-        crate::models::AuthType::Agent => {
-            // 使用SSH代理，不需要额外参数
-        },
-        crate::models::AuthType::Password(_) => {
-            return Err(anyhow::anyhow!("SFTP不支持直接传递密码，请使用密钥或代理认证"));
+    progress.finish();
+    for (local, stat) in plan.dirs.iter().rev() {
+        apply_file_stat(local, stat)?;
+    }
+    println!("文件下载成功！共 {} 个文件，{}", count, format_size(total));
+    Ok(())
+}
+
+/// 一次 SFTP 传输的内容：要传输的文件（源, 目标, 源的属性）和要设置属性的目标目录
+struct TransferPlan<S> {
+    files: Vec<(PathBuf, PathBuf, S)>,
+    dirs: Vec<(PathBuf, S)>,
+}
+
+impl TransferPlan<std::fs::Metadata> {
+    fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|(_, _, metadata)| metadata.len()).sum()
+    }
+}
+
+impl TransferPlan<ssh2::FileStat> {
+    fn total_bytes(&self) -> u64 {
+        self.files.iter().filter_map(|(_, _, stat)| stat.size).sum()
+    }
+}
+
+/// 进度中显示的文件名：相对于传输的根目录
+fn display_name(root: &Path, path: &Path) -> String {
+    let rel = path.strip_prefix(root).ok().filter(|rel| !rel.as_os_str().is_empty());
+    rel.unwrap_or(path).display().to_string()
+}
+
+/// 列出本地目录下要上传的文件，同时在远程创建对应的目录
+fn plan_upload_dir(sftp: &ssh2::Sftp, local_dir: &Path, remote_dir: &Path) -> Result<TransferPlan<std::fs::Metadata>> {
+    sftp_mkdir_p(sftp, remote_dir)?;
+    let mut plan = TransferPlan { files: Vec::new(), dirs: vec![(remote_dir.to_path_buf(), std::fs::metadata(local_dir)?)] };
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel_dir) = stack.pop() {
+        let dir = local_dir.join(&rel_dir);
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("无法读取目录: {}", dir.display()))? {
+            let rel = rel_dir.join(entry?.file_name());
+            let (local, remote) = (local_dir.join(&rel), remote_dir.join(&rel));
+            let metadata = std::fs::metadata(&local).with_context(|| format!("无法读取: {}", local.display()))?;
+            if metadata.is_dir() {
+                if sftp.stat(&remote).is_err() {
+                    sftp.mkdir(&remote, 0o700).with_context(|| format!("无法创建远程目录: {}", remote.display()))?;
+                }
+                stack.push(rel);
+                plan.dirs.push((remote, metadata));
+            } else if metadata.is_file() {
+                plan.files.push((local, remote, metadata));
+            }
         }
     }
-    
-    // 禁用主机密钥检查
-    cmd.args(["-o", "StrictHostKeyChecking=no"]);
-    
-    // 使用批处理文件
-    cmd.args(["-b", sftp_batch.to_str().unwrap()]);
-    
-    // 添加远程主机
-    cmd.arg(format!("{}@{}", server.username, server.host));
-    
-    // 显示命令
-    let cmd_str = format!("{:?}", cmd);
-    println!("执行: {}", cmd_str.bright_blue());
-    
-    // 执行命令
-    let status = cmd.status()
-        .with_context(|| "无法执行SFTP命令")?;
-    
-    // 删除临时批处理文件
-    let _ = std::fs::remove_file(sftp_batch);
-    
-    if status.success() {
-        println!("文件下载成功！");
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!("文件下载失败，SFTP退出代码: {:?}", status.code()))
+    Ok(plan)
+}
+
+/// 列出远程目录下要下载的文件，同时在本地创建对应的目录；符号链接等特殊文件跳过
+fn plan_download_dir(sftp: &ssh2::Sftp, remote_dir: &Path, local_dir: &Path) -> Result<TransferPlan<ssh2::FileStat>> {
+    std::fs::create_dir_all(local_dir).with_context(|| format!("无法创建目录: {}", local_dir.display()))?;
+    let mut plan = TransferPlan { files: Vec::new(), dirs: vec![(local_dir.to_path_buf(), sftp.stat(remote_dir)?)] };
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel_dir) = stack.pop() {
+        let dir = remote_dir.join(&rel_dir);
+        for (path, stat) in sftp.readdir(&dir).with_context(|| format!("无法读取远程目录: {}", dir.display()))? {
+            let Some(name) = path.file_name().filter(|name| *name != "." && *name != "..") else {
+                continue;
+            };
+            let rel = rel_dir.join(name);
+            let (remote, local) = (remote_dir.join(&rel), local_dir.join(&rel));
+            if stat.is_dir() {
+                std::fs::create_dir_all(&local).with_context(|| format!("无法创建目录: {}", local.display()))?;
+                stack.push(rel);
+                plan.dirs.push((local, stat));
+            } else if stat.is_file() {
+                plan.files.push((remote, local, stat));
+            } else {
+                println!("{} 跳过非普通文件: {}", "⚠".bright_yellow(), remote.display());
+            }
+        }
     }
+    Ok(plan)
+}
+
+fn sftp_upload_one(sftp: &ssh2::Sftp, local: &Path, remote: &Path, metadata: &std::fs::Metadata, progress: &mut TransferProgress) -> Result<()> {
+    let mut reader = std::fs::File::open(local).with_context(|| format!("无法打开文件: {}", local.display()))?;
+    let file = sftp.create(remote).with_context(|| format!("无法创建远程文件: {}", remote.display()))?;
+    let mut writer = progress.writer(file);
+    std::io::copy(&mut reader, &mut writer).with_context(|| format!("写入远程文件失败: {}", remote.display()))?;
+    writer.flush()?;
+    drop(writer);
+    sftp.setstat(remote, local_file_stat(metadata))
+        .with_context(|| format!("无法设置远程文件属性: {}", remote.display()))
+}
+
+fn sftp_download_one(sftp: &ssh2::Sftp, remote: &Path, local: &Path, stat: &ssh2::FileStat, progress: &mut TransferProgress) -> Result<()> {
+    let mut reader = sftp.open(remote).with_context(|| format!("无法打开远程文件: {}", remote.display()))?;
+    let file = std::fs::File::create(local).with_context(|| format!("无法创建文件: {}", local.display()))?;
+    let mut writer = progress.writer(file);
+    std::io::copy(&mut reader, &mut writer).with_context(|| format!("写入文件失败: {}", local.display()))?;
+    drop(writer);
+    apply_file_stat(local, stat)
+}
+
+/// 本地文件的权限和访问/修改时间，用于设置远程文件
+fn local_file_stat(metadata: &std::fs::Metadata) -> ssh2::FileStat {
+    let seconds = |time: std::io::Result<SystemTime>| {
+        time.ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map(|elapsed| elapsed.as_secs())
+    };
+    #[cfg(unix)]
+    let perm = std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o7777;
+    #[cfg(not(unix))]
+    let perm = if metadata.permissions().readonly() { 0o444 } else { 0o644 } | if metadata.is_dir() { 0o111 } else { 0 };
+    ssh2::FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: Some(perm),
+        atime: seconds(metadata.accessed()),
+        mtime: seconds(metadata.modified()),
+    }
+}
+
+/// 把远程文件的修改时间和权限设置到本地文件上
+fn apply_file_stat(path: &Path, stat: &ssh2::FileStat) -> Result<()> {
+    if let Some(mtime) = stat.mtime {
+        let modified = UNIX_EPOCH + Duration::from_secs(mtime);
+        let accessed = stat.atime.map_or(modified, |atime| UNIX_EPOCH + Duration::from_secs(atime));
+        // 先设置时间再设置权限，权限可能让文件无法再打开
+        std::fs::File::open(path)
+            .and_then(|file| file.set_times(std::fs::FileTimes::new().set_accessed(accessed).set_modified(modified)))
+            .with_context(|| format!("无法设置修改时间: {}", path.display()))?;
+    }
+    #[cfg(unix)]
+    if let Some(perm) = stat.perm {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(perm & 0o7777))
+            .with_context(|| format!("无法设置权限: {}", path.display()))?;
+    }
+    Ok(())
 }
 
 /// 使用Kitty传输协议上传文件到远程服务器
@@ -362,38 +418,182 @@ pub fn download_file_kitty(
     crate::utils::kitty_transfer::download_via_kitty(&remote_full_path, local_path)
 }
 
-/// 自动选择最佳传输方式上传文件
+/// rsync 模式的同步选项
+#[derive(Debug, Clone, Default)]
+pub struct RsyncOptions {
+    /// 删除目标中源没有的文件（`--delete`）
+    pub delete: bool,
+    /// 排除规则（`--exclude`），写法与 rsync 相同
+    pub excludes: Vec<String>,
+}
+
+impl RsyncOptions {
+    /// `--protect-args` 让远程路径不经远程 shell 拆分和展开，含空格、`$` 等字符的路径也能原样传输
+    fn args(&self) -> Vec<String> {
+        let mut args = ["-az", "--partial", "--progress", "--protect-args"].map(String::from).to_vec();
+        if self.delete {
+            args.push("--delete".to_string());
+        }
+        args.extend(self.excludes.iter().map(|pattern| format!("--exclude={}", pattern)));
+        args
+    }
+}
+
+/// 使用 rsync 上传文件或目录，中断后重新执行会从已传输的部分继续
+pub fn upload_file_rsync<P: AsRef<Path>>(
+    server: &ServerConfig,
+    local_path: P,
+    remote_path: Option<String>,
+    options: &RsyncOptions,
+) -> Result<()> {
+    let local_path = local_path.as_ref();
+    if !local_path.exists() {
+        return Err(anyhow::anyhow!("本地文件不存在: {}", local_path.display()));
+    }
+    let remote_dest = remote_path.unwrap_or_else(|| "./".to_string());
+
+    let mut cmd = rsync_command(server, options)?;
+    cmd.arg(local_path.as_os_str())
+        .arg(format!("{}@{}:{}", server.username, server.host, remote_dest));
+    run_rsync(cmd, "上传").inspect_err(|e| report_upload_interrupted(e, local_path, &remote_dest))
+}
+
+/// 使用 rsync 下载文件或目录
+pub fn download_file_rsync(
+    server: &ServerConfig,
+    remote_path: &str,
+    local_path: Option<PathBuf>,
+    options: &RsyncOptions,
+) -> Result<()> {
+    let local_dest = local_path.unwrap_or_else(|| PathBuf::from("."));
+
+    let mut cmd = rsync_command(server, options)?;
+    cmd.arg(format!("{}@{}:{}", server.username, server.host, remote_path))
+        .arg(local_dest.as_os_str());
+    run_rsync(cmd, "下载").inspect_err(|e| report_download_interrupted(e, &local_dest))
+}
+
+pub(crate) fn rsync_command(server: &ServerConfig, options: &RsyncOptions) -> Result<Command> {
+    if let crate::models::AuthType::Password(_) = &server.auth_type {
+        return Err(anyhow::anyhow!("rsync不支持直接传递密码，请使用密钥或代理认证"));
+    }
+    let mut cmd = Command::new("rsync");
+    let ssh_cmd = SshCommand::new(server).with_config_file(&get_ssh_config_dir()?)?.remote_shell();
+    cmd.args(options.args()).args(["-e", &ssh_cmd]);
+    Ok(cmd)
+}
+
+fn run_rsync(mut cmd: Command, action: &str) -> Result<()> {
+    println!("执行: {}", format!("{:?}", cmd).bright_blue());
+    let status = interrupt::status(&mut cmd)?;
+    if status.success() {
+        println!("文件{}成功！", action);
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("文件{}失败，rsync退出代码: {:?}", action, status.code()))
+    }
+}
+
+/// 上传被中断时说明本地文件大小和远程可能不完整的文件
+fn report_upload_interrupted(error: &anyhow::Error, local_path: &Path, remote_dest: &str) {
+    if !interrupt::is_interrupted(error) {
+        return;
+    }
+    let size = std::fs::metadata(local_path).map(|m| format_size(m.len())).unwrap_or_else(|_| "?".into());
+    println!("上传已中断（本地文件 {}），远程 {} 可能不完整；rsync 模式重新执行可从断点继续", size, remote_dest);
+}
+
+/// 下载被中断时说明本地已经收到的大小
+fn report_download_interrupted(error: &anyhow::Error, local_dest: &Path) {
+    if !interrupt::is_interrupted(error) {
+        return;
+    }
+    match std::fs::metadata(local_dest) {
+        Ok(meta) if meta.is_file() => println!(
+            "下载已中断，本地 {} 已收到 {}；rsync 模式重新执行可从断点继续",
+            local_dest.display(),
+            format_size(meta.len())
+        ),
+        _ => println!("下载已中断，本地 {} 可能不完整", local_dest.display()),
+    }
+}
+
+/// auto 模式下 kitty/rsync 传输失败时，清除探测缓存并改用 scp；被中断时不再重试
+fn fallback_to_scp(server: &ServerConfig, method: TransferMethod, result: Result<()>, scp: impl FnOnce() -> Result<()>) -> Result<()> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if interrupt::is_interrupted(&e) => Err(e),
+        Err(e) => {
+            println!("{} {} 传输失败: {:#}，改用 scp", "⚠".bright_yellow(), method, e);
+            let _ = remote_probe::forget(server);
+            scp()
+        }
+    }
+}
+
+/// 自动选择最佳传输方式上传文件：按本地终端和远程探测结果在 kitty、rsync、scp 中选择，
+/// `verbose` 时打印选择的原因
 pub fn upload_file_auto<P: AsRef<Path>>(
     server: &ServerConfig,
     local_path: P,
     remote_path: Option<String>,
+    verbose: bool,
 ) -> Result<()> {
-    // // 如果是Kitty终端，优先使用Kitty传输
-    // if crate::utils::kitty_transfer::is_kitty_available() {
-    //     println!("检测到Kitty终端，使用Kitty传输协议");
-    //     upload_file_kitty(server, local_path, remote_path)
-    // } 
-    // // 否则使用SCP（通常是最可靠的方式）
-    // else {
-        println!("使用SCP传输文件");
-        upload_file(server, local_path, remote_path)
-    // }
+    let local_path = local_path.as_ref();
+    let method = remote_probe::select_method(server, verbose);
+    println!("使用{}传输文件", method);
+    let result = match method {
+        TransferMethod::Kitty => upload_file_kitty(server, local_path, remote_path.clone()),
+        TransferMethod::Rsync => upload_file_rsync(server, local_path, remote_path.clone(), &RsyncOptions::default()),
+        TransferMethod::Scp => return upload_file(server, local_path, remote_path),
+    };
+    fallback_to_scp(server, method, result, || upload_file(server, local_path, remote_path))
 }
 
-/// 自动选择最佳传输方式下载文件
+/// 自动选择最佳传输方式下载文件，规则与 [`upload_file_auto`] 相同
 pub fn download_file_auto(
     server: &ServerConfig,
     remote_path: &str,
     local_path: Option<PathBuf>,
+    verbose: bool,
 ) -> Result<()> {
-    // 如果是Kitty终端，优先使用Kitty传输
-    if crate::utils::kitty_transfer::is_kitty_available() {
-        println!("检测到Kitty终端，使用Kitty传输协议");
-        download_file_kitty(server, remote_path, local_path)
-    } 
-    // 否则使用SCP（通常是最可靠的方式）
-    else {
-        println!("使用SCP传输文件");
-        download_file(server, remote_path, local_path)
-    }
-} 
\ No newline at end of file
+    let method = remote_probe::select_method(server, verbose);
+    println!("使用{}传输文件", method);
+    let result = match method {
+        TransferMethod::Kitty => download_file_kitty(server, remote_path, local_path.clone()),
+        TransferMethod::Rsync => download_file_rsync(server, remote_path, local_path.clone(), &RsyncOptions::default()),
+        TransferMethod::Scp => return download_file(server, remote_path, local_path),
+    };
+    fallback_to_scp(server, method, result, || download_file(server, remote_path, local_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_permissions_and_mtime_between_stats() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.sh");
+        std::fs::write(&path, "echo hi\n").unwrap();
+        let stat = ssh2::FileStat { size: None, uid: None, gid: None, perm: Some(0o100750), atime: None, mtime: Some(1_600_000_000) };
+        apply_file_stat(&path, &stat).unwrap();
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o750);
+        let local = local_file_stat(&metadata);
+        assert_eq!((local.perm, local.mtime, local.atime), (Some(0o750), Some(1_600_000_000), Some(1_600_000_000)));
+    }
+
+    #[test]
+    fn rsync_options_add_delete_and_excludes() {
+        assert_eq!(RsyncOptions::default().args(), ["-az", "--partial", "--progress", "--protect-args"]);
+        let options = RsyncOptions { delete: true, excludes: vec!["node_modules".into(), "*.log".into()] };
+        assert_eq!(
+            options.args(),
+            ["-az", "--partial", "--progress", "--protect-args", "--delete", "--exclude=node_modules", "--exclude=*.log"]
+        );
+    }
+}
@@ -0,0 +1,515 @@
+use anyhow::Result;
+use colored::*;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::models::{JobHostRecord, JobHostStatus, ServerConfig};
+use crate::utils::dns_cache;
+use crate::utils::output;
+use crate::utils::ssh::SshClient;
+use crate::utils::sudo::{self, Sudo};
+
+/// 单台主机的执行结果
+#[derive(Debug, Clone)]
+pub struct HostResult {
+    pub server_id: String,
+    pub server_name: String,
+    pub stdout: String,
+    pub stderr: String,
+    /// 连接失败时为 None
+    pub exit_code: Option<i32>,
+    /// 连接或执行出错的原因
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+impl HostResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// `--output json` 时的表示
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "server": self.server_name,
+            "server_id": self.server_id,
+            "exit_code": self.exit_code,
+            "stdout": self.stdout,
+            "stderr": self.stderr,
+            "error": self.error,
+            "duration_ms": self.duration.as_millis() as u64,
+        })
+    }
+}
+
+impl From<&HostResult> for JobHostRecord {
+    fn from(result: &HostResult) -> Self {
+        JobHostRecord {
+            server_id: result.server_id.clone(),
+            server_name: result.server_name.clone(),
+            status: if result.success() { JobHostStatus::Success } else { JobHostStatus::Failed },
+            stdout: result.stdout.clone(),
+            stderr: result.stderr.clone(),
+            exit_code: result.exit_code,
+            error: result.error.clone(),
+            duration_ms: result.duration.as_millis() as u64,
+        }
+    }
+}
+
+impl From<&JobHostRecord> for HostResult {
+    fn from(record: &JobHostRecord) -> Self {
+        HostResult {
+            server_id: record.server_id.clone(),
+            server_name: record.server_name.clone(),
+            stdout: record.stdout.clone(),
+            stderr: record.stderr.clone(),
+            exit_code: record.exit_code,
+            error: record.error.clone(),
+            duration: Duration::from_millis(record.duration_ms),
+        }
+    }
+}
+
+/// 在单台主机上执行命令（使用内置 SSH 库，支持密码认证）
+pub fn run_on_host(server: &ServerConfig, command: &str) -> HostResult {
+    run_on_host_as(server, command, None)
+}
+
+/// 在单台主机上执行命令，`sudo` 时通过 `sudo -S` 以 root 身份执行，输出中的密码会被替换掉
+pub fn run_on_host_as(server: &ServerConfig, command: &str, sudo: Option<&Sudo>) -> HostResult {
+    let start = Instant::now();
+    let password = sudo.and_then(|sudo| sudo.password_for(server)).unwrap_or_default();
+    let outcome = SshClient::connect(server).and_then(|client| match sudo {
+        Some(_) => client.execute_command_with_input(&sudo::wrap_command(command), &sudo::stdin_for(password)),
+        None => client.execute_command(command),
+    });
+
+    let (stdout, stderr, exit_code, error) = match outcome {
+        Ok((stdout, stderr, code)) => (sudo::scrub(&stdout, password), sudo::scrub(&stderr, password), Some(code), None),
+        Err(e) => (String::new(), String::new(), None, Some(format!("{:#}", e))),
+    };
+
+    HostResult {
+        server_id: server.id.clone(),
+        server_name: server.name.clone(),
+        stdout,
+        stderr,
+        exit_code,
+        error,
+        duration: start.elapsed(),
+    }
+}
+
+/// 在多台主机上并发执行命令，最多同时 `parallel` 台，按完成顺序回调 `on_result`。
+/// 连接前先解析所有主机，无法解析的主机直接记为失败
+pub fn run_on_hosts<F>(servers: &[ServerConfig], command: &str, parallel: usize, sudo: Option<&Sudo>, mut on_result: F) -> Vec<HostResult>
+where
+    F: FnMut(&HostResult),
+{
+    let (servers, mut results) = pre_resolve(servers, &mut on_result);
+    results.extend(run_batch(&servers, command, parallel, sudo, on_result));
+    results
+}
+
+/// 并发解析所有目标主机的地址（结果缓存到本次运行结束），在连接任何主机之前报告无法解析的主机。
+/// 返回可以解析的主机，以及无法解析的主机对应的失败结果（已回调 `on_result`）。
+/// 有备用地址时任一地址能解析即可；经跳板机连接的主机由跳板机解析，不检查
+pub fn pre_resolve<F>(servers: &[ServerConfig], on_result: &mut F) -> (Vec<ServerConfig>, Vec<HostResult>)
+where
+    F: FnMut(&HostResult),
+{
+    let direct: Vec<&ServerConfig> = servers.iter().filter(|s| !s.uses_jump()).collect();
+    let hosts: Vec<&str> = direct.iter().flat_map(|s| s.addresses()).collect();
+    let failed = dns_cache::resolve_all(&hosts);
+    let error_for = |host: &str| failed.iter().find(|(name, _)| name == host).map(|(_, e)| e);
+
+    let mut resolved = Vec::with_capacity(servers.len());
+    let mut unresolved = Vec::new();
+    for server in servers {
+        let addresses = server.addresses();
+        if server.uses_jump() || addresses.iter().any(|host| error_for(host).is_none()) {
+            resolved.push(server.clone());
+            continue;
+        }
+        let reasons: Vec<String> = addresses.iter().map(|host| format!("{}: {}", host, error_for(host).unwrap())).collect();
+        unresolved.push(HostResult {
+            server_id: server.id.clone(),
+            server_name: server.name.clone(),
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: None,
+            error: Some(format!("无法解析 {}", reasons.join(", "))),
+            duration: Duration::ZERO,
+        });
+    }
+
+    if !unresolved.is_empty() {
+        let _ = writeln!(output::status(), "{} {} 台主机无法解析，不会连接:", "⚠".bright_yellow(), unresolved.len());
+        for result in &unresolved {
+            on_result(result);
+        }
+    }
+    (resolved, unresolved)
+}
+
+/// 在已解析的主机上分批并发执行
+fn run_batch<F>(servers: &[ServerConfig], command: &str, parallel: usize, sudo: Option<&Sudo>, mut on_result: F) -> Vec<HostResult>
+where
+    F: FnMut(&HostResult),
+{
+    let mut results = Vec::with_capacity(servers.len());
+
+    for chunk in servers.chunks(parallel.max(1)) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            for server in chunk {
+                let tx = tx.clone();
+                scope.spawn(move || {
+                    let _ = tx.send(run_on_host_as(server, command, sudo));
+                });
+            }
+            drop(tx);
+
+            for result in rx {
+                on_result(&result);
+                results.push(result);
+            }
+        });
+    }
+
+    results
+}
+
+/// 分批执行的计划
+#[derive(Debug, Clone, Default)]
+pub struct Rollout {
+    /// 先在前 N 台上执行，确认后再继续
+    pub canary: usize,
+    /// 之后每批的主机数，0 表示剩余主机一批完成
+    pub batch_size: usize,
+    /// 两批之间的等待时间
+    pub batch_delay: Duration,
+}
+
+/// 将主机按金丝雀 + 分批切分，返回每一批的下标范围
+pub fn plan_waves(total: usize, canary: usize, batch_size: usize) -> Vec<std::ops::Range<usize>> {
+    let mut waves = Vec::new();
+    let mut start = 0;
+
+    if canary > 0 && total > 0 {
+        let end = canary.min(total);
+        waves.push(0..end);
+        start = end;
+    }
+
+    let step = if batch_size == 0 { total.max(1) } else { batch_size };
+    while start < total {
+        let end = (start + step).min(total);
+        waves.push(start..end);
+        start = end;
+    }
+
+    waves
+}
+
+/// 按计划分批执行。金丝雀批次完成后暂停确认：`assume_yes` 且金丝雀全部成功时自动继续，
+/// 其它情况询问用户；用户拒绝时返回已完成的结果。无法解析的主机在第一批开始前报告，不参与分批。
+pub fn run_rollout<F>(
+    servers: &[ServerConfig],
+    command: &str,
+    parallel: usize,
+    rollout: &Rollout,
+    assume_yes: bool,
+    sudo: Option<&Sudo>,
+    mut on_result: F,
+) -> Result<Vec<HostResult>>
+where
+    F: FnMut(&HostResult),
+{
+    let (servers, mut results) = pre_resolve(servers, &mut on_result);
+    let waves = plan_waves(servers.len(), rollout.canary, rollout.batch_size);
+
+    for (index, wave) in waves.iter().enumerate() {
+        if index > 0 && !rollout.batch_delay.is_zero() {
+            writeln!(output::status(), "等待 {} 秒后执行下一批...", rollout.batch_delay.as_secs())?;
+            std::thread::sleep(rollout.batch_delay);
+        }
+
+        if waves.len() > 1 {
+            let label = if index == 0 && rollout.canary > 0 { "金丝雀".to_string() } else { format!("第 {} 批", index + 1) };
+            writeln!(output::status(), "{} ({} 台): {}", "==>".bright_blue(), wave.len(), label)?;
+        }
+
+        let wave_results = run_batch(&servers[wave.clone()], command, parallel, sudo, &mut on_result);
+        let wave_ok = wave_results.iter().all(HostResult::success);
+        results.extend(wave_results);
+
+        let is_canary = index == 0 && rollout.canary > 0;
+        let remaining = servers.len() - wave.end;
+        if is_canary && remaining > 0 && !(assume_yes && wave_ok) {
+            let mut status = output::status();
+            if !wave_ok {
+                writeln!(status, "{}", "金丝雀主机执行失败".bright_red())?;
+            }
+            write!(status, "继续在剩余 {} 台主机上执行? [y/N] ", remaining)?;
+            status.flush()?;
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+            if !input.trim().eq_ignore_ascii_case("y") {
+                writeln!(status, "已停止，剩余 {} 台未执行", remaining)?;
+                break;
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// 以 `[主机名]` 前缀逐行打印一台主机的输出
+pub fn print_host_result(result: &HostResult) {
+    let prefix = format!("[{}]", result.server_name);
+    let prefix = if result.success() { prefix.bright_green() } else { prefix.bright_red() };
+
+    if let Some(error) = &result.error {
+        println!("{} 连接失败: {}", prefix, error);
+        return;
+    }
+    for line in result.stdout.lines() {
+        println!("{} {}", prefix, line);
+    }
+    for line in result.stderr.lines() {
+        println!("{} {}", prefix, line.bright_black());
+    }
+    if !result.success() {
+        println!("{} 退出码: {}", prefix, result.exit_code.unwrap_or(-1));
+    }
+}
+
+/// 输出完全相同的一组主机
+#[derive(Debug, Clone)]
+pub struct OutputGroup {
+    pub hosts: Vec<String>,
+    /// 该组的代表结果（输出、退出码、错误）
+    pub sample: HostResult,
+}
+
+/// 按输出（stdout、stderr、退出码、错误）对主机分组，主机最多的组在前
+pub fn collate(results: &[HostResult]) -> Vec<OutputGroup> {
+    let mut groups: Vec<OutputGroup> = Vec::new();
+
+    for result in results {
+        let existing = groups.iter_mut().find(|g| {
+            g.sample.stdout == result.stdout
+                && g.sample.stderr == result.stderr
+                && g.sample.exit_code == result.exit_code
+                && g.sample.error == result.error
+        });
+        match existing {
+            Some(group) => group.hosts.push(result.server_name.clone()),
+            None => groups.push(OutputGroup {
+                hosts: vec![result.server_name.clone()],
+                sample: result.clone(),
+            }),
+        }
+    }
+
+    for group in &mut groups {
+        group.hosts.sort();
+    }
+    // 数量相同时按第一台主机名排序，保证输出稳定
+    groups.sort_by(|a, b| b.hosts.len().cmp(&a.hosts.len()).then_with(|| a.hosts.cmp(&b.hosts)));
+    groups
+}
+
+/// 打印分组后的输出；除最大的一组外都视为离群，用红色标出
+pub fn print_collated(results: &[HostResult]) {
+    let groups = collate(results);
+
+    for (index, group) in groups.iter().enumerate() {
+        let header = format!("{} 台主机", group.hosts.len());
+        let header = if index == 0 && groups.len() > 1 {
+            header.bright_green()
+        } else if index > 0 {
+            format!("{} (离群)", header).bright_red()
+        } else {
+            header.bright_green()
+        };
+        println!();
+        println!("{} {}: {}", "==>".bright_blue(), header, group.hosts.join(", "));
+
+        let sample = &group.sample;
+        if let Some(error) = &sample.error {
+            println!("连接失败: {}", error);
+            continue;
+        }
+        print!("{}", sample.stdout);
+        if !sample.stdout.is_empty() && !sample.stdout.ends_with('\n') {
+            println!();
+        }
+        for line in sample.stderr.lines() {
+            println!("{}", line.bright_black());
+        }
+        if !sample.success() {
+            println!("退出码: {}", sample.exit_code.unwrap_or(-1));
+        }
+    }
+}
+
+/// 分组模式下执行过程中只显示进度
+pub fn print_progress(result: &HostResult) {
+    let mark = if result.success() { "✓".bright_green() } else { "✗".bright_red() };
+    println!("{} {}", mark, result.server_name);
+}
+
+/// 打印汇总：成功/失败数量和失败主机列表
+pub fn print_summary(results: &[HostResult]) {
+    let failed: Vec<&HostResult> = results.iter().filter(|r| !r.success()).collect();
+    println!();
+    println!(
+        "完成: {} 台成功, {} 台失败",
+        (results.len() - failed.len()).to_string().bright_green(),
+        failed.len().to_string().bright_red()
+    );
+    if !failed.is_empty() {
+        let names: Vec<&str> = failed.iter().map(|r| r.server_name.as_str()).collect();
+        println!("失败主机: {}", names.join(", "));
+    }
+}
+
+/// 执行前确认目标主机。
+///
+/// 列出解析出的主机（名称、地址、分组）；目标数超过 `threshold` 时必须输入分组名
+/// （未指定分组时输入主机数量）才能继续，否则只需回答 y。`assume_yes` 时跳过确认。
+pub fn confirm_targets(
+    servers: &[ServerConfig],
+    group: Option<&str>,
+    command: &str,
+    threshold: usize,
+    assume_yes: bool,
+) -> Result<bool> {
+    if assume_yes {
+        return Ok(true);
+    }
+
+    let mut status = output::status();
+    writeln!(status, "将在以下 {} 台主机上执行: {}", servers.len().to_string().bright_yellow(), command.bright_cyan())?;
+    for server in servers {
+        writeln!(
+            status,
+            "  {:<24} {}@{}:{}  {}",
+            server.name.bright_green(),
+            server.username,
+            server.host,
+            server.port,
+            server.group.as_deref().unwrap_or("-").bright_magenta()
+        )?;
+    }
+
+    let expected = if requires_typed_confirmation(servers.len(), threshold) {
+        let token = group.map(str::to_string).unwrap_or_else(|| servers.len().to_string());
+        write!(status, "目标超过 {} 台，请输入 {} 确认: ", threshold, token.bright_yellow())?;
+        Some(token)
+    } else {
+        write!(status, "确认执行? [y/N] ")?;
+        None
+    };
+    status.flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+
+    Ok(match expected {
+        Some(token) => input == token,
+        None => input.eq_ignore_ascii_case("y"),
+    })
+}
+
+/// 目标数超过阈值时需要输入分组名确认
+pub fn requires_typed_confirmation(count: usize, threshold: usize) -> bool {
+    count > threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    #[test]
+    fn typed_confirmation_only_above_threshold() {
+        assert!(!requires_typed_confirmation(5, 5));
+        assert!(requires_typed_confirmation(6, 5));
+    }
+
+    fn result(name: &str, stdout: &str, code: i32) -> HostResult {
+        HostResult {
+            server_id: name.into(),
+            server_name: name.into(),
+            stdout: stdout.into(),
+            stderr: String::new(),
+            exit_code: Some(code),
+            error: None,
+            duration: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn collates_identical_output() {
+        let results = vec![
+            result("web3", "v1\n", 0),
+            result("web1", "v1\n", 0),
+            result("web2", "v2\n", 0),
+            result("web4", "v1\n", 1),
+        ];
+        let groups = collate(&results);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].hosts, vec!["web1", "web3"]);
+        assert_eq!(groups[0].sample.stdout, "v1\n");
+        // 退出码不同也算不同的输出
+        assert_eq!(groups[1].hosts, vec!["web2"]);
+        assert_eq!(groups[2].hosts, vec!["web4"]);
+    }
+
+    #[test]
+    fn plans_canary_and_batches() {
+        assert_eq!(plan_waves(10, 1, 4), vec![0..1, 1..5, 5..9, 9..10]);
+        assert_eq!(plan_waves(3, 0, 0), vec![0..3]);
+        assert_eq!(plan_waves(2, 5, 1), vec![0..2]);
+        assert!(plan_waves(0, 1, 2).is_empty());
+    }
+
+    #[test]
+    fn connection_failure_is_reported_per_host() {
+        let server = ServerConfig::new(
+            "1".into(), "dead".into(), "127.0.0.1".into(), 1, "root".into(),
+            AuthType::Agent, None, None, None,
+        );
+        let mut seen = 0;
+        let results = run_on_hosts(&[server.clone(), server], "true", 4, None, |_| seen += 1);
+        assert_eq!(seen, 2);
+        assert!(results.iter().all(|r| !r.success() && r.error.is_some()));
+    }
+
+    #[test]
+    fn unresolvable_hosts_fail_before_connecting() {
+        let server = |id: &str, host: &str| ServerConfig::new(
+            id.into(), id.into(), host.into(), 1, "root".into(),
+            AuthType::Agent, None, None, None,
+        );
+        let mut fallback = server("fallback", "web.invalid");
+        fallback.alt_hosts = vec!["127.0.0.1".into()];
+        let mut bastion = server("bastion", "internal.invalid");
+        bastion.jump_host = Some("jump.example.com".into());
+
+        let mut seen = Vec::new();
+        let (resolved, failed) = pre_resolve(
+            &[server("ok", "127.0.0.1"), server("bad", "nohost.invalid"), fallback, bastion],
+            &mut |r: &HostResult| seen.push(r.server_name.clone()),
+        );
+        let names: Vec<&str> = resolved.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["ok", "fallback", "bastion"]);
+        assert_eq!(seen, ["bad"]);
+        assert!(failed[0].error.as_deref().unwrap().contains("nohost.invalid"));
+    }
+}
@@ -0,0 +1,157 @@
+//! `rssh diff`：从分组内每台主机读取同一个配置文件，按内容分组后与基准
+//! （第一台主机或本地文件）做统一 diff，找出配置漂移。
+
+use colored::*;
+use similar::TextDiff;
+
+use crate::utils::fleet::{collate, HostResult};
+
+/// 在服务器上读取文件的命令
+pub fn fetch_command(path: &str) -> String {
+    format!("cat -- {}", shell_escape::escape(path.into()))
+}
+
+/// 比较的基准
+pub struct Reference {
+    /// 显示在 diff 头部的名称，例如 `lb-1:/etc/haproxy/haproxy.cfg` 或本地路径
+    pub label: String,
+    pub content: String,
+    /// 基准来自某台主机时为其名称，这台主机不再单独列出
+    pub host: Option<String>,
+}
+
+/// 与基准内容不同的一组主机及其 diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    pub hosts: Vec<String>,
+    pub diff: String,
+}
+
+/// 统一 diff（3 行上下文），内容相同时为空字符串
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+/// 按内容对读取成功的主机分组，返回与基准相同的主机和每组不同内容的 diff
+pub fn compare(reference: &Reference, results: &[HostResult], path: &str) -> (Vec<String>, Vec<Drift>) {
+    let fetched: Vec<HostResult> = results.iter().filter(|r| r.success()).cloned().collect();
+    let mut same = Vec::new();
+    let mut drifts = Vec::new();
+    for group in collate(&fetched) {
+        if group.sample.stdout == reference.content {
+            same.extend(group.hosts);
+            continue;
+        }
+        let label = format!("{}:{}", group.hosts[0], path);
+        drifts.push(Drift {
+            diff: unified_diff(&reference.content, &group.sample.stdout, &reference.label, &label),
+            hosts: group.hosts,
+        });
+    }
+    same.retain(|host| Some(host) != reference.host.as_ref());
+    (same, drifts)
+}
+
+/// 打印带颜色的 diff
+pub fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        let line = if line.starts_with("+++") || line.starts_with("---") {
+            line.bold()
+        } else if line.starts_with('+') {
+            line.bright_green()
+        } else if line.starts_with('-') {
+            line.bright_red()
+        } else if line.starts_with("@@") {
+            line.bright_cyan()
+        } else {
+            line.normal()
+        };
+        println!("{}", line);
+    }
+}
+
+/// 打印比较结果，返回是否存在漂移或读取失败的主机
+pub fn print_report(reference: &Reference, results: &[HostResult], path: &str) -> bool {
+    let (same, drifts) = compare(reference, results, path);
+    let failed: Vec<&HostResult> = results.iter().filter(|r| !r.success()).collect();
+
+    println!("基准: {}", reference.label.bright_blue());
+    if !same.is_empty() {
+        println!("{} 与基准相同 ({} 台): {}", "✓".bright_green(), same.len(), same.join(", "));
+    }
+    for drift in &drifts {
+        println!();
+        println!(
+            "{} 与基准不同 ({} 台): {}",
+            "✗".bright_red(),
+            drift.hosts.len(),
+            drift.hosts.join(", ").bright_yellow()
+        );
+        print_diff(&drift.diff);
+    }
+    if !failed.is_empty() {
+        println!();
+        for result in &failed {
+            let reason = match &result.error {
+                Some(error) => error.clone(),
+                None => result.stderr.trim().to_string(),
+            };
+            println!("{} {} 读取失败: {}", "!".bright_red(), result.server_name, reason);
+        }
+    }
+
+    println!();
+    println!(
+        "{} 台相同, {} 台不同, {} 台读取失败",
+        (same.len() + usize::from(reference.host.is_some())).to_string().bright_green(),
+        drifts.iter().map(|d| d.hosts.len()).sum::<usize>().to_string().bright_yellow(),
+        failed.len().to_string().bright_red()
+    );
+    !drifts.is_empty() || !failed.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn fetched(name: &str, content: &str) -> HostResult {
+        HostResult {
+            server_id: name.into(),
+            server_name: name.into(),
+            stdout: content.into(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            error: None,
+            duration: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn groups_hosts_by_drift() {
+        let base = "global\n  maxconn 4096\ndefaults\n  mode http\n";
+        let drifted = "global\n  maxconn 2048\ndefaults\n  mode http\n";
+        let results = vec![
+            fetched("lb-1", base),
+            fetched("lb-2", drifted),
+            fetched("lb-3", base),
+            fetched("lb-4", drifted),
+        ];
+        let reference = Reference { label: "lb-1:/etc/haproxy/haproxy.cfg".into(), content: base.into(), host: Some("lb-1".into()) };
+
+        let (same, drifts) = compare(&reference, &results, "/etc/haproxy/haproxy.cfg");
+        assert_eq!(same, ["lb-3"]);
+        assert_eq!(drifts.len(), 1);
+        assert_eq!(drifts[0].hosts, ["lb-2", "lb-4"]);
+        assert_eq!(
+            drifts[0].diff,
+            "--- lb-1:/etc/haproxy/haproxy.cfg\n+++ lb-2:/etc/haproxy/haproxy.cfg\n\
+             @@ -1,4 +1,4 @@\n global\n-  maxconn 4096\n+  maxconn 2048\n defaults\n   mode http\n"
+        );
+        assert_eq!(fetch_command("/etc/my app.conf"), "cat -- '/etc/my app.conf'");
+    }
+}
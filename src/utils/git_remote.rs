@@ -0,0 +1,99 @@
+//! `git-remote-rssh`：git 的远程助手，让 `git clone rssh://服务器/仓库.git` 使用 rssh 中保存的服务器配置。
+//!
+//! 只实现 `connect` 能力：git 请求 `git-upload-pack` / `git-receive-pack` 后，
+//! 在服务器上执行对应命令，之后的数据原样在 git 和远程命令之间转发，与 git 的 ssh 传输相同。
+
+use anyhow::Result;
+use std::io::{self, Read, Write};
+
+use crate::utils::ssh::SshClient;
+
+/// 远程仓库地址：rssh 中的服务器名和仓库路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRemote {
+    pub server: String,
+    pub path: String,
+}
+
+impl GitRemote {
+    /// 支持 `rssh://服务器/路径`（与 ssh:// 相同，`/~/` 开头表示主目录）和 `服务器:路径` 两种写法
+    pub fn parse(url: &str) -> Result<Self> {
+        let (server, path) = match url.strip_prefix("rssh://") {
+            Some(rest) => {
+                let (server, path) = rest.split_once('/').unwrap_or((rest, ""));
+                let path = match path.strip_prefix('~') {
+                    Some(_) => path.to_string(),
+                    None => format!("/{}", path),
+                };
+                (server, path)
+            }
+            None => url.split_once(':').map(|(server, path)| (server, path.to_string())).unwrap_or((url, String::new())),
+        };
+        if server.is_empty() || path.is_empty() || path == "/" {
+            return Err(anyhow::anyhow!("仓库地址应为 rssh://服务器/路径 或 服务器:路径: {}", url));
+        }
+        Ok(GitRemote { server: server.to_string(), path })
+    }
+
+    /// 在服务器上执行的 git 服务命令
+    pub fn service_command(&self, service: &str) -> String {
+        format!("{} {}", service, shell_escape::escape(self.path.as_str().into()))
+    }
+}
+
+/// 按 git 远程助手协议处理标准输入上的命令，`connect` 用于按服务器名建立连接。
+/// 返回远程 git 命令的退出码
+pub fn run_helper<F>(remote: &GitRemote, connect: F) -> Result<i32>
+where
+    F: FnOnce(&str) -> Result<SshClient>,
+{
+    let mut stdout = io::stdout();
+    loop {
+        let line = read_line()?;
+        match line.split_once(' ').unwrap_or((line.as_str(), "")) {
+            ("capabilities", _) => stdout.write_all(b"connect\n\n")?,
+            ("option", _) => stdout.write_all(b"unsupported\n")?,
+            ("connect", service @ ("git-upload-pack" | "git-receive-pack")) => {
+                let client = connect(&remote.server)?;
+                // 空行表示连接已建立，之后就是 git 自己的协议数据
+                stdout.write_all(b"\n")?;
+                stdout.flush()?;
+                return client.pipe_command(&remote.service_command(service));
+            }
+            ("connect", service) => return Err(anyhow::anyhow!("不支持的 git 服务: {}", service)),
+            ("", _) => return Ok(0),
+            (command, _) => return Err(anyhow::anyhow!("不支持的远程助手命令: {}", command)),
+        }
+        stdout.flush()?;
+    }
+}
+
+/// 逐字节读取一行，避免缓冲读走 git 随后发送的协议数据
+fn read_line() -> Result<String> {
+    let mut stdin = io::stdin();
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while stdin.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end_matches('\r').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_remote_urls() {
+        let remote = GitRemote::parse("rssh://buildbox/srv/git/app.git").unwrap();
+        assert_eq!(remote, GitRemote { server: "buildbox".into(), path: "/srv/git/app.git".into() });
+        assert_eq!(remote.service_command("git-upload-pack"), "git-upload-pack /srv/git/app.git");
+
+        assert_eq!(GitRemote::parse("rssh://buildbox/~/my repo.git").unwrap().path, "~/my repo.git");
+        assert_eq!(
+            GitRemote::parse("buildbox:app.git").unwrap(),
+            GitRemote { server: "buildbox".into(), path: "app.git".into() }
+        );
+        assert!(GitRemote::parse("rssh://buildbox").is_err());
+    }
+}
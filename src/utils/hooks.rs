@@ -0,0 +1,352 @@
+//! 脚本钩子（`config.toml` 的 `[hooks]`）：在连接、导入、解析密钥和列表搜索时调用用户的脚本，
+//! 实现静态配置项表达不了的行为。
+//!
+//! 钩子可以写在内置的 Rhai 脚本（`script`）中：脚本定义与钩子同名的函数，在 rssh 进程内执行，
+//! 服务器以对象的形式传入，格式与插件清单相同（见 [`plugin::server_json`](crate::utils::plugin::server_json)）。
+//!
+//! 脚本没有定义的钩子使用对应的命令：一条通过 `sh -c` 执行的命令，脚本用什么语言写都可以
+//! （`lua hooks.lua`、`python3 hooks.py` 等）。rssh 把一个 JSON 对象写入命令的标准输入，其中 `hook` 为钩子名称；
+//! 需要返回结果的钩子从标准输出读取。命令的标准错误直接显示在终端上。
+
+use anyhow::{Context, Result};
+use rhai::{Dynamic, Engine, FuncArgs, Scope, AST};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::models::ServerConfig;
+use crate::utils::plugin::server_json;
+use crate::utils::ssh_config::expand_tilde;
+
+/// `config.toml` 中的 `[hooks]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    /// Rhai 脚本文件，其中定义的 `on_connect(server)`、`on_import(servers)`、`resolve_secret(reference)`、
+    /// `fuzzy_score(query, server)` 函数优先于下面对应的命令
+    pub script: Option<String>,
+    /// 交互式连接前调用，输入 `{"hook": "on_connect", "server": {...}}`，退出码非 0 时取消连接
+    pub on_connect: Option<String>,
+    /// 导入服务器前调用，输入 `{"hook": "on_import", "servers": [...]}`，输出 `{"servers": [...]}`：
+    /// 按 `id` 对应，可以修改名称、地址、端口、用户、分组、描述和别名，没有输出的服务器不导入；不输出任何内容时原样导入
+    pub on_import: Option<String>,
+    /// 解析 `hook:<引用>` 形式的密码，输入 `{"hook": "resolve_secret", "reference": "..."}`，输出密码
+    pub resolve_secret: Option<String>,
+    /// 列表中 `/` 搜索时替代内置的模糊匹配，输入 `{"hook": "fuzzy_score", "query": "...", "servers": [...]}`，
+    /// 输出 `{"scores": [...]}`：与服务器一一对应，分数越高越靠前，null 表示不匹配
+    pub fuzzy_score: Option<String>,
+}
+
+static HOOKS: OnceLock<HookSettings> = OnceLock::new();
+
+/// 启用脚本钩子，只在启动时调用一次
+pub fn configure(settings: HookSettings) {
+    let _ = HOOKS.set(settings);
+}
+
+fn hook(select: fn(&HookSettings) -> &Option<String>) -> Option<&'static str> {
+    HOOKS.get().and_then(|hooks| select(hooks).as_deref()).filter(|cmd| !cmd.trim().is_empty())
+}
+
+/// 交互式连接前调用 `on_connect`，脚本失败（或脚本函数返回 false）时取消连接
+pub fn on_connect(server: &ServerConfig) -> Result<()> {
+    let cancelled = || format!("on_connect 钩子取消了到 {} 的连接", server.name);
+    if let Some(script) = script_defining("on_connect", 1)? {
+        return script.on_connect(server).with_context(cancelled);
+    }
+    let Some(command) = hook(|hooks| &hooks.on_connect) else {
+        return Ok(());
+    };
+    call("on_connect", command, &json!({ "hook": "on_connect", "server": server_json(server) })).with_context(cancelled)?;
+    Ok(())
+}
+
+/// 导入前调用 `on_import`，返回脚本修改后要导入的服务器
+pub fn on_import(servers: Vec<ServerConfig>) -> Result<Vec<ServerConfig>> {
+    if let Some(script) = script_defining("on_import", 1)? {
+        return script.on_import(servers);
+    }
+    let Some(command) = hook(|hooks| &hooks.on_import) else {
+        return Ok(servers);
+    };
+    let input = json!({ "hook": "on_import", "servers": servers.iter().map(server_json).collect::<Vec<_>>() });
+    let output = call("on_import", command, &input)?;
+    apply_import_output(servers, &output)
+}
+
+/// 通过 `resolve_secret` 钩子取回 `hook:<引用>` 对应的密码
+pub fn resolve_secret(reference: &str) -> Result<String> {
+    let secret = if let Some(script) = script_defining("resolve_secret", 1)? {
+        script.resolve_secret(reference)?
+    } else {
+        let command = hook(|hooks| &hooks.resolve_secret)
+            .ok_or_else(|| anyhow::anyhow!("config.toml 中没有配置 [hooks] resolve_secret，无法解析 hook:{}", reference))?;
+        let output = call("resolve_secret", command, &json!({ "hook": "resolve_secret", "reference": reference }))?;
+        output.trim_end_matches(['\n', '\r']).to_string()
+    };
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("resolve_secret 钩子没有输出任何内容: hook:{}", reference));
+    }
+    Ok(secret)
+}
+
+/// 配置了 `fuzzy_score` 时由脚本为服务器打分，返回与 `servers` 一一对应的分数（None 为不匹配）
+pub fn fuzzy_scores(query: &str, servers: &[ServerConfig]) -> Option<Result<Vec<Option<i64>>>> {
+    match script_defining("fuzzy_score", 2) {
+        Ok(Some(script)) => return Some(script.fuzzy_scores(query, servers)),
+        Ok(None) => {},
+        Err(e) => return Some(Err(e)),
+    }
+    let command = hook(|hooks| &hooks.fuzzy_score)?;
+    let input = json!({
+        "hook": "fuzzy_score",
+        "query": query,
+        "servers": servers.iter().map(server_json).collect::<Vec<_>>(),
+    });
+    Some(call("fuzzy_score", command, &input).and_then(|output| parse_scores(&output, servers.len())))
+}
+
+/// 搜索打分是否要启动外部命令，此时列表界面等输入停顿后再过滤
+pub fn fuzzy_score_runs_command() -> bool {
+    hook(|hooks| &hooks.fuzzy_score).is_some() && !matches!(script_defining("fuzzy_score", 2), Ok(Some(_)))
+}
+
+/// `script` 中定义了该函数时返回脚本；脚本在第一次使用时编译，无法编译时每次调用都返回错误
+fn script_defining(name: &str, arity: usize) -> Result<Option<&'static Script>> {
+    static SCRIPT: OnceLock<std::result::Result<Script, String>> = OnceLock::new();
+    let Some(path) = hook(|hooks| &hooks.script) else {
+        return Ok(None);
+    };
+    let script = SCRIPT
+        .get_or_init(|| Script::load(Path::new(&expand_tilde(path))).map_err(|e| format!("{:#}", e)))
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(script.defines(name, arity).then_some(script))
+}
+
+/// 编译好的 Rhai 钩子脚本
+struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    fn load(path: &Path) -> Result<Self> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| anyhow::anyhow!("无法加载钩子脚本 {}: {}", path.display(), e))?;
+        Ok(Script { engine, ast })
+    }
+
+    fn defines(&self, name: &str, arity: usize) -> bool {
+        self.ast.iter_functions().any(|function| function.name == name && function.params.len() == arity)
+    }
+
+    fn call(&self, name: &str, args: impl FuncArgs) -> Result<Dynamic> {
+        self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args)
+            .map_err(|e| anyhow::anyhow!("钩子脚本的 {} 函数执行失败: {}", name, e))
+    }
+
+    fn on_connect(&self, server: &ServerConfig) -> Result<()> {
+        match self.call("on_connect", (to_dynamic(server_json(server))?,))?.as_bool() {
+            Ok(false) => Err(anyhow::anyhow!("on_connect 函数返回了 false")),
+            _ => Ok(()),
+        }
+    }
+
+    /// 函数返回 `()` 时原样导入，否则返回值与 `on_import` 命令输出中的 `servers` 数组相同
+    fn on_import(&self, servers: Vec<ServerConfig>) -> Result<Vec<ServerConfig>> {
+        let input = to_dynamic(Value::Array(servers.iter().map(server_json).collect()))?;
+        let output = self.call("on_import", (input,))?;
+        if output.is_unit() {
+            return Ok(servers);
+        }
+        let output: Value = rhai::serde::from_dynamic(&output).map_err(|e| anyhow::anyhow!("on_import 函数的返回值无效: {}", e))?;
+        apply_import_output(servers, &json!({ "servers": output }).to_string())
+    }
+
+    fn resolve_secret(&self, reference: &str) -> Result<String> {
+        self.call("resolve_secret", (reference.to_string(),))?
+            .into_string()
+            .map_err(|kind| anyhow::anyhow!("resolve_secret 函数应返回字符串，实际为 {}", kind))
+    }
+
+    /// 每台服务器调用一次 `fuzzy_score(query, server)`，返回整数或小数为分数，`()` 为不匹配
+    fn fuzzy_scores(&self, query: &str, servers: &[ServerConfig]) -> Result<Vec<Option<i64>>> {
+        servers
+            .iter()
+            .map(|server| {
+                let score = self.call("fuzzy_score", (query.to_string(), to_dynamic(server_json(server))?))?;
+                if score.is_unit() {
+                    Ok(None)
+                } else if let Ok(score) = score.as_int() {
+                    Ok(Some(score))
+                } else if let Ok(score) = score.as_float() {
+                    Ok(Some(score.round() as i64))
+                } else {
+                    Err(anyhow::anyhow!("fuzzy_score 函数应返回数字或 ()，实际为 {}", score.type_name()))
+                }
+            })
+            .collect()
+    }
+}
+
+fn to_dynamic(value: Value) -> Result<Dynamic> {
+    rhai::serde::to_dynamic(value).map_err(|e| anyhow::anyhow!("无法把服务器传给钩子脚本: {}", e))
+}
+
+/// 执行钩子命令，输入写入标准输入，返回标准输出
+fn call(name: &str, command: &str, input: &Value) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("无法执行 {} 钩子: {}", name, command))?;
+    {
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("无法写入 {} 钩子的输入", name))?;
+        // 脚本不读取输入就退出时写入会失败，以退出码为准
+        let _ = writeln!(stdin, "{}", input);
+    }
+    let output = child.wait_with_output().with_context(|| format!("等待 {} 钩子失败", name))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("{} 钩子执行失败 (退出码: {:?}): {}", name, output.status.code(), command));
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("{} 钩子的输出不是有效的UTF-8", name))
+}
+
+/// 按 `on_import` 的输出修改服务器，输出为空时原样返回
+fn apply_import_output(mut servers: Vec<ServerConfig>, output: &str) -> Result<Vec<ServerConfig>> {
+    if output.trim().is_empty() {
+        return Ok(servers);
+    }
+    let output: Value = serde_json::from_str(output).context("on_import 钩子的输出不是有效的 JSON")?;
+    let entries = output["servers"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("on_import 钩子的输出缺少 servers 数组"))?;
+
+    let mut result = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let id = entry["id"].as_str().ok_or_else(|| anyhow::anyhow!("on_import 钩子输出的服务器缺少 id"))?;
+        let index = servers
+            .iter()
+            .position(|server| server.id == id)
+            .ok_or_else(|| anyhow::anyhow!("on_import 钩子输出了未知的服务器 id: {}", id))?;
+        let mut server = servers.swap_remove(index);
+        let text = |key: &str| entry[key].as_str().map(str::to_string);
+        if let Some(name) = text("name") {
+            server.name = name;
+        }
+        if let Some(host) = text("host") {
+            server.host = host;
+        }
+        if let Some(username) = text("username") {
+            server.username = username;
+        }
+        if let Some(port) = entry["port"].as_u64() {
+            server.port = u16::try_from(port).with_context(|| format!("on_import 钩子输出的端口无效: {}", port))?;
+        }
+        // 分组和描述写 null 时清空
+        if entry.get("group").is_some() {
+            server.group = text("group");
+        }
+        if entry.get("description").is_some() {
+            server.description = text("description");
+        }
+        if let Some(aliases) = entry["aliases"].as_array() {
+            server.aliases = aliases.iter().filter_map(|alias| alias.as_str().map(str::to_string)).collect();
+        }
+        result.push(server);
+    }
+    Ok(result)
+}
+
+fn parse_scores(output: &str, expected: usize) -> Result<Vec<Option<i64>>> {
+    let output: Value = serde_json::from_str(output).context("fuzzy_score 钩子的输出不是有效的 JSON")?;
+    let scores = output["scores"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("fuzzy_score 钩子的输出缺少 scores 数组"))?;
+    if scores.len() != expected {
+        return Err(anyhow::anyhow!("fuzzy_score 钩子输出了 {} 个分数，应为 {} 个", scores.len(), expected));
+    }
+    Ok(scores.iter().map(|score| score.as_f64().map(|score| score.round() as i64)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    fn server(id: &str, name: &str) -> ServerConfig {
+        ServerConfig::new(id.into(), name.into(), format!("{}.example.com", name), 22, "root".into(), AuthType::Agent, Some("imported".into()), None, None)
+    }
+
+    #[test]
+    fn import_output_renames_regroups_and_drops_servers() {
+        let servers = vec![server("1", "web1"), server("2", "scratch"), server("3", "db1")];
+        let output = r#"{"servers": [
+            {"id": "3", "name": "prod-db1", "group": "prod", "port": 2222},
+            {"id": "1", "group": null, "aliases": ["w1"]}
+        ]}"#;
+        let result = apply_import_output(servers, output).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!((result[0].name.as_str(), result[0].group.as_deref(), result[0].port), ("prod-db1", Some("prod"), 2222));
+        assert_eq!((result[1].name.as_str(), result[1].group.as_deref()), ("web1", None));
+        assert_eq!(result[1].aliases, ["w1"]);
+
+        assert_eq!(apply_import_output(vec![server("1", "web1")], "\n").unwrap().len(), 1);
+        assert!(apply_import_output(vec![server("1", "web1")], r#"{"servers": [{"id": "9"}]}"#).is_err());
+    }
+
+    #[test]
+    fn runs_hooks_defined_in_the_rhai_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hooks.rhai");
+        std::fs::write(
+            &path,
+            r#"
+            fn on_connect(server) { server.name != "scratch" }
+            fn on_import(servers) {
+                servers.filter(|s| s.name != "scratch").map(|s| #{ id: s.id, name: "prod-" + s.name, port: 2222 })
+            }
+            fn resolve_secret(reference) { "pw-" + reference }
+            fn fuzzy_score(query, server) {
+                if server.name.contains(query) { 100 - server.name.len() } else if server.port == 22 { 0.6 }
+            }
+            "#,
+        )
+        .unwrap();
+        let script = Script::load(&path).unwrap();
+        assert!(script.defines("fuzzy_score", 2) && !script.defines("fuzzy_score", 1));
+
+        assert!(script.on_connect(&server("1", "web1")).is_ok());
+        assert!(script.on_connect(&server("2", "scratch")).is_err());
+
+        let imported = script.on_import(vec![server("1", "web1"), server("2", "scratch")]).unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!((imported[0].name.as_str(), imported[0].port), ("prod-web1", 2222));
+
+        assert_eq!(script.resolve_secret("prod/db1").unwrap(), "pw-prod/db1");
+
+        let mut db = server("3", "db1");
+        db.port = 2200;
+        let scores = script.fuzzy_scores("web", &[server("1", "web1"), server("2", "scratch"), db]).unwrap();
+        assert_eq!(scores, [Some(96), Some(1), None]);
+
+        std::fs::write(&path, "fn on_connect(server) {").unwrap();
+        assert!(Script::load(&path).is_err());
+    }
+
+    #[test]
+    fn parses_scores_and_checks_length() {
+        assert_eq!(parse_scores(r#"{"scores": [3, null, 1.6]}"#, 3).unwrap(), [Some(3), None, Some(2)]);
+        assert!(parse_scores(r#"{"scores": [1]}"#, 2).is_err());
+    }
+}
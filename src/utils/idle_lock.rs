@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::utils::secret::resolve_secret;
+
+/// 空闲锁定配置，由 `config.toml` 的 `idle_timeout` / `idle_lock_password` 设置
+#[derive(Debug, Clone)]
+pub struct IdleLockConfig {
+    pub timeout: Duration,
+    /// 解锁密码，支持 `env:` / `pass:` 等密钥引用；未设置时回车即可解锁
+    pub password: Option<String>,
+}
+
+static CONFIG: OnceLock<IdleLockConfig> = OnceLock::new();
+
+/// 设置本进程的空闲锁定，只在启动时调用一次
+pub fn configure(config: IdleLockConfig) {
+    let _ = CONFIG.set(config);
+}
+
+/// 锁定期间保留的远程输出上限，超出时丢弃较早的部分
+const MAX_HELD_OUTPUT: usize = 4 * 1024 * 1024;
+
+/// 交互式会话的空闲计时：超时前提示一次，超时后锁定屏幕，
+/// 锁定期间键盘输入不发送到远程，远程输出先保留，解锁后再显示
+#[derive(Debug)]
+pub struct IdleLock {
+    config: IdleLockConfig,
+    last_activity: Instant,
+    warned: bool,
+    locked: bool,
+    input: Vec<u8>,
+    held: Vec<u8>,
+}
+
+impl IdleLock {
+    /// 按全局配置创建，未启用空闲锁定时返回 None。解锁密码在会话开始时解析
+    pub fn from_config() -> Result<Option<Self>> {
+        let Some(config) = CONFIG.get() else {
+            return Ok(None);
+        };
+        let password = match &config.password {
+            Some(password) => Some(resolve_secret(password).context("无法解析 idle_lock_password")?),
+            None => None,
+        };
+        Ok(Some(IdleLock::new(IdleLockConfig { timeout: config.timeout, password })))
+    }
+
+    pub fn new(config: IdleLockConfig) -> Self {
+        IdleLock {
+            config,
+            last_activity: Instant::now(),
+            warned: false,
+            locked: false,
+            input: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// 记录一次键盘输入
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+        self.warned = false;
+    }
+
+    /// 定期调用，返回需要写到终端的提示（锁定前的警告或锁定画面）
+    pub fn tick(&mut self) -> Option<String> {
+        self.tick_at(Instant::now())
+    }
+
+    fn tick_at(&mut self, now: Instant) -> Option<String> {
+        if self.locked {
+            return None;
+        }
+
+        let idle = now.saturating_duration_since(self.last_activity);
+        let timeout = self.config.timeout;
+        if idle >= timeout {
+            self.locked = true;
+            self.input.clear();
+            // 切换到备用屏幕，隐藏会话内容，解锁后恢复
+            return Some(format!(
+                "\x1b[?1049h\x1b[2J\x1b[H[rssh] 会话空闲超过 {}，已锁定。\r\n{}",
+                format_duration(timeout),
+                self.prompt()
+            ));
+        }
+
+        // 超时前 1 分钟提示（超时较短时为前四分之一）
+        let warn_before = (timeout / 4).min(Duration::from_secs(60));
+        if !self.warned && idle + warn_before >= timeout {
+            self.warned = true;
+            return Some(format!(
+                "\r\n[rssh] 会话空闲，{}后锁定，有键盘输入即可保持会话\r\n",
+                format_duration(timeout - idle)
+            ));
+        }
+        None
+    }
+
+    /// 锁定期间收到的远程输出
+    pub fn hold(&mut self, data: &[u8]) {
+        self.held.extend_from_slice(data);
+        if self.held.len() > MAX_HELD_OUTPUT {
+            let excess = self.held.len() - MAX_HELD_OUTPUT;
+            self.held.drain(..excess);
+        }
+    }
+
+    /// 处理锁定期间的键盘输入，返回需要写到终端的内容；
+    /// 解锁时返回恢复屏幕的控制序列和锁定期间保留的远程输出
+    pub fn unlock_input(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        for &byte in input {
+            if !self.locked {
+                break;
+            }
+            match byte {
+                b'\r' | b'\n' => {
+                    let accepted = match &self.config.password {
+                        Some(password) => self.input == password.as_bytes(),
+                        None => true,
+                    };
+                    self.input.clear();
+                    if accepted {
+                        self.locked = false;
+                        self.touch();
+                        output.extend_from_slice(b"\x1b[?1049l");
+                        output.append(&mut self.held);
+                    } else {
+                        output.extend_from_slice(format!("\r\n密码错误。{}", self.prompt()).as_bytes());
+                    }
+                }
+                // 退格
+                0x7f | 0x08 => {
+                    self.input.pop();
+                }
+                // Ctrl+C / Ctrl+U 清空已输入的内容
+                0x03 | 0x15 => self.input.clear(),
+                _ => self.input.push(byte),
+            }
+        }
+        output
+    }
+
+    fn prompt(&self) -> &'static str {
+        if self.config.password.is_some() {
+            "输入解锁密码后回车: "
+        } else {
+            "按回车键解锁: "
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 60 && secs.is_multiple_of(60) {
+        format!("{} 分钟", secs / 60)
+    } else {
+        format!("{} 秒", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_locks_and_unlocks_with_password() {
+        let mut lock = IdleLock::new(IdleLockConfig {
+            timeout: Duration::from_secs(600),
+            password: Some("s3cret".into()),
+        });
+        let start = lock.last_activity;
+
+        assert!(lock.tick_at(start + Duration::from_secs(300)).is_none());
+        let warning = lock.tick_at(start + Duration::from_secs(545)).unwrap();
+        assert!(warning.contains("55 秒后锁定"));
+        // 只提示一次
+        assert!(lock.tick_at(start + Duration::from_secs(550)).is_none());
+
+        assert!(lock.tick_at(start + Duration::from_secs(600)).unwrap().contains("已锁定"));
+        assert!(lock.is_locked());
+
+        lock.hold(b"remote output");
+        assert!(String::from_utf8(lock.unlock_input(b"wrong\r")).unwrap().contains("密码错误"));
+        assert!(lock.is_locked());
+
+        let output = lock.unlock_input(b"s3cx\x7fret\r");
+        assert!(!lock.is_locked());
+        assert!(output.ends_with(b"remote output"));
+    }
+}
@@ -0,0 +1,155 @@
+//! scp、sftp、rsync、rclone 等长时间运行的子进程的 Ctrl+C 处理。
+//!
+//! 子进程在单独的进程组中运行，并接管终端前台，Ctrl+C 直接发给整个子进程组，
+//! rssh 自己不会被中断：子进程退出后结束进程组中残留的进程（如 scp 启动的 ssh），
+//! 再由调用方删除临时文件、说明传输到了哪里。
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::fmt;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+/// 正在运行的子进程组，没有时为 0
+static CHILD_PGID: AtomicI32 = AtomicI32::new(0);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static HANDLER: Once = Once::new();
+
+/// 残留进程收到 SIGTERM 后等待多久再 SIGKILL
+const TERMINATE_GRACE: Duration = Duration::from_secs(2);
+
+/// 子进程被 Ctrl+C 中断
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("操作已被中断")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// 错误是否由 Ctrl+C 中断引起
+pub fn is_interrupted(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Interrupted>().is_some()
+}
+
+/// 不在终端前台运行时（例如输出被重定向），SIGINT 发给 rssh，由这里转发给子进程组；
+/// 没有子进程时按默认行为退出
+fn install_handler() {
+    HANDLER.call_once(|| {
+        let _ = ctrlc::set_handler(|| {
+            let pgid = CHILD_PGID.load(Ordering::SeqCst);
+            if pgid == 0 {
+                crate::utils::secure_temp::cleanup();
+                std::process::exit(130);
+            }
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            unsafe { libc::kill(-pgid, libc::SIGINT) };
+        });
+    });
+}
+
+/// 子进程运行期间把终端前台交给子进程组，结束后交还给 rssh
+struct Foreground {
+    previous: Option<libc::pid_t>,
+}
+
+impl Foreground {
+    fn give_to(pgid: libc::pid_t) -> Self {
+        let previous = unsafe {
+            let ours = libc::getpgrp();
+            if libc::isatty(libc::STDIN_FILENO) == 1 && libc::tcgetpgrp(libc::STDIN_FILENO) == ours {
+                libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+                Some(ours)
+            } else {
+                None
+            }
+        };
+        // 子进程可能在交出前台之前就读了终端（密码提示）而被 SIGTTIN 暂停
+        unsafe { libc::kill(-pgid, libc::SIGCONT) };
+        Foreground { previous }
+    }
+}
+
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.previous {
+            // 此时 rssh 在后台，设置前台进程组需要忽略 SIGTTOU
+            unsafe {
+                let handler = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+                libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+                libc::signal(libc::SIGTTOU, handler);
+            }
+        }
+    }
+}
+
+/// 运行命令直到结束，返回退出状态；被 Ctrl+C 中断时结束整个进程组并返回 [`Interrupted`]
+pub fn status(command: &mut Command) -> Result<ExitStatus> {
+    install_handler();
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .process_group(0)
+        .spawn()
+        .with_context(|| format!("无法执行 {}", program))?;
+    let pgid = child.id() as libc::pid_t;
+    CHILD_PGID.store(pgid, Ordering::SeqCst);
+
+    let foreground = Foreground::give_to(pgid);
+    let status = child.wait();
+    drop(foreground);
+    CHILD_PGID.store(0, Ordering::SeqCst);
+    let status = status.with_context(|| format!("等待 {} 结束失败", program))?;
+
+    let by_signal = matches!(status.signal(), Some(libc::SIGINT | libc::SIGTERM | libc::SIGHUP));
+    if INTERRUPTED.swap(false, Ordering::SeqCst) || by_signal || status.code() == Some(130) {
+        eprintln!("\n{} 已中断，正在结束 {} 及其子进程", "⚠".bright_yellow(), program);
+        terminate_group(pgid);
+        return Err(Interrupted.into());
+    }
+    Ok(status)
+}
+
+/// 结束进程组中残留的进程：先 SIGTERM，超时后 SIGKILL
+fn terminate_group(pgid: libc::pid_t) {
+    let alive = || unsafe { libc::kill(-pgid, 0) } == 0;
+    if !alive() {
+        return;
+    }
+    unsafe { libc::kill(-pgid, libc::SIGTERM) };
+    let start = Instant::now();
+    while alive() && start.elapsed() < TERMINATE_GRACE {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    if alive() {
+        unsafe { libc::kill(-pgid, libc::SIGKILL) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupted_child_takes_its_process_group_down() {
+        let status = status(Command::new("sh").args(["-c", "exit 3"])).unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        // 后台的 sleep 在非交互 shell 中忽略 SIGINT，模拟中断后残留的进程
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("sleep 30 & echo $! > {}; kill -INT $$", pid_file.display());
+        let error = super::status(Command::new("sh").args(["-c", &script])).unwrap_err();
+        assert!(is_interrupted(&error));
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        let state = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        // 进程已不存在，或只剩等待回收的僵尸进程
+        assert!(state.is_empty() || state.contains(") Z "), "{}", state);
+    }
+}
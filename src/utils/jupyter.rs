@@ -0,0 +1,116 @@
+use anyhow::Result;
+use colored::*;
+use std::time::Duration;
+
+use crate::utils::session_escape::Forward;
+use crate::utils::ssh::SshClient;
+use crate::utils::tunnel::local_host;
+
+/// 等待新启动的 notebook 输出访问地址的最长时间（秒）
+const START_TIMEOUT: u64 = 60;
+
+/// 远程 notebook 的访问信息，从 `jupyter server list` 或启动日志中解析
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notebook {
+    /// http 或 https
+    pub scheme: String,
+    pub port: u16,
+    /// 关闭了令牌认证时为 None
+    pub token: Option<String>,
+}
+
+impl Notebook {
+    /// 经本地转发访问的地址，带上令牌后浏览器无需再输入
+    pub fn local_url(&self, forward: &Forward) -> String {
+        let mut url = format!("{}://{}:{}/", self.scheme, local_host(forward), forward.port);
+        if let Some(token) = &self.token {
+            url.push_str(&format!("?token={}", token));
+        }
+        url
+    }
+}
+
+/// 从 jupyter 的输出中找到监听 `port` 的 notebook，例如
+/// `http://localhost:8888/?token=abc :: /home/user` 或 `http://127.0.0.1:8888/lab?token=abc`
+pub fn parse_notebook(output: &str, port: u16) -> Option<Notebook> {
+    output.split_whitespace().find_map(|word| {
+        let (scheme, rest) = word.split_once("://")?;
+        if scheme != "http" && scheme != "https" {
+            return None;
+        }
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let url_port: u16 = authority.rsplit_once(':')?.1.parse().ok()?;
+        if url_port != port {
+            return None;
+        }
+        let token = path
+            .split_once('?')
+            .and_then(|(_, query)| query.split('&').find_map(|pair| pair.strip_prefix("token=")))
+            .filter(|token| !token.is_empty())
+            .map(str::to_string);
+        Some(Notebook { scheme: scheme.to_string(), port, token })
+    })
+}
+
+/// 连接已在远程 `port` 上运行的 notebook，没有时在后台启动一个（`jupyter lab` 或 `jupyter notebook`），
+/// 并等待其输出访问地址。启动的 notebook 在 rssh 退出后继续运行，下次可以直接连接
+pub fn ensure_notebook(client: &SshClient, jupyter: &str, lab: bool, port: u16) -> Result<Notebook> {
+    let list = format!("{0} server list 2>/dev/null; {0} notebook list 2>/dev/null", jupyter);
+    let (stdout, _, _) = client.execute_command(&list)?;
+    if let Some(notebook) = parse_notebook(&stdout, port) {
+        println!("连接远程已在运行的 notebook（端口 {}）", port);
+        return Ok(notebook);
+    }
+
+    let app = if lab { "lab" } else { "notebook" };
+    let log = format!("$HOME/.rssh-jupyter-{}.log", port);
+    let start = format!(
+        "nohup {} {} --no-browser --ip=127.0.0.1 --port={} --port-retries=0 > {} 2>&1 < /dev/null &",
+        jupyter, app, port, log
+    );
+    println!("在远程启动 jupyter {}（端口 {}，日志 {}）...", app, port, log);
+    client.execute_command(&start)?;
+
+    let mut output = String::new();
+    for _ in 0..START_TIMEOUT {
+        std::thread::sleep(Duration::from_secs(1));
+        output = client.execute_command(&format!("cat {}", log))?.0;
+        if let Some(notebook) = parse_notebook(&output, port) {
+            return Ok(notebook);
+        }
+        if output.contains("command not found") || output.contains("No such file or directory") {
+            break;
+        }
+    }
+
+    let tail: Vec<&str> = output.lines().rev().take(10).collect();
+    let tail: Vec<&str> = tail.into_iter().rev().collect();
+    eprintln!("{}", tail.join("\n").dimmed());
+    Err(anyhow::anyhow!(
+        "远程 jupyter 没有在端口 {} 上启动，请检查日志 {}；jupyter 不在 PATH 中时可用 --jupyter 指定完整路径",
+        port, log
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::session_escape::ForwardKind;
+
+    #[test]
+    fn parses_notebook_url_and_token() {
+        let list = "Currently running servers:\n\
+                    http://localhost:8889/?token=other :: /srv\n\
+                    http://localhost:8888/?token=abc123 :: /home/ds\n";
+        let notebook = parse_notebook(list, 8888).unwrap();
+        assert_eq!(notebook.token.as_deref(), Some("abc123"));
+
+        let log = "[I 10:00:00 ServerApp] Jupyter Server is running at:\n\
+                   [I 10:00:00 ServerApp]     http://127.0.0.1:8888/lab?token=f00&foo=bar\n";
+        assert_eq!(parse_notebook(log, 8888).unwrap().token.as_deref(), Some("f00"));
+        assert!(parse_notebook(log, 9999).is_none());
+
+        let forward = Forward::parse(ForwardKind::Local, "18888:127.0.0.1:8888").unwrap();
+        assert_eq!(notebook.local_url(&forward), "http://localhost:18888/?token=abc123");
+    }
+}
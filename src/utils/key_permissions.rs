@@ -0,0 +1,118 @@
+//! 私钥文件权限检查。私钥可以被其他用户读取时 ssh 会以 "UNPROTECTED PRIVATE KEY FILE"
+//! 拒绝使用，错误信息里看不出是哪台服务器的哪个配置导致的，所以在连接和添加服务器时提前检查。
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::utils::ssh_config::expand_tilde;
+
+/// 权限过宽的私钥文件或 .ssh 目录
+#[derive(Debug, PartialEq, Eq)]
+pub struct PermissionIssue {
+    pub path: PathBuf,
+    pub mode: u32,
+    /// 修复后的权限
+    pub wanted: u32,
+}
+
+/// 检查私钥文件（不能被其他用户访问）和所在的 .ssh 目录（不能被其他用户写入）
+pub fn check(key_path: &str) -> Vec<PermissionIssue> {
+    let key = PathBuf::from(expand_tilde(key_path));
+    let mut issues = Vec::new();
+
+    if let Ok(meta) = std::fs::metadata(&key) {
+        let mode = meta.permissions().mode() & 0o777;
+        if meta.is_file() && mode & 0o077 != 0 {
+            issues.push(PermissionIssue { path: key.clone(), mode, wanted: mode & 0o700 | 0o600 });
+        }
+    }
+
+    if let Some(dir) = key.parent().filter(|dir| dir.file_name().is_some_and(|name| name == ".ssh")) {
+        if let Ok(meta) = std::fs::metadata(dir) {
+            let mode = meta.permissions().mode() & 0o777;
+            if mode & 0o022 != 0 {
+                issues.push(PermissionIssue { path: dir.to_path_buf(), mode, wanted: 0o700 });
+            }
+        }
+    }
+    issues
+}
+
+/// 连接或添加服务器前检查私钥权限。`fix` 为 true（`--fix-perms`）时直接修复，
+/// 否则在终端中询问；不修复时只提示，不阻止连接
+pub fn ensure(key_path: &str, fix: bool) -> Result<()> {
+    let issues = check(key_path);
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!(
+            "{} {} 的权限为 {:04o}，ssh 会拒绝使用（应为 {:04o}）",
+            "⚠".bright_yellow(),
+            issue.path.display(),
+            issue.mode,
+            issue.wanted
+        );
+    }
+
+    let fix = fix || (io::stdin().is_terminal() && {
+        print!("是否修复权限？[Y/n] ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        !input.trim().eq_ignore_ascii_case("n")
+    });
+    if !fix {
+        println!("可以加 {} 自动修复", "--fix-perms".bright_blue());
+        return Ok(());
+    }
+
+    for issue in &issues {
+        set_mode(&issue.path, issue.wanted)?;
+        println!("{} 已将 {} 的权限改为 {:04o}", "✓".bright_green(), issue.path.display(), issue.wanted);
+    }
+    Ok(())
+}
+
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("无法修改 {} 的权限", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_and_fixes_readable_key_and_writable_ssh_dir() {
+        let home = tempfile::tempdir().unwrap();
+        let ssh_dir = home.path().join(".ssh");
+        std::fs::create_dir(&ssh_dir).unwrap();
+        let key = ssh_dir.join("id_ed25519");
+        std::fs::write(&key, "key").unwrap();
+        set_mode(&ssh_dir, 0o775).unwrap();
+        set_mode(&key, 0o644).unwrap();
+
+        let key_path = key.display().to_string();
+        assert_eq!(
+            check(&key_path),
+            vec![
+                PermissionIssue { path: key.clone(), mode: 0o644, wanted: 0o600 },
+                PermissionIssue { path: ssh_dir.clone(), mode: 0o775, wanted: 0o700 },
+            ]
+        );
+
+        ensure(&key_path, true).unwrap();
+        assert!(check(&key_path).is_empty());
+        assert_eq!(std::fs::metadata(&key).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // 0755 的 .ssh 目录和 0400 的私钥 ssh 都接受
+        set_mode(&ssh_dir, 0o755).unwrap();
+        set_mode(&key, 0o400).unwrap();
+        assert!(check(&key_path).is_empty());
+    }
+}
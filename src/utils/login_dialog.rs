@@ -0,0 +1,138 @@
+//! 登录后的多步对话：网络设备的 enable 密码、堡垒机的菜单选择等。
+//!
+//! 内置库连接打开 shell 后，按顺序等待每一步的正则匹配远程输出，匹配后发送对应内容并回车。
+//! 远程输出照常显示在终端上，发送的内容（可能是密码）不回显。
+
+use anyhow::{Context, Result};
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::models::DialogStep;
+
+/// 每一步最多等待的时间
+pub const STEP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 超时时最多显示的远程输出
+const TAIL_LEN: usize = 200;
+
+/// 在非阻塞的通道上执行登录对话，远程输出交给 `output`。
+/// 某一步超时或连接关闭时返回错误，说明卡在哪一步以及远程最后发送的内容
+pub fn run<S, F>(channel: &mut S, steps: &[DialogStep], timeout: Duration, mut output: F) -> Result<()>
+where
+    S: Read + Write,
+    F: FnMut(&[u8]) -> Result<()>,
+{
+    let mut buf = [0u8; 4096];
+    // 上一步匹配之后收到的输出
+    let mut pending = String::new();
+    for (index, step) in steps.iter().enumerate() {
+        let pattern = step.pattern()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(found) = pattern.find(&pending) {
+                pending.drain(..found.end());
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(unmatched(index, step, &pending, "超时"));
+            }
+            match channel.read(&mut buf) {
+                Ok(0) => return Err(unmatched(index, step, &pending, "连接已关闭")),
+                Ok(n) => {
+                    output(&buf[..n])?;
+                    pending.push_str(&String::from_utf8_lossy(&buf[..n]));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(5)),
+                Err(e) => return Err(e).with_context(|| "读取远程输出失败"),
+            }
+        }
+        send(channel, format!("{}\r", step.send).as_bytes())
+            .with_context(|| format!("登录对话第 {} 步发送失败", index + 1))?;
+    }
+    Ok(())
+}
+
+fn send<S: Write>(channel: &mut S, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        match channel.write(data) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(1)),
+            Err(e) => return Err(e),
+        }
+    }
+    channel.flush()
+}
+
+fn unmatched(index: usize, step: &DialogStep, pending: &str, reason: &str) -> anyhow::Error {
+    let tail: String = {
+        let chars: Vec<char> = pending.chars().collect();
+        chars[chars.len().saturating_sub(TAIL_LEN)..].iter().collect()
+    };
+    let received = if tail.trim().is_empty() {
+        "远程没有发送任何内容".to_string()
+    } else {
+        format!("远程最后发送的内容: {:?}", tail)
+    };
+    anyhow::anyhow!("登录对话第 {} 步（等待 {}）{}，{}", index + 1, step.expect, reason, received)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按顺序返回预设的输出，记录写入的内容
+    struct FakeChannel {
+        reads: Vec<&'static str>,
+        written: Vec<u8>,
+    }
+
+    impl Read for FakeChannel {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.reads.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let data = self.reads.remove(0).as_bytes();
+            buf[..data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    impl Write for FakeChannel {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn steps(specs: &[&str]) -> Vec<DialogStep> {
+        specs.iter().map(|spec| DialogStep::parse(spec).unwrap()).collect()
+    }
+
+    #[test]
+    fn answers_each_prompt_in_order() {
+        let mut channel = FakeChannel { reads: vec!["switch>", "\r\nPass", "word: ", "switch#"], written: Vec::new() };
+        let mut shown = Vec::new();
+        let steps = steps(&["> => enable", "Password: => s3cret", "# => terminal length 0"]);
+        run(&mut channel, &steps, STEP_TIMEOUT, |data| {
+            shown.extend_from_slice(data);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(channel.written, b"enable\rs3cret\rterminal length 0\r");
+        assert_eq!(shown, b"switch>\r\nPassword: switch#");
+    }
+
+    #[test]
+    fn reports_what_the_remote_sent_on_timeout() {
+        let mut channel = FakeChannel { reads: vec!["Select server:"], written: Vec::new() };
+        let err = run(&mut channel, &steps(&["Opt> => 1"]), Duration::from_millis(50), |_| Ok(())).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("第 1 步") && message.contains("Select server:"), "{}", message);
+        assert!(channel.written.is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+//! 输入宏（`config.toml` 的 `[macros.<名称>]`）：内置库模式的交互式会话中，按功能键发送保存的命令，
+//! 或把输入的缩写展开为完整的文本，适合命令行重复度高的网络设备等。
+//!
+//! `keys` 把 F1–F12 映射为命令，按下时发送命令并回车；`expand` 把缩写（例如 `;;ll`）替换为文本，不回车。
+//! 输入的内容可能是某个缩写的开头时先不发送，直到能确定是否为缩写，因此缩写最好以不常用的字符开头。
+//! 可以用 `servers` / `groups` 限定宏生效的服务器，多组宏对同一服务器生效时合并，名称靠后的优先。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::models::ServerConfig;
+
+/// `config.toml` 中的一个 `[macros.<名称>]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroSettings {
+    /// 只对这些服务器（名称或别名）生效
+    pub servers: Vec<String>,
+    /// 只对这些分组的服务器生效
+    pub groups: Vec<String>,
+    /// 功能键（`F1`–`F12`）发送的命令，自动加回车
+    pub keys: HashMap<String, String>,
+    /// 缩写展开为的文本
+    pub expand: HashMap<String, String>,
+}
+
+static MACROS: OnceLock<Vec<(String, MacroSettings)>> = OnceLock::new();
+
+/// 设置输入宏，只在启动时调用一次。无法识别的按键跳过并打印警告
+pub fn configure(settings: HashMap<String, MacroSettings>) {
+    let mut sets: Vec<(String, MacroSettings)> = settings.into_iter().collect();
+    sets.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, set) in &mut sets {
+        set.keys.retain(|key, _| {
+            let known = key_sequences(key).is_some();
+            if !known {
+                eprintln!("警告: 输入宏 {} 中无法识别的按键 {}（支持 F1–F12）", name, key);
+            }
+            known
+        });
+        set.expand.retain(|abbreviation, _| !abbreviation.is_empty());
+    }
+    let _ = MACROS.set(sets);
+}
+
+/// 功能键在终端中的输入序列（xterm 与 VT220 风格）
+fn key_sequences(key: &str) -> Option<&'static [&'static [u8]]> {
+    let sequences: &'static [&'static [u8]] = match key.to_ascii_uppercase().as_str() {
+        "F1" => &[b"\x1bOP", b"\x1b[11~"],
+        "F2" => &[b"\x1bOQ", b"\x1b[12~"],
+        "F3" => &[b"\x1bOR", b"\x1b[13~"],
+        "F4" => &[b"\x1bOS", b"\x1b[14~"],
+        "F5" => &[b"\x1b[15~"],
+        "F6" => &[b"\x1b[17~"],
+        "F7" => &[b"\x1b[18~"],
+        "F8" => &[b"\x1b[19~"],
+        "F9" => &[b"\x1b[20~"],
+        "F10" => &[b"\x1b[21~"],
+        "F11" => &[b"\x1b[23~"],
+        "F12" => &[b"\x1b[24~"],
+        _ => return None,
+    };
+    Some(sequences)
+}
+
+/// 一个会话中生效的输入宏
+#[derive(Debug, Clone)]
+pub struct InputMacros {
+    /// 输入序列和替换成的内容
+    rules: Vec<(Vec<u8>, Vec<u8>)>,
+    /// 可能是某个规则开头、尚未发送的输入
+    pending: Vec<u8>,
+}
+
+impl InputMacros {
+    /// 按全局配置创建对该服务器生效的输入宏，没有时返回 None
+    pub fn for_server(server: &ServerConfig) -> Option<Self> {
+        let sets = MACROS.get()?.iter().filter(|(_, set)| server.in_scope(&set.servers, &set.groups));
+        let mut rules: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+        for (_, set) in sets {
+            for (key, command) in &set.keys {
+                for sequence in key_sequences(key).unwrap_or_default() {
+                    rules.insert(sequence.to_vec(), format!("{}\r", command).into_bytes());
+                }
+            }
+            for (abbreviation, text) in &set.expand {
+                rules.insert(abbreviation.as_bytes().to_vec(), text.as_bytes().to_vec());
+            }
+        }
+        (!rules.is_empty()).then(|| InputMacros { rules: rules.into_iter().collect(), pending: Vec::new() })
+    }
+
+    /// 处理一段键盘输入，返回应发送到远程的内容
+    pub fn feed(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len());
+        for &byte in input {
+            self.pending.push(byte);
+            self.resolve(&mut output, false);
+        }
+        // 功能键的序列总是一次读到，以 ESC 开头的内容不等待下一次输入，单独按 ESC 不会被延迟
+        if self.pending.first() == Some(&0x1b) {
+            self.resolve(&mut output, true);
+        }
+        output
+    }
+
+    /// 确定 `pending` 中能确定的部分：完整匹配且不会更长时替换，不可能匹配时发送第一个字节后继续
+    fn resolve(&mut self, output: &mut Vec<u8>, flush: bool) {
+        while !self.pending.is_empty() {
+            let longer = self.rules.iter().any(|(from, _)| from.len() > self.pending.len() && from.starts_with(&self.pending));
+            if longer && !flush {
+                return;
+            }
+            if let Some((_, to)) = self.rules.iter().find(|(from, _)| *from == self.pending) {
+                output.extend_from_slice(to);
+                self.pending.clear();
+                return;
+            }
+            // 不再可能匹配：替换最长的已完成规则，或发送第一个字节，剩下的重新匹配
+            let prefix = self
+                .rules
+                .iter()
+                .filter(|(from, _)| self.pending.starts_with(from))
+                .max_by_key(|(from, _)| from.len());
+            let consumed = match prefix {
+                Some((from, to)) => {
+                    output.extend_from_slice(to);
+                    from.len()
+                },
+                None => {
+                    output.push(self.pending[0]);
+                    1
+                },
+            };
+            self.pending.drain(..consumed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    fn macros(rules: &[(&[u8], &[u8])]) -> InputMacros {
+        InputMacros { rules: rules.iter().map(|(from, to)| (from.to_vec(), to.to_vec())).collect(), pending: Vec::new() }
+    }
+
+    #[test]
+    fn expands_abbreviations_and_function_keys() {
+        let mut macros = macros(&[(b";;l", b"ls"), (b";;ll", b"ls -la"), (b"\x1bOQ", b"show version\r")]);
+        assert_eq!(macros.feed(b"echo ;;"), b"echo ");
+        assert_eq!(macros.feed(b"ll"), b"ls -la");
+        assert_eq!(macros.feed(b" ;;lx ;"), b" lsx ");
+        assert_eq!(macros.feed(b"a\r"), b";a\r");
+        assert_eq!(macros.feed(b"\x1bOQ"), b"show version\r");
+        // 单独的 ESC 和其它转义序列不延迟
+        assert_eq!(macros.feed(b"\x1b"), b"\x1b");
+        assert_eq!(macros.feed(b"\x1b[A"), b"\x1b[A");
+    }
+
+    #[test]
+    fn merges_sets_for_the_server() {
+        let set = |groups: &[&str], keys: &[(&str, &str)], expand: &[(&str, &str)]| MacroSettings {
+            groups: groups.iter().map(|group| group.to_string()).collect(),
+            keys: keys.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            expand: expand.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Default::default()
+        };
+        configure(HashMap::from([
+            ("common".to_string(), set(&[], &[("F2", "uptime"), ("F13", "x")], &[(";;ll", "ls -la")])),
+            ("switches".to_string(), set(&["switches"], &[("f2", "show version")], &[])),
+        ]));
+        let server = |group: &str| {
+            ServerConfig::new("id".into(), "sw1".into(), "10.0.0.2".into(), 22, "admin".into(), AuthType::Agent, Some(group.into()), None, None)
+        };
+
+        let mut switch = InputMacros::for_server(&server("switches")).unwrap();
+        assert_eq!(switch.feed(b"\x1b[12~;;ll"), b"show version\rls -la");
+        let mut web = InputMacros::for_server(&server("web")).unwrap();
+        assert_eq!(web.feed(b"\x1bOQ"), b"uptime\r");
+    }
+}
@@ -10,6 +10,61 @@ pub mod handle_rzsz;
 pub mod server_info;
 pub mod rclone;
 pub mod terminal;
+pub mod secret;
+pub mod picker;
+pub mod ssh_uri;
+pub mod raw_terminal;
+pub mod console;
+pub mod telnet;
+pub mod watch;
+pub mod deploy;
+pub mod cp;
+pub mod fleet;
+pub mod connection_pool;
+pub mod audit;
+pub mod session_escape;
+pub mod session_registry;
+pub mod idle_lock;
+pub mod motd;
+pub mod badge;
+pub mod tunnel;
+pub mod jupyter;
+pub mod desktop;
+pub mod git_remote;
+pub mod passwd;
+pub mod remote_user;
+pub mod bootstrap;
+pub mod sshd_audit;
+pub mod ca;
+pub mod recording;
+pub mod share;
+pub mod ssh_command_builder;
+pub mod remote_probe;
+pub mod multi_upload;
+pub mod fleet_diff;
+pub mod template_push;
+pub mod notification;
+pub mod address_fallback;
+pub mod dns_cache;
+pub mod network_location;
+pub mod interrupt;
+pub mod secure_temp;
+pub mod key_permissions;
+pub mod sudo;
+pub mod login_dialog;
+pub mod siem;
+pub mod credential_vault;
+pub mod traffic;
+pub mod plugin;
+pub mod hooks;
+pub mod completions;
+pub mod output;
+pub mod retention;
+pub mod clipboard;
+pub mod scrollback;
+pub mod triggers;
+pub mod macros;
+pub mod progress;
 
 pub use ssh::*;
 pub use ssh_config::*;
@@ -19,6 +74,7 @@ pub use file_transfer::{
     upload_file, download_file,
     upload_file_sftp, download_file_sftp,
     upload_file_kitty, download_file_kitty,
+    upload_file_rsync, download_file_rsync, RsyncOptions,
     upload_file_auto, download_file_auto
 };
 pub use self::ssh::SshClient;
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::models::LoginBanner;
+use crate::utils::ssh_config::sanitize_host_alias;
+
+/// 远程连续这么久没有输出时，认为登录信息已经结束
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+/// 最多等待这么久，避免登录信息一直不结束时卡住会话
+const MAX_WAIT: Duration = Duration::from_secs(3);
+
+/// 登录横幅为 capture 时保存登录信息的目录，由 rssh 启动时设置
+static CAPTURE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// 设置保存登录信息的目录，只在启动时调用一次
+pub fn set_capture_dir(dir: PathBuf) {
+    let _ = CAPTURE_DIR.set(dir);
+}
+
+/// 服务器登录信息的保存位置 `<目录>/<服务器名>.txt`，没有设置目录时为 None
+pub fn capture_path(server: &str) -> Option<PathBuf> {
+    CAPTURE_DIR.get().map(|dir| dir.join(format!("{}.txt", sanitize_host_alias(server))))
+}
+
+/// 隐藏或保存会话开头的登录信息（MOTD、Last login 等）。
+///
+/// 会话开始后先缓存远程输出，直到输出停顿或超时；之后只把最后一行（通常是 shell 提示符）
+/// 显示出来，前面的内容丢弃或写入文件。
+pub struct MotdFilter {
+    mode: LoginBanner,
+    capture_path: Option<PathBuf>,
+    buf: Vec<u8>,
+    started: Instant,
+    last_data: Option<Instant>,
+    done: bool,
+}
+
+impl MotdFilter {
+    /// `capture_path` 只在 `LoginBanner::Capture` 时使用
+    pub fn new(mode: LoginBanner, capture_path: Option<PathBuf>) -> Self {
+        MotdFilter {
+            mode,
+            capture_path,
+            buf: Vec::new(),
+            started: Instant::now(),
+            last_data: None,
+            done: mode == LoginBanner::Show,
+        }
+    }
+
+    /// 处理远程输出，返回应写到终端的内容
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        if self.done {
+            return data.to_vec();
+        }
+        self.buf.extend_from_slice(data);
+        self.last_data = Some(Instant::now());
+        Vec::new()
+    }
+
+    /// 定期调用，登录信息结束时返回其后应显示的内容
+    pub fn poll(&mut self) -> Vec<u8> {
+        if self.done {
+            return Vec::new();
+        }
+        let settled = self.last_data.is_some_and(|t| t.elapsed() >= QUIET_PERIOD);
+        if !settled && self.started.elapsed() < MAX_WAIT {
+            return Vec::new();
+        }
+
+        self.done = true;
+        let buf = std::mem::take(&mut self.buf);
+        let (banner, rest) = split_banner(&buf);
+        if self.mode == LoginBanner::Capture && !banner.is_empty() {
+            if let Some(path) = &self.capture_path {
+                let saved = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(path, banner));
+                if let Err(e) = saved {
+                    eprint!("无法保存登录信息到 {}: {}\r\n", path.display(), e);
+                }
+            }
+        }
+        rest.to_vec()
+    }
+}
+
+/// 拆分为登录信息和最后一行未结束的内容（提示符）
+fn split_banner(buf: &[u8]) -> (&[u8], &[u8]) {
+    match buf.iter().rposition(|&b| b == b'\n') {
+        Some(pos) => buf.split_at(pos + 1),
+        None => (&[], buf),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_prompt() {
+        let (banner, rest) = split_banner(b"Welcome to Ubuntu\r\nLast login: today\r\nroot@web:~# ");
+        assert_eq!(banner, b"Welcome to Ubuntu\r\nLast login: today\r\n");
+        assert_eq!(rest, b"root@web:~# ");
+
+        let mut filter = MotdFilter::new(LoginBanner::Show, None);
+        assert_eq!(filter.feed(b"motd\r\n"), b"motd\r\n");
+    }
+
+    #[test]
+    fn holds_chunked_banner_until_output_settles() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = dir.path().join("web.txt");
+        let chunks: [&[u8]; 3] = [b"Welcome to Ub", b"untu\r\nLast login: to", b"day\r\nroot@web:~# "];
+
+        for (mode, path) in [(LoginBanner::Quiet, None), (LoginBanner::Capture, Some(capture.clone()))] {
+            let mut filter = MotdFilter::new(mode, path);
+            for chunk in chunks {
+                assert!(filter.feed(chunk).is_empty());
+                assert!(filter.poll().is_empty());
+            }
+            std::thread::sleep(QUIET_PERIOD);
+            assert_eq!(filter.poll(), b"root@web:~# ");
+            // 登录信息结束后原样输出
+            assert_eq!(filter.feed(b"ls\r\n"), b"ls\r\n");
+            assert!(filter.poll().is_empty());
+        }
+        assert_eq!(std::fs::read(&capture).unwrap(), b"Welcome to Ubuntu\r\nLast login: today\r\n");
+    }
+}
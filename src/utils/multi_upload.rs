@@ -0,0 +1,268 @@
+//! `rssh upload --group`：把同一个文件并发上传到分组内的所有服务器，
+//! 每台主机一行进度，结束后打印成功/失败汇总表。
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::ServerConfig;
+use crate::utils::cp::resolve_target;
+use crate::utils::deploy::sftp_mkdir_p;
+use crate::utils::ssh::SshClient;
+
+/// 每次写入的块大小，也是进度更新的粒度
+const CHUNK: usize = 256 * 1024;
+
+/// 单台主机的上传状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadState {
+    Waiting,
+    Connecting,
+    Uploading,
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct HostUpload {
+    pub server_name: String,
+    pub state: UploadState,
+    pub sent: u64,
+    /// 实际写入的远程路径
+    pub target: Option<PathBuf>,
+    pub duration: Duration,
+}
+
+impl HostUpload {
+    pub fn success(&self) -> bool {
+        self.state == UploadState::Done
+    }
+}
+
+/// 以 KB/MB/GB 显示大小
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// 一台主机的进度行，例如 `web-1   [##########----------]  50%  1.0 MB/2.0 MB`
+pub fn progress_line(upload: &HostUpload, total: u64, name_width: usize) -> String {
+    const WIDTH: usize = 20;
+    let name = format!("{:<width$}", upload.server_name, width = name_width);
+    match &upload.state {
+        UploadState::Waiting => format!("{}  等待中", name),
+        UploadState::Connecting => format!("{}  连接中", name),
+        UploadState::Failed(error) => format!("{}  {} {}", name, "✗".bright_red(), error),
+        UploadState::Uploading | UploadState::Done => {
+            let percent = (upload.sent * 100).checked_div(total).unwrap_or(100) as usize;
+            let filled = percent * WIDTH / 100;
+            let mark = if upload.success() { "✓".bright_green().to_string() } else { " ".to_string() };
+            format!(
+                "{}  [{}{}] {:>3}%  {}/{} {}",
+                name,
+                "#".repeat(filled),
+                "-".repeat(WIDTH - filled),
+                percent,
+                format_size(upload.sent),
+                format_size(total),
+                mark
+            )
+        }
+    }
+}
+
+/// 上传到一台主机，`on_progress` 在状态或已发送字节数变化时调用
+fn upload_one(
+    server: &ServerConfig,
+    local_path: &Path,
+    remote_path: &str,
+    mut on_progress: impl FnMut(UploadState, u64, Option<PathBuf>),
+) -> Result<()> {
+    on_progress(UploadState::Connecting, 0, None);
+    let client = SshClient::connect(server)?;
+    let sftp = client.sftp()?;
+
+    let remote = Path::new(remote_path);
+    let remote_is_dir = sftp.stat(remote).map(|stat| stat.is_dir()).unwrap_or(false);
+    if remote_path.ends_with('/') && !remote_is_dir {
+        sftp_mkdir_p(&sftp, remote)?;
+    }
+    let target = resolve_target(local_path, remote, remote_is_dir);
+    on_progress(UploadState::Uploading, 0, Some(target.clone()));
+
+    let mut reader = std::fs::File::open(local_path)
+        .with_context(|| format!("无法打开文件: {}", local_path.display()))?;
+    let mut writer = sftp
+        .create(&target)
+        .with_context(|| format!("无法创建远程文件: {}", target.display()))?;
+    let mut buffer = vec![0u8; CHUNK];
+    let mut sent = 0u64;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        writer
+            .write_all(&buffer[..read])
+            .with_context(|| format!("写入远程文件失败: {}", target.display()))?;
+        sent += read as u64;
+        on_progress(UploadState::Uploading, sent, Some(target.clone()));
+    }
+    writer.flush()?;
+    on_progress(UploadState::Done, sent, Some(target));
+    Ok(())
+}
+
+/// 重绘所有主机的进度行；`previous` 为上次绘制的行数
+fn redraw(uploads: &[HostUpload], total: u64, name_width: usize, previous: usize) -> usize {
+    let mut out = std::io::stdout().lock();
+    if previous > 0 {
+        let _ = write!(out, "\x1b[{}A", previous);
+    }
+    for upload in uploads {
+        let _ = writeln!(out, "\x1b[2K{}", progress_line(upload, total, name_width));
+    }
+    let _ = out.flush();
+    uploads.len()
+}
+
+/// 并发上传到多台主机，最多同时 `parallel` 台。
+/// `remote_path` 是已存在的目录或以 `/` 结尾时，文件放到该目录下（目录不存在时创建）
+pub fn upload_to_hosts(servers: &[ServerConfig], local_path: &Path, remote_path: &str, parallel: usize) -> Result<Vec<HostUpload>> {
+    let metadata = std::fs::metadata(local_path)
+        .with_context(|| format!("本地文件不存在: {}", local_path.display()))?;
+    if !metadata.is_file() {
+        return Err(anyhow::anyhow!("{} 不是文件，目录请使用 rssh deploy 或 rssh cp -r", local_path.display()));
+    }
+    let total = metadata.len();
+
+    let uploads = Mutex::new(
+        servers
+            .iter()
+            .map(|server| HostUpload {
+                server_name: server.name.clone(),
+                state: UploadState::Waiting,
+                sent: 0,
+                target: None,
+                duration: Duration::ZERO,
+            })
+            .collect::<Vec<_>>(),
+    );
+    let next = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
+    let interactive = std::io::stdout().is_terminal();
+    let name_width = servers.iter().map(|s| s.name.chars().count()).max().unwrap_or(0);
+
+    std::thread::scope(|scope| {
+        for _ in 0..parallel.clamp(1, servers.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(server) = servers.get(index) else { break };
+                let start = Instant::now();
+                let result = upload_one(server, local_path, remote_path, |state, sent, target| {
+                    let mut uploads = uploads.lock().unwrap();
+                    let upload = &mut uploads[index];
+                    upload.state = state;
+                    upload.sent = sent;
+                    upload.target = target;
+                });
+
+                let mut uploads = uploads.lock().unwrap();
+                let upload = &mut uploads[index];
+                if let Err(e) = result {
+                    upload.state = UploadState::Failed(format!("{:#}", e));
+                }
+                upload.duration = start.elapsed();
+                if !interactive {
+                    // 输出被重定向时不重绘，每台主机完成时打印一行
+                    println!("{}", progress_line(upload, total, name_width));
+                }
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        if interactive {
+            let mut drawn = 0;
+            loop {
+                let done = finished.load(Ordering::SeqCst) == servers.len();
+                drawn = redraw(&uploads.lock().unwrap(), total, name_width, drawn);
+                if done {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(200));
+            }
+        }
+    });
+
+    Ok(uploads.into_inner().unwrap())
+}
+
+/// 汇总表：每台主机的结果、远程路径、耗时和错误
+pub fn print_summary(uploads: &[HostUpload]) {
+    let failed = uploads.iter().filter(|upload| !upload.success()).count();
+    let name_width = uploads.iter().map(|u| u.server_name.chars().count()).max().unwrap_or(0).max(4);
+    println!();
+    // 中文表头占两列宽，按显示宽度补齐
+    println!("主机{}  结果      耗时  路径 / 错误", " ".repeat(name_width - 4));
+    for upload in uploads {
+        let status = if upload.success() { "成功".bright_green() } else { "失败".bright_red() };
+        let detail = match &upload.state {
+            UploadState::Failed(error) => error.clone(),
+            _ => upload.target.as_ref().map(|t| t.display().to_string()).unwrap_or_default(),
+        };
+        println!(
+            "{:<width$}  {}  {:>7.1}s  {}",
+            upload.server_name,
+            status,
+            upload.duration.as_secs_f64(),
+            detail,
+            width = name_width
+        );
+    }
+    println!();
+    println!(
+        "完成: {} 台成功, {} 台失败",
+        (uploads.len() - failed).to_string().bright_green(),
+        failed.to_string().bright_red()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_host_progress() {
+        colored::control::set_override(false);
+        let mut upload = HostUpload {
+            server_name: "web-1".into(),
+            state: UploadState::Uploading,
+            sent: 1024 * 1024,
+            target: None,
+            duration: Duration::ZERO,
+        };
+        assert_eq!(
+            progress_line(&upload, 2 * 1024 * 1024, 6),
+            "web-1   [##########----------]  50%  1.0 MB/2.0 MB  "
+        );
+        upload.state = UploadState::Failed("连接超时".into());
+        assert_eq!(progress_line(&upload, 0, 5), "web-1  ✗ 连接超时");
+        upload.state = UploadState::Done;
+        upload.sent = 0;
+        assert!(progress_line(&upload, 0, 5).contains("100%"));
+    }
+}
@@ -0,0 +1,143 @@
+//! 按网络位置选择连接路线（split-horizon）：在办公网 / VPN 内时直接连接服务器的内网地址，
+//! 否则使用外部地址并经跳板机连接。是否在某个网络中由 `config.toml` 中
+//! `[networks.<名称>]` 的检测命令判断，每个进程只检测一次。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::models::ServerConfig;
+
+/// 检测命令的超时，超时视为不在该网络中
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 网络位置，对应 `config.toml` 中的 `[networks.<名称>]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkProfile {
+    /// 检测命令（sh -c 执行），退出码为 0 表示当前在该网络中，
+    /// 例如 `nc -z -w1 10.0.0.1 22` 或 `ip route | grep -q 10.8.0.0/16`
+    pub check: String,
+}
+
+static NETWORKS: OnceLock<HashMap<String, NetworkProfile>> = OnceLock::new();
+
+/// 本进程中已经检测过的网络
+static DETECTED: Mutex<Option<HashMap<String, bool>>> = Mutex::new(None);
+
+/// 设置网络位置，只在启动时调用一次
+pub fn configure(networks: HashMap<String, NetworkProfile>) {
+    let _ = NETWORKS.set(networks);
+}
+
+fn networks() -> &'static HashMap<String, NetworkProfile> {
+    NETWORKS.get_or_init(HashMap::new)
+}
+
+/// 执行检测命令，超时或无法执行时视为不在该网络中
+pub fn run_check(check: &str) -> bool {
+    let Ok(mut child) = Command::new("sh")
+        .args(["-c", check])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if start.elapsed() < CHECK_TIMEOUT => std::thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+        }
+    }
+}
+
+/// 当前是否在网络 `name` 中
+pub fn is_inside(name: &str) -> Result<bool> {
+    let profile = networks()
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("config.toml 中没有定义网络 [networks.{}]", name))?;
+    let mut detected = DETECTED.lock().unwrap();
+    let detected = detected.get_or_insert_with(HashMap::new);
+    if let Some(&inside) = detected.get(name) {
+        return Ok(inside);
+    }
+    let inside = run_check(&profile.check);
+    detected.insert(name.to_string(), inside);
+    Ok(inside)
+}
+
+/// 服务器使用的网络：显式配置的优先，否则使用唯一定义的网络
+fn network_name(server: &ServerConfig) -> Result<String> {
+    if let Some(network) = &server.network {
+        return Ok(network.clone());
+    }
+    let mut names = networks().keys();
+    match (names.next(), names.next()) {
+        (Some(name), None) => Ok(name.clone()),
+        (None, _) => Err(anyhow::anyhow!(
+            "{} 配置了内网地址，但 config.toml 中没有定义 [networks.<名称>]",
+            server.name
+        )),
+        _ => Err(anyhow::anyhow!("定义了多个网络，请为 {} 指定 --network", server.name)),
+    }
+}
+
+/// 在内网时改用内网地址并去掉跳板机、代理命令、备用地址；不在内网或没有内网地址时保持不变
+pub fn apply(server: &mut ServerConfig) -> Result<()> {
+    let Some(internal_host) = server.internal_host.clone() else {
+        return Ok(());
+    };
+    let network = network_name(server)?;
+    if is_inside(&network).with_context(|| format!("无法判断 {} 的网络位置", server.name))? {
+        server.host = internal_host;
+        server.jump_host = None;
+        server.proxy_jump = None;
+        server.proxy_command = None;
+        server.alt_hosts.clear();
+        server.preferred_host = None;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_route_by_network_location() {
+        configure(HashMap::from([
+            ("office".to_string(), NetworkProfile { check: "true".into() }),
+            ("vpn".to_string(), NetworkProfile { check: "exit 1".into() }),
+        ]));
+
+        let mut server = ServerConfig::new(
+            "1".into(), "db".into(), "db.example.com".into(), 22, "ops".into(),
+            crate::models::AuthType::Agent, None, None, None,
+        );
+        server.internal_host = Some("10.0.0.20".into());
+        server.jump_host = Some("ops@bastion.example.com:2222".into());
+
+        // 定义了多个网络时必须指定
+        assert!(apply(&mut server.clone()).is_err());
+
+        let mut outside = ServerConfig { network: Some("vpn".into()), ..server.clone() };
+        apply(&mut outside).unwrap();
+        assert_eq!(outside.host, "db.example.com");
+        assert_eq!(outside.jump_host.as_deref(), Some("ops@bastion.example.com:2222"));
+
+        let mut inside = ServerConfig { network: Some("office".into()), ..server };
+        apply(&mut inside).unwrap();
+        assert_eq!(inside.host, "10.0.0.20");
+        assert_eq!(inside.jump_host, None);
+    }
+}
@@ -0,0 +1,214 @@
+//! 长时间操作（传输、批量执行、隧道）结束或失败时发送通知，由各命令的 `--notify` 指定目标：
+//! `desktop`（notify-send / osascript）、通用 webhook（POST JSON）或 Slack 兼容的 incoming webhook。
+//! webhook 通过本地的 curl 发送。
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::utils::secret::resolve_secret;
+
+/// 通知目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Desktop,
+    /// POST 完整的事件 JSON
+    Webhook(String),
+    /// POST `{"text": ...}`，Slack、Mattermost、Discord（`/slack` 结尾的地址）等都支持
+    Slack(String),
+}
+
+impl Target {
+    /// 解析 `--notify` 的值：`desktop`、`slack:<URL>`、`webhook:<URL>`、直接写 URL，
+    /// 或 `config.toml` 中 `[notify]` 定义的名称。URL 部分支持 `env:` / `pass:` 等密钥引用
+    pub fn parse(spec: &str, named: &HashMap<String, String>) -> Result<Self> {
+        if let Some(target) = Self::parse_spec(spec) {
+            return Ok(target);
+        }
+        match named.get(spec) {
+            Some(value) => Self::parse_spec(value)
+                .ok_or_else(|| anyhow::anyhow!("config.toml 中的通知目标 {} 无效: {}", spec, value)),
+            None => Err(anyhow::anyhow!(
+                "未知的通知目标: {}（可用 desktop、slack:<URL>、webhook:<URL> 或 config.toml [notify] 中的名称）",
+                spec
+            )),
+        }
+    }
+
+    fn parse_spec(spec: &str) -> Option<Self> {
+        if spec == "desktop" {
+            return Some(Target::Desktop);
+        }
+        if let Some(url) = spec.strip_prefix("slack:") {
+            return Some(Target::Slack(url.to_string()));
+        }
+        if let Some(url) = spec.strip_prefix("webhook:") {
+            return Some(Target::Webhook(url.to_string()));
+        }
+        if spec.starts_with("https://hooks.slack.com/") {
+            return Some(Target::Slack(spec.to_string()));
+        }
+        if spec.starts_with("http://") || spec.starts_with("https://") {
+            return Some(Target::Webhook(spec.to_string()));
+        }
+        None
+    }
+}
+
+/// 一次操作的结果
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// 操作，例如 `upload web-1`
+    pub action: String,
+    /// 补充说明，例如失败原因或成功/失败主机数
+    pub detail: Option<String>,
+    pub success: bool,
+    pub duration: Duration,
+}
+
+impl Event {
+    pub fn title(&self) -> String {
+        format!("rssh {} {}", self.action, if self.success { "完成" } else { "失败" })
+    }
+
+    pub fn body(&self) -> String {
+        let elapsed = format!("耗时 {}", format_elapsed(self.duration));
+        match &self.detail {
+            Some(detail) => format!("{}\n{}", detail, elapsed),
+            None => elapsed,
+        }
+    }
+
+    fn webhook_payload(&self) -> serde_json::Value {
+        serde_json::json!({
+            "source": "rssh",
+            "action": self.action,
+            "status": if self.success { "success" } else { "failure" },
+            "title": self.title(),
+            "detail": self.detail,
+            "duration_secs": self.duration.as_secs(),
+        })
+    }
+
+    fn slack_payload(&self) -> serde_json::Value {
+        let icon = if self.success { "✅" } else { "❌" };
+        serde_json::json!({ "text": format!("{} *{}*\n{}", icon, self.title(), self.body()) })
+    }
+}
+
+/// 例如 `1 小时 2 分`、`3 分 5 秒`、`12 秒`
+fn format_elapsed(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{} 秒", s),
+        (0, m, s) => format!("{} 分 {} 秒", m, s),
+        (h, m, _) => format!("{} 小时 {} 分", h, m),
+    }
+}
+
+/// 发送到所有目标；发送失败只打印警告，不影响命令本身的结果
+pub fn send(targets: &[Target], event: &Event) {
+    for target in targets {
+        let result = match target {
+            Target::Desktop => desktop(&event.title(), &event.body(), !event.success),
+            Target::Webhook(url) => post_json(url, &event.webhook_payload()),
+            Target::Slack(url) => post_json(url, &event.slack_payload()),
+        };
+        if let Err(e) = result {
+            eprintln!("{} 发送通知失败: {:#}", "⚠".bright_yellow(), e);
+        }
+    }
+}
+
+/// 发送一条不对应某个操作结果的消息，例如输出触发器的提醒；失败只打印警告
+pub fn send_message(targets: &[Target], title: &str, body: &str) {
+    for target in targets {
+        let result = match target {
+            Target::Desktop => desktop(title, body, true),
+            Target::Webhook(url) => post_json(url, &serde_json::json!({ "source": "rssh", "title": title, "detail": body })),
+            Target::Slack(url) => post_json(url, &serde_json::json!({ "text": format!("🔔 *{}*\n{}", title, body) })),
+        };
+        if let Err(e) = result {
+            eprintln!("{} 发送通知失败: {:#}", "⚠".bright_yellow(), e);
+        }
+    }
+}
+
+fn desktop(title: &str, body: &str, critical: bool) -> Result<()> {
+    let status = if cfg!(target_os = "macos") {
+        let script = format!("display notification {} with title {}", applescript_string(body), applescript_string(title));
+        Command::new("osascript").args(["-e", &script]).status()
+    } else {
+        Command::new("notify-send")
+            .args(["-a", "rssh", "-u", if critical { "critical" } else { "normal" }])
+            .arg(title)
+            .arg(body)
+            .status()
+    }
+    .context("无法发送桌面通知（Linux 需要 notify-send）")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("桌面通知命令退出码 {}", status.code().unwrap_or(-1)));
+    }
+    Ok(())
+}
+
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn post_json(url: &str, payload: &serde_json::Value) -> Result<()> {
+    let url = resolve_secret(url).context("无法解析 webhook 地址")?;
+    let mut child = Command::new("curl")
+        .args(["-fsS", "-m", "10", "-X", "POST", "-H", "Content-Type: application/json", "--data-binary", "@-"])
+        .arg(&url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("无法执行 curl")?;
+    child
+        .stdin
+        .take()
+        .expect("stdin 已设置为 piped")
+        .write_all(payload.to_string().as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("webhook 请求失败（curl 退出码 {}）", status.code().unwrap_or(-1)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_targets_and_formats_events() {
+        let named = HashMap::from([("team".to_string(), "slack:env:TEAM_WEBHOOK".to_string())]);
+        assert_eq!(Target::parse("desktop", &named).unwrap(), Target::Desktop);
+        assert_eq!(Target::parse("team", &named).unwrap(), Target::Slack("env:TEAM_WEBHOOK".into()));
+        assert_eq!(
+            Target::parse("https://hooks.slack.com/services/T/B/x", &named).unwrap(),
+            Target::Slack("https://hooks.slack.com/services/T/B/x".into())
+        );
+        assert_eq!(
+            Target::parse("https://ci.example.com/hook", &named).unwrap(),
+            Target::Webhook("https://ci.example.com/hook".into())
+        );
+        assert!(Target::parse("pager", &named).is_err());
+
+        let event = Event {
+            action: "upload web-1".into(),
+            detail: Some("backup.tar.gz".into()),
+            success: false,
+            duration: Duration::from_secs(3725),
+        };
+        assert_eq!(event.title(), "rssh upload web-1 失败");
+        assert_eq!(event.body(), "backup.tar.gz\n耗时 1 小时 2 分");
+        assert_eq!(event.slack_payload()["text"], "❌ *rssh upload web-1 失败*\nbackup.tar.gz\n耗时 1 小时 2 分");
+        assert_eq!(event.webhook_payload()["status"], "failure");
+        assert_eq!(format_elapsed(Duration::from_secs(185)), "3 分 5 秒");
+    }
+}
@@ -0,0 +1,116 @@
+//! 命令的输出格式（全局选项 `--output json|table|plain`），供脚本和 fzf 等工具读取。
+//!
+//! 输出 JSON 时，进度和确认提示写到标准错误，标准输出只有结果。
+
+use anyhow::Result;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+use crate::models::ServerConfig;
+
+/// `--output` 的取值
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// JSON，字段与插件清单相同
+    Json,
+    /// 对齐的表格
+    Table,
+    /// 每行一条记录，字段以 Tab 分隔
+    Plain,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// 设置本次运行的输出格式，只在启动时调用一次
+pub fn configure(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// 命令行上指定的输出格式，未指定时为 None（使用各命令默认的显示方式）
+pub fn format() -> Option<OutputFormat> {
+    FORMAT.get().copied()
+}
+
+pub fn is_json() -> bool {
+    format() == Some(OutputFormat::Json)
+}
+
+/// 进度和提示信息的输出位置：输出 JSON 时为标准错误，否则为标准输出
+pub fn status() -> Box<dyn Write> {
+    if is_json() {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    print(&format!("{}\n", serde_json::to_string_pretty(value)?))
+}
+
+/// 写到标准输出。读取方提前退出（如 `| head`）时不报错
+pub fn print(text: &str) -> Result<()> {
+    match io::stdout().lock().write_all(text.as_bytes()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// 服务器列表的表格，列宽按最长的值对齐
+pub fn server_table(servers: &[&ServerConfig]) -> String {
+    let header = ["名称", "主机", "端口", "用户", "分组"].map(str::to_string);
+    let rows: Vec<[String; 5]> = servers
+        .iter()
+        .map(|server| {
+            [
+                server.name.clone(),
+                server.host.clone(),
+                server.port.to_string(),
+                server.username.clone(),
+                server.group.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+
+    let mut widths = [0usize; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - display_width(cell))))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// 终端中的显示宽度，中日韩字符占两列
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c >= '\u{1100}' && !c.is_ascii() { 2 } else { 1 }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    #[test]
+    fn aligns_table_columns() {
+        let web = ServerConfig::new("1".into(), "web1".into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, Some("prod".into()), None, None);
+        let db = ServerConfig::new("2".into(), "database".into(), "db.internal".into(), 2222, "admin".into(), AuthType::Agent, None, None, None);
+        let table = server_table(&[&web, &db]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "名称      主机         端口  用户   分组");
+        assert_eq!(lines[1], "web1      10.0.0.1     22    root   prod");
+        assert_eq!(lines[2], "database  db.internal  2222  admin  -");
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+
+use crate::utils::ssh::SshClient;
+
+/// passwd 最多提示这么多次，超过说明新密码被拒绝后在反复要求重新输入
+const MAX_PROMPTS: usize = 6;
+
+/// passwd 的输入提示
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    /// 当前密码（root 修改自己的密码时没有这一步）
+    Current,
+    New,
+    Retype,
+}
+
+/// 识别 passwd 输出的最后一行是否在等待输入，例如
+/// `Current password:`、`(current) UNIX password:`、`New password:`、`Retype new password:`
+pub fn classify_prompt(line: &str) -> Option<Prompt> {
+    let line = line.trim().to_lowercase();
+    if !line.ends_with(':') || !line.contains("password") {
+        return None;
+    }
+    if line.contains("retype") || line.contains("re-enter") || line.contains("again") || line.contains("repeat") {
+        Some(Prompt::Retype)
+    } else if line.contains("new") {
+        Some(Prompt::New)
+    } else {
+        Some(Prompt::Current)
+    }
+}
+
+/// 在服务器上执行 passwd 修改登录用户的密码，按提示依次输入当前密码和新密码。
+/// 失败时返回 passwd 的输出
+pub fn change_password(client: &SshClient, current: &str, new: &str) -> Result<()> {
+    // 固定英文提示，便于识别
+    let mut channel = client.pty_channel("LC_ALL=C LANG=C passwd")?;
+
+    let mut transcript = String::new();
+    let mut pending = String::new();
+    let mut prompts = 0;
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = channel.read(&mut buf).with_context(|| "读取 passwd 输出失败")?;
+        if n == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&buf[..n]);
+        transcript.push_str(&text);
+        pending.push_str(&text);
+
+        let last_line = pending.rsplit(['\r', '\n']).next().unwrap_or_default();
+        let Some(prompt) = classify_prompt(last_line) else {
+            continue;
+        };
+        prompts += 1;
+        if prompts > MAX_PROMPTS {
+            let _ = channel.close();
+            break;
+        }
+        let reply = match prompt {
+            Prompt::Current => current,
+            Prompt::New | Prompt::Retype => new,
+        };
+        channel.write_all(format!("{}\n", reply).as_bytes())
+            .with_context(|| "向 passwd 输入失败")?;
+        channel.flush()?;
+        pending.clear();
+    }
+
+    channel.wait_close().with_context(|| "等待通道关闭失败")?;
+    let status = channel.exit_status().with_context(|| "获取退出状态失败")?;
+    if status != 0 {
+        return Err(anyhow::anyhow!(
+            "passwd 失败（退出码 {}）:\n{}",
+            status,
+            transcript.trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_passwd_prompts() {
+        assert_eq!(classify_prompt("Changing password for ops.\r\nCurrent password: ".rsplit('\n').next().unwrap()), Some(Prompt::Current));
+        assert_eq!(classify_prompt("(current) UNIX password:"), Some(Prompt::Current));
+        assert_eq!(classify_prompt("New password: "), Some(Prompt::New));
+        assert_eq!(classify_prompt("Enter new UNIX password:"), Some(Prompt::New));
+        assert_eq!(classify_prompt("Retype new password:"), Some(Prompt::Retype));
+        assert_eq!(classify_prompt("Re-enter new password:"), Some(Prompt::Retype));
+        assert_eq!(classify_prompt("passwd: password updated successfully"), None);
+        assert_eq!(classify_prompt("BAD PASSWORD: The password is shorter than 8 characters"), None);
+    }
+}
@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::models::ServerConfig;
+use crate::utils::hooks;
+
+/// tmux 配置中由 rssh 管理的区块标记，重复安装时整体替换
+const TMUX_BLOCK_BEGIN: &str = "# >>> rssh integration >>>";
+const TMUX_BLOCK_END: &str = "# <<< rssh integration <<<";
+
+/// 生成供 fzf 等工具使用的一行服务器描述（以 Tab 分隔，第一列为名称）
+pub fn server_line(server: &ServerConfig) -> String {
+    format!(
+        "{}\t{}@{}:{}\t{}",
+        server.name,
+        server.username,
+        server.host,
+        server.port,
+        server.group.as_deref().unwrap_or("-")
+    )
+}
+
+/// 模糊匹配打分：`query` 的字符按顺序（不必连续）出现在 `text` 中时返回分数，否则返回 None。
+///
+/// 连续命中和命中单词开头（`-`、`_`、`.`、空格之后）加分，匹配越靠前分数越高，不区分大小写。
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let Some(&first) = query.first() else {
+        return Some(0);
+    };
+
+    // 首字符可能出现多次，逐个作为起点贪心匹配，取最高分
+    (0..text.len())
+        .filter(|&start| text[start] == first)
+        .filter_map(|start| {
+            let mut score = 1 - (start as i64).min(3) + word_start_bonus(&text, start);
+            let mut prev = start;
+            for &qc in &query[1..] {
+                let found = prev + 1 + text[prev + 1..].iter().position(|&c| c == qc)?;
+                score += 1 + word_start_bonus(&text, found);
+                if found == prev + 1 {
+                    score += 5;
+                }
+                prev = found;
+            }
+            Some(score)
+        })
+        .max()
+}
+
+fn word_start_bonus(text: &[char], index: usize) -> i64 {
+    if index == 0 || matches!(text[index - 1], '-' | '_' | '.' | ' ' | '/' | '@') { 3 } else { 0 }
+}
+
+/// 按名称、主机、分组、描述模糊过滤服务器，结果按匹配程度排序（分数相同时保持原顺序）
+pub fn fuzzy_filter<'a>(servers: &'a [ServerConfig], query: &str) -> Vec<&'a ServerConfig> {
+    if query.trim().is_empty() {
+        return servers.iter().collect();
+    }
+    // 配置了 fuzzy_score 钩子时由脚本打分，脚本出错时退回内置的匹配（TUI 中无法显示错误）
+    if let Some(Ok(scores)) = hooks::fuzzy_scores(query, servers) {
+        let mut scored: Vec<(i64, &ServerConfig)> = scores
+            .into_iter()
+            .zip(servers)
+            .filter_map(|(score, server)| score.map(|score| (score, server)))
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        return scored.into_iter().map(|(_, server)| server).collect();
+    }
+    let mut scored: Vec<(i64, &ServerConfig)> = servers
+        .iter()
+        .filter_map(|server| {
+            [Some(server.name.as_str()), Some(server.host.as_str()), server.group.as_deref(), server.description.as_deref()]
+                .into_iter()
+                .flatten()
+                .filter_map(|field| fuzzy_score(query, field))
+                .max()
+                .map(|score| (score, server))
+        })
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, server)| server).collect()
+}
+
+/// 检查 fzf 是否可用
+pub fn fzf_available() -> bool {
+    which::which("fzf").is_ok()
+}
+
+/// 调用 fzf 让用户选择服务器，返回选中的服务器（取消时返回 None）。
+///
+/// fzf 自己通过 /dev/tty 绘制界面，因此可以在 tmux popup 中直接使用。
+pub fn pick_with_fzf<'a>(servers: &'a [ServerConfig], query: Option<&str>) -> Result<Option<&'a ServerConfig>> {
+    let mut cmd = Command::new("fzf");
+    cmd.args(["--delimiter", "\t", "--prompt", "rssh> ", "--height", "100%"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    if let Some(q) = query {
+        cmd.args(["--query", q]);
+    }
+
+    let mut child = cmd.spawn().with_context(|| "无法启动 fzf")?;
+
+    {
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("无法写入 fzf 输入"))?;
+        for server in servers {
+            writeln!(stdin, "{}", server_line(server))?;
+        }
+    }
+
+    let output = child.wait_with_output().with_context(|| "等待 fzf 失败")?;
+    if !output.status.success() {
+        // 130: 用户按 Esc/Ctrl-C 取消；1: 没有匹配项
+        return Ok(None);
+    }
+
+    let selected = String::from_utf8_lossy(&output.stdout);
+    let name = selected.split('\t').next().unwrap_or("").trim();
+
+    Ok(servers.iter().find(|s| s.name == name))
+}
+
+/// 在 tmux 配置中安装 popup 快捷键：`prefix + <key>` 打开 rssh pick。
+///
+/// 返回写入的配置文件路径。已安装过时替换原有区块，保证幂等。
+pub fn install_tmux_binding(rssh_path: &Path, key: &str) -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("无法确定用户主目录"))?;
+    let conf_path = home.join(".tmux.conf");
+
+    let existing = if conf_path.exists() {
+        std::fs::read_to_string(&conf_path)
+            .with_context(|| format!("无法读取 {}", conf_path.display()))?
+    } else {
+        String::new()
+    };
+
+    let block = format!(
+        "{}\nbind-key {} display-popup -E -w 80% -h 60% \"{} pick --new-window\"\n{}\n",
+        TMUX_BLOCK_BEGIN,
+        key,
+        rssh_path.display(),
+        TMUX_BLOCK_END
+    );
+
+    let content = replace_managed_block(&existing, &block);
+    std::fs::write(&conf_path, content)
+        .with_context(|| format!("无法写入 {}", conf_path.display()))?;
+
+    Ok(conf_path)
+}
+
+/// 用新区块替换配置中已有的 rssh 区块；不存在时追加到末尾
+fn replace_managed_block(existing: &str, block: &str) -> String {
+    if let (Some(start), Some(end)) = (existing.find(TMUX_BLOCK_BEGIN), existing.find(TMUX_BLOCK_END)) {
+        if start < end {
+            let mut tail = &existing[end + TMUX_BLOCK_END.len()..];
+            tail = tail.strip_prefix('\n').unwrap_or(tail);
+            return format!("{}{}{}", &existing[..start], block, tail);
+        }
+    }
+
+    let mut content = existing.to_string();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(block);
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_block_when_missing() {
+        let out = replace_managed_block("set -g mouse on", "BLOCK\n");
+        assert_eq!(out, "set -g mouse on\nBLOCK\n");
+    }
+
+    #[test]
+    fn replaces_existing_block_in_place() {
+        let existing = format!("a\n{}\nold\n{}\nb\n", TMUX_BLOCK_BEGIN, TMUX_BLOCK_END);
+        let out = replace_managed_block(&existing, "NEW\n");
+        assert_eq!(out, "a\nNEW\nb\n");
+    }
+
+    #[test]
+    fn fuzzy_matches_subsequences_and_prefers_tight_matches() {
+        assert!(fuzzy_score("pdb", "prod-db-01").is_some());
+        assert!(fuzzy_score("dbp", "prod-db-01").is_none());
+        assert!(fuzzy_score("db", "prod-db-01") > fuzzy_score("db", "dev-web"));
+
+        let web = ServerConfig::new("1".into(), "web-01".into(), "10.0.0.1".into(), 22, "root".into(), crate::models::AuthType::Agent, Some("frontend".into()), Some("nginx 入口".into()), None);
+        let db = ServerConfig::new("2".into(), "prod-db-01".into(), "db.internal".into(), 22, "root".into(), crate::models::AuthType::Agent, None, None, None);
+        let servers = vec![web, db];
+
+        let names = |query| fuzzy_filter(&servers, query).iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names("pdb"), ["prod-db-01"]);
+        assert_eq!(names("ngx"), ["web-01"]);
+        assert_eq!(names("fend"), ["web-01"]);
+        assert_eq!(names(""), ["web-01", "prod-db-01"]);
+    }
+}
@@ -0,0 +1,147 @@
+//! 外部插件：PATH 中名为 `rssh-<名称>` 的可执行文件可以作为 `rssh <名称> [参数...]` 运行，
+//! 团队可以用任意语言添加自己的子命令而不必修改 rssh。
+//!
+//! 插件启动时通过环境变量拿到调用它的 rssh（`RSSH_BIN`）和协议版本（`RSSH_PLUGIN_API`），
+//! 需要服务器清单时执行 `$RSSH_BIN plugin inventory`，从标准输出读取 JSON（见 [`inventory`]）。
+//! 清单中不包含密码等凭据，插件需要连接服务器时应调用 `rssh exec` / `rssh connect`。
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::models::{ConnectionType, ServerConfig};
+
+/// 插件可执行文件的名称前缀
+pub const PREFIX: &str = "rssh-";
+
+/// 插件协议版本，清单格式不兼容地变化时递增
+pub const API_VERSION: u32 = 1;
+
+/// 在 PATH 中查找所有插件，返回 名称 → 路径（同名时 PATH 中靠前的优先）
+pub fn discover() -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    let Some(path) = std::env::var_os("PATH") else {
+        return plugins;
+    };
+    for dir in std::env::split_paths(&path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str().and_then(plugin_name) else {
+                continue;
+            };
+            if is_executable(&entry.path()) {
+                plugins.entry(name.to_string()).or_insert_with(|| entry.path());
+            }
+        }
+    }
+    plugins
+}
+
+/// 查找名为 `rssh-<name>` 的插件
+pub fn find(name: &str) -> Option<PathBuf> {
+    which::which(format!("{}{}", PREFIX, name)).ok()
+}
+
+/// 运行插件，标准输入输出直接交给插件，返回插件的退出码
+pub fn run(path: &Path, args: &[OsString], db_path: &Path) -> Result<i32> {
+    let rssh = std::env::current_exe().with_context(|| "无法获取当前rssh可执行文件路径")?;
+    let status = Command::new(path)
+        .args(args)
+        .env("RSSH_BIN", &rssh)
+        .env("RSSH_PLUGIN_API", API_VERSION.to_string())
+        .env("RSSH_DB", db_path)
+        .status()
+        .with_context(|| format!("无法启动插件 {}", path.display()))?;
+    // 被信号终止时按 shell 的习惯返回 128 + 信号
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return Ok(128 + signal);
+    }
+    Ok(status.code().unwrap_or(1))
+}
+
+/// 提供给插件的服务器清单：
+///
+/// ```json
+/// {"api_version": 1, "rssh_version": "0.1.0", "servers": [
+///   {"id": "...", "name": "web1", "host": "10.0.0.1", "port": 22, "username": "root",
+///    "group": "prod", "description": null, "aliases": [], "alt_hosts": [], "connection": "ssh"}
+/// ]}
+/// ```
+pub fn inventory(servers: &[ServerConfig]) -> Value {
+    json!({
+        "api_version": API_VERSION,
+        "rssh_version": env!("CARGO_PKG_VERSION"),
+        "servers": servers.iter().map(server_json).collect::<Vec<_>>(),
+    })
+}
+
+/// 服务器在插件清单和脚本钩子中的 JSON 表示，不含凭据
+pub fn server_json(server: &ServerConfig) -> Value {
+    json!({
+        "id": server.id,
+        "name": server.name,
+        "host": server.host,
+        "port": server.port,
+        "username": server.username,
+        "group": server.group,
+        "description": server.description,
+        "aliases": server.aliases,
+        "alt_hosts": server.alt_hosts,
+        "jump_host": server.jump_host,
+        "proxy_jump": server.proxy_jump,
+        "connection": match server.connection_type {
+            ConnectionType::Ssh => "ssh",
+            ConnectionType::Serial { .. } => "serial",
+            ConnectionType::Ipmi => "ipmi",
+            ConnectionType::Telnet => "telnet",
+        },
+    })
+}
+
+/// 从文件名中取出插件名称，`rssh-foo` → `foo`（Windows 上去掉扩展名）
+fn plugin_name(file_name: &str) -> Option<&str> {
+    let name = file_name.strip_prefix(PREFIX)?;
+    let name = if cfg!(windows) { name.strip_suffix(".exe").unwrap_or(name) } else { name };
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    #[test]
+    fn parses_plugin_names() {
+        assert_eq!(plugin_name("rssh-inventory"), Some("inventory"));
+        assert_eq!(plugin_name("rssh-"), None);
+        assert_eq!(plugin_name("rsshd"), None);
+    }
+
+    #[test]
+    fn inventory_omits_credentials() {
+        let server = ServerConfig::new("1".into(), "web1".into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Password("secret".into()), Some("prod".into()), None, Some("secret".into()));
+        let value = inventory(&[server]);
+        assert_eq!(value["api_version"], API_VERSION);
+        assert_eq!(value["servers"][0]["name"], "web1");
+        assert_eq!(value["servers"][0]["group"], "prod");
+        assert!(!value.to_string().contains("secret"));
+    }
+}
@@ -0,0 +1,177 @@
+//! 文件传输的进度条：已传输大小、速度和预计剩余时间，传输多个文件时同时显示当前文件的进度。
+//!
+//! 进度写到标准错误，只在标准错误是终端时绘制，输出重定向时不显示。
+
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crate::utils::multi_upload::format_size;
+
+/// 两次重绘的最小间隔
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// 进度条宽度
+const BAR_WIDTH: usize = 20;
+/// 文件名最多显示的字符数，超过时保留结尾
+const NAME_WIDTH: usize = 32;
+
+#[derive(Debug)]
+pub struct TransferProgress {
+    total: u64,
+    files: usize,
+    /// 已完成文件的字节数
+    finished: u64,
+    file_index: usize,
+    file_name: String,
+    file_size: u64,
+    file_sent: u64,
+    started: Instant,
+    last_draw: Option<Instant>,
+    enabled: bool,
+}
+
+impl TransferProgress {
+    /// `total` 为所有文件的总字节数，`files` 为文件数
+    pub fn new(total: u64, files: usize) -> Self {
+        TransferProgress {
+            total,
+            files,
+            finished: 0,
+            file_index: 0,
+            file_name: String::new(),
+            file_size: 0,
+            file_sent: 0,
+            started: Instant::now(),
+            last_draw: None,
+            enabled: std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// 开始传输下一个文件
+    pub fn start_file(&mut self, name: &str, size: u64) {
+        self.finished += self.file_sent;
+        self.file_index += 1;
+        self.file_name = name.to_string();
+        self.file_size = size;
+        self.file_sent = 0;
+        self.draw(true);
+    }
+
+    /// 当前文件又传输了 `bytes` 字节
+    pub fn advance(&mut self, bytes: u64) {
+        self.file_sent += bytes;
+        self.draw(false);
+    }
+
+    /// 绘制最终状态并换行
+    pub fn finish(&mut self) {
+        if self.enabled && self.last_draw.is_some() {
+            self.draw(true);
+            eprintln!();
+        }
+    }
+
+    /// 包装写入端，写入的字节计入当前文件的进度
+    pub fn writer<W: Write>(&mut self, inner: W) -> ProgressWriter<'_, W> {
+        ProgressWriter { inner, progress: self }
+    }
+
+    fn draw(&mut self, force: bool) {
+        if !self.enabled || (!force && self.last_draw.is_some_and(|last| last.elapsed() < REDRAW_INTERVAL)) {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+        let line = self.line(self.started.elapsed());
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    }
+
+    /// 例如 `[##########----------]  50%  1.0 MB/2.0 MB  512.0 KB/s  剩余 00:02  (2/3) logs/app.log`
+    fn line(&self, elapsed: Duration) -> String {
+        let sent = self.finished + self.file_sent;
+        let percent = (sent * 100).checked_div(self.total).unwrap_or(100).min(100) as usize;
+        let filled = percent * BAR_WIDTH / 100;
+        let speed = (sent as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
+        let eta = match self.total.saturating_sub(sent) {
+            0 => "00:00".to_string(),
+            _ if speed == 0 => "--:--".to_string(),
+            remaining => format_duration(Duration::from_secs(remaining / speed)),
+        };
+        let mut line = format!(
+            "[{}{}] {:>3}%  {}/{}  {}/s  剩余 {}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            percent,
+            format_size(sent),
+            format_size(self.total),
+            format_size(speed),
+            eta,
+        );
+        if self.files > 1 {
+            let file_percent = (self.file_sent * 100).checked_div(self.file_size).unwrap_or(100).min(100);
+            line.push_str(&format!("  ({}/{}) {} {}%", self.file_index, self.files, shorten(&self.file_name), file_percent));
+        }
+        line
+    }
+}
+
+/// 写入时更新进度的包装，见 [`TransferProgress::writer`]
+pub struct ProgressWriter<'a, W> {
+    inner: W,
+    progress: &'a mut TransferProgress,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.progress.advance(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `mm:ss`，超过一小时时为 `h:mm:ss`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn shorten(name: &str) -> String {
+    let count = name.chars().count();
+    if count <= NAME_WIDTH {
+        return name.to_string();
+    }
+    let tail: String = name.chars().skip(count - (NAME_WIDTH - 1)).collect();
+    format!("…{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_aggregate_and_file_progress() {
+        let mut progress = TransferProgress::new(4 * 1024 * 1024, 2);
+        progress.enabled = false;
+        progress.start_file("a.bin", 1024 * 1024);
+        progress.advance(1024 * 1024);
+        progress.start_file("logs/app.log", 3 * 1024 * 1024);
+        progress.advance(1024 * 1024);
+        assert_eq!(
+            progress.line(Duration::from_secs(2)),
+            "[##########----------]  50%  2.0 MB/4.0 MB  1.0 MB/s  剩余 00:02  (2/2) logs/app.log 33%"
+        );
+
+        let mut single = TransferProgress::new(0, 1);
+        single.start_file("empty", 0);
+        assert_eq!(single.line(Duration::ZERO), "[####################] 100%  0 B/0 B  0 B/s  剩余 00:00");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+/// 退出原始终端会话的按键：Ctrl+]（与 telnet 保持一致）
+pub const ESCAPE_BYTE: u8 = 0x1d;
+
+/// 将 stdin 切换到原始、非阻塞模式，离开作用域时恢复
+pub struct RawModeGuard {
+    fd: i32,
+    original_flags: i32,
+    termios_backup: termios::Termios,
+}
+
+impl RawModeGuard {
+    pub fn enter() -> Result<Self> {
+        let fd = io::stdin().as_raw_fd();
+        let original_flags = unsafe { libc::fcntl(fd, libc::F_GETFL, 0) };
+
+        let mut raw = termios::Termios::from_fd(fd)
+            .with_context(|| "无法读取终端设置")?;
+        let termios_backup = raw;
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(fd, termios::TCSANOW, &raw)
+            .with_context(|| "无法将终端设置为原始模式")?;
+        unsafe { libc::fcntl(fd, libc::F_SETFL, original_flags | libc::O_NONBLOCK) };
+
+        Ok(RawModeGuard { fd, original_flags, termios_backup })
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = termios::tcsetattr(self.fd, termios::TCSANOW, &self.termios_backup);
+        unsafe { libc::fcntl(self.fd, libc::F_SETFL, self.original_flags) };
+    }
+}
+
+/// 原始会话中对收发数据的转换，例如 telnet 的 IAC 协商。默认原样透传
+pub trait StreamCodec {
+    /// 将用户输入转换为发往远端的字节
+    fn encode(&mut self, input: &[u8]) -> Vec<u8> {
+        input.to_vec()
+    }
+
+    /// 处理远端数据，返回要显示的内容；需要回复远端的字节写入 `reply`
+    fn decode(&mut self, data: &[u8], _reply: &mut Vec<u8>) -> Vec<u8> {
+        data.to_vec()
+    }
+}
+
+/// 不做任何转换
+pub struct Passthrough;
+
+impl StreamCodec for Passthrough {}
+
+/// 在原始终端模式下把 stdin/stdout 与一个非阻塞的字节流（串口、TCP 等）对接，
+/// 直到对端关闭或用户按下 Ctrl+]。
+///
+/// `stream` 必须已经设置为非阻塞，读写返回 `WouldBlock` 时视为暂无数据。
+pub fn run_raw_session<S: Read + Write>(stream: &mut S) -> Result<()> {
+    run_raw_session_with(stream, &mut Passthrough)
+}
+
+/// 与 `run_raw_session` 相同，但收发数据经过 `codec` 转换
+pub fn run_raw_session_with<S: Read + Write, C: StreamCodec>(stream: &mut S, codec: &mut C) -> Result<()> {
+    let _guard = RawModeGuard::enter()?;
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    let mut stdin_buf = [0u8; 1024];
+    let mut remote_buf = [0u8; 4096];
+
+    loop {
+        let mut idle = true;
+
+        match stdin.read(&mut stdin_buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                idle = false;
+                let input = &stdin_buf[..n];
+                if let Some(pos) = input.iter().position(|&b| b == ESCAPE_BYTE) {
+                    write_all_nonblocking(stream, &codec.encode(&input[..pos]))?;
+                    break;
+                }
+                write_all_nonblocking(stream, &codec.encode(input))?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e).with_context(|| "读取标准输入失败"),
+        }
+
+        match stream.read(&mut remote_buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                idle = false;
+                let mut reply = Vec::new();
+                let display = codec.decode(&remote_buf[..n], &mut reply);
+                if !reply.is_empty() {
+                    write_all_nonblocking(stream, &reply)?;
+                }
+                stdout.write_all(&display)?;
+                stdout.flush()?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e).with_context(|| "读取远端数据失败"),
+        }
+
+        // 短暂休眠以避免CPU使用率过高
+        if idle {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    Ok(())
+}
+
+/// 向非阻塞流写入全部数据，遇到 `WouldBlock` 时稍后重试
+pub fn write_all_nonblocking<S: Write>(stream: &mut S, mut data: &[u8]) -> Result<()> {
+    while !data.is_empty() {
+        match stream.write(data) {
+            Ok(0) => return Err(anyhow::anyhow!("连接已关闭")),
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            Err(e) => return Err(e).with_context(|| "写入远端失败"),
+        }
+    }
+    stream.flush().ok();
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use std::process::Command;
 use crate::models::{ServerConfig, AuthType};
+use crate::utils::interrupt;
 use shellexpand;
 
 pub struct RcloneConfig {
@@ -127,7 +128,11 @@ impl RcloneConfig {
         let command_str = format!("{:?}", cmd);
         println!("完整命令: {}", command_str);
         
-        let status = cmd.status()?;
+        let status = interrupt::status(&mut cmd).inspect_err(|e| {
+            if interrupt::is_interrupted(e) {
+                println!("复制已中断，{} 中可能已有部分文件；重新执行会跳过已复制完整的文件", to_remote);
+            }
+        })?;
             
         if status.success() {
             println!("文件复制成功");
@@ -0,0 +1,193 @@
+//! 会话录制，格式为 asciicast v2（与 asciinema 兼容）。
+//!
+//! 会话中行首输入 `~M` 添加的标记写成 `"m"` 事件，与输出一起保存在同一个文件里，
+//! `rssh replay --markers` 列出标记，`--from` 从标记处开始回放。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// 录制中的一个事件：`o` 为输出，`m` 为标记
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastEvent {
+    pub time: f64,
+    pub kind: String,
+    pub data: String,
+}
+
+/// 把会话输出写入 asciicast 文件
+pub struct Recorder {
+    file: BufWriter<File>,
+    start: Instant,
+    /// 上一段输出末尾不完整的 UTF-8 字符
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, width: u16, height: u16, title: &str) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("无法创建录制文件: {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+        let header = CastHeader {
+            version: 2,
+            width,
+            height,
+            timestamp: Some(chrono::Local::now().timestamp()),
+            title: Some(title.to_string()),
+        };
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+        file.flush()?;
+        Ok(Recorder { file, start: Instant::now(), pending: Vec::new() })
+    }
+
+    /// 记录显示到终端的输出
+    pub fn output(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(data);
+        let (text, rest) = split_utf8(&self.pending);
+        self.pending = rest;
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &text)
+    }
+
+    /// 在当前时间点添加标记
+    pub fn marker(&mut self, label: &str) -> Result<()> {
+        self.event("m", label)
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> Result<()> {
+        let time = (self.start.elapsed().as_secs_f64() * 1000.0).round() / 1000.0;
+        writeln!(self.file, "{}", serde_json::to_string(&(time, kind, data))?)?;
+        // 逐条写入磁盘，连接异常断开时也不丢失已录制的内容
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// 转换为字符串，末尾不完整的多字节字符留到下一段输出
+fn split_utf8(data: &[u8]) -> (String, Vec<u8>) {
+    match std::str::from_utf8(data) {
+        Ok(text) => (text.to_string(), Vec::new()),
+        Err(e) if e.error_len().is_none() => {
+            let valid = e.valid_up_to();
+            (String::from_utf8_lossy(&data[..valid]).into_owned(), data[valid..].to_vec())
+        }
+        Err(_) => (String::from_utf8_lossy(data).into_owned(), Vec::new()),
+    }
+}
+
+/// 读取的录制文件
+#[derive(Debug, Clone)]
+pub struct Cast {
+    pub header: CastHeader,
+    pub events: Vec<CastEvent>,
+}
+
+impl Cast {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("无法打开录制文件: {}", path.display()))?;
+        Self::parse(BufReader::new(file)).with_context(|| format!("无法解析录制文件: {}", path.display()))
+    }
+
+    pub fn parse(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines();
+        let header: CastHeader = serde_json::from_str(&lines.next().context("文件为空")??)?;
+        if header.version != 2 {
+            return Err(anyhow::anyhow!("只支持 asciicast v2，文件版本为 {}", header.version));
+        }
+        let mut events = Vec::new();
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (time, kind, data): (f64, String, String) = serde_json::from_str(&line)?;
+            events.push(CastEvent { time, kind, data });
+        }
+        Ok(Cast { header, events })
+    }
+
+    /// 所有标记：事件序号、时间和内容
+    pub fn markers(&self) -> Vec<(usize, &CastEvent)> {
+        self.events.iter().enumerate().filter(|(_, event)| event.kind == "m").collect()
+    }
+
+    /// 按序号（从 1 开始）或内容查找标记，返回事件序号
+    pub fn find_marker(&self, query: &str) -> Result<usize> {
+        let markers = self.markers();
+        let found = match query.parse::<usize>() {
+            Ok(number) => markers.get(number.wrapping_sub(1)),
+            Err(_) => markers.iter().find(|(_, event)| event.data.contains(query)),
+        };
+        found
+            .map(|(index, _)| *index)
+            .ok_or_else(|| anyhow::anyhow!("未找到标记: {}（用 --markers 查看所有标记）", query))
+    }
+}
+
+/// 录制中的时间位置，例如 `01:02:03`
+pub fn format_offset(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// 回放录制：`from` 之前的输出立即显示（快进），之后按原来的节奏播放。
+/// 两次输出之间的停顿最长 `max_idle` 秒，`speed` 为播放倍速
+pub fn replay(cast: &Cast, from: usize, speed: f64, max_idle: f64) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    let mut last = cast.events.get(from).map_or(0.0, |event| event.time);
+    for (index, event) in cast.events.iter().enumerate() {
+        if index >= from {
+            let delay = (event.time - last).clamp(0.0, max_idle) / speed;
+            std::thread::sleep(Duration::from_secs_f64(delay));
+            last = event.time;
+        }
+        if event.kind == "o" {
+            stdout.write_all(event.data.as_bytes())?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_output_and_markers() {
+        let path = std::env::temp_dir().join(format!("rssh-cast-{}.cast", std::process::id()));
+        let mut recorder = Recorder::create(&path, 80, 24, "web-1").unwrap();
+        let text = "中文".as_bytes();
+        // 多字节字符被拆在两次输出中
+        recorder.output(&text[..4]).unwrap();
+        recorder.output(&text[4..]).unwrap();
+        recorder.marker("started migration here").unwrap();
+        recorder.output(b"done\r\n").unwrap();
+        drop(recorder);
+
+        let cast = Cast::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((cast.header.width, cast.header.height), (80, 24));
+        let data: Vec<&str> = cast.events.iter().map(|e| e.data.as_str()).collect();
+        assert_eq!(data, ["中", "文", "started migration here", "done\r\n"]);
+        assert_eq!(cast.find_marker("1").unwrap(), 2);
+        assert_eq!(cast.find_marker("migration").unwrap(), 2);
+        assert!(cast.find_marker("2").is_err());
+        assert_eq!(format_offset(3725.4), "01:02:05");
+    }
+}
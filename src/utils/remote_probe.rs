@@ -0,0 +1,219 @@
+//! 探测远程服务器上可用的传输工具（kitten、rz、rsync），结果缓存在数据库中，
+//! 用于 `upload`/`download` 的 auto 模式选择传输方式。
+
+use anyhow::{Context, Result};
+use std::fmt;
+
+use crate::config::{get_db_path, get_ssh_config_dir, ProbeStore};
+use crate::models::{AuthType, ServerConfig};
+use crate::utils::ssh_command_builder::SshCommand;
+
+/// 探测结果的有效期（秒），过期后重新探测
+const PROBE_TTL: i64 = 7 * 24 * 3600;
+
+/// 需要探测的远程命令
+const TOOLS: [&str; 3] = ["kitten", "rz", "rsync"];
+
+/// 远程服务器上可用的传输工具
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteTools {
+    pub kitten: bool,
+    pub rz: bool,
+    pub rsync: bool,
+    /// 探测时间（Unix 时间戳）
+    pub probed_at: i64,
+}
+
+impl RemoteTools {
+    /// 解析探测命令的输出：每行一个可用的命令名
+    pub fn parse(output: &str, probed_at: i64) -> Self {
+        let has = |tool: &str| output.lines().any(|line| line.trim() == tool);
+        RemoteTools { kitten: has("kitten"), rz: has("rz"), rsync: has("rsync"), probed_at }
+    }
+
+    /// 可用的命令名
+    pub fn names(&self) -> Vec<&'static str> {
+        TOOLS
+            .into_iter()
+            .zip([self.kitten, self.rz, self.rsync])
+            .filter_map(|(tool, available)| available.then_some(tool))
+            .collect()
+    }
+
+    pub fn is_stale(&self, now: i64) -> bool {
+        now - self.probed_at > PROBE_TTL
+    }
+}
+
+/// 在服务器上执行的探测命令，只输出存在的命令名，总是以 0 退出
+pub fn probe_command() -> String {
+    let script = format!(
+        "for tool in {}; do command -v \"$tool\" >/dev/null 2>&1 && echo \"$tool\"; done; exit 0",
+        TOOLS.join(" ")
+    );
+    format!("sh -c {}", shell_escape::escape(script.into()))
+}
+
+/// 用系统 ssh 探测（不交互，无法免密登录时直接失败）
+pub fn probe(server: &ServerConfig) -> Result<RemoteTools> {
+    let output = SshCommand::new(server)
+        .option("BatchMode", "yes")
+        .option("ConnectTimeout", "10")
+        .with_config_file(&get_ssh_config_dir()?)?
+        .command(probe_command())
+        .to_command()
+        .output()
+        .with_context(|| "无法执行ssh命令")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "探测失败: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(RemoteTools::parse(&String::from_utf8_lossy(&output.stdout), chrono::Utc::now().timestamp()))
+}
+
+/// 读取缓存的探测结果，没有或已过期时重新探测并写入缓存；第二个返回值表示是否来自缓存
+pub fn remote_tools(server: &ServerConfig) -> Result<(RemoteTools, bool)> {
+    let store = ProbeStore::new(get_db_path()?)?;
+    let now = chrono::Utc::now().timestamp();
+    if let Some(tools) = store.get(&server.id)? {
+        if !tools.is_stale(now) {
+            return Ok((tools, true));
+        }
+    }
+    let tools = probe(server)?;
+    store.save(&server.id, &tools)?;
+    Ok((tools, false))
+}
+
+/// 删除服务器的探测缓存（自动选择的传输方式失败时调用）
+pub fn forget(server: &ServerConfig) -> Result<()> {
+    ProbeStore::new(get_db_path()?)?.forget(&server.id)
+}
+
+/// 本地可用的传输工具
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTools {
+    pub kitty: bool,
+    pub rsync: bool,
+}
+
+impl LocalTools {
+    pub fn detect() -> Self {
+        LocalTools {
+            kitty: crate::utils::kitty_transfer::is_kitty_available(),
+            rsync: which::which("rsync").is_ok(),
+        }
+    }
+}
+
+/// auto 模式选出的传输方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferMethod {
+    Kitty,
+    Rsync,
+    Scp,
+}
+
+impl fmt::Display for TransferMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransferMethod::Kitty => "kitty",
+            TransferMethod::Rsync => "rsync",
+            TransferMethod::Scp => "scp",
+        })
+    }
+}
+
+/// 按本地和远程环境选择传输方式：kitty（两端都支持时）> rsync（可断点续传）> scp。
+/// `remote` 为 None 表示无法探测，此时只用 scp。返回选择及原因
+pub fn choose(local: LocalTools, remote: Option<&RemoteTools>) -> (TransferMethod, Vec<String>) {
+    let mut reasons = Vec::new();
+    let Some(remote) = remote else {
+        reasons.push("无法探测远程环境，使用 scp".to_string());
+        return (TransferMethod::Scp, reasons);
+    };
+
+    if local.kitty {
+        if remote.kitten {
+            reasons.push("当前终端是 kitty，远程已安装 kitten".to_string());
+            return (TransferMethod::Kitty, reasons);
+        }
+        reasons.push("当前终端是 kitty，但远程没有 kitten".to_string());
+    }
+    match (local.rsync, remote.rsync) {
+        (true, true) => {
+            reasons.push("本地和远程都安装了 rsync".to_string());
+            return (TransferMethod::Rsync, reasons);
+        }
+        (true, false) => reasons.push("远程没有 rsync".to_string()),
+        (false, true) => reasons.push("本地没有 rsync".to_string()),
+        (false, false) => reasons.push("本地和远程都没有 rsync".to_string()),
+    }
+    reasons.push("使用 scp".to_string());
+    (TransferMethod::Scp, reasons)
+}
+
+/// 选择传输方式，`verbose` 时打印探测结果和原因
+pub fn select_method(server: &ServerConfig, verbose: bool) -> TransferMethod {
+    // scp/rsync 都依赖系统 ssh，密码认证时无法探测也无法使用
+    let remote = match &server.auth_type {
+        AuthType::Password(_) => None,
+        _ => match remote_tools(server) {
+            Ok((tools, cached)) => {
+                if verbose {
+                    let names = tools.names();
+                    println!(
+                        "远程可用的传输工具{}: {}",
+                        if cached { "（缓存）" } else { "" },
+                        if names.is_empty() { "无".to_string() } else { names.join(", ") }
+                    );
+                }
+                Some(tools)
+            }
+            Err(e) => {
+                if verbose {
+                    println!("{:#}", e);
+                }
+                None
+            }
+        },
+    };
+
+    let (method, reasons) = choose(LocalTools::detect(), remote.as_ref());
+    if verbose {
+        for reason in &reasons {
+            println!("  - {}", reason);
+        }
+        if remote.as_ref().is_some_and(|tools| tools.rz) {
+            println!("  - 远程已安装 rz/sz，交互式会话中也可以用 rssh connect --rzsz 传输");
+        }
+    }
+    method
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chooses_transfer_method_from_probe() {
+        let tools = RemoteTools::parse("/usr/bin/rz\nrz\nrsync\n", 100);
+        assert_eq!(tools.names(), ["rz", "rsync"]);
+        assert!(!tools.is_stale(100 + PROBE_TTL));
+        assert!(tools.is_stale(101 + PROBE_TTL));
+
+        let kitty = LocalTools { kitty: true, rsync: true };
+        let plain = LocalTools { kitty: false, rsync: false };
+        // kitty 终端但远程没有 kitten 时不选 kitty
+        assert_eq!(choose(kitty, Some(&tools)).0, TransferMethod::Rsync);
+        assert_eq!(choose(plain, Some(&tools)).0, TransferMethod::Scp);
+        assert_eq!(choose(kitty, None).0, TransferMethod::Scp);
+
+        let with_kitten = RemoteTools { kitten: true, ..tools };
+        let (method, reasons) = choose(kitty, Some(&with_kitten));
+        assert_eq!(method, TransferMethod::Kitty);
+        assert_eq!(reasons, ["当前终端是 kitty，远程已安装 kitten"]);
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use shell_escape::escape;
+use std::path::Path;
+
+use crate::models::ServerConfig;
+use crate::utils::ssh::SshClient;
+use crate::utils::ssh_config::expand_tilde;
+
+/// 影响用户管理命令的发行版系列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distro {
+    /// Debian / Ubuntu：useradd，管理员组为 sudo
+    Debian,
+    /// RHEL / CentOS / Fedora 等：useradd，管理员组为 wheel
+    RedHat,
+    /// Alpine（busybox）：adduser / deluser，管理员组为 wheel
+    Alpine,
+    Other,
+}
+
+impl Distro {
+    /// 按 /etc/os-release 的 ID / ID_LIKE 识别
+    pub fn from_os_release(content: &str) -> Self {
+        let ids: Vec<String> = content
+            .lines()
+            .filter_map(|line| line.strip_prefix("ID=").or_else(|| line.strip_prefix("ID_LIKE=")))
+            .flat_map(|value| value.trim_matches('"').split_whitespace().map(str::to_lowercase).collect::<Vec<_>>())
+            .collect();
+        let has = |names: &[&str]| ids.iter().any(|id| names.contains(&id.as_str()));
+        if has(&["alpine"]) {
+            Distro::Alpine
+        } else if has(&["debian", "ubuntu"]) {
+            Distro::Debian
+        } else if has(&["rhel", "centos", "fedora", "rocky", "almalinux", "amzn", "suse", "opensuse"]) {
+            Distro::RedHat
+        } else {
+            Distro::Other
+        }
+    }
+
+    fn admin_group(self) -> &'static str {
+        match self {
+            Distro::Debian => "sudo",
+            _ => "wheel",
+        }
+    }
+}
+
+/// 用户名只允许常见的 Linux 用户名字符，避免拼进命令时产生歧义
+pub fn validate_username(username: &str) -> Result<()> {
+    let valid = !username.is_empty()
+        && username.len() <= 32
+        && username.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && username.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("无效的用户名: {}（只能包含小写字母、数字、_ 和 -，且以字母或 _ 开头）", username))
+    }
+}
+
+/// `--key` 参数：公钥文件路径，或直接给出的公钥内容
+pub fn load_public_key(value: &str) -> Result<String> {
+    let path = expand_tilde(value);
+    let key = if Path::new(&path).is_file() {
+        std::fs::read_to_string(&path).with_context(|| format!("无法读取公钥文件: {}", path))?
+    } else {
+        value.to_string()
+    };
+    let key = key.trim().to_string();
+    let looks_like_key = key.starts_with("ssh-") || key.starts_with("ecdsa-") || key.starts_with("sk-");
+    if !looks_like_key || key.contains('\n') {
+        return Err(anyhow::anyhow!("不是有效的单行公钥: {}", value));
+    }
+    Ok(key)
+}
+
+/// 创建用户（已存在时跳过），加入管理员组，并把公钥追加到 authorized_keys
+pub fn add_user_script(distro: Distro, username: &str, keys: &[String], admin: bool) -> String {
+    let user = escape(username.into());
+    let mut script = vec![format!("set -e; id {user} >/dev/null 2>&1 || ")];
+    script[0].push_str(&match distro {
+        Distro::Alpine => format!("adduser -D -s /bin/sh {user}"),
+        Distro::Other => format!(
+            "{{ if command -v useradd >/dev/null; then useradd -m -s /bin/sh {user}; else adduser --disabled-password --gecos '' {user}; fi; }}"
+        ),
+        Distro::Debian | Distro::RedHat => format!("useradd -m -s /bin/bash {user}"),
+    });
+
+    if admin {
+        let group = distro.admin_group();
+        script.push(match distro {
+            Distro::Alpine => format!("addgroup {user} {group}"),
+            _ => format!("usermod -aG {group} {user}"),
+        });
+    }
+
+    if !keys.is_empty() {
+        script.push(format!("home=$(eval echo ~{user})"));
+        script.push("mkdir -p \"$home/.ssh\"".to_string());
+        for key in keys {
+            let key = escape(key.as_str().into());
+            script.push(format!(
+                "grep -qxF {key} \"$home/.ssh/authorized_keys\" 2>/dev/null || echo {key} >> \"$home/.ssh/authorized_keys\""
+            ));
+        }
+        script.push(format!("chown -R {user}: \"$home/.ssh\""));
+        script.push("chmod 700 \"$home/.ssh\"; chmod 600 \"$home/.ssh/authorized_keys\"".to_string());
+    }
+    script.join("\n")
+}
+
+/// 删除用户，`keep_home` 时保留主目录
+pub fn remove_user_script(distro: Distro, username: &str, keep_home: bool) -> String {
+    let user = escape(username.into());
+    match (distro, keep_home) {
+        (Distro::Alpine, false) => format!("deluser --remove-home {user}"),
+        (Distro::Alpine, true) => format!("deluser {user}"),
+        (_, false) => format!("userdel -r {user}"),
+        (_, true) => format!("userdel {user}"),
+    }
+}
+
+/// 识别服务器的发行版
+pub fn detect_distro(client: &SshClient) -> Result<Distro> {
+    let (stdout, _, _) = client.execute_command("cat /etc/os-release 2>/dev/null")?;
+    Ok(Distro::from_os_release(&stdout))
+}
+
+/// 以 root 身份执行脚本并返回标准输出：登录用户不是 root 时通过 `sudo -n` 执行（需要免密 sudo）
+pub fn run_as_root(client: &SshClient, server: &ServerConfig, script: &str) -> Result<String> {
+    let command = if server.username == "root" {
+        format!("sh -c {}", escape(script.into()))
+    } else {
+        format!("sudo -n sh -c {}", escape(script.into()))
+    };
+    let (stdout, stderr, code) = client.execute_command(&command)?;
+    if code != 0 {
+        return Err(anyhow::anyhow!("远程命令失败（退出码 {}）: {}", code, stderr.trim()));
+    }
+    Ok(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_distro_specific_scripts() {
+        assert_eq!(Distro::from_os_release("ID=ubuntu\nID_LIKE=debian\n"), Distro::Debian);
+        assert_eq!(Distro::from_os_release("ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n"), Distro::RedHat);
+        assert_eq!(Distro::from_os_release("ID=alpine\n"), Distro::Alpine);
+
+        assert!(validate_username("deploy-1").is_ok());
+        assert!(validate_username("Bob; rm -rf /").is_err());
+
+        let key = "ssh-ed25519 AAAAC3Nza dev@laptop".to_string();
+        let script = add_user_script(Distro::Alpine, "deploy", std::slice::from_ref(&key), true);
+        assert!(script.contains("adduser -D -s /bin/sh deploy"));
+        assert!(script.contains("addgroup deploy wheel"));
+        assert!(script.contains("echo 'ssh-ed25519 AAAAC3Nza dev@laptop' >>"));
+
+        assert_eq!(remove_user_script(Distro::Debian, "deploy", false), "userdel -r deploy");
+    }
+}
@@ -0,0 +1,204 @@
+//! 数据保留（`config.toml` 的 `[retention]`）：按时间和大小清理会话录制、调试日志和批量任务结果，
+//! 避免数据目录和状态目录无限增长。
+//!
+//! 启动时每天最多自动清理一次（`auto = false` 关闭），`rssh clean` 立即按同样的规则清理。
+//! 审计日志（audit.log）不在清理范围内。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::job_store::JobStore;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const MB: u64 = 1024 * 1024;
+
+/// `config.toml` 中的 `[retention]`，各项为 0 时不限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    /// 启动时自动清理（每天最多一次）
+    pub auto: bool,
+    /// 会话录制保留的天数（默认 90）
+    pub recordings_max_age_days: u64,
+    /// 会话录制目录的总大小上限，超过时从最旧的开始删除（默认 1024 MB）
+    pub recordings_max_size_mb: u64,
+    /// 调试日志超过该大小时轮转为 debug.log.1（默认 10 MB）
+    pub debug_log_max_size_mb: u64,
+    /// 已结束的批量任务结果保留的天数（默认 90）
+    pub jobs_max_age_days: u64,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            auto: true,
+            recordings_max_age_days: 90,
+            recordings_max_size_mb: 1024,
+            debug_log_max_size_mb: 10,
+            jobs_max_age_days: 90,
+        }
+    }
+}
+
+/// 一次清理的结果
+#[derive(Debug, Default)]
+pub struct Report {
+    /// 删除的会话录制
+    pub recordings: Vec<PathBuf>,
+    /// 删除的会话录制总大小
+    pub recording_bytes: u64,
+    /// 轮转掉的调试日志大小，0 表示未轮转
+    pub debug_log_bytes: u64,
+    /// 删除的批量任务数
+    pub jobs: usize,
+}
+
+/// 按设置清理，`dry_run` 时只统计不删除
+pub fn clean(settings: &RetentionSettings, dry_run: bool) -> Result<Report> {
+    let mut report = Report::default();
+    let now = SystemTime::now();
+
+    let (recordings, bytes) = prune_recordings(
+        &crate::config::get_recording_dir()?,
+        days(settings.recordings_max_age_days),
+        settings.recordings_max_size_mb * MB,
+        now,
+        dry_run,
+    )?;
+    report.recordings = recordings;
+    report.recording_bytes = bytes;
+
+    report.debug_log_bytes = rotate_debug_log(&crate::config::get_debug_log_path()?, settings.debug_log_max_size_mb * MB, dry_run)?;
+
+    if let Some(max_age) = days(settings.jobs_max_age_days) {
+        let before = chrono::Utc::now().timestamp() - max_age.as_secs() as i64;
+        report.jobs = JobStore::new(crate::config::get_db_path()?)?.prune_before(before, dry_run)?;
+    }
+    Ok(report)
+}
+
+/// 启动时的自动清理：距上次清理不足一天时跳过，出错只打印警告
+pub fn auto_clean(settings: &RetentionSettings) {
+    if !settings.auto {
+        return;
+    }
+    let result = (|| -> Result<()> {
+        let marker = crate::config::get_state_dir()?.join("last-clean");
+        let recent = std::fs::metadata(&marker)
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|elapsed| elapsed < DAY));
+        if recent {
+            return Ok(());
+        }
+        clean(settings, false)?;
+        if let Some(dir) = marker.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("无法创建目录: {}", dir.display()))?;
+        }
+        std::fs::write(&marker, b"").with_context(|| format!("无法写入 {}", marker.display()))?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        eprintln!("警告: 自动清理失败: {}", e);
+    }
+}
+
+fn days(days: u64) -> Option<Duration> {
+    (days > 0).then(|| DAY * days as u32)
+}
+
+/// 删除超过 `max_age` 的录制，再从最旧的开始删除直到目录总大小不超过 `max_bytes`（0 为不限）。
+/// 返回删除的文件和总大小
+fn prune_recordings(dir: &Path, max_age: Option<Duration>, max_bytes: u64, now: SystemTime, dry_run: bool) -> Result<(Vec<PathBuf>, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok((Vec::new(), 0));
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok().filter(|meta| meta.is_file())?;
+            Some((meta.modified().ok()?, meta.len(), entry.path()))
+        })
+        .collect();
+    files.sort();
+
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    let mut removed = Vec::new();
+    let mut removed_bytes = 0;
+    for (modified, len, path) in files {
+        let expired = max_age.is_some_and(|max_age| now.duration_since(modified).is_ok_and(|age| age > max_age));
+        let over_size = max_bytes > 0 && total > max_bytes;
+        if !expired && !over_size {
+            // 文件按时间排序，之后的都更新
+            break;
+        }
+        if !dry_run {
+            std::fs::remove_file(&path).with_context(|| format!("无法删除 {}", path.display()))?;
+        }
+        total -= len;
+        removed_bytes += len;
+        removed.push(path);
+    }
+    Ok((removed, removed_bytes))
+}
+
+/// 调试日志超过 `max_bytes`（0 为不限）时重命名为 `<文件名>.1`，替换上一次轮转的文件。返回轮转的大小
+fn rotate_debug_log(path: &Path, max_bytes: u64, dry_run: bool) -> Result<u64> {
+    let len = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    if max_bytes == 0 || len <= max_bytes {
+        return Ok(0);
+    }
+    if !dry_run {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(".1");
+        std::fs::rename(path, &rotated).with_context(|| format!("无法轮转调试日志 {}", path.display()))?;
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(dir: &Path, name: &str, len: usize, age_days: u32) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, vec![b'x'; len]).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - DAY * age_days).unwrap();
+        path
+    }
+
+    #[test]
+    fn prunes_recordings_by_age_then_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = recording(dir.path(), "old.cast", 10, 100);
+        let older = recording(dir.path(), "mid.cast", 30, 20);
+        let newer = recording(dir.path(), "new.cast", 30, 1);
+        let now = SystemTime::now();
+
+        let (removed, bytes) = prune_recordings(dir.path(), days(90), 50, now, true).unwrap();
+        assert_eq!((removed, bytes), (vec![old.clone(), older.clone()], 40));
+        assert!(old.exists());
+
+        let (removed, _) = prune_recordings(dir.path(), days(90), 0, now, false).unwrap();
+        assert_eq!(removed, std::slice::from_ref(&old));
+        assert!(!old.exists() && older.exists() && newer.exists());
+
+        let (removed, _) = prune_recordings(dir.path(), None, 30, now, false).unwrap();
+        assert_eq!(removed, [older]);
+        assert!(newer.exists());
+    }
+
+    #[test]
+    fn rotates_debug_log_over_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("debug.log");
+        std::fs::write(&log, vec![b'x'; 100]).unwrap();
+        assert_eq!(rotate_debug_log(&log, 200, false).unwrap(), 0);
+        assert_eq!(rotate_debug_log(&log, 0, false).unwrap(), 0);
+        assert_eq!(rotate_debug_log(&log, 50, false).unwrap(), 100);
+        assert!(!log.exists());
+        assert!(dir.path().join("debug.log.1").exists());
+    }
+}
@@ -1,23 +1,191 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use russh::{client, ChannelId};
+use russh::{cipher, client, kex, mac, ChannelId, Preferred};
 use russh_keys::key;
-use std::net::ToSocketAddrs;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use crate::models::{AuthType, ServerConfig};
+use std::collections::HashMap;
+use std::os::fd::AsFd;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use crate::models::{audit_mode, Algorithms, AuthType, HostKeyPolicy, LoginBanner, ServerConfig};
+use crate::utils::address_fallback;
+use crate::utils::idle_lock::IdleLock;
+use crate::utils::motd::{self, MotdFilter};
+use crate::utils::session_escape::{
+    parse_command_line, EscapeAction, EscapeCommand, EscapeEvent, EscapeParser, Forward, ForwardKind, ESCAPE_HELP,
+};
+use crate::utils::session_registry::SessionRegistry;
 use crate::utils::ssh_config::expand_tilde;
+use crate::utils::traffic;
 use crate::utils::terminal_style::{Style, colors, Styled};
 
+/// russh 支持的算法，用于把配置中的算法名映射到 russh 的常量
+const KNOWN_KEX: &[kex::Name] = &[
+    kex::CURVE25519,
+    kex::CURVE25519_PRE_RFC_8731,
+    kex::DH_G14_SHA256,
+    kex::DH_G14_SHA1,
+    kex::DH_G1_SHA1,
+];
+const KNOWN_CIPHERS: &[cipher::Name] = &[
+    cipher::CHACHA20_POLY1305,
+    cipher::AES_256_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+];
+const KNOWN_MACS: &[mac::Name] = &[
+    mac::HMAC_SHA512_ETM,
+    mac::HMAC_SHA256_ETM,
+    mac::HMAC_SHA512,
+    mac::HMAC_SHA256,
+    mac::HMAC_SHA1_ETM,
+    mac::HMAC_SHA1,
+];
+/// 没有启用 openssl，不支持 RSA 主机密钥
+const KNOWN_HOST_KEYS: &[key::Name] = &[key::ED25519, key::ECDSA_SHA2_NISTP256];
+
+/// russh 的算法偏好只接受 'static 切片：同样的列表只分配一次，之后的连接复用
+trait AlgorithmName: AsRef<str> + Copy + PartialEq + Send + 'static {
+    fn interned() -> &'static Mutex<Vec<&'static [Self]>>;
+}
+
+macro_rules! algorithm_name {
+    ($($name:ty),*) => {$(
+        impl AlgorithmName for $name {
+            fn interned() -> &'static Mutex<Vec<&'static [Self]>> {
+                static LISTS: Mutex<Vec<&'static [$name]>> = Mutex::new(Vec::new());
+                &LISTS
+            }
+        }
+    )*};
+}
+
+algorithm_name!(kex::Name, cipher::Name, mac::Name, key::Name);
+
+fn intern<T: AlgorithmName>(names: Vec<T>) -> &'static [T] {
+    let mut lists = T::interned().lock().unwrap();
+    if let Some(list) = lists.iter().find(|list| ***list == names[..]) {
+        return list;
+    }
+    let list: &'static [T] = Box::leak(names.into_boxed_slice());
+    lists.push(list);
+    list
+}
+
+/// 按配置展开算法列表，russh 不支持的算法报错而不是忽略
+fn resolve_algorithms<T: AlgorithmName>(kind: &str, spec: &str, defaults: &[T], known: &[T]) -> Result<Vec<T>> {
+    let find = |name: &str| known.iter().find(|k| k.as_ref() == name).copied();
+    let default_names: Vec<&str> = defaults.iter().map(|name| name.as_ref()).filter(|name| find(name).is_some()).collect();
+    let names = Algorithms::resolve(spec, &default_names)
+        .iter()
+        .map(|name| {
+            find(name).ok_or_else(|| {
+                let known: Vec<&str> = known.iter().map(|k| k.as_ref()).collect();
+                anyhow::anyhow!("russh 模式不支持{}算法 {}，可用: {}", kind, name, known.join(", "))
+            })
+        })
+        .collect::<Result<Vec<T>>>()?;
+    if names.is_empty() {
+        return Err(anyhow::anyhow!("{}算法配置 {} 没有留下任何算法", kind, spec));
+    }
+    Ok(names)
+}
+
+fn pick_algorithms<T: AlgorithmName>(
+    kind: &str,
+    spec: Option<&str>,
+    defaults: &'static [T],
+    known: &[T],
+) -> Result<&'static [T]> {
+    match spec {
+        Some(spec) => resolve_algorithms(kind, spec, defaults, known).map(intern),
+        None => Ok(defaults),
+    }
+}
+
+fn preferred_algorithms(server: &ServerConfig) -> Result<Preferred> {
+    let base = if server.compression { Preferred::COMPRESSED } else { Preferred::DEFAULT };
+    let algorithms = server.algorithms();
+
+    let kex = match algorithms.kex.as_deref() {
+        Some(spec) => {
+            let mut names = resolve_algorithms("密钥交换", spec, base.kex, KNOWN_KEX)?;
+            // 扩展协商标记不是真正的算法，替换列表时也要保留
+            names.extend([kex::EXTENSION_SUPPORT_AS_CLIENT, kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT]);
+            intern(names)
+        }
+        None => base.kex,
+    };
+
+    Ok(Preferred {
+        kex,
+        key: pick_algorithms("主机密钥", algorithms.host_key.as_deref(), base.key, KNOWN_HOST_KEYS)?,
+        cipher: pick_algorithms("加密", algorithms.ciphers.as_deref(), base.cipher, KNOWN_CIPHERS)?,
+        mac: pick_algorithms("MAC ", algorithms.macs.as_deref(), base.mac, KNOWN_MACS)?,
+        ..base
+    })
+}
+
 // SSH客户端处理程序
+/// 会话与 Handler 共享的状态
+#[derive(Clone, Default)]
+struct SessionState {
+    /// 交互式 shell 所在的通道，端口转发通道的数据不输出到终端
+    shell_channel: Arc<Mutex<Option<ChannelId>>>,
+    /// 远程转发：服务器上的监听端口 -> 本机要连接的目标
+    remote_forwards: Arc<Mutex<HashMap<u32, (String, u16)>>>,
+    /// 空闲锁定，锁定期间远程输出先保留
+    idle_lock: Arc<Mutex<Option<IdleLock>>>,
+    /// 会话开头登录信息的处理，shell 打开后设置
+    motd: Arc<Mutex<Option<MotdFilter>>>,
+    /// shell / 命令通道已关闭
+    closed: Arc<AtomicBool>,
+    /// 远程 shell / 命令的退出码
+    exit_status: Arc<Mutex<Option<u32>>>,
+}
+
+impl SessionState {
+    fn is_shell(&self, channel: ChannelId) -> bool {
+        *self.shell_channel.lock().unwrap() == Some(channel)
+    }
+
+    /// 会话已锁定时保留输出并返回 true
+    fn hold_if_locked(&self, data: &[u8]) -> bool {
+        match self.idle_lock.lock().unwrap().as_mut() {
+            Some(lock) if lock.is_locked() => {
+                lock.hold(data);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 经过登录信息处理后应显示的输出
+    fn filter_motd(&self, data: &[u8]) -> Vec<u8> {
+        match self.motd.lock().unwrap().as_mut() {
+            Some(motd) => motd.feed(data),
+            None => data.to_vec(),
+        }
+    }
+}
+
 struct Handler {
     connection_success: bool,
+    host: String,
+    port: u16,
+    host_key_policy: HostKeyPolicy,
+    state: SessionState,
 }
 
 impl Handler {
-    fn new() -> Self {
+    fn new(server: &ServerConfig, state: SessionState) -> Self {
         Handler {
             connection_success: false,
+            host: server.host.clone(),
+            port: server.port,
+            host_key_policy: server.host_key_policy(),
+            state,
         }
     }
 }
@@ -29,10 +197,29 @@ impl client::Handler for Handler {
     
     async fn check_server_key(
         self,
-        _server_public_key: &key::PublicKey,
+        server_public_key: &key::PublicKey,
     ) -> Result<(Self, bool), Self::Error> {
-        // 简化起见，接受所有服务器密钥
-        Ok((self, true))
+        if self.host_key_policy == HostKeyPolicy::Off {
+            return Ok((self, true));
+        }
+
+        let accepted = match russh_keys::check_known_hosts(&self.host, self.port, server_public_key) {
+            Ok(true) => true,
+            Ok(false) if self.host_key_policy == HostKeyPolicy::AcceptNew => {
+                russh_keys::learn_known_hosts(&self.host, self.port, server_public_key)
+                    .with_context(|| "无法写入 known_hosts")?;
+                true
+            }
+            Ok(false) => {
+                eprintln!("known_hosts 中没有 {} 的主机密钥（主机密钥策略为 strict）", self.host);
+                false
+            }
+            Err(e) => {
+                eprintln!("{} 的主机密钥与 known_hosts 中记录的不一致，可能存在中间人攻击: {}", self.host, e);
+                false
+            }
+        };
+        Ok((self, accepted))
     }
 
     async fn channel_open_confirmation(
@@ -50,12 +237,19 @@ impl client::Handler for Handler {
 
     async fn data(
         self,
-        _channel: ChannelId,
+        channel: ChannelId,
         data: &[u8],
         session: client::Session,
     ) -> Result<(Self, client::Session), Self::Error> {
+        if !self.state.is_shell(channel) || self.state.hold_if_locked(data) {
+            return Ok((self, session));
+        }
+        // 登录信息还没有结束时先缓存
+        let data_vec = self.state.filter_motd(data);
+        if data_vec.is_empty() {
+            return Ok((self, session));
+        }
         // 接收到数据，使用样式处理后打印到标准输出
-        let data_vec = data.to_vec();
         tokio::spawn(async move {
             // 创建默认样式
             let style = Style::new()
@@ -76,13 +270,39 @@ impl client::Handler for Handler {
         Ok((self, session))
     }
 
+    async fn exit_status(
+        self,
+        channel: ChannelId,
+        exit_status: u32,
+        session: client::Session,
+    ) -> Result<(Self, client::Session), Self::Error> {
+        if self.state.is_shell(channel) {
+            *self.state.exit_status.lock().unwrap() = Some(exit_status);
+        }
+        Ok((self, session))
+    }
+
+    async fn channel_close(
+        self,
+        channel: ChannelId,
+        session: client::Session,
+    ) -> Result<(Self, client::Session), Self::Error> {
+        if self.state.is_shell(channel) {
+            self.state.closed.store(true, Ordering::SeqCst);
+        }
+        Ok((self, session))
+    }
+
     async fn extended_data(
         self,
-        _channel: ChannelId,
+        channel: ChannelId,
         _data_type: u32,
         data: &[u8],
         session: client::Session,
     ) -> Result<(Self, client::Session), Self::Error> {
+        if !self.state.is_shell(channel) || self.state.hold_if_locked(data) {
+            return Ok((self, session));
+        }
         // 接收到扩展数据（通常是stderr），使用红色样式打印到stderr
         let data_vec = data.to_vec();
         tokio::spawn(async move {
@@ -104,23 +324,132 @@ impl client::Handler for Handler {
         });
         Ok((self, session))
     }
+
+    async fn server_channel_open_forwarded_tcpip(
+        self,
+        channel: russh::Channel<client::Msg>,
+        _connected_address: &str,
+        connected_port: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        session: client::Session,
+    ) -> Result<(Self, client::Session), Self::Error> {
+        let target = self.state.remote_forwards.lock().unwrap().get(&connected_port).cloned();
+        if let Some((host, port)) = target {
+            tokio::spawn(async move {
+                if let Ok(mut stream) = tokio::net::TcpStream::connect((host.as_str(), port)).await {
+                    let _ = tokio::io::copy_bidirectional(&mut stream, &mut channel.into_stream()).await;
+                }
+            });
+        }
+        Ok((self, session))
+    }
+}
+
+type SharedHandle = Arc<tokio::sync::Mutex<client::Handle<Handler>>>;
+
+/// 会话运行中通过 `~C` 添加的端口转发
+struct RusshForward {
+    spec: Forward,
+    /// 本地转发的监听任务
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl RusshForward {
+    async fn open(handle: &SharedHandle, state: &SessionState, spec: Forward) -> Result<Self> {
+        match spec.kind {
+            ForwardKind::Local => {
+                let listener = tokio::net::TcpListener::bind(spec.listen_address()).await
+                    .with_context(|| format!("无法监听 {}", spec.listen_address()))?;
+                let handle = handle.clone();
+                let (host, port) = (spec.host.clone(), spec.host_port as u32);
+                let task = tokio::spawn(async move {
+                    while let Ok((mut stream, peer)) = listener.accept().await {
+                        let channel = handle.lock().await
+                            .channel_open_direct_tcpip(host.clone(), port, peer.ip().to_string(), peer.port() as u32)
+                            .await;
+                        if let Ok(channel) = channel {
+                            tokio::spawn(async move {
+                                let _ = tokio::io::copy_bidirectional(&mut stream, &mut channel.into_stream()).await;
+                            });
+                        }
+                    }
+                });
+                Ok(RusshForward { spec, task: Some(task) })
+            }
+            ForwardKind::Remote => {
+                state.remote_forwards.lock().unwrap()
+                    .insert(spec.port as u32, (spec.host.clone(), spec.host_port));
+                let accepted = handle.lock().await
+                    .tcpip_forward(spec.bind_address.clone(), spec.port as u32).await
+                    .unwrap_or(false);
+                if !accepted {
+                    state.remote_forwards.lock().unwrap().remove(&(spec.port as u32));
+                    return Err(anyhow::anyhow!("服务器拒绝在 {} 上监听", spec.listen_address()));
+                }
+                Ok(RusshForward { spec, task: None })
+            }
+        }
+    }
+
+    async fn close(self, handle: &SharedHandle, state: &SessionState) {
+        match self.task {
+            Some(task) => task.abort(),
+            None => {
+                state.remote_forwards.lock().unwrap().remove(&(self.spec.port as u32));
+                let _ = handle.lock().await
+                    .cancel_tcpip_forward(self.spec.bind_address.clone(), self.spec.port as u32).await;
+            }
+        }
+    }
+}
+
+/// 执行 `~C` 命令行中输入的命令
+async fn run_escape_command(line: &str, handle: &SharedHandle, state: &SessionState, forwards: &mut Vec<RusshForward>) {
+    match parse_command_line(line) {
+        Ok(EscapeCommand::AddForward(spec)) => match RusshForward::open(handle, state, spec).await {
+            Ok(forward) => {
+                println!("已添加端口转发 {}", forward.spec);
+                forwards.push(forward);
+            }
+            Err(e) => println!("{:#}", e),
+        },
+        Ok(EscapeCommand::CancelForward(kind, address)) => {
+            match forwards.iter().position(|f| f.spec.kind == kind && f.spec.listen_address() == address) {
+                Some(index) => {
+                    forwards.remove(index).close(handle, state).await;
+                    println!("已取消端口转发 {}", address);
+                }
+                None => println!("没有监听在 {} 的端口转发", address),
+            }
+        }
+        Err(e) => println!("{}", e),
+    }
 }
 
 // 使用russh库连接远程服务器
-pub async fn connect_with_russh(server: &ServerConfig) -> Result<()> {
+/// 打开交互式 shell，`command` 不为空时在终端中执行该命令。返回远程的退出码
+pub async fn connect_with_russh(server: &ServerConfig, mut registry: Option<SessionRegistry>, command: Option<&str>) -> Result<i32> {
+    if audit_mode() {
+        return Err(anyhow::anyhow!("审计模式下不能使用 russh 模式（无法保证只使用 FIPS 认可的算法），请使用 system 或 library 模式"));
+    }
+    if let Some(jump) = server.proxy_jump.as_ref().or(server.jump_host.as_ref()) {
+        return Err(anyhow::anyhow!("russh 模式不支持跳板机 {}，请使用 system 或 library 模式", jump));
+    }
+    if server.proxy_command.is_some() {
+        return Err(anyhow::anyhow!("russh 模式不支持代理命令，请使用 system 或 library 模式"));
+    }
     // 配置客户端
     let config = client::Config {
+        preferred: preferred_algorithms(server)?,
+        // russh 模式的防空闲只支持 keepalive
+        keepalive_interval: server.anti_idle.map(|anti_idle| std::time::Duration::from_secs(anti_idle.interval as u64)),
         ..Default::default()
     };
 
     let config = Arc::new(config);
-    let handler = Handler::new();
-
-    // 解析服务器地址
-    let socket_addr = format!("{}:{}", server.host, server.port)
-        .to_socket_addrs()?
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("无法解析服务器地址"))?;
+    let state = SessionState::default();
+    let handler = Handler::new(server, state.clone());
 
     // 连接到服务器
     let style = Style::new()
@@ -132,7 +461,14 @@ pub async fn connect_with_russh(server: &ServerConfig) -> Result<()> {
         server.port
     ).style(style));
 
-    let mut session = client::connect(config, socket_addr, handler).await
+    // 与内置库模式相同，各地址错开尝试（happy eyeballs）
+    let target = server.clone();
+    let (socket, _) = tokio::task::spawn_blocking(move || address_fallback::connect(&target)).await??;
+    socket.set_nonblocking(true)?;
+    let socket = tokio::net::TcpStream::from_std(socket)?;
+    // 在会话之后释放，函数返回时记录这条连接的流量
+    let _meter = traffic::Meter::new(server, socket.as_fd().try_clone_to_owned()?);
+    let mut session = client::connect_stream(config, socket, handler).await
         .with_context(|| "无法连接到服务器")?;
 
     // 进行认证
@@ -144,31 +480,32 @@ pub async fn connect_with_russh(server: &ServerConfig) -> Result<()> {
                 return Err(anyhow::anyhow!("认证失败：服务器拒绝了密码"));
             }
         },
-        AuthType::Key(key_path) => {
-            let expanded_path = expand_tilde(key_path);
-            
-            match russh_keys::load_secret_key(&expanded_path, None) {
-                Ok(key_pair) => {
-                    let auth_success = session.authenticate_publickey(&server.username, Arc::new(key_pair)).await
-                        .with_context(|| "密钥认证失败")?;
-                    
-                    if !auth_success {
-                        return Err(anyhow::anyhow!("认证失败：服务器拒绝了密钥"));
-                    }
-                },
-                Err(e) => {
-                    if e.to_string().contains("ssh-rsa") {
+        AuthType::Key(_) => {
+            // 依次尝试各私钥，直到有一个被服务器接受
+            let mut authenticated = false;
+            for key_path in server.identities() {
+                let expanded_path = expand_tilde(key_path);
+                let key_pair = match russh_keys::load_secret_key(&expanded_path, None) {
+                    Ok(key_pair) => key_pair,
+                    Err(e) if e.to_string().contains("ssh-rsa") => {
                         return Err(anyhow::anyhow!(
                             "无法加载SSH-RSA类型的密钥: {}\n\
                              原因: 当前使用的russh库不支持ssh-rsa密钥格式\n\
                              解决方案: 请使用--mode system或--mode exec连接模式，\n\
                              或者生成更新的密钥类型如ED25519: ssh-keygen -t ed25519", 
                              expanded_path));
-                    } else {
-                        return Err(anyhow::anyhow!("无法加载私钥: {}\n原因: {}", expanded_path, e));
                     }
+                    Err(e) => return Err(anyhow::anyhow!("无法加载私钥: {}\n原因: {}", expanded_path, e)),
+                };
+                authenticated = session.authenticate_publickey(&server.username, Arc::new(key_pair)).await
+                    .with_context(|| "密钥认证失败")?;
+                if authenticated {
+                    break;
                 }
             }
+            if !authenticated {
+                return Err(anyhow::anyhow!("认证失败：服务器拒绝了密钥"));
+            }
         },
         AuthType::Agent => {
             return Err(anyhow::anyhow!("Russh模式暂不支持SSH Agent认证"));
@@ -178,6 +515,27 @@ pub async fn connect_with_russh(server: &ServerConfig) -> Result<()> {
     // 打开通道
     let channel = session.channel_open_session().await
         .with_context(|| "无法打开会话通道")?;
+    *state.shell_channel.lock().unwrap() = Some(channel.id());
+    let session: SharedHandle = Arc::new(tokio::sync::Mutex::new(session));
+    // `rssh connect -L` 的转发随会话建立，失败时与 ssh 一样只提示
+    let mut forwards: Vec<RusshForward> = Vec::new();
+    for spec in &server.local_forwards {
+        match RusshForward::open(&session, &state, spec.clone()).await {
+            Ok(forward) => {
+                println!("已添加端口转发 {}", forward.spec);
+                forwards.push(forward);
+            }
+            Err(e) => println!("{:#}", e),
+        }
+    }
+    if let Some(registry) = registry.as_mut() {
+        registry.set_forwards(forwards.iter().map(|f| f.spec.to_string()).collect());
+    }
+    let mut escapes = EscapeParser::default();
+    *state.idle_lock.lock().unwrap() = IdleLock::from_config()?;
+    // 执行命令时远程不输出登录信息，命令的输出原样显示
+    let banner = if command.is_some() { LoginBanner::Show } else { server.login_banner };
+    *state.motd.lock().unwrap() = Some(MotdFilter::new(banner, motd::capture_path(&server.name)));
 
     // 设置终端大小
     let terminal_size = crate::utils::ssh::terminal_size();
@@ -194,13 +552,17 @@ pub async fn connect_with_russh(server: &ServerConfig) -> Result<()> {
         .with_context(|| "无法请求PTY")?;
 
     // 请求shell
-    channel.request_shell(true).await
-        .with_context(|| "无法请求shell")?;
+    match command {
+        Some(command) => channel.exec(true, command).await
+            .with_context(|| format!("无法执行命令: {}", command))?,
+        None => channel.request_shell(true).await
+            .with_context(|| "无法请求shell")?,
+    }
 
     let style = Style::new()
         .fg(colors::GREEN)
         .bold();
-    println!("{}", "已连接，启动交互式shell...".style(style));
+    println!("{}", "已连接，启动交互式shell...（行首输入 ~? 查看转义命令）".style(style));
 
     // 设置标准输入
     let stdin = tokio::io::stdin();
@@ -226,13 +588,85 @@ pub async fn connect_with_russh(server: &ServerConfig) -> Result<()> {
             } => {
                 match result {
                     Ok(0) => break, // EOF
+                    Ok(n) if state.idle_lock.lock().unwrap().as_ref().is_some_and(IdleLock::is_locked) => {
+                        // 锁定期间的输入只用于解锁
+                        let output = state.idle_lock.lock().unwrap().as_mut()
+                            .map(|lock| lock.unlock_input(&buffer[0..n]))
+                            .unwrap_or_default();
+                        if !state.idle_lock.lock().unwrap().as_ref().is_some_and(IdleLock::is_locked) {
+                            set_echo(true);
+                        }
+                        tokio::io::stdout().write_all(&output).await?;
+                        tokio::io::stdout().flush().await?;
+                    },
                     Ok(n) => {
-                        // 发送数据到远程
-                        if let Err(e) = channel.data(&buffer[0..n]).await {
-                            let style = Style::new()
-                                .fg(colors::RED)
-                                .bold();
-                            eprintln!("{}", format!("发送数据失败: {}", e).style(style));
+                        if let Some(lock) = state.idle_lock.lock().unwrap().as_mut() {
+                            lock.touch();
+                        }
+                        // 标准输入是行缓冲的，转义序列后面跟着的换行不发送到远程
+                        let mut after_escape = false;
+                        let mut disconnect = false;
+                        for event in escapes.feed(&buffer[0..n]) {
+                            let data = match event {
+                                EscapeEvent::Data(data) => data,
+                                EscapeEvent::Action(action) => {
+                                    after_escape = true;
+                                    match action {
+                                        EscapeAction::Disconnect => {
+                                            disconnect = true;
+                                            break;
+                                        }
+                                        EscapeAction::Help => print!("{}", ESCAPE_HELP.replace('\r', "")),
+                                        EscapeAction::ListForwards => {
+                                            println!("当前端口转发:");
+                                            for forward in &forwards {
+                                                println!("  {}", forward.spec);
+                                            }
+                                        }
+                                        EscapeAction::CommandLine => {
+                                            print!("ssh> ");
+                                            let _ = std::io::Write::flush(&mut std::io::stdout());
+                                            let mut line = String::new();
+                                            stdin_reader.read_line(&mut line).await?;
+                                            if !line.trim().is_empty() {
+                                                run_escape_command(&line, &session, &state, &mut forwards).await;
+                                                if let Some(registry) = registry.as_mut() {
+                                                    registry.set_forwards(forwards.iter().map(|f| f.spec.to_string()).collect());
+                                                }
+                                            }
+                                        }
+                                        EscapeAction::Marker => println!("russh 模式不支持会话录制，请使用 rssh shell --record"),
+                                        EscapeAction::Scrollback => println!("russh 模式不支持回滚缓冲，请使用内置库模式（--mode library）"),
+                                        EscapeAction::LocalShell => {
+                                            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+                                            println!("进入本地 shell，exit 后回到会话");
+                                            if let Err(e) = tokio::process::Command::new(&shell).status().await {
+                                                eprintln!("无法启动 {}: {}", shell, e);
+                                            }
+                                        }
+                                    }
+                                    continue;
+                                }
+                            };
+                            let data = match data.strip_prefix(b"\n") {
+                                Some(rest) if after_escape => rest.to_vec(),
+                                _ => data,
+                            };
+                            after_escape = false;
+                            if data.is_empty() {
+                                continue;
+                            }
+                            // 发送数据到远程
+                            if let Err(e) = channel.data(&data[..]).await {
+                                let style = Style::new()
+                                    .fg(colors::RED)
+                                    .bold();
+                                eprintln!("{}", format!("发送数据失败: {}", e).style(style));
+                                disconnect = true;
+                                break;
+                            }
+                        }
+                        if disconnect {
                             break;
                         }
                     },
@@ -245,30 +679,75 @@ pub async fn connect_with_russh(server: &ServerConfig) -> Result<()> {
                     }
                 }
             },
-            // 定期检查退出条件
+            // 定期检查退出条件和空闲时间
             _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
-                continue;
+                if state.closed.load(Ordering::SeqCst) {
+                    break;
+                }
+                let rest = state.motd.lock().unwrap().as_mut().map(MotdFilter::poll).unwrap_or_default();
+                if !rest.is_empty() {
+                    tokio::io::stdout().write_all(&rest).await?;
+                    tokio::io::stdout().flush().await?;
+                }
+                let message = state.idle_lock.lock().unwrap().as_mut().and_then(IdleLock::tick);
+                if let Some(message) = message {
+                    // 锁定后输入解锁密码时不回显
+                    if state.idle_lock.lock().unwrap().as_ref().is_some_and(IdleLock::is_locked) {
+                        set_echo(false);
+                    }
+                    tokio::io::stdout().write_all(message.as_bytes()).await?;
+                    tokio::io::stdout().flush().await?;
+                }
             }
         }
     }
 
+    if state.idle_lock.lock().unwrap().as_ref().is_some_and(IdleLock::is_locked) {
+        set_echo(true);
+        print!("\x1b[?1049l");
+    }
+
     // 关闭连接
     let style = Style::new()
         .fg(colors::YELLOW)
         .bold();
     println!("{}", "正在关闭连接...".style(style));
+    for forward in forwards {
+        forward.close(&session, &state).await;
+    }
 
-    Ok(())
+    let exit_status = *state.exit_status.lock().unwrap();
+    Ok(exit_status.map_or(0, |code| code as i32))
+}
+
+/// 打开或关闭终端回显
+fn set_echo(enabled: bool) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = std::io::stdin().as_raw_fd();
+        if let Ok(mut attrs) = termios::Termios::from_fd(fd) {
+            if enabled {
+                attrs.c_lflag |= termios::ECHO;
+            } else {
+                attrs.c_lflag &= !termios::ECHO;
+            }
+            let _ = termios::tcsetattr(fd, termios::TCSANOW, &attrs);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = enabled;
 }
 
 // 使用russh库进行连接的入口函数
-pub fn russh_connect(server: &ServerConfig) -> Result<()> {
+pub fn russh_connect(server: &ServerConfig, registry: Option<SessionRegistry>, command: Option<&str>) -> Result<i32> {
     // 创建tokio运行时
     let runtime = tokio::runtime::Runtime::new()
         .with_context(|| "无法创建tokio运行时")?;
     
     // 在tokio运行时中执行异步连接函数
-    let result = runtime.block_on(connect_with_russh(server));
+    let result = runtime.block_on(connect_with_russh(server, registry, command));
     
     // 处理错误，提供使用system模式的建议
     if let Err(err) = &result {
@@ -281,4 +760,23 @@ pub fn russh_connect(server: &ServerConfig) -> Result<()> {
     }
     
     result
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_supported_algorithms_and_rejects_others() {
+        let kex = resolve_algorithms("密钥交换", "+diffie-hellman-group1-sha1", Preferred::DEFAULT.kex, KNOWN_KEX).unwrap();
+        assert_eq!(kex, [kex::CURVE25519, kex::CURVE25519_PRE_RFC_8731, kex::DH_G14_SHA256, kex::DH_G1_SHA1]);
+        let ciphers = resolve_algorithms("加密", "-chacha20-poly1305@openssh.com", Preferred::DEFAULT.cipher, KNOWN_CIPHERS).unwrap();
+        assert_eq!(ciphers[0], cipher::AES_256_GCM);
+
+        assert!(resolve_algorithms("加密", "aes128-gcm@openssh.com", Preferred::DEFAULT.cipher, KNOWN_CIPHERS).is_err());
+        assert!(resolve_algorithms("主机密钥", "ssh-rsa", Preferred::DEFAULT.key, KNOWN_HOST_KEYS).is_err());
+        assert!(resolve_algorithms("主机密钥", "-ssh-ed25519,ecdsa-sha2-nistp256", Preferred::DEFAULT.key, KNOWN_HOST_KEYS).is_err());
+
+        // 同样的列表只分配一次
+        assert!(std::ptr::eq(intern(vec![mac::HMAC_SHA256]), intern(vec![mac::HMAC_SHA256])));
+    }
+}
@@ -3,6 +3,8 @@ use std::path::Path;
 use std::process::{Command, Stdio};
 use std::io::{self, Write};
 
+use crate::utils::secure_temp;
+
 /// RZSZ特征标识
 const ZMODEM_DETECT: &[u8] = b"**\x18B00000000000000\r\n";
 const RZ_COMMAND: &[u8] = b"rz\r";
@@ -71,18 +73,7 @@ fn handle_receive_file(_channel: &mut ssh2::Channel) -> Result<()> {
         fi
     "#, file_path);
     
-    let mut temp_script = std::env::temp_dir();
-    temp_script.push("rssh_rz_helper.sh");
-    std::fs::write(&temp_script, script)?;
-    
-    // 设置执行权限
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(&temp_script)?.permissions();
-        perms.set_mode(0o755);
-        std::fs::set_permissions(&temp_script, perms)?;
-    }
+    let temp_script = secure_temp::write("rz_helper.sh", script, 0o700)?;
     
     // 启动进程执行rz操作
     let mut cmd = Command::new(&temp_script)
@@ -133,18 +124,7 @@ fn handle_send_file(channel: &mut ssh2::Channel, args: &str) -> Result<()> {
         fi
     "#, save_path);
     
-    let mut temp_script = std::env::temp_dir();
-    temp_script.push("rssh_sz_helper.sh");
-    std::fs::write(&temp_script, script)?;
-    
-    // 设置执行权限
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(&temp_script)?.permissions();
-        perms.set_mode(0o755);
-        std::fs::set_permissions(&temp_script, perms)?;
-    }
+    let temp_script = secure_temp::write("sz_helper.sh", script, 0o700)?;
     
     // 启动进程执行sz操作
     let mut cmd = Command::new(&temp_script)
@@ -0,0 +1,129 @@
+//! 内置库模式会话的回滚缓冲：保留最近的远程输出，行首输入 `~/` 时在本地分页器中查看和搜索，
+//! 服务器上没有 tmux 时也能找回已经滚出屏幕的输出。
+
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+use crate::utils::secure_temp;
+
+/// 默认保留的输出大小（KB）
+pub const DEFAULT_KB: usize = 1024;
+
+static CAPACITY_KB: OnceLock<usize> = OnceLock::new();
+
+/// 设置回滚缓冲的大小（KB，0 为关闭），只在启动时调用一次
+pub fn configure(kb: usize) {
+    let _ = CAPACITY_KB.set(kb);
+}
+
+#[derive(Debug)]
+pub struct Scrollback {
+    capacity: usize,
+    data: VecDeque<u8>,
+}
+
+impl Scrollback {
+    /// 按全局配置创建，关闭时返回 None
+    pub fn from_config() -> Option<Self> {
+        let kb = CAPACITY_KB.get().copied().unwrap_or(DEFAULT_KB);
+        (kb > 0).then(|| Scrollback::new(kb * 1024))
+    }
+
+    pub fn new(capacity: usize) -> Self {
+        Scrollback { capacity, data: VecDeque::new() }
+    }
+
+    /// 记录一段远程输出，超过容量时丢弃最早的部分
+    pub fn push(&mut self, output: &[u8]) {
+        let output = &output[output.len().saturating_sub(self.capacity)..];
+        let overflow = (self.data.len() + output.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(output);
+    }
+
+    /// 去掉颜色等转义序列后的纯文本；被 `\r` 覆盖的内容（进度条等）只保留最后一次
+    pub fn text(&self) -> String {
+        let raw: Vec<u8> = self.data.iter().copied().collect();
+        let raw = String::from_utf8_lossy(&raw);
+        let mut text = String::with_capacity(raw.len());
+        let mut line = String::new();
+        let mut chars = raw.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => skip_escape(&mut chars),
+                '\n' => {
+                    text.push_str(line.trim_end());
+                    text.push('\n');
+                    line.clear();
+                },
+                '\r' if chars.peek() != Some(&'\n') => line.clear(),
+                '\x08' => {
+                    line.pop();
+                },
+                '\t' => line.push('\t'),
+                c if c.is_control() => {},
+                c => line.push(c),
+            }
+        }
+        text.push_str(&line);
+        text
+    }
+
+    /// 在分页器（`$PAGER`，默认 `less +G`）中打开回滚内容，分页器退出后返回
+    pub fn open_pager(&self) -> Result<()> {
+        let path = secure_temp::write("scrollback.txt", self.text(), 0o600)?;
+        let pager = std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()).unwrap_or_else(|| "less +G".to_string());
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", pager))
+            .arg("sh")
+            .arg(&path)
+            .status()
+            .with_context(|| format!("无法启动分页器: {}", pager));
+        let _ = std::fs::remove_file(&path);
+        status?;
+        Ok(())
+    }
+}
+
+/// 跳过 ESC 之后的转义序列：CSI（`ESC [ ... 终止字符`）、OSC（`ESC ] ... BEL/ST`）以及两个字符的序列
+fn skip_escape(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    match chars.next() {
+        Some('[') => {
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        },
+        Some(']') => {
+            while let Some(c) = chars.next() {
+                if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                    break;
+                }
+            }
+        },
+        // 字符集选择等带一个参数的序列
+        Some('(' | ')' | '#') => {
+            chars.next();
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_recent_output_as_plain_text() {
+        let mut scrollback = Scrollback::new(128);
+        scrollback.push(b"\x1b]0;user@host\x07\x1b[1;32m$\x1b[0m ls  \r\n");
+        scrollback.push(b"a.txt\tb.txt\r\n 10%\r 50%\r100%\r\n$ ecoh\x08\x08ho");
+        assert_eq!(scrollback.text(), "$ ls\na.txt\tb.txt\n100%\n$ echo");
+
+        scrollback.push(&[b'x'; 200]);
+        assert_eq!(scrollback.text(), "x".repeat(128));
+    }
+}
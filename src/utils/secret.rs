@@ -0,0 +1,389 @@
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+
+/// 认证提供者：根据引用字符串取回真正的凭据。
+///
+/// 每个提供者对应一个前缀（如 `pass:`），数据库中保存 `<前缀><引用>`，
+/// 或者在服务器上配置 `secret_provider` 后只保存 `<引用>` 本身。
+/// 所有连接路径都通过 [`ServerConfig::with_resolved_secrets`](crate::models::ServerConfig::with_resolved_secrets)
+/// 解析凭据，新增来源只需实现该 trait 并加入 [`builtin_providers`]。
+pub trait AuthProvider {
+    /// 提供者名称，同时也是引用前缀（不含冒号）
+    fn name(&self) -> &'static str;
+
+    /// 根据引用取回密钥
+    fn fetch(&self, reference: &str) -> Result<String>;
+
+    /// 值以 `<名称>:` 开头时是否视为该提供者的引用
+    fn prefixed(&self) -> bool {
+        true
+    }
+}
+
+/// `db`：数据库中保存的就是明文凭据，原样返回。未配置提供者时的默认行为，
+/// 不识别 `db:` 前缀，避免误伤以此开头的明文密码
+pub struct StaticProvider;
+
+impl AuthProvider for StaticProvider {
+    fn name(&self) -> &'static str {
+        "db"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        Ok(reference.to_string())
+    }
+
+    fn prefixed(&self) -> bool {
+        false
+    }
+}
+
+/// `env:NAME`：读取环境变量
+pub struct EnvProvider;
+
+impl AuthProvider for EnvProvider {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        std::env::var(reference)
+            .with_context(|| format!("无法读取环境变量中的密钥: {}", reference))
+    }
+}
+
+/// `cmd:<shell 命令>`：通过 `sh -c` 执行命令，取标准输出
+pub struct CommandProvider;
+
+impl AuthProvider for CommandProvider {
+    fn name(&self) -> &'static str {
+        "cmd"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(reference);
+        capture_secret(cmd, reference)
+    }
+}
+
+/// `pass:<路径>`：从 password-store 读取，按 pass 的约定只取第一行
+pub struct PassProvider;
+
+impl AuthProvider for PassProvider {
+    fn name(&self) -> &'static str {
+        "pass"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        ensure_installed("pass", "请先安装 password-store: https://www.passwordstore.org/")?;
+        let mut cmd = Command::new("pass");
+        cmd.args(["show", reference]);
+        let output = capture_secret(cmd, &format!("pass show {}", reference))?;
+        Ok(output.lines().next().unwrap_or_default().to_string())
+    }
+}
+
+/// `bw:<条目名或ID>`：通过 Bitwarden CLI 读取条目密码
+pub struct BitwardenProvider;
+
+impl AuthProvider for BitwardenProvider {
+    fn name(&self) -> &'static str {
+        "bw"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        ensure_installed("bw", "请先安装 Bitwarden CLI: npm install -g @bitwarden/cli")?;
+        if std::env::var("BW_SESSION").is_err() {
+            eprintln!("提示: 未检测到 BW_SESSION，如读取失败请先执行 `bw unlock` 并导出 BW_SESSION");
+        }
+        let mut cmd = Command::new("bw");
+        cmd.args(["get", "password", reference]);
+        capture_secret(cmd, &format!("bw get password {}", reference))
+    }
+}
+
+/// `op:<vault>/<item>/<field>` 或 `op:op://...`：通过 1Password CLI 读取
+pub struct OnePasswordProvider;
+
+impl AuthProvider for OnePasswordProvider {
+    fn name(&self) -> &'static str {
+        "op"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        ensure_installed("op", "请先安装 1Password CLI: https://developer.1password.com/docs/cli/")?;
+        let secret_ref = if reference.starts_with("op://") {
+            reference.to_string()
+        } else {
+            format!("op://{}", reference)
+        };
+        let mut cmd = Command::new("op");
+        cmd.args(["read", &secret_ref]);
+        capture_secret(cmd, &format!("op read {}", secret_ref))
+    }
+}
+
+/// `keychain:[服务/]账户`：从系统钥匙串读取，macOS 使用 `security`，其它系统使用
+/// libsecret 的 `secret-tool`，Windows 上读取凭据管理器。省略服务时为 `rssh`
+pub struct KeychainProvider;
+
+impl AuthProvider for KeychainProvider {
+    fn name(&self) -> &'static str {
+        "keychain"
+    }
+
+    #[cfg(windows)]
+    fn fetch(&self, reference: &str) -> Result<String> {
+        let (service, account) = reference.split_once('/').unwrap_or(("rssh", reference));
+        keyring::Entry::new(service, account)
+            .and_then(|entry| entry.get_password())
+            .with_context(|| format!("无法读取 Windows 凭据管理器 {}/{}", service, account))
+    }
+
+    #[cfg(not(windows))]
+    fn fetch(&self, reference: &str) -> Result<String> {
+        let (service, account) = reference.split_once('/').unwrap_or(("rssh", reference));
+        let cmd = if cfg!(target_os = "macos") {
+            let mut cmd = Command::new("security");
+            cmd.args(["find-generic-password", "-s", service, "-a", account, "-w"]);
+            cmd
+        } else {
+            ensure_installed("secret-tool", "请先安装 libsecret-tools（secret-tool）")?;
+            let mut cmd = Command::new("secret-tool");
+            cmd.args(["lookup", "service", service, "account", account]);
+            cmd
+        };
+        capture_secret(cmd, &format!("钥匙串 {}/{}", service, account))
+    }
+}
+
+/// `vault:<路径>[#字段]`：通过 HashiCorp Vault CLI 读取 KV 中的字段，省略字段时为 `password`。
+/// 地址和令牌使用 vault 自己的 `VAULT_ADDR`/`VAULT_TOKEN`
+pub struct VaultProvider;
+
+impl AuthProvider for VaultProvider {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        ensure_installed("vault", "请先安装 Vault CLI: https://developer.hashicorp.com/vault/install")?;
+        let (path, field) = vault_path_and_field(reference);
+        let mut cmd = Command::new("vault");
+        cmd.args(["kv", "get", &format!("-field={}", field), path]);
+        capture_secret(cmd, &format!("vault kv get -field={} {}", field, path))
+    }
+}
+
+fn vault_path_and_field(reference: &str) -> (&str, &str) {
+    match reference.rsplit_once('#') {
+        Some((path, field)) if !field.is_empty() => (path, field),
+        _ => (reference.trim_end_matches('#'), "password"),
+    }
+}
+
+/// `enc:v1:<密文>`：用凭据库主密码加密后保存在数据库中的密码，见 [`credential_vault`](crate::utils::credential_vault)
+pub struct EncryptedProvider;
+
+impl AuthProvider for EncryptedProvider {
+    fn name(&self) -> &'static str {
+        "enc"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        crate::utils::credential_vault::decrypt(reference)
+    }
+}
+
+/// `hook:<引用>`：交给 `config.toml` 中 `[hooks] resolve_secret` 配置的脚本解析，见 [`hooks`](crate::utils::hooks)
+pub struct HookProvider;
+
+impl AuthProvider for HookProvider {
+    fn name(&self) -> &'static str {
+        "hook"
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String> {
+        crate::utils::hooks::resolve_secret(reference)
+    }
+}
+
+/// 所有内置提供者
+pub fn builtin_providers() -> Vec<Box<dyn AuthProvider>> {
+    vec![
+        Box::new(StaticProvider),
+        Box::new(EnvProvider),
+        Box::new(CommandProvider),
+        Box::new(PassProvider),
+        Box::new(BitwardenProvider),
+        Box::new(OnePasswordProvider),
+        Box::new(KeychainProvider),
+        Box::new(VaultProvider),
+        Box::new(EncryptedProvider),
+        Box::new(HookProvider),
+    ]
+}
+
+/// 按名称查找提供者
+pub fn find_provider(name: &str) -> Option<Box<dyn AuthProvider>> {
+    builtin_providers().into_iter().find(|p| p.name() == name)
+}
+
+/// 可选的提供者名称，用于提示信息
+pub fn provider_names() -> String {
+    builtin_providers().iter().map(|p| p.name()).collect::<Vec<_>>().join("/")
+}
+
+/// 将 `<前缀>:<引用>` 拆分为对应的提供者和引用
+fn split_reference(value: &str) -> Option<(Box<dyn AuthProvider>, &str)> {
+    let (prefix, rest) = value.split_once(':')?;
+    let provider = find_provider(prefix).filter(|p| p.prefixed())?;
+    Some((provider, rest.trim()))
+}
+
+/// 判断值是否为密钥引用（而非明文）
+pub fn is_secret_reference(value: &str) -> bool {
+    split_reference(value).is_some()
+}
+
+/// 在连接时解析密钥引用。
+///
+/// 值以已知提供者前缀开头（`env:`、`cmd:`、`pass:`、`bw:`、`op:`、`keychain:`、`vault:`、`enc:`）时交给对应
+/// 提供者取回，其它值按明文原样返回，兼容已有配置。
+pub fn resolve_secret(value: &str) -> Result<String> {
+    match split_reference(value) {
+        Some((provider, reference)) => provider.fetch(reference),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// rssh 自己写入数据库的形式：凭据库加密的 `enc:` 和保存到钥匙串后的 `keychain:` 引用
+pub fn is_stored_form(value: &str) -> bool {
+    value.starts_with("enc:") || value.starts_with("keychain:")
+}
+
+/// 使用服务器上配置的提供者解析密钥。
+///
+/// 值本身仍带前缀时以前缀为准，否则整个值都作为该提供者的引用。
+/// 提供者为 `db` 时值是明文，以 `pass:`、`cmd:` 等开头也不解析，只有 rssh 自己写入的形式（见 [`is_stored_form`]）除外。
+pub fn resolve_secret_with(provider_name: &str, value: &str) -> Result<String> {
+    if provider_name == "db" && !is_stored_form(value) {
+        return Ok(value.to_string());
+    }
+    if is_secret_reference(value) {
+        return resolve_secret(value);
+    }
+
+    let provider = find_provider(provider_name)
+        .ok_or_else(|| anyhow::anyhow!("未知的密钥提供者: {}", provider_name))?;
+    provider.fetch(value.trim())
+}
+
+pub(crate) fn ensure_installed(program: &str, hint: &str) -> Result<()> {
+    if which::which(program).is_err() {
+        return Err(anyhow::anyhow!("未找到 {} 命令，{}", program, hint));
+    }
+    Ok(())
+}
+
+/// 执行命令并取其标准输出作为密钥。stdin/stderr 继承自当前终端，
+/// 以便 gpg pinentry、`bw unlock` 等交互式解锁提示可以正常显示。
+fn capture_secret(mut cmd: Command, desc: &str) -> Result<String> {
+    if desc.trim().is_empty() {
+        return Err(anyhow::anyhow!("密钥命令为空"));
+    }
+
+    let output = cmd
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .output()
+        .with_context(|| format!("无法执行密钥命令: {}", desc))?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "密钥命令执行失败 (退出码: {:?}): {}",
+            output.status.code(),
+            desc
+        ));
+    }
+
+    let secret = String::from_utf8(output.stdout)
+        .with_context(|| format!("密钥命令输出不是有效的UTF-8: {}", desc))?
+        .trim_end_matches(['\n', '\r'])
+        .to_string();
+
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("密钥命令没有输出任何内容: {}", desc));
+    }
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_value_is_returned_as_is() {
+        assert_eq!(resolve_secret("hunter2").unwrap(), "hunter2");
+        assert!(!is_secret_reference("hunter2"));
+        // 未知前缀不算引用，避免误伤含冒号的明文密码
+        assert!(!is_secret_reference("abc:def"));
+    }
+
+    #[test]
+    fn resolves_env_reference() {
+        std::env::set_var("RSSH_TEST_SECRET_ENV", "from-env");
+        assert!(is_secret_reference("env:RSSH_TEST_SECRET_ENV"));
+        assert_eq!(resolve_secret("env:RSSH_TEST_SECRET_ENV").unwrap(), "from-env");
+    }
+
+    #[test]
+    fn missing_env_reference_is_an_error() {
+        assert!(resolve_secret("env:RSSH_TEST_SECRET_DOES_NOT_EXIST").is_err());
+    }
+
+    #[test]
+    fn resolves_cmd_reference_and_strips_newline() {
+        assert_eq!(resolve_secret("cmd:echo from-cmd").unwrap(), "from-cmd");
+    }
+
+    #[test]
+    fn failing_cmd_reference_is_an_error() {
+        assert!(resolve_secret("cmd:exit 3").is_err());
+        assert!(resolve_secret("cmd:true").is_err());
+    }
+
+    #[test]
+    fn configured_provider_treats_bare_value_as_reference() {
+        std::env::set_var("RSSH_TEST_SECRET_PROVIDER", "via-provider");
+        assert_eq!(
+            resolve_secret_with("env", "RSSH_TEST_SECRET_PROVIDER").unwrap(),
+            "via-provider"
+        );
+        // 值自带前缀时以前缀为准
+        assert_eq!(resolve_secret_with("pass", "cmd:echo x").unwrap(), "x");
+        assert!(resolve_secret_with("nope", "x").is_err());
+    }
+
+    #[test]
+    fn static_provider_keeps_value_and_ignores_prefix() {
+        assert_eq!(resolve_secret_with("db", "hunter2").unwrap(), "hunter2");
+        // 明文密码恰好以提供者前缀开头时不执行
+        assert_eq!(resolve_secret_with("db", "cmd:echo x").unwrap(), "cmd:echo x");
+        assert_eq!(resolve_secret_with("db", "pass:work/web1").unwrap(), "pass:work/web1");
+        assert!(!is_secret_reference("db:hunter2"));
+        assert_eq!(resolve_secret("db:hunter2").unwrap(), "db:hunter2");
+        assert!(is_secret_reference("vault:secret/web1"));
+        assert!(provider_names().starts_with("db/env/cmd"));
+    }
+
+    #[test]
+    fn vault_reference_defaults_to_password_field() {
+        assert_eq!(vault_path_and_field("secret/web1"), ("secret/web1", "password"));
+        assert_eq!(vault_path_and_field("secret/web1#token"), ("secret/web1", "token"));
+        assert_eq!(vault_path_and_field("secret/web1#"), ("secret/web1", "password"));
+    }
+}
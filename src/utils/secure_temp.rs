@@ -0,0 +1,169 @@
+//! 本地临时文件。expect 脚本、kitty 会话文件等不再以固定文件名
+//! 写到共享的 /tmp，而是写到只有当前用户可以访问的目录：
+//!
+//! - `$TMPDIR/rssh-<uid>`：当前用户的目录，权限 0700，需要在 rssh 退出后继续使用的文件放在这里
+//! - `$XDG_RUNTIME_DIR/rssh`：保存密钥等敏感数据，通常在 tmpfs 上，注销或重启后清空；
+//!   没有设置 `XDG_RUNTIME_DIR` 时使用上面的用户目录
+//! - 其中的 `<pid>-<随机>`：本次运行的目录，正常退出或 panic 时删除，
+//!   被 kill 或 exec 替换而留下的目录在下次创建时清理
+
+use anyhow::{Context, Result};
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+use tempfile::TempDir;
+
+static RUN_DIR: Mutex<Option<TempDir>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+/// 当前用户的临时目录，权限 0700；已存在但不属于当前用户时拒绝使用
+pub fn user_dir() -> Result<PathBuf> {
+    let uid = unsafe { libc::getuid() };
+    private_dir(std::env::temp_dir().join(format!("rssh-{}", uid)))
+}
+
+/// 保存敏感数据的目录：`$XDG_RUNTIME_DIR/rssh`，没有设置时为 `user_dir()`
+pub fn runtime_dir() -> Result<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        Some(dir) => private_dir(dir.join("rssh")),
+        None => user_dir(),
+    }
+}
+
+/// 创建或检查只有当前用户可以访问的目录
+fn private_dir(dir: PathBuf) -> Result<PathBuf> {
+    let uid = unsafe { libc::getuid() };
+    if let Err(e) = DirBuilder::new().mode(0o700).create(&dir) {
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return Err(e).with_context(|| format!("无法创建临时目录 {}", dir.display()));
+        }
+    }
+
+    // 不跟随符号链接，防止其他用户预先放置的链接
+    let meta = std::fs::symlink_metadata(&dir).with_context(|| format!("无法读取 {}", dir.display()))?;
+    if !meta.is_dir() || meta.uid() != uid {
+        return Err(anyhow::anyhow!("{} 不是当前用户的目录，拒绝使用", dir.display()));
+    }
+    if meta.mode() & 0o077 != 0 {
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+            .with_context(|| format!("无法修改 {} 的权限", dir.display()))?;
+    }
+    Ok(dir)
+}
+
+/// 本次运行的临时目录，第一次调用时创建
+pub fn run_dir() -> Result<PathBuf> {
+    let mut run_dir = RUN_DIR.lock().unwrap();
+    if let Some(dir) = run_dir.as_ref() {
+        return Ok(dir.path().to_path_buf());
+    }
+
+    let base = user_dir()?;
+    remove_stale(&base);
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            cleanup();
+            previous(info);
+        }));
+    });
+
+    let dir = tempfile::Builder::new()
+        .prefix(&format!("{}-", std::process::id()))
+        .tempdir_in(&base)
+        .with_context(|| format!("无法在 {} 中创建临时目录", base.display()))?;
+    let path = dir.path().to_path_buf();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("无法修改 {} 的权限", path.display()))?;
+    *run_dir = Some(dir);
+    Ok(path)
+}
+
+/// 在本次运行的临时目录中写入文件，创建时即使用 `mode` 权限
+pub fn write(name: &str, contents: impl AsRef<[u8]>, mode: u32) -> Result<PathBuf> {
+    let path = run_dir()?.join(name);
+    write_file(&path, contents.as_ref(), mode)?;
+    Ok(path)
+}
+
+/// 在当前用户的临时目录中写入文件，rssh 退出后仍然保留（例如交给后台 kitty 读取的会话文件）
+pub fn write_persistent(name: &str, contents: impl AsRef<[u8]>, mode: u32) -> Result<PathBuf> {
+    let path = user_dir()?.join(name);
+    write_file(&path, contents.as_ref(), mode)?;
+    Ok(path)
+}
+
+/// 在 `runtime_dir()` 中写入敏感数据，rssh 退出后仍然保留
+pub fn write_runtime(name: &str, contents: impl AsRef<[u8]>, mode: u32) -> Result<PathBuf> {
+    let path = runtime_dir()?.join(name);
+    write_file(&path, contents.as_ref(), mode)?;
+    Ok(path)
+}
+
+fn write_file(path: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(path)
+        .with_context(|| format!("无法创建临时文件 {}", path.display()))?;
+    // 文件已存在时 mode 不生效
+    file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+    file.write_all(contents)
+        .with_context(|| format!("无法写入临时文件 {}", path.display()))
+}
+
+/// 删除本次运行的临时目录，退出前调用；panic 时由 panic hook 调用
+pub fn cleanup() {
+    // panic 可能发生在持有锁的时候
+    if let Ok(mut run_dir) = RUN_DIR.try_lock() {
+        run_dir.take();
+    }
+}
+
+/// 删除已经退出的 rssh 进程留下的运行目录
+fn remove_stale(base: &Path) {
+    let Ok(entries) = std::fs::read_dir(base) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str().and_then(|n| n.split_once('-')).and_then(|(pid, _)| pid.parse::<libc::pid_t>().ok())
+        else {
+            continue;
+        };
+        let exited = unsafe { libc::kill(pid, 0) } == -1
+            && std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH);
+        if exited {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_files_are_private_and_removed_on_cleanup() {
+        let base = user_dir().unwrap();
+        assert_eq!(std::fs::metadata(&base).unwrap().mode() & 0o777, 0o700);
+
+        // 已退出进程留下的目录会被清理
+        let stale = base.join(format!("{}-stale", i32::MAX));
+        std::fs::create_dir_all(&stale).unwrap();
+
+        let script = write("login.exp", "send \"secret\\r\"", 0o700).unwrap();
+        let batch = write("batch.txt", "put a b", 0o600).unwrap();
+        assert!(!stale.exists());
+        assert_eq!(script.parent(), batch.parent());
+        assert_eq!(std::fs::metadata(&script).unwrap().mode() & 0o777, 0o700);
+        assert_eq!(std::fs::metadata(&batch).unwrap().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::metadata(batch.parent().unwrap()).unwrap().mode() & 0o777, 0o700);
+
+        cleanup();
+        assert!(!script.exists());
+        assert!(!batch.parent().unwrap().exists());
+    }
+}
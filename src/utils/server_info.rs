@@ -1,5 +1,9 @@
-use anyhow::Result;
-use crate::models::ServerConfig;
+use anyhow::{Context, Result};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use crate::models::{ConnectionType, ForwardAgent, LoginBanner, ServerConfig};
+use crate::utils::ssh_command_builder::SshCommand;
+use crate::utils::ssh_uri::format_ssh_uri;
 use crate::utils::terminal_style::{Style, colors, Styled};
 
 pub fn display_server_info(server: &ServerConfig) -> Result<()> {
@@ -32,8 +36,62 @@ pub fn display_server_info(server: &ServerConfig) -> Result<()> {
     println!("{}: {}", "ID".style(label_style), server.id.clone().style(value_style));
     println!("{}: {}", "名称".style(label_style), server.name.clone().style(value_style));
     println!("{}: {}", "主机".style(label_style), server.host.clone().style(value_style));
+    if !server.alt_hosts.is_empty() {
+        println!("{}: {}", "备用地址".style(label_style), server.alt_hosts.join(", ").style(value_style));
+    }
+    if !server.aliases.is_empty() {
+        println!("{}: {}", "别名".style(label_style), server.aliases.join(", ").style(value_style));
+    }
+    if let Some(slot) = server.slot {
+        println!("{}: {}", "快捷编号".style(label_style), format!("rssh {}", slot).style(value_style));
+    }
+    if let Some(host) = server.preferred_host.as_ref().filter(|host| **host != server.host) {
+        println!("{}: {}", "上次连接地址".style(label_style), host.as_str().style(value_style));
+    }
+    if let Some(host) = &server.internal_host {
+        let network = server.network.as_deref().map(|n| format!(" (网络 {})", n)).unwrap_or_default();
+        println!("{}: {}", "内网地址".style(label_style), format!("{}{}", host, network).style(value_style));
+    }
+    if let Some(jump) = &server.jump_host {
+        println!("{}: {}", "跳板机".style(label_style), jump.as_str().style(value_style));
+    }
+    if let Some(jump) = &server.proxy_jump {
+        println!("{}: {}", "跳板服务器".style(label_style), jump.as_str().style(value_style));
+    }
+    if let Some(command) = &server.proxy_command {
+        println!("{}: {}", "代理命令".style(label_style), command.as_str().style(value_style));
+    }
+    if !server.extra_options.is_empty() {
+        println!("{}: {}", "其它 ssh 选项".style(label_style), server.extra_options.join(", ").style(value_style));
+    }
     println!("{}: {}", "端口".style(label_style), server.port.to_string().style(value_style));
     println!("{}: {}", "用户名".style(label_style), server.username.clone().style(value_style));
+    if server.connection_type != ConnectionType::Ssh {
+        println!("{}: {}", "连接类型".style(label_style), server.connection_type.to_string().style(value_style));
+    }
+    if server.compression {
+        println!("{}: {}", "压缩".style(label_style), "已启用".style(value_style));
+    }
+    if let Some(policy) = server.host_key_policy {
+        println!("{}: {}", "主机密钥策略".style(label_style), policy.name().style(value_style));
+    }
+    if server.login_banner != LoginBanner::Show {
+        println!("{}: {}", "登录横幅".style(label_style), server.login_banner.name().style(value_style));
+    }
+    if let Some(note) = &server.note {
+        println!("{}: {}", "连接提示".style(label_style), note.as_str().style(value_style));
+    }
+    let algorithms = &server.algorithms;
+    for (label, value) in [
+        ("加密算法", &algorithms.ciphers),
+        ("密钥交换算法", &algorithms.kex),
+        ("MAC 算法", &algorithms.macs),
+        ("主机密钥算法", &algorithms.host_key),
+    ] {
+        if let Some(value) = value {
+            println!("{}: {}", label.style(label_style), value.clone().style(value_style));
+        }
+    }
     println!();
 
     // 显示认证信息
@@ -42,6 +100,41 @@ pub fn display_server_info(server: &ServerConfig) -> Result<()> {
     if let Some(key_path) = server.auth_type.get_key_path() {
         println!("{}: {}", "密钥路径".style(label_style), key_path.style(value_style));
     }
+    if !server.identity_files.is_empty() {
+        println!("{}: {}", "额外私钥".style(label_style), server.identity_files.join(", ").style(value_style));
+    }
+    if server.identities_only {
+        println!("{}: {}", "IdentitiesOnly".style(label_style), "yes".style(value_style));
+    }
+    if let Some(anti_idle) = server.anti_idle {
+        let value = format!("空闲 {} 秒后发送 {}", anti_idle.interval, anti_idle.method.name());
+        println!("{}: {}", "防空闲".style(label_style), value.style(value_style));
+    }
+    if let Some(provider) = &server.secret_provider {
+        println!("{}: {}", "密钥提供者".style(label_style), provider.clone().style(value_style));
+    }
+    if let Some(changed_at) = server.password_changed_at.and_then(|t| chrono::DateTime::from_timestamp(t, 0)) {
+        let changed_at = changed_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string();
+        println!("{}: {}", "密码修改时间".style(label_style), changed_at.style(value_style));
+    }
+    for (label, patterns) in [("允许执行的命令", &server.command_policy.allow), ("禁止执行的命令", &server.command_policy.deny)] {
+        if !patterns.is_empty() {
+            println!("{}: {}", label.style(label_style), patterns.join("  ").style(value_style));
+        }
+    }
+    for (label, pattern) in [("密码提示", &server.prompts.password), ("就绪提示", &server.prompts.ready)] {
+        if let Some(pattern) = pattern {
+            println!("{}: {}", label.style(label_style), pattern.clone().style(value_style));
+        }
+    }
+    if !server.login_dialog.is_empty() {
+        // 发送内容可能是明文密码，只显示等待的提示
+        let prompts: Vec<&str> = server.login_dialog.iter().map(|step| step.expect.as_str()).collect();
+        println!("{}: {}", "登录对话".style(label_style), prompts.join(" → ").style(value_style));
+    }
+    if server.forward_agent != ForwardAgent::No {
+        println!("{}: {}", "Agent 转发".style(label_style), agent_forwarding_text(server).style(group_style));
+    }
     println!();
 
     // 显示其他信息
@@ -56,13 +149,70 @@ pub fn display_server_info(server: &ServerConfig) -> Result<()> {
 
     // 显示连接信息
     println!("{}", "连接信息".style(label_style));
-    let ssh_cmd = format!("ssh {}@{} -p {} {}", 
-        server.username,
-        server.host,
-        server.port,
-        server.auth_type.get_ssh_args()
-    );
+    let ssh_cmd = ssh_command_string(server);
     println!("{}: {}", "SSH命令".style(label_style), ssh_cmd.style(cmd_style));
+    println!("{}: {}", "SSH URI".style(label_style), format_ssh_uri(server).style(cmd_style));
+
+    Ok(())
+} 
+/// agent 转发的说明，分组不在 `agent_forwarding_groups` 中时注明不会生效
+fn agent_forwarding_text(server: &ServerConfig) -> String {
+    let mode = match server.forward_agent {
+        ForwardAgent::No => "关闭",
+        ForwardAgent::Yes => "开启",
+        ForwardAgent::Confirm => "每次连接前确认",
+    };
+    if server.agent_forwarding_allowed() {
+        mode.to_string()
+    } else {
+        format!("{}（分组不在 agent_forwarding_groups 中，不会生效）", mode)
+    }
+}
+
+/// 列出开启了 agent 转发的服务器，方便检查转发范围
+pub fn display_agent_forwarding(servers: &[ServerConfig]) {
+    let forwarding: Vec<&ServerConfig> = servers
+        .iter()
+        .filter(|server| server.forward_agent != ForwardAgent::No)
+        .collect();
+    if forwarding.is_empty() {
+        println!("没有服务器开启 SSH agent 转发");
+        return;
+    }
+
+    let label_style = Style::new().fg(colors::CYAN).bold();
+    println!("{}", "开启了 SSH agent 转发的服务器".style(label_style));
+    for server in forwarding {
+        println!(
+            "  {}  {}  {}",
+            server.name.as_str().style(Style::new().fg(colors::GREEN).bold()),
+            server.group.as_deref().unwrap_or("--").style(Style::new().fg(colors::BRIGHT_BLACK)),
+            agent_forwarding_text(server).style(Style::new().fg(colors::YELLOW).bold())
+        );
+    }
+}
+
+/// 生成连接该服务器的系统 ssh 命令（用于展示或交给其它工具执行）
+pub fn ssh_command_string(server: &ServerConfig) -> String {
+    SshCommand::new(server).to_string()
+}
+
+/// 以 ANSI 方块字符在终端打印服务器 ssh:// URI 的二维码
+pub fn display_server_qr(server: &ServerConfig) -> Result<()> {
+    let uri = format_ssh_uri(server);
+    let code = QrCode::new(uri.as_bytes())
+        .with_context(|| format!("无法生成二维码: {}", uri))?;
+
+    // 深色背景终端上需要反色，手机才能正确识别
+    let image = code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build();
+
+    println!("{}", image);
+    println!("{}", uri);
 
     Ok(())
-} 
\ No newline at end of file
+}
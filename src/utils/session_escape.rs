@@ -0,0 +1,278 @@
+use anyhow::Result;
+
+/// 交互式会话中的转义命令，写法与 OpenSSH 相同，只在行首输入 `~` 时生效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeAction {
+    /// `~.` 断开连接
+    Disconnect,
+    /// `~C` 打开命令行（添加 / 取消端口转发）
+    CommandLine,
+    /// `~#` 列出当前的端口转发
+    ListForwards,
+    /// `~!` 临时打开本地 shell，退出后回到会话
+    LocalShell,
+    /// `~M` 在会话录制中添加标记
+    Marker,
+    /// `~/` 在本地分页器中查看最近的会话输出
+    Scrollback,
+    /// `~?` 显示帮助
+    Help,
+}
+
+/// 键盘输入经过转义解析后的结果，按输入顺序排列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscapeEvent {
+    /// 需要发送到远程的数据
+    Data(Vec<u8>),
+    Action(EscapeAction),
+}
+
+pub const ESCAPE_HELP: &str = "\
+支持的转义序列（需在行首输入）:\r
+ ~.  断开连接\r
+ ~C  打开命令行: -L/-R [绑定地址:]端口:主机:主机端口 添加本地/远程转发, -KL/-KR [绑定地址:]端口 取消\r
+ ~#  列出端口转发\r
+ ~!  打开本地 shell，退出后回到会话\r
+ ~M  在会话录制中添加标记（rssh shell --record）\r
+ ~/  在本地分页器中查看和搜索最近的会话输出\r
+ ~?  显示本帮助\r
+ ~~  发送一个 ~\r
+";
+
+/// 从键盘输入中识别转义序列
+#[derive(Debug)]
+pub struct EscapeParser {
+    at_line_start: bool,
+    /// 行首已经输入了 `~`，等待下一个字符
+    pending: bool,
+}
+
+impl Default for EscapeParser {
+    fn default() -> Self {
+        EscapeParser { at_line_start: true, pending: false }
+    }
+}
+
+impl EscapeParser {
+    pub fn feed(&mut self, input: &[u8]) -> Vec<EscapeEvent> {
+        let mut events = Vec::new();
+        let mut data = Vec::new();
+
+        for &byte in input {
+            if self.pending {
+                self.pending = false;
+                let action = match byte {
+                    b'.' => Some(EscapeAction::Disconnect),
+                    b'C' => Some(EscapeAction::CommandLine),
+                    b'#' => Some(EscapeAction::ListForwards),
+                    b'!' => Some(EscapeAction::LocalShell),
+                    b'M' => Some(EscapeAction::Marker),
+                    b'/' => Some(EscapeAction::Scrollback),
+                    b'?' => Some(EscapeAction::Help),
+                    _ => None,
+                };
+                match action {
+                    Some(action) => {
+                        if !data.is_empty() {
+                            events.push(EscapeEvent::Data(std::mem::take(&mut data)));
+                        }
+                        events.push(EscapeEvent::Action(action));
+                        self.at_line_start = true;
+                    }
+                    // `~~` 发送一个 `~`，其它字符连同 `~` 原样发送
+                    None => {
+                        if byte != b'~' {
+                            data.push(b'~');
+                        }
+                        data.push(byte);
+                        self.at_line_start = matches!(byte, b'\r' | b'\n');
+                    }
+                }
+                continue;
+            }
+
+            if byte == b'~' && self.at_line_start {
+                self.pending = true;
+                continue;
+            }
+
+            data.push(byte);
+            self.at_line_start = matches!(byte, b'\r' | b'\n');
+        }
+
+        if !data.is_empty() {
+            events.push(EscapeEvent::Data(data));
+        }
+        events
+    }
+}
+
+/// 端口转发方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardKind {
+    /// `-L`：本地监听，经服务器连接目标
+    Local,
+    /// `-R`：服务器监听，经本机连接目标
+    Remote,
+}
+
+/// 端口转发 `[绑定地址:]端口:主机:主机端口`，绑定地址默认为 127.0.0.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forward {
+    pub kind: ForwardKind,
+    pub bind_address: String,
+    pub port: u16,
+    pub host: String,
+    pub host_port: u16,
+}
+
+impl Forward {
+    pub fn parse(kind: ForwardKind, spec: &str) -> Result<Self> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let (bind_address, rest) = match parts.len() {
+            3 => ("127.0.0.1", &parts[..]),
+            4 => (parts[0], &parts[1..]),
+            _ => return Err(anyhow::anyhow!("转发格式应为 [绑定地址:]端口:主机:主机端口: {}", spec)),
+        };
+        Ok(Forward {
+            kind,
+            bind_address: bind_address.to_string(),
+            port: parse_port(rest[0])?,
+            host: rest[1].to_string(),
+            host_port: parse_port(rest[2])?,
+        })
+    }
+
+    /// 监听地址（`-L` 在本机，`-R` 在服务器上）
+    pub fn listen_address(&self) -> String {
+        format!("{}:{}", self.bind_address, self.port)
+    }
+}
+
+impl std::fmt::Display for Forward {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = match self.kind {
+            ForwardKind::Local => "-L",
+            ForwardKind::Remote => "-R",
+        };
+        write!(f, "{} {} -> {}:{}", flag, self.listen_address(), self.host, self.host_port)
+    }
+}
+
+fn parse_port(value: &str) -> Result<u16> {
+    value.parse::<u16>().map_err(|_| anyhow::anyhow!("无效的端口: {}", value))
+}
+
+/// `~C` 命令行中的命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscapeCommand {
+    AddForward(Forward),
+    /// 取消监听在该地址上的转发
+    CancelForward(ForwardKind, String),
+}
+
+pub fn parse_command_line(line: &str) -> Result<EscapeCommand> {
+    let line = line.trim();
+    for (flag, kind) in [("-KL", ForwardKind::Local), ("-KR", ForwardKind::Remote)] {
+        if let Some(spec) = line.strip_prefix(flag) {
+            let spec = spec.trim();
+            let address = match spec.rsplit_once(':') {
+                Some((bind, port)) => format!("{}:{}", bind, parse_port(port)?),
+                None => format!("127.0.0.1:{}", parse_port(spec)?),
+            };
+            return Ok(EscapeCommand::CancelForward(kind, address));
+        }
+    }
+    for (flag, kind) in [("-L", ForwardKind::Local), ("-R", ForwardKind::Remote)] {
+        if let Some(spec) = line.strip_prefix(flag) {
+            return Ok(EscapeCommand::AddForward(Forward::parse(kind, spec.trim())?));
+        }
+    }
+    Err(anyhow::anyhow!("不支持的命令: {}（可用: -L/-R 添加转发, -KL/-KR 取消转发）", line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_escapes_only_at_line_start() {
+        let mut parser = EscapeParser::default();
+        assert_eq!(parser.feed(b"~."), vec![EscapeEvent::Action(EscapeAction::Disconnect)]);
+
+        let mut parser = EscapeParser::default();
+        assert_eq!(
+            parser.feed(b"cd ~.\r~C"),
+            vec![EscapeEvent::Data(b"cd ~.\r".to_vec()), EscapeEvent::Action(EscapeAction::CommandLine)]
+        );
+
+        // 跨两次读取的转义序列，以及 ~~ 和普通字符
+        let mut parser = EscapeParser::default();
+        assert!(parser.feed(b"~").is_empty());
+        assert_eq!(parser.feed(b"~x"), vec![EscapeEvent::Data(b"~x".to_vec())]);
+        let mut parser = EscapeParser::default();
+        assert_eq!(parser.feed(b"~a"), vec![EscapeEvent::Data(b"~a".to_vec())]);
+        assert_eq!(parser.feed(b"\r~M"), vec![EscapeEvent::Data(b"\r".to_vec()), EscapeEvent::Action(EscapeAction::Marker)]);
+        assert_eq!(parser.feed(b"\n~/"), vec![EscapeEvent::Data(b"\n".to_vec()), EscapeEvent::Action(EscapeAction::Scrollback)]);
+    }
+
+    #[test]
+    fn parses_forward_commands() {
+        assert_eq!(
+            parse_command_line("-L 8080:localhost:80").unwrap(),
+            EscapeCommand::AddForward(Forward {
+                kind: ForwardKind::Local,
+                bind_address: "127.0.0.1".into(),
+                port: 8080,
+                host: "localhost".into(),
+                host_port: 80,
+            })
+        );
+        assert!(matches!(
+            parse_command_line("-L0.0.0.0:5432:db:5432").unwrap(),
+            EscapeCommand::AddForward(f) if f.bind_address == "0.0.0.0"
+        ));
+        assert!(matches!(
+            parse_command_line("-R 9000:localhost:3000").unwrap(),
+            EscapeCommand::AddForward(f) if f.kind == ForwardKind::Remote && f.port == 9000
+        ));
+        assert_eq!(
+            parse_command_line("-KL 8080").unwrap(),
+            EscapeCommand::CancelForward(ForwardKind::Local, "127.0.0.1:8080".into())
+        );
+        assert!(parse_command_line("-D 1080").is_err());
+    }
+
+    #[test]
+    fn parses_forward_cancellation_and_rejects_bad_ports() {
+        assert_eq!(
+            parse_command_line("-KR 0.0.0.0:9000").unwrap(),
+            EscapeCommand::CancelForward(ForwardKind::Remote, "0.0.0.0:9000".into())
+        );
+        assert!(parse_command_line("-KL http").is_err());
+        assert!(parse_command_line("-L 8080:localhost").is_err());
+        assert!(parse_command_line("-L 70000:localhost:80").is_err());
+        assert!(parse_command_line("-R 9000:localhost:x").is_err());
+    }
+
+    #[test]
+    fn parses_forward_specs_with_optional_bind_address() {
+        let forward = Forward::parse(ForwardKind::Local, "8080:localhost:80").unwrap();
+        assert_eq!((forward.bind_address.as_str(), forward.port, forward.host.as_str(), forward.host_port), ("127.0.0.1", 8080, "localhost", 80));
+        let forward = Forward::parse(ForwardKind::Local, "0.0.0.0:5432:db.internal:5432").unwrap();
+        assert_eq!((forward.listen_address(), forward.host.as_str()), ("0.0.0.0:5432".to_string(), "db.internal"));
+        assert!(Forward::parse(ForwardKind::Local, "8080").is_err());
+        assert!(Forward::parse(ForwardKind::Local, "a:b:8080:localhost:80").is_err());
+        assert!(Forward::parse(ForwardKind::Local, "http:localhost:80").is_err());
+    }
+
+    #[test]
+    fn formats_forwards_for_listing() {
+        let local = Forward::parse(ForwardKind::Local, "8080:localhost:80").unwrap();
+        assert_eq!(local.to_string(), "-L 127.0.0.1:8080 -> localhost:80");
+        let remote = Forward::parse(ForwardKind::Remote, "0.0.0.0:9000:127.0.0.1:3000").unwrap();
+        assert_eq!(remote.to_string(), "-R 0.0.0.0:9000 -> 127.0.0.1:3000");
+        // 取消时按监听地址匹配
+        assert_eq!(parse_command_line("-KR 0.0.0.0:9000").unwrap(), EscapeCommand::CancelForward(ForwardKind::Remote, remote.listen_address()));
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::models::ServerConfig;
+
+/// 正在运行的交互式会话，供 `rssh ps` 查看
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveSession {
+    pub pid: u32,
+    pub server: String,
+    /// 连接方式：library / russh
+    pub mode: String,
+    /// 开始时间（Unix 秒）
+    pub started_at: i64,
+    /// 会话中添加的端口转发
+    #[serde(default)]
+    pub forwards: Vec<String>,
+}
+
+/// 会话运行期间在状态目录中维护一个 `<pid>.json`，会话结束时删除
+pub struct SessionRegistry {
+    path: PathBuf,
+    session: ActiveSession,
+}
+
+impl SessionRegistry {
+    pub fn register(dir: &Path, server: &ServerConfig, mode: &str) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("无法创建目录: {}", dir.display()))?;
+
+        let pid = std::process::id();
+        let registry = SessionRegistry {
+            path: dir.join(format!("{}.json", pid)),
+            session: ActiveSession {
+                pid,
+                server: server.name.clone(),
+                mode: mode.to_string(),
+                started_at: chrono::Utc::now().timestamp(),
+                forwards: Vec::new(),
+            },
+        };
+        registry.save()?;
+        Ok(registry)
+    }
+
+    /// 更新会话中的端口转发列表
+    pub fn set_forwards(&mut self, forwards: Vec<String>) {
+        self.session.forwards = forwards;
+        // 状态文件只用于展示，写入失败不影响会话
+        let _ = self.save();
+    }
+
+    fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, serde_json::to_string(&self.session)?)
+            .with_context(|| format!("无法写入会话状态: {}", self.path.display()))
+    }
+}
+
+impl Drop for SessionRegistry {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 列出仍在运行的会话；进程已退出（例如被 kill）留下的状态文件会被清理
+pub fn list_active_sessions(dir: &Path) -> Result<Vec<ActiveSession>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut sessions = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("无法读取目录: {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let session = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<ActiveSession>(&content).ok());
+        match session {
+            Some(session) if process_alive(session.pid) => sessions.push(session),
+            _ => {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+
+    sessions.sort_by_key(|s| s.started_at);
+    Ok(sessions)
+}
+
+fn process_alive(pid: u32) -> bool {
+    // 信号 0 只检查进程是否存在；EPERM 说明进程存在但属于其他用户
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    #[test]
+    fn lists_running_sessions_with_their_forwards() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = ServerConfig::new("1".into(), "web".into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, None, None, None);
+        let mut registry = SessionRegistry::register(dir.path(), &server, "russh").unwrap();
+        registry.set_forwards(vec!["-L 127.0.0.1:8080 -> localhost:80".into()]);
+        // 已退出进程留下的状态文件
+        let stale = ActiveSession { pid: u32::MAX / 2, server: "db".into(), mode: "library".into(), started_at: 0, forwards: Vec::new() };
+        std::fs::write(dir.path().join("stale.json"), serde_json::to_string(&stale).unwrap()).unwrap();
+
+        let sessions = list_active_sessions(dir.path()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].server.as_str(), sessions[0].mode.as_str()), ("web", "russh"));
+        assert_eq!(sessions[0].forwards, ["-L 127.0.0.1:8080 -> localhost:80"]);
+        assert!(!dir.path().join("stale.json").exists());
+
+        drop(registry);
+        assert!(list_active_sessions(dir.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+//! `rssh share`：通过 tmux 3.3 的 `server-access` 把正在运行的 rssh tmux 会话共享给本机的其他用户。
+//!
+//! tmux 会话属于创建它的 tmux 服务器，同事需要连接到同一个 socket；
+//! socket 默认只有所有者能访问，共享时放开文件权限，再由 server-access 按用户授权（只读或可写）。
+
+use anyhow::{Context, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::models::SessionConfig;
+
+/// `server-access` 从 tmux 3.3 开始提供
+const MIN_VERSION: (u32, u32) = (3, 3);
+
+/// rssh 为会话创建的 tmux 会话名
+pub fn tmux_session_name(session: &SessionConfig) -> String {
+    format!("rssh_{}", session.id.split('-').next().unwrap_or("session"))
+}
+
+/// 解析 `tmux -V` 的输出，例如 `tmux 3.3a`、`tmux next-3.4`
+pub fn parse_tmux_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.split_whitespace().nth(1)?;
+    let version = version.rsplit('-').next()?;
+    let (major, minor) = version.split_once('.')?;
+    let minor: String = minor.chars().take_while(|c| c.is_ascii_digit()).collect();
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// `server-access -l` 的输出（每行 `用户名 (R)` 或 `用户名 (W)`）中除所有者外是否还有其他用户
+pub fn has_guests(acl: &str, owner: &str) -> bool {
+    acl.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .any(|user| user != owner)
+}
+
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .output()
+        .with_context(|| "无法执行 tmux")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "tmux {} 失败: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// 检查 tmux 版本是否支持 server-access
+pub fn check_tmux() -> Result<()> {
+    which::which("tmux").map_err(|_| anyhow::anyhow!("未找到 tmux 命令"))?;
+    let version = tmux(&["-V"])?;
+    match parse_tmux_version(&version) {
+        Some(found) if found >= MIN_VERSION => Ok(()),
+        _ => Err(anyhow::anyhow!("共享会话需要 tmux 3.3 及以上（server-access），当前为: {}", version)),
+    }
+}
+
+/// 正在运行的 rssh tmux 会话
+pub fn list_rssh_sessions() -> Vec<String> {
+    tmux(&["list-sessions", "-F", "#{session_name}"])
+        .map(|output| output.lines().filter(|name| name.starts_with("rssh_")).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// 会话所在 tmux 服务器的 socket
+pub fn socket_path(session: &str) -> Result<PathBuf> {
+    tmux(&["has-session", "-t", session]).map_err(|_| anyhow::anyhow!("tmux 会话不存在: {}", session))?;
+    Ok(PathBuf::from(tmux(&["display-message", "-p", "-t", session, "#{socket_path}"])?))
+}
+
+/// 给其他用户加上（或去掉）进入 socket 所在目录和读写 socket 的权限，保留其余权限位
+fn set_other_access(socket: &Path, allow: bool) -> Result<()> {
+    let dir = socket.parent().unwrap_or(Path::new("/"));
+    for (path, bits) in [(dir, 0o001), (socket, 0o006)] {
+        let mode = std::fs::metadata(path)
+            .with_context(|| format!("无法读取权限: {}", path.display()))?
+            .permissions()
+            .mode();
+        let mode = if allow { mode | bits } else { mode & !bits };
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("无法修改权限: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// 允许 `user` 连接到会话，`write` 为 false 时只能观看。返回同事需要执行的命令
+pub fn grant(session: &str, user: &str, write: bool) -> Result<String> {
+    let socket = socket_path(session)?;
+    set_other_access(&socket, true)?;
+    tmux(&["server-access", "-a", user])?;
+    tmux(&["server-access", if write { "-w" } else { "-r" }, user])?;
+    Ok(format!(
+        "tmux -S {} attach -t {}",
+        shell_escape::escape(socket.to_string_lossy()),
+        shell_escape::escape(session.into())
+    ))
+}
+
+/// 取消 `user` 的共享并断开其客户端；没有其他共享用户时恢复 socket 的权限
+pub fn revoke(session: &str, user: &str) -> Result<()> {
+    let socket = socket_path(session)?;
+    tmux(&["server-access", "-d", user])?;
+    let owner = std::env::var("USER").unwrap_or_default();
+    if !has_guests(&tmux(&["server-access", "-l"])?, &owner) {
+        set_other_access(&socket, false)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tmux_version_and_acl() {
+        assert_eq!(parse_tmux_version("tmux 3.3a"), Some((3, 3)));
+        assert_eq!(parse_tmux_version("tmux next-3.5"), Some((3, 5)));
+        assert_eq!(parse_tmux_version("tmux 2.9"), Some((2, 9)));
+        assert!(parse_tmux_version("tmux 3.2a").unwrap() < MIN_VERSION);
+        assert_eq!(parse_tmux_version("tmux master"), None);
+
+        assert!(!has_guests("alice (W)\n", "alice"));
+        assert!(has_guests("alice (W)\nbob (R)\n", "alice"));
+    }
+}
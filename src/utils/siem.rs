@@ -0,0 +1,139 @@
+//! 把连接、断开、远程执行和文件传输事件以 JSON POST 到安全团队的日志平台（`config.toml` 的 `[siem]`）。
+//!
+//! 配置了 `secret` 时用 HMAC-SHA256 签名：`X-Rssh-Signature: sha256=<hex>`，
+//! 签名内容为 `<X-Rssh-Timestamp>.<请求体>`，接收端据此校验来源并拒绝重放。
+//! 事件通过后台的 curl 发送，不等待结果，不影响命令本身。
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::models::ServerConfig;
+use crate::utils::secret::resolve_secret;
+
+/// `config.toml` 中的 `[siem]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiemSettings {
+    /// 接收事件的地址，支持 `env:` / `pass:` 等密钥引用
+    pub url: String,
+    /// HMAC 签名密钥，支持密钥引用；未设置时不签名
+    pub secret: Option<String>,
+    /// 只发送这些事件（connect、disconnect、exec、upload、download 等），未设置时全部发送
+    pub events: Option<Vec<String>>,
+}
+
+struct SiemState {
+    settings: SiemSettings,
+    /// 解析后的地址和签名密钥，第一次发送时才解析，避免每次运行 rssh 都调用密码管理器
+    resolved: OnceLock<Option<(String, Option<String>)>>,
+}
+
+static SIEM: OnceLock<SiemState> = OnceLock::new();
+
+/// 启用事件上报，只在启动时调用一次
+pub fn configure(settings: SiemSettings) {
+    let _ = SIEM.set(SiemState { settings, resolved: OnceLock::new() });
+}
+
+#[derive(Debug, Serialize)]
+struct SiemEvent<'a> {
+    source: &'static str,
+    time: String,
+    event: &'a str,
+    local_user: String,
+    local_host: String,
+    server: &'a str,
+    group: Option<&'a str>,
+    target: String,
+    /// 远程执行的命令或传输的路径
+    detail: Option<&'a str>,
+}
+
+/// 上报一个事件；未配置 `[siem]` 或事件被过滤时什么也不做，发送失败只打印警告
+pub fn emit(server: &ServerConfig, event: &str, detail: Option<&str>) {
+    let Some(state) = SIEM.get() else { return };
+    if state.settings.events.as_ref().is_some_and(|events| !events.iter().any(|e| e == event)) {
+        return;
+    }
+    let resolved = state.resolved.get_or_init(|| match resolve(&state.settings) {
+        Ok(resolved) => Some(resolved),
+        Err(e) => {
+            eprintln!("rssh: 无法解析 [siem] 配置，不上报事件: {:#}", e);
+            None
+        }
+    });
+    let Some((url, secret)) = resolved else { return };
+
+    let body = serde_json::to_string(&SiemEvent {
+        source: "rssh",
+        time: chrono::Local::now().to_rfc3339(),
+        event,
+        local_user: std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default(),
+        local_host: nix::unistd::gethostname().map(|h| h.to_string_lossy().into_owned()).unwrap_or_default(),
+        server: &server.name,
+        group: server.group.as_deref(),
+        target: format!("{}@{}:{}", server.username, server.host, server.port),
+        detail,
+    })
+    .expect("事件可以序列化为 JSON");
+    if let Err(e) = post(url, secret.as_deref(), &body) {
+        eprintln!("rssh: 上报 {} 事件失败: {:#}", event, e);
+    }
+}
+
+fn resolve(settings: &SiemSettings) -> Result<(String, Option<String>)> {
+    let url = resolve_secret(&settings.url).context("无法解析 [siem] url")?;
+    let secret = settings.secret.as_deref().map(resolve_secret).transpose().context("无法解析 [siem] secret")?;
+    Ok((url, secret))
+}
+
+/// `sha256=<hex>`，签名内容为 `<timestamp>.<body>`
+fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC 接受任意长度的密钥");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+fn post(url: &str, secret: Option<&str>, body: &str) -> Result<()> {
+    let mut command = Command::new("curl");
+    command.args(["-fsS", "-m", "10", "-X", "POST", "-H", "Content-Type: application/json"]);
+    if let Some(secret) = secret {
+        let timestamp = chrono::Utc::now().timestamp();
+        command
+            .arg("-H")
+            .arg(format!("X-Rssh-Timestamp: {}", timestamp))
+            .arg("-H")
+            .arg(format!("X-Rssh-Signature: {}", signature(secret, timestamp, body)));
+    }
+    let mut child = command
+        .args(["--data-binary", "@-"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("无法执行 curl")?;
+    let written = child.stdin.take().expect("stdin 已设置为 piped").write_all(body.as_bytes());
+    // 不等待上报完成，由后台线程回收 curl 进程，避免留下僵尸进程
+    std::thread::spawn(move || child.wait());
+    written?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        // 与 `printf '1700000000.{}' | openssl dgst -sha256 -hmac key` 的结果一致
+        assert_eq!(
+            signature("key", 1700000000, "{}"),
+            "sha256=9d713ed406bb7076d4123f0dc2c39d2df5c654ed4b0cd56b52c8b4c940bd63ae"
+        );
+    }
+}
@@ -4,6 +4,9 @@ use std::process::{Command, Stdio};
 use crate::models::{AuthType, ServerConfig};
 use crate::utils::ssh_config::{expand_tilde, sanitize_host_alias};
 use crate::utils::kitty_transfer::is_kitty_available;
+use crate::config::get_ssh_config_dir;
+use crate::utils::secure_temp;
+use crate::utils::ssh_command_builder::SshCommand;
 
 // 使用基于子进程的方法
 // 这个实现直接使用系统的ssh命令，绕过Rust的SSH库
@@ -41,7 +44,9 @@ pub fn connect_via_system_ssh_with_command(
             AuthType::Key(_) => server.password.is_none(),
             AuthType::Password(_) => false,
         };
+        // wezterm 的 SSH 域不支持本次连接临时指定的端口转发
         if wezterm_compatible_auth
+            && server.local_forwards.is_empty()
             && crate::utils::terminal::is_wezterm()
             && which::which("wezterm").is_ok()
         {
@@ -57,32 +62,26 @@ pub fn connect_via_system_ssh_with_command(
             .unwrap_or_else(|_| std::path::PathBuf::from("/usr/bin/ssh"))
     };
 
-    // 构建命令参数
-    let mut args = Vec::new();
-
-    // 如果是kitty kitten，添加kitten ssh命令
-    if use_kitty_kitten {
-        // args.push("+kitten".to_string());
-        args.push("ssh".to_string());
-    }
-
-    // 添加用户名和主机
-    args.push(format!("{}@{}", server.username, server.host));
-
-    // 添加端口
-    if server.port != 22 {
-        args.push("-p".to_string());
-        args.push(server.port.to_string());
+    // 端口、密钥和服务器级别的附加选项（主机密钥策略、压缩、算法偏好等）由 SshCommand 统一处理
+    let program = if use_kitty_kitten {
+        vec!["kitten".to_string(), "ssh".to_string()]
+    } else {
+        vec![ssh_path.display().to_string()]
+    };
+    let mut ssh_command = SshCommand::new(server)
+        .program(&program)
+        .option("HashKnownHosts", "no")
+        .option("ServerAliveInterval", "60")
+        .with_config_file(&get_ssh_config_dir()?)?;
+    if let Some(cmd) = command {
+        ssh_command = ssh_command.command(cmd);
     }
 
     // 添加认证相关参数
     match &server.auth_type {
         AuthType::Key(key_path) => {
             println!("使用密钥认证，密钥路径: {}", key_path);
-            let expanded_path = expand_tilde(key_path);
-            println!("展开后的密钥路径: {}", expanded_path);
-            args.push("-i".to_string());
-            args.push(expanded_path.clone());
+            println!("展开后的密钥路径: {}", expand_tilde(key_path));
 
             // 如果同时提供了密码，在密钥认证后尝试密码认证
             if let Some(password) = &server.password {
@@ -91,73 +90,62 @@ pub fn connect_via_system_ssh_with_command(
                 if let Ok(expect_path) = which::which("expect") {
                     println!("找到expect程序: {}", expect_path.display());
 
-                    let mut args_str = String::new();
-                    for arg in args {
-                        args_str.push_str(&format!("{} ", arg));
-                    }
-
                     // 创建expect脚本
                     let expect_script = format!(
                         r#"#!/usr/bin/expect -f
 set timeout 30
+# rssh 被 exec 替换后无法清理，读入后立即删除
+file delete -- [info script]
+{}
+set password_prompt {}
+set ready_prompt {}
 puts "开始SSH连接..."
-spawn {} {} -o StrictHostKeyChecking=no -o HashKnownHosts=no -o ServerAliveInterval=60 -o HostKeyAlgorithms=+ssh-rsa -o PubkeyAcceptedAlgorithms=+ssh-rsa
+spawn {}
 puts "等待密码提示..."
 expect {{
-    -re "password:" {{
+    -nocase -re $password_prompt {{
         puts "检测到密码提示"
         puts "准备发送密码"
-        send "{}\r"
-        puts "密码已发送，等待Opt>提示"
+        send -- "$password\r"
+        puts "密码已发送，等待就绪提示 $ready_prompt"
         exp_continue
     }}
-    -re "Opt>" {{
-        puts "检测到Opt>提示，进入交互模式"
+    -nocase -re $ready_prompt {{
+        puts "检测到就绪提示，进入交互模式"
         interact
     }}
     timeout {{
-        puts "超时，未检测到Opt>提示"
-        exit 1
+{}
+    }}
+    eof {{
+{}
     }}
 }}"#,
-                        ssh_path.display(),
-                        args_str,
-                        password.replace("\"", "\\\"").replace("\\", "\\\\")
+                        READ_PASSWORD,
+                        tcl_braced(server.prompts.password()),
+                        tcl_braced(server.prompts.ready()),
+                        ssh_command.to_tcl(),
+                        report_unmatched("30 秒内没有匹配到密码提示 $password_prompt 或就绪提示 $ready_prompt", true),
+                        report_unmatched("ssh 在出现就绪提示 $ready_prompt 前退出", false),
                     );
 
-                    println!("生成的expect脚本:\n{}", expect_script);
-
-                    // 创建临时脚本文件
-                    let temp_dir = std::env::temp_dir();
-                    let script_path = temp_dir.join(format!("rssh_expect_{}.sh", std::process::id()));
-                    println!("创建临时脚本文件: {}", script_path.display());
-                    std::fs::write(&script_path, expect_script)
+                    // 创建临时脚本文件（只有当前用户可读）
+                    let script_path = secure_temp::write("expect.exp", expect_script, 0o700)
                         .with_context(|| "无法创建expect脚本")?;
-
-                    // 设置脚本权限
-                    #[cfg(unix)]
-                    {
-                        use std::os::unix::fs::PermissionsExt;
-                        std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o700))
-                            .with_context(|| "无法设置脚本权限")?;
-                        println!("设置脚本权限为700");
-                    }
+                    println!("创建临时脚本文件: {}", script_path.display());
 
                     println!("开始执行expect脚本...");
                     // 执行expect脚本
                     #[cfg(unix)]
                     {
                         use std::os::unix::process::CommandExt;
-                        let error = Command::new(expect_path)
-                            .arg(&script_path)
-                            .exec();
+                        let error = expect_command(&expect_path, &script_path, password).exec();
                         return Err(anyhow::anyhow!("执行expect脚本失败: {}", error));
                     }
 
                     #[cfg(not(unix))]
                     {
-                        let child = Command::new(expect_path)
-                            .arg(&script_path)
+                        let child = expect_command(&expect_path, &script_path, password)
                             .stdin(Stdio::inherit())
                             .stdout(Stdio::inherit())
                             .stderr(Stdio::inherit())
@@ -186,59 +174,45 @@ expect {{
         AuthType::Agent => {
             // 默认使用SSH代理，不需要额外参数
         },
-        AuthType::Password(_password) => {
+        AuthType::Password(password) => {
             // 检查是否安装了expect
             if let Ok(expect_path) = which::which("expect") {
                 println!("使用expect自动处理密码输入...");
 
-                let mut args_str = String::new();
-                for arg in args {
-                    args_str.push_str(&format!("{} ", arg));
-                }
-
                 // 创建 expect 脚本。
                 // 关键：`send "...\r"` 里的 `\r` 必须是 expect 层面的转义（字面两
                 // 字符 `\` + `r`），expect 才会真的发出回车。早期版本写成 `\\\r`
                 // 在 Rust 字符串里展开成 `\` + 真 CR，被 expect 当成行连接吃掉，
                 // 导致密码只发了字符、没有回车，服务器一直停在 password 提示。
-                let escaped_password = match &server.auth_type {
-                    AuthType::Password(pwd) => pwd.as_str(),
-                    _ => "",
-                }
-                .replace('\\', "\\\\")
-                .replace('"', "\\\"");
                 let expect_script = format!(
-                    "#!/usr/bin/expect -f\n\
-                     set timeout 30\n\
-                     spawn {} {} -o StrictHostKeyChecking=no -o HashKnownHosts=no -o ServerAliveInterval=60 -o HostKeyAlgorithms=+ssh-rsa -o PubkeyAcceptedAlgorithms=+ssh-rsa\n\
-                     expect {{\n\
-                         -re {{[Pp]assword:}} {{ send \"{password}\\r\" }}\n\
-                         timeout {{ puts stderr \"rssh: timed out waiting for password prompt\"; exit 1 }}\n\
-                         eof {{ puts stderr \"rssh: ssh exited before password prompt\"; exit 1 }}\n\
-                     }}\n\
-                     interact",
-                    ssh_path.display(),
-                    args_str,
-                    password = escaped_password,
+                    r#"#!/usr/bin/expect -f
+set timeout 30
+{}
+set password_prompt {}
+spawn {}
+expect {{
+    -nocase -re $password_prompt {{ send -- "$password\r" }}
+    timeout {{
+{}
+    }}
+    eof {{
+{}
+    }}
+}}
+interact"#,
+                    READ_PASSWORD,
+                    tcl_braced(server.prompts.password()),
+                    ssh_command.to_tcl(),
+                    report_unmatched("30 秒内没有匹配到密码提示 $password_prompt", true),
+                    report_unmatched("ssh 在出现密码提示前退出", false),
                 );
 
-                // 创建临时脚本文件
-                let temp_dir = std::env::temp_dir();
-                let script_path = temp_dir.join(format!("rssh_expect_{}.sh", std::process::id()));
-                std::fs::write(&script_path, expect_script)
+                // 创建临时脚本文件（只有当前用户可读）
+                let script_path = secure_temp::write("expect.exp", expect_script, 0o700)
                     .with_context(|| "无法创建expect脚本")?;
 
-                // 设置脚本权限
-                #[cfg(unix)]
-                {
-                    use std::os::unix::fs::PermissionsExt;
-                    std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o700))
-                        .with_context(|| "无法设置脚本权限")?;
-                }
-
                 // 执行expect脚本
-                let status = Command::new(expect_path)
-                    .arg(&script_path)
+                let status = expect_command(&expect_path, &script_path, password)
                     .stdin(Stdio::inherit())
                     .stdout(Stdio::inherit())
                     .stderr(Stdio::inherit())
@@ -270,33 +244,13 @@ expect {{
         }
     }
 
-    // 禁用严格主机密钥检查
-    args.push("-o".to_string());
-    args.push("StrictHostKeyChecking=no".to_string());
-
-    // 禁用HashKnownHosts
-    args.push("-o".to_string());
-    args.push("HashKnownHosts=no".to_string());
-
-    // 保持会话活跃
-    args.push("-o".to_string());
-    args.push("ServerAliveInterval=60".to_string());
-
-    // 启用ssh-rsa算法支持
-    args.push("-o".to_string());
-    args.push("HostKeyAlgorithms=+ssh-rsa".to_string());
-    args.push("-o".to_string());
-    args.push("PubkeyAcceptedAlgorithms=+ssh-rsa".to_string());
-
-    // 添加命令（如果有）
-    if let Some(cmd) = command {
-        args.push(cmd);
-    }
-
     // 检查是否安装了lrzsz，如果是，则使用我们的rzsz代理
     let rzsz_enabled = is_lrzsz_installed();
 
-    // 只有在用户通过命令行参数启用并且本地有lrzsz才使用代理
+    // 用户明确要求 --rzsz 时不能静默降级为普通连接；目前没有内置的 ZMODEM 实现可以替代 lrzsz
+    if use_rzsz && !rzsz_enabled {
+        return Err(anyhow::anyhow!("本地未安装 lrzsz，无法使用 --rzsz，请安装后重试或去掉 --rzsz 参数"));
+    }
     let use_rzsz_proxy = use_rzsz && rzsz_enabled;
 
     println!("RZSZ文件传输{}", if rzsz_enabled {
@@ -319,6 +273,7 @@ expect {{
             cmd.env("RSSH_HOST", &server.host)
                .env("RSSH_PORT", &server.port.to_string())
                .env("RSSH_USER", &server.username)
+               .env("RSSH_SSH_OPTIONS", server.ssh_options().join(" "))
                .stdin(Stdio::inherit())
                .stdout(Stdio::inherit())
                .stderr(Stdio::inherit());
@@ -349,7 +304,7 @@ expect {{
 
             return Ok(0);
         } else {
-            println!("未找到rzsz-proxy程序，使用普通SSH连接");
+            return Err(anyhow::anyhow!("未找到 rzsz-proxy 程序，无法使用 --rzsz（应与 rssh 安装在同一目录）"));
         }
     }
 
@@ -371,11 +326,11 @@ expect {{
         println!("提示: 如果需要rzsz文件传输功能，请确保远程服务器也安装了lrzsz软件包");
     }
 
-    println!("命令: {} {}", ssh_path.display(), args.join(" "));
+    println!("命令: {}", ssh_command);
 
     // 创建一个新的进程
-    let mut child = Command::new(ssh_path)
-        .args(&args)
+    let mut child = ssh_command
+        .to_command()
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
@@ -396,6 +351,44 @@ expect {{
     Ok(exit_code)
 }
 
+/// 传给 expect 的密码所在的环境变量；密码不写进脚本文件，也不出现在命令行参数里
+const PASSWORD_ENV: &str = "RSSH_EXPECT_PASSWORD";
+
+/// expect 脚本开头：读出密码后立即从环境中删除，spawn 的 ssh 不会继承
+const READ_PASSWORD: &str = "set password $env(RSSH_EXPECT_PASSWORD)\nunset env(RSSH_EXPECT_PASSWORD)";
+
+fn expect_command(expect_path: &std::path::Path, script_path: &std::path::Path, password: &str) -> Command {
+    let mut cmd = Command::new(expect_path);
+    cmd.arg(script_path).env(PASSWORD_ENV, password);
+    cmd
+}
+
+/// 把字符串写成 Tcl 的花括号字面量，其中的花括号按字面匹配
+fn tcl_braced(value: &str) -> String {
+    format!("{{{}}}", value.replace('{', "\\{").replace('}', "\\}"))
+}
+
+/// expect 等待提示超时或 ssh 提前退出时执行的 Tcl 片段：打印远程实际发送的内容，
+/// 便于看出提示是大写、其它语言还是根本没有出现，然后以 1 退出。
+/// 超时时 expect 不会设置 `expect_out(buffer)`，`drain` 时先把未匹配的输出读出来
+fn report_unmatched(reason: &str, drain: bool) -> String {
+    let drain = if drain {
+        "        unset -nocomplain expect_out(buffer)\n        catch {expect -timeout 0 -re {.+}}\n"
+    } else {
+        ""
+    };
+    format!(
+        r#"{drain}        puts stderr "\nrssh: {reason}"
+        if {{[info exists expect_out(buffer)] && $expect_out(buffer) ne ""}} {{
+            puts stderr "rssh: 远程发送的内容:\n$expect_out(buffer)"
+        }} else {{
+            puts stderr "rssh: 远程没有发送任何内容"
+        }}
+        puts stderr "rssh: 提示格式不同时可用 rssh edit 为该服务器设置密码提示 / 就绪提示（Tcl 正则）"
+        exit 1"#
+    )
+}
+
 /// 推导某台服务器对应的 wezterm 多路复用域名。
 ///
 /// `wezterm.default_ssh_domains()` 会为 ~/.ssh/config 里每个 `Host <别名>` 生成
@@ -545,58 +538,22 @@ pub fn ssh_command_connect(server: &ServerConfig, use_kitten: bool) -> Result<()
     // 检查是否使用kitty的kitten ssh
     let use_kitty_kitten = use_kitten && is_kitty_available();
 
-    let host_str = if server.port != 22 {
-        format!("-p {} {}@{}", server.port, server.username, server.host)
-    } else {
-        format!("{}@{}", server.username, server.host)
-    };
+    if let AuthType::Password(_) = &server.auth_type {
+        println!("警告: 系统SSH命令不支持直接传递密码，请使用其他验证方式。");
+        return Err(anyhow::anyhow!("不支持密码验证"));
+    }
 
-    let ssh_path = if use_kitty_kitten {
-        "kitty".into()
+    let ssh_command = if use_kitty_kitten {
+        SshCommand::new(server).program(&["kitty", "+kitten", "ssh"])
     } else {
-        which::which("ssh").unwrap_or_else(|_| "ssh".into())
-    };
-
-    // 提前声明变量以延长生命周期
-    let expanded_path_storage;
-
-    // 创建参数列表
-    let mut all_args = Vec::new();
-
-    // 如果使用kitty kitten，添加相应的命令和参数
-    if use_kitty_kitten {
-        all_args.push("+kitten");
-        all_args.push("ssh");
+        let ssh_path = which::which("ssh").unwrap_or_else(|_| "ssh".into());
+        SshCommand::new(server).program(&[ssh_path.display().to_string()])
     }
+    .with_config_file(&get_ssh_config_dir()?)?;
 
-    // 添加ssh-rsa算法支持
-    all_args.push("-o");
-    all_args.push("HostKeyAlgorithms=+ssh-rsa");
-    all_args.push("-o");
-    all_args.push("PubkeyAcceptedAlgorithms=+ssh-rsa");
-
-    // 添加认证相关参数
-    match &server.auth_type {
-        AuthType::Key(key_path) => {
-            expanded_path_storage = expand_tilde(key_path);
-            all_args.push("-i");
-            all_args.push(&expanded_path_storage);
-            all_args.push(&host_str);
-        },
-        AuthType::Agent => {
-            all_args.push(&host_str);
-        },
-        AuthType::Password(password) => {
-            println!("警告: 系统SSH命令不支持直接传递密码，请使用其他验证方式。");
-            return Err(anyhow::anyhow!("不支持密码验证"));
-        }
-    };
-
+    println!("执行: {}", ssh_command);
     if use_kitty_kitten {
-        println!("执行: kitty +kitten ssh {}", all_args[2..].join(" "));
         println!("已启用kitty kitten模式");
-    } else {
-        println!("执行: {} {}", ssh_path.display(), all_args.join(" "));
     }
 
     // 使用exec系统调用直接替换当前进程
@@ -604,12 +561,8 @@ pub fn ssh_command_connect(server: &ServerConfig, use_kitten: bool) -> Result<()
     {
         use std::os::unix::process::CommandExt;
 
-        // 创建命令但不启动
-        let mut cmd = Command::new(ssh_path);
-        cmd.args(&all_args);
-
         // 使用exec启动进程，替换当前进程
-        let error = cmd.exec();
+        let error = ssh_command.to_command().exec();
 
         // 如果exec返回，则表示出错
         return Err(anyhow::anyhow!("执行SSH命令失败: {}", error));
@@ -618,8 +571,8 @@ pub fn ssh_command_connect(server: &ServerConfig, use_kitten: bool) -> Result<()
     // 非Unix平台使用普通的spawn
     #[cfg(not(unix))]
     {
-        let mut child = Command::new(ssh_path)
-            .args(&all_args)
+        let mut child = ssh_command
+            .to_command()
             .spawn()
             .with_context(|| "无法启动SSH进程")?;
 
@@ -695,4 +648,16 @@ mod tests {
             &["bash".to_string(), "-lc".to_string(), "uptime".to_string()]
         );
     }
+
+    #[test]
+    fn expect_password_is_passed_through_environment() {
+        let password = "p\"a$s [w]\\d";
+        let script = secure_temp::write("env_test.sh", format!("printf %s \"${}\"", PASSWORD_ENV), 0o700).unwrap();
+        let mut cmd = expect_command(std::path::Path::new("sh"), &script, password);
+        assert!(!cmd.get_args().any(|a| a.to_string_lossy().contains(password)));
+
+        let output = cmd.output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), password);
+        assert!(READ_PASSWORD.contains(&format!("unset env({})", PASSWORD_ENV)));
+    }
 }
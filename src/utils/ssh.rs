@@ -1,23 +1,189 @@
 use anyhow::{Context, Result};
-use ssh2::Session;
+use ssh2::{CheckResult, HostKeyType, KnownHostFileKind, MethodType, Session, TraceFlags};
 use std::io::{self, Read, Write};
-use std::net::TcpStream;
-use std::path::Path;
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::process::{Child, Command, Stdio};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::fs::OpenOptions;
-use crate::models::{AuthType, ServerConfig};
+use std::os::unix::fs::OpenOptionsExt;
+use crate::models::{ssh_verbosity, Algorithms, AntiIdle, AntiIdleMethod, AuthType, DialogStep, HostKeyPolicy, LoginBanner, ServerConfig};
 use crate::utils::handle_rzsz::handle_rzsz;
+use crate::utils::session_registry::SessionRegistry;
+use crate::utils::idle_lock::IdleLock;
+use crate::utils::motd::MotdFilter;
+use crate::utils::recording::Recorder;
+use crate::utils::clipboard::Osc52Bridge;
+use crate::utils::scrollback::Scrollback;
+use crate::utils::triggers::{self, Action, Triggers};
+use crate::utils::macros::InputMacros;
+use crate::utils::session_escape::{
+    parse_command_line, EscapeAction, EscapeCommand, EscapeEvent, EscapeParser, Forward, ForwardKind, ESCAPE_HELP,
+};
 use crate::utils::ssh_config::expand_tilde;
+use crate::utils::address_fallback;
+use crate::utils::login_dialog;
+use crate::utils::traffic;
+
+/// 依次用私钥文件认证，直到有一个被服务器接受。
+/// 有 CA 签发的证书时，密钥认证的密钥先用证书认证，服务器不信任该 CA 时再直接用密钥
+fn authenticate_with_files(sess: &Session, server: &ServerConfig, files: &[&str]) -> Result<()> {
+    let primary = server.auth_type.get_key_path();
+    let mut last_error = None;
+    for file in files {
+        let expanded_path = expand_tilde(file);
+        let key_path = Path::new(&expanded_path);
+        verbose(2, format_args!("尝试私钥: {}", key_path.display()));
+        let by_certificate = primary == Some(*file) && server.certificate.as_ref().is_some_and(|cert| {
+            sess.userauth_pubkey_file(&server.username, Some(Path::new(cert)), key_path, None).is_ok()
+        });
+        if by_certificate {
+            return Ok(());
+        }
+        match sess.userauth_pubkey_file(&server.username, None, key_path, None) {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = Some(anyhow::Error::from(e).context(format!("密钥认证失败，路径: {}", key_path.display()))),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("没有可用的私钥")))
+}
+
+/// 私钥对应的 `.pub` 文件中的公钥，用于在 agent 的身份中找出配置的私钥
+fn public_key_blob(file: &str) -> Option<Vec<u8>> {
+    use base64::Engine;
+
+    let public = std::fs::read_to_string(format!("{}.pub", expand_tilde(file))).ok()?;
+    let encoded = public.split_whitespace().nth(1)?;
+    base64::engine::general_purpose::STANDARD.decode(encoded).ok()
+}
+
+/// 把服务器配置的算法偏好应用到 libssh2 会话，只处理明确配置过的项
+fn apply_algorithms(sess: &Session, algorithms: &Algorithms) -> Result<()> {
+    let configured = [
+        (&algorithms.kex, &[MethodType::Kex][..]),
+        (&algorithms.ciphers, &[MethodType::CryptCs, MethodType::CryptSc][..]),
+        (&algorithms.macs, &[MethodType::MacCs, MethodType::MacSc][..]),
+        (&algorithms.host_key, &[MethodType::HostKey][..]),
+    ];
+
+    for (spec, method_types) in configured {
+        let Some(spec) = spec else { continue };
+        for method_type in method_types {
+            let supported = sess.supported_algs(*method_type)?;
+            // libssh2 不认识的算法会导致整个设置失败，这里先过滤掉
+            let prefs: Vec<String> = Algorithms::resolve(spec, &supported)
+                .into_iter()
+                .filter(|name| supported.contains(&name.as_str()))
+                .collect();
+            if prefs.is_empty() {
+                return Err(anyhow::anyhow!("内置 SSH 库不支持任何配置的算法: {}", spec));
+            }
+            sess.method_pref(*method_type, &prefs.join(","))
+                .with_context(|| format!("无法设置算法偏好: {}", spec))?;
+        }
+    }
+    Ok(())
+}
+
+/// 按主机密钥策略对照 ~/.ssh/known_hosts 校验服务器的主机密钥
+/// 按实际连接的地址 `host` 校验主机密钥（使用备用地址时与 `server.host` 不同）
+fn verify_host_key(sess: &Session, server: &ServerConfig, host: &str) -> Result<()> {
+    let policy = server.host_key_policy();
+    if policy == HostKeyPolicy::Off {
+        return Ok(());
+    }
+
+    let (key, key_type) = sess.host_key().ok_or_else(|| anyhow::anyhow!("服务器未提供主机密钥"))?;
+    let path = dirs::home_dir()
+        .ok_or_else(|| anyhow::anyhow!("无法确定用户主目录"))?
+        .join(".ssh")
+        .join("known_hosts");
+
+    let mut known_hosts = sess.known_hosts()?;
+    if path.exists() {
+        known_hosts.read_file(&path, KnownHostFileKind::OpenSSH)
+            .with_context(|| format!("无法读取 {}", path.display()))?;
+    }
+
+    match known_hosts.check_port(host, server.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(anyhow::anyhow!(
+            "{} 的主机密钥与 {} 中记录的不一致，可能存在中间人攻击",
+            host,
+            path.display()
+        )),
+        CheckResult::NotFound if policy == HostKeyPolicy::AcceptNew => {
+            // 追加一行而不是让 libssh2 重写整个文件，避免丢失它不认识的条目
+            let key_name = match key_type {
+                HostKeyType::Rsa => "ssh-rsa",
+                HostKeyType::Dss => "ssh-dss",
+                HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+                HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+                HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+                HostKeyType::Ed25519 => "ssh-ed25519",
+                HostKeyType::Unknown => return Err(anyhow::anyhow!("未知的主机密钥类型")),
+            };
+            let host = if server.port == 22 {
+                host.to_string()
+            } else {
+                format!("[{}]:{}", host, server.port)
+            };
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("无法写入 {}", path.display()))?;
+            writeln!(file, "{} {} {}", host, key_name, base64::Engine::encode(&base64::engine::general_purpose::STANDARD, key))?;
+            Ok(())
+        }
+        CheckResult::NotFound => Err(anyhow::anyhow!(
+            "{} 中没有 {} 的主机密钥（主机密钥策略为 strict）",
+            path.display(),
+            server.host
+        )),
+        CheckResult::Failure => Err(anyhow::anyhow!("无法校验 {} 的主机密钥", server.host)),
+    }
+}
+
+/// `--ssh-verbose` 时把内置库的连接过程输出到 stderr，格式与 ssh -v 相同
+fn verbose(level: u8, msg: impl std::fmt::Display) {
+    if ssh_verbosity() >= level {
+        eprintln!("debug{}: {}", level, msg);
+    }
+}
+
+/// 调试日志的位置，由 rssh 启动时设置为状态目录中的 debug.log
+static DEBUG_LOG_PATH: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+/// 设置调试日志的位置，只在启动时调用一次
+pub fn set_debug_log_path(path: PathBuf) {
+    let _ = DEBUG_LOG_PATH.set(path);
+}
+
+/// 调试日志的位置；没有设置时使用当前用户的私有临时目录，/tmp 中的固定文件名可能被其他用户预先创建成符号链接
+pub fn debug_log_path() -> Result<PathBuf> {
+    match DEBUG_LOG_PATH.get() {
+        Some(path) => Ok(path.clone()),
+        None => Ok(crate::utils::secure_temp::user_dir()?.join("debug.log")),
+    }
+}
 
 // 调试日志函数
 fn debug_log(msg: &str) -> std::io::Result<()> {
+    let path = debug_log_path().map_err(std::io::Error::other)?;
     // 创建或追加到调试日志文件
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open("/tmp/rssh_debug.log")?;
+        .mode(0o600)
+        .open(path)?;
     
     // 添加时间戳
     let now = chrono::Local::now();
@@ -30,44 +196,234 @@ fn debug_log(msg: &str) -> std::io::Result<()> {
     Ok(())
 }
 
+/// SSH 会话底层的连接：直接 TCP，经跳板机 `ssh -W` 或代理命令转发的本地 socket，
+/// 或经跳板服务器（`proxy_jump`）的 direct-tcpip 通道转发的本地 socket
+enum Transport {
+    Tcp(TcpStream),
+    Jump(UnixStream, Child),
+    Tunnel(UnixStream),
+}
+
+impl Transport {
+    /// 服务器配置了跳板服务器、跳板机或代理命令时经它们连接，否则直接连接（有备用地址时依次尝试），返回连接和实际使用的地址
+    fn open(server: &ServerConfig) -> Result<(Self, String)> {
+        if let Some((first, rest)) = server.jump_servers.split_first() {
+            // 逐跳用内置库登录，每一跳经上一跳的通道连接
+            let mut via = (SshClient::connect(first)?, first.name.as_str());
+            for hop in rest {
+                verbose(1, format_args!("经 {} 连接跳板服务器 {}", via.1, hop.name));
+                let transport = Transport::tunnel(via.0, via.1, &hop.host, hop.port)?;
+                via = (SshClient::establish(hop, transport, hop.host.clone())?, hop.name.as_str());
+            }
+            let transport = Transport::tunnel(via.0, via.1, &server.host, server.port)?;
+            return Ok((transport, server.host.clone()));
+        }
+        let (mut command, via) = match (&server.jump_host, server.expanded_proxy_command()) {
+            (Some(jump), _) => {
+                let mut command = Command::new("ssh");
+                command
+                    .args(["-q", "-W", &format!("{}:{}", server.host, server.port)])
+                    .arg(format!("ssh://{}", jump));
+                (command, format!("跳板机 {}", jump))
+            },
+            (None, Some(proxy_command)) => {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(&proxy_command);
+                (command, format!("代理命令 {}", proxy_command))
+            },
+            (None, None) => {
+                let (tcp, host) = address_fallback::connect(server)?;
+                tcp.set_read_timeout(Some(Duration::from_secs(30)))?;
+                tcp.set_write_timeout(Some(Duration::from_secs(30)))?;
+                return Ok((Transport::Tcp(tcp), host));
+            },
+        };
+
+        let (ours, theirs) = UnixStream::pair().with_context(|| "无法创建本地 socket")?;
+        let stdin = OwnedFd::from(theirs.try_clone()?);
+        let child = command
+            .stdin(Stdio::from(stdin))
+            .stdout(Stdio::from(OwnedFd::from(theirs)))
+            .spawn()
+            .with_context(|| format!("无法通过{}连接", via))?;
+        ours.set_read_timeout(Some(Duration::from_secs(30)))?;
+        ours.set_write_timeout(Some(Duration::from_secs(30)))?;
+        Ok((Transport::Jump(ours, child), server.host.clone()))
+    }
+
+    /// 经已登录的服务器打开到 `host:port` 的通道，由后台线程在通道和本地 socket 之间搬运数据，
+    /// 本地 socket 关闭后线程结束并断开与该服务器的连接
+    fn tunnel(via: SshClient, via_name: &str, host: &str, port: u16) -> Result<Self> {
+        let channel = via.session.channel_direct_tcpip(host, port, None)
+            .with_context(|| format!("无法经跳板服务器 {} 连接 {}:{}", via_name, host, port))?;
+        let (ours, theirs) = UnixStream::pair().with_context(|| "无法创建本地 socket")?;
+        theirs.set_nonblocking(true)?;
+        std::thread::spawn(move || relay(via, channel, theirs));
+        ours.set_read_timeout(Some(Duration::from_secs(30)))?;
+        ours.set_write_timeout(Some(Duration::from_secs(30)))?;
+        Ok(Transport::Tunnel(ours))
+    }
+
+    /// 直接 TCP 连接时统计流量，经跳板机时流量在 ssh 进程中，不统计
+    fn meter(&self, server: &ServerConfig) -> Result<Option<traffic::Meter>> {
+        Ok(match self {
+            Transport::Tcp(tcp) => Some(traffic::Meter::new(server, OwnedFd::from(tcp.try_clone()?))),
+            Transport::Jump(..) | Transport::Tunnel(_) => None,
+        })
+    }
+
+    fn try_clone_fd(&self) -> Result<OwnedFd> {
+        Ok(match self {
+            Transport::Tcp(tcp) => OwnedFd::from(tcp.try_clone()?),
+            Transport::Jump(stream, _) | Transport::Tunnel(stream) => OwnedFd::from(stream.try_clone()?),
+        })
+    }
+}
+
+/// 在跳板服务器的通道和本地 socket 之间搬运数据，直到任一端关闭
+fn relay(via: SshClient, mut channel: ssh2::Channel, mut socket: UnixStream) {
+    via.session.set_keepalive(true, 30);
+    via.session.set_blocking(false);
+    let mut buf = [0u8; 32 * 1024];
+    let mut next_keepalive = std::time::Instant::now() + Duration::from_secs(30);
+    loop {
+        let mut idle = true;
+
+        if std::time::Instant::now() >= next_keepalive {
+            let secs = match via.session.keepalive_send().map_err(io::Error::from) {
+                Ok(secs) => secs,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => 1,
+                Err(_) => break,
+            };
+            next_keepalive = std::time::Instant::now() + Duration::from_secs(secs.max(1) as u64);
+        }
+
+        match socket.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                if write_all_nonblocking(&mut channel, &buf[..n]).is_err() {
+                    break;
+                }
+                idle = false;
+            },
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {},
+            Err(_) => break,
+        }
+        match channel.read(&mut buf) {
+            Ok(0) => {},
+            Ok(n) => {
+                if write_all_nonblocking(&mut socket, &buf[..n]).is_err() {
+                    break;
+                }
+                idle = false;
+            },
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {},
+            Err(_) => break,
+        }
+
+        if idle {
+            if channel.eof() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+    let _ = channel.close();
+}
+
+impl Drop for Transport {
+    fn drop(&mut self) {
+        if let Transport::Jump(_, child) = self {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
 pub struct SshClient {
     session: Session,
-    _stream: TcpStream,
+    /// 在会话之后释放，计入断开消息
+    _meter: Option<traffic::Meter>,
+    _transport: Transport,
+    login_banner: LoginBanner,
+    banner_capture: Option<PathBuf>,
+    /// 录制文件和标题
+    recording: Option<(PathBuf, String)>,
+    /// 只读会话：键盘输入不发送到远程，只处理转义命令
+    read_only: bool,
+    /// 在终端中执行的命令，为空时打开登录 shell
+    command: Option<String>,
+    /// 打开登录 shell 后执行的登录对话
+    login_dialog: Vec<DialogStep>,
+    /// 会话开始时建立的本地端口转发
+    local_forwards: Vec<Forward>,
+    /// 对该服务器生效的输出触发器
+    triggers: Option<Triggers>,
+    /// 对该服务器生效的输入宏
+    macros: Option<InputMacros>,
+    /// 防空闲设置
+    anti_idle: Option<AntiIdle>,
 }
 
 impl SshClient {
     pub fn connect(server: &ServerConfig) -> Result<Self> {
-        let addr = format!("{}:{}", server.host, server.port);
-        
-        let tcp = TcpStream::connect(&addr)
-            .with_context(|| format!("无法连接到服务器 {}", addr))?;
-        
-        tcp.set_read_timeout(Some(Duration::from_secs(30)))?;
-        tcp.set_write_timeout(Some(Duration::from_secs(30)))?;
-        
+        let names: Vec<&str> = server.jump_servers.iter().map(|hop| hop.name.as_str()).collect();
+        match &server.jump_host {
+            _ if !names.is_empty() => verbose(1, format_args!("经跳板服务器 {} 连接 {}:{}", names.join(" -> "), server.host, server.port)),
+            Some(jump) => verbose(1, format_args!("经跳板机 {} 连接 {}:{}", jump, server.host, server.port)),
+            None if server.proxy_command.is_some() => verbose(1, format_args!("经代理命令连接 {}:{}", server.host, server.port)),
+            None => verbose(1, format_args!("连接 {} 端口 {}", server.addresses().join(", "), server.port)),
+        }
+        let (transport, host) = Transport::open(server)?;
+        verbose(1, format_args!("已连接 {}:{}", host, server.port));
+        Self::establish(server, transport, host)
+    }
+
+    /// 在已建立的连接上完成握手、主机密钥校验和认证
+    fn establish(server: &ServerConfig, transport: Transport, host: String) -> Result<Self> {
         let mut sess = Session::new()
             .with_context(|| "无法创建SSH会话")?;
+        // 只有以调试模式编译的 libssh2 才会输出
+        if ssh_verbosity() >= 3 {
+            sess.trace(TraceFlags::all());
+        }
         
-        sess.set_tcp_stream(tcp.try_clone()?);
-        sess.handshake()
-            .with_context(|| "SSH握手失败")?;
+        sess.set_tcp_stream(transport.try_clone_fd()?);
+        // 压缩需要在握手前设置，服务器不支持时协商为不压缩
+        sess.set_compress(server.compression);
+        apply_algorithms(&sess, &server.algorithms())?;
+        sess.handshake().with_context(|| match (&transport, &server.jump_host) {
+            (Transport::Tunnel(_), _) => "SSH握手失败（经跳板服务器）".to_string(),
+            (_, Some(jump)) => format!("SSH握手失败（经跳板机 {}）", jump),
+            (Transport::Jump(..), None) => "SSH握手失败（经代理命令）".to_string(),
+            _ => "SSH握手失败".to_string(),
+        })?;
+        verbose(2, format_args!("服务器版本: {}", sess.banner().unwrap_or("未知")));
+        verbose(1, format_args!(
+            "协商结果: 密钥交换 {}，主机密钥 {}，加密 {}，MAC {}",
+            sess.methods(MethodType::Kex).unwrap_or("?"),
+            sess.methods(MethodType::HostKey).unwrap_or("?"),
+            sess.methods(MethodType::CryptCs).unwrap_or("?"),
+            sess.methods(MethodType::MacCs).unwrap_or("?"),
+        ));
+        verify_host_key(&sess, server, &host)?;
+        verbose(1, format_args!("主机密钥校验通过（策略 {}）", server.host_key_policy().name()));
+        // 查询认证方式会向服务器发送一次 none 认证请求，只在需要输出时查询
+        if ssh_verbosity() >= 2 {
+            verbose(2, format_args!("服务器支持的认证方式: {}", sess.auth_methods(&server.username).unwrap_or("?")));
+        }
+        verbose(1, format_args!("以 {} 进行{}认证", server.username, match &server.auth_type {
+            AuthType::Password(_) => "密码",
+            AuthType::Key(_) => "密钥",
+            AuthType::Agent => "SSH 代理",
+        }));
         
         match &server.auth_type {
             AuthType::Password(password) => {
                 sess.userauth_password(&server.username, password)
                     .with_context(|| "密码认证失败")?;
             },
-            AuthType::Key(key_path) => {
-                let expanded_path = expand_tilde(key_path);
-                let key_path = Path::new(&expanded_path);
-                sess.userauth_pubkey_file(
-                    &server.username,
-                    None,
-                    key_path,
-                    None,
-                )
-                .with_context(|| format!("密钥认证失败，路径: {}", key_path.display()))?;
-            },
+            AuthType::Key(_) => authenticate_with_files(&sess, server, &server.identities())?,
             AuthType::Agent => {
                 let mut agent = sess.agent()
                     .with_context(|| "无法连接到SSH代理")?;
@@ -78,19 +434,32 @@ impl SshClient {
                 agent.list_identities()
                     .with_context(|| "无法列出SSH代理身份")?;
                 
-                let identities = agent.identities()
+                let mut identities = agent.identities()
                     .with_context(|| "读取SSH代理身份失败")?;
+                // IdentitiesOnly：只用 agent 中与配置的私钥对应的密钥
+                if server.identities_only {
+                    let allowed: Vec<Vec<u8>> = server.identity_files.iter().filter_map(|file| public_key_blob(file)).collect();
+                    identities.retain(|identity| allowed.iter().any(|blob| blob == identity.blob()));
+                }
                 
-                if identities.is_empty() {
+                if identities.is_empty() && server.identity_files.is_empty() {
+                    if server.identities_only {
+                        return Err(anyhow::anyhow!("SSH代理中没有与配置的私钥对应的身份（已开启 IdentitiesOnly）"));
+                    }
                     return Err(anyhow::anyhow!("SSH代理中没有可用的身份"));
                 }
                 
                 let authenticated = identities.iter().any(|identity| {
+                    verbose(2, format_args!("尝试代理中的密钥: {}", identity.comment()));
                     agent.userauth(&server.username, identity).is_ok()
                 });
                 
                 if !authenticated {
-                    return Err(anyhow::anyhow!("SSH代理认证失败"));
+                    if server.identity_files.is_empty() {
+                        return Err(anyhow::anyhow!("SSH代理认证失败"));
+                    }
+                    let files: Vec<&str> = server.identity_files.iter().map(String::as_str).collect();
+                    authenticate_with_files(&sess, server, &files).with_context(|| "SSH代理认证失败")?;
                 }
             }
         }
@@ -101,17 +470,89 @@ impl SshClient {
         
         Ok(SshClient {
             session: sess,
-            _stream: tcp,
+            _meter: transport.meter(server)?,
+            _transport: transport,
+            login_banner: server.login_banner,
+            banner_capture: None,
+            recording: None,
+            read_only: false,
+            command: None,
+            login_dialog: server.login_dialog.clone(),
+            local_forwards: server.local_forwards.clone(),
+            triggers: Triggers::for_server(server),
+            macros: InputMacros::for_server(server),
+            anti_idle: server.anti_idle,
         })
     }
+
+    /// 登录横幅为 capture 时，登录信息保存到的文件
+    pub fn capture_banner_to(mut self, path: PathBuf) -> Self {
+        self.banner_capture = Some(path);
+        self
+    }
+
+    /// 只读会话：只显示远程输出，键盘输入（除转义命令外）不发送到远程
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// 在终端中执行命令而不是打开登录 shell，例如 `tail -f`、`tmux attach -r`
+    pub fn shell_command(mut self, command: String) -> Self {
+        self.command = Some(command);
+        self
+    }
+
+    /// 把交互式会话的输出录制为 asciicast 文件
+    pub fn record_to(mut self, path: PathBuf, title: &str) -> Self {
+        self.recording = Some((path, title.to_string()));
+        self
+    }
     
+    /// 打开 SFTP 子系统
+    pub fn sftp(&self) -> Result<ssh2::Sftp> {
+        self.session.sftp().with_context(|| "无法打开SFTP会话")
+    }
+
+    /// 在远程执行命令并返回通道，用于持续读取输出（如 inotifywait -m）
+    pub fn exec_channel(&self, command: &str) -> Result<ssh2::Channel> {
+        let mut channel = self.session.channel_session()
+            .with_context(|| "无法创建SSH通道")?;
+        channel.exec(command)
+            .with_context(|| format!("执行命令失败: {}", command))?;
+        Ok(channel)
+    }
+
+    /// 在伪终端中执行命令，用于只从终端读取输入的程序（如 passwd）
+    pub fn pty_channel(&self, command: &str) -> Result<ssh2::Channel> {
+        let mut channel = self.session.channel_session()
+            .with_context(|| "无法创建SSH通道")?;
+        channel.request_pty("dumb", None, None)
+            .with_context(|| "无法分配伪终端")?;
+        channel.exec(command)
+            .with_context(|| format!("执行命令失败: {}", command))?;
+        Ok(channel)
+    }
+
     pub fn execute_command(&self, command: &str) -> Result<(String, String, i32)> {
+        self.execute_command_with_input(command, &[])
+    }
+    
+    /// 执行命令，`input` 非空时写入命令的标准输入后关闭（例如 `sudo -S` 的密码）
+    pub fn execute_command_with_input(&self, command: &str, input: &[u8]) -> Result<(String, String, i32)> {
         let mut channel = self.session.channel_session()
             .with_context(|| "无法创建SSH通道")?;
         
         channel.exec(command)
             .with_context(|| format!("执行命令失败: {}", command))?;
         
+        if !input.is_empty() {
+            channel.write_all(input)
+                .with_context(|| "写入标准输入失败")?;
+            channel.send_eof()
+                .with_context(|| "关闭标准输入失败")?;
+        }
+        
         let mut stdout = String::new();
         channel.read_to_string(&mut stdout)
             .with_context(|| "读取标准输出失败")?;
@@ -129,7 +570,9 @@ impl SshClient {
         Ok((stdout, stderr, exit_status))
     }
     
-    pub fn start_shell(&self) -> Result<()> {
+    /// 打开交互式 shell；`registry` 用于在 `rssh ps` 中展示会话及其端口转发。
+    /// 返回远程 shell（或 `shell_command` 设置的命令）的退出码
+    pub fn start_shell(&self, mut registry: Option<SessionRegistry>) -> Result<i32> {
         debug_log("开始启动SSH交互式shell")?;
         
         let mut channel = self.session.channel_session()
@@ -152,8 +595,12 @@ impl SshClient {
         .with_context(|| "请求PTY失败")?;
         
         debug_log("正在启动shell")?;
-        channel.shell()
-            .with_context(|| "启动Shell失败")?;
+        match &self.command {
+            Some(command) => channel.exec(command)
+                .with_context(|| format!("执行命令失败: {}", command))?,
+            None => channel.shell()
+                .with_context(|| "启动Shell失败")?,
+        }
         
         // 设置信号处理，优雅退出
         debug_log("设置信号处理程序")?;
@@ -194,11 +641,71 @@ impl SshClient {
             debug_log("设置终端为非阻塞模式")?;
             unsafe { fcntl(stdin_fd, F_SETFL, original_flags | O_NONBLOCK) };
             
+            // 转义命令需要临时回到普通终端模式读取输入
+            let cooked_mode = || -> Result<()> {
+                termios::tcsetattr(stdin_fd, termios::TCSANOW, &termios_backup)?;
+                unsafe { fcntl(stdin_fd, F_SETFL, original_flags) };
+                Ok(())
+            };
+            let raw_mode = || -> Result<()> {
+                termios::tcsetattr(stdin_fd, termios::TCSANOW, &termios_org)?;
+                unsafe { fcntl(stdin_fd, F_SETFL, original_flags | O_NONBLOCK) };
+                Ok(())
+            };
+            
             // 创建缓冲区
             let mut stdin_buf = [0u8; 1024];
             let mut channel_buf = [0u8; 4096];
+            let mut escapes = EscapeParser::default();
+            let mut forwards: Vec<RuntimeForward> = Vec::new();
+            // `rssh connect -L` 的转发随会话建立，失败时与 ssh 一样只提示
+            for spec in &self.local_forwards {
+                match RuntimeForward::open(&self.session, spec.clone()) {
+                    Ok(forward) => {
+                        print!("已添加端口转发 {}\r\n", forward.spec);
+                        forwards.push(forward);
+                    },
+                    Err(e) => print!("{:#}\r\n", e),
+                }
+            }
+            if let Some(registry) = registry.as_mut() {
+                registry.set_forwards(forwards.iter().map(|f| f.spec.to_string()).collect());
+            }
+            let mut idle_lock = IdleLock::from_config()?;
+            // 执行命令时远程不输出登录信息，命令的输出原样显示
+            let banner = if self.command.is_some() { LoginBanner::Show } else { self.login_banner };
+            let mut motd = MotdFilter::new(banner, self.banner_capture.clone());
+            let mut clipboard = Osc52Bridge::from_config();
+            let mut scrollback = Scrollback::from_config();
+            let mut triggers = self.triggers.clone();
+            let mut macros = self.macros.clone();
+            let mut recorder = match &self.recording {
+                Some((path, title)) => Some(Recorder::create(path, term_size.0 as u16, term_size.1 as u16, title)?),
+                None => None,
+            };
             
-            println!("连接成功，按Ctrl+C退出。");
+            // 会话切换为非阻塞，没有远程输出时不会卡住键盘输入和端口转发
+            self.session.set_blocking(false);
+            
+            print!("连接成功，行首输入 ~. 断开连接，~? 查看转义命令。\r\n");
+            if self.read_only {
+                print!("只读模式：键盘输入不会发送到服务器。\r\n");
+            }
+            let mut read_only_hint = self.read_only;
+            if self.command.is_none() && !self.login_dialog.is_empty() {
+                let result = login_dialog::run(&mut channel, &self.login_dialog, login_dialog::STEP_TIMEOUT, |data| {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.output(data)?;
+                    }
+                    io::stdout().write_all(data)?;
+                    io::stdout().flush()?;
+                    Ok(())
+                });
+                // 对话没有走完时交给用户手动继续
+                if let Err(e) = result {
+                    print!("\r\nrssh: {:#}，请手动继续\r\n", e);
+                }
+            }
             debug_log("进入主循环")?;
             
             // 尝试一种不同的方法 - 将Channel设置为非阻塞模式
@@ -208,6 +715,16 @@ impl SshClient {
             // 支持键盘输入调试模式
             let mut debug_mode = false;
             
+            // 防空闲：只读会话不发送 NUL 按键，改为 keepalive
+            let anti_idle = self.anti_idle.map(|anti_idle| match anti_idle.method {
+                AntiIdleMethod::Nul if self.read_only => AntiIdle { method: AntiIdleMethod::Keepalive, ..anti_idle },
+                _ => anti_idle,
+            });
+            if let Some(anti_idle) = anti_idle {
+                self.session.set_keepalive(true, anti_idle.interval);
+            }
+            let mut last_activity = std::time::Instant::now();
+            
             // 主循环
             while running.load(Ordering::SeqCst) {
                 // 检查stdin是否有数据可读（非阻塞模式）
@@ -215,8 +732,20 @@ impl SshClient {
                     libc::read(stdin_fd, stdin_buf.as_mut_ptr() as *mut libc::c_void, stdin_buf.len()) 
                 };
                 
-                if read_result > 0 {
+                let locked = idle_lock.as_ref().is_some_and(IdleLock::is_locked);
+                if read_result > 0 && locked {
+                    // 锁定期间的输入只用于解锁
+                    if let Some(lock) = idle_lock.as_mut() {
+                        let output = lock.unlock_input(&stdin_buf[0..read_result as usize]);
+                        io::stdout().write_all(&output)?;
+                        io::stdout().flush()?;
+                    }
+                } else if read_result > 0 {
                     debug_log(&format!("从stdin读取了{}字节数据", read_result))?;
+                    if let Some(lock) = idle_lock.as_mut() {
+                        lock.touch();
+                    }
+                    last_activity = std::time::Instant::now();
                     
                     // 检查是否启用调试模式（按Alt+D）
                     if read_result >= 2 && stdin_buf[0] == 27 && stdin_buf[1] == 'd' as u8 {
@@ -235,34 +764,94 @@ impl SshClient {
                     }
                     
                     // 检测并处理rzsz命令
-                    if let Ok(handled) = handle_rzsz(&stdin_buf[0..read_result as usize], &mut channel) {
-                        if handled {
-                            debug_log("rzsz命令已处理")?;
-                            continue;
-                        }
+                    if !self.read_only && handle_rzsz(&stdin_buf[0..read_result as usize], &mut channel).unwrap_or(false) {
+                        debug_log("rzsz命令已处理")?;
+                        continue;
                     }
                     
-                    // 将数据发送到远程
-                    match channel.write(&stdin_buf[0..read_result as usize]) {
-                        Ok(n) => {
-                            debug_log(&format!("向channel写入了{}字节数据", n))?;
-                            // 确保所有数据都发送出去
-                            if n < read_result as usize {
-                                debug_log("未能发送所有数据！")?;
-                            }
-                            
-                            // 尝试立即刷新通道
-                            if let Err(e) = channel.flush() {
-                                debug_log(&format!("刷新通道失败: {}", e))?;
-                            } else {
-                                debug_log("通道刷新成功")?;
-                            }
-                        },
-                        Err(e) => {
-                            debug_log(&format!("写入channel失败: {}", e))?;
-                            break;
+                    // 将数据发送到远程，行首的 ~ 转义序列在本地处理
+                    let mut disconnect = false;
+                    let mut events = escapes.feed(&stdin_buf[0..read_result as usize]);
+                    if self.read_only && drop_read_only_input(&mut events) && read_only_hint {
+                        // 第一次丢弃输入时提示如何退出
+                        read_only_hint = false;
+                        print!("\r\n只读模式，输入不会发送；行首输入 ~. 断开连接\r\n");
+                    }
+                    for event in events {
+                        match event {
+                            EscapeEvent::Data(mut data) => {
+                                if let Some(macros) = macros.as_mut() {
+                                    data = macros.feed(&data);
+                                }
+                                if let Err(e) = write_all_nonblocking(&mut channel, &data) {
+                                    debug_log(&format!("写入channel失败: {}", e))?;
+                                    disconnect = true;
+                                    break;
+                                }
+                                debug_log(&format!("向channel写入了{}字节数据", data.len()))?;
+                            },
+                            EscapeEvent::Action(EscapeAction::Disconnect) => {
+                                disconnect = true;
+                                break;
+                            },
+                            EscapeEvent::Action(EscapeAction::Help) => print!("\r\n{}", ESCAPE_HELP),
+                            EscapeEvent::Action(EscapeAction::ListForwards) => {
+                                print!("\r\n当前端口转发:\r\n");
+                                for forward in &forwards {
+                                    print!("  {} ({} 个连接)\r\n", forward.spec, forward.conns.len());
+                                }
+                            },
+                            EscapeEvent::Action(EscapeAction::CommandLine) => {
+                                cooked_mode()?;
+                                run_escape_command(&self.session, &mut forwards);
+                                if let Some(registry) = registry.as_mut() {
+                                    registry.set_forwards(forwards.iter().map(|f| f.spec.to_string()).collect());
+                                }
+                                raw_mode()?;
+                            },
+                            EscapeEvent::Action(EscapeAction::Marker) => {
+                                let Some(recorder) = recorder.as_mut() else {
+                                    print!("\r\n当前会话没有录制，使用 rssh shell --record <文件> 开始录制\r\n");
+                                    continue;
+                                };
+                                cooked_mode()?;
+                                print!("\n标记: ");
+                                io::stdout().flush()?;
+                                let mut label = String::new();
+                                io::stdin().read_line(&mut label)?;
+                                let label = label.trim();
+                                if !label.is_empty() {
+                                    recorder.marker(label)?;
+                                    println!("已添加标记: {}", label);
+                                }
+                                raw_mode()?;
+                            },
+                            EscapeEvent::Action(EscapeAction::Scrollback) => {
+                                let Some(scrollback) = scrollback.as_ref() else {
+                                    print!("\r\n回滚缓冲已关闭（scrollback_kb = 0）\r\n");
+                                    continue;
+                                };
+                                cooked_mode()?;
+                                if let Err(e) = scrollback.open_pager() {
+                                    eprintln!("{:#}", e);
+                                }
+                                raw_mode()?;
+                            },
+                            EscapeEvent::Action(EscapeAction::LocalShell) => {
+                                cooked_mode()?;
+                                let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+                                println!("\n进入本地 shell，exit 后回到会话");
+                                if let Err(e) = std::process::Command::new(&shell).status() {
+                                    eprintln!("无法启动 {}: {}", shell, e);
+                                }
+                                raw_mode()?;
+                            },
                         }
                     }
+                    io::stdout().flush()?;
+                    if disconnect {
+                        break;
+                    }
                 } else if read_result < 0 {
                     let err = io::Error::last_os_error();
                     // EAGAIN和EWOULDBLOCK表示没有数据可读，不是真正的错误
@@ -276,6 +865,7 @@ impl SshClient {
                 match channel.read(&mut channel_buf) {
                     Ok(n) if n > 0 => {
                         debug_log(&format!("从channel读取了{}字节数据", n))?;
+                        last_activity = std::time::Instant::now();
                         
                         // 显示远程返回数据的十六进制表示（在调试模式下）
                         if debug_mode {
@@ -286,8 +876,30 @@ impl SshClient {
                             debug_log(&format!("从远程收到数据: {}", hex_data))?;
                         }
                         
+                        if let Some(lock) = idle_lock.as_mut().filter(|lock| lock.is_locked()) {
+                            lock.hold(&channel_buf[..n]);
+                            if let Some(recorder) = recorder.as_mut() {
+                                recorder.output(&channel_buf[..n])?;
+                            }
+                            continue;
+                        }
+                        
+                        let mut output = motd.feed(&channel_buf[..n]);
+                        if let Some(clipboard) = clipboard.as_mut() {
+                            output = clipboard.feed(&output);
+                        }
+                        apply_triggers(triggers.as_mut(), &mut output, &mut channel, self.read_only)?;
+                        if output.is_empty() {
+                            continue;
+                        }
+                        if let Some(recorder) = recorder.as_mut() {
+                            recorder.output(&output)?;
+                        }
+                        if let Some(scrollback) = scrollback.as_mut() {
+                            scrollback.push(&output);
+                        }
                         let write_result = unsafe { 
-                            libc::write(stdout_fd, channel_buf.as_ptr() as *const libc::c_void, n) 
+                            libc::write(stdout_fd, output.as_ptr() as *const libc::c_void, output.len()) 
                         };
                         
                         if write_result < 0 {
@@ -317,9 +929,46 @@ impl SshClient {
                     }
                 }
                 
+                for forward in &mut forwards {
+                    forward.poll(&self.session);
+                }
+                
+                let mut rest = motd.poll();
+                if let Some(clipboard) = clipboard.as_mut().filter(|_| !rest.is_empty()) {
+                    rest = clipboard.feed(&rest);
+                }
+                apply_triggers(triggers.as_mut(), &mut rest, &mut channel, self.read_only)?;
+                if !rest.is_empty() {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.output(&rest)?;
+                    }
+                    if let Some(scrollback) = scrollback.as_mut() {
+                        scrollback.push(&rest);
+                    }
+                    io::stdout().write_all(&rest)?;
+                    io::stdout().flush()?;
+                }
+                
+                if let Some(message) = idle_lock.as_mut().and_then(IdleLock::tick) {
+                    print!("{}", message);
+                    io::stdout().flush()?;
+                }
+                
+                if let Some(anti_idle) = anti_idle.filter(|anti_idle| last_activity.elapsed() >= Duration::from_secs(anti_idle.interval as u64)) {
+                    if let Err(e) = send_anti_idle(&self.session, &mut channel, anti_idle.method) {
+                        debug_log(&format!("防空闲发送失败: {}", e))?;
+                    }
+                    last_activity = std::time::Instant::now();
+                }
+                
                 // 短暂休眠以避免CPU使用率过高
                 std::thread::sleep(Duration::from_millis(5));
             }
+            self.session.set_blocking(true);
+            drop(forwards);
+            if idle_lock.as_ref().is_some_and(IdleLock::is_locked) {
+                print!("\x1b[?1049l");
+            }
             
             // 恢复终端设置
             debug_log("恢复终端设置")?;
@@ -380,7 +1029,329 @@ impl SshClient {
             println!("\r\n连接已关闭");
         }
         
-        Ok(())
+        Ok(channel.exit_status().unwrap_or(0))
+    }
+
+    /// 只做端口转发、不打开 shell（`rssh tunnel`），Ctrl+C 或连接断开时结束。
+    /// 所有转发都建立后调用 `on_ready`
+    pub fn run_forwards<F>(&self, specs: Vec<Forward>, mut registry: Option<SessionRegistry>, on_ready: F) -> Result<()>
+    where
+        F: FnOnce(&[Forward]),
+    {
+        let mut forwards = Vec::new();
+        for spec in specs {
+            let forward = RuntimeForward::open(&self.session, spec)?;
+            println!("已建立端口转发 {}", forward.spec);
+            forwards.push(forward);
+        }
+        if let Some(registry) = registry.as_mut() {
+            registry.set_forwards(forwards.iter().map(|f| f.spec.to_string()).collect());
+        }
+        on_ready(&forwards.iter().map(|f| f.spec.clone()).collect::<Vec<_>>());
+        println!("按 Ctrl+C 结束");
+
+        let running = Arc::new(AtomicBool::new(true));
+        let r = running.clone();
+        let _ = ctrlc::set_handler(move || r.store(false, Ordering::SeqCst));
+
+        // 定期发送 keepalive，连接断开时及时退出
+        self.session.set_keepalive(true, 30);
+        let mut next_keepalive = std::time::Instant::now();
+        self.session.set_blocking(false);
+        let mut result = Ok(());
+        while running.load(Ordering::SeqCst) {
+            for forward in &mut forwards {
+                forward.poll(&self.session);
+            }
+            if std::time::Instant::now() >= next_keepalive {
+                self.session.set_blocking(true);
+                let sent = self.session.keepalive_send();
+                self.session.set_blocking(false);
+                match sent {
+                    Ok(secs) => next_keepalive = std::time::Instant::now() + Duration::from_secs(secs.max(1) as u64),
+                    Err(e) => {
+                        result = Err(anyhow::anyhow!("连接已断开: {}", e));
+                        break;
+                    }
+                }
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        self.session.set_blocking(true);
+        drop(forwards);
+        result
+    }
+
+    /// 在远程执行命令（不分配终端），本地标准输入输出直接接到命令上，返回退出码。
+    /// 用于 git 等通过标准输入输出传输数据的程序，期间本进程不能再向标准输出写其他内容
+    pub fn pipe_command(&self, command: &str) -> Result<i32> {
+        let mut channel = self.exec_channel(command)?;
+        self.pipe_stdio(&mut channel)?;
+        channel.wait_close().with_context(|| "等待通道关闭失败")?;
+        channel.exit_status().with_context(|| "获取退出状态失败")
+    }
+
+    /// 经服务器连接 `host:port`（与 `ssh -W` 相同），本地标准输入输出直接接到该连接上，
+    /// 用作其他程序的 ProxyCommand
+    pub fn pipe_direct(&self, host: &str, port: u16) -> Result<()> {
+        let mut channel = self.session.channel_direct_tcpip(host, port, None)
+            .with_context(|| format!("无法经服务器连接 {}:{}", host, port))?;
+        self.pipe_stdio(&mut channel)
+    }
+
+    fn pipe_stdio(&self, channel: &mut ssh2::Channel) -> Result<()> {
+        // 标准输入在单独的线程中阻塞读取，主循环以非阻塞方式读写通道
+        let (tx, rx) = std::sync::mpsc::channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            let mut stdin = io::stdin();
+            let mut buf = [0u8; 32 * 1024];
+            loop {
+                match stdin.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        // 用作 ProxyCommand 时连接可能长时间空闲，定期发送 keepalive
+        self.session.set_keepalive(true, 30);
+        self.session.set_blocking(false);
+        let result = pump_channel(&self.session, channel, &rx);
+        self.session.set_blocking(true);
+        result
+    }
+}
+
+/// 在标准输入输出和通道之间转发数据，直到远程的输出结束
+fn pump_channel(session: &Session, channel: &mut ssh2::Channel, input: &std::sync::mpsc::Receiver<Vec<u8>>) -> Result<()> {
+    use std::sync::mpsc::TryRecvError;
+
+    let mut stdout = io::stdout();
+    let mut stderr = io::stderr();
+    let mut buf = [0u8; 32 * 1024];
+    let mut input_open = true;
+    let mut next_keepalive = std::time::Instant::now() + Duration::from_secs(30);
+    loop {
+        let mut idle = true;
+
+        if std::time::Instant::now() >= next_keepalive {
+            let secs = session.keepalive_send().map_err(io::Error::from).or_else(|e| match e.kind() {
+                io::ErrorKind::WouldBlock => Ok(1),
+                _ => Err(e),
+            }).with_context(|| "连接已断开")?;
+            next_keepalive = std::time::Instant::now() + Duration::from_secs(secs.max(1) as u64);
+        }
+
+        while input_open {
+            match input.try_recv() {
+                Ok(data) => {
+                    write_all_nonblocking(channel, &data).with_context(|| "向远程写入数据失败")?;
+                    idle = false;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    input_open = false;
+                    loop {
+                        match channel.send_eof().map_err(io::Error::from) {
+                            Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(1)),
+                            other => break other.with_context(|| "关闭远程的输入失败")?,
+                        }
+                    }
+                }
+            }
+        }
+
+        match channel.read(&mut buf) {
+            Ok(0) => {}
+            Ok(n) => {
+                stdout.write_all(&buf[..n])?;
+                stdout.flush()?;
+                idle = false;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e).with_context(|| "读取远程输出失败"),
+        }
+        match channel.stderr().read(&mut buf) {
+            Ok(0) => {}
+            Ok(n) => {
+                stderr.write_all(&buf[..n])?;
+                idle = false;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e).with_context(|| "读取远程错误输出失败"),
+        }
+
+        if idle {
+            if channel.eof() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// 只读会话丢弃要发送到远程的输入，只保留转义命令，返回是否丢弃了输入
+fn drop_read_only_input(events: &mut Vec<EscapeEvent>) -> bool {
+    let count = events.len();
+    events.retain(|event| !matches!(event, EscapeEvent::Data(_)));
+    events.len() != count
+}
+
+/// 非阻塞模式下写入全部数据，遇到 WouldBlock 时稍后重试
+/// 会话空闲时发送一次防空闲内容；非阻塞会话暂时无法发送时跳过，等下一个间隔
+fn send_anti_idle(session: &Session, channel: &mut ssh2::Channel, method: AntiIdleMethod) -> io::Result<()> {
+    let result = match method {
+        AntiIdleMethod::Keepalive => session.keepalive_send().map(|_| ()).map_err(io::Error::from),
+        AntiIdleMethod::Resize => {
+            let (width, height) = terminal_size();
+            channel.request_pty_size(width as u32, height as u32, None, None).map_err(io::Error::from)
+        },
+        AntiIdleMethod::Nul => write_all_nonblocking(channel, &[0]),
+    };
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        result => result,
+    }
+}
+
+/// 把远程输出交给输出触发器：响铃追加到 `output`，自动回答发送到远程（只读会话不发送）
+fn apply_triggers(triggers: Option<&mut Triggers>, output: &mut Vec<u8>, channel: &mut ssh2::Channel, read_only: bool) -> io::Result<()> {
+    let Some(triggers) = triggers.filter(|_| !output.is_empty()) else {
+        return Ok(());
+    };
+    for action in triggers.feed(output) {
+        match action {
+            Action::Bell => output.push(0x07),
+            Action::Notify { target, title, body } => triggers::notify(&target, &title, &body),
+            Action::Send(data) if !read_only => write_all_nonblocking(channel, &data)?,
+            Action::Send(_) => {},
+        }
+    }
+    Ok(())
+}
+
+fn write_all_nonblocking<W: Write>(writer: &mut W, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        match writer.write(data) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(1)),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+enum ForwardListener {
+    Local(TcpListener),
+    Remote(ssh2::Listener),
+}
+
+/// 会话运行中通过 `~C` 添加的端口转发
+struct RuntimeForward {
+    spec: Forward,
+    listener: ForwardListener,
+    conns: Vec<(TcpStream, ssh2::Channel)>,
+}
+
+impl RuntimeForward {
+    fn open(session: &Session, spec: Forward) -> Result<Self> {
+        let listener = match spec.kind {
+            ForwardKind::Local => {
+                let listener = TcpListener::bind(spec.listen_address())
+                    .with_context(|| format!("无法监听 {}", spec.listen_address()))?;
+                listener.set_nonblocking(true)?;
+                ForwardListener::Local(listener)
+            },
+            ForwardKind::Remote => {
+                session.set_blocking(true);
+                let listener = session.channel_forward_listen(spec.port, Some(&spec.bind_address), None);
+                session.set_blocking(false);
+                let (listener, _) = listener
+                    .with_context(|| format!("服务器拒绝在 {} 上监听", spec.listen_address()))?;
+                ForwardListener::Remote(listener)
+            },
+        };
+        Ok(RuntimeForward { spec, listener, conns: Vec::new() })
+    }
+
+    /// 接受新连接，并在本地连接和远程通道之间搬运数据；调用时会话处于非阻塞模式
+    fn poll(&mut self, session: &Session) {
+        let accepted = match &mut self.listener {
+            ForwardListener::Local(listener) => listener.accept().ok().map(|(stream, _)| {
+                // 打开通道需要等待服务器响应，临时切回阻塞模式
+                session.set_blocking(true);
+                let channel = session.channel_direct_tcpip(&self.spec.host, self.spec.host_port, None);
+                session.set_blocking(false);
+                channel.map(|channel| (stream, channel)).map_err(anyhow::Error::from)
+            }),
+            ForwardListener::Remote(listener) => listener.accept().ok().map(|channel| {
+                TcpStream::connect((self.spec.host.as_str(), self.spec.host_port))
+                    .map(|stream| (stream, channel))
+                    .map_err(anyhow::Error::from)
+            }),
+        };
+        match accepted {
+            Some(Ok((stream, channel))) if stream.set_nonblocking(true).is_ok() => self.conns.push((stream, channel)),
+            Some(Err(e)) => print!("\r\n转发 {} 失败: {}\r\n", self.spec, e),
+            _ => {},
+        }
+
+        let mut buf = [0u8; 16384];
+        self.conns.retain_mut(|(stream, channel)| {
+            let open = match stream.read(&mut buf) {
+                Ok(0) => false,
+                Ok(n) => write_all_nonblocking(channel, &buf[..n]).is_ok(),
+                Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+            };
+            let open = open
+                && match channel.read(&mut buf) {
+                    Ok(0) => false,
+                    Ok(n) => write_all_nonblocking(stream, &buf[..n]).is_ok(),
+                    Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+                };
+            if !open {
+                let _ = channel.close();
+            }
+            open
+        });
+    }
+}
+
+/// `~C` 命令行：读取一行命令并添加或取消端口转发，调用时终端处于普通模式
+fn run_escape_command(session: &Session, forwards: &mut Vec<RuntimeForward>) {
+    print!("\nssh> ");
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line).is_err() || line.trim().is_empty() {
+        return;
+    }
+
+    match parse_command_line(&line) {
+        Ok(EscapeCommand::AddForward(spec)) => match RuntimeForward::open(session, spec) {
+            Ok(forward) => {
+                println!("已添加端口转发 {}", forward.spec);
+                forwards.push(forward);
+            },
+            Err(e) => println!("{:#}", e),
+        },
+        Ok(EscapeCommand::CancelForward(kind, address)) => {
+            let before = forwards.len();
+            // 取消远程监听需要等待服务器响应
+            session.set_blocking(true);
+            forwards.retain(|f| f.spec.kind != kind || f.spec.listen_address() != address);
+            session.set_blocking(false);
+            if forwards.len() < before {
+                println!("已取消端口转发 {}", address);
+            } else {
+                println!("没有监听在 {} 的端口转发", address);
+            }
+        },
+        Err(e) => println!("{}", e),
     }
 }
 
@@ -409,4 +1380,28 @@ pub fn terminal_size() -> (usize, usize) {
     {
         (80, 24) // 非Unix系统使用默认值
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_sessions_drop_input_but_keep_escapes() {
+        let mut escapes = EscapeParser::default();
+        let mut events = escapes.feed(b"rm -rf /\r~?");
+        assert!(drop_read_only_input(&mut events));
+        assert_eq!(events, [EscapeEvent::Action(EscapeAction::Help)]);
+
+        // 不在行首的 ~. 是普通输入，同样丢弃
+        let mut events = escapes.feed(b"x~.");
+        assert!(drop_read_only_input(&mut events));
+        assert!(events.is_empty());
+
+        let mut events = escapes.feed(b"\r~.");
+        assert!(drop_read_only_input(&mut events));
+        assert_eq!(events, [EscapeEvent::Action(EscapeAction::Disconnect)]);
+
+        let mut events = escapes.feed(b"");
+        assert!(!drop_read_only_input(&mut events));
+    }
+}
@@ -0,0 +1,344 @@
+//! 系统 ssh 命令行的统一构造。
+//!
+//! expect 脚本、`rssh info` 展示的命令、tmux/kitty/wezterm 会话里执行的命令以及 rsync 的 `-e`
+//! 都由这里生成，端口、密钥和服务器级别的 `-o` 选项只在一处处理，
+//! 再按使用场景转义成 shell 命令行或 expect（Tcl）的 `spawn` 参数。
+//! 也可以把这些选项写成一份临时 ssh_config，改用 `ssh -F <文件>` 执行，命令行更短，也便于复现。
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::process::Command;
+
+use crate::models::{AuthType, ServerConfig};
+use crate::utils::ssh_config::expand_tilde;
+
+/// 一条系统 ssh 命令：程序、目标之前的参数、`user@host` 和远程命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshCommand {
+    /// 服务器名称，用于生成的 ssh_config 文件名
+    name: String,
+    program: Vec<String>,
+    options: Vec<String>,
+    destination: String,
+    command: Option<String>,
+}
+
+impl SshCommand {
+    /// 按服务器配置生成：非 22 端口加 `-p`，密钥认证加 `-i`（展开 `~`），再加上 `ServerConfig::ssh_options`
+    /// （其中的跳板机写成 `-J user@host:port`）和本次连接的 `-L` 转发
+    pub fn new(server: &ServerConfig) -> Self {
+        let mut options = Vec::new();
+        if server.port != 22 {
+            options.push("-p".to_string());
+            options.push(server.port.to_string());
+        }
+        if let AuthType::Key(key_path) = &server.auth_type {
+            options.push("-i".to_string());
+            options.push(expand_tilde(key_path));
+        }
+        for option in server.ssh_options() {
+            match option.strip_prefix("ProxyJump=") {
+                Some(jump) if options.last().is_some_and(|last| last == "-o") => {
+                    options.pop();
+                    options.push("-J".to_string());
+                    options.push(jump.to_string());
+                },
+                _ => options.push(option),
+            }
+        }
+        for forward in &server.local_forwards {
+            options.push("-L".to_string());
+            options.push(format!("{}:{}:{}", forward.listen_address(), forward.host, forward.host_port));
+        }
+        SshCommand {
+            name: server.name.clone(),
+            program: vec!["ssh".to_string()],
+            options,
+            destination: format!("{}@{}", server.username, server.host),
+            command: None,
+        }
+    }
+
+    /// 替换执行的程序，例如 `["kitten", "ssh"]` 或 ssh 的完整路径
+    pub fn program<S: AsRef<str>>(mut self, program: &[S]) -> Self {
+        self.program = program.iter().map(|part| part.as_ref().to_string()).collect();
+        self
+    }
+
+    /// 追加一个放在目标之前的参数，例如 `-t`
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.options.push(arg.into());
+        self
+    }
+
+    /// 追加 `-o key=value`，例如经跳板机连接时的 `ProxyJump`
+    pub fn option(self, key: &str, value: &str) -> Self {
+        self.arg("-o").arg(format!("{}={}", key, value))
+    }
+
+    /// 复用同一台服务器的连接（ControlMaster），`control_path` 可以包含 `%C` 等 ssh 占位符
+    pub fn control_master(self, control_path: &Path) -> Self {
+        self.option("ControlMaster", "auto")
+            .option("ControlPersist", "60")
+            .option("ControlPath", &control_path.to_string_lossy())
+    }
+
+    /// 把 `-p`、`-i`、`-J`、`-L` 和 `-o` 选项写成 ssh_config 格式，其余参数（如 `-t`）不包含在内。
+    /// 选项放在 `Host <主机>` 下，不会作用到跳板机的连接上；末尾 Include 用户和系统的配置，
+    /// 与直接传 `-o` 时一样，这里的选项优先
+    pub fn ssh_config(&self) -> String {
+        let host = self.destination.rsplit_once('@').map_or(self.destination.as_str(), |(_, host)| host);
+        let mut config = format!("# rssh 为 {} ({}) 生成的连接配置\nHost {}\n", self.name, self.destination, host);
+        let mut options = self.options.iter();
+        while let Some(option) = options.next() {
+            let line = match option.as_str() {
+                "-p" => options.next().map(|port| format!("Port {}", port)),
+                "-i" => options.next().map(|key| format!("IdentityFile {}", config_quote(key))),
+                "-J" => options.next().map(|jump| format!("ProxyJump {}", jump)),
+                // `[绑定地址:]端口:主机:主机端口` 的最后两段是转发目标
+                "-L" => options.next().and_then(|spec| {
+                    let mut parts = spec.rsplitn(3, ':');
+                    let (host_port, host, listen) = (parts.next()?, parts.next()?, parts.next()?);
+                    Some(format!("LocalForward {} {}:{}", listen, host, host_port))
+                }),
+                "-o" => options.next().map(|kv| match kv.split_once('=') {
+                    Some((key, value)) => format!("{} {}", key, value),
+                    None => kv.clone(),
+                }),
+                _ => None,
+            };
+            if let Some(line) = line {
+                config.push_str("    ");
+                config.push_str(&line);
+                config.push('\n');
+            }
+        }
+        config.push_str("\nHost *\n    Include ~/.ssh/config\n    Include /etc/ssh/ssh_config\n");
+        config
+    }
+
+    /// 把选项写入 `dir` 下的 ssh_config，改为 `ssh -F <文件>` 执行。
+    /// 文件名包含内容的哈希，同样的选项总是得到同一个文件，打印出的命令可以直接复制执行
+    pub fn with_config_file(mut self, dir: &Path) -> Result<Self> {
+        let config = self.ssh_config();
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        config.hash(&mut hasher);
+        let file_name: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}-{:08x}.conf", file_name, hasher.finish() as u32));
+
+        if !path.exists() {
+            std::fs::create_dir_all(dir).with_context(|| format!("无法创建目录 {}", dir.display()))?;
+            // 先写临时文件再改名，并发连接不会读到写了一半的配置
+            let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+            std::fs::write(&tmp, &config).with_context(|| format!("无法写入 {}", tmp.display()))?;
+            std::fs::rename(&tmp, &path).with_context(|| format!("无法写入 {}", path.display()))?;
+        }
+
+        let mut options = vec!["-F".to_string(), path.to_string_lossy().into_owned()];
+        let mut rest = self.options.iter();
+        while let Some(option) = rest.next() {
+            if matches!(option.as_str(), "-p" | "-i" | "-J" | "-L" | "-o") {
+                rest.next();
+            } else {
+                options.push(option.clone());
+            }
+        }
+        self.options = options;
+        Ok(self)
+    }
+
+    /// 在服务器上执行的命令，作为一个参数传给 ssh，由远程 shell 解释
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    /// 程序之后的全部参数，用于 `std::process::Command::args`
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.options.clone();
+        args.push(self.destination.clone());
+        args.extend(self.command.clone());
+        args
+    }
+
+    /// 可以直接执行的进程
+    pub fn to_command(&self) -> Command {
+        let mut command = Command::new(&self.program[0]);
+        command.args(&self.program[1..]).args(self.args());
+        command
+    }
+
+    /// 不含目标和远程命令的 ssh 命令行，用于 rsync 的 `-e`
+    pub fn remote_shell(&self) -> String {
+        join_shell(self.program.iter().chain(&self.options))
+    }
+
+    /// expect 脚本 `spawn` 之后的部分，按 Tcl 的规则转义
+    pub fn to_tcl(&self) -> String {
+        self.program
+            .iter()
+            .cloned()
+            .chain(self.args())
+            .map(|word| tcl_quote(&word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// 可以直接粘贴到 shell 中执行的命令行
+impl fmt::Display for SshCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&join_shell(self.program.iter().chain(&self.args())))
+    }
+}
+
+fn join_shell<'a>(words: impl Iterator<Item = &'a String>) -> String {
+    words
+        .map(|word| shell_quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// shell 转义；`user@host`、`ControlPath=.../%C` 这类常见参数保持原样，便于阅读
+fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || "@%+,-./:=_".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        shell_escape::escape(word.into()).into_owned()
+    }
+}
+
+/// ssh_config 中含空白的值需要加双引号
+fn config_quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// 转义为一个 Tcl 单词：空白和 Tcl 的特殊字符前加反斜杠
+fn tcl_quote(word: &str) -> String {
+    if word.is_empty() {
+        return "{}".to_string();
+    }
+    let mut quoted = String::with_capacity(word.len());
+    for c in word.chars() {
+        if c.is_whitespace() || "\\$[]{}\";".contains(c) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::session_escape::{Forward, ForwardKind};
+
+    #[test]
+    fn builds_and_escapes_ssh_commands() {
+        let mut server = ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 2222, "deploy".into(),
+            AuthType::Key("/keys/my key".into()), None, None, None,
+        );
+        let options = server.ssh_options().join(" ");
+
+        let command = SshCommand::new(&server).arg("-t").option("ProxyJump", "bastion").command("echo 'hi'; exec $SHELL");
+        assert_eq!(
+            command.args()[..4],
+            ["-p", "2222", "-i", "/keys/my key"]
+        );
+        assert_eq!(
+            command.to_string(),
+            format!("ssh -p 2222 -i '/keys/my key' {} -t -o ProxyJump=bastion deploy@10.0.0.1 'echo '\\''hi'\\''; exec $SHELL'", options)
+        );
+        assert_eq!(
+            command.to_tcl(),
+            format!("ssh -p 2222 -i /keys/my\\ key {} -t -o ProxyJump=bastion deploy@10.0.0.1 echo\\ 'hi'\\;\\ exec\\ \\$SHELL", options)
+        );
+        assert_eq!(command.remote_shell(), format!("ssh -p 2222 -i '/keys/my key' {} -t -o ProxyJump=bastion", options));
+
+        // 默认端口不加 -p
+        server.port = 22;
+        server.auth_type = AuthType::Agent;
+        let command = SshCommand::new(&server).program(&["kitten", "ssh"]);
+        assert_eq!(command.to_string(), format!("kitten ssh {} deploy@10.0.0.1", options));
+
+        // 写成 ssh_config 后只保留 -F 和其他参数，相同的选项复用同一个文件
+        server.auth_type = AuthType::Key("/keys/my key".into());
+        let dir = tempfile::tempdir().unwrap();
+        let command = SshCommand::new(&server).arg("-t").option("ProxyJump", "bastion").command("uptime");
+        let config = command.ssh_config();
+        assert!(config.contains("Host 10.0.0.1\n    IdentityFile \"/keys/my key\"\n    StrictHostKeyChecking accept-new\n"));
+        assert!(config.contains("    ProxyJump bastion\n\nHost *\n    Include ~/.ssh/config\n"));
+        assert!(!config.contains("Port"));
+
+        let with_file = command.clone().with_config_file(dir.path()).unwrap();
+        let path = with_file.args()[1].clone();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), config);
+        assert_eq!(with_file.args(), ["-F", path.as_str(), "-t", "deploy@10.0.0.1", "uptime"]);
+        assert_eq!(command.with_config_file(dir.path()).unwrap().args()[1], path);
+
+        // 本次连接的 -L 转发放在服务器选项之后，写成 ssh_config 时为 LocalForward
+        server.local_forwards = vec![Forward::parse(ForwardKind::Local, "8080:localhost:80").unwrap()];
+        let command = SshCommand::new(&server);
+        assert!(command.to_string().ends_with(" -L 127.0.0.1:8080:localhost:80 deploy@10.0.0.1"));
+        assert!(command.ssh_config().contains("    LocalForward 127.0.0.1:8080 localhost:80\n"));
+        assert!(!command.with_config_file(dir.path()).unwrap().args().contains(&"-L".to_string()));
+
+        // 经已保存的跳板服务器连接时写成 -J，多跳用逗号分隔
+        server.local_forwards.clear();
+        let mut bastion = ServerConfig::new(
+            "2".into(), "bastion".into(), "203.0.113.1".into(), 2222, "ops".into(),
+            AuthType::Agent, None, None, None,
+        );
+        bastion.jump_host = Some("edge.example.com".into());
+        let inner = ServerConfig::new(
+            "3".into(), "inner".into(), "10.0.0.254".into(), 22, "ops".into(),
+            AuthType::Agent, None, None, None,
+        );
+        server.jump_servers = vec![bastion, inner];
+        let command = SshCommand::new(&server);
+        assert!(command.to_string().contains(" -J edge.example.com,ops@203.0.113.1:2222,ops@10.0.0.254:22 "));
+        assert!(!command.to_string().contains("ProxyJump"));
+        assert!(command.ssh_config().contains("    ProxyJump edge.example.com,ops@203.0.113.1:2222,ops@10.0.0.254:22\n"));
+        assert!(!command.with_config_file(dir.path()).unwrap().args().contains(&"-J".to_string()));
+    }
+
+    #[test]
+    fn names_config_files_by_server_and_content_hash() {
+        let mut server = ServerConfig::new(
+            "1".into(), "web 1/prod".into(), "10.0.0.1".into(), 22, "deploy".into(),
+            AuthType::Agent, None, None, None,
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let file_name = |server: &ServerConfig| {
+            let command = SshCommand::new(server).with_config_file(dir.path()).unwrap();
+            Path::new(&command.args()[1]).file_name().unwrap().to_string_lossy().into_owned()
+        };
+
+        // 服务器名称中的特殊字符替换为 `_`，后面是 8 位十六进制的内容哈希
+        let default_port = file_name(&server);
+        let hash = default_port.strip_prefix("web_1_prod-").unwrap().strip_suffix(".conf").unwrap();
+        assert!(hash.len() == 8 && hash.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(file_name(&server), default_port);
+
+        // 选项不同时写入另一个文件，已有的文件不变
+        server.port = 2222;
+        let custom_port = file_name(&server);
+        assert_ne!(custom_port, default_port);
+        assert!(std::fs::read_to_string(dir.path().join(&custom_port)).unwrap().contains("    Port 2222\n"));
+        assert!(!std::fs::read_to_string(dir.path().join(&default_port)).unwrap().contains("Port"));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -4,7 +4,7 @@ use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
-use crate::models::{AuthType, ServerConfig};
+use crate::models::{Algorithms, AuthType, ForwardAgent, HostKeyPolicy, LoginBanner, ServerConfig};
 
 /// 将包含波浪号的路径扩展为完整路径
 pub fn expand_tilde(path: &str) -> String {
@@ -53,20 +53,42 @@ pub fn sanitize_host_alias(name: &str) -> String {
 
 pub struct SshConfigEntry {
     pub host: String,
+    /// 同一 `Host` 行上的其它名称，导入为别名
+    pub aliases: Vec<String>,
     pub hostname: Option<String>,
     pub port: Option<u16>,
     pub user: Option<String>,
     pub identity_file: Option<String>,
+    pub compression: bool,
+    pub algorithms: Algorithms,
+    pub host_key_policy: Option<HostKeyPolicy>,
+    pub login_banner: LoginBanner,
+    pub forward_agent: ForwardAgent,
+    /// `ProxyJump`，导入时指向同一文件中的 Host 则引用该服务器，否则原样作为跳板机
+    pub proxy_jump: Option<String>,
+    pub proxy_command: Option<String>,
+    /// 其它未单独处理的选项（`Key=Value`），原样传给系统 ssh
+    pub extra_options: Vec<String>,
 }
 
 impl SshConfigEntry {
     pub fn new(host: &str) -> Self {
+        let mut names = host.split_whitespace().map(str::to_string);
         SshConfigEntry {
-            host: host.to_string(),
+            host: names.next().unwrap_or_default(),
+            aliases: names.collect(),
             hostname: None,
             port: None,
             user: None,
             identity_file: None,
+            compression: false,
+            algorithms: Algorithms::default(),
+            host_key_policy: None,
+            login_banner: LoginBanner::Show,
+            forward_agent: ForwardAgent::No,
+            proxy_jump: None,
+            proxy_command: None,
+            extra_options: Vec::new(),
         }
     }
 
@@ -91,7 +113,7 @@ impl SshConfigEntry {
             AuthType::Agent
         };
         
-        Some(ServerConfig::new(
+        let mut server = ServerConfig::new(
             Uuid::new_v4().to_string(),
             self.host.clone(),
             hostname,
@@ -101,7 +123,17 @@ impl SshConfigEntry {
             None,
             None,
             None,
-        ))
+        );
+        server.compression = self.compression;
+        server.algorithms = self.algorithms.clone();
+        server.host_key_policy = self.host_key_policy;
+        server.login_banner = self.login_banner;
+        server.forward_agent = self.forward_agent;
+        server.aliases = self.aliases.clone();
+        server.jump_host = self.proxy_jump.clone();
+        server.proxy_command = self.proxy_command.clone();
+        server.extra_options = self.extra_options.clone();
+        Some(server)
     }
 }
 
@@ -123,24 +155,27 @@ pub fn parse_ssh_config<P: AsRef<Path>>(path: P) -> Result<Vec<SshConfigEntry>>
             continue;
         }
         
-        // 将行分割为键和值
-        let parts: Vec<&str> = line.splitn(2, ' ').collect();
-        
-        if parts.len() < 2 {
+        // 将行分割为键和值，键和值之间可以是空白或 `=`
+        let Some((name, value)) = line.split_once(|c: char| c.is_whitespace() || c == '=') else {
+            continue;
+        };
+        let value = value.trim_start_matches(|c: char| c.is_whitespace() || c == '=').trim();
+        if value.is_empty() {
             continue;
         }
         
-        let key = parts[0].trim().to_lowercase();
-        let value = parts[1].trim();
+        let key = name.to_lowercase();
         
-        if key == "host" && !value.contains('*') {
+        if key == "host" {
             // 如果有当前条目，则将其添加到结果中
-            if let Some(entry) = current_entry {
+            if let Some(entry) = current_entry.take() {
                 entries.push(entry);
             }
             
-            // 创建新条目
-            current_entry = Some(SshConfigEntry::new(value));
+            // 创建新条目；通配的 Host 块不是具体的服务器，其中的选项也不归入上一个条目
+            if !value.contains('*') {
+                current_entry = Some(SshConfigEntry::new(value));
+            }
         } else if let Some(ref mut entry) = current_entry {
             // 更新当前条目
             match key.as_str() {
@@ -152,7 +187,29 @@ pub fn parse_ssh_config<P: AsRef<Path>>(path: P) -> Result<Vec<SshConfigEntry>>
                 },
                 "user" => entry.user = Some(value.to_string()),
                 "identityfile" => entry.identity_file = Some(value.to_string()),
-                _ => {},
+                "compression" => entry.compression = value.eq_ignore_ascii_case("yes"),
+                "ciphers" => entry.algorithms.ciphers = Some(value.to_string()),
+                "kexalgorithms" => entry.algorithms.kex = Some(value.to_string()),
+                "macs" => entry.algorithms.macs = Some(value.to_string()),
+                "hostkeyalgorithms" => entry.algorithms.host_key = Some(value.to_string()),
+                "stricthostkeychecking" => entry.host_key_policy = HostKeyPolicy::parse(&value.to_lowercase()).ok(),
+                "loglevel" => {
+                    if matches!(value.to_lowercase().as_str(), "quiet" | "fatal" | "error") {
+                        entry.login_banner = LoginBanner::Quiet;
+                    }
+                }
+                "forwardagent" => {
+                    if value.eq_ignore_ascii_case("yes") {
+                        entry.forward_agent = ForwardAgent::Yes;
+                    }
+                }
+                // none 表示不使用跳板
+                "proxyjump" if !value.eq_ignore_ascii_case("none") => entry.proxy_jump = Some(value.to_string()),
+                "proxycommand" if !value.eq_ignore_ascii_case("none") => entry.proxy_command = Some(value.to_string()),
+                "proxyjump" | "proxycommand" => {},
+                // Match 块和 Include 的文件不逐个展开
+                "match" | "include" => {},
+                _ => entry.extra_options.push(format!("{}={}", name, value)),
             }
         }
     }
@@ -165,14 +222,32 @@ pub fn parse_ssh_config<P: AsRef<Path>>(path: P) -> Result<Vec<SshConfigEntry>>
     Ok(entries)
 }
 
+/// 导入 ssh config 中的主机。`ProxyJump` 是同一文件中另一个导入的 Host（名称或别名）时，
+/// 改为引用该服务器（`proxy_jump`），内置库模式也能逐跳用它保存的认证方式连接
 pub fn import_ssh_config<P: AsRef<Path>>(path: P) -> Result<Vec<ServerConfig>> {
     let entries = parse_ssh_config(path)?;
 
-    let configs: Vec<ServerConfig> = entries
+    let mut configs: Vec<ServerConfig> = entries
         .iter()
         .filter_map(|entry| entry.to_server_config())
         .collect();
 
+    let names: Vec<(String, String)> = configs
+        .iter()
+        .flat_map(|server| std::iter::once(&server.name).chain(&server.aliases).map(|name| (name.clone(), server.name.clone())))
+        .collect();
+    for server in &mut configs {
+        let linked = server
+            .jump_host
+            .as_ref()
+            .and_then(|jump| names.iter().find(|(name, _)| name == jump))
+            .map(|(_, target)| target.clone());
+        if let Some(target) = linked.filter(|target| *target != server.name) {
+            server.proxy_jump = Some(target);
+            server.jump_host = None;
+        }
+    }
+
     Ok(configs)
 }
 
@@ -191,4 +266,77 @@ mod tests {
     fn falls_back_for_empty_name() {
         assert_eq!(sanitize_host_alias("   "), "unnamed");
     }
+
+    #[test]
+    fn extra_host_names_become_aliases() {
+        let mut entry = SshConfigEntry::new("prod-database-01  db1 pdb");
+        entry.hostname = Some("10.0.0.5".into());
+        let server = entry.to_server_config().unwrap();
+        assert_eq!(server.name, "prod-database-01");
+        assert_eq!(server.aliases, ["db1", "pdb"]);
+    }
+
+    #[test]
+    fn imports_compression_and_passes_it_to_ssh() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        std::fs::write(&path, "Host slow\n    HostName 10.0.0.8\n    Compression yes\n\nHost fast\n    HostName 10.0.0.9\n    Compression no\n").unwrap();
+
+        let servers = import_ssh_config(&path).unwrap();
+        let slow = servers.iter().find(|s| s.name == "slow").unwrap();
+        let fast = servers.iter().find(|s| s.name == "fast").unwrap();
+        assert!(slow.compression);
+        assert!(slow.ssh_options().contains(&"Compression=yes".to_string()));
+        assert!(!fast.compression);
+        assert!(!fast.ssh_options().iter().any(|option| option.starts_with("Compression")));
+    }
+
+    #[test]
+    fn imports_bastion_setups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        std::fs::write(&path, "\
+Host bastion jump
+    HostName bastion.example.com
+    User ops
+
+Host db1
+    HostName 10.0.0.5
+    ProxyJump jump
+    ForwardAgent yes
+    ServerAliveInterval=30
+
+Host legacy
+    HostName 10.0.0.6
+    ProxyCommand ssh -W %h:%p gateway.example.com
+
+Host external
+    HostName 10.0.0.7
+    ProxyJump ops@edge.example.com:2222
+    ProxyCommand none
+
+Host *
+    ServerAliveInterval 60
+").unwrap();
+
+        let servers = import_ssh_config(&path).unwrap();
+        let find = |name: &str| servers.iter().find(|s| s.name == name).unwrap();
+
+        let db1 = find("db1");
+        assert_eq!(db1.proxy_jump.as_deref(), Some("bastion"));
+        assert_eq!(db1.jump_host, None);
+        assert_eq!(db1.forward_agent, ForwardAgent::Yes);
+        assert_eq!(db1.extra_options, ["ServerAliveInterval=30"]);
+        assert!(db1.ssh_options().contains(&"ServerAliveInterval=30".to_string()));
+
+        let legacy = find("legacy");
+        assert_eq!(legacy.proxy_command.as_deref(), Some("ssh -W %h:%p gateway.example.com"));
+        assert_eq!(legacy.expanded_proxy_command().as_deref(), Some("ssh -W 10.0.0.6:22 gateway.example.com"));
+        assert!(legacy.ssh_options().contains(&"ProxyCommand=ssh -W %h:%p gateway.example.com".to_string()));
+
+        let external = find("external");
+        assert_eq!(external.jump_host.as_deref(), Some("ops@edge.example.com:2222"));
+        assert_eq!((external.proxy_jump.as_ref(), external.proxy_command.as_ref()), (None, None));
+        assert!(external.extra_options.is_empty());
+    }
 } 
\ No newline at end of file
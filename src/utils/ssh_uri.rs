@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+
+use crate::models::{AuthType, ServerConfig};
+
+const SCHEME: &str = "ssh://";
+
+/// 判断字符串是否为 ssh:// URI
+pub fn is_ssh_uri(value: &str) -> bool {
+    value.len() > SCHEME.len() && value[..SCHEME.len()].eq_ignore_ascii_case(SCHEME)
+}
+
+/// 生成服务器的 ssh:// URI（不包含任何凭据），默认端口时省略端口
+pub fn format_ssh_uri(server: &ServerConfig) -> String {
+    let host = if server.host.contains(':') {
+        // IPv6 地址需要加方括号
+        format!("[{}]", server.host)
+    } else {
+        server.host.clone()
+    };
+
+    let mut uri = format!("{}{}@{}", SCHEME, percent_encode(&server.username), host);
+    if server.port != 22 {
+        uri.push_str(&format!(":{}", server.port));
+    }
+    uri
+}
+
+/// 解析 `ssh://[user[;参数]@]host[:port][/]`，返回 (用户名, 主机, 端口)。
+///
+/// 未写用户名时使用当前系统用户，与 ssh 命令行的行为一致。
+pub fn parse_ssh_uri(uri: &str) -> Result<(String, String, u16)> {
+    if !is_ssh_uri(uri) {
+        return Err(anyhow::anyhow!("不是有效的 ssh:// URI: {}", uri));
+    }
+
+    let rest = uri[SCHEME.len()..].trim_end_matches('/');
+    let (userinfo, hostport) = match rest.rsplit_once('@') {
+        Some((user, hp)) => (Some(user), hp),
+        None => (None, rest),
+    };
+
+    // RFC 草案允许在用户名后附加 `;fingerprint=...` 等参数，这里忽略
+    let username = match userinfo.map(|u| u.split(';').next().unwrap_or_default()) {
+        Some(user) if !user.is_empty() => percent_decode(user)?,
+        _ => std::env::var("USER").unwrap_or_else(|_| "root".to_string()),
+    };
+
+    let (host, port) = if let Some(stripped) = hostport.strip_prefix('[') {
+        let (host, after) = stripped
+            .split_once(']')
+            .ok_or_else(|| anyhow::anyhow!("URI 中的 IPv6 地址缺少 ']': {}", uri))?;
+        (host.to_string(), after.strip_prefix(':'))
+    } else {
+        match hostport.rsplit_once(':') {
+            Some((host, port)) => (host.to_string(), Some(port)),
+            None => (hostport.to_string(), None),
+        }
+    };
+
+    if host.is_empty() {
+        return Err(anyhow::anyhow!("URI 中缺少主机名: {}", uri));
+    }
+
+    let port = match port {
+        Some(p) if !p.is_empty() => p
+            .parse::<u16>()
+            .with_context(|| format!("URI 中的端口无效: {}", p))?,
+        _ => 22,
+    };
+
+    Ok((username, host, port))
+}
+
+/// 由 URI 构造一个临时服务器配置（不写入数据库），使用 SSH Agent / 默认密钥认证。
+///
+/// ID 即 URI 本身，这样 `connect --new-window` 在子进程中可以原样再次解析。
+pub fn server_from_uri(uri: &str, name: Option<&str>) -> Result<ServerConfig> {
+    let (username, host, port) = parse_ssh_uri(uri)?;
+    let name = name.map(str::to_string).unwrap_or_else(|| host.clone());
+
+    Ok(ServerConfig::new(
+        uri.to_string(),
+        name,
+        host,
+        port,
+        username,
+        AuthType::Agent,
+        None,
+        None,
+        None,
+    ))
+}
+
+/// 解析 URI 列表：每行一个 URI，可在其后以空白分隔跟上服务器名称；
+/// 空行和 `#` 开头的注释行会被忽略。返回 (URI, 名称)。
+pub fn parse_uri_list(content: &str) -> Result<Vec<(String, Option<String>)>> {
+    let mut entries = Vec::new();
+
+    for (lineno, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let uri = parts.next().unwrap_or_default();
+        let name = parts.next().map(str::trim).filter(|n| !n.is_empty());
+
+        // 提前校验，出错时指出行号
+        parse_ssh_uri(uri).with_context(|| format!("第 {} 行解析失败", lineno + 1))?;
+        entries.push((uri.to_string(), name.map(str::to_string)));
+    }
+
+    Ok(entries)
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn percent_decode(value: &str) -> Result<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3])?;
+            out.push(u8::from_str_radix(hex, 16).with_context(|| format!("URI 中的转义无效: %{}", hex))?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).with_context(|| "URI 中的用户名不是有效的UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_uri() {
+        assert_eq!(
+            parse_ssh_uri("ssh://deploy@example.com:2222").unwrap(),
+            ("deploy".to_string(), "example.com".to_string(), 2222)
+        );
+        assert_eq!(
+            parse_ssh_uri("SSH://root@[::1]/").unwrap(),
+            ("root".to_string(), "::1".to_string(), 22)
+        );
+        assert_eq!(
+            parse_ssh_uri("ssh://a%40b;fingerprint=x@h").unwrap(),
+            ("a@b".to_string(), "h".to_string(), 22)
+        );
+        assert!(parse_ssh_uri("ssh://u@host:notaport").is_err());
+        assert!(parse_ssh_uri("myserver").is_err());
+    }
+
+    #[test]
+    fn format_roundtrips() {
+        let server = server_from_uri("ssh://a%40b@[fe80::1]:2200", None).unwrap();
+        assert_eq!(server.username, "a@b");
+        assert_eq!(format_ssh_uri(&server), "ssh://a%40b@[fe80::1]:2200");
+
+        let server = server_from_uri("ssh://root@example.com:22", Some("web")).unwrap();
+        assert_eq!(server.name, "web");
+        assert_eq!(format_ssh_uri(&server), "ssh://root@example.com");
+    }
+
+    #[test]
+    fn parses_uri_list_with_names_and_comments() {
+        let list = "# 机房A\nssh://root@10.0.0.1 db-1\n\nssh://10.0.0.2:2022\n";
+        let entries = parse_uri_list(list).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], ("ssh://root@10.0.0.1".to_string(), Some("db-1".to_string())));
+        assert_eq!(entries[1].1, None);
+        assert!(parse_uri_list("ssh://h:x").is_err());
+    }
+}
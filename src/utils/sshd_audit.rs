@@ -0,0 +1,235 @@
+use colored::*;
+use std::collections::BTreeMap;
+
+/// 输出以这一行开头时表示读取的是 sshd_config 文件而不是 `sshd -T` 的生效配置
+const FILE_MARKER: &str = "# rssh: sshd_config";
+
+/// 在服务器上获取 sshd 配置：优先 `sshd -T`（生效配置，通常需要 root），失败时读取 sshd_config 文件
+pub fn fetch_command() -> String {
+    let script = format!(
+        r#"sshd=$(command -v sshd || echo /usr/sbin/sshd)
+"$sshd" -T 2>/dev/null || sudo -n "$sshd" -T 2>/dev/null && exit 0
+echo '{FILE_MARKER}'
+cat /etc/ssh/sshd_config 2>/dev/null || sudo -n cat /etc/ssh/sshd_config"#
+    );
+    format!("sh -c {}", shell_escape::escape(script.into()))
+}
+
+/// 配置来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// `sshd -T` 输出的生效配置
+    Effective,
+    /// 直接解析的 sshd_config，不含 Include 的文件和 Match 块
+    File,
+}
+
+/// 解析后的 sshd 配置，键为小写的选项名
+#[derive(Debug, Clone)]
+pub struct SshdConfig {
+    pub source: Source,
+    pub options: BTreeMap<String, String>,
+}
+
+impl SshdConfig {
+    /// 解析 `fetch_command` 的输出。与 sshd 相同，同一选项以第一次出现的值为准，遇到 Match 块停止
+    pub fn parse(output: &str) -> Self {
+        let source = if output.trim_start().starts_with(FILE_MARKER) { Source::File } else { Source::Effective };
+        let mut options = BTreeMap::new();
+        for line in output.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(|c: char| c.is_whitespace() || c == '=')
+                .unwrap_or((line, ""));
+            let key = key.to_lowercase();
+            if key == "match" {
+                break;
+            }
+            let value = value.trim_start_matches(|c: char| c.is_whitespace() || c == '=').trim();
+            options.entry(key).or_insert_with(|| value.to_string());
+        }
+
+        // 文件中未设置的选项按 OpenSSH 默认值计算；算法列表的默认值随版本变化，不做推断
+        if source == Source::File {
+            for (key, value) in [
+                ("passwordauthentication", "yes"),
+                ("permitrootlogin", "prohibit-password"),
+                ("permitemptypasswords", "no"),
+                ("permituserenvironment", "no"),
+                ("x11forwarding", "no"),
+                ("maxauthtries", "6"),
+            ] {
+                options.entry(key.to_string()).or_insert_with(|| value.to_string());
+            }
+        }
+        SshdConfig { source, options }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str)
+    }
+
+    fn is_yes(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|value| value.eq_ignore_ascii_case("yes"))
+    }
+}
+
+/// 问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    High,
+    Medium,
+    Low,
+}
+
+impl Severity {
+    /// 每个问题从 100 分中扣除的分数
+    fn penalty(self) -> u32 {
+        match self {
+            Severity::High => 25,
+            Severity::Medium => 10,
+            Severity::Low => 5,
+        }
+    }
+
+    fn label(self) -> ColoredString {
+        match self {
+            Severity::High => "高".bright_red(),
+            Severity::Medium => "中".bright_yellow(),
+            Severity::Low => "低".bright_blue(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// 选项名及当前值，例如 `PasswordAuthentication yes`
+    pub setting: String,
+    pub message: String,
+}
+
+const WEAK_CIPHERS: &[&str] = &["3des-cbc", "aes128-cbc", "aes192-cbc", "aes256-cbc", "blowfish-cbc", "cast128-cbc", "arcfour", "arcfour128", "arcfour256", "rijndael-cbc@lysator.liu.se"];
+const WEAK_KEX: &[&str] = &["diffie-hellman-group1-sha1", "diffie-hellman-group14-sha1", "diffie-hellman-group-exchange-sha1"];
+
+/// MAC 算法是否较弱：MD5、SHA-1、RIPEMD-160、64 位 UMAC 以及截断到 96 位的变体
+fn weak_mac(mac: &str) -> bool {
+    mac.starts_with("hmac-md5")
+        || mac.starts_with("hmac-sha1")
+        || mac.starts_with("hmac-ripemd160")
+        || mac.starts_with("umac-64")
+        || mac.contains("-96")
+}
+
+/// 检查有风险的配置，按严重程度排序
+pub fn audit(config: &SshdConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut flag = |severity, key: &str, name: &str, message: &str| {
+        let value = config.get(key).unwrap_or_default();
+        findings.push(Finding { severity, setting: format!("{} {}", name, value), message: message.to_string() });
+    };
+
+    if config.is_yes("passwordauthentication") {
+        flag(Severity::High, "passwordauthentication", "PasswordAuthentication", "允许密码登录，容易被暴力破解，建议只用公钥认证");
+    }
+    if config.is_yes("permitrootlogin") {
+        flag(Severity::High, "permitrootlogin", "PermitRootLogin", "允许 root 用密码登录，建议设为 prohibit-password 或 no");
+    }
+    if config.is_yes("permitemptypasswords") {
+        flag(Severity::High, "permitemptypasswords", "PermitEmptyPasswords", "允许空密码账号登录");
+    }
+    if config.is_yes("permituserenvironment") {
+        flag(Severity::Medium, "permituserenvironment", "PermitUserEnvironment", "用户可通过 ~/.ssh/environment 设置 LD_PRELOAD 等变量");
+    }
+
+    let weak_ciphers = |name: &str| WEAK_CIPHERS.contains(&name);
+    let weak_kex = |name: &str| WEAK_KEX.contains(&name);
+    let algorithms = [("ciphers", "Ciphers", &weak_ciphers as &dyn Fn(&str) -> bool), ("macs", "MACs", &weak_mac), ("kexalgorithms", "KexAlgorithms", &weak_kex)];
+    for (key, name, is_weak) in algorithms {
+        let Some(list) = config.get(key) else { continue };
+        let weak: Vec<&str> = list.split(',').map(str::trim).filter(|item| is_weak(item)).collect();
+        if !weak.is_empty() {
+            findings.push(Finding {
+                severity: Severity::Medium,
+                setting: format!("{} {}", name, weak.join(",")),
+                message: "启用了较弱的算法，建议从列表中移除".to_string(),
+            });
+        }
+    }
+
+    if config.is_yes("x11forwarding") {
+        findings.push(Finding {
+            severity: Severity::Low,
+            setting: "X11Forwarding yes".to_string(),
+            message: "服务器不需要图形界面时建议关闭".to_string(),
+        });
+    }
+    if let Some(tries) = config.get("maxauthtries").and_then(|value| value.parse::<u32>().ok()).filter(|&tries| tries > 6) {
+        findings.push(Finding {
+            severity: Severity::Low,
+            setting: format!("MaxAuthTries {}", tries),
+            message: "每个连接允许的认证次数过多，建议不超过 6".to_string(),
+        });
+    }
+
+    findings.sort_by_key(|finding| finding.severity);
+    findings
+}
+
+/// 满分 100，按问题的严重程度扣分
+pub fn score(findings: &[Finding]) -> u32 {
+    100u32.saturating_sub(findings.iter().map(|finding| finding.severity.penalty()).sum())
+}
+
+/// 打印一台主机的检查报告
+pub fn print_report(server_name: &str, config: &SshdConfig) {
+    let findings = audit(config);
+    let score = score(&findings);
+    let score_text = format!("{}/100", score);
+    let score_text = match score {
+        80.. => score_text.bright_green(),
+        50..=79 => score_text.bright_yellow(),
+        _ => score_text.bright_red(),
+    };
+    println!("{} {}", server_name.bold(), score_text);
+    if config.source == Source::File {
+        println!("  {}", "无法执行 sshd -T，仅检查了 /etc/ssh/sshd_config（不含 Include 的文件和 Match 块）".dimmed());
+    }
+    if findings.is_empty() {
+        println!("  {} 未发现风险配置", "✓".bright_green());
+    }
+    for finding in &findings {
+        println!("  [{}] {} — {}", finding.severity.label(), finding.setting.bright_white(), finding.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audits_sshd_settings() {
+        let config = SshdConfig::parse(
+            "# rssh: sshd_config\n\
+             PermitRootLogin yes\n\
+             PermitRootLogin no\n\
+             MACs hmac-sha2-256,hmac-md5,umac-64@openssh.com\n\
+             Match User backup\n\
+             PasswordAuthentication no\n",
+        );
+        assert_eq!(config.source, Source::File);
+        let findings = audit(&config);
+        let settings: Vec<&str> = findings.iter().map(|f| f.setting.as_str()).collect();
+        // 默认允许密码登录，Match 块里的设置不计入；同一选项以第一次出现为准
+        assert_eq!(settings, ["PasswordAuthentication yes", "PermitRootLogin yes", "MACs hmac-md5,umac-64@openssh.com"]);
+        assert_eq!(score(&findings), 40);
+
+        let effective = SshdConfig::parse("passwordauthentication no\npermitrootlogin without-password\nciphers chacha20-poly1305@openssh.com,aes256-gcm@openssh.com\n");
+        assert_eq!(effective.source, Source::Effective);
+        assert!(audit(&effective).is_empty());
+        assert_eq!(score(&[]), 100);
+    }
+}
@@ -0,0 +1,134 @@
+//! `rssh exec --sudo`：命令包装成 `sudo -S`，密码经通道的标准输入发送，
+//! 不出现在远程命令行（ps 可见）、审计日志和任务记录中。
+//! 远程只读取第一行交给 sudo，命令本身的标准输入为 /dev/null：sudo 不需要密码（NOPASSWD）时
+//! 密码也不会被命令读到。
+
+use anyhow::{Context, Result};
+use shell_escape::escape;
+use std::io::{self, IsTerminal, Write};
+
+use crate::models::{AuthType, ServerConfig};
+
+/// 输出中出现密码时替换成的内容
+const MASK: &str = "********";
+
+/// 各服务器的 sudo 密码：优先使用服务器保存的登录密码，没有保存的使用执行前输入的密码
+pub struct Sudo {
+    fallback: Option<String>,
+}
+
+impl Sudo {
+    /// 有服务器没有保存密码时在终端中输入一次，所有这些服务器共用
+    pub fn prepare(servers: &[ServerConfig]) -> Result<Self> {
+        let missing: Vec<&str> = servers
+            .iter()
+            .filter(|s| stored_password(s).is_none())
+            .map(|s| s.name.as_str())
+            .collect();
+        if missing.is_empty() {
+            return Ok(Sudo { fallback: None });
+        }
+        if !io::stdin().is_terminal() {
+            return Err(anyhow::anyhow!("{} 没有保存密码，--sudo 需要在终端中输入 sudo 密码", missing.join(", ")));
+        }
+
+        print!("{} 的 sudo 密码: ", missing.join(", "));
+        io::stdout().flush()?;
+        let password = rpassword::read_password().with_context(|| "无法读取 sudo 密码")?;
+        Ok(Sudo { fallback: Some(password) })
+    }
+
+    pub fn password_for<'a>(&'a self, server: &'a ServerConfig) -> Option<&'a str> {
+        stored_password(server).or(self.fallback.as_deref())
+    }
+}
+
+/// 服务器保存的登录密码：密码认证的密码，或密钥认证时设置的备用密码（需已解析密钥引用）
+fn stored_password(server: &ServerConfig) -> Option<&str> {
+    match (&server.auth_type, &server.password) {
+        (AuthType::Password(password), _) | (_, Some(password)) if !password.is_empty() => Some(password),
+        _ => None,
+    }
+}
+
+/// `-S` 从标准输入读取密码，`-p ''` 不输出提示，避免提示混进命令输出，`-k` 不使用之前缓存的认证。
+/// 外层的 sh 只把标准输入的第一行（`stdin_for` 的密码）交给 sudo，不受登录 shell 种类影响
+pub fn wrap_command(command: &str) -> String {
+    let inner = format!("exec </dev/null; {}", command);
+    let outer = format!(
+        "IFS= read -r password; printf '%s\\n' \"$password\" | sudo -k -S -p '' sh -c {}",
+        escape(inner.into())
+    );
+    format!("sh -c {}", escape(outer.into()))
+}
+
+/// 经标准输入发送给 sudo 的内容
+pub fn stdin_for(password: &str) -> Vec<u8> {
+    format!("{}\n", password).into_bytes()
+}
+
+/// 去掉输出中的密码（命令回显、sudo 出错时打印输入等情况），结果会写入任务记录
+pub fn scrub(output: &str, password: &str) -> String {
+    if password.is_empty() {
+        return output.to_string();
+    }
+    output.replace(password, MASK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 用本地的假 sudo 执行包装后的命令，返回命令的输出和 sudo 读到的密码；
+    /// `reads_password` 为假时模拟免密 sudo，不读取标准输入
+    fn run_wrapped(command: &str, reads_password: bool) -> (String, String) {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let received = dir.path().join("received");
+        let read = if reads_password { format!("IFS= read -r pw; printf %s \"$pw\" > {}", received.display()) } else { String::new() };
+        let sudo = dir.path().join("sudo");
+        std::fs::write(&sudo, format!("#!/bin/sh\n[ \"$1 $2 $3 $4\" = \"-k -S -p \" ] || exit 99\nshift 4\n{}\nexec \"$@\"\n", read)).unwrap();
+        std::fs::set_permissions(&sudo, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(wrap_command(command))
+            .env("PATH", format!("{}:{}", dir.path().display(), std::env::var("PATH").unwrap_or_default()))
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut input = stdin_for("hunter2");
+        input.extend_from_slice(b"more input\n");
+        child.stdin.take().unwrap().write_all(&input).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        (String::from_utf8(output.stdout).unwrap(), std::fs::read_to_string(&received).unwrap_or_default())
+    }
+
+    #[test]
+    fn command_never_sees_the_password_on_stdin() {
+        // sudo 需要密码时只收到第一行
+        assert_eq!(run_wrapped("cat; echo done", true), ("done\n".to_string(), "hunter2".to_string()));
+        // 免密 sudo 不读取密码，密码也不会交给命令
+        assert_eq!(run_wrapped("cat; echo 'it''s done'", false), ("its done\n".to_string(), String::new()));
+    }
+
+    #[test]
+    fn wraps_command_and_scrubs_password() {
+        assert_eq!(
+            wrap_command("systemctl restart nginx"),
+            r#"sh -c 'IFS= read -r password; printf '\''%s\n'\'' "$password" | sudo -k -S -p '\'''\'' sh -c '\''exec </dev/null; systemctl restart nginx'\'''"#
+        );
+        assert_eq!(scrub("pw: hunter2\n", "hunter2"), "pw: ********\n");
+
+        let mut server = ServerConfig::new(
+            "1".into(), "web".into(), "10.0.0.1".into(), 22, "deploy".into(),
+            AuthType::Password("hunter2".into()), None, None, None,
+        );
+        let sudo = Sudo { fallback: Some("typed".into()) };
+        assert_eq!(sudo.password_for(&server), Some("hunter2"));
+        server.auth_type = AuthType::Agent;
+        assert_eq!(sudo.password_for(&server), Some("typed"));
+    }
+}
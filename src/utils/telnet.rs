@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::utils::raw_terminal::{run_raw_session_with, StreamCodec};
+use crate::utils::ssh::terminal_size;
+
+/// telnet 默认端口
+pub const DEFAULT_PORT: u16 = 23;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const OPT_ECHO: u8 = 1;
+const OPT_SGA: u8 = 3;
+const OPT_TTYPE: u8 = 24;
+const OPT_NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+/// 连接 telnet 设备并进入交互模式，按 Ctrl+] 退出
+pub fn connect_telnet(host: &str, port: u16) -> Result<i32> {
+    let addr = format!("{}:{}", host, port);
+    let mut stream = TcpStream::connect(&addr)
+        .with_context(|| format!("无法连接到 {}", addr))?;
+    stream.set_nodelay(true).ok();
+    stream
+        .set_nonblocking(true)
+        .with_context(|| "无法将连接设置为非阻塞模式")?;
+
+    println!("已通过 telnet 连接到 {}，按 Ctrl+] 退出", addr);
+
+    let (cols, rows) = terminal_size();
+    let mut codec = TelnetCodec::new(cols as u16, rows as u16);
+    run_raw_session_with(&mut stream, &mut codec)?;
+
+    // 等待缓冲区中的数据发送完毕再关闭
+    std::thread::sleep(Duration::from_millis(50));
+    println!("\r\ntelnet 会话已结束");
+
+    Ok(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Data,
+    Iac,
+    Negotiate(u8),
+    Sub,
+    SubIac,
+}
+
+/// 最小化的 telnet 协议处理：只协商回显、SGA、终端类型与窗口大小，
+/// 其它选项一律拒绝，足以应付交换机、路由器等老设备的登录界面。
+pub struct TelnetCodec {
+    state: State,
+    sub: Vec<u8>,
+    cols: u16,
+    rows: u16,
+}
+
+impl TelnetCodec {
+    pub fn new(cols: u16, rows: u16) -> Self {
+        TelnetCodec { state: State::Data, sub: Vec::new(), cols, rows }
+    }
+
+    fn negotiate(&self, command: u8, option: u8, reply: &mut Vec<u8>) {
+        match (command, option) {
+            // 由远端回显、抑制 GA
+            (WILL, OPT_ECHO) | (WILL, OPT_SGA) => reply.extend_from_slice(&[IAC, DO, option]),
+            (WILL, _) => reply.extend_from_slice(&[IAC, DONT, option]),
+            (DO, OPT_SGA) | (DO, OPT_TTYPE) => reply.extend_from_slice(&[IAC, WILL, option]),
+            (DO, OPT_NAWS) => {
+                reply.extend_from_slice(&[IAC, WILL, OPT_NAWS, IAC, SB, OPT_NAWS]);
+                for byte in self.cols.to_be_bytes().into_iter().chain(self.rows.to_be_bytes()) {
+                    // 数据中的 255 需要转义
+                    if byte == IAC {
+                        reply.push(IAC);
+                    }
+                    reply.push(byte);
+                }
+                reply.extend_from_slice(&[IAC, SE]);
+            }
+            (DO, _) => reply.extend_from_slice(&[IAC, WONT, option]),
+            // WONT/DONT 不需要应答，避免协商死循环
+            _ => {}
+        }
+    }
+
+    fn subnegotiate(&mut self, reply: &mut Vec<u8>) {
+        if self.sub.as_slice() == [OPT_TTYPE, TTYPE_SEND] {
+            let term = std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
+            reply.extend_from_slice(&[IAC, SB, OPT_TTYPE, TTYPE_IS]);
+            reply.extend_from_slice(term.as_bytes());
+            reply.extend_from_slice(&[IAC, SE]);
+        }
+        self.sub.clear();
+    }
+}
+
+impl StreamCodec for TelnetCodec {
+    fn encode(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(input.len());
+        for &byte in input {
+            match byte {
+                IAC => out.extend_from_slice(&[IAC, IAC]),
+                // 原始模式下回车只产生 CR，按 NVT 约定补上 NUL
+                b'\r' => out.extend_from_slice(b"\r\0"),
+                _ => out.push(byte),
+            }
+        }
+        out
+    }
+
+    fn decode(&mut self, data: &[u8], reply: &mut Vec<u8>) -> Vec<u8> {
+        let mut display = Vec::with_capacity(data.len());
+
+        for &byte in data {
+            self.state = match (self.state, byte) {
+                (State::Data, IAC) => State::Iac,
+                (State::Data, _) => {
+                    display.push(byte);
+                    State::Data
+                }
+                (State::Iac, IAC) => {
+                    display.push(IAC);
+                    State::Data
+                }
+                (State::Iac, WILL | WONT | DO | DONT) => State::Negotiate(byte),
+                (State::Iac, SB) => State::Sub,
+                // NOP、GA 等其它命令忽略
+                (State::Iac, _) => State::Data,
+                (State::Negotiate(command), option) => {
+                    self.negotiate(command, option, reply);
+                    State::Data
+                }
+                (State::Sub, IAC) => State::SubIac,
+                (State::Sub, _) => {
+                    self.sub.push(byte);
+                    State::Sub
+                }
+                (State::SubIac, SE) => {
+                    self.subnegotiate(reply);
+                    State::Data
+                }
+                (State::SubIac, _) => {
+                    self.sub.push(byte);
+                    State::Sub
+                }
+            };
+        }
+
+        display
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_negotiation_and_replies() {
+        let mut codec = TelnetCodec::new(80, 24);
+        let mut reply = Vec::new();
+
+        let display = codec.decode(&[b'h', IAC, WILL, OPT_ECHO, b'i', IAC, DO, 5, IAC, IAC], &mut reply);
+        assert_eq!(display, vec![b'h', b'i', IAC]);
+        assert_eq!(reply, vec![IAC, DO, OPT_ECHO, IAC, WONT, 5]);
+    }
+
+    #[test]
+    fn handles_sequences_split_across_reads() {
+        let mut codec = TelnetCodec::new(80, 24);
+        let mut reply = Vec::new();
+
+        assert!(codec.decode(&[IAC, DO], &mut reply).is_empty());
+        codec.decode(&[OPT_NAWS, b'>'], &mut reply);
+        assert_eq!(reply, vec![IAC, WILL, OPT_NAWS, IAC, SB, OPT_NAWS, 0, 80, 0, 24, IAC, SE]);
+
+        reply.clear();
+        let display = codec.decode(&[IAC, SB, OPT_TTYPE, TTYPE_SEND, IAC, SE, b'$'], &mut reply);
+        assert_eq!(display, vec![b'$']);
+        assert_eq!(&reply[..4], &[IAC, SB, OPT_TTYPE, TTYPE_IS]);
+    }
+
+    #[test]
+    fn escapes_user_input() {
+        let mut codec = TelnetCodec::new(80, 24);
+        assert_eq!(codec.encode(&[b'a', b'\r', IAC]), vec![b'a', b'\r', 0, IAC, IAC]);
+    }
+}
@@ -0,0 +1,239 @@
+//! `rssh push-template`：按每台主机渲染本地模板（minijinja 语法），预览与远程现有文件的
+//! diff，确认后写入并可选执行重载命令（例如 `systemctl reload nginx`）。
+
+use anyhow::{Context, Result};
+use colored::*;
+use minijinja::{context, Environment, UndefinedBehavior};
+use serde::Serialize;
+use shell_escape::escape;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use crate::models::ServerConfig;
+use crate::utils::fleet::{run_on_host, HostResult};
+use crate::utils::fleet_diff::{print_diff, unified_diff};
+use crate::utils::ssh::SshClient;
+
+/// 远程文件不存在时读取命令的退出码
+const MISSING_EXIT_CODE: i32 = 3;
+
+/// 模板中 `server` 和 `hosts[]` 的字段
+#[derive(Serialize)]
+struct HostContext<'a> {
+    name: &'a str,
+    host: &'a str,
+    port: u16,
+    user: &'a str,
+    group: Option<&'a str>,
+    description: Option<&'a str>,
+    note: Option<&'a str>,
+}
+
+impl<'a> From<&'a ServerConfig> for HostContext<'a> {
+    fn from(server: &'a ServerConfig) -> Self {
+        HostContext {
+            name: &server.name,
+            host: &server.host,
+            port: server.port,
+            user: &server.username,
+            group: server.group.as_deref(),
+            description: server.description.as_deref(),
+            note: server.note.as_deref(),
+        }
+    }
+}
+
+/// 解析 `--var key=value`
+pub fn parse_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(format!("变量格式应为 key=value: {}", s)),
+    }
+}
+
+/// 为 `servers[index]` 渲染模板。可用变量：`server`（当前主机）、`index`（在目标中的序号，从 0 开始）、
+/// `hosts`（所有目标主机）和 `vars`（命令行 `--var`）。引用未定义的变量会报错
+pub fn render(source: &str, servers: &[ServerConfig], index: usize, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    // 配置文件通常以换行结尾，默认会被去掉
+    env.set_keep_trailing_newline(true);
+    env.add_template("template", source).context("模板语法错误")?;
+
+    let hosts: Vec<HostContext> = servers.iter().map(HostContext::from).collect();
+    let server = HostContext::from(&servers[index]);
+    env.get_template("template")?
+        .render(context! { server, index, hosts, vars })
+        .with_context(|| format!("为 {} 渲染模板失败", servers[index].name))
+}
+
+/// 按需通过 `sudo -n` 以 root 身份执行脚本（需要免密 sudo）
+fn shell_command(script: &str, sudo: bool) -> String {
+    if sudo {
+        format!("sudo -n sh -c {}", escape(script.into()))
+    } else {
+        format!("sh -c {}", escape(script.into()))
+    }
+}
+
+/// 读取远程文件的命令，文件不存在时以 `MISSING_EXIT_CODE` 退出
+pub fn read_command(dest: &str, sudo: bool) -> String {
+    let path = escape(dest.into());
+    shell_command(&format!("test -e {path} || exit {MISSING_EXIT_CODE}; cat -- {path}"), sudo)
+}
+
+/// 把临时文件的内容写入目标文件：用 `cat >` 而不是 `mv`，保留已有文件的属主和权限
+pub fn install_command(tmp: &str, dest: &str, sudo: bool) -> String {
+    let (tmp, dest) = (escape(tmp.into()), escape(dest.into()));
+    shell_command(&format!("cat -- {tmp} > {dest}; status=$?; rm -f -- {tmp}; exit $status"), sudo)
+}
+
+/// 远程文件的现状
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Current {
+    Missing,
+    Content(String),
+    Failed(String),
+}
+
+impl From<HostResult> for Current {
+    fn from(result: HostResult) -> Self {
+        match (result.error, result.exit_code) {
+            (Some(error), _) => Current::Failed(error),
+            (None, Some(0)) => Current::Content(result.stdout),
+            (None, Some(MISSING_EXIT_CODE)) => Current::Missing,
+            (None, _) => Current::Failed(format!("读取失败: {}", result.stderr.trim())),
+        }
+    }
+}
+
+/// 一台主机的推送计划
+pub struct HostPlan {
+    pub server: ServerConfig,
+    pub rendered: String,
+    pub current: Current,
+}
+
+impl HostPlan {
+    pub fn changed(&self) -> bool {
+        match &self.current {
+            Current::Content(content) => *content != self.rendered,
+            Current::Missing => true,
+            Current::Failed(_) => false,
+        }
+    }
+}
+
+/// 先为所有主机渲染模板（任何一台失败都不连接服务器），再并发读取各主机上的现有文件
+pub fn plan(
+    servers: &[ServerConfig],
+    source: &str,
+    dest: &str,
+    vars: &BTreeMap<String, String>,
+    sudo: bool,
+    parallel: usize,
+) -> Result<Vec<HostPlan>> {
+    let rendered = (0..servers.len())
+        .map(|index| render(source, servers, index, vars))
+        .collect::<Result<Vec<_>>>()?;
+
+    let command = read_command(dest, sudo);
+    let mut current = Vec::with_capacity(servers.len());
+    for chunk in servers.chunks(parallel.max(1)) {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = chunk.iter().map(|server| scope.spawn(|| run_on_host(server, &command))).collect();
+            current.extend(handles.into_iter().map(|handle| Current::from(handle.join().unwrap())));
+        });
+    }
+
+    Ok(servers
+        .iter()
+        .cloned()
+        .zip(rendered)
+        .zip(current)
+        .map(|((server, rendered), current)| HostPlan { server, rendered, current })
+        .collect())
+}
+
+/// 打印每台主机的 diff 预览
+pub fn print_preview(plans: &[HostPlan], dest: &str) {
+    for plan in plans {
+        let name = &plan.server.name;
+        match &plan.current {
+            Current::Failed(error) => println!("{} {} {}", "!".bright_red(), name, error),
+            Current::Content(_) if !plan.changed() => println!("{} {} 无变化", "✓".bright_green(), name),
+            Current::Content(content) => {
+                println!("{} {}", "~".bright_yellow(), name.bright_yellow());
+                print_diff(&unified_diff(content, &plan.rendered, &format!("{}:{}", name, dest), "rendered"));
+            }
+            Current::Missing => {
+                println!("{} {} 新建文件", "+".bright_green(), name.bright_yellow());
+                print_diff(&unified_diff("", &plan.rendered, "/dev/null", &format!("{}:{}", name, dest)));
+            }
+        }
+    }
+}
+
+/// 写入渲染结果并执行重载命令：先通过 SFTP 写到 /tmp 下的临时文件（权限 600），再复制到目标路径
+pub fn apply(plan: &HostPlan, dest: &str, sudo: bool, reload: Option<&str>) -> Result<()> {
+    let client = SshClient::connect(&plan.server)?;
+    let sftp = client.sftp()?;
+    let tmp = format!("/tmp/.rssh-template-{}", uuid::Uuid::new_v4().simple());
+    let mut file = sftp
+        .open_mode(
+            Path::new(&tmp),
+            ssh2::OpenFlags::WRITE | ssh2::OpenFlags::CREATE | ssh2::OpenFlags::TRUNCATE,
+            0o600,
+            ssh2::OpenType::File,
+        )
+        .with_context(|| format!("无法创建远程临时文件: {}", tmp))?;
+    file.write_all(plan.rendered.as_bytes())
+        .with_context(|| format!("写入远程临时文件失败: {}", tmp))?;
+    drop(file);
+
+    let (_, stderr, code) = client.execute_command(&install_command(&tmp, dest, sudo))?;
+    if code != 0 {
+        return Err(anyhow::anyhow!("写入 {} 失败（退出码 {}）: {}", dest, code, stderr.trim()));
+    }
+
+    if let Some(reload) = reload {
+        let (_, stderr, code) = client.execute_command(&shell_command(reload, sudo))?;
+        if code != 0 {
+            return Err(anyhow::anyhow!("已写入，但重载命令失败（退出码 {}）: {}", code, stderr.trim()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    #[test]
+    fn renders_template_per_host() {
+        let web1 = ServerConfig::new(
+            "1".into(), "web-1".into(), "10.0.0.11".into(), 22, "deploy".into(), AuthType::Agent, Some("web".into()), None, None,
+        );
+        let mut web2 = web1.clone();
+        web2.name = "web-2".into();
+        web2.host = "10.0.0.12".into();
+        let servers = [web1, web2];
+        let vars = BTreeMap::from([("workers".to_string(), "4".to_string())]);
+
+        let source = "# {{ server.name }} ({{ index }})\nworker_processes {{ vars.workers }};\n\
+                      {% for host in hosts %}server {{ host.host }}:8080;\n{% endfor %}";
+        assert_eq!(
+            render(source, &servers, 1, &vars).unwrap(),
+            "# web-2 (1)\nworker_processes 4;\nserver 10.0.0.11:8080;\nserver 10.0.0.12:8080;\n"
+        );
+        assert!(render("{{ vars.missing }}", &servers, 0, &vars).is_err());
+        assert_eq!(parse_var("port=8080=x"), Ok(("port".to_string(), "8080=x".to_string())));
+        assert!(parse_var("port").is_err());
+        assert_eq!(
+            install_command("/tmp/t", "/etc/my app.conf", false),
+            "sh -c 'cat -- /tmp/t > '\\''/etc/my app.conf'\\''; status=$?; rm -f -- /tmp/t; exit $status'"
+        );
+    }
+}
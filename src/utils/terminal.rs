@@ -1,9 +1,12 @@
+use anyhow::{Context, Result};
 use std::env;
+use std::process::Command;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TerminalKind {
     Kitty,
     WezTerm,
+    ITerm2,
     Other,
 }
 
@@ -22,6 +25,10 @@ pub fn detect() -> TerminalKind {
         return TerminalKind::Kitty;
     }
 
+    if term_program_eq("iTerm.app") || env::var("ITERM_SESSION_ID").is_ok() {
+        return TerminalKind::ITerm2;
+    }
+
     TerminalKind::Other
 }
 
@@ -38,3 +45,93 @@ fn term_program_eq(name: &str) -> bool {
         .map(|v| v.eq_ignore_ascii_case(name))
         .unwrap_or(false)
 }
+
+/// 在新的终端窗口/标签中运行命令，当前 shell 不受影响。
+///
+/// `preferred` 为配置中指定的终端（kitty/wezterm/iterm2/gnome-terminal/tmux），
+/// 未指定时根据当前终端自动选择；`template` 为自定义命令模板，其中的 `{cmd}`
+/// 会被替换为转义后的完整命令，设置后优先使用。
+pub fn spawn_in_new_window(
+    argv: &[String],
+    preferred: Option<&str>,
+    template: Option<&str>,
+) -> Result<()> {
+    if argv.is_empty() {
+        return Err(anyhow::anyhow!("没有要在新窗口中执行的命令"));
+    }
+
+    if let Some(template) = template {
+        let command_line = template.replace("{cmd}", &shell_join(argv));
+        return run_detached(Command::new("sh").arg("-c").arg(&command_line), "自定义终端命令");
+    }
+
+    let terminal = match preferred {
+        Some(name) => name.to_lowercase(),
+        None => match detect() {
+            TerminalKind::Kitty => "kitty".to_string(),
+            TerminalKind::WezTerm => "wezterm".to_string(),
+            TerminalKind::ITerm2 => "iterm2".to_string(),
+            TerminalKind::Other if env::var("TMUX").is_ok() => "tmux".to_string(),
+            TerminalKind::Other if which::which("gnome-terminal").is_ok() => "gnome-terminal".to_string(),
+            TerminalKind::Other => {
+                return Err(anyhow::anyhow!(
+                    "无法识别当前终端，请在 config.toml 中设置 terminal 或 terminal_command"
+                ))
+            }
+        },
+    };
+
+    match terminal.as_str() {
+        "kitty" => {
+            // 开启了远程控制时在当前窗口新建标签，否则打开新的 OS 窗口
+            if env::var("KITTY_LISTEN_ON").is_ok() {
+                run_detached(
+                    Command::new("kitty").args(["@", "launch", "--type=tab", "--"]).args(argv),
+                    "kitty @ launch",
+                )
+            } else {
+                run_detached(Command::new("kitty").arg("--detach").args(argv), "kitty --detach")
+            }
+        }
+        "wezterm" => run_detached(
+            Command::new("wezterm").args(["cli", "spawn", "--"]).args(argv),
+            "wezterm cli spawn",
+        ),
+        "iterm2" | "iterm" => {
+            let script = format!(
+                "tell application \"iTerm2\" to create window with default profile command \"{}\"",
+                shell_join(argv).replace('\\', "\\\\").replace('"', "\\\"")
+            );
+            run_detached(Command::new("osascript").args(["-e", &script]), "osascript")
+        }
+        "gnome-terminal" => run_detached(
+            Command::new("gnome-terminal").arg("--").args(argv),
+            "gnome-terminal",
+        ),
+        "tmux" => run_detached(
+            Command::new("tmux").arg("new-window").arg(shell_join(argv)),
+            "tmux new-window",
+        ),
+        other => Err(anyhow::anyhow!("不支持的终端: {}", other)),
+    }
+}
+
+fn run_detached(cmd: &mut Command, desc: &str) -> Result<()> {
+    let status = cmd
+        .status()
+        .with_context(|| format!("无法执行 {}", desc))?;
+
+    if !status.success() {
+        return Err(anyhow::anyhow!("{} 执行失败，退出码: {:?}", desc, status.code()));
+    }
+
+    Ok(())
+}
+
+/// 将参数列表拼成可交给 shell 的单行命令
+fn shell_join(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| shell_escape::escape(arg.as_str().into()).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,115 @@
+//! 内置库 / russh 连接的流量统计（`rssh stats`）。
+//!
+//! 连接关闭时从内核读取这条 TCP 连接收发的字节数（Linux `TCP_INFO`，macOS `TCP_CONNECTION_INFO`），
+//! 包括加密和协议开销，交给启动时设置的记录函数保存。系统 ssh 模式和经跳板机的连接
+//! 不经过 rssh 进程的 socket，不统计。
+
+use anyhow::Result;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::sync::OnceLock;
+
+use crate::models::ServerConfig;
+
+/// 保存一次连接的流量（服务器 ID, 服务器名称, 接收字节数, 发送字节数）
+type Recorder = fn(&str, &str, u64, u64) -> Result<()>;
+
+/// 由 rssh 启动时设置；未设置时不记录
+static RECORDER: OnceLock<Recorder> = OnceLock::new();
+
+/// 设置保存流量的方式，只在启动时调用一次
+pub fn configure(recorder: Recorder) {
+    let _ = RECORDER.set(recorder);
+}
+
+/// 持有连接 socket 的副本，释放时记录这条连接的流量。应在 SSH 会话之后释放，以便计入断开消息
+pub struct Meter {
+    server_id: String,
+    server_name: String,
+    socket: OwnedFd,
+}
+
+impl Meter {
+    pub fn new(server: &ServerConfig, socket: OwnedFd) -> Self {
+        Meter { server_id: server.id.clone(), server_name: server.name.clone(), socket }
+    }
+}
+
+impl Drop for Meter {
+    fn drop(&mut self) {
+        let Some(recorder) = RECORDER.get() else { return };
+        let Some((received, sent)) = socket_bytes(self.socket.as_raw_fd()) else { return };
+        if let Err(e) = recorder(&self.server_id, &self.server_name, received, sent) {
+            eprintln!("rssh: 无法记录流量: {:#}", e);
+        }
+    }
+}
+
+/// Linux `struct tcp_info` 中到 `tcpi_bytes_received` 为止的部分（4.2 起），libc 中的定义不含这些字段
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct TcpInfo {
+    flags: [u8; 8],
+    counters: [u32; 24],
+    pacing_rate: u64,
+    max_pacing_rate: u64,
+    bytes_acked: u64,
+    bytes_received: u64,
+}
+
+/// 这条 TCP 连接（接收, 发送）的字节数；不是 TCP socket 或内核不支持时为 None
+#[cfg(target_os = "linux")]
+fn socket_bytes(fd: RawFd) -> Option<(u64, u64)> {
+    let mut info: TcpInfo = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<TcpInfo>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(fd, libc::IPPROTO_TCP, libc::TCP_INFO, &mut info as *mut TcpInfo as *mut libc::c_void, &mut len)
+    };
+    (rc == 0 && len as usize >= std::mem::size_of::<TcpInfo>()).then_some((info.bytes_received, info.bytes_acked))
+}
+
+#[cfg(target_os = "macos")]
+fn socket_bytes(fd: RawFd) -> Option<(u64, u64)> {
+    let mut info: libc::tcp_connection_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_connection_info>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_CONNECTION_INFO,
+            &mut info as *mut libc::tcp_connection_info as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    (rc == 0).then_some((info.tcpi_rxbytes, info.tcpi_txbytes))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn socket_bytes(_fd: RawFd) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn reads_bytes_sent_and_received_on_a_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+
+        client.write_all(&[0u8; 1000]).unwrap();
+        server.read_exact(&mut [0u8; 1000]).unwrap();
+        server.write_all(&[0u8; 300]).unwrap();
+        client.read_exact(&mut [0u8; 300]).unwrap();
+
+        let (received, sent) = socket_bytes(client.as_raw_fd()).unwrap();
+        assert_eq!(received, 300);
+        // 部分内核的 bytes_acked 把 SYN 也算作 1 字节
+        assert!((1000..=1001).contains(&sent), "{}", sent);
+        let (pipe, _) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert_eq!(socket_bytes(pipe.as_raw_fd()), None);
+    }
+}
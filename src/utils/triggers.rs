@@ -0,0 +1,215 @@
+//! 输出触发器（`config.toml` 的 `[triggers.<名称>]`）：内置库模式的交互式会话中，远程输出匹配正则时
+//! 执行动作，例如出现 `panic:` 时响铃并发送通知，或自动回答 `(yes/no)?` 提示。
+//!
+//! 可以用 `servers`（服务器名称或别名）和 `groups` 限定触发器生效的服务器，都不设置时对所有服务器生效。
+//! 匹配在远程输出上进行（颜色等转义序列不去掉），每段输出只触发一次，之后从匹配结束的位置继续。
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::models::ServerConfig;
+
+/// 等待匹配的输出最多保留的字节数，超过时丢弃较早的部分
+const WINDOW: usize = 4096;
+
+/// `config.toml` 中的一个 `[triggers.<名称>]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggerSettings {
+    /// 匹配远程输出的正则
+    pub pattern: String,
+    /// 只对这些服务器（名称或别名）生效
+    pub servers: Vec<String>,
+    /// 只对这些分组的服务器生效
+    pub groups: Vec<String>,
+    /// 匹配时在本地终端响铃
+    pub bell: bool,
+    /// 匹配时发送通知，值与 `--notify` 相同（`desktop`、`slack:<URL>` 或 `[notify]` 中的名称）
+    pub notify: Option<String>,
+    /// 匹配时发送到远程的内容，自动加回车
+    pub send: Option<String>,
+}
+
+impl TriggerSettings {
+    fn applies_to(&self, server: &ServerConfig) -> bool {
+        server.in_scope(&self.servers, &self.groups)
+    }
+}
+
+/// 发送通知（通知目标, 标题, 内容），由 rssh 启动时设置
+type Notifier = fn(&str, &str, &str) -> Result<()>;
+
+static TRIGGERS: OnceLock<(Vec<Trigger>, Notifier)> = OnceLock::new();
+
+/// 设置触发器和发送通知的方式，只在启动时调用一次。正则无效的触发器跳过并打印警告
+pub fn configure(settings: HashMap<String, TriggerSettings>, notifier: Notifier) {
+    let mut names: Vec<&String> = settings.keys().collect();
+    names.sort();
+    let triggers = names
+        .into_iter()
+        .filter_map(|name| match Trigger::new(name, &settings[name]) {
+            Ok(trigger) => Some(trigger),
+            Err(e) => {
+                eprintln!("警告: {}", e);
+                None
+            },
+        })
+        .collect();
+    let _ = TRIGGERS.set((triggers, notifier));
+}
+
+/// 触发器匹配后要执行的动作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Bell,
+    /// 发送通知（通知目标, 标题, 内容）
+    Notify { target: String, title: String, body: String },
+    /// 发送到远程的内容
+    Send(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+struct Trigger {
+    name: String,
+    pattern: regex::Regex,
+    settings: TriggerSettings,
+}
+
+/// 一个会话中生效的触发器
+#[derive(Debug, Clone)]
+pub struct Triggers {
+    server: String,
+    triggers: Vec<Trigger>,
+    /// 上次匹配之后收到的输出
+    pending: String,
+}
+
+impl Triggers {
+    /// 按全局配置创建对该服务器生效的触发器，没有时返回 None
+    pub fn for_server(server: &ServerConfig) -> Option<Self> {
+        let (triggers, _) = TRIGGERS.get()?;
+        let triggers: Vec<Trigger> = triggers.iter().filter(|trigger| trigger.settings.applies_to(server)).cloned().collect();
+        (!triggers.is_empty()).then(|| Triggers { server: server.name.clone(), triggers, pending: String::new() })
+    }
+
+    /// 处理一段远程输出，返回按出现顺序触发的动作
+    pub fn feed(&mut self, output: &[u8]) -> Vec<Action> {
+        self.pending.push_str(&String::from_utf8_lossy(output));
+        let mut actions = Vec::new();
+        loop {
+            // 同一段输出中最先出现的匹配先触发
+            let found = self
+                .triggers
+                .iter()
+                .filter_map(|trigger| trigger.pattern.find(&self.pending).map(|found| (found.start(), found.end(), trigger)))
+                .min_by_key(|(start, _, _)| *start);
+            let Some((_, end, trigger)) = found else {
+                break;
+            };
+            actions.extend(trigger.actions(&self.server, &self.pending[..end]));
+            // 空匹配也要前进，避免重复触发
+            let end = end.max(self.pending.chars().next().map_or(0, char::len_utf8));
+            self.pending.drain(..end);
+        }
+        if self.pending.len() > WINDOW {
+            let mut start = self.pending.len() - WINDOW;
+            while !self.pending.is_char_boundary(start) {
+                start += 1;
+            }
+            self.pending.drain(..start);
+        }
+        actions
+    }
+}
+
+/// 执行通知动作，失败只打印警告
+pub fn notify(target: &str, title: &str, body: &str) {
+    let Some((_, notifier)) = TRIGGERS.get() else {
+        return;
+    };
+    if let Err(e) = notifier(target, title, body) {
+        eprintln!("警告: 触发器发送通知失败: {:#}", e);
+    }
+}
+
+impl Trigger {
+    fn new(name: &str, settings: &TriggerSettings) -> Result<Self> {
+        let pattern = regex::Regex::new(&settings.pattern)
+            .map_err(|e| anyhow::anyhow!("触发器 {} 的正则无效 {}: {}", name, settings.pattern, e))?;
+        Ok(Trigger { name: name.to_string(), pattern, settings: settings.clone() })
+    }
+
+    /// `matched` 为匹配结束之前的输出，通知中带上匹配所在的那一行
+    fn actions(&self, server: &str, matched: &str) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.settings.bell {
+            actions.push(Action::Bell);
+        }
+        if let Some(target) = &self.settings.notify {
+            let line = matched.rsplit(['\r', '\n']).find(|line| !line.trim().is_empty()).unwrap_or_default();
+            actions.push(Action::Notify {
+                target: target.clone(),
+                title: format!("rssh {}: 触发器 {}", server, self.name),
+                body: line.trim().to_string(),
+            });
+        }
+        if let Some(send) = &self.settings.send {
+            actions.push(Action::Send(format!("{}\r", send).into_bytes()));
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthType;
+
+    fn triggers(specs: &[(&str, TriggerSettings)]) -> Triggers {
+        Triggers {
+            server: "web1".to_string(),
+            triggers: specs.iter().map(|(name, settings)| Trigger::new(name, settings).unwrap()).collect(),
+            pending: String::new(),
+        }
+    }
+
+    #[test]
+    fn fires_actions_in_output_order() {
+        let mut triggers = triggers(&[
+            ("panic", TriggerSettings { pattern: "panic:".into(), bell: true, notify: Some("desktop".into()), ..Default::default() }),
+            ("host-key", TriggerSettings { pattern: r"\(yes/no\)\?".into(), send: Some("yes".into()), ..Default::default() }),
+        ]);
+        assert!(triggers.feed(b"Are you sure (yes/").is_empty());
+        assert_eq!(triggers.feed(b"no)? "), [Action::Send(b"yes\r".to_vec())]);
+        assert!(triggers.feed(b"ok\r\n").is_empty());
+
+        let actions = triggers.feed(b"thread 'main' panic: boom\r\n(yes/no)? ");
+        assert_eq!(
+            actions,
+            [
+                Action::Bell,
+                Action::Notify {
+                    target: "desktop".into(),
+                    title: "rssh web1: 触发器 panic".into(),
+                    body: "thread 'main' panic:".into(),
+                },
+                Action::Send(b"yes\r".to_vec()),
+            ]
+        );
+        assert!(triggers.feed(b"").is_empty());
+    }
+
+    #[test]
+    fn limits_triggers_to_servers_and_groups() {
+        let mut server =
+            ServerConfig::new("id".into(), "web1".into(), "10.0.0.1".into(), 22, "root".into(), AuthType::Agent, Some("prod".into()), None, None);
+        server.aliases = vec!["w1".into()];
+        let all = TriggerSettings::default();
+        assert!(all.applies_to(&server));
+        assert!(TriggerSettings { servers: vec!["w1".into()], ..Default::default() }.applies_to(&server));
+        assert!(TriggerSettings { groups: vec!["prod".into()], ..Default::default() }.applies_to(&server));
+        assert!(!TriggerSettings { servers: vec!["db1".into()], groups: vec!["dev".into()], ..Default::default() }.applies_to(&server));
+    }
+}
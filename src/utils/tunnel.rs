@@ -0,0 +1,169 @@
+use anyhow::Result;
+use colored::*;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::utils::session_escape::{Forward, ForwardKind};
+use crate::utils::session_registry::ActiveSession;
+
+/// 自动选择端口时最多向后尝试的数量
+const AUTO_PORT_RANGE: u16 = 100;
+
+/// 本地端口当前是否可以监听
+pub fn port_available(bind_address: &str, port: u16) -> bool {
+    TcpListener::bind((bind_address, port)).is_ok()
+}
+
+/// 从 `port` 之后找下一个可以监听的端口，跳过 `taken` 中已分配的端口
+pub fn next_free_port(bind_address: &str, port: u16, taken: &HashSet<u16>) -> Option<u16> {
+    (1..=AUTO_PORT_RANGE)
+        .filter_map(|offset| port.checked_add(offset))
+        .find(|candidate| !taken.contains(candidate) && port_available(bind_address, *candidate))
+}
+
+/// 占用该监听地址的 rssh 会话（按 `rssh ps` 登记的转发查找）
+pub fn port_owner<'a>(sessions: &'a [ActiveSession], forward: &Forward) -> Option<&'a ActiveSession> {
+    let prefix = format!("-L {} ", forward.listen_address());
+    sessions
+        .iter()
+        .find(|session| session.forwards.iter().any(|f| f.starts_with(&prefix)))
+}
+
+/// 建立转发前检查本地端口：被占用时报告占用者并给出下一个可用端口，
+/// `auto_port` 时直接改用下一个可用端口。远程转发的端口在服务器上，不在这里检查
+pub fn check_local_ports(forwards: &mut [Forward], auto_port: bool, sessions: &[ActiveSession]) -> Result<()> {
+    let mut taken = HashSet::new();
+    for forward in forwards.iter_mut().filter(|f| f.kind == ForwardKind::Local) {
+        if !taken.contains(&forward.port) && port_available(&forward.bind_address, forward.port) {
+            taken.insert(forward.port);
+            continue;
+        }
+
+        let owner = match port_owner(sessions, forward) {
+            Some(session) => format!("rssh 进程 {}（{} {}）", session.pid, session.mode, session.server),
+            None if taken.contains(&forward.port) => "本次的其他转发".to_string(),
+            None => "其他程序".to_string(),
+        };
+        let next = next_free_port(&forward.bind_address, forward.port, &taken);
+
+        match next {
+            Some(port) if auto_port => {
+                println!(
+                    "{} 本地端口 {} 已被{}占用，改用 {}",
+                    "⚠".bright_yellow(), forward.port, owner, port.to_string().bright_green()
+                );
+                forward.port = port;
+                taken.insert(port);
+            }
+            Some(port) => {
+                return Err(anyhow::anyhow!(
+                    "本地端口 {} 已被{}占用，下一个可用端口为 {}，可以改用该端口或加 --auto-port 自动选择",
+                    forward.listen_address(), owner, port
+                ));
+            }
+            None => {
+                return Err(anyhow::anyhow!(
+                    "本地端口 {} 已被{}占用，之后 {} 个端口也都不可用",
+                    forward.listen_address(), owner, AUTO_PORT_RANGE
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 访问本地转发时使用的主机名：监听所有地址时用 localhost
+pub fn local_host(forward: &Forward) -> &str {
+    match forward.bind_address.as_str() {
+        "" | "*" | "0.0.0.0" | "::" | "127.0.0.1" => "localhost",
+        address => address,
+    }
+}
+
+/// 本地转发对应的 HTTP 地址
+pub fn local_url(forward: &Forward) -> String {
+    format!("http://{}:{}", local_host(forward), forward.port)
+}
+
+/// 经本地转发发一个 HTTP 请求，收到任何响应说明转发和远程服务都可用
+pub fn probe_http(host: &str, port: u16) -> bool {
+    let Ok(mut stream) = TcpStream::connect((host, port)) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    if stream.write_all(b"HEAD / HTTP/1.0\r\nHost: localhost\r\n\r\n").is_err() {
+        return false;
+    }
+    // 远程目标连不上时通道打开失败，本地连接会被直接关闭
+    let mut buf = [0u8; 16];
+    matches!(stream.read(&mut buf), Ok(n) if n > 0)
+}
+
+/// 用系统默认浏览器打开网址
+pub fn open_browser(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("无法打开浏览器: {}", e))?;
+    Ok(())
+}
+
+/// 在后台等待转发可用（远程服务可能还在启动，最多等 30 秒）后打开浏览器
+pub fn open_when_ready(forward: &Forward, url: String) {
+    let host = local_host(forward).to_string();
+    let port = forward.port;
+    std::thread::spawn(move || {
+        for _ in 0..30 {
+            if probe_http(&host, port) {
+                println!("打开 {}", url.bright_blue());
+                if let Err(e) = open_browser(&url) {
+                    eprintln!("{} {}，请手动访问 {}", "⚠".bright_yellow(), e, url);
+                }
+                return;
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+        eprintln!("{} {} 没有响应，未打开浏览器", "⚠".bright_yellow(), url);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_next_free_port_when_busy() {
+        let busy = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = busy.local_addr().unwrap().port();
+        let session = ActiveSession {
+            pid: 4242,
+            server: "db-1".into(),
+            mode: "tunnel".into(),
+            started_at: 0,
+            forwards: vec![format!("-L 127.0.0.1:{} -> localhost:5432", port)],
+        };
+
+        let spec = format!("{}:localhost:5432", port);
+        let mut forwards = vec![Forward::parse(ForwardKind::Local, &spec).unwrap()];
+        let err = check_local_ports(&mut forwards, false, std::slice::from_ref(&session)).unwrap_err();
+        assert!(err.to_string().contains("rssh 进程 4242"));
+
+        check_local_ports(&mut forwards, true, &[session]).unwrap();
+        assert_ne!(forwards[0].port, port);
+        assert!(port_available("127.0.0.1", forwards[0].port));
+    }
+}
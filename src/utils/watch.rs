@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::models::ServerConfig;
+use crate::utils::ssh::SshClient;
+
+/// 远程目录快照：文件名 -> (大小, 修改时间)
+pub type Snapshot = HashMap<String, (u64, u64)>;
+
+/// watch-download 的选项
+pub struct WatchDownloadOptions {
+    /// 轮询间隔
+    pub interval: Duration,
+    /// 使用远程 inotifywait 代替轮询
+    pub inotify: bool,
+    /// 启动时也下载目录中已有的文件
+    pub existing: bool,
+}
+
+/// 监视远程目录，新文件出现（且写入完成）后自动下载到本地目录，直到 Ctrl+C
+pub fn watch_download(
+    server: &ServerConfig,
+    remote_dir: &str,
+    local_dir: &Path,
+    options: &WatchDownloadOptions,
+) -> Result<()> {
+    std::fs::create_dir_all(local_dir)
+        .with_context(|| format!("无法创建本地目录: {}", local_dir.display()))?;
+
+    let client = SshClient::connect(server)?;
+    let sftp = client.sftp()?;
+
+    let initial = remote_snapshot(&sftp, remote_dir)?;
+    let mut downloaded = if options.existing { Snapshot::new() } else { initial.clone() };
+
+    println!(
+        "正在监视 {}:{} -> {}，按 Ctrl+C 停止",
+        server.name.bright_green(),
+        remote_dir,
+        local_dir.display()
+    );
+
+    if options.inotify {
+        if options.existing {
+            for name in initial.keys() {
+                download_one(&sftp, remote_dir, name, local_dir)?;
+            }
+        }
+        return watch_with_inotify(&client, &sftp, remote_dir, local_dir);
+    }
+
+    let mut previous = if options.existing { initial } else { Snapshot::new() };
+    loop {
+        let current = remote_snapshot(&sftp, remote_dir)?;
+
+        for name in stable_changes(&previous, &current, &downloaded) {
+            download_one(&sftp, remote_dir, &name, local_dir)?;
+            downloaded.insert(name.clone(), current[&name]);
+        }
+
+        previous = current;
+        std::thread::sleep(options.interval);
+    }
+}
+
+/// 找出需要下载的文件：与上一次轮询相比大小和修改时间都没有变化（写入已完成），
+/// 且与上次下载时的状态不同（新文件或被覆盖的文件）
+pub fn stable_changes(previous: &Snapshot, current: &Snapshot, downloaded: &Snapshot) -> Vec<String> {
+    let mut names: Vec<String> = current
+        .iter()
+        .filter(|(name, state)| previous.get(*name) == Some(state) && downloaded.get(*name) != Some(state))
+        .map(|(name, _)| name.clone())
+        .collect();
+    names.sort();
+    names
+}
+
+fn remote_snapshot(sftp: &ssh2::Sftp, remote_dir: &str) -> Result<Snapshot> {
+    let entries = sftp
+        .readdir(Path::new(remote_dir))
+        .with_context(|| format!("无法读取远程目录: {}", remote_dir))?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|(_, stat)| stat.is_file())
+        .filter_map(|(path, stat)| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some((name, (stat.size.unwrap_or(0), stat.mtime.unwrap_or(0))))
+        })
+        .collect())
+}
+
+/// 通过远程 `inotifywait -m` 获取写入完成 / 移入目录的文件名
+fn watch_with_inotify(client: &SshClient, sftp: &ssh2::Sftp, remote_dir: &str, local_dir: &Path) -> Result<()> {
+    let command = format!(
+        "command -v inotifywait >/dev/null || {{ echo 'rssh: 远程未安装 inotifywait (inotify-tools)' >&2; exit 127; }}; \
+         exec inotifywait -m -q -e close_write -e moved_to --format %f {}",
+        shell_escape::escape(remote_dir.into())
+    );
+    let mut channel = client.exec_channel(&command)?;
+
+    let mut reader = BufReader::new(&mut channel);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let name = line.trim_end_matches('\n');
+        if !name.is_empty() {
+            download_one(sftp, remote_dir, name, local_dir)?;
+        }
+    }
+    drop(reader);
+
+    let mut stderr = String::new();
+    std::io::Read::read_to_string(&mut channel.stderr(), &mut stderr).ok();
+    channel.wait_close().ok();
+    match channel.exit_status() {
+        Ok(0) => Ok(()),
+        Ok(code) => Err(anyhow::anyhow!("远程 inotifywait 退出 (退出码: {}): {}", code, stderr.trim())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 下载单个文件。先写入 `.part` 临时文件再改名，避免其它程序读到半个文件
+fn download_one(sftp: &ssh2::Sftp, remote_dir: &str, name: &str, local_dir: &Path) -> Result<()> {
+    let remote_path = Path::new(remote_dir).join(name);
+    let local_path = local_dir.join(name);
+    let part_path = PathBuf::from(format!("{}.part", local_path.display()));
+
+    let mut remote = sftp
+        .open(&remote_path)
+        .with_context(|| format!("无法打开远程文件: {}", remote_path.display()))?;
+    let mut local = std::fs::File::create(&part_path)
+        .with_context(|| format!("无法创建本地文件: {}", part_path.display()))?;
+    let bytes = std::io::copy(&mut remote, &mut local)
+        .with_context(|| format!("下载失败: {}", remote_path.display()))?;
+    std::fs::rename(&part_path, &local_path)
+        .with_context(|| format!("无法重命名为: {}", local_path.display()))?;
+
+    println!(
+        "[{}] 已下载 {} ({} 字节)",
+        chrono::Local::now().format("%H:%M:%S"),
+        name.bright_green(),
+        bytes
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(entries: &[(&str, u64, u64)]) -> Snapshot {
+        entries.iter().map(|(n, s, m)| (n.to_string(), (*s, *m))).collect()
+    }
+
+    #[test]
+    fn waits_until_file_is_stable() {
+        let downloaded = snapshot(&[("old.csv", 10, 1)]);
+        let first = snapshot(&[("old.csv", 10, 1), ("report.csv", 100, 5)]);
+        let second = snapshot(&[("old.csv", 10, 1), ("report.csv", 200, 6)]);
+        let third = second.clone();
+
+        // 新文件还在写入，不下载
+        assert!(stable_changes(&first, &second, &downloaded).is_empty());
+        // 连续两次相同，下载；已下载的旧文件不重复下载
+        assert_eq!(stable_changes(&second, &third, &downloaded), vec!["report.csv"]);
+    }
+
+    #[test]
+    fn redownloads_overwritten_files() {
+        let downloaded = snapshot(&[("a.log", 10, 1)]);
+        let current = snapshot(&[("a.log", 20, 2)]);
+        assert_eq!(stable_changes(&current, &current, &downloaded), vec!["a.log"]);
+    }
+}
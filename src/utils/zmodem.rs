@@ -0,0 +1,324 @@
+//! 旁路解析 ZMODEM 发送方的数据流，用于显示文件名、大小和传输进度。
+//! 只读取数据，不参与协议交互，解析失败也不会影响 lrzsz 的传输。
+
+const ZDLE: u8 = 0x18;
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+
+const ZFILE: u8 = 4;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+
+/// ZDLE 后表示数据子包结束的字符
+const ZCRCE: u8 = b'h';
+const ZCRCG: u8 = b'i';
+const ZCRCQ: u8 = b'j';
+const ZCRCW: u8 = b'k';
+
+/// 发送方提供的文件信息（ZFILE 帧）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileOffer {
+    pub name: String,
+    /// 发送方没有提供大小时为 None
+    pub size: Option<u64>,
+}
+
+#[derive(Debug)]
+enum State {
+    /// 寻找帧头 ZPAD(`*`) ZDLE
+    Scan,
+    /// 已读到 ZPAD ZDLE，下一个字符是帧头格式
+    Format,
+    /// 二进制帧头，收集 ZDLE 解码后的字节（类型 + 4 字节位置 + CRC）
+    Binary { crc32: bool, bytes: Vec<u8> },
+    /// 十六进制帧头，收集 14 个十六进制字符
+    Hex { digits: Vec<u8> },
+    /// 数据子包；`info` 表示是 ZFILE 的文件信息
+    Data { crc32: bool, info: bool },
+    /// 子包结束后跳过 CRC，`frame_end` 表示帧结束、回到寻找帧头
+    Crc { remaining: usize, crc32: bool, frame_end: bool },
+}
+
+/// ZMODEM 发送方数据流的解析器
+#[derive(Debug)]
+pub struct ZmodemMonitor {
+    state: State,
+    /// 上一个字节是 ZDLE
+    escaped: bool,
+    /// 当前帧头使用 32 位 CRC
+    crc32: bool,
+    info: Vec<u8>,
+    offer: Option<FileOffer>,
+    /// 已传输的字节数（按 ZDATA 帧的位置加上之后的数据计算）
+    position: u64,
+    finished: bool,
+}
+
+impl Default for ZmodemMonitor {
+    fn default() -> Self {
+        ZmodemMonitor {
+            state: State::Scan,
+            escaped: false,
+            crc32: false,
+            info: Vec::new(),
+            offer: None,
+            position: 0,
+            finished: false,
+        }
+    }
+}
+
+impl ZmodemMonitor {
+    pub fn offer(&self) -> Option<&FileOffer> {
+        self.offer.as_ref()
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// 已收到当前文件的 ZEOF
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.feed_byte(byte);
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8) {
+        // 流控字符不属于数据
+        if matches!(byte, 0x11 | 0x13 | 0x91 | 0x93) {
+            return;
+        }
+
+        match &mut self.state {
+            State::Scan => {
+                // 帧头以一个或多个 ZPAD 开始，后跟 ZDLE
+                if byte == ZDLE {
+                    self.state = State::Format;
+                }
+            }
+            State::Format => {
+                self.state = match byte {
+                    ZBIN => State::Binary { crc32: false, bytes: Vec::new() },
+                    ZBIN32 => State::Binary { crc32: true, bytes: Vec::new() },
+                    ZHEX => State::Hex { digits: Vec::new() },
+                    _ => State::Scan,
+                };
+            }
+            State::Hex { digits } => {
+                digits.push(byte);
+                if digits.len() == 14 {
+                    let header = decode_hex(digits);
+                    self.crc32 = false;
+                    match header {
+                        Some(header) => self.on_header(&header),
+                        None => self.state = State::Scan,
+                    }
+                }
+            }
+            State::Binary { .. } | State::Data { .. } | State::Crc { .. } => {
+                let decoded = if self.escaped {
+                    self.escaped = false;
+                    match byte {
+                        ZCRCE | ZCRCG | ZCRCQ | ZCRCW => return self.on_subpacket_end(byte),
+                        0x6c => 0x7f,
+                        0x6d => 0xff,
+                        _ => byte ^ 0x40,
+                    }
+                } else if byte == ZDLE {
+                    self.escaped = true;
+                    return;
+                } else {
+                    byte
+                };
+                self.on_decoded(decoded);
+            }
+        }
+    }
+
+    fn on_decoded(&mut self, byte: u8) {
+        match &mut self.state {
+            State::Binary { crc32, bytes } => {
+                bytes.push(byte);
+                let crc_len = if *crc32 { 4 } else { 2 };
+                if bytes.len() == 5 + crc_len {
+                    self.crc32 = *crc32;
+                    let header = [bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]];
+                    self.on_header(&header);
+                }
+            }
+            State::Data { info: true, .. } => self.info.push(byte),
+            State::Data { info: false, .. } => self.position += 1,
+            State::Crc { remaining, crc32, frame_end } => {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    self.state = if *frame_end {
+                        State::Scan
+                    } else {
+                        State::Data { crc32: *crc32, info: false }
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_header(&mut self, header: &[u8; 5]) {
+        let crc32 = self.crc32;
+        self.state = match header[0] {
+            ZFILE => {
+                self.info.clear();
+                self.finished = false;
+                State::Data { crc32, info: true }
+            }
+            ZDATA => {
+                // 位置为小端序
+                self.position = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as u64;
+                State::Data { crc32, info: false }
+            }
+            ZEOF => {
+                self.finished = true;
+                State::Scan
+            }
+            _ => State::Scan,
+        };
+    }
+
+    fn on_subpacket_end(&mut self, kind: u8) {
+        let State::Data { crc32, info } = self.state else {
+            self.state = State::Scan;
+            return;
+        };
+        if info {
+            self.offer = parse_file_info(&self.info);
+        }
+        self.state = State::Crc {
+            remaining: if crc32 { 4 } else { 2 },
+            crc32,
+            frame_end: matches!(kind, ZCRCE | ZCRCW),
+        };
+    }
+}
+
+fn decode_hex(digits: &[u8]) -> Option<[u8; 5]> {
+    let text = std::str::from_utf8(digits).ok()?;
+    let mut header = [0u8; 5];
+    for (i, byte) in header.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(text.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(header)
+}
+
+/// 文件信息子包：`文件名\0大小 修改时间 权限 ...\0`
+fn parse_file_info(info: &[u8]) -> Option<FileOffer> {
+    let mut parts = info.splitn(2, |&b| b == 0);
+    let name = String::from_utf8_lossy(parts.next()?).to_string();
+    if name.is_empty() {
+        return None;
+    }
+    let size = parts
+        .next()
+        .and_then(|rest| std::str::from_utf8(rest).ok())
+        .and_then(|rest| rest.split([' ', '\0']).next())
+        .and_then(|size| size.parse().ok());
+    Some(FileOffer { name, size })
+}
+
+/// 以 KB/MB/GB 显示大小
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// 单行进度条，例如 `app.log [##########----------] 50% 1.0 MB/2.0 MB`
+pub fn progress_line(offer: &FileOffer, position: u64) -> String {
+    const WIDTH: usize = 30;
+    match offer.size {
+        Some(size) if size > 0 => {
+            let position = position.min(size);
+            let filled = (position as f64 / size as f64 * WIDTH as f64) as usize;
+            format!(
+                "{} [{}{}] {:>3}% {}/{}",
+                offer.name,
+                "#".repeat(filled),
+                "-".repeat(WIDTH - filled),
+                position * 100 / size,
+                format_size(position),
+                format_size(size)
+            )
+        }
+        _ => format!("{} {}", offer.name, format_size(position)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按 lrzsz 的方式对数据做 ZDLE 转义
+    fn escape(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &b in data {
+            if matches!(b, ZDLE | 0x10 | 0x11 | 0x13 | 0x90 | 0x91 | 0x93) {
+                out.extend([ZDLE, b ^ 0x40]);
+            } else {
+                out.push(b);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn tracks_file_offer_and_progress() {
+        let mut stream = Vec::new();
+        // ZFILE（32 位 CRC 二进制帧头）+ 文件信息子包
+        stream.extend(b"*\x18C");
+        stream.extend(escape(&[ZFILE, 0, 0, 0, 0, 1, 2, 3, 4]));
+        stream.extend(escape(b"report.tar.gz\x002048 14500000000 100644 0 1 2048\x00"));
+        stream.extend([ZDLE, ZCRCW]);
+        stream.extend(escape(&[9, 9, 9, 9]));
+
+        // ZDATA，位置 0，发送 1024 字节（含需要转义的字节）
+        stream.extend(b"*\x18C");
+        stream.extend(escape(&[ZDATA, 0, 0, 0, 0, 1, 2, 3, 4]));
+        let payload: Vec<u8> = (0..1024u32).map(|i| (i % 256) as u8).collect();
+        stream.extend(escape(&payload));
+        stream.extend([ZDLE, ZCRCG]);
+        stream.extend(escape(&[1, 2, 3, 4]));
+        // 帧以 ZCRCE 结束，子包为空
+        stream.extend([ZDLE, ZCRCE]);
+        stream.extend(escape(&[1, 2, 3, 4]));
+
+        let mut monitor = ZmodemMonitor::default();
+        // 分成小块喂入，模拟多次读取
+        for chunk in stream.chunks(7) {
+            monitor.feed(chunk);
+        }
+
+        assert_eq!(
+            monitor.offer(),
+            Some(&FileOffer { name: "report.tar.gz".into(), size: Some(2048) })
+        );
+        assert_eq!(monitor.position(), 1024);
+        assert!(progress_line(monitor.offer().unwrap(), monitor.position()).contains(" 50% "));
+
+        // 十六进制 ZEOF 帧头
+        monitor.feed(b"**\x18B0b00080000xxxx\r\n");
+        assert!(monitor.finished());
+    }
+}